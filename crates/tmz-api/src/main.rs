@@ -1,18 +1,67 @@
-//! HTTP API server for rust-workspace.
+//! HTTP API server for tmz.
+//!
+//! `/` and `/health` are open; every other route requires
+//! `Authorization: Bearer <token>` (`--token`, `[api] token`, or a token
+//! generated into the state directory on first start), or a scoped token
+//! from `tmz api token create` (see [`tmz_core::apitokens`]), which may be
+//! limited to `POST /send` and to some conversations. The `/daemon` and
+//! `/sync` routes let remote dashboards check on the daemon and start a
+//! sync through its engine socket.
+//!
+//! `/conversations`, `/conversations/{id}/messages`, and `/search` read the
+//! cache (read-only, so they never block the daemon's writes). They back
+//! the dashboard at `/dashboard`, a static page embedded in the binary that
+//! asks for the token once and keeps it in the browser's local storage.
+//!
+//! `POST /send` takes `{target, message, format}` and sends the message
+//! through the daemon (or directly), so CI jobs and cron scripts can notify
+//! a chat or channel with a plain HTTP request.
 
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use anyhow::Result;
-use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use anyhow::{Context, Result};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{StatusCode, header::AUTHORIZATION};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::{
+    Extension, Json, Router,
+    routing::{get, post},
+};
 use clap::{Args, Parser};
 use log::info;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 
-use tmz_core::{AppConfig, AppPaths};
+use tmz_core::apitokens::{Scope, TokenStore};
+use tmz_core::audit::AuditLog;
+use tmz_core::cache::{OutgoingKind, TargetMatch};
+use tmz_core::engine::{EngineClient, SyncOptions, SyncReport};
+use tmz_core::markup::BodyFormat;
+use tmz_core::secrets;
+use tmz_core::{
+    AppConfig, AppPaths, AuthManager, Cache, CachedMessage, CoreError, OffHoursAction,
+    SearchResult, daemon,
+};
+use tokio::sync::OnceCell;
+
+/// File name of the generated bearer token inside the state directory.
+const TOKEN_FILE: &str = "api-token";
+
+/// Log lines returned by `/daemon/logs` without `tail`.
+const DEFAULT_LOG_TAIL: usize = 200;
+
+/// Upper bound for `/daemon/logs?tail=N`.
+const MAX_LOG_TAIL: usize = 10_000;
+
+/// Upper bound for `limit` on the cache routes.
+const MAX_LIMIT: i64 = 500;
+
+/// The dashboard page served at `/dashboard`.
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
 
 fn main() -> anyhow::Result<()> {
     try_main()
@@ -25,38 +74,73 @@ async fn try_main() -> Result<()> {
     let cli = Cli::parse();
     let paths = AppPaths::discover(cli.common.config.as_deref())?;
     let config = AppConfig::load(&paths, false)?;
+    tmz_core::teams::endpoints::configure(&config.endpoints);
+    tmz_core::network::configure(&config.network)?;
+    tmz_core::retry::configure(&config.runtime.retries);
+    tmz_core::ratelimit::configure(&config.runtime.rate_limit);
+    if config.logging.trace_http {
+        tmz_core::trace::enable(&paths.state_dir);
+    }
+    let tokens = Tokens {
+        main: Arc::from(api_token(cli.token, &config, &paths)?),
+        scoped: TokenStore::new(&paths.state_dir),
+    };
 
     let state = AppState {
+        audit: Arc::new(AuditLog::new(&paths.state_dir, &config.audit, "api")),
         config: Arc::new(config),
+        db_path: Arc::new(paths.data_dir.join("cache.db")),
+        cache: Arc::new(OnceCell::new()),
+        outbox: Arc::new(OnceCell::new()),
     };
 
+    let addr = SocketAddr::from(([127, 0, 0, 1], cli.common.port));
+    info!("Starting API server on {addr}");
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, router(state, tokens)).await?;
+
+    Ok(())
+}
+
+/// All routes, with the protected ones behind [`require_token`].
+fn router(state: AppState, tokens: Tokens) -> Router {
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any);
 
-    let app = Router::new()
+    let protected = Router::new()
+        .route("/config", get(get_config))
+        .route("/daemon/status", get(daemon_status))
+        .route("/daemon/logs", get(daemon_logs))
+        .route("/sync/trigger", post(trigger_sync))
+        .route("/conversations", get(list_conversations))
+        .route("/conversations/{id}/messages", get(list_messages))
+        .route("/search", get(search))
+        .route("/send", post(send))
+        .layer(middleware::from_fn_with_state(tokens, require_token));
+
+    Router::new()
         .route("/", get(root))
         .route("/health", get(health))
-        .route("/config", get(get_config))
+        .route("/dashboard", get(dashboard))
+        .merge(protected)
         .layer(cors)
         .layer(TraceLayer::new_for_http())
-        .with_state(state);
-
-    let addr = SocketAddr::from(([127, 0, 0, 1], cli.common.port));
-    info!("Starting API server on {addr}");
-
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
-
-    Ok(())
+        .with_state(state)
 }
 
 #[derive(Debug, Parser)]
-#[command(author, version, about = "HTTP API server for rust-workspace")]
+#[command(author, version, about = "HTTP API server for tmz")]
 struct Cli {
     #[command(flatten)]
     common: CommonOpts,
+
+    /// Bearer token for protected routes (default: `[api] token`, or a
+    /// generated token stored in the state directory)
+    #[arg(long, env = "TMZ_API_TOKEN", value_name = "TOKEN")]
+    token: Option<String>,
 }
 
 #[derive(Debug, Clone, Args)]
@@ -73,6 +157,129 @@ struct CommonOpts {
 #[derive(Clone)]
 struct AppState {
     config: Arc<AppConfig>,
+    audit: Arc<AuditLog>,
+    db_path: Arc<PathBuf>,
+    /// Read-only cache connection, opened on first use.
+    cache: Arc<OnceCell<Cache>>,
+    /// Writable cache connection for the delivery state of sends, opened
+    /// on the first send.
+    outbox: Arc<OnceCell<Cache>>,
+}
+
+impl AppState {
+    /// The read-only cache, or `503` if it does not exist yet.
+    async fn cache(&self) -> Result<&Cache, ApiError> {
+        self.cache
+            .get_or_try_init(|| Cache::open_read_only(&self.db_path))
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("cache not available (run 'tmz sync' first): {e}"),
+                )
+            })
+    }
+
+    /// The writable cache sends are tracked in, or `None` (with a warning)
+    /// if it cannot be opened, so the send still goes out.
+    async fn outbox(&self) -> Option<&Cache> {
+        self.outbox
+            .get_or_try_init(|| Cache::open(&self.db_path))
+            .await
+            .map_err(|e| log::warn!("opening cache to track sends: {e}"))
+            .ok()
+    }
+
+    /// Conversation ID for a people alias, cached chat name, or ID.
+    async fn resolve_target(&self, target: &str) -> Result<String, ApiError> {
+        // IDs need no cache, which may not exist yet.
+        let found = match self.config.conversation_id(target) {
+            Some(id) => TargetMatch::Found(id.to_string()),
+            None => self
+                .cache()
+                .await?
+                .resolve_target(&self.config, target)
+                .await
+                .map_err(internal)?,
+        };
+        match found {
+            TargetMatch::Found(id) => Ok(id),
+            TargetMatch::NotFound => Err((
+                StatusCode::NOT_FOUND,
+                format!("no cached chat matches '{target}'"),
+            )),
+            TargetMatch::Ambiguous(matches) => {
+                let names: Vec<&str> = matches.iter().map(|c| c.display_name.as_str()).collect();
+                Err((
+                    StatusCode::CONFLICT,
+                    format!("'{target}' is ambiguous, matches: {}", names.join(", ")),
+                ))
+            }
+        }
+    }
+}
+
+/// Bearer tokens accepted on protected routes.
+#[derive(Clone)]
+struct Tokens {
+    /// Token with full access.
+    main: Arc<str>,
+    /// Tokens from `tmz api token create`.
+    scoped: TokenStore,
+}
+
+/// Reject requests without a valid bearer token, and requests outside the
+/// scope of a scoped token. The [`Scope`] of a scoped token is passed on to
+/// the handler as an extension.
+async fn require_token(State(tokens): State<Tokens>, mut req: Request, next: Next) -> Response {
+    let Some(given) = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::to_string)
+    else {
+        return (StatusCode::UNAUTHORIZED, "missing or invalid bearer token").into_response();
+    };
+    if secrets::constant_time_eq(&given, &tokens.main) {
+        return next.run(req).await;
+    }
+    match tokens.scoped.verify(&given) {
+        Ok(Some(token)) if token.scope.allows_route(req.uri().path()) => {
+            req.extensions_mut().insert(token.scope);
+            next.run(req).await
+        }
+        Ok(Some(token)) => (
+            StatusCode::FORBIDDEN,
+            format!("token {} may only send messages", token.id),
+        )
+            .into_response(),
+        Ok(None) => (StatusCode::UNAUTHORIZED, "missing or invalid bearer token").into_response(),
+        Err(e) => internal(e).into_response(),
+    }
+}
+
+/// Pick the bearer token: flag/env, then config, then the persisted
+/// generated token (created on first use).
+fn api_token(flag: Option<String>, config: &AppConfig, paths: &AppPaths) -> Result<String> {
+    if let Some(token) = flag.or_else(|| config.api.token.clone()) {
+        return Ok(token);
+    }
+
+    let path = paths.state_dir.join(TOKEN_FILE);
+    let (token, generated) = secrets::persisted_token(&path)
+        .with_context(|| format!("creating token in {}", path.display()))?;
+    if generated {
+        eprintln!("Generated API token in {}", path.display());
+    }
+    Ok(token)
+}
+
+/// A failed request: status code and plain-text message.
+type ApiError = (StatusCode, String);
+
+fn internal(e: impl std::fmt::Display) -> ApiError {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
 }
 
 #[derive(Serialize)]
@@ -84,6 +291,8 @@ struct RootResponse {
 #[derive(Serialize)]
 struct HealthResponse {
     status: &'static str,
+    /// Whether mutating operations are refused (`[security] read_only`).
+    read_only: bool,
 }
 
 async fn root() -> Json<RootResponse> {
@@ -93,10 +302,372 @@ async fn root() -> Json<RootResponse> {
     })
 }
 
-async fn health() -> Json<HealthResponse> {
-    Json(HealthResponse { status: "ok" })
+async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok",
+        read_only: state.config.security.is_read_only(),
+    })
+}
+
+/// The config with tokens, API keys, and signing secrets redacted.
+async fn get_config(State(state): State<AppState>) -> Result<Json<serde_json::Value>, ApiError> {
+    tmz_core::bundle::config_without_secrets(&state.config)
+        .map(Json)
+        .map_err(internal)
+}
+
+#[derive(Serialize)]
+struct DaemonStatus {
+    /// Whether the daemon process is alive.
+    running: bool,
+    /// Daemon process ID.
+    pid: Option<u32>,
+    /// Whether the daemon's engine socket answered.
+    engine: bool,
+    /// Token expiry (Unix seconds), if tokens are stored.
+    token_expires_at: Option<i64>,
+    /// Report of the daemon's last successful sync.
+    last_sync: Option<SyncReport>,
+    /// Why a new `tmz auth login` is needed, if the daemon's refresh failed.
+    reauth_needed: Option<String>,
+}
+
+async fn daemon_status() -> Result<Json<DaemonStatus>, ApiError> {
+    let running = daemon::is_running().map_err(internal)?;
+    let pid = if running {
+        daemon::read_pid().map_err(internal)?
+    } else {
+        None
+    };
+    let engine = match EngineClient::connect().await {
+        Some(mut client) => client.status().await.ok(),
+        None => None,
+    };
+    let token_expires_at = engine
+        .as_ref()
+        .and_then(|status| status.token_expires_at)
+        .or_else(|| {
+            AuthManager::new()
+                .ok()
+                .and_then(|auth| auth.stored_tokens().ok())
+                .map(|tokens| tokens.expires_at)
+        });
+    Ok(Json(DaemonStatus {
+        running,
+        pid,
+        engine: engine.is_some(),
+        token_expires_at,
+        last_sync: engine.and_then(|status| status.last_sync),
+        reauth_needed: daemon::check_reauth_needed(),
+    }))
+}
+
+#[derive(Deserialize)]
+struct LogsQuery {
+    /// Number of lines from the end of the log.
+    tail: Option<usize>,
+}
+
+/// The last lines of the daemon log, with tokens and email addresses masked.
+async fn daemon_logs(Query(query): Query<LogsQuery>) -> Result<String, ApiError> {
+    let path = daemon::log_file_path().map_err(internal)?;
+    let lines = query.tail.unwrap_or(DEFAULT_LOG_TAIL).min(MAX_LOG_TAIL);
+    tmz_core::bundle::tail_file(&path, lines)
+        .map(|text| tmz_core::bundle::redact(&text))
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "no daemon log yet".to_string()))
+}
+
+#[derive(Deserialize)]
+struct SyncQuery {
+    /// Sync messages of every conversation, not just the most recent.
+    #[serde(default)]
+    full: bool,
+    /// Answer with the sync report once it finishes instead of right away.
+    #[serde(default)]
+    wait: bool,
+}
+
+/// Ask the daemon to sync. Without `wait`, answers `202 Accepted` as soon
+/// as the daemon is reached.
+async fn trigger_sync(Query(query): Query<SyncQuery>) -> Result<Response, ApiError> {
+    let Some(mut client) = EngineClient::connect().await else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "daemon is not running; start it with 'tmz service start'".to_string(),
+        ));
+    };
+    let opts = SyncOptions {
+        full: query.full,
+        images: true,
+        ..SyncOptions::default()
+    };
+    if query.wait {
+        let report = client.sync(opts).await.map_err(internal)?;
+        return Ok(Json(report).into_response());
+    }
+    tokio::spawn(async move {
+        if let Err(e) = client.sync(opts).await {
+            log::warn!("triggered sync failed: {e}");
+        }
+    });
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "status": "started" })),
+    )
+        .into_response())
+}
+
+async fn dashboard() -> Html<&'static str> {
+    Html(DASHBOARD_HTML)
+}
+
+#[derive(Deserialize)]
+struct LimitQuery {
+    /// Maximum number of entries.
+    limit: Option<i64>,
 }
 
-async fn get_config(State(state): State<AppState>) -> Result<Json<AppConfig>, StatusCode> {
-    Ok(Json((*state.config).clone()))
+fn clamp_limit(limit: Option<i64>, default: i64) -> i64 {
+    limit.unwrap_or(default).clamp(1, MAX_LIMIT)
+}
+
+#[derive(Serialize)]
+struct ConversationEntry {
+    id: String,
+    display_name: String,
+    product_type: String,
+    last_activity: String,
+    last_message_from: String,
+    last_message_preview: String,
+    /// Unread messages from others.
+    unread: i64,
+}
+
+/// Most recently active conversations with their unread counts.
+async fn list_conversations(
+    State(state): State<AppState>,
+    Query(query): Query<LimitQuery>,
+) -> Result<Json<Vec<ConversationEntry>>, ApiError> {
+    let cache = state.cache().await?;
+    let conversations = cache
+        .list_conversation_summaries(clamp_limit(query.limit, 50))
+        .await
+        .map_err(internal)?;
+    let unread = cache.unread_counts().await.map_err(internal)?;
+    Ok(Json(
+        conversations
+            .into_iter()
+            .map(|conv| ConversationEntry {
+                unread: unread.get(&conv.id).copied().unwrap_or(0),
+                display_name: if conv.display_name.is_empty() {
+                    conv.member_names
+                } else {
+                    conv.display_name
+                },
+                id: conv.id,
+                product_type: conv.product_type,
+                last_activity: conv.last_activity,
+                last_message_from: conv.last_message_from,
+                last_message_preview: conv.last_message_preview,
+            })
+            .collect(),
+    ))
+}
+
+/// Newest cached messages of a conversation, oldest first, without raw JSON.
+async fn list_messages(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<LimitQuery>,
+) -> Result<Json<Vec<CachedMessage>>, ApiError> {
+    let cache = state.cache().await?;
+    cache
+        .get_messages_without_raw(&id, clamp_limit(query.limit, 50))
+        .await
+        .map(Json)
+        .map_err(internal)
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    /// Full-text query (FTS5 syntax).
+    q: String,
+    /// Maximum number of results.
+    limit: Option<i64>,
+}
+
+/// Full-text search over cached messages, newest first.
+async fn search(
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchResult>>, ApiError> {
+    if query.q.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "empty query".to_string()));
+    }
+    let cache = state.cache().await?;
+    let mut results = cache
+        .search(&query.q, clamp_limit(query.limit, 20))
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    for result in &mut results {
+        result.message.raw_json = "{}".to_string();
+    }
+    Ok(Json(results))
+}
+
+#[derive(Deserialize)]
+struct SendRequest {
+    /// People alias, cached chat name, or conversation ID.
+    target: String,
+    /// Message body.
+    message: String,
+    /// `text` (default), `markdown`, or `html`.
+    #[serde(default)]
+    format: BodyFormat,
+    /// Notify nobody (see [`tmz_core::markup::silence`]).
+    #[serde(default)]
+    silent: bool,
+}
+
+#[derive(Serialize)]
+struct SendResponse {
+    conversation_id: String,
+    /// `[sending] off_hours = "warn"` warning about the recipient.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warning: Option<String>,
+}
+
+/// Send a message to a chat or channel. Applies read-only mode, the
+/// outbound composer checks, and `[sending] off_hours`; with nobody to
+/// confirm, `confirm` refuses like `block`. Scoped tokens may only send to
+/// their targets. The delivery state is kept in the outbox.
+async fn send(
+    State(state): State<AppState>,
+    scope: Option<Extension<Scope>>,
+    Json(req): Json<SendRequest>,
+) -> Result<Json<SendResponse>, ApiError> {
+    state
+        .config
+        .security
+        .ensure_writable("sending messages")
+        .map_err(|e| (StatusCode::FORBIDDEN, e.to_string()))?;
+    if req.message.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "empty message".to_string()));
+    }
+    tmz_core::composer::check_outbound(&state.config.composer, &req.message)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    let conversation_id = state.resolve_target(&req.target).await?;
+    if let Some(Extension(scope)) = scope
+        && !scope.allows_target(&conversation_id)
+    {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("this token may not send to '{}'", req.target),
+        ));
+    }
+    let warning = check_off_hours(&state, &conversation_id).await?;
+
+    let mut content = req.format.to_html(&req.message);
+    if req.silent || state.config.sending.silent {
+        content = tmz_core::markup::silence(&content);
+    }
+    let sent = tmz_core::outbox::track(
+        state.outbox().await,
+        &conversation_id,
+        &content,
+        OutgoingKind::Text,
+        tmz_core::engine::send_message(&conversation_id, &content),
+    )
+    .await;
+    state.audit.record("send", Some(&conversation_id), &sent);
+    sent.map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+    Ok(Json(SendResponse {
+        conversation_id,
+        warning,
+    }))
+}
+
+/// Apply `[sending] off_hours` to a send to `conv_id`.
+async fn check_off_hours(state: &AppState, conv_id: &str) -> Result<Option<String>, ApiError> {
+    let off = match tmz_core::hours::check_recipient(&state.config.sending, conv_id).await {
+        Ok(Some(off)) => off,
+        Ok(None) => return Ok(None),
+        Err(e @ CoreError::Config(_)) => return Err(internal(e)),
+        Err(e) => {
+            log::debug!("checking working hours: {e}");
+            return Ok(None);
+        }
+    };
+    let reason = off.describe("the recipient");
+    if state.config.sending.off_hours == OffHoursAction::Warn {
+        Ok(Some(reason))
+    } else {
+        Err((StatusCode::CONFLICT, format!("not sent: {reason}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tmz_core::testutil::TempDir;
+    use tower::ServiceExt as _;
+
+    /// The API router with `main-token` as the main token and a token for
+    /// `ci` scoped to one target, which is returned.
+    fn app(dir: &TempDir) -> (Router, String) {
+        let scoped = TokenStore::new(dir);
+        let scope = Scope {
+            send_only: false,
+            targets: vec!["19:deploys@thread.tacv2".to_string()],
+        };
+        let (_, ci_token) = scoped.create(Some("ci"), scope).unwrap();
+
+        let mut config = AppConfig::default();
+        config.api.token = Some("main-token".to_string());
+        config.mcp.http_token = Some("mcp-token".to_string());
+        let state = AppState {
+            audit: Arc::new(AuditLog::new(dir, &config.audit, "api")),
+            config: Arc::new(config),
+            db_path: Arc::new(dir.join("cache.db")),
+            cache: Arc::new(OnceCell::new()),
+            outbox: Arc::new(OnceCell::new()),
+        };
+        let tokens = Tokens {
+            main: Arc::from("main-token"),
+            scoped,
+        };
+        (router(state, tokens), ci_token)
+    }
+
+    fn get(path: &str, token: &str) -> Request<Body> {
+        Request::get(path)
+            .header(AUTHORIZATION, format!("Bearer {token}"))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn target_scoped_tokens_may_only_send() {
+        let dir = TempDir::new("api-test").unwrap();
+        let (app, ci_token) = app(&dir);
+        for path in ["/config", "/search?q=x", "/conversations", "/daemon/logs"] {
+            let response = app.clone().oneshot(get(path, &ci_token)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn config_is_served_with_tokens_redacted() {
+        let dir = TempDir::new("api-test").unwrap();
+        let (app, _) = app(&dir);
+        let response = app.oneshot(get("/config", "main-token")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(!body.contains("main-token") && !body.contains("mcp-token"));
+        assert!(body.contains("<redacted>"));
+    }
 }
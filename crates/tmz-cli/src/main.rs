@@ -1,17 +1,33 @@
 //! CLI interface for tmz - Microsoft Teams from the terminal.
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::io::{self, IsTerminal};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::{Context as _, Result, anyhow};
 use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
 use env_logger::fmt::WriteStyle;
 use log::{LevelFilter, debug};
-use tmz_core::cache::{self, Cache};
+use tmz_core::apitokens::{Scope, TokenStore};
+use tmz_core::audit::AuditLog;
+use tmz_core::bundle::BundleFile;
+use tmz_core::cache::{self, Cache, CachedConversation, FileMatch, OutgoingKind, SearchResult};
+use tmz_core::engine::{self, Engine, EngineClient, SyncOptions, SyncProgress};
+use tmz_core::graphics::ImageProtocol;
+use tmz_core::insights;
+use tmz_core::inspect::TokenKind;
+use tmz_core::markup::{self, BodyFormat};
 use tmz_core::paths::write_default_config;
-use tmz_core::{AppConfig, AppPaths, AuthManager, TeamsClient, default_cache_dir};
+use tmz_core::restrictions::Action;
+use tmz_core::scripting::{self, ActionContext, Scripts};
+use tmz_core::teams::PresenceStatus;
+use tmz_core::{
+    AppConfig, AppPaths, AuthManager, Density, DisplayConfig, MessagesConfig, OutputFormat,
+    SavedSearch, SearchScope, TeamsClient, TokenAudience, default_cache_dir,
+};
 
 const APP_NAME: &str = "tmz";
 
@@ -26,23 +42,39 @@ fn try_main() -> Result<()> {
     ctx.init_logging()?;
     debug!("resolved paths: {:#?}", ctx.paths);
 
-    // Show reauth warning if the daemon's headless refresh failed.
     // Skip for auth commands (user is probably about to fix it).
-    if !matches!(cli.command, Command::Auth { .. })
-        && let Some(reason) = tmz_core::daemon::check_reauth_needed()
-    {
-        eprintln!(
-            "\x1b[33mwarning:\x1b[0m session expired. Run \x1b[1mtmz auth login\x1b[0m to re-authenticate."
-        );
-        debug!("reauth reason: {reason}");
+    if !matches!(cli.command, Command::Auth { .. }) {
+        warn_if_reauth_needed();
     }
 
     let rt = tokio::runtime::Runtime::new()?;
 
+    if cli.command.talks_to_teams() {
+        rt.block_on(refresh_ahead(&ctx));
+    }
+
     match cli.command {
-        Command::Auth { subcommand } => rt.block_on(handle_auth(&ctx, subcommand)),
+        Command::Auth { subcommand } => {
+            let action = subcommand.audit_action();
+            let result = rt.block_on(handle_auth(&ctx, subcommand));
+            if let Some(action) = action {
+                ctx.audit().record(action, None, &result);
+            }
+            result
+        }
         Command::Sync(cmd) => rt.block_on(handle_sync(&ctx, cmd)),
         Command::Chats(cmd) => rt.block_on(handle_chats(&ctx, cmd)),
+        Command::Msg {
+            target,
+            inspect: Some(message_id),
+            ..
+        } => rt.block_on(handle_inspect(&ctx, &target, &message_id)),
+        Command::Msg {
+            target,
+            delete,
+            delete_last,
+            ..
+        } if delete.is_some() || delete_last => rt.block_on(handle_delete(&ctx, &target, delete)),
         Command::Msg {
             target,
             message,
@@ -50,23 +82,52 @@ fn try_main() -> Result<()> {
             limit,
             no_images,
             sync,
+            legend,
+            translate,
+            inspect: None,
+            send,
+            ack,
+            ..
         } => rt.block_on(handle_msg(
-            &ctx, target, message, file, limit, no_images, sync,
+            &ctx,
+            target,
+            message.or_else(|| ack.then(|| ctx.config.sending.ack.clone())),
+            file,
+            sync,
+            MsgView {
+                limit,
+                no_images,
+                legend,
+                translate,
+            },
+            send,
         )),
+        Command::React(args) => rt.block_on(handle_react(&ctx, &args)),
         Command::Tldr { chats, per_chat } => rt.block_on(handle_tldr(&ctx, chats, per_chat)),
-        Command::Search { query, chat, limit } => {
-            rt.block_on(handle_search(&ctx, &query, chat.as_deref(), limit))
-        }
-        Command::Find { query, conv_type } => rt.block_on(handle_find(&ctx, &query, conv_type)),
-        Command::Alias {
-            name,
-            target,
-            conv_type,
-        } => rt.block_on(handle_alias(&ctx, &name, target, conv_type)),
+        Command::Digest { since } => rt.block_on(handle_digest(&ctx, &since)),
+        Command::Search(args) => rt.block_on(handle_search(&ctx, &resolve_search(&ctx, args)?)),
+        Command::PersonFeed(args) => rt.block_on(handle_person_feed(&ctx, &args)),
+        Command::Export(args) => rt.block_on(handle_export(&ctx, args)),
+        Command::Outbox(cmd) => rt.block_on(handle_outbox(&ctx, cmd)),
+        Command::Remind(cmd) => rt.block_on(handle_remind(&ctx, cmd)),
+        Command::Save(args) => rt.block_on(handle_save(&ctx, &args)),
+        Command::Saved { command } => rt.block_on(handle_saved(&ctx, command)),
+        Command::Listen { fifo } => rt.block_on(handle_listen(&ctx, &fifo)),
+        Command::Audit { command } => handle_audit(&ctx, &command),
+        Command::Stats { command } => rt.block_on(handle_stats(&ctx, &command)),
+        Command::Script { command } => rt.block_on(handle_script(&ctx, command)),
+        Command::Find(args) => rt.block_on(handle_find(&ctx, &args)),
+        Command::Alias(args) => rt.block_on(handle_alias(&ctx, &args)),
+        Command::People { command } => rt.block_on(handle_people(&ctx, command)),
+        Command::Status { command } => rt.block_on(handle_status(&ctx, &command)),
         Command::Teams { subcommand } => rt.block_on(handle_teams(&ctx, subcommand)),
+        Command::Channel { command } => rt.block_on(handle_channel(&ctx, command)),
         Command::Service { command } => rt.block_on(handle_service(&ctx, command)),
         Command::Init(cmd) => handle_init(&ctx, cmd),
         Command::Cache { command } => rt.block_on(handle_cache(&ctx, command)),
+        Command::Api { command } => rt.block_on(handle_api(&ctx, command)),
+        Command::Doctor => rt.block_on(handle_doctor(&ctx)),
+        Command::Debug { command } => rt.block_on(handle_debug(&ctx, command)),
         Command::Config { command } => handle_config(&ctx, command),
         Command::Completions { shell } => {
             handle_completions(shell);
@@ -96,6 +157,13 @@ pub struct CommonOpts {
     /// Override the config file path.
     #[arg(long, value_name = "PATH", global = true)]
     pub config: Option<PathBuf>,
+    /// Config profile to apply (`[profiles.NAME]`; default: `TMZ_PROFILE` or `profile`).
+    #[arg(long, value_name = "NAME", global = true)]
+    pub profile: Option<String>,
+    /// Named account with its own tokens and cache (`default` for the
+    /// unnamed one).
+    #[arg(long, value_name = "NAME", env = "TMZ_ACCOUNT", global = true)]
+    pub account: Option<String>,
     /// Reduce output to only errors.
     #[arg(short, long, action = clap::ArgAction::SetTrue, global = true)]
     pub quiet: bool,
@@ -108,9 +176,22 @@ pub struct CommonOpts {
     /// Enable trace logging.
     #[arg(long, global = true)]
     pub trace: bool,
+    /// Record every HTTP request in the state directory's
+    /// `http-trace.jsonl` (see `tmz debug last-requests`).
+    #[arg(long, global = true)]
+    pub trace_http: bool,
     /// Output machine-readable JSON.
     #[arg(long, global = true)]
     pub json: bool,
+    /// Structured output of list commands (chats, find, search, teams).
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        global = true,
+        conflicts_with = "json"
+    )]
+    pub format: Option<FormatOption>,
     /// Disable ANSI colors in output.
     #[arg(long = "no-color", global = true, conflicts_with = "color")]
     pub no_color: bool,
@@ -123,6 +204,9 @@ pub struct CommonOpts {
     /// Assume "yes" for interactive prompts.
     #[arg(short = 'y', long = "yes", alias = "force", global = true)]
     pub assume_yes: bool,
+    /// Show messages as compact one-liners (overrides `[display] density`).
+    #[arg(long, global = true)]
+    pub compact: bool,
 }
 
 /// Color output mode.
@@ -136,6 +220,115 @@ pub enum ColorOption {
     Never,
 }
 
+/// Structured output format (`--format`).
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FormatOption {
+    /// Same as `--json`.
+    Json,
+    /// Nushell object notation (`from nuon`).
+    Nuon,
+    /// CSV with a header row; nested fields become `parent.child` columns.
+    Csv,
+    /// YAML.
+    Yaml,
+}
+
+impl From<FormatOption> for OutputFormat {
+    fn from(format: FormatOption) -> Self {
+        match format {
+            FormatOption::Json => Self::Json,
+            FormatOption::Nuon => Self::Nuon,
+            FormatOption::Csv => Self::Csv,
+            FormatOption::Yaml => Self::Yaml,
+        }
+    }
+}
+
+/// What `tmz search` looks through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum SearchWhat {
+    /// Message text and senders.
+    Messages,
+    /// Conversation names, topics, and member names.
+    Chats,
+    /// Both chats and messages.
+    All,
+}
+
+/// Output format for `tmz export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ExportFormat {
+    /// Markdown with one header per day.
+    #[value(alias = "markdown")]
+    Md,
+    /// One mbox file with a mail per message, for notmuch, mu, and mail
+    /// clients.
+    Mbox,
+    /// One `.eml` file per message in the --output folder.
+    Eml,
+}
+
+/// How a message given to `tmz msg` is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum MessageFormat {
+    /// Plain text, sent as written.
+    #[default]
+    Text,
+    /// The Markdown subset: bold, italic, code, links, lists, headings.
+    #[value(alias = "md")]
+    Markdown,
+    /// HTML, reduced to the tags Teams renders.
+    Html,
+}
+
+impl From<MessageFormat> for BodyFormat {
+    fn from(format: MessageFormat) -> Self {
+        match format {
+            MessageFormat::Text => Self::Text,
+            MessageFormat::Markdown => Self::Markdown,
+            MessageFormat::Html => Self::Html,
+        }
+    }
+}
+
+/// A presence `tmz status set` can set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum PresenceChoice {
+    /// Available.
+    Available,
+    /// Busy.
+    Busy,
+    /// Do not disturb: notifications are muted.
+    #[value(alias = "do-not-disturb")]
+    Dnd,
+    /// Appear away.
+    Away,
+    /// Appear offline.
+    Offline,
+}
+
+impl From<PresenceChoice> for PresenceStatus {
+    fn from(choice: PresenceChoice) -> Self {
+        match choice {
+            PresenceChoice::Available => Self::Available,
+            PresenceChoice::Busy => Self::Busy,
+            PresenceChoice::Dnd => Self::DoNotDisturb,
+            PresenceChoice::Away => Self::Away,
+            PresenceChoice::Offline => Self::Offline,
+        }
+    }
+}
+
+impl From<SearchWhat> for SearchScope {
+    fn from(what: SearchWhat) -> Self {
+        match what {
+            SearchWhat::Messages => Self::Messages,
+            SearchWhat::Chats => Self::Chats,
+            SearchWhat::All => Self::All,
+        }
+    }
+}
+
 /// Filter conversations by type.
 #[derive(Debug, Clone, Copy, clap::ValueEnum)]
 enum ConvTypeFilter {
@@ -211,12 +404,16 @@ enum Command {
     /// Sync conversations and messages to local cache.
     Sync(SyncCommand),
     /// List cached conversations.
+    #[command(visible_alias = "chat")]
     Chats(ChatsCommand),
     /// Read or send messages. Usage: tmz msg <person> [message].
     Msg {
-        /// Person alias, display name, or conversation ID.
+        /// Person alias, display name, or conversation ID; `-` for the
+        /// last conversation opened. An alias group (`[groups]`) sends to
+        /// each of its members.
         target: String,
-        /// Message to send. Omit to show recent messages.
+        /// Message to send (`-` reads it from stdin). Omit to show recent
+        /// messages.
         message: Option<String>,
         /// Send a file instead of (or with) a message.
         #[arg(short, long, value_name = "PATH")]
@@ -230,7 +427,43 @@ enum Command {
         /// Sync this conversation before showing messages.
         #[arg(short, long)]
         sync: bool,
+        /// Show a legend of senders and their colors above the messages.
+        #[arg(long)]
+        legend: bool,
+        /// Translate messages into LANG (default: `[ai.translation] target_language`).
+        #[arg(
+            short = 't',
+            long,
+            value_name = "LANG",
+            num_args = 0..=1,
+            default_missing_value = "",
+            conflicts_with_all = ["message", "file"]
+        )]
+        translate: Option<String>,
+        /// Pretty-print the stored raw JSON of a message, with known fields annotated.
+        #[arg(long, value_name = "MESSAGE_ID", conflicts_with_all = ["message", "file", "sync"])]
+        inspect: Option<String>,
+        #[command(flatten)]
+        send: SendOpts,
+        /// Send the `[sending] ack` acknowledgment (default 👍).
+        #[arg(long, conflicts_with_all = ["message", "file", "translate", "inspect"])]
+        ack: bool,
+        /// Delete a message you sent (asks first unless `--yes`).
+        #[arg(
+            long,
+            value_name = "MESSAGE_ID",
+            conflicts_with_all = ["message", "file", "translate", "inspect", "ack"]
+        )]
+        delete: Option<String>,
+        /// Delete your most recent message (asks first unless `--yes`).
+        #[arg(
+            long,
+            conflicts_with_all = ["message", "file", "translate", "inspect", "ack", "delete"]
+        )]
+        delete_last: bool,
     },
+    /// React to a message without looking up its ID.
+    React(ReactArgs),
     /// Show latest messages across your most recent chats.
     Tldr {
         /// Number of chats to show (most recently active).
@@ -240,40 +473,76 @@ enum Command {
         #[arg(short, long, default_value_t = 5)]
         per_chat: i64,
     },
+    /// Show unread mentions and chats, as the scheduled digest job would.
+    Digest {
+        /// Start of the window (`1d`, `yesterday`, `monday`, ...).
+        #[arg(long, default_value = "1d")]
+        since: String,
+    },
     /// Full-text search across cached messages.
-    Search {
-        /// Search query (FTS5 syntax).
-        query: String,
-        /// Scope to a specific chat (alias, name, or ID).
-        #[arg(short, long, value_name = "CHAT")]
-        chat: Option<String>,
-        /// Max results.
-        #[arg(short, long, default_value_t = 20)]
-        limit: i64,
+    Search(SearchArgs),
+    /// Everything one person wrote in any chat, as one stream, oldest first.
+    PersonFeed(PersonFeedArgs),
+    /// Show or cancel messages waiting out the `[sending] undo_window`.
+    Outbox(OutboxCommand),
+    /// Get reminded to follow up on a conversation; the daemon notifies
+    /// and marks it unread again when it is due.
+    Remind(RemindCommand),
+    /// Copy a message into your local saved items.
+    Save(SaveArgs),
+    /// List or remove saved messages.
+    Saved {
+        #[command(subcommand)]
+        command: SavedCommand,
     },
-    /// Find a conversation by name and show its ID.
-    Find {
-        /// Search term (fuzzy matched against names, members, IDs).
-        query: String,
-        /// Filter by conversation type: 1:1, group, channel, meeting.
-        #[arg(short = 't', long = "type", value_enum)]
-        conv_type: Option<ConvTypeFilter>,
+    /// Send each `target<TAB>message` line written to a named pipe.
+    Listen {
+        /// Named pipe to read; created (mode 600) and removed again if
+        /// it does not exist.
+        #[arg(long, value_name = "PATH")]
+        fifo: PathBuf,
+    },
+    /// Show or verify the audit log of sends, uploads, deletes, and auth events.
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+    /// Show statistics such as the time spent per conversation.
+    Stats {
+        #[command(subcommand)]
+        command: StatsCommand,
+    },
+    /// Run actions defined in the user script (`scripts.rhai`).
+    Script {
+        #[command(subcommand)]
+        command: ScriptCommand,
     },
+    /// Export a conversation from the cache (e.g. as Markdown meeting notes).
+    Export(ExportArgs),
+    /// Find a conversation by name and show its ID.
+    Find(FindArgs),
     /// Create a people/chat alias (written to config.toml).
-    Alias {
-        /// Short alias name (e.g., "alex").
-        name: String,
-        /// Conversation ID or search term. Omit to search interactively.
-        target: Option<String>,
-        /// Filter by conversation type: 1:1, group, channel, meeting.
-        #[arg(short = 't', long = "type", value_enum)]
-        conv_type: Option<ConvTypeFilter>,
+    Alias(AliasArgs),
+    /// Look up people: availability and org chart.
+    People {
+        #[command(subcommand)]
+        command: PeopleCommand,
+    },
+    /// Show or set your own presence (Available, Busy, ...).
+    Status {
+        #[command(subcommand)]
+        command: StatusCommand,
     },
     /// Teams and channels (via Graph API).
     Teams {
         #[command(subcommand)]
         subcommand: TeamsSubcommand,
     },
+    /// Post to team channels.
+    Channel {
+        #[command(subcommand)]
+        command: ChannelCommand,
+    },
     /// Create config directories and default files.
     Init(InitCommand),
     /// Background daemon for token refresh and sync.
@@ -286,6 +555,18 @@ enum Command {
         #[command(subcommand)]
         command: CacheCommand,
     },
+    /// Manage access to the HTTP API (`tmz-api`).
+    Api {
+        #[command(subcommand)]
+        command: ApiCommand,
+    },
+    /// Diagnose installation, auth, and cache health.
+    Doctor,
+    /// Debugging aids for bug reports.
+    Debug {
+        #[command(subcommand)]
+        command: DebugCommand,
+    },
     /// Inspect and manage configuration.
     Config {
         #[command(subcommand)]
@@ -298,11 +579,32 @@ enum Command {
     },
 }
 
+#[derive(Debug, Clone, Subcommand)]
+enum StatusCommand {
+    /// Show your current presence.
+    Show,
+    /// Set your presence, for a while or until `tmz status reset`.
+    Set {
+        /// Presence to show to others.
+        #[arg(value_enum)]
+        status: PresenceChoice,
+        /// Go back to the automatic presence after this long (`30m`, `1h`).
+        #[arg(short, long, value_name = "DURATION")]
+        duration: Option<String>,
+    },
+    /// Let Teams set your presence from your activity and calendar again.
+    Reset,
+}
+
 #[derive(Debug, Clone, Subcommand)]
 enum AuthSubcommand {
     /// Check authentication status.
     Status,
-    /// Login to Microsoft Teams (opens browser, extracts tokens automatically).
+    /// Show the signed-in user's Graph profile, presence, and the scopes
+    /// and expiry of each token.
+    Whoami,
+    /// Login to Microsoft Teams (device code in your browser, no Node.js
+    /// needed).
     Login {
         /// Timeout in seconds for the browser login flow.
         #[arg(long, default_value_t = 300)]
@@ -310,12 +612,62 @@ enum AuthSubcommand {
         /// Skip automated extraction and print manual instructions.
         #[arg(long)]
         manual: bool,
+        /// Log in through the Playwright auth script instead (needs Node.js).
+        #[arg(long, conflicts_with = "manual")]
+        browser: bool,
         /// Clear browser profile and start fresh (fixes "sign in again" errors).
-        #[arg(long)]
+        #[arg(long, requires = "browser")]
         fresh: bool,
+        /// Log in from another device (e.g. a phone) via device code and QR code.
+        #[arg(long, conflicts_with_all = ["manual", "browser"])]
+        remote: bool,
+    },
+    /// Refresh tokens silently with the stored refresh token or browser session.
+    ///
+    /// Use -v to show per-audience expiry details.
+    Refresh,
+    /// Wipe the persistent browser profile and log in again to re-seed it.
+    ///
+    /// Use when headless refresh keeps failing despite a working login.
+    ResetBrowser {
+        /// Only wipe the profile, do not start a new login.
+        #[arg(long)]
+        no_login: bool,
+        /// Timeout in seconds for the browser login flow.
+        #[arg(long, default_value_t = 300)]
+        timeout: u64,
+    },
+    /// Import tokens from another client (fallback when Playwright cannot run)
+    /// or from a file written by `tmz auth export`.
+    Import {
+        /// Read tokens from the official Teams desktop client's local storage.
+        #[arg(long, required_unless_present = "file")]
+        from_teams_desktop: bool,
+        /// Client profile directory to scan (default: platform-specific locations).
+        #[arg(long, value_name = "DIR", requires = "from_teams_desktop")]
+        path: Vec<PathBuf>,
+        /// Encrypted token file from `tmz auth export` (`-` reads stdin).
+        /// The passphrase is prompted for, or taken from
+        /// `TMZ_TOKEN_PASSPHRASE`.
+        #[arg(
+            long,
+            short,
+            value_name = "FILE",
+            conflicts_with = "from_teams_desktop"
+        )]
+        file: Option<PathBuf>,
+    },
+    /// Write the stored tokens to a passphrase-encrypted file (age format),
+    /// to import them on a machine where you cannot log in.
+    Export {
+        /// File to write (`-` for stdout).
+        #[arg(long, short, value_name = "FILE")]
+        file: PathBuf,
     },
     /// Logout and clear stored tokens.
     Logout,
+    /// List the default and named accounts and who is logged in to each.
+    Accounts,
     /// Store tokens manually (fallback if automated extraction fails).
     Store {
         /// Token for api.spaces.skype.com.
@@ -333,6 +685,121 @@ enum AuthSubcommand {
     },
 }
 
+impl Command {
+    /// Whether the command calls Teams, so expiring tokens should be
+    /// refreshed before it starts.
+    const fn talks_to_teams(&self) -> bool {
+        match self {
+            Self::Sync(_)
+            | Self::People { .. }
+            | Self::Status { .. }
+            | Self::Teams { .. }
+            | Self::Channel { .. }
+            | Self::Listen { .. }
+            | Self::React(_) => true,
+            Self::Msg {
+                message,
+                file,
+                sync,
+                ack,
+                delete,
+                delete_last,
+                ..
+            } => {
+                message.is_some()
+                    || file.is_some()
+                    || *sync
+                    || *ack
+                    || delete.is_some()
+                    || *delete_last
+            }
+            Self::Chats(cmd) => matches!(
+                cmd.command,
+                Some(
+                    ChatsSubcommand::Hide { remote: true, .. }
+                        | ChatsSubcommand::Unhide { remote: true, .. }
+                )
+            ),
+            Self::Cache { command } => matches!(command, CacheCommand::IndexFiles { .. }),
+            _ => false,
+        }
+    }
+}
+
+impl AuthSubcommand {
+    /// Audit log action for subcommands that change stored credentials.
+    const fn audit_action(&self) -> Option<&'static str> {
+        match self {
+            Self::Status | Self::Whoami | Self::Accounts | Self::Login { manual: true, .. } => None,
+            Self::Login { .. } => Some("auth.login"),
+            Self::Refresh => Some("auth.refresh"),
+            Self::ResetBrowser { .. } => Some("auth.reset-browser"),
+            Self::Import { .. } => Some("auth.import"),
+            Self::Export { .. } => Some("auth.export"),
+            Self::Logout => Some("auth.logout"),
+            Self::Store { .. } => Some("auth.store"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum AuditCommand {
+    /// List recorded actions, oldest first.
+    Show {
+        /// Only actions since: YYYY-MM-DD, yesterday, 7d, "2 weeks ago".
+        #[arg(short, long)]
+        since: Option<String>,
+        /// Only actions whose name starts with this (e.g. `send`, `auth`).
+        #[arg(short, long)]
+        action: Option<String>,
+        /// Show at most the last N actions.
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Check record signatures (needs `[audit] sign = true` and the key).
+    Verify,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum StatsCommand {
+    /// Time spent per conversation in the TUI (needs `[time_tracking]
+    /// enabled = true`), most first.
+    Time {
+        /// Only time since: YYYY-MM-DD, yesterday, 1w, "last monday".
+        #[arg(short, long)]
+        since: Option<String>,
+    },
+    /// When a contact is most active (weekday by hour, local time) and how
+    /// fast they answer you, from cached history.
+    Person {
+        /// Display name (or part of it), or an alias of your 1:1 chat.
+        name: String,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum ScriptCommand {
+    /// List the actions the script defines.
+    List,
+    /// Run an action.
+    Run {
+        /// Action name (see `tmz script list`).
+        action: String,
+        /// Conversation passed as `ctx.chat`: alias, display name, or
+        /// conversation ID; `-` for the last conversation opened.
+        #[arg(short, long)]
+        chat: Option<String>,
+        /// Message ID passed as `ctx.message`.
+        #[arg(long, value_name = "MESSAGE_ID", requires = "chat")]
+        message: Option<String>,
+        /// Extra arguments passed as `ctx.args`.
+        #[arg(trailing_var_arg = true)]
+        args: Vec<String>,
+    },
+    /// Compile the script and report errors.
+    Check,
+}
+
 #[derive(Debug, Clone, Copy, Args)]
 struct SyncCommand {
     /// Sync messages for ALL conversations (not just top N).
@@ -344,13 +811,186 @@ struct SyncCommand {
     /// Number of messages per conversation to fetch.
     #[arg(short = 'n', long, default_value_t = 50)]
     per_chat: i32,
+    /// Lite sync for metered connections: at most 15 messages per chat,
+    /// no images, and less stored per message.
+    #[arg(long)]
+    lite: bool,
 }
 
-#[derive(Debug, Clone, Copy, Args)]
+#[derive(Debug, Clone, Args)]
+#[command(args_conflicts_with_subcommands = true)]
 struct ChatsCommand {
+    #[command(subcommand)]
+    command: Option<ChatsSubcommand>,
     /// Max number of conversations to show.
     #[arg(short, long, default_value_t = 20)]
     limit: i64,
+    /// Include conversations hidden with `tmz chats hide`.
+    #[arg(short, long)]
+    all: bool,
+    /// Only conversations with this tag (see `tmz chats tag`).
+    #[arg(long, value_name = "TAG")]
+    tag: Option<String>,
+    /// One tab-separated line per conversation (id, type, name,
+    /// `last_activity`), for fzf and awk.
+    #[arg(long, conflicts_with_all = ["json", "format"])]
+    plumbing: bool,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum ChatsSubcommand {
+    /// Hide a conversation from chats, find, search, and the TUI.
+    Hide {
+        /// Person alias, display name, or conversation ID.
+        target: String,
+        /// Also hide it in Teams itself.
+        #[arg(long)]
+        remote: bool,
+    },
+    /// Show a hidden conversation again.
+    Unhide {
+        /// Person alias, display name, or conversation ID.
+        target: String,
+        /// Also unhide it in Teams itself.
+        #[arg(long)]
+        remote: bool,
+    },
+    /// List hidden conversations.
+    Hidden,
+    /// Attach local tags to a conversation, for `--tag` filters and the TUI.
+    Tag {
+        /// Person alias, display name, or conversation ID.
+        target: String,
+        /// Tags to attach, such as `project-x`.
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from a conversation.
+    Untag {
+        /// Person alias, display name, or conversation ID.
+        target: String,
+        /// Tags to remove.
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// List tags and the conversations that have them, including `[tags]`
+    /// from the config.
+    Tags,
+    /// Show who is in a conversation, as far as the cache knows.
+    Members {
+        /// Person alias, display name, or conversation ID.
+        target: String,
+        /// Show when people joined and left instead.
+        #[arg(long)]
+        history: bool,
+    },
+}
+
+#[derive(Debug, Clone, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct OutboxCommand {
+    #[command(subcommand)]
+    command: Option<OutboxSubcommand>,
+    /// Also show sent, cancelled, and failed messages.
+    #[arg(short, long)]
+    all: bool,
+    /// Max number of messages to show.
+    #[arg(short, long, default_value_t = 20)]
+    limit: i64,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum OutboxSubcommand {
+    /// Cancel a queued message before it is sent.
+    Cancel {
+        /// Outbox id (shown when queueing and by `tmz outbox`).
+        id: i64,
+    },
+    /// Show the delivery state of sent messages, newest first: queued,
+    /// sending or uploading, sent (with the message ID and time Teams
+    /// acknowledged), or failed.
+    History {
+        /// Only messages to this conversation (name, alias, ID, or `-`).
+        target: Option<String>,
+        /// Max number of messages to show.
+        #[arg(short, long, default_value_t = 20)]
+        limit: i64,
+    },
+}
+
+#[derive(Debug, Clone, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct RemindCommand {
+    #[command(subcommand)]
+    command: Option<RemindSubcommand>,
+    /// Conversation to come back to (name, alias, ID, or `-`).
+    target: Option<String>,
+    /// What to do there, shown in the notification.
+    note: Option<String>,
+    /// Remind after this long (`30m`, `2h`, `1d`).
+    #[arg(long = "in", value_name = "DURATION", conflicts_with = "at")]
+    after: Option<String>,
+    /// Remind at TIME (`09:00`, `tomorrow 09:00`, `mon 09:00`).
+    #[arg(long, value_name = "TIME")]
+    at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Subcommand)]
+enum RemindSubcommand {
+    /// List pending reminders, soonest first.
+    List {
+        /// Also show reminders that fired or were cancelled.
+        #[arg(short, long)]
+        all: bool,
+        /// Max number of reminders to show.
+        #[arg(short, long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// Cancel a pending reminder.
+    Cancel {
+        /// Reminder id (shown when setting it and by `tmz remind list`).
+        id: i64,
+    },
+}
+
+#[derive(Debug, Clone, Args)]
+struct PersonFeedArgs {
+    /// Person whose messages to show (name, alias, or part of a name).
+    name: String,
+    /// Show at most this many of their latest messages.
+    #[arg(short = 'n', long, default_value_t = 50)]
+    limit: i64,
+    /// Only messages since this point (e.g. `monday`, `2024-05-01`, `3d`).
+    #[arg(long, value_name = "WHEN")]
+    since: Option<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+struct SaveArgs {
+    /// Conversation the message is in (name, alias, ID, or `-`).
+    target: String,
+    /// Message ID (shown by `tmz msg <target> --json`).
+    message_id: String,
+    /// Why you saved it, shown in `tmz saved list`.
+    #[arg(short = 'm', long)]
+    note: Option<String>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum SavedCommand {
+    /// List saved messages, most recently saved first.
+    List {
+        /// Only items whose text, note, sender, or chat contain this.
+        query: Option<String>,
+        /// Max number of items to show.
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// Remove a saved message.
+    Remove {
+        /// Saved item id (shown by `tmz saved list`).
+        id: i64,
+    },
 }
 
 #[derive(Debug, Clone, Subcommand)]
@@ -364,6 +1004,172 @@ enum TeamsSubcommand {
     },
 }
 
+#[derive(Debug, Clone, Subcommand)]
+enum ChannelCommand {
+    /// Start a new post in a channel, optionally as an announcement with a
+    /// subject line.
+    Post {
+        /// `team/channel` by display name, or a channel ID (`19:...`).
+        target: String,
+        /// Message to post.
+        message: String,
+        /// Subject line shown above the post.
+        #[arg(long)]
+        subject: Option<String>,
+        /// Mark the post important.
+        #[arg(long)]
+        important: bool,
+        /// How the message is written (as for `tmz msg`).
+        #[arg(long, value_enum, value_name = "FORMAT", default_value_t)]
+        markup: MessageFormat,
+        /// Post without notifying anyone (as for `tmz msg`).
+        #[arg(long, conflicts_with = "important")]
+        silent: bool,
+    },
+}
+
+#[derive(Debug, Clone, Args)]
+struct ExportArgs {
+    /// Person alias, display name, or conversation ID.
+    target: String,
+    /// Output format.
+    #[arg(short, long, value_enum, default_value_t = ExportFormat::Md)]
+    format: ExportFormat,
+    /// Start of the range: YYYY-MM-DD, yesterday, "last monday", 3d, "2 weeks ago".
+    #[arg(long, value_name = "WHEN")]
+    since: Option<String>,
+    /// End of the range (exclusive), same forms as --since.
+    #[arg(long, value_name = "WHEN")]
+    until: Option<String>,
+    /// Write to a file instead of stdout (with --images or --format eml: the
+    /// folder).
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+    /// Maximum number of messages.
+    #[arg(short = 'n', long, default_value_t = 1000)]
+    limit: i64,
+    /// Save the images shared in the conversation to the --output folder
+    /// instead of exporting text.
+    #[arg(long, requires = "output")]
+    images: bool,
+}
+
+/// How `tmz msg` sends a message.
+#[derive(Debug, Clone, Args)]
+struct SendOpts {
+    /// Hold the message in the outbox until TIME (`09:00`, `tomorrow
+    /// 09:00`, `mon 09:00`, `in 2h`); the daemon sends it.
+    #[arg(
+        long,
+        value_name = "TIME",
+        requires = "message",
+        conflicts_with = "file"
+    )]
+    schedule: Option<String>,
+    /// In a 1:1 chat, check the recipient's presence first and, unless
+    /// they are Available, offer to hold the message until they are.
+    #[arg(
+        long,
+        requires = "message",
+        conflicts_with_all = ["file", "schedule"]
+    )]
+    if_available: bool,
+    /// Send the message as a reply quoting this message, shown as a
+    /// quoted reply in the Teams clients.
+    #[arg(
+        long,
+        value_name = "MESSAGE_ID",
+        requires = "message",
+        conflicts_with = "file"
+    )]
+    reply_to: Option<String>,
+    /// How the message is written; plain text is escaped so `<` and `&`
+    /// show up as typed.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t)]
+    markup: MessageFormat,
+    /// Send without notifying anyone: mentions become plain text and
+    /// `@channel`, `@team`, `@everyone`, and `@here` cannot ping (also
+    /// `[sending] silent`).
+    #[arg(long)]
+    silent: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+struct ReactArgs {
+    /// Person alias, display name, or conversation ID; `-` for the
+    /// last conversation opened.
+    target: String,
+    /// One of the six classic reaction emoji (👍), a name such as
+    /// `thumbsup` or `heart`, or a Teams reaction key (`like`,
+    /// `1f680_rocket`).
+    reaction: String,
+    /// React to the newest message someone else sent.
+    #[arg(long, required_unless_present = "message", conflicts_with = "message")]
+    last: bool,
+    /// React to the message with this ID.
+    #[arg(long, value_name = "MESSAGE_ID")]
+    message: Option<String>,
+    /// Remove the reaction instead of adding it.
+    #[arg(long)]
+    remove: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+struct AliasArgs {
+    /// Short alias name (e.g., "alex").
+    name: String,
+    /// Conversation ID or search term. Omit to search interactively.
+    target: Option<String>,
+    /// Filter by conversation type: 1:1, group, channel, meeting.
+    #[arg(short = 't', long = "type", value_enum)]
+    conv_type: Option<ConvTypeFilter>,
+}
+
+#[derive(Debug, Clone, Args)]
+struct FindArgs {
+    /// Search term (fuzzy matched against names, members, IDs).
+    query: String,
+    /// Filter by conversation type: 1:1, group, channel, meeting.
+    #[arg(short = 't', long = "type", value_enum)]
+    conv_type: Option<ConvTypeFilter>,
+    /// Only conversations with this tag (see `tmz chats tag`).
+    #[arg(long, value_name = "TAG")]
+    tag: Option<String>,
+    /// One tab-separated line per conversation (id, type, name,
+    /// `last_activity`), for fzf and awk.
+    #[arg(long, conflicts_with_all = ["json", "format"])]
+    plumbing: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+struct SearchArgs {
+    /// Search query (FTS5 syntax).
+    #[arg(required_unless_present_any = ["saved", "mentions_me"])]
+    query: Option<String>,
+    /// Run a saved search from the `[searches]` config section.
+    #[arg(short, long, value_name = "NAME", conflicts_with = "query")]
+    saved: Option<String>,
+    /// Scope to a specific chat (alias, name, or ID).
+    #[arg(short, long, value_name = "CHAT")]
+    chat: Option<String>,
+    /// Only conversations with this tag (see `tmz chats tag`).
+    #[arg(long, value_name = "TAG")]
+    tag: Option<String>,
+    /// Max results per kind [default: 20].
+    #[arg(short, long)]
+    limit: Option<i64>,
+    /// What to search: message text, chats (names, topics, members), or
+    /// both [default: all].
+    #[arg(short, long, value_enum)]
+    what: Option<SearchWhat>,
+    /// Only messages that @-mention you (the query becomes optional).
+    #[arg(long)]
+    mentions_me: bool,
+    /// Also search the text of files shared in chats (needs `[files] index = true`).
+    #[arg(short = 'F', long)]
+    include_files: bool,
+}
+
 #[derive(Debug, Clone, Copy, Args)]
 struct InitCommand {
     /// Recreate configuration even if it already exists.
@@ -381,22 +1187,137 @@ enum CacheCommand {
         #[arg(short, long, default_value_t = 30)]
         days: u32,
     },
+    /// Show the messages `[retention]` prunes after each sync.
+    Retention {
+        /// Prune them now instead of only reporting.
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Download files shared in chats and index their text for
+    /// `tmz search --include-files` (needs `[files] index = true`).
+    IndexFiles {
+        /// Maximum number of files to process.
+        #[arg(short = 'n', long, default_value_t = 200)]
+        limit: usize,
+    },
+    /// Extract text from cached images so search can find it (needs `[ocr] enabled = true`).
+    Ocr {
+        /// Maximum number of images to process.
+        #[arg(short = 'n', long, default_value_t = 1000)]
+        limit: usize,
+    },
+    /// Rewrite stored raw JSON to match `[cache] compress_raw_json` and
+    /// report the space saved.
+    Compact,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum ApiCommand {
+    /// Scoped tokens for clients such as CI jobs.
+    Token {
+        #[command(subcommand)]
+        command: ApiTokenCommand,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum ApiTokenCommand {
+    /// Create a token and print it once; only its hash is stored.
+    Create {
+        /// Only allow `POST /send`.
+        #[arg(long)]
+        send_only: bool,
+        /// Only allow sending to this chat or channel (alias, name, or
+        /// conversation ID); repeatable. Implies --send-only.
+        #[arg(short, long, value_name = "TARGET")]
+        target: Vec<String>,
+        /// Label shown in `tmz api token list`, such as the CI system.
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// List scoped tokens.
+    List,
+    /// Revoke a token by its ID.
+    Revoke {
+        /// Token ID from `tmz api token list`.
+        id: String,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum DebugCommand {
+    /// Write a redacted `.tar.gz` of logs, config, cache stats, and recent
+    /// errors to attach to an issue.
+    Bundle {
+        /// Archive path (default: ./tmz-debug-<timestamp>.tar.gz).
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Number of recent error events to include.
+        #[arg(short = 'n', long, default_value_t = 50)]
+        errors: usize,
+        /// Lines of each log to include.
+        #[arg(long, default_value_t = 500)]
+        log_lines: usize,
+    },
+    /// Print the newest requests recorded with `--trace-http`.
+    LastRequests {
+        /// Number of requests to show.
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+        /// Only show failed requests (status 400 and above, or no response).
+        #[arg(long)]
+        failed: bool,
+    },
 }
 
 #[derive(Debug, Clone, Copy, Subcommand)]
 enum ConfigCommand {
     /// Output the effective configuration.
-    Show,
+    Show {
+        /// Show which layer (default, system, user, project, profile, env) set each value.
+        #[arg(long)]
+        origin: bool,
+    },
     /// Print the resolved config file path.
     Path,
     /// Print all resolved paths.
     Paths,
+    /// List the TMZ__* environment variables that override config values.
+    Env {
+        /// Only list variables that are set.
+        #[arg(long)]
+        active: bool,
+    },
+    /// Migrate config files written for older versions and report unknown keys.
+    Migrate,
     /// Print the JSON schema.
     Schema,
     /// Regenerate the default configuration file.
     Reset,
 }
 
+#[derive(Debug, Clone, Subcommand)]
+enum PeopleCommand {
+    /// Show someone's presence, focus time, working hours, and calendar.
+    Show {
+        /// Person alias, display name, or 1:1 conversation ID.
+        person: String,
+    },
+    /// Show someone's manager.
+    Manager {
+        /// Person alias, 1:1 chat, name, email, or `me`.
+        person: String,
+        /// Follow the chain of managers up to the top.
+        #[arg(long)]
+        chain: bool,
+    },
+    /// List someone's direct reports.
+    Reports {
+        /// Person alias, 1:1 chat, name, email, or `me`.
+        person: String,
+    },
+}
+
 #[derive(Debug, Clone, Copy, Subcommand)]
 enum ServiceCommand {
     /// Start the background daemon.
@@ -415,6 +1336,17 @@ enum ServiceCommand {
     Run,
 }
 
+/// Show a warning if the daemon's headless refresh failed.
+fn warn_if_reauth_needed() {
+    if let Some(reason) = tmz_core::daemon::check_reauth_needed() {
+        eprintln!(
+            "\x1b[33mwarning:\x1b[0m session expired. Run \x1b[1m{}\x1b[0m to re-authenticate.",
+            tmz_core::accounts::login_command()
+        );
+        debug!("reauth reason: {reason}");
+    }
+}
+
 // ─── Runtime ─────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
@@ -426,9 +1358,29 @@ struct RuntimeContext {
 
 impl RuntimeContext {
     fn new(common: CommonOpts) -> Result<Self> {
+        if let Some(ref account) = common.account {
+            tmz_core::accounts::select(account)?;
+        }
         let paths = AppPaths::discover(common.config.as_deref())?;
-        let config = AppConfig::load(&paths, common.dry_run)?;
+        if !common.quiet
+            && let Some(report) =
+                tmz_core::migrate::migrate_file(&paths.config_file, common.dry_run)?
+        {
+            print_migration(&report);
+        }
+        let mut config =
+            AppConfig::load_with_profile(&paths, common.dry_run, common.profile.as_deref())?;
+        if common.compact {
+            config.display.density = Density::Compact;
+        }
         let paths = paths.apply_overrides(&config)?;
+        tmz_core::teams::endpoints::configure(&config.endpoints);
+        tmz_core::network::configure(&config.network)?;
+        tmz_core::retry::configure(&config.runtime.retries);
+        tmz_core::ratelimit::configure(&config.runtime.rate_limit);
+        if common.trace_http || config.logging.trace_http {
+            tmz_core::trace::enable(&paths.state_dir);
+        }
         let ctx = Self {
             common,
             paths,
@@ -470,6 +1422,20 @@ impl RuntimeContext {
         })
     }
 
+    /// Whether to emit ANSI colors on stdout, honoring `--color`,
+    /// `--no-color`, `NO_COLOR`, and `FORCE_COLOR`.
+    fn stdout_color(&self) -> bool {
+        if self.common.no_color
+            || matches!(self.common.color, ColorOption::Never)
+            || env::var_os("NO_COLOR").is_some()
+        {
+            return false;
+        }
+        matches!(self.common.color, ColorOption::Always)
+            || env::var_os("FORCE_COLOR").is_some()
+            || io::stdout().is_terminal()
+    }
+
     const fn effective_log_level(&self) -> LevelFilter {
         if self.common.trace {
             LevelFilter::Trace
@@ -485,6 +1451,14 @@ impl RuntimeContext {
         }
     }
 
+    /// Structured output asked for with `--json` or `--format`.
+    fn output_format(&self) -> Option<OutputFormat> {
+        self.common
+            .format
+            .map(OutputFormat::from)
+            .or_else(|| self.common.json.then_some(OutputFormat::Json))
+    }
+
     fn ensure_directories(&self) -> Result<()> {
         if self.common.dry_run {
             self.paths.log_dry_run();
@@ -495,17 +1469,48 @@ impl RuntimeContext {
 
     async fn open_cache(&self) -> Result<Cache> {
         let db_path = self.paths.data_dir.join("cache.db");
-        Cache::open(&db_path).await.map_err(|e| anyhow!("{e}"))
+        Cache::open(&db_path)
+            .await
+            .map(|c| {
+                c.with_attachment_limit(self.config.cache.max_attachment_bytes())
+                    .with_raw_json_compression(self.config.cache.compress_raw_json)
+            })
+            .map_err(|e| anyhow!("{e}"))
+    }
+
+    /// The audit log for actions taken by this command.
+    fn audit(&self) -> AuditLog {
+        AuditLog::new(&self.paths.state_dir, &self.config.audit, "cli")
+    }
+
+    /// Open the cache for a command that only reads, so it never contends
+    /// for the write lock. Falls back to a normal open if the cache is new.
+    async fn open_cache_read_only(&self) -> Result<Cache> {
+        let db_path = self.paths.data_dir.join("cache.db");
+        if !db_path.exists() {
+            return self.open_cache().await;
+        }
+        Cache::open_read_only(&db_path)
+            .await
+            .map_err(|e| anyhow!("{e}"))
     }
 
     /// Resolve a target string to a conversation ID.
-    /// Checks: 1) config alias  2) exact conversation ID in cache  3) fuzzy search cache.
+    /// Checks: 0) `-` for the last opened  1) config alias  2) exact conversation ID in cache
+    /// 3) fuzzy search cache.
     async fn resolve_target(&self, cache: &Cache, target: &str) -> Result<String> {
+        // 0. Last conversation opened in `tmz msg` or the TUI
+        if target == "-" {
+            return cache.last_opened_conversation().await?.ok_or_else(|| {
+                anyhow!("no conversation opened yet, so '-' has nothing to refer to")
+            });
+        }
+
         // 1. Config alias
         if let Some(resolved) = self.config.resolve_alias(target) {
             // The alias value might be a conversation ID or another name
-            // If it looks like a conversation ID (starts with 19:), use it
-            if resolved.starts_with("19:") {
+            // If it is a conversation ID, use it
+            if cache::is_conversation_id(resolved) {
                 return Ok(resolved.to_string());
             }
             // Otherwise try to find the conversation by name
@@ -527,7 +1532,7 @@ impl RuntimeContext {
         }
 
         // 2. Exact conversation ID
-        if target.starts_with("19:") {
+        if cache::is_conversation_id(target) {
             return Ok(target.to_string());
         }
 
@@ -551,44 +1556,25 @@ impl RuntimeContext {
 
 // ─── Handlers ────────────────────────────────────────────────────────
 
-async fn handle_auth(_ctx: &RuntimeContext, cmd: AuthSubcommand) -> Result<()> {
-    let auth = AuthManager::new()?;
+async fn handle_auth(ctx: &RuntimeContext, cmd: AuthSubcommand) -> Result<()> {
+    let auth = AuthManager::new()?.with_browser_profile(ctx.paths.browser_profile_dir.clone());
 
     match cmd {
-        AuthSubcommand::Status => {
-            match auth.is_authenticated() {
-                Ok(true) => {
-                    let tokens = auth.get_tokens()?;
-                    println!("Authenticated as: {}", tokens.user_principal_name);
-                    println!("Tenant ID:        {}", tokens.tenant_id);
-
-                    let now = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .map_or(0, |d| d.as_secs() as i64);
-                    let remaining = tokens.expires_at - now;
-                    if remaining > 0 {
-                        let mins = remaining / 60;
-                        let secs = remaining % 60;
-                        println!("Token expires:    {mins}m {secs}s remaining");
-                    }
-                }
-                Ok(false) => {
-                    println!("Not authenticated. Run 'tmz auth login' to authenticate.");
-                }
-                Err(e) => {
-                    return Err(anyhow!("Error checking auth status: {e}"));
-                }
-            }
-            Ok(())
-        }
+        AuthSubcommand::Status => auth_status(&auth),
+        AuthSubcommand::Whoami => auth_whoami(ctx, &auth).await,
         AuthSubcommand::Login {
             timeout,
             manual,
+            browser,
             fresh,
+            remote,
         } => {
+            if !browser && !manual {
+                return auth_login_device(&auth, remote).await;
+            }
             if manual {
                 println!("Opening browser for manual authentication...");
-                let _ = open::that_detached(AuthManager::TEAMS_URL);
+                let _ = open::that_detached(tmz_core::teams::endpoints::current().teams_web_url());
                 println!();
                 println!("After login, extract tokens and run:");
                 println!(
@@ -614,11 +1600,21 @@ async fn handle_auth(_ctx: &RuntimeContext, cmd: AuthSubcommand) -> Result<()> {
             println!("Tenant: {}", tokens.tenant_id);
             Ok(())
         }
+        AuthSubcommand::Refresh => auth_refresh(ctx, &auth).await,
+        AuthSubcommand::ResetBrowser { no_login, timeout } => {
+            auth_reset_browser(ctx, &auth, no_login, timeout).await
+        }
+        AuthSubcommand::Import {
+            file: Some(file), ..
+        } => auth_import_file(&auth, &file),
+        AuthSubcommand::Import { path, .. } => auth_import_desktop(&auth, &path),
+        AuthSubcommand::Export { file } => auth_export(&auth, &file),
         AuthSubcommand::Logout => {
             auth.logout()?;
             println!("Logged out.");
             Ok(())
         }
+        AuthSubcommand::Accounts => auth_accounts(ctx),
         AuthSubcommand::Store {
             skype_token,
             chat_token,
@@ -637,304 +1633,2966 @@ async fn handle_auth(_ctx: &RuntimeContext, cmd: AuthSubcommand) -> Result<()> {
     }
 }
 
-async fn handle_sync(ctx: &RuntimeContext, cmd: SyncCommand) -> Result<()> {
-    let client = TeamsClient::new()?;
-    let db = ctx.open_cache().await?;
-
-    // Get current user info for proper message attribution
-    let my_name: Option<String> = client.get_me().await.ok().and_then(|u| {
-        u["displayName"]
-            .as_str()
-            .map(std::string::ToString::to_string)
-    });
-
-    // 1. Sync conversations
-    eprint!("Syncing conversations... ");
-    let data = client.list_chats().await?;
-    let conversations = data["conversations"]
-        .as_array()
-        .ok_or_else(|| anyhow!("unexpected API response: missing conversations array"))?;
-
-    let mut conv_count = 0u64;
-    for conv in conversations {
-        let cached = cache::parse_conversation(conv);
-        db.upsert_conversation(&cached).await?;
-        conv_count += 1;
-    }
-    eprintln!("{conv_count} conversations.");
-
-    // 2. Sync messages for conversations (all if --full, top N otherwise)
-    if cmd.full || cmd.messages > 0 {
-        let top_convs = if cmd.full {
-            db.list_conversations(conv_count as i64).await?
-        } else {
-            db.list_conversations(cmd.messages as i64).await?
-        };
-        let total = top_convs.len();
-        let mut msg_count = 0u64;
-        let mut image_urls: Vec<String> = Vec::new();
-
-        for (i, conv) in top_convs.iter().enumerate() {
-            let name = if conv.display_name.is_empty() {
-                &conv.id
-            } else {
-                &conv.display_name
-            };
-            let short_name: String = name.chars().take(40).collect();
-            eprint!("\rSyncing messages [{}/{}] {short_name:<40}", i + 1, total);
-
-            match client.get_chat_messages(&conv.id, Some(cmd.per_chat)).await {
-                Ok(msg_data) => {
-                    if let Some(messages) = msg_data["messages"].as_array() {
-                        for msg in messages {
-                            if let Some(cached) =
-                                cache::parse_message(msg, &conv.id, my_name.as_deref())
-                            {
-                                // Extract image URLs for caching
-                                let urls =
-                                    tmz_core::kitty::extract_image_urls(&cached.content_html);
-                                for url in &urls {
-                                    if !db.has_image(url).await.unwrap_or(true) {
-                                        image_urls.push(url.clone());
-                                    }
-                                }
-                                db.upsert_message(&cached).await?;
-                                msg_count += 1;
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    log::warn!("failed to sync messages for {}: {e}", conv.id);
-                }
+/// Print the account, user, tenant, and token expiry.
+fn auth_status(auth: &AuthManager) -> Result<()> {
+    if let Some(account) = auth.account() {
+        println!("Account:          {account}");
+    }
+    match auth.is_authenticated() {
+        Ok(true) => {
+            let tokens = auth.get_tokens()?;
+            println!("Authenticated as: {}", tokens.user_principal_name);
+            println!("Tenant ID:        {}", tokens.tenant_id);
+
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64);
+            let remaining = tokens.expires_at - now;
+            if remaining > 0 {
+                let mins = remaining / 60;
+                let secs = remaining % 60;
+                println!("Token expires:    {mins}m {secs}s remaining");
             }
         }
-        eprintln!(
-            "\r{msg_count} messages across {total} conversations.{:>40}",
-            ""
-        );
-
-        // Download uncached images
-        if !image_urls.is_empty() {
-            let img_total = image_urls.len();
-            let mut img_ok = 0u64;
-            for (i, url) in image_urls.iter().enumerate() {
-                eprint!("\rCaching images [{}/{}]...", i + 1, img_total);
-                match client.download_image(url).await {
-                    Ok(data) => {
-                        let content_type = if url.contains("imgpsh") {
-                            "image/jpeg"
-                        } else {
-                            "image/png"
-                        };
-                        if db.cache_image(url, &data, content_type).await.is_ok() {
-                            img_ok += 1;
-                        }
-                    }
-                    Err(e) => log::debug!("image download failed: {e}"),
-                }
-            }
-            if img_ok > 0 {
-                eprintln!("\r{img_ok} images cached.{:>40}", "");
-            }
+        Ok(false) => {
+            println!(
+                "Not authenticated. Run '{}' to authenticate.",
+                tmz_core::accounts::login_command()
+            );
+        }
+        Err(e) => {
+            return Err(anyhow!("Error checking auth status: {e}"));
         }
     }
-
-    let stats = db.stats().await?;
-    println!(
-        "Cache: {} conversations, {} messages, {} images ({}).",
-        stats.conversations,
-        stats.messages,
-        stats.images,
-        format_bytes(stats.image_bytes),
-    );
-
     Ok(())
 }
 
-async fn handle_chats(ctx: &RuntimeContext, cmd: ChatsCommand) -> Result<()> {
-    let db = ctx.open_cache().await?;
-    let convs = db.list_conversations(cmd.limit).await?;
-
-    if convs.is_empty() {
-        println!("No conversations cached. Run 'tmz sync' first.");
-        return Ok(());
-    }
+/// List the default account and every named account with its login.
+fn auth_accounts(ctx: &RuntimeContext) -> Result<()> {
+    use tmz_core::accounts;
+    use tmz_core::teams::storage::TokenStorage;
+
+    let state_root = tmz_core::paths::base_state_dir()?;
+    let names = std::iter::once(None).chain(accounts::list(&state_root).into_iter().map(Some));
+    let now = chrono::Utc::now().timestamp();
+    let rows: Vec<(String, bool, Option<tmz_core::TeamsTokens>)> = names
+        .map(|name| {
+            let dir = accounts::scoped(state_root.clone(), name.as_deref());
+            let tokens = TokenStorage::in_dir(&dir).load_tokens().ok();
+            let active = name.as_deref() == accounts::active();
+            (
+                name.unwrap_or_else(|| accounts::DEFAULT_ACCOUNT.to_string()),
+                active,
+                tokens,
+            )
+        })
+        .collect();
 
     if ctx.common.json {
-        let json: Vec<serde_json::Value> = convs
+        let json: Vec<_> = rows
             .iter()
-            .filter_map(|c| serde_json::from_str(&c.raw_json).ok())
+            .map(|(name, active, tokens)| {
+                serde_json::json!({
+                    "account": name,
+                    "active": active,
+                    "user": tokens.as_ref().map(|t| &t.user_principal_name),
+                    "tenant_id": tokens.as_ref().map(|t| &t.tenant_id),
+                    "expires_at": tokens.as_ref().map(|t| t.expires_at),
+                })
+            })
             .collect();
         println!("{}", serde_json::to_string_pretty(&json)?);
         return Ok(());
     }
 
-    print_conversation_list(&convs);
+    for (name, active, tokens) in &rows {
+        let marker = if *active { '*' } else { ' ' };
+        let login = tokens.as_ref().map_or_else(
+            || "not logged in".to_string(),
+            |t| {
+                let state = if t.expires_at > now { "" } else { " (expired)" };
+                format!("{} [{}]{state}", t.user_principal_name, t.tenant_id)
+            },
+        );
+        println!("{marker} {name:<16} {login}");
+    }
     Ok(())
 }
 
-/// Sync messages for a specific conversation.
-async fn sync_conversation(db: &tmz_core::Cache, conv_id: &str, limit: i64) -> Result<u64> {
+/// Profile fields shown by `tmz auth whoami`, in order.
+const WHOAMI_FIELDS: &[(&str, &str)] = &[
+    ("displayName", "Name"),
+    ("userPrincipalName", "UPN"),
+    ("mail", "Mail"),
+    ("jobTitle", "Job title"),
+    ("department", "Department"),
+    ("officeLocation", "Office"),
+    ("mobilePhone", "Mobile"),
+];
+
+async fn auth_whoami(ctx: &RuntimeContext, auth: &AuthManager) -> Result<()> {
+    let tokens = auth.get_tokens()?;
     let client = TeamsClient::new()?;
-    let me = client
-        .get_me()
-        .await
-        .map_err(|e| anyhow!("get user info: {e}"))?;
-    let my_name = me["displayName"].as_str();
-    let limit_i32 = i32::try_from(limit).unwrap_or(50);
+    let profile = client.get_me().await?;
+    let presence = match client.get_user_presence(&tokens.user_id).await {
+        Ok(presence) => Some(presence),
+        Err(e) => {
+            debug!("presence lookup failed: {e}");
+            None
+        }
+    };
+    let audiences: Vec<(TokenAudience, Vec<String>, Option<i64>)> = TokenAudience::ALL
+        .iter()
+        .map(|&audience| {
+            let token = tokens.token_for(audience);
+            (
+                audience,
+                tmz_core::teams::auth::token_scopes(token),
+                tmz_core::teams::auth::token_expires_at(token),
+            )
+        })
+        .collect();
 
-    let data = client
-        .get_chat_messages(conv_id, Some(limit_i32))
-        .await
-        .map_err(|e| anyhow!("fetch messages: {e}"))?;
+    if ctx.common.json {
+        let json = serde_json::json!({
+            "display_name": profile["displayName"],
+            "user_principal_name": profile["userPrincipalName"],
+            "job_title": profile["jobTitle"],
+            "user_id": tokens.user_id,
+            "tenant_id": tokens.tenant_id,
+            "presence": presence.as_ref().map(|p| serde_json::json!({
+                "availability": p.availability.label(),
+                "activity": p.activity,
+            })),
+            "profile": profile,
+            "tokens": audiences.iter().map(|(audience, scopes, expires_at)| {
+                serde_json::json!({
+                    "audience": audience.as_str(),
+                    "resource": audience.resource(),
+                    "scopes": scopes,
+                    "expires_at": expires_at,
+                })
+            }).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
 
-    let mut count = 0u64;
-    if let Some(msgs) = data["messages"].as_array() {
-        for msg in msgs {
-            if let Some(cached) = cache::parse_message(msg, conv_id, my_name) {
-                let _ = db.upsert_message(&cached).await;
-                count += 1;
-            }
+    for (key, label) in WHOAMI_FIELDS {
+        if let Some(value) = profile[*key].as_str().filter(|v| !v.is_empty()) {
+            println!("{:<12} {value}", format!("{label}:"));
         }
     }
-    Ok(count)
+    println!("{:<12} {}", "Tenant ID:", tokens.tenant_id);
+    println!("{:<12} {}", "User ID:", tokens.user_id);
+    if let Some(presence) = &presence {
+        let activity = presence
+            .activity
+            .as_deref()
+            .map(|a| format!(" ({a})"))
+            .unwrap_or_default();
+        println!(
+            "{:<12} {}{activity}",
+            "Presence:",
+            presence.availability.label()
+        );
+    }
+    println!();
+    println!("Tokens:");
+    for (audience, scopes, expires_at) in &audiences {
+        let expiry = if tokens.token_for(*audience).is_empty() {
+            "missing".to_string()
+        } else {
+            expires_at.map_or_else(|| "-".to_string(), format_expiry)
+        };
+        println!("  {:<9} {expiry}", audience.as_str());
+        if !scopes.is_empty() {
+            println!("            {}", scopes.join(" "));
+        }
+    }
+    Ok(())
 }
 
-async fn handle_msg(
-    ctx: &RuntimeContext,
-    target: String,
-    message: Option<String>,
-    file: Option<PathBuf>,
-    limit: i64,
-    no_images: bool,
-    sync: bool,
-) -> Result<()> {
-    let db = ctx.open_cache().await?;
-    let conv_id = ctx.resolve_target(&db, &target).await?;
+async fn auth_refresh(ctx: &RuntimeContext, auth: &AuthManager) -> Result<()> {
+    let previous = auth.stored_tokens().ok();
+    let started = std::time::Instant::now();
+    let tokens = auth.refresh_tokens().await?;
+    let elapsed = started.elapsed();
+    let _ = tmz_core::daemon::clear_reauth_needed();
+
+    let audiences: Vec<(TokenAudience, &str, Option<i64>)> = TokenAudience::ALL
+        .iter()
+        .map(|&audience| {
+            let token = tokens.token_for(audience);
+            let status = if token.is_empty() {
+                "missing"
+            } else if previous
+                .as_ref()
+                .is_some_and(|p| p.token_for(audience) == token)
+            {
+                "unchanged"
+            } else {
+                "refreshed"
+            };
+            (
+                audience,
+                status,
+                tmz_core::teams::auth::token_expires_at(token),
+            )
+        })
+        .collect();
 
-    // Sync messages for this conversation if requested
-    if sync {
-        eprint!("Syncing conversation... ");
-        match sync_conversation(&db, &conv_id, limit).await {
-            Ok(count) => eprintln!("{count} messages synced."),
-            Err(e) => eprintln!("failed: {e}"),
-        }
+    if ctx.common.json {
+        let json = serde_json::json!({
+            "user": tokens.user_principal_name,
+            "tenant_id": tokens.tenant_id,
+            "expires_at": tokens.expires_at,
+            "audiences": audiences.iter().map(|(audience, status, expires_at)| {
+                serde_json::json!({
+                    "audience": audience.as_str(),
+                    "resource": audience.resource(),
+                    "status": status,
+                    "expires_at": expires_at,
+                })
+            }).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
     }
 
-    // Send file if --file is specified
-    if let Some(ref file_path) = file {
-        if !file_path.exists() {
-            return Err(anyhow!("file not found: {}", file_path.display()));
+    let refreshed: Vec<&str> = audiences
+        .iter()
+        .filter(|(_, status, _)| *status == "refreshed")
+        .map(|(audience, _, _)| audience.as_str())
+        .collect();
+
+    println!("Authenticated as: {}", tokens.user_principal_name);
+    if refreshed.is_empty() {
+        println!("Refreshed:        none (tokens unchanged)");
+    } else {
+        println!("Refreshed:        {}", refreshed.join(", "));
+    }
+    println!("Token expires:    {}", format_expiry(tokens.expires_at));
+
+    if ctx.common.verbose > 0 {
+        println!();
+        for (audience, status, expires_at) in &audiences {
+            println!(
+                "  {:<9} {:<10} {:<31} {}",
+                audience.as_str(),
+                status,
+                audience.resource(),
+                expires_at.map_or_else(|| "-".to_string(), format_expiry)
+            );
         }
-        let client = TeamsClient::new()?;
-        let file_name = file_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("file");
-        eprint!("Uploading {file_name}... ");
-        client.send_file(&conv_id, file_path).await?;
-        eprintln!("done.");
-
-        // Also send text message if provided
-        if let Some(ref msg_text) = message {
-            client.send_message(&conv_id, msg_text).await?;
-        }
-        println!("Sent.");
-        return Ok(());
+        println!();
+        println!("Refresh took {:.1}s", elapsed.as_secs_f64());
     }
+    Ok(())
+}
 
-    if let Some(msg_text) = message {
-        // Send a text message
-        let client = TeamsClient::new()?;
-        client.send_message(&conv_id, &msg_text).await?;
-        println!("Sent.");
+fn auth_import_desktop(auth: &AuthManager, paths: &[PathBuf]) -> Result<()> {
+    let tokens = auth.import_from_teams_desktop(paths)?;
+    let _ = tmz_core::daemon::clear_reauth_needed();
+    let found: Vec<&str> = TokenAudience::ALL
+        .iter()
+        .filter(|a| !tokens.token_for(**a).is_empty())
+        .map(|a| a.as_str())
+        .collect();
+    println!("Imported tokens for: {}", tokens.user_principal_name);
+    println!("Audiences:           {}", found.join(", "));
+    println!("Token expires:       {}", format_expiry(tokens.expires_at));
+    Ok(())
+}
+
+/// `tmz auth export`: encrypt the stored tokens into `file`.
+fn auth_export(auth: &AuthManager, file: &Path) -> Result<()> {
+    let tokens = auth.stored_tokens()?;
+    let passphrase = token_passphrase(true)?;
+    let sealed = auth.export_tokens(&passphrase)?;
+    if file == Path::new("-") {
+        print!("{sealed}");
         return Ok(());
     }
+    tmz_core::secrets::write_private(file, sealed.as_bytes())
+        .with_context(|| format!("writing {}", file.display()))?;
+    println!(
+        "Exported tokens for {} to {}",
+        tokens.user_principal_name,
+        file.display()
+    );
+    println!("Import them elsewhere with: tmz auth import --file <FILE>");
+    if tokens.refresh_token.is_empty() {
+        println!(
+            "Note: no refresh token is stored, so the imported tokens stop working when they expire ({}).",
+            format_expiry(tokens.expires_at)
+        );
+    }
+    Ok(())
+}
 
-    // Show recent messages (prefer cache, fall back to API)
-    let messages = db.get_messages(&conv_id, limit).await?;
-
-    // Fetch live if no cached messages
-    let messages = if messages.is_empty() {
-        eprintln!("No cached messages. Fetching from API...");
-        let client = TeamsClient::new()?;
-        let limit_i32 = i32::try_from(limit).unwrap_or(20);
-        let data = client.get_chat_messages(&conv_id, Some(limit_i32)).await?;
-        if ctx.common.json {
-            println!("{}", serde_json::to_string_pretty(&data)?);
-            return Ok(());
-        }
-        if let Some(msgs) = data["messages"].as_array() {
-            let my_name = client.get_me().await.ok().and_then(|u| {
-                u["displayName"]
-                    .as_str()
-                    .map(std::string::ToString::to_string)
-            });
-            msgs.iter()
-                .filter_map(|m| cache::parse_message(m, &conv_id, my_name.as_deref()))
-                .collect()
-        } else {
-            Vec::new()
-        }
+/// `tmz auth import --file`: decrypt and store tokens from `tmz auth export`.
+fn auth_import_file(auth: &AuthManager, file: &Path) -> Result<()> {
+    let data = if file == Path::new("-") {
+        let mut data = Vec::new();
+        io::Read::read_to_end(&mut io::stdin(), &mut data)?;
+        data
     } else {
-        messages
+        std::fs::read(file).with_context(|| format!("reading {}", file.display()))?
     };
+    let passphrase = token_passphrase(false)?;
+    let tokens = auth.import_tokens(&data, &passphrase)?;
+    let _ = tmz_core::daemon::clear_reauth_needed();
+    println!("Imported tokens for: {}", tokens.user_principal_name);
+    println!("Token expires:       {}", format_expiry(tokens.expires_at));
+    if tokens.refresh_token.is_empty() {
+        println!(
+            "Refresh:             none (log in again where the tokens came from and re-export once they expire)"
+        );
+    }
+    Ok(())
+}
 
-    if ctx.common.json {
-        println!("{}", serde_json::to_string_pretty(&messages)?);
-        return Ok(());
+/// Passphrase for exported tokens: `TMZ_TOKEN_PASSPHRASE`, or asked for on
+/// the terminal (twice with `confirm`).
+fn token_passphrase(confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var("TMZ_TOKEN_PASSPHRASE") {
+        return Ok(passphrase);
     }
+    let passphrase = rpassword::prompt_password("Passphrase: ").context("reading passphrase")?;
+    if passphrase.is_empty() {
+        return Err(anyhow!("the passphrase must not be empty"));
+    }
+    if confirm {
+        let again =
+            rpassword::prompt_password("Repeat passphrase: ").context("reading passphrase")?;
+        if again != passphrase {
+            return Err(anyhow!("the passphrases do not match"));
+        }
+    }
+    Ok(passphrase)
+}
 
-    // Print header
-    let convs = db.find_conversation(&conv_id).await?;
-    if let Some(conv) = convs.first() {
-        println!("\x1b[1m{}\x1b[0m", conv.display_name);
+/// Log in with the device code flow: in the local browser, or with
+/// `remote` on another device through a QR code.
+async fn auth_login_device(auth: &AuthManager, remote: bool) -> Result<()> {
+    let code = auth.start_device_login().await?;
+
+    if remote {
+        println!("Scan the QR code or open the URL on your phone or another device:");
         println!();
+        match qrcode::QrCode::new(code.verification_uri.as_bytes()) {
+            Ok(qr) => {
+                let rendered = qr
+                    .render::<qrcode::render::unicode::Dense1x2>()
+                    .quiet_zone(true)
+                    .build();
+                println!("{rendered}");
+            }
+            Err(e) => debug!("QR rendering failed: {e}"),
+        }
+    } else {
+        println!("Opening the sign-in page in your browser. Enter the code below there.");
+        if let Err(e) = open::that_detached(&code.verification_uri) {
+            debug!("opening browser failed: {e}");
+            println!("Could not open a browser; open the URL yourself.");
+        }
     }
+    println!();
+    println!("  URL:  {}", code.verification_uri);
+    println!("  Code: {}", code.user_code);
+    println!();
+    println!(
+        "Waiting for login to complete (code expires in {}m)...",
+        code.expires_in / 60
+    );
 
-    let show_images = !no_images && tmz_core::kitty::is_supported();
-
-    render_messages(&messages, &db, show_images).await
+    let tokens = auth.complete_device_login(&code).await?;
+    let _ = tmz_core::daemon::clear_reauth_needed();
+    println!("Authenticated as: {}", tokens.user_principal_name);
+    println!("Tenant: {}", tokens.tenant_id);
+    Ok(())
 }
 
-/// Render message groups with optional inline images.
-async fn render_messages(
-    messages: &[tmz_core::CachedMessage],
-    db: &tmz_core::Cache,
-    show_images: bool,
+async fn auth_reset_browser(
+    ctx: &RuntimeContext,
+    auth: &AuthManager,
+    no_login: bool,
+    timeout: u64,
 ) -> Result<()> {
-    let groups = group_messages(messages);
-    let mut prev_group: Option<&MessageGroup<'_>> = None;
-
-    for group in &groups {
-        print_bubble(group, prev_group);
+    let dir = auth.browser_profile_dir();
+    if ctx.common.dry_run {
+        let size = tmz_core::paths::disk_usage(dir);
+        println!(
+            "dry-run: would remove browser profile {} ({})",
+            dir.display(),
+            format_bytes(i64::try_from(size).unwrap_or(i64::MAX))
+        );
+        return Ok(());
+    }
 
-        if show_images {
-            render_message_images(&group.messages, db).await;
-        }
+    let freed = auth.reset_browser_profile()?;
+    println!(
+        "Removed browser profile {} ({})",
+        dir.display(),
+        format_bytes(i64::try_from(freed).unwrap_or(i64::MAX))
+    );
 
-        prev_group = Some(group);
+    if no_login {
+        println!("Run 'tmz auth login' to re-seed the SSO session.");
+        return Ok(());
     }
 
+    println!("Re-seeding SSO session...");
+    let tokens = auth.browser_login(Some(timeout), false, false).await?;
+    let _ = tmz_core::daemon::clear_reauth_needed();
+    println!("Authenticated as: {}", tokens.user_principal_name);
     Ok(())
 }
 
-/// Render inline images for a set of messages.
-async fn render_message_images(messages: &[&tmz_core::CachedMessage], db: &tmz_core::Cache) {
-    for msg in messages {
-        let urls = tmz_core::kitty::extract_image_urls(&msg.content_html);
-        for url in &urls {
-            let image_data = if let Ok(Some(data)) = db.get_image(url).await {
-                Some(data)
-            } else if let Ok(client) = TeamsClient::new() {
-                match client.download_image(url).await {
-                    Ok(data) => {
-                        let _ = db.cache_image(url, &data, "image/png").await;
-                        Some(data)
-                    }
-                    Err(e) => {
-                        debug!("image download failed: {e}");
+/// Refresh tokens that would expire while the command runs, or warn when
+/// that is not possible. A failed refresh is not an error: the tokens are
+/// still valid and the client refreshes again when they run out.
+async fn refresh_ahead(ctx: &RuntimeContext) {
+    let Ok(auth) = AuthManager::new() else {
+        return;
+    };
+    let Some(left) = auth.expiring_within(tmz_core::teams::auth::REFRESH_AHEAD_SECS) else {
+        return;
+    };
+    let minutes = (left + 59) / 60;
+    if ctx.common.dry_run {
+        if !ctx.common.quiet {
+            eprintln!(
+                "{}",
+                dim(&format!("tokens expire in {minutes}m; refresh may trigger"))
+            );
+        }
+        return;
+    }
+
+    if !ctx.common.quiet {
+        eprintln!(
+            "{}",
+            dim(&format!("tokens expire in {minutes}m; refreshing..."))
+        );
+    }
+    let refreshed = auth.refresh_tokens().await;
+    ctx.audit().record("auth.refresh", None, &refreshed);
+    match refreshed {
+        Ok(_) => {
+            let _ = tmz_core::daemon::clear_reauth_needed();
+        }
+        Err(e) => {
+            debug!("refresh ahead of expiry failed: {e}");
+            if !ctx.common.quiet {
+                eprintln!(
+                    "{}",
+                    dim(&format!(
+                        "refresh failed; tokens expire in {minutes}m and may run out mid-command. Run `tmz auth refresh` to retry."
+                    ))
+                );
+            }
+        }
+    }
+}
+
+/// Format an epoch expiry as local time plus remaining minutes.
+fn format_expiry(expires_at: i64) -> String {
+    let now = chrono::Utc::now().timestamp();
+    let local = chrono::DateTime::from_timestamp(expires_at, 0).map_or_else(
+        || expires_at.to_string(),
+        |dt| {
+            dt.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        },
+    );
+    let remaining = (expires_at - now) / 60;
+    if remaining > 0 {
+        format!("{local} ({remaining}m remaining)")
+    } else {
+        format!("{local} (expired)")
+    }
+}
+
+async fn handle_sync(ctx: &RuntimeContext, cmd: SyncCommand) -> Result<()> {
+    let opts = SyncOptions {
+        full: cmd.full,
+        chats: cmd.messages as i64,
+        per_chat: cmd.per_chat,
+        images: true,
+        lite: cmd.lite,
+    };
+
+    // Let the daemon do the work when it is running, so only one process
+    // talks to Teams and writes the cache.
+    let report = if let Some(mut engine) = EngineClient::connect().await {
+        eprint!("Syncing via daemon... ");
+        let report = tokio::select! {
+            report = engine.sync(opts) => report?,
+            _ = tokio::signal::ctrl_c() => {
+                eprintln!("\nInterrupted; the daemon finishes the sync in the background.");
+                return Err(anyhow!("sync interrupted"));
+            }
+        };
+        eprintln!(
+            "{} conversations, {} messages across {} chats.",
+            report.conversations, report.messages, report.chats
+        );
+        if report.resumed > 0 {
+            eprintln!(
+                "Resumed an interrupted sync; {} conversations were already done.",
+                report.resumed
+            );
+        }
+        report
+    } else {
+        let engine = Engine::new(ctx.open_cache().await?)
+            .with_message_types(ctx.config.messages.clone())
+            .with_ocr(ctx.config.ocr.clone())
+            .with_files(ctx.config.files.clone())
+            .with_retention(tmz_core::retention::RetentionPolicy::new(&ctx.config))
+            .with_lite(ctx.config.sync.lite)
+            .with_hooks(ctx.config.hooks.clone())
+            .with_notifications(ctx.config.notifications.clone())
+            .with_audit(ctx.audit());
+        eprint!("Syncing conversations... ");
+        let caching_images = AtomicBool::new(false);
+        let mut on_progress = |progress: SyncProgress| {
+            if matches!(progress, SyncProgress::Image { .. }) {
+                caching_images.store(true, Ordering::Relaxed);
+            }
+            print_sync_progress(progress);
+        };
+        let report = tokio::select! {
+            report = engine.sync_with_progress(opts, &mut on_progress) => report?,
+            _ = tokio::signal::ctrl_c() => {
+                let images = caching_images.load(Ordering::Relaxed);
+                return Err(sync_interrupted(engine.cache(), images).await);
+            }
+        };
+        if opts.full || opts.chats > 0 {
+            eprintln!(
+                "\r{} messages across {} conversations.{:>40}",
+                report.messages, report.chats, ""
+            );
+        }
+        report
+    };
+    if report.images > 0 {
+        eprintln!("\r{} images cached.{:>40}", report.images, "");
+    }
+    if report.images_ocr > 0 {
+        eprintln!("{} images indexed for search (OCR).", report.images_ocr);
+    }
+    if report.files_indexed > 0 {
+        eprintln!("{} shared files indexed for search.", report.files_indexed);
+    }
+    if report.pruned > 0 {
+        eprintln!("{} old messages pruned by [retention].", report.pruned);
+    }
+    if report.hooks > 0 {
+        eprintln!("{} new messages posted to [hooks] webhooks.", report.hooks);
+    }
+    if report.notified > 0 {
+        eprintln!("{} notifications shown for new messages.", report.notified);
+    }
+
+    let db = ctx.open_cache_read_only().await?;
+    let stats = db.stats().await?;
+    println!(
+        "Cache: {} conversations, {} messages, {} images ({}).",
+        stats.conversations,
+        stats.messages,
+        stats.images,
+        format_bytes(stats.image_bytes),
+    );
+
+    Ok(())
+}
+
+/// Report what an interrupted sync saved. Finished conversations are
+/// checkpointed, so running the same sync again resumes after them.
+async fn sync_interrupted(cache: &tmz_core::Cache, caching_images: bool) -> anyhow::Error {
+    eprintln!();
+    if caching_images {
+        eprintln!("Interrupted while caching images; all messages were saved.");
+    } else {
+        match cache.sync_checkpoint_totals().await {
+            Ok((0, _)) | Err(_) => eprintln!("Interrupted before any conversation finished."),
+            Ok((chats, messages)) => eprintln!(
+                "Interrupted: {chats} conversations ({messages} messages) saved. \
+                 Run the same sync within an hour to resume."
+            ),
+        }
+    }
+    anyhow!("sync interrupted")
+}
+
+fn print_sync_progress(progress: SyncProgress) {
+    match progress {
+        SyncProgress::Conversations(n) => eprintln!("{n} conversations."),
+        SyncProgress::Resuming { done } => {
+            eprintln!("Resuming an interrupted sync; {done} conversations already done.");
+        }
+        SyncProgress::Chat { index, total, name } => {
+            let short_name: String = name.chars().take(40).collect();
+            eprint!("\rSyncing messages [{index}/{total}] {short_name:<40}");
+        }
+        SyncProgress::Image { index, total } => {
+            eprint!("\rCaching images [{index}/{total}]...{:>40}", "");
+        }
+    }
+}
+
+async fn handle_chats(ctx: &RuntimeContext, cmd: ChatsCommand) -> Result<()> {
+    match cmd.command {
+        Some(ChatsSubcommand::Hide { target, remote }) => {
+            return handle_chat_visibility(ctx, &target, true, remote).await;
+        }
+        Some(ChatsSubcommand::Unhide { target, remote }) => {
+            return handle_chat_visibility(ctx, &target, false, remote).await;
+        }
+        Some(ChatsSubcommand::Hidden) => return handle_hidden_chats(ctx).await,
+        Some(ChatsSubcommand::Tag { target, tags }) => {
+            return handle_chat_tags(ctx, &target, &tags, true).await;
+        }
+        Some(ChatsSubcommand::Untag { target, tags }) => {
+            return handle_chat_tags(ctx, &target, &tags, false).await;
+        }
+        Some(ChatsSubcommand::Tags) => return handle_list_tags(ctx).await,
+        Some(ChatsSubcommand::Members { target, history }) => {
+            return handle_chat_members(ctx, &target, history).await;
+        }
+        None => {}
+    }
+
+    let db = ctx.open_cache_read_only().await?;
+    let convs = match cmd.tag.as_deref() {
+        Some(tag) => {
+            let tagged = tagged_conversations(ctx, &db, tag).await?;
+            db.list_conversations_with_hidden(i64::MAX, cmd.all)
+                .await?
+                .into_iter()
+                .filter(|c| tagged.contains(&c.id))
+                .take(usize::try_from(cmd.limit).unwrap_or(0))
+                .collect()
+        }
+        None => {
+            db.list_conversations_with_hidden(cmd.limit, cmd.all)
+                .await?
+        }
+    };
+
+    if cmd.plumbing {
+        print_plumbing(&convs);
+        return Ok(());
+    }
+
+    if convs.is_empty() {
+        match cmd.tag {
+            Some(tag) => println!("No conversations tagged '{tag}'."),
+            None => println!("No conversations cached. Run 'tmz sync' first."),
+        }
+        return Ok(());
+    }
+
+    match ctx.output_format() {
+        // `--json` keeps the raw Teams objects.
+        Some(OutputFormat::Json) => {
+            let json: Vec<serde_json::Value> = convs
+                .iter()
+                .filter_map(|c| serde_json::from_str(&c.raw_json).ok())
+                .collect();
+            return print_structured(&serde_json::Value::from(json), OutputFormat::Json);
+        }
+        Some(format) => return print_structured(&conversation_records(&convs), format),
+        None => {}
+    }
+
+    print_conversation_list(&convs);
+    Ok(())
+}
+
+async fn handle_stats(ctx: &RuntimeContext, cmd: &StatsCommand) -> Result<()> {
+    let since = match cmd {
+        StatsCommand::Time { since } => since,
+        StatsCommand::Person { name } => return stats_person(ctx, name).await,
+    };
+    let since = since
+        .as_deref()
+        .map(|s| tmz_core::dates::parse_point_in_time(s, chrono::Local::now()))
+        .transpose()?
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+    let db = ctx.open_cache_read_only().await?;
+    let totals = db.time_totals(since.as_deref()).await?;
+    if let Some(format) = ctx.output_format() {
+        return print_structured(&serde_json::to_value(&totals)?, format);
+    }
+    if totals.is_empty() {
+        if ctx.config.time_tracking.enabled {
+            println!("No time recorded yet.");
+        } else {
+            println!(
+                "No time recorded. Set [time_tracking] enabled = true to track time in tmz-tui."
+            );
+        }
+        return Ok(());
+    }
+    for total in &totals {
+        let name = if total.name.is_empty() {
+            total.conversation_id.as_str()
+        } else {
+            total.name.as_str()
+        };
+        println!(
+            "  {:>8}  {:>4} sessions  {name}",
+            format_seconds(total.seconds),
+            total.sessions
+        );
+    }
+    let sum: i64 = totals.iter().map(|t| t.seconds).sum();
+    println!("  {:>8}  total", format_seconds(sum));
+    Ok(())
+}
+
+/// `tmz script`.
+async fn handle_script(ctx: &RuntimeContext, command: ScriptCommand) -> Result<()> {
+    let config_dir = ctx
+        .paths
+        .config_file
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    let path = scripting::script_path(&ctx.config.scripting, config_dir);
+    let scripts = Scripts::load(&path, &ctx.config)?
+        .ok_or_else(|| {
+            anyhow!(
+                "no script at {}: define actions there as 'fn name(ctx) {{ ... }}'",
+                path.display()
+            )
+        })?
+        .with_audit(ctx.audit());
+
+    let (action, chat, message, args) = match command {
+        ScriptCommand::Check => {
+            println!(
+                "{}: {} action(s), no errors.",
+                path.display(),
+                scripts.actions().len()
+            );
+            return Ok(());
+        }
+        ScriptCommand::List => {
+            let actions = scripts.actions();
+            if let Some(format) = ctx.output_format() {
+                return print_structured(&serde_json::to_value(&actions)?, format);
+            }
+            if actions.is_empty() {
+                println!("{} defines no actions.", path.display());
+            }
+            for action in &actions {
+                println!("  {:<20}  {}", action.name, dim(&action.description));
+            }
+            return Ok(());
+        }
+        ScriptCommand::Run {
+            action,
+            chat,
+            message,
+            args,
+        } => (action, chat, message, args),
+    };
+
+    if ctx.common.dry_run {
+        println!("dry-run: would run {action}");
+        return Ok(());
+    }
+    let db = ctx.open_cache_read_only().await?;
+    let (chat, chat_name) = match chat {
+        Some(target) => {
+            let conv_id = ctx.resolve_target(&db, &target).await?;
+            let name = db
+                .find_conversation(&conv_id)
+                .await?
+                .first()
+                .map(|c| c.display_name.clone());
+            (Some(conv_id), name)
+        }
+        None => (None, None),
+    };
+    let ctx_info = ActionContext {
+        chat,
+        chat_name,
+        message,
+        args,
+    };
+    for line in scripts.run(&db, &action, ctx_info).await? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// `1h 05m` / `12m` for a number of seconds.
+/// `tmz stats person`: activity heatmap and response time of a contact.
+async fn stats_person(ctx: &RuntimeContext, name: &str) -> Result<()> {
+    let db = ctx.open_cache_read_only().await?;
+    let sender = resolve_sender(ctx, &db, name).await?;
+    let messages = db.chats_with_sender(&sender).await?;
+    let activity = insights::contact_activity(&sender, &messages, &chrono::Local);
+    let median = activity.median_response();
+
+    if let Some(format) = ctx.output_format() {
+        let mut value = serde_json::to_value(&activity)?;
+        value["median_response_secs"] = median.into();
+        return print_structured(&value, format);
+    }
+
+    println!(
+        "{sender}: {} messages in the cache, by weekday and hour (local time)\n",
+        activity.messages
+    );
+    for line in heatmap_lines(&activity.heatmap, ctx.stdout_color()) {
+        println!("{line}");
+    }
+    println!();
+    match median {
+        Some(secs) => println!(
+            "  Median response to you: {} ({} replies)",
+            format_latency(secs),
+            activity.response_secs.len()
+        ),
+        None => println!("  Median response to you: no replies to your messages cached"),
+    }
+    let peaks = activity
+        .peak_hours(3)
+        .iter()
+        .map(|&(day, hour, _)| format!("{} {hour:02}:00", insights::WEEKDAYS[day]))
+        .collect::<Vec<_>>();
+    if !peaks.is_empty() {
+        println!("  Most active:            {}", peaks.join(", "));
+    }
+    Ok(())
+}
+
+/// The cached sender `name` refers to: a display name containing it (an
+/// exact match wins), or the other person in the chat it is an alias of.
+async fn resolve_sender(ctx: &RuntimeContext, db: &Cache, name: &str) -> Result<String> {
+    let mut senders = db.senders_matching(name, None).await?;
+    if let Some(exact) = senders.iter().find(|(s, _)| s.eq_ignore_ascii_case(name)) {
+        return Ok(exact.0.clone());
+    }
+    if senders.is_empty()
+        && ctx.config.resolve_alias(name).is_some()
+        && let Ok(conv_id) = ctx.resolve_target(db, name).await
+    {
+        senders = db.senders_matching("", Some(&conv_id)).await?;
+        senders.truncate(1);
+    }
+    match senders.as_slice() {
+        [] => Err(anyhow!(
+            "nobody named '{name}' in the cached messages (try 'tmz sync')"
+        )),
+        [(sender, _)] => Ok(sender.clone()),
+        many => {
+            eprintln!("'{name}' matches several people:");
+            for (sender, count) in many.iter().take(10) {
+                eprintln!("  {sender} {}", dim(&format!("({count} messages)")));
+            }
+            Err(anyhow!("ambiguous name; give more of it"))
+        }
+    }
+}
+
+/// A 7x24 heatmap of message counts, Monday first, shaded relative to the
+/// busiest hour.
+fn heatmap_lines(heatmap: &[[u32; 24]; 7], color: bool) -> Vec<String> {
+    const SHADES: [&str; 5] = [
+        "\u{b7} ",
+        "\u{2591}\u{2591}",
+        "\u{2592}\u{2592}",
+        "\u{2593}\u{2593}",
+        "\u{2588}\u{2588}",
+    ];
+    let max = heatmap.iter().flatten().copied().max().unwrap_or(0).max(1);
+    let header = (0..24)
+        .step_by(3)
+        .fold("      ".to_string(), |mut out, hour| {
+            use std::fmt::Write as _;
+            let _ = write!(out, "{hour:02}    ");
+            out
+        });
+    let mut lines = vec![header.trim_end().to_string()];
+    for (day, hours) in heatmap.iter().enumerate() {
+        let cells = hours.iter().fold(String::new(), |mut out, &count| {
+            let level = (count * 4).div_ceil(max) as usize;
+            match (color, level) {
+                (true, 0) => out.push_str(&dim(SHADES[0])),
+                (true, _) => {
+                    out.push_str("\x1b[32m");
+                    out.push_str(SHADES[level]);
+                    out.push_str("\x1b[0m");
+                }
+                (false, _) => out.push_str(SHADES[level]),
+            }
+            out
+        });
+        let line = format!("  {} {cells}", insights::WEEKDAYS[day]);
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
+
+/// `45s`, `12m`, `3h 05m`.
+fn format_latency(seconds: i64) -> String {
+    if seconds < 60 {
+        format!("{seconds}s")
+    } else {
+        format_seconds(seconds)
+    }
+}
+
+fn format_seconds(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+/// `tmz chats hide` / `tmz chats unhide`.
+async fn handle_chat_visibility(
+    ctx: &RuntimeContext,
+    target: &str,
+    hide: bool,
+    remote: bool,
+) -> Result<()> {
+    let db = ctx.open_cache().await?;
+    let conv_id = ctx.resolve_target(&db, target).await?;
+    let name = db
+        .find_conversation(&conv_id)
+        .await?
+        .first()
+        .map_or_else(|| conv_id.clone(), |c| c.display_name.clone());
+
+    if ctx.common.dry_run {
+        let action = if hide { "hide" } else { "unhide" };
+        println!("dry-run: would {action} {name}");
+        return Ok(());
+    }
+
+    let changed = if hide {
+        db.hide_conversation(&conv_id).await?
+    } else {
+        db.unhide_conversation(&conv_id).await?
+    };
+    if remote {
+        TeamsClient::new()?
+            .set_conversation_hidden(&conv_id, hide)
+            .await
+            .context("updating Teams")?;
+    }
+
+    match (hide, changed) {
+        (true, true) => println!("Hid {name}."),
+        (true, false) => println!("{name} was already hidden."),
+        (false, true) => println!("Unhid {name}."),
+        (false, false) => println!("{name} was not hidden."),
+    }
+    Ok(())
+}
+
+/// `tmz chats tag` / `tmz chats untag`.
+async fn handle_chat_tags(
+    ctx: &RuntimeContext,
+    target: &str,
+    tags: &[String],
+    add: bool,
+) -> Result<()> {
+    let tags = tags
+        .iter()
+        .map(|tag| {
+            cache::normalize_tag(tag)
+                .ok_or_else(|| anyhow!("invalid tag '{tag}': use a single word such as project-x"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let db = ctx.open_cache().await?;
+    let conv_id = ctx.resolve_target(&db, target).await?;
+    let name = db
+        .find_conversation(&conv_id)
+        .await?
+        .first()
+        .map_or_else(|| conv_id.clone(), |c| c.display_name.clone());
+
+    for tag in &tags {
+        if ctx.common.dry_run {
+            let action = if add { "tag" } else { "untag" };
+            println!("dry-run: would {action} {name} with '{tag}'");
+            continue;
+        }
+        let changed = if add {
+            db.tag_conversation(&conv_id, tag).await?
+        } else {
+            db.untag_conversation(&conv_id, tag).await?
+        };
+        match (add, changed) {
+            (true, true) => println!("Tagged {name} with '{tag}'."),
+            (true, false) => println!("{name} was already tagged '{tag}'."),
+            (false, true) => println!("Removed '{tag}' from {name}."),
+            (false, false) => println!("{name} was not tagged '{tag}'."),
+        }
+    }
+    Ok(())
+}
+
+/// `tmz chats tags`.
+async fn handle_list_tags(ctx: &RuntimeContext) -> Result<()> {
+    let db = ctx.open_cache_read_only().await?;
+    let mut by_tag: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (conv_id, tags) in db.conversation_tags(&ctx.config.shared_tags()).await? {
+        for tag in tags {
+            by_tag.entry(tag).or_default().push(conv_id.clone());
+        }
+    }
+    let names: HashMap<String, String> = db
+        .list_conversations_with_hidden(i64::MAX, true)
+        .await?
+        .into_iter()
+        .map(|c| (c.id, c.display_name))
+        .collect();
+
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&by_tag)?);
+        return Ok(());
+    }
+    if by_tag.is_empty() {
+        println!("No tags yet. Add one with 'tmz chats tag <target> <tag>'.");
+        return Ok(());
+    }
+    for (tag, ids) in &by_tag {
+        println!("\x1b[1m#{tag}\x1b[0m");
+        for id in ids {
+            match names.get(id).filter(|name| !name.is_empty()) {
+                Some(name) => println!("  {name}  {}", dim(id)),
+                None => println!("  {id}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// IDs of the conversations tagged `tag`, in the cache or in `[tags]`.
+async fn tagged_conversations(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    tag: &str,
+) -> Result<HashSet<String>> {
+    if cache::normalize_tag(tag).is_none() {
+        return Err(anyhow!(
+            "invalid tag '{tag}': use a single word such as project-x"
+        ));
+    }
+    Ok(db
+        .conversations_tagged(tag, &ctx.config.shared_tags())
+        .await?)
+}
+
+/// `tmz chats hidden`.
+async fn handle_hidden_chats(ctx: &RuntimeContext) -> Result<()> {
+    let db = ctx.open_cache_read_only().await?;
+    let hidden = db.list_hidden_conversations().await?;
+
+    if ctx.common.json {
+        let json: Vec<serde_json::Value> = hidden
+            .iter()
+            .map(|(c, hidden_at)| {
+                serde_json::json!({
+                    "id": c.id,
+                    "display_name": c.display_name,
+                    "hidden_at": hidden_at,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+
+    if hidden.is_empty() {
+        println!("No hidden conversations.");
+        return Ok(());
+    }
+    let convs: Vec<CachedConversation> = hidden.into_iter().map(|(c, _)| c).collect();
+    print_conversation_list(&convs);
+    println!("\nUnhide with 'tmz chats unhide <target>'.");
+    Ok(())
+}
+
+/// `tmz chats members`: members known from join/leave events and senders,
+/// or with `history` the events themselves.
+async fn handle_chat_members(ctx: &RuntimeContext, target: &str, history: bool) -> Result<()> {
+    let db = ctx.open_cache_read_only().await?;
+    let conv_id = ctx.resolve_target(&db, target).await?;
+    let conv = db.find_conversation(&conv_id).await?.into_iter().next();
+    let name = conv
+        .as_ref()
+        .map_or_else(|| conv_id.clone(), |c| c.display_name.clone());
+    let events = db.membership_history(&conv_id).await?;
+
+    if history {
+        if ctx.common.json {
+            println!("{}", serde_json::to_string_pretty(&events)?);
+        } else if events.is_empty() {
+            println!(
+                "No joins or departures cached for {name}. Sync older messages with 'tmz msg {target} --sync -n 500'."
+            );
+        } else {
+            for change in &events {
+                print_membership_change(change);
+            }
+        }
+        return Ok(());
+    }
+
+    // Members known from events, then senders without a departure.
+    let present = tmz_core::cache::present_members(&events);
+    let mut members: Vec<(String, String)> = present
+        .iter()
+        .map(|c| {
+            (
+                c.name().to_string(),
+                format!("joined {}", format_time(&c.at)),
+            )
+        })
+        .collect();
+    let departed: Vec<String> = events
+        .iter()
+        .filter(|c| !present.iter().any(|p| p.member_id == c.member_id))
+        .map(|c| c.name().to_lowercase())
+        .collect();
+    for sender in conv
+        .as_ref()
+        .map(|c| c.member_names.as_str())
+        .unwrap_or_default()
+        .split(", ")
+        .filter(|n| !n.is_empty())
+    {
+        let lower = sender.to_lowercase();
+        if !members.iter().any(|(n, _)| n.to_lowercase() == lower) && !departed.contains(&lower) {
+            members.push((sender.to_string(), "sent messages".to_string()));
+        }
+    }
+
+    if ctx.common.json {
+        let json: Vec<serde_json::Value> = members
+            .iter()
+            .map(|(name, source)| serde_json::json!({ "name": name, "source": source }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+    if members.is_empty() {
+        println!("No members known for {name} yet. Run 'tmz msg {target} --sync'.");
+        return Ok(());
+    }
+    println!("{name}: {} members known", members.len());
+    for (member, source) in &members {
+        println!("  {member}  {}", dim(source));
+    }
+    if !departed.is_empty() {
+        println!("{}", dim("See who left with --history."));
+    }
+    Ok(())
+}
+
+fn print_membership_change(change: &tmz_core::cache::MembershipChange) {
+    let (sign, verb) = if change.joined {
+        ("\x1b[32m+\x1b[0m", "added by")
+    } else {
+        ("\x1b[31m-\x1b[0m", "removed by")
+    };
+    let how = change.actor.as_ref().map_or_else(
+        || if change.joined { "joined" } else { "left" }.to_string(),
+        |actor| format!("{verb} {actor}"),
+    );
+    println!(
+        "{}  {sign} {}  {}",
+        dim(&format!(
+            "{} {}",
+            extract_date(&change.at),
+            format_time_short(&change.at)
+        )),
+        change.name(),
+        dim(&how)
+    );
+}
+
+fn handle_audit(ctx: &RuntimeContext, cmd: &AuditCommand) -> Result<()> {
+    let audit = ctx.audit();
+    match cmd {
+        AuditCommand::Show {
+            since,
+            action,
+            limit,
+        } => {
+            let since = since
+                .as_deref()
+                .map(|s| tmz_core::dates::parse_point_in_time(s, chrono::Local::now()))
+                .transpose()?;
+            let mut records = audit.read(since)?;
+            if let Some(action) = action {
+                records.retain(|r| r.action.starts_with(action.as_str()));
+            }
+            if let Some(limit) = limit {
+                records.drain(..records.len().saturating_sub(*limit));
+            }
+            if ctx.common.json {
+                println!("{}", serde_json::to_string_pretty(&records)?);
+                return Ok(());
+            }
+            if records.is_empty() {
+                println!("No audited actions.");
+                return Ok(());
+            }
+            for r in &records {
+                let outcome = if r.outcome == "ok" {
+                    "\x1b[32mok\x1b[0m".to_string()
+                } else {
+                    format!("\x1b[31m{}\x1b[0m", r.outcome)
+                };
+                println!(
+                    "  {}  {:<6} {:<18} {outcome}  {}",
+                    format_time(&r.ts),
+                    r.source,
+                    r.action,
+                    r.target.as_deref().unwrap_or("-")
+                );
+                if let Some(ref detail) = r.detail {
+                    println!("        {detail}");
+                }
+            }
+        }
+        AuditCommand::Verify => {
+            let problems = audit.verify()?;
+            if !problems.is_empty() {
+                for p in &problems {
+                    println!("  {}:{}: {}", p.file.display(), p.line, p.reason);
+                }
+                return Err(anyhow!(
+                    "{} audit record(s) failed verification",
+                    problems.len()
+                ));
+            }
+            println!("All audit records verified.");
+        }
+    }
+    Ok(())
+}
+
+async fn handle_api(ctx: &RuntimeContext, command: ApiCommand) -> Result<()> {
+    let ApiCommand::Token { command } = command;
+    let store = TokenStore::new(&ctx.paths.state_dir);
+    match command {
+        ApiTokenCommand::Create {
+            send_only,
+            target,
+            name,
+        } => {
+            let mut targets = Vec::with_capacity(target.len());
+            if !target.is_empty() {
+                let db = ctx.open_cache_read_only().await?;
+                for t in &target {
+                    targets.push(ctx.resolve_target(&db, t).await?);
+                }
+            }
+            let scope = Scope {
+                send_only: send_only || !targets.is_empty(),
+                targets,
+            };
+            let created = store.create(name.as_deref(), scope);
+            ctx.audit().record(
+                "api.token.create",
+                created.as_ref().ok().map(|(entry, _)| entry.id.as_str()),
+                &created,
+            );
+            let (entry, secret) = created?;
+            if ctx.common.json {
+                let out = serde_json::json!({ "token": secret, "entry": entry });
+                println!("{}", serde_json::to_string_pretty(&out)?);
+                return Ok(());
+            }
+            println!("{secret}");
+            eprintln!(
+                "Created token {} ({}). It is not shown again; revoke it with 'tmz api token revoke {}'.",
+                entry.id,
+                entry.scope.describe(),
+                entry.id
+            );
+        }
+        ApiTokenCommand::List => {
+            let tokens = store.list()?;
+            if ctx.common.json {
+                println!("{}", serde_json::to_string_pretty(&tokens)?);
+                return Ok(());
+            }
+            if tokens.is_empty() {
+                println!("No scoped API tokens.");
+                return Ok(());
+            }
+            for token in &tokens {
+                println!(
+                    "  {}  {}  {:<20} {}",
+                    token.id,
+                    format_time(&token.created_at),
+                    token.name.as_deref().unwrap_or("-"),
+                    token.scope.describe()
+                );
+                for target in &token.scope.targets {
+                    println!("        -> {target}");
+                }
+            }
+        }
+        ApiTokenCommand::Revoke { id } => {
+            let revoked = store.revoke(&id);
+            ctx.audit().record("api.token.revoke", Some(&id), &revoked);
+            if !revoked? {
+                return Err(anyhow!("no API token with ID '{id}'"));
+            }
+            println!("Revoked token {id}.");
+        }
+    }
+    Ok(())
+}
+
+async fn handle_people(ctx: &RuntimeContext, command: PeopleCommand) -> Result<()> {
+    match command {
+        PeopleCommand::Show { person } => show_person(ctx, &person).await,
+        PeopleCommand::Manager { person, chain } => show_org(ctx, &person, Some(chain)).await,
+        PeopleCommand::Reports { person } => show_org(ctx, &person, None).await,
+    }
+}
+
+/// Find the one person `query` names: a 1:1 chat or alias first, then
+/// `me`, an email address, or a name from the directory.
+async fn resolve_person(
+    ctx: &RuntimeContext,
+    db: &tmz_core::Cache,
+    client: &TeamsClient,
+    query: &str,
+) -> Result<tmz_core::cache::CachedPerson> {
+    let mut query = query.to_string();
+    if let Ok(conv_id) = ctx.resolve_target(db, &query).await {
+        let me = client.user_id().await?;
+        if let Some(peer) = tmz_core::hours::one_on_one_peer(&conv_id, &me) {
+            query = peer.to_string();
+        }
+    }
+    let mut found = tmz_core::org::resolve(client, db, &query).await?;
+    match found.len() {
+        0 => Err(anyhow!("no one matches '{query}'")),
+        1 => Ok(found.remove(0)),
+        _ => {
+            let names: Vec<&str> = found.iter().map(|p| p.display_name.as_str()).collect();
+            Err(anyhow!(
+                "'{query}' matches several people: {}; be more specific",
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+/// `tmz people manager` (`chain` is `Some`) and `tmz people reports`.
+async fn show_org(ctx: &RuntimeContext, query: &str, chain: Option<bool>) -> Result<()> {
+    use tmz_core::org::{self, Origin};
+
+    let db = ctx.open_cache().await?;
+    let client = TeamsClient::new()?;
+    let person = resolve_person(ctx, &db, &client, query).await?;
+    let (people, origin) = match chain {
+        Some(true) => org::chain(&client, &db, &person).await?,
+        Some(false) => {
+            let (manager, origin) = org::manager(&client, &db, &person).await?;
+            (manager.into_iter().collect(), origin)
+        }
+        None => org::reports(&client, &db, &person).await?,
+    };
+
+    if ctx.common.json {
+        let json = serde_json::json!({
+            "person": person,
+            "people": people,
+            "cached": origin == Origin::Cache,
+        });
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+
+    if origin == Origin::Cache {
+        eprintln!("Graph lookup failed; showing cached org data.");
+    }
+    if people.is_empty() {
+        let what = if chain.is_some() {
+            "has no manager"
+        } else {
+            "has no direct reports"
+        };
+        println!("{} {what}.", person.display_name);
+        return Ok(());
+    }
+    for (depth, p) in people.iter().enumerate() {
+        let indent = if chain == Some(true) { depth * 2 } else { 0 };
+        let title = p
+            .job_title
+            .as_deref()
+            .map_or_else(String::new, |t| format!("  {t}"));
+        let email = p
+            .email
+            .as_deref()
+            .map_or_else(String::new, |e| format!("  <{e}>"));
+        println!(
+            "{:indent$}\x1b[1m{}\x1b[0m{title}{email}",
+            "", p.display_name
+        );
+    }
+    Ok(())
+}
+
+async fn show_person(ctx: &RuntimeContext, person: &str) -> Result<()> {
+    let db = ctx.open_cache_read_only().await?;
+    let conv_id = ctx.resolve_target(&db, person).await?;
+    let client = TeamsClient::new()?;
+    let me = client.user_id().await?;
+    let user_id = tmz_core::hours::one_on_one_peer(&conv_id, &me)
+        .ok_or_else(|| anyhow!("'{person}' is not a 1:1 chat"))?;
+    let (profile, availability) =
+        tmz_core::availability::lookup(&client, user_id, &ctx.config.sending.working_hours).await?;
+    let now = chrono::Utc::now();
+
+    if ctx.common.json {
+        let json = serde_json::json!({
+            "profile": profile,
+            "presence": availability.presence,
+            "focusing": availability.is_focusing(now),
+            "working_hours": availability.hours.map(|h| h.describe()),
+            "off_hours": availability.off_hours(now).is_some(),
+            "schedule": availability.schedule,
+            "summary": availability.summary(now),
+        });
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+
+    let title = profile
+        .job_title
+        .as_deref()
+        .map_or_else(String::new, |t| format!("  {t}"));
+    println!("\x1b[1m{}\x1b[0m{title}", profile.name);
+    if let Some(ref email) = profile.email {
+        println!("  {email}");
+    }
+    println!();
+    println!("  Status    {}", availability.summary(now));
+    if let Some(hours) = availability.hours {
+        let source = match hours.source {
+            tmz_core::hours::HoursSource::Outlook => "from their calendar",
+            tmz_core::hours::HoursSource::Config => "assumed, from [sending.working_hours]",
+        };
+        println!("  Hours     {} ({source})", hours.describe());
+    }
+    let local = |t: chrono::DateTime<chrono::Utc>| t.with_timezone(&chrono::Local).format("%H:%M");
+    let upcoming: Vec<_> = availability
+        .schedule
+        .iter()
+        .filter(|item| item.end > now)
+        .collect();
+    if !upcoming.is_empty() {
+        println!("  Calendar");
+        for item in upcoming {
+            let what = item.subject.as_deref().unwrap_or(&item.status);
+            println!(
+                "            {}-{}  {what}{}",
+                local(item.start),
+                local(item.end),
+                if item.is_focus() { "  (focus)" } else { "" }
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `tmz listen --fifo`: send each `target<TAB>message` line written to
+/// the pipe until interrupted.
+async fn handle_listen(ctx: &RuntimeContext, fifo: &Path) -> Result<()> {
+    ctx.config.security.ensure_writable("sending messages")?;
+    let created = ensure_fifo(fifo)?;
+    let db = ctx.open_cache().await?;
+    if !ctx.common.quiet {
+        eprintln!(
+            "Listening on {} for target<TAB>message lines (Ctrl+C to stop)...",
+            fifo.display()
+        );
+    }
+    let result = tokio::select! {
+        result = read_fifo(ctx, &db, fifo) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+    if created && let Err(e) = std::fs::remove_file(fifo) {
+        debug!("removing {}: {e}", fifo.display());
+    }
+    result
+}
+
+/// Create a named pipe at `path` unless one exists; returns whether it was
+/// created. Any other existing file is refused.
+fn ensure_fifo(path: &Path) -> Result<bool> {
+    use std::os::unix::fs::FileTypeExt as _;
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.file_type().is_fifo() => return Ok(false),
+        Ok(_) => return Err(anyhow!("{} exists and is not a named pipe", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(anyhow!("reading {}: {e}", path.display())),
+    }
+    let status = std::process::Command::new("mkfifo")
+        .args(["-m", "600"])
+        .arg(path)
+        .status()
+        .context("running mkfifo")?;
+    if !status.success() {
+        return Err(anyhow!("mkfifo {} failed", path.display()));
+    }
+    Ok(true)
+}
+
+async fn read_fifo(ctx: &RuntimeContext, db: &Cache, path: &Path) -> Result<()> {
+    use tokio::io::AsyncBufReadExt as _;
+    use tokio::net::unix::pipe;
+
+    let receiver = pipe::OpenOptions::new()
+        .open_receiver(path)
+        .with_context(|| format!("opening {}", path.display()))?;
+    // Hold a writer ourselves so the pipe does not report end-of-file
+    // every time a client closes its end.
+    let _keepalive = pipe::OpenOptions::new()
+        .open_sender(path)
+        .with_context(|| format!("opening {}", path.display()))?;
+    let mut lines = tokio::io::BufReader::new(receiver).lines();
+    while let Some(line) = lines.next_line().await? {
+        send_fifo_line(ctx, db, &line).await;
+    }
+    Ok(())
+}
+
+/// Send one line read by `tmz listen`. Problems are reported and the line
+/// skipped, so one bad line does not stop the listener.
+async fn send_fifo_line(ctx: &RuntimeContext, db: &Cache, line: &str) {
+    let line = line.trim_end_matches('\r');
+    if line.trim().is_empty() || line.starts_with('#') {
+        return;
+    }
+    let Some((target, text)) = line.split_once('\t') else {
+        eprintln!(
+            "warning: skipping line without a tab: {}",
+            truncate(line, 60)
+        );
+        return;
+    };
+    let target = target.trim();
+    let text = text.trim().replace("\\n", "\n");
+    if text.is_empty() {
+        eprintln!("warning: skipping empty message for {target}");
+        return;
+    }
+    let conv_id = match ctx.resolve_target(db, target).await {
+        Ok(conv_id) => conv_id,
+        Err(e) => {
+            eprintln!("warning: {target}: {e}");
+            return;
+        }
+    };
+    if ctx.common.dry_run {
+        eprintln!("dry-run: would send to {target}: {}", truncate(&text, 60));
+        return;
+    }
+    if let Err(e) = lint_outgoing(ctx, &text) {
+        eprintln!("warning: not sent to {target}: {e}");
+        return;
+    }
+    let mut html = BodyFormat::Text.to_html(&text);
+    if ctx.config.sending.silent {
+        html = markup::silence(&html);
+    }
+    let sent = send_tracked(ctx, db, &conv_id, &html).await;
+    match sent {
+        Ok(_) if !ctx.common.quiet => eprintln!("Sent to {target} \u{2713}"),
+        Ok(_) => {}
+        Err(e) => eprintln!("warning: sending to {target} failed: {e}"),
+    }
+}
+
+async fn handle_outbox(ctx: &RuntimeContext, cmd: OutboxCommand) -> Result<()> {
+    if let Some(OutboxSubcommand::History { target, limit }) = cmd.command {
+        return outbox_history(ctx, target.as_deref(), limit).await;
+    }
+    if let Some(OutboxSubcommand::Cancel { id }) = cmd.command {
+        let db = ctx.open_cache().await?;
+        let cancelled = db.cancel_outgoing(id).await;
+        ctx.audit()
+            .record("outbox.cancel", Some(&format!("#{id}")), &cancelled);
+        return match cancelled? {
+            Some(item) => {
+                println!("Cancelled #{id}: {}", truncate(&item.content, 60));
+                Ok(())
+            }
+            None => Err(anyhow!(
+                "no queued message #{id} (already sent or cancelled?)"
+            )),
+        };
+    }
+
+    let db = ctx.open_cache_read_only().await?;
+    let items = db.list_outbox(cmd.all, cmd.limit).await?;
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&items)?);
+        return Ok(());
+    }
+    if items.is_empty() {
+        println!("Outbox is empty.");
+        return Ok(());
+    }
+    for item in &items {
+        let name = db
+            .find_conversation(&item.conversation_id)
+            .await?
+            .first()
+            .map_or_else(|| item.conversation_id.clone(), |c| c.display_name.clone());
+        let when = if item.until_available.is_some() && item.status == "pending" {
+            "when free".to_string()
+        } else {
+            local_time(&item.send_at)
+        };
+        println!(
+            "  #{:<4} {:<9} {when}  \x1b[1m{name}\x1b[0m  {}",
+            item.id,
+            item.status,
+            truncate(&item.content, 50)
+        );
+        if let Some(ref error) = item.error {
+            println!("        \x1b[31m{error}\x1b[0m");
+        }
+    }
+    Ok(())
+}
+
+/// `tmz outbox history`: the delivery state of outgoing messages.
+async fn outbox_history(ctx: &RuntimeContext, target: Option<&str>, limit: i64) -> Result<()> {
+    let db = ctx.open_cache_read_only().await?;
+    let conv_id = match target {
+        Some(target) => Some(ctx.resolve_target(&db, target).await?),
+        None => None,
+    };
+    let items = db.outgoing_history(conv_id.as_deref(), limit).await?;
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&items)?);
+        return Ok(());
+    }
+    if items.is_empty() {
+        println!("No messages sent yet.");
+        return Ok(());
+    }
+    for item in &items {
+        let name = db
+            .find_conversation(&item.conversation_id)
+            .await?
+            .first()
+            .map_or_else(|| item.conversation_id.clone(), |c| c.display_name.clone());
+        let (marker, state) = match item.status.as_str() {
+            "sent" => ("\x1b[32m\u{2713}\x1b[0m", "sent"),
+            "failed" => ("\x1b[31m\u{2717}\x1b[0m", "failed"),
+            "cancelled" => ("\x1b[2m-\x1b[0m", "cancelled"),
+            "uploading" => ("\u{2191}", "uploading"),
+            "sending" => ("\u{2026}", "sending"),
+            _ => ("\u{2026}", "queued"),
+        };
+        let when = local_time(item.sent_at.as_deref().unwrap_or(&item.queued_at));
+        let what = if item.kind == "file" {
+            format!("[file] {}", item.content)
+        } else {
+            item.content.clone()
+        };
+        println!(
+            "  {marker} #{:<4} {state:<9} {when}  \x1b[1m{name}\x1b[0m  {}",
+            item.id,
+            truncate(&what, 50)
+        );
+        if let Some(ref message_id) = item.message_id {
+            println!("        {}", dim(&format!("message {message_id}")));
+        }
+        if let Some(ref error) = item.error {
+            println!("        \x1b[31m{error}\x1b[0m");
+        }
+    }
+    Ok(())
+}
+
+async fn handle_remind(ctx: &RuntimeContext, cmd: RemindCommand) -> Result<()> {
+    match cmd.command {
+        Some(RemindSubcommand::List { all, limit }) => {
+            let db = ctx.open_cache_read_only().await?;
+            return list_reminders(ctx, &db, all, limit).await;
+        }
+        Some(RemindSubcommand::Cancel { id }) => {
+            let db = ctx.open_cache().await?;
+            return match db.cancel_reminder(id).await? {
+                Some(_) => {
+                    println!("Cancelled reminder #{id}.");
+                    Ok(())
+                }
+                None => Err(anyhow!(
+                    "no pending reminder #{id} (already fired or cancelled?)"
+                )),
+            };
+        }
+        None => {}
+    }
+
+    let target = cmd
+        .target
+        .ok_or_else(|| anyhow!("usage: tmz remind <target> --in 2h [note]"))?;
+    let when = match (cmd.after, cmd.at) {
+        (Some(after), _) => format!("in {after}"),
+        (None, Some(at)) => at,
+        (None, None) => return Err(anyhow!("say when with --in 2h or --at 09:00")),
+    };
+    let due_at = tmz_core::dates::parse_send_time(&when, chrono::Local::now())?;
+    let db = ctx.open_cache().await?;
+    let conv_id = ctx.resolve_target(&db, &target).await?;
+    let reminder = db
+        .add_reminder(&conv_id, cmd.note.as_deref().unwrap_or_default(), due_at)
+        .await?;
+    println!(
+        "Reminder #{id} set for {when}. Cancel with 'tmz remind cancel {id}'.",
+        id = reminder.id,
+        when = due_at
+            .with_timezone(&chrono::Local)
+            .format("%a %d %b %H:%M")
+    );
+    if EngineClient::connect().await.is_none() {
+        eprintln!("note: the daemon fires reminders; start it with 'tmz service start'.");
+    }
+    Ok(())
+}
+
+async fn list_reminders(ctx: &RuntimeContext, db: &Cache, all: bool, limit: i64) -> Result<()> {
+    let reminders = db.list_reminders(all, limit).await?;
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&reminders)?);
+        return Ok(());
+    }
+    if reminders.is_empty() {
+        println!("No reminders.");
+        return Ok(());
+    }
+    for reminder in &reminders {
+        let name = db
+            .find_conversation(&reminder.conversation_id)
+            .await?
+            .first()
+            .map_or_else(
+                || reminder.conversation_id.clone(),
+                |c| c.display_name.clone(),
+            );
+        let when = chrono::DateTime::parse_from_rfc3339(&reminder.due_at).map_or_else(
+            |_| reminder.due_at.clone(),
+            |t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%a %d %b %H:%M")
+                    .to_string()
+            },
+        );
+        println!(
+            "  #{:<4} {:<9} {when}  \x1b[1m{name}\x1b[0m  {}",
+            reminder.id,
+            reminder.status,
+            truncate(&reminder.note, 50)
+        );
+    }
+    Ok(())
+}
+
+/// `tmz save`: copy a cached message into the saved items.
+async fn handle_save(ctx: &RuntimeContext, args: &SaveArgs) -> Result<()> {
+    let db = ctx.open_cache().await?;
+    let conv_id = ctx.resolve_target(&db, &args.target).await?;
+    let id = &args.message_id;
+    let msg = db
+        .get_message(&conv_id, id)
+        .await?
+        .ok_or_else(|| anyhow!("message {id} is not in the cache (try 'tmz sync')"))?;
+    let about = format!("{}: {}", msg.from_display_name, truncate(&msg.content, 50));
+    if ctx.common.dry_run {
+        println!("dry-run: would save {about}");
+        return Ok(());
+    }
+    let item = db
+        .save_item(&msg, args.note.as_deref().unwrap_or_default())
+        .await?;
+    println!("Saved #{} {about}", item.id);
+    Ok(())
+}
+
+async fn handle_saved(ctx: &RuntimeContext, cmd: SavedCommand) -> Result<()> {
+    match cmd {
+        SavedCommand::List { query, limit } => {
+            let db = ctx.open_cache_read_only().await?;
+            let items = db.list_saved_items(query.as_deref(), limit).await?;
+            if ctx.common.json {
+                println!("{}", serde_json::to_string_pretty(&items)?);
+                return Ok(());
+            }
+            if items.is_empty() {
+                println!("No saved messages. Save one with 'tmz save <target> <message-id>'.");
+                return Ok(());
+            }
+            for item in &items {
+                let chat = if item.conversation_name.is_empty() {
+                    &item.conversation_id
+                } else {
+                    &item.conversation_name
+                };
+                let when = chrono::DateTime::parse_from_rfc3339(&item.compose_time).map_or_else(
+                    |_| item.compose_time.clone(),
+                    |t| {
+                        t.with_timezone(&chrono::Local)
+                            .format("%d %b %Y %H:%M")
+                            .to_string()
+                    },
+                );
+                println!(
+                    "  #{:<4} \x1b[1m{chat}\x1b[0m  {}  {when}",
+                    item.id, item.from_display_name
+                );
+                println!("        {}", truncate(&item.content, 70));
+                if !item.note.is_empty() {
+                    println!("        {}", dim(&format!("note: {}", item.note)));
+                }
+            }
+            Ok(())
+        }
+        SavedCommand::Remove { id } => {
+            let db = ctx.open_cache().await?;
+            if ctx.common.dry_run {
+                println!("dry-run: would remove saved item #{id}");
+            } else if db.remove_saved_item(id).await? {
+                println!("Removed saved item #{id}.");
+            } else {
+                return Err(anyhow!("no saved item #{id}"));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Sync messages for a specific conversation.
+async fn sync_conversation(
+    db: &tmz_core::Cache,
+    conv_id: &str,
+    limit: i64,
+    types: &MessagesConfig,
+) -> Result<u64> {
+    let client = TeamsClient::new()?;
+    let me = client
+        .get_me()
+        .await
+        .map_err(|e| anyhow!("get user info: {e}"))?;
+    let my_name = me["displayName"].as_str();
+    let limit_i32 = i32::try_from(limit).unwrap_or(50);
+
+    let msgs = client
+        .get_chat_messages(conv_id, Some(limit_i32))
+        .await
+        .map_err(|e| anyhow!("fetch messages: {e}"))?;
+
+    let mut count = 0u64;
+    let mut skipped = BTreeMap::new();
+    for msg in &msgs {
+        if let Some(cached) = cache::parse_message(msg, conv_id, my_name, types) {
+            let _ = db.upsert_message(&cached).await;
+            count += 1;
+        } else {
+            *skipped.entry(msg.message_type.clone()).or_insert(0u64) += 1;
+        }
+    }
+    cache::log_skipped_types(&skipped);
+    Ok(count)
+}
+
+impl SendOpts {
+    /// `text` as message HTML in the `--markup` format, silenced for
+    /// `--silent` or `[sending] silent`.
+    fn to_html(&self, ctx: &RuntimeContext, text: &str) -> String {
+        self.silenced(ctx, BodyFormat::from(self.markup).to_html(text))
+    }
+
+    /// `html` silenced for `--silent` or `[sending] silent`.
+    fn silenced(&self, ctx: &RuntimeContext, html: String) -> String {
+        if self.silent || ctx.config.sending.silent {
+            markup::silence(&html)
+        } else {
+            html
+        }
+    }
+}
+
+/// How `tmz msg` shows a conversation.
+#[derive(Debug, Clone)]
+struct MsgView {
+    limit: i64,
+    no_images: bool,
+    legend: bool,
+    translate: Option<String>,
+}
+
+async fn handle_msg(
+    ctx: &RuntimeContext,
+    target: String,
+    message: Option<String>,
+    file: Option<PathBuf>,
+    sync: bool,
+    view: MsgView,
+    opts: SendOpts,
+) -> Result<()> {
+    let message = match message.as_deref() {
+        Some("-") => {
+            let mut text = String::new();
+            io::Read::read_to_string(&mut io::stdin(), &mut text)?;
+            Some(text.trim_end().to_string())
+        }
+        _ => message,
+    };
+    // Lint what was typed, before it is escaped or converted.
+    if let Some(ref text) = message {
+        lint_outgoing(ctx, text)?;
+    }
+    if let Some(members) = ctx.config.resolve_group(&target) {
+        let text = message.ok_or_else(|| {
+            anyhow!("'{target}' is an alias group; pass a message to send to its members")
+        })?;
+        if file.is_some() || opts.schedule.is_some() || opts.if_available || opts.reply_to.is_some()
+        {
+            return Err(anyhow!(
+                "--file, --schedule, --if-available, and --reply-to are not supported for alias groups"
+            ));
+        }
+        return send_to_group(ctx, &target, members, &opts.to_html(ctx, &text)).await;
+    }
+
+    let limit = view.limit;
+    let db = ctx.open_cache().await?;
+    let conv_id = ctx.resolve_target(&db, &target).await?;
+    if let Err(e) = db.record_open(&conv_id).await {
+        debug!("recording conversation open: {e}");
+    }
+
+    // Sync messages for this conversation if requested
+    if sync {
+        eprint!("Syncing conversation... ");
+        match sync_conversation(&db, &conv_id, limit, &ctx.config.messages).await {
+            Ok(count) => eprintln!("{count} messages synced."),
+            Err(e) => eprintln!("failed: {e}"),
+        }
+    }
+
+    let Some((message, file)) =
+        expand_slash_command(ctx, &db, &conv_id, message, file, &opts).await?
+    else {
+        return Ok(());
+    };
+
+    // Send file if --file is specified
+    if let Some(ref file_path) = file {
+        return send_file(ctx, &db, &conv_id, file_path, message.as_deref()).await;
+    }
+
+    if let Some(msg_text) = message {
+        let msg_text = match opts.reply_to {
+            Some(ref id) => {
+                let quoted = db.get_message(&conv_id, id).await?.ok_or_else(|| {
+                    anyhow!("message {id} is not in the cache (try 'tmz msg {target} --sync')")
+                })?;
+                markup::quote_reply(&quoted, &msg_text)
+            }
+            None => msg_text,
+        };
+        // Send a text message (through the daemon when it is running)
+        return send_text(ctx, &db, &conv_id, &msg_text, &opts).await;
+    }
+
+    // Show recent messages (prefer cache, fall back to API)
+    let messages = db.get_messages(&conv_id, limit).await?;
+
+    // Fetch live if no cached messages
+    let mut messages = if messages.is_empty() {
+        eprintln!("No cached messages. Fetching from API...");
+        let client = TeamsClient::new()?;
+        let limit_i32 = i32::try_from(limit).unwrap_or(20);
+        let msgs = client.get_chat_messages(&conv_id, Some(limit_i32)).await?;
+        if ctx.common.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&serde_json::json!({ "messages": msgs }))?
+            );
+            return Ok(());
+        }
+        let my_name = client.get_me().await.ok().and_then(|u| {
+            u["displayName"]
+                .as_str()
+                .map(std::string::ToString::to_string)
+        });
+        msgs.iter()
+            .filter_map(|m| {
+                cache::parse_message(m, &conv_id, my_name.as_deref(), &ctx.config.messages)
+            })
+            .collect()
+    } else {
+        messages
+    };
+
+    if let Some(ref lang) = view.translate {
+        let lang = if lang.is_empty() {
+            ctx.config.ai.translation.target_language.as_str()
+        } else {
+            lang.as_str()
+        };
+        return print_translated(ctx, &db, &conv_id, &mut messages, lang, &view).await;
+    }
+
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&messages)?);
+        return Ok(());
+    }
+
+    print_conversation(ctx, &db, &conv_id, &messages, &view).await
+}
+
+/// Apply a slash command (`/me`, `/code`, `/file`, `/react`, `/shrug`) in
+/// the message, returning the message HTML and file to send, or `None`
+/// once a reaction has been sent. Other messages are converted as `opts`
+/// say, and either is silenced for `--silent` or `[sending] silent`.
+async fn expand_slash_command(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+    message: Option<String>,
+    file: Option<PathBuf>,
+    opts: &SendOpts,
+) -> Result<Option<(Option<String>, Option<PathBuf>)>> {
+    use tmz_core::slash::SlashCommand;
+    let Some(text) = message else {
+        return Ok(Some((None, file)));
+    };
+    let me = db.my_display_name().await?;
+    Ok(match tmz_core::slash::parse(&text, me.as_deref())? {
+        None => Some((Some(opts.to_html(ctx, &text)), file)),
+        Some(SlashCommand::Send(html)) => Some((Some(opts.silenced(ctx, html)), file)),
+        Some(SlashCommand::File(path)) => Some((None, Some(path))),
+        Some(SlashCommand::React(key)) => {
+            react_to(ctx, db, conv_id, &key, None, false).await?;
+            None
+        }
+    })
+}
+
+/// `tmz react`: react to a message by ID, or with `--last` to the newest
+/// one someone else sent.
+async fn handle_react(ctx: &RuntimeContext, args: &ReactArgs) -> Result<()> {
+    let reaction = args.reaction.as_str();
+    let key = tmz_core::reactions::key_for(reaction).ok_or_else(|| {
+        let classic: Vec<&str> = tmz_core::reactions::COMMON
+            .iter()
+            .map(|(_, e)| *e)
+            .collect();
+        anyhow!(
+            "unknown reaction '{reaction}': use {} or a key like '1f680_rocket'",
+            classic.join(" ")
+        )
+    })?;
+    let db = ctx.open_cache().await?;
+    let conv_id = ctx.resolve_target(&db, &args.target).await?;
+    react_to(
+        ctx,
+        &db,
+        &conv_id,
+        &key,
+        args.message.as_deref(),
+        args.remove,
+    )
+    .await
+}
+
+/// React to the cached message `message_id`, or to the newest cached
+/// message someone else sent (`/react`, `tmz react --last`); with `remove`,
+/// take the reaction back.
+async fn react_to(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+    key: &str,
+    message_id: Option<&str>,
+    remove: bool,
+) -> Result<()> {
+    ctx.config.security.ensure_writable(if remove {
+        "removing reactions"
+    } else {
+        "adding reactions"
+    })?;
+    db.restrictions(conv_id).await?.check(Action::React)?;
+    let target = match message_id {
+        Some(id) => db
+            .get_message(conv_id, id)
+            .await?
+            .ok_or_else(|| anyhow!("message {id} is not in the cache (try 'tmz sync')"))?,
+        None => db
+            .get_messages(conv_id, 50)
+            .await?
+            .into_iter()
+            .rev()
+            .find(|m| !m.is_from_me && !m.is_system_event())
+            .ok_or_else(|| {
+                anyhow!("no cached message from someone else to react to (try 'tmz sync')")
+            })?,
+    };
+    let label = tmz_core::reactions::emoji(key).unwrap_or_else(|| key.to_string());
+    let about = format!(
+        "{}: {}",
+        target.from_display_name,
+        truncate(&target.content, 50)
+    );
+    if ctx.common.dry_run {
+        let action = if remove { "remove" } else { "react" };
+        println!("dry-run: would {action} {label} to {about}");
+        return Ok(());
+    }
+    let client = TeamsClient::new()?;
+    if remove {
+        let result = client.remove_reaction(conv_id, &target.id, key).await;
+        ctx.audit().record("react.remove", Some(conv_id), &result);
+        result?;
+        println!("Removed {label} from {about}");
+    } else {
+        let result = client.add_reaction(conv_id, &target.id, key).await;
+        ctx.audit().record("react", Some(conv_id), &result);
+        result?;
+        println!("Reacted {label} to {about}");
+    }
+    Ok(())
+}
+
+/// Send a text message, holding it in the outbox for `[sending]
+/// undo_window` first. Messages over `[sending] max_message_bytes` are
+/// split into parts or attached as a `.txt` file.
+async fn send_text(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+    text: &str,
+    opts: &SendOpts,
+) -> Result<()> {
+    ctx.config.security.ensure_writable("sending messages")?;
+    let parts = match fit_message(ctx, text)? {
+        MessageFit::Parts(parts) => parts,
+        MessageFit::Attach => return attach_text(ctx, db, conv_id, text).await,
+        MessageFit::Cancel => {
+            println!("Not sent.");
+            return Ok(());
+        }
+    };
+    if let Some(ref when) = opts.schedule {
+        let at = tmz_core::dates::parse_send_time(when, chrono::Local::now())?;
+        for part in &parts {
+            schedule_text(ctx, db, conv_id, part, at).await?;
+        }
+        return Ok(());
+    }
+    if opts.if_available {
+        match confirm_available(ctx, db, conv_id).await? {
+            AvailabilityChoice::Send => {}
+            AvailabilityChoice::Hold(user) => {
+                for part in &parts {
+                    hold_until_available(ctx, db, conv_id, part, &user).await?;
+                }
+                return Ok(());
+            }
+            AvailabilityChoice::Cancel => {
+                println!("Not sent.");
+                return Ok(());
+            }
+        }
+    }
+    match confirm_off_hours(ctx, db, conv_id).await? {
+        OffHoursChoice::Send => send_now(ctx, db, conv_id, &parts).await,
+        OffHoursChoice::Schedule(at) => {
+            for part in &parts {
+                schedule_text(ctx, db, conv_id, part, at).await?;
+            }
+            Ok(())
+        }
+        OffHoursChoice::Cancel => {
+            println!("Not sent.");
+            Ok(())
+        }
+    }
+}
+
+/// Send the parts of a message in order after the undo window. Without a
+/// daemon to dispatch them, waits out the window in the foreground;
+/// `Ctrl+C` cancels.
+async fn send_now(ctx: &RuntimeContext, db: &Cache, conv_id: &str, parts: &[String]) -> Result<()> {
+    let audit = ctx.audit();
+    let window = ctx.config.sending.undo_window()?;
+    if window.is_zero() {
+        for part in parts {
+            let sent = send_tracked(ctx, db, conv_id, part).await?;
+            println!("{}", sent_line(&sent));
+        }
+        return Ok(());
+    }
+
+    let mut ids = Vec::with_capacity(parts.len());
+    for part in parts {
+        let item = db.queue_outgoing(conv_id, part, window).await?;
+        audit.record_with(
+            "send.queue",
+            Some(conv_id),
+            "ok",
+            Some(&format!("outbox #{}", item.id)),
+        );
+        ids.push(item.id);
+    }
+    let secs = window.as_secs();
+    let list = ids
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" ");
+    if EngineClient::connect().await.is_some() {
+        let queued = ids.iter().map(|id| format!("#{id}")).collect::<Vec<_>>();
+        println!(
+            "Queued as {}, sending in {secs}s. Undo with 'tmz outbox cancel {list}'.",
+            queued.join(", ")
+        );
+        return Ok(());
+    }
+
+    eprint!("Sending in {secs}s (Ctrl+C or 'tmz outbox cancel {list}' to undo)... ");
+    tokio::select! {
+        () = tokio::time::sleep(window) => {}
+        _ = tokio::signal::ctrl_c() => {
+            for id in ids {
+                let cancelled = db.cancel_outgoing(id).await;
+                audit.record("outbox.cancel", Some(&format!("#{id}")), &cancelled);
+                cancelled?;
+            }
+            eprintln!("cancelled.");
+            return Ok(());
+        }
+    }
+    let mut sent = 0;
+    for id in &ids {
+        sent += tmz_core::outbox::dispatch_due(db, Some(*id), |conv, content| {
+            let audit = &audit;
+            async move {
+                let sent = engine::send_message(&conv, &content).await;
+                audit.record("send", Some(&conv), &sent);
+                sent
+            }
+        })
+        .await?
+        .len();
+    }
+    if sent == ids.len() {
+        eprintln!("sent \u{2713}");
+    } else if sent == 0 {
+        eprintln!("not sent (cancelled or failed, see 'tmz outbox history').");
+    } else {
+        eprintln!(
+            "sent {sent} of {} parts (see 'tmz outbox history').",
+            ids.len()
+        );
+    }
+    Ok(())
+}
+
+/// How an outgoing message is sent.
+#[derive(Debug)]
+enum MessageFit {
+    /// As these messages, in order.
+    Parts(Vec<String>),
+    /// As a `.txt` file attachment.
+    Attach,
+    Cancel,
+}
+
+/// Decide how to send `text` when it is over `[sending] max_message_bytes`,
+/// following `[sending] oversized`.
+fn fit_message(ctx: &RuntimeContext, text: &str) -> Result<MessageFit> {
+    use tmz_core::OversizedAction;
+    let sending = &ctx.config.sending;
+    let max = sending.max_message_bytes;
+    if text.len() <= max {
+        return Ok(MessageFit::Parts(vec![text.to_string()]));
+    }
+    let parts = tmz_core::composer::split_message(text, max);
+    let count = parts.len();
+    let over = format!(
+        "message is {} bytes, over the {max} byte limit ([sending] max_message_bytes)",
+        text.len()
+    );
+    let split = || {
+        if count > sending.max_parts {
+            eprintln!(
+                "note: {over}; it needs {count} parts (more than max_parts = {}), attaching it as a .txt file.",
+                sending.max_parts
+            );
+            MessageFit::Attach
+        } else {
+            eprintln!("note: {over}; sending it in {count} parts.");
+            MessageFit::Parts(parts.clone())
+        }
+    };
+    match sending.oversized {
+        OversizedAction::Refuse => Err(anyhow!(
+            "not sent: {over}. Send it with --file, or change [sending] oversized"
+        )),
+        OversizedAction::Attach => {
+            eprintln!("note: {over}; attaching it as a .txt file.");
+            Ok(MessageFit::Attach)
+        }
+        OversizedAction::Split => Ok(split()),
+        OversizedAction::Ask if ctx.common.assume_yes => Ok(split()),
+        OversizedAction::Ask if !io::stdin().is_terminal() => Err(anyhow!(
+            "not sent: {over}. Pass --yes to send it in {count} parts, or change [sending] oversized"
+        )),
+        OversizedAction::Ask => {
+            eprint!("The {over}.\n[s]plit into {count} parts / [a]ttach as a .txt file / [N]o: ");
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            Ok(match answer.trim().to_lowercase().as_str() {
+                "s" | "split" => MessageFit::Parts(parts),
+                "a" | "attach" => MessageFit::Attach,
+                _ => MessageFit::Cancel,
+            })
+        }
+    }
+}
+
+/// Send the message HTML `html` as a `.txt` file attachment.
+async fn attach_text(ctx: &RuntimeContext, db: &Cache, conv_id: &str, html: &str) -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("tmz-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "message-{}.txt",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    std::fs::write(&path, markup::html_to_text(html))?;
+    let sent = send_file(ctx, db, conv_id, &path, None).await;
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        debug!("removing {}: {e}", dir.display());
+    }
+    sent
+}
+
+/// `tmz msg --file`: upload `file_path`, then send `message` if given.
+async fn send_file(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+    file_path: &Path,
+    message: Option<&str>,
+) -> Result<()> {
+    ctx.config.security.ensure_writable("uploading files")?;
+    db.restrictions(conv_id).await?.check(Action::Upload)?;
+    if !file_path.exists() {
+        return Err(anyhow!("file not found: {}", file_path.display()));
+    }
+    let client = TeamsClient::new()?;
+    let file_name = file_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("file");
+    eprint!("Uploading {file_name}... ");
+    let uploaded = tmz_core::outbox::track(
+        Some(db),
+        conv_id,
+        &file_path.display().to_string(),
+        OutgoingKind::File,
+        client.send_file(conv_id, file_path),
+    )
+    .await;
+    ctx.audit().record("upload", Some(conv_id), &uploaded);
+    let mut sent = uploaded?;
+    eprintln!("done.");
+
+    // Also send text message if provided
+    if let Some(msg_text) = message {
+        sent = send_tracked(ctx, db, conv_id, msg_text).await?;
+    }
+    println!("{}", sent_line(&sent));
+    Ok(())
+}
+
+/// Send `text` to `conv_id` right away (through the daemon when it is
+/// running), recording its delivery state in the outbox and the audit log.
+async fn send_tracked(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+    text: &str,
+) -> Result<serde_json::Value, tmz_core::CoreError> {
+    let sent = tmz_core::outbox::track(
+        Some(db),
+        conv_id,
+        text,
+        OutgoingKind::Text,
+        engine::send_message(conv_id, text),
+    )
+    .await;
+    ctx.audit().record("send", Some(conv_id), &sent);
+    sent
+}
+
+/// `Sent ✓` with the time the service acknowledged the message.
+fn sent_line(response: &serde_json::Value) -> String {
+    let ack = tmz_core::outbox::acknowledgement(response);
+    ack.sent_at.as_deref().map(local_time).map_or_else(
+        || "Sent \u{2713}".to_string(),
+        |at| format!("Sent \u{2713} {}", dim(&at)),
+    )
+}
+
+/// An RFC 3339 time as local `HH:MM:SS`, or as given if it does not parse.
+fn local_time(time: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(time).map_or_else(
+        |_| time.to_string(),
+        |t| {
+            t.with_timezone(&chrono::Local)
+                .format("%H:%M:%S")
+                .to_string()
+        },
+    )
+}
+
+/// Send `text` to every conversation of the alias group `name`, pausing
+/// `[sending] bulk_interval` between messages. Groups with more than
+/// `bulk_confirm_above` conversations list them and need `--yes`.
+async fn send_to_group(
+    ctx: &RuntimeContext,
+    name: &str,
+    members: &[String],
+    text: &str,
+) -> Result<()> {
+    ctx.config.security.ensure_writable("sending messages")?;
+    let db = ctx.open_cache().await?;
+    let mut targets: Vec<(String, &str)> = Vec::new();
+    for member in members {
+        let conv_id = ctx
+            .resolve_target(&db, member)
+            .await
+            .with_context(|| format!("group '{name}' member '{member}'"))?;
+        if !targets.iter().any(|(id, _)| *id == conv_id) {
+            targets.push((conv_id, member));
+        }
+    }
+
+    let sending = &ctx.config.sending;
+    let interval = sending.bulk_interval()?;
+    let bulk = targets.len() > sending.bulk_confirm_above;
+    if bulk || ctx.common.dry_run {
+        eprintln!("Group '{name}' sends to {} conversations:", targets.len());
+        for (conv_id, member) in &targets {
+            eprintln!("  {member} {}", dim(conv_id));
+        }
+    }
+    if ctx.common.dry_run {
+        log::info!("dry-run: would send to {} conversations", targets.len());
+        return Ok(());
+    }
+    if bulk && !ctx.common.assume_yes {
+        return Err(anyhow!(
+            "not sent: '{name}' expands to {} conversations, more than [sending] bulk_confirm_above ({}). Pass --yes to send to all of them",
+            targets.len(),
+            sending.bulk_confirm_above
+        ));
+    }
+
+    let mut failed = 0;
+    for (i, (conv_id, member)) in targets.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(interval).await;
+        }
+        let sent = send_tracked(ctx, &db, conv_id, text).await;
+        match sent {
+            Ok(_) => eprintln!("Sent to {member} \u{2713}"),
+            Err(e) => {
+                failed += 1;
+                eprintln!("Failed to send to {member}: {e}");
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!("{failed} of {} sends failed", targets.len()));
+    }
+    Ok(())
+}
+
+/// What to do with a message to someone outside their working hours.
+enum OffHoursChoice {
+    Send,
+    Schedule(chrono::DateTime<chrono::Utc>),
+    Cancel,
+}
+
+/// Apply `[sending] off_hours` to a message for `conv_id`: warn, refuse,
+/// or ask whether to send now or at the start of the recipient's day
+/// (`--yes` sends). Failing to look up the hours never stops a send.
+async fn confirm_off_hours(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+) -> Result<OffHoursChoice> {
+    use tmz_core::OffHoursAction;
+    let action = ctx.config.sending.off_hours;
+    if ctx.common.assume_yes && action != OffHoursAction::Block {
+        return Ok(OffHoursChoice::Send);
+    }
+    let off = match tmz_core::hours::check_recipient(&ctx.config.sending, conv_id).await {
+        Ok(Some(off)) => off,
+        Ok(None) => return Ok(OffHoursChoice::Send),
+        Err(e @ tmz_core::CoreError::Config(_)) => return Err(e.into()),
+        Err(e) => {
+            debug!("checking working hours: {e}");
+            return Ok(OffHoursChoice::Send);
+        }
+    };
+    let who = db
+        .find_conversation(conv_id)
+        .await?
+        .first()
+        .map_or_else(|| "them".to_string(), |c| c.display_name.clone());
+    let reason = off.describe(&who);
+    let hint = off.schedule_hint(chrono::Local::now());
+    let suggestion = hint
+        .as_ref()
+        .map_or_else(String::new, |h| format!(" with --schedule \"{h}\""));
+    match action {
+        OffHoursAction::Off => Ok(OffHoursChoice::Send),
+        OffHoursAction::Warn => {
+            eprintln!("warning: {reason}; consider sending it{suggestion}");
+            Ok(OffHoursChoice::Send)
+        }
+        OffHoursAction::Block => Err(anyhow!(
+            "not sent: {reason}. Send it{suggestion}, or change [sending] off_hours"
+        )),
+        OffHoursAction::Confirm if !io::stdin().is_terminal() => Err(anyhow!(
+            "not sent: {reason}. Send it{suggestion}, or pass --yes"
+        )),
+        OffHoursAction::Confirm => {
+            let schedule = hint
+                .as_ref()
+                .map_or_else(String::new, |h| format!(" / [s]chedule for {h}"));
+            eprint!("Note: {reason}.\nSend anyway? [y]es{schedule} / [N]o: ");
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            Ok(match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => OffHoursChoice::Send,
+                "s" | "schedule" => off
+                    .next_start
+                    .map_or(OffHoursChoice::Cancel, OffHoursChoice::Schedule),
+                _ => OffHoursChoice::Cancel,
+            })
+        }
+    }
+}
+
+/// `tmz status`: show, set, or reset your own presence.
+async fn handle_status(ctx: &RuntimeContext, cmd: &StatusCommand) -> Result<()> {
+    let client = TeamsClient::new()?;
+    match cmd {
+        StatusCommand::Show => {
+            let presence = client.get_user_presence(&client.user_id().await?).await?;
+            if let Some(format) = ctx.output_format() {
+                return print_structured(&serde_json::to_value(&presence)?, format);
+            }
+            let label = presence.availability.label();
+            match presence.activity {
+                Some(ref activity) if !activity.eq_ignore_ascii_case(label) => {
+                    println!("{label} ({activity})");
+                }
+                _ => println!("{label}"),
+            }
+        }
+        StatusCommand::Set { status, duration } => {
+            ctx.config
+                .security
+                .ensure_writable("setting your presence")?;
+            let until = match duration {
+                Some(duration) => {
+                    let duration = tmz_core::dates::parse_duration(duration)?;
+                    if duration.is_zero() {
+                        return Err(anyhow!("--duration must be longer than zero"));
+                    }
+                    Some(chrono::Utc::now() + chrono::Duration::from_std(duration)?)
+                }
+                None => None,
+            };
+            let status = PresenceStatus::from(*status);
+            client.set_presence(status, until).await?;
+            if let Some(until) = until {
+                let local = until.with_timezone(&chrono::Local);
+                println!(
+                    "Presence set to {} until {}.",
+                    status.label(),
+                    local.format("%Y-%m-%d %H:%M")
+                );
+            } else {
+                println!(
+                    "Presence set to {} until `tmz status reset`.",
+                    status.label()
+                );
+            }
+        }
+        StatusCommand::Reset => {
+            ctx.config
+                .security
+                .ensure_writable("setting your presence")?;
+            client.reset_presence().await?;
+            println!("Presence reset; Teams sets it from your activity again.");
+        }
+    }
+    Ok(())
+}
+
+/// What to do with a message sent with `--if-available`.
+enum AvailabilityChoice {
+    Send,
+    /// Hold it until this user is Available.
+    Hold(String),
+    Cancel,
+}
+
+/// Check the presence of the other person in a 1:1 chat. Unless they are
+/// Available, offer to hold the message (`--yes` holds it without asking).
+async fn confirm_available(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+) -> Result<AvailabilityChoice> {
+    let client = TeamsClient::new()?;
+    let me = client.user_id().await?;
+    let peer = tmz_core::hours::one_on_one_peer(conv_id, &me)
+        .ok_or_else(|| anyhow!("--if-available needs a 1:1 chat"))?
+        .to_string();
+    let presence = client.get_user_presence(&peer).await?;
+    if presence.availability == PresenceStatus::Available {
+        return Ok(AvailabilityChoice::Send);
+    }
+    let who = db
+        .find_conversation(conv_id)
+        .await?
+        .first()
+        .map_or_else(|| "They".to_string(), |c| c.display_name.clone());
+    let label = presence.availability.label();
+    let state = match presence.activity {
+        Some(ref activity) if !activity.eq_ignore_ascii_case(label) => {
+            format!("{label} ({activity})")
+        }
+        _ => label.to_string(),
+    };
+    if ctx.common.assume_yes {
+        eprintln!("{who} is {state}.");
+        return Ok(AvailabilityChoice::Hold(peer));
+    }
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "not sent: {who} is {state}. Pass --yes to queue it until they are available"
+        ));
+    }
+    eprint!("{who} is {state}. Queue it until they are available? [Y/n]: ");
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(match answer.trim().to_lowercase().as_str() {
+        "" | "y" | "yes" => AvailabilityChoice::Hold(peer),
+        _ => AvailabilityChoice::Cancel,
+    })
+}
+
+/// Queue a message in the outbox until `user_id` is Available.
+async fn hold_until_available(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+    text: &str,
+    user_id: &str,
+) -> Result<()> {
+    let item = db.queue_until_available(conv_id, text, user_id).await?;
+    ctx.audit().record_with(
+        "send.queue",
+        Some(conv_id),
+        "ok",
+        Some(&format!("outbox #{}, until available", item.id)),
+    );
+    println!(
+        "Queued as #{id}; it is sent once they are available. Cancel with 'tmz outbox cancel {id}'.",
+        id = item.id
+    );
+    if EngineClient::connect().await.is_none() {
+        eprintln!("note: the daemon watches presence; start it with 'tmz service start'.");
+    }
+    Ok(())
+}
+
+/// Queue a message in the outbox to be sent at `at`.
+async fn schedule_text(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+    text: &str,
+    at: chrono::DateTime<chrono::Utc>,
+) -> Result<()> {
+    let delay = (at - chrono::Utc::now()).to_std().unwrap_or_default();
+    let item = db.queue_outgoing(conv_id, text, delay).await?;
+    ctx.audit().record_with(
+        "send.queue",
+        Some(conv_id),
+        "ok",
+        Some(&format!("outbox #{}", item.id)),
+    );
+    println!(
+        "Scheduled as #{id} for {when}. Cancel with 'tmz outbox cancel {id}'.",
+        id = item.id,
+        when = at.with_timezone(&chrono::Local).format("%a %d %b %H:%M")
+    );
+    if EngineClient::connect().await.is_none() {
+        eprintln!("note: the daemon sends scheduled messages; start it with 'tmz service start'.");
+    }
+    Ok(())
+}
+
+/// Apply `[[composer.lint]]` rules to an outgoing message, printing
+/// warnings and failing on blocking matches.
+fn lint_outgoing(ctx: &RuntimeContext, text: &str) -> Result<()> {
+    for hit in tmz_core::composer::check_outbound(&ctx.config.composer, text)? {
+        eprintln!("warning: {}", hit.message);
+    }
+    Ok(())
+}
+
+/// Print the conversation header and messages for `tmz msg`.
+async fn print_conversation(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+    messages: &[tmz_core::CachedMessage],
+    view: &MsgView,
+) -> Result<()> {
+    // Print header
+    let convs = db.find_conversation(conv_id).await?;
+    if let Some(conv) = convs.first() {
+        println!("\x1b[1m{}\x1b[0m", conv.display_name);
+        if let Some(banner) = conv.restrictions.banner() {
+            println!("\x1b[33m{banner}\x1b[0m");
+        }
+        println!();
+    }
+
+    if view.legend {
+        print_sender_legend(messages, &ctx.config.display);
+    }
+
+    let images = if view.no_images {
+        None
+    } else {
+        tmz_core::graphics::detect(ctx.config.display.images)
+    };
+
+    render_messages(messages, db, images, &ctx.config.display).await
+}
+
+/// Translate messages into `lang` (`tmz msg --translate`) and print them,
+/// each followed by the detected source language. With `--json`, messages
+/// get a `translation` field instead.
+async fn print_translated(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    conv_id: &str,
+    messages: &mut [tmz_core::CachedMessage],
+    lang: &str,
+    view: &MsgView,
+) -> Result<()> {
+    let translations =
+        tmz_core::translate::translate_messages(db, &ctx.config.ai.translation, messages, lang)
+            .await?;
+
+    if ctx.common.json {
+        let mut out = Vec::with_capacity(messages.len());
+        for msg in messages.iter() {
+            let mut value = serde_json::to_value(msg)?;
+            if let Some(t) = translations.get(&msg.id) {
+                value["translation"] = serde_json::to_value(t)?;
+            }
+            out.push(value);
+        }
+        println!("{}", serde_json::to_string_pretty(&out)?);
+        return Ok(());
+    }
+
+    for msg in messages.iter_mut() {
+        if let Some(t) = translations.get(&msg.id)
+            && !t.is_from(lang)
+        {
+            let from = t.detected_language.as_deref().unwrap_or("?");
+            msg.content = format!("{}\n\x1b[2m[{from} \u{2192} {lang}]\x1b[0m", t.text);
+        }
+    }
+    print_conversation(ctx, db, conv_id, messages, view).await
+}
+
+/// `tmz msg --delete`/`--delete-last`: delete a message you sent, in Teams
+/// and in the cache, after asking unless `--yes`.
+async fn handle_delete(
+    ctx: &RuntimeContext,
+    target: &str,
+    message_id: Option<String>,
+) -> Result<()> {
+    ctx.config.security.ensure_writable("deleting messages")?;
+    let db = ctx.open_cache().await?;
+    let conv_id = ctx.resolve_target(&db, target).await?;
+    let msg = match message_id {
+        Some(id) => db
+            .get_message(&conv_id, &id)
+            .await?
+            .ok_or_else(|| anyhow!("message {id} is not in the cache (try 'tmz sync')"))?,
+        None => db
+            .get_messages(&conv_id, 50)
+            .await?
+            .into_iter()
+            .rev()
+            .find(|m| m.is_from_me && !m.is_system_event())
+            .ok_or_else(|| anyhow!("no cached message of yours to delete (try 'tmz sync')"))?,
+    };
+    if !msg.is_from_me {
+        return Err(anyhow!(
+            "message {} was sent by {}; only your own messages can be deleted",
+            msg.id,
+            msg.from_display_name
+        ));
+    }
+    let about = format!("\"{}\" ({})", truncate(&msg.content, 50), msg.compose_time);
+    if ctx.common.dry_run {
+        println!("dry-run: would delete {about}");
+        return Ok(());
+    }
+    if !ctx.common.assume_yes {
+        if !io::stdin().is_terminal() {
+            return Err(anyhow!("not deleted: pass --yes to delete {about}"));
+        }
+        eprint!("Delete {about}? [y/N]: ");
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Not deleted.");
+            return Ok(());
+        }
+    }
+    let result = TeamsClient::new()?.delete_message(&conv_id, &msg.id).await;
+    ctx.audit().record("delete", Some(&conv_id), &result);
+    result?;
+    db.delete_message(&conv_id, &msg.id).await?;
+    println!("Deleted {about}");
+    Ok(())
+}
+
+/// Print the raw JSON of one cached message (`tmz msg --inspect`).
+async fn handle_inspect(ctx: &RuntimeContext, target: &str, message_id: &str) -> Result<()> {
+    let db = ctx.open_cache_read_only().await?;
+    let conv_id = ctx.resolve_target(&db, target).await?;
+    let msg = db
+        .get_message(&conv_id, message_id)
+        .await?
+        .ok_or_else(|| anyhow!("message {message_id} not found in cache for {conv_id}"))?;
+    let raw: serde_json::Value =
+        serde_json::from_str(&msg.raw_json).context("stored raw_json is not valid JSON")?;
+
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&raw)?);
+        return Ok(());
+    }
+
+    let color = ctx.stdout_color();
+    for line in tmz_core::inspect::inspect_lines(&raw) {
+        let rendered = line.iter().fold(String::new(), |mut out, token| {
+            use std::fmt::Write as _;
+            let sgr = match token.kind {
+                TokenKind::Key => "34",
+                TokenKind::String => "32",
+                TokenKind::Number => "33",
+                TokenKind::Literal => "35",
+                TokenKind::Note => "2",
+                TokenKind::Punct => "",
+            };
+            if color && !sgr.is_empty() {
+                let _ = write!(out, "\x1b[{sgr}m{}\x1b[0m", token.text);
+            } else {
+                out.push_str(&token.text);
+            }
+            out
+        });
+        println!("{rendered}");
+    }
+    Ok(())
+}
+
+/// Render message groups, with inline images when `images` names a protocol.
+async fn render_messages(
+    messages: &[tmz_core::CachedMessage],
+    db: &tmz_core::Cache,
+    images: Option<ImageProtocol>,
+    display: &DisplayConfig,
+) -> Result<()> {
+    let groups = group_messages(messages, display);
+    let mut prev_group: Option<&MessageGroup<'_>> = None;
+
+    for group in &groups {
+        print_bubble(group, prev_group, display);
+
+        if let Some(protocol) = images {
+            render_message_images(&group.messages, db, protocol).await;
+        }
+
+        prev_group = Some(group);
+    }
+
+    Ok(())
+}
+
+/// Render inline images for a set of messages.
+async fn render_message_images(
+    messages: &[&tmz_core::CachedMessage],
+    db: &tmz_core::Cache,
+    protocol: ImageProtocol,
+) {
+    for msg in messages {
+        let urls = tmz_core::kitty::extract_image_urls(&msg.content_html);
+        for url in &urls {
+            let image_data = if let Ok(Some(data)) = db.get_image(url).await {
+                Some(data)
+            } else if let Ok(client) = TeamsClient::new() {
+                match client.download_image(url).await {
+                    Ok(data) => {
+                        let _ = db.cache_image(url, &data, "image/png").await;
+                        Some(data)
+                    }
+                    Err(e) => {
+                        debug!("image download failed: {e}");
                         None
                     }
                 }
@@ -943,16 +4601,16 @@ async fn render_message_images(messages: &[&tmz_core::CachedMessage], db: &tmz_c
             };
 
             if let Some(data) = image_data
-                && let Err(e) = tmz_core::kitty::display_image(&data)
+                && let Err(e) = tmz_core::graphics::display_image(protocol, &data)
             {
-                debug!("kitty image render failed: {e}");
+                debug!("{protocol:?} image render failed: {e}");
             }
         }
     }
 }
 
 async fn handle_tldr(ctx: &RuntimeContext, num_chats: i64, per_chat: i64) -> Result<()> {
-    let db = ctx.open_cache().await?;
+    let db = ctx.open_cache_read_only().await?;
     let chat_groups = db.latest_across_chats(num_chats, per_chat).await?;
 
     if chat_groups.is_empty() {
@@ -991,10 +4649,10 @@ async fn handle_tldr(ctx: &RuntimeContext, num_chats: i64, per_chat: i64) -> Res
         };
         println!("\x1b[1m{name}\x1b[0m  \x1b[2m[{conv_type}]\x1b[0m");
 
-        let groups = group_messages(messages);
+        let groups = group_messages(messages, &ctx.config.display);
         let mut prev: Option<&MessageGroup<'_>> = None;
         for g in &groups {
-            print_bubble(g, prev);
+            print_bubble(g, prev, &ctx.config.display);
             prev = Some(g);
         }
         println!();
@@ -1003,43 +4661,247 @@ async fn handle_tldr(ctx: &RuntimeContext, num_chats: i64, per_chat: i64) -> Res
     Ok(())
 }
 
-async fn handle_search(
-    ctx: &RuntimeContext,
-    query: &str,
-    chat: Option<&str>,
-    limit: i64,
-) -> Result<()> {
-    let db = ctx.open_cache().await?;
+async fn handle_digest(ctx: &RuntimeContext, since: &str) -> Result<()> {
+    let since = tmz_core::dates::parse_point_in_time(since, chrono::Local::now())?;
+    let db = ctx.open_cache_read_only().await?;
+    let digest = tmz_core::digest::Digest::compile(&db, since).await?;
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&digest)?);
+    } else {
+        println!("{}", digest.to_text());
+    }
+    Ok(())
+}
 
-    let (results, scope_name) = if let Some(target) = chat {
-        let conv_id = ctx.resolve_target(&db, target).await?;
-        let convs = db.find_conversation(&conv_id).await?;
-        let name = convs
-            .first()
-            .map_or_else(|| conv_id.clone(), |c| c.display_name.clone());
-        let res = db.search_in_conversation(query, &conv_id, limit).await?;
-        (res, Some(name))
+/// Combine command-line search arguments with a saved search; explicit
+/// arguments take precedence over the saved values.
+fn resolve_search(ctx: &RuntimeContext, args: SearchArgs) -> Result<SavedSearch> {
+    let base = match args.saved.as_deref() {
+        Some(name) => ctx.config.searches.get(name).cloned().ok_or_else(|| {
+            let names: Vec<&str> = ctx
+                .config
+                .saved_searches()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect();
+            if names.is_empty() {
+                anyhow!("no saved search '{name}': add one under [searches] in the config")
+            } else {
+                anyhow!("no saved search '{name}'. Available: {}", names.join(", "))
+            }
+        })?,
+        None => SavedSearch {
+            query: args.query.unwrap_or_default(),
+            what: SearchScope::default(),
+            chat: None,
+            tag: None,
+            limit: None,
+            mentions_me: false,
+            include_files: false,
+        },
+    };
+    Ok(SavedSearch {
+        query: base.query,
+        mentions_me: args.mentions_me || base.mentions_me,
+        include_files: args.include_files || base.include_files,
+        what: args.what.map_or(base.what, SearchScope::from),
+        chat: args.chat.or(base.chat),
+        tag: args.tag.or(base.tag),
+        limit: Some(args.limit.or(base.limit).unwrap_or(20)),
+    })
+}
+
+async fn handle_search(ctx: &RuntimeContext, search: &SavedSearch) -> Result<()> {
+    let db = ctx.open_cache_read_only().await?;
+    let query = search.query.as_str();
+    let chat = search.chat.as_deref();
+    let limit = search.limit.unwrap_or(20);
+
+    // Chat scopes and mention filters only make sense for message search.
+    let what = if chat.is_some() || search.mentions_me {
+        SearchScope::Messages
+    } else {
+        search.what
+    };
+
+    // A tag filters afterwards, so everything is fetched first.
+    let tagged = match search.tag.as_deref() {
+        Some(tag) => Some(tagged_conversations(ctx, &db, tag).await?),
+        None => None,
+    };
+    let fetch = if tagged.is_some() { i64::MAX } else { limit };
+    let keep = |id: &String| tagged.as_ref().is_none_or(|ids| ids.contains(id));
+    let take = usize::try_from(limit).unwrap_or(0);
+
+    let chats: Vec<_> = if what == SearchScope::Messages {
+        Vec::new()
+    } else {
+        db.search_conversations(query, fetch).await?
+    };
+    let chats = chats
+        .into_iter()
+        .filter(|c| keep(&c.id))
+        .take(take)
+        .collect::<Vec<_>>();
+
+    let (results, files, scope_name) = if what == SearchScope::Chats {
+        (Vec::new(), Vec::new(), None)
     } else {
-        let res = db.search(query, limit).await?;
-        (res, None)
+        search_messages(ctx, &db, search, fetch).await?
     };
+    let results: Vec<_> = results
+        .into_iter()
+        .filter(|r| keep(&r.message.conversation_id))
+        .take(take)
+        .collect();
+    let files: Vec<_> = files
+        .into_iter()
+        .filter(|f| keep(&f.message.conversation_id))
+        .take(take)
+        .collect();
+    if search.include_files && !ctx.config.files.index {
+        eprintln!(
+            "\x1b[2mnote: [files] index is off; only files indexed earlier are searched\x1b[0m"
+        );
+    }
 
-    if results.is_empty() {
+    if results.is_empty() && chats.is_empty() && files.is_empty() {
+        let what = if search.mentions_me && query.is_empty() {
+            "No mentions of you".to_string()
+        } else {
+            format!("No results for '{query}'")
+        };
         if let Some(ref name) = scope_name {
-            println!("No results for '{query}' in {name}.");
+            println!("{what} in {name}.");
         } else {
-            println!("No results for '{query}'.");
+            println!("{what}.");
         }
         return Ok(());
     }
 
-    if ctx.common.json {
-        println!("{}", serde_json::to_string_pretty(&results)?);
-        return Ok(());
+    if let Some(format) = ctx.output_format() {
+        let json = match what {
+            SearchScope::Messages if search.include_files => {
+                serde_json::json!({ "messages": results, "files": files })
+            }
+            SearchScope::Messages => serde_json::to_value(&results)?,
+            SearchScope::Chats => serde_json::to_value(&chats)?,
+            SearchScope::All if search.include_files => {
+                serde_json::json!({ "chats": chats, "messages": results, "files": files })
+            }
+            SearchScope::All => serde_json::json!({ "chats": chats, "messages": results }),
+        };
+        return print_structured(&json, format);
+    }
+
+    if !chats.is_empty() {
+        print_chat_matches(query, &chats);
+    }
+    if !results.is_empty() {
+        print_message_matches(query, &results, scope_name.as_deref(), &ctx.config.display);
+    }
+    if !files.is_empty() {
+        print_file_matches(query, &files, scope_name.is_some());
+    }
+    Ok(())
+}
+
+/// Run the message half of a search, returning the matching messages, the
+/// shared files matching with `--include-files`, and the name of the chat
+/// it was scoped to.
+async fn search_messages(
+    ctx: &RuntimeContext,
+    db: &Cache,
+    search: &SavedSearch,
+    limit: i64,
+) -> Result<(Vec<SearchResult>, Vec<FileMatch>, Option<String>)> {
+    let scope = match search.chat.as_deref() {
+        Some(target) => {
+            let conv_id = ctx.resolve_target(db, target).await?;
+            let convs = db.find_conversation(&conv_id).await?;
+            let name = convs
+                .first()
+                .map_or_else(|| conv_id.clone(), |c| c.display_name.clone());
+            Some((conv_id, name))
+        }
+        None => None,
+    };
+    let conv_id = scope.as_ref().map(|(id, _)| id.as_str());
+    let query = search.query.as_str();
+
+    let results = if search.mentions_me {
+        let query = Some(query).filter(|q| !q.is_empty());
+        db.search_mentions(query, conv_id, limit).await?
+    } else if let Some(conv_id) = conv_id {
+        db.search_in_conversation(query, conv_id, limit).await?
+    } else {
+        db.search(query, limit).await?
+    };
+    let files = if search.include_files && !query.is_empty() {
+        db.search_files(query, conv_id, limit).await?
+    } else {
+        Vec::new()
+    };
+    Ok((results, files, scope.map(|(_, name)| name)))
+}
+
+/// Print shared files whose contents or names match, with an excerpt.
+fn print_file_matches(query: &str, files: &[FileMatch], scoped: bool) {
+    println!(
+        "\x1b[1m{}\x1b[0m file(s) matching '\x1b[1m{query}\x1b[0m'",
+        files.len()
+    );
+    for f in files {
+        let from = if f.message.from_display_name.is_empty() {
+            "(system)"
+        } else {
+            &f.message.from_display_name
+        };
+        let conv = if scoped || f.conversation_name.is_empty() {
+            String::new()
+        } else {
+            format!(" in {}", f.conversation_name)
+        };
+        let date = extract_date(&f.message.compose_time);
+        println!(
+            "  \x1b[1m{}\x1b[0m  \x1b[2m{from}{conv}, {date}\x1b[0m",
+            f.file_name
+        );
+        if !f.snippet.is_empty() {
+            let snippet = f.snippet.replace('[', "\x1b[1;33m").replace(']', "\x1b[0m");
+            println!("    {snippet}");
+        }
+        println!("    \x1b[2m{}\x1b[0m", f.url);
+    }
+    println!();
+}
+
+/// Print conversations matched by name, topic, or members.
+fn print_chat_matches(query: &str, chats: &[CachedConversation]) {
+    println!(
+        "\x1b[1m{}\x1b[0m chat(s) matching '\x1b[1m{query}\x1b[0m'",
+        chats.len()
+    );
+    for c in chats {
+        let kind = format_chat_type(&c.product_type, &c.id);
+        println!("  {kind:>9}  \x1b[1m{}\x1b[0m", c.display_name);
+        if !c.member_names.is_empty() {
+            println!("             \x1b[2m{}\x1b[0m", c.member_names);
+        }
+        println!("             \x1b[2m{}\x1b[0m", c.id);
     }
+    println!();
+}
 
+/// Print message search results grouped by date.
+fn print_message_matches(
+    query: &str,
+    results: &[SearchResult],
+    scope_name: Option<&str>,
+    display: &DisplayConfig,
+) {
     // Header
-    if let Some(ref name) = scope_name {
+    if let Some(name) = scope_name {
         println!(
             "\x1b[1m{}\x1b[0m result(s) for '\x1b[1m{query}\x1b[0m' in \x1b[1m{name}\x1b[0m",
             results.len()
@@ -1051,12 +4913,23 @@ async fn handle_search(
         );
     }
 
-    let w = term_width();
     let query_lower = query.to_lowercase();
     let query_words: Vec<&str> = query_lower.split_whitespace().collect();
+    print_message_stream(results, &query_words, scope_name.is_none(), display);
+}
 
+/// Prints messages from several conversations with date separators, naming
+/// each message's conversation when `show_conversation` is set and
+/// highlighting `query_words` in the text.
+fn print_message_stream(
+    results: &[SearchResult],
+    query_words: &[&str],
+    show_conversation: bool,
+    display: &DisplayConfig,
+) {
+    let w = term_width();
     let mut prev_date: Option<String> = None;
-    for r in &results {
+    for r in results {
         let date = extract_date(&r.message.compose_time);
 
         // Date separator
@@ -1078,14 +4951,13 @@ async fn handle_search(
         } else {
             &r.message.from_display_name
         };
-        let conv = if scope_name.is_some() || r.conversation_name.is_empty() {
+        let conv = if !show_conversation || r.conversation_name.is_empty() {
             String::new()
         } else {
             format!(" \x1b[2min {}\x1b[0m", r.conversation_name)
         };
 
-        let bar_color = if r.message.is_from_me { "36" } else { "33" };
-        let name_color = if r.message.is_from_me { "1;36" } else { "1;33" };
+        let (bar_color, name_color) = sender_sgr(name, r.message.is_from_me, display);
 
         // Header line
         let name_vis = visible_len(name) + visible_len(&conv);
@@ -1105,26 +4977,213 @@ async fn handle_search(
             if trimmed.is_empty() {
                 continue;
             }
-            let shortened = shorten_urls(trimmed, 50);
-            let highlighted = highlight_matches(&shortened, &query_words);
-            let wrapped = wrap_lines(&[highlighted], content_w_inner);
-            for wl in &wrapped {
-                println!("  \x1b[{bar_color}m\u{2502}\x1b[0m {wl}");
+            let shortened = shorten_urls(trimmed, 50);
+            let highlighted = highlight_matches(&shortened, query_words);
+            let wrapped = wrap_lines(&[highlighted], content_w_inner);
+            for wl in &wrapped {
+                println!("  \x1b[{bar_color}m\u{2502}\x1b[0m {wl}");
+            }
+        }
+        println!();
+    }
+}
+
+/// `tmz person-feed`: one person's cached messages from every chat,
+/// interleaved in time order.
+async fn handle_person_feed(ctx: &RuntimeContext, args: &PersonFeedArgs) -> Result<()> {
+    let since = args
+        .since
+        .as_deref()
+        .map(|s| tmz_core::dates::parse_point_in_time(s, chrono::Local::now()))
+        .transpose()?
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true));
+    let db = ctx.open_cache_read_only().await?;
+    let sender = resolve_sender(ctx, &db, &args.name).await?;
+    let feed = db
+        .messages_from(&sender, since.as_deref(), args.limit)
+        .await?;
+    if let Some(format) = ctx.output_format() {
+        return print_structured(&serde_json::to_value(&feed)?, format);
+    }
+    if feed.is_empty() {
+        println!("No cached messages from {sender}.");
+        return Ok(());
+    }
+    let chats: HashSet<&str> = feed
+        .iter()
+        .map(|r| r.message.conversation_id.as_str())
+        .collect();
+    println!(
+        "\x1b[1m{}\x1b[0m message(s) from \x1b[1m{sender}\x1b[0m in {} chat(s)",
+        feed.len(),
+        chats.len()
+    );
+    print_message_stream(&feed, &[], true, &ctx.config.display);
+    Ok(())
+}
+
+async fn handle_export(ctx: &RuntimeContext, args: ExportArgs) -> Result<()> {
+    let ExportArgs {
+        target,
+        format,
+        since,
+        until,
+        output,
+        limit,
+        images,
+    } = args;
+    if images {
+        let dir = output.unwrap_or_else(|| PathBuf::from("."));
+        return handle_export_images(
+            ctx,
+            &target,
+            since.as_deref(),
+            until.as_deref(),
+            &dir,
+            limit,
+        )
+        .await;
+    }
+    let (since, until) = export_bounds(since.as_deref(), until.as_deref())?;
+
+    let db = ctx.open_cache_read_only().await?;
+    let conv_id = ctx.resolve_target(&db, &target).await?;
+    let title = db
+        .find_conversation(&conv_id)
+        .await?
+        .first()
+        .map_or_else(|| conv_id.clone(), |c| c.display_name.clone());
+    let messages = db
+        .get_messages_between(&conv_id, since.as_deref(), until.as_deref(), limit)
+        .await?;
+
+    let rendered = match format {
+        ExportFormat::Md => {
+            tmz_core::export::messages_to_markdown(&title, &messages, &chrono::Local)
+        }
+        ExportFormat::Mbox => tmz_core::export::messages_to_mbox(&title, &messages),
+        ExportFormat::Eml => {
+            let dir = output.context("--format eml needs --output DIR")?;
+            if ctx.common.dry_run {
+                log::info!(
+                    "dry-run: would write {} messages to {}",
+                    messages.len(),
+                    dir.display()
+                );
+                return Ok(());
+            }
+            let written = tmz_core::export::export_eml(&title, &messages, &dir)?;
+            if !ctx.common.quiet {
+                eprintln!("Exported {written} messages to {}", dir.display());
+            }
+            return Ok(());
+        }
+    };
+
+    match output {
+        Some(path) => {
+            if ctx.common.dry_run {
+                log::info!(
+                    "dry-run: would write {} messages to {}",
+                    messages.len(),
+                    path.display()
+                );
+                return Ok(());
+            }
+            std::fs::write(&path, rendered)
+                .with_context(|| format!("writing {}", path.display()))?;
+            if !ctx.common.quiet {
+                eprintln!("Exported {} messages to {}", messages.len(), path.display());
             }
         }
-        println!();
+        None => print!("{rendered}"),
     }
-
     Ok(())
 }
 
-async fn handle_find(
+/// Parse `--since`/`--until` into the RFC 3339 form the cache compares.
+fn export_bounds(
+    since: Option<&str>,
+    until: Option<&str>,
+) -> Result<(Option<String>, Option<String>)> {
+    let now = chrono::Local::now();
+    let bound = |when: Option<&str>| -> Result<Option<String>> {
+        when.map(|w| {
+            tmz_core::dates::parse_point_in_time(w, now)
+                .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        })
+        .transpose()
+        .map_err(Into::into)
+    };
+    Ok((bound(since)?, bound(until)?))
+}
+
+/// Save a conversation's images to `dir` (`tmz export --images`).
+async fn handle_export_images(
     ctx: &RuntimeContext,
-    query: &str,
-    conv_type: Option<ConvTypeFilter>,
+    target: &str,
+    since: Option<&str>,
+    until: Option<&str>,
+    dir: &Path,
+    limit: i64,
 ) -> Result<()> {
+    let (since, until) = export_bounds(since, until)?;
     let db = ctx.open_cache().await?;
-    let all_matches = db.find_conversation(query).await?;
+    let conv_id = ctx.resolve_target(&db, target).await?;
+    let messages = db
+        .get_messages_between(&conv_id, since.as_deref(), until.as_deref(), limit)
+        .await?;
+    if ctx.common.dry_run {
+        let count: usize = messages
+            .iter()
+            .map(|m| tmz_core::kitty::extract_image_urls(&m.content_html).len())
+            .sum();
+        log::info!("dry-run: would export {count} images to {}", dir.display());
+        return Ok(());
+    }
+
+    // Only needed for images missing from the cache.
+    let client = TeamsClient::new().ok();
+    let report =
+        tmz_core::export::export_images(&db, client.as_ref(), &messages, dir, &chrono::Local)
+            .await?;
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if !ctx.common.quiet {
+        eprintln!(
+            "Exported {} images to {} ({} downloaded, {} already there).",
+            report.written,
+            dir.display(),
+            report.downloaded,
+            report.existing
+        );
+    }
+    if report.failed > 0 {
+        eprintln!(
+            "{} images could not be fetched (not cached; run 'tmz auth login'?).",
+            report.failed
+        );
+    }
+    Ok(())
+}
+
+async fn handle_find(ctx: &RuntimeContext, args: &FindArgs) -> Result<()> {
+    let query = args.query.as_str();
+    let conv_type = args.conv_type;
+    let tag = args.tag.as_deref();
+    let db = ctx.open_cache_read_only().await?;
+    let hidden = db.hidden_conversation_ids().await?;
+    let tagged = match tag {
+        Some(tag) => Some(tagged_conversations(ctx, &db, tag).await?),
+        None => None,
+    };
+    let all_matches: Vec<_> = db
+        .find_conversation(query)
+        .await?
+        .into_iter()
+        .filter(|c| !hidden.contains(&c.id))
+        .filter(|c| tagged.as_ref().is_none_or(|ids| ids.contains(&c.id)))
+        .collect();
 
     let matches: Vec<_> = if let Some(filter) = conv_type {
         all_matches
@@ -1135,26 +5194,23 @@ async fn handle_find(
         all_matches
     };
 
+    if args.plumbing {
+        print_plumbing(&matches);
+        return Ok(());
+    }
+
     if matches.is_empty() {
-        let hint = conv_type.map_or(String::new(), |f| format!(" (filter: {f:?})"));
+        let mut hint = conv_type.map_or(String::new(), |f| format!(" (filter: {f:?})"));
+        if let Some(tag) = tag {
+            use std::fmt::Write as _;
+            let _ = write!(hint, " tagged '{tag}'");
+        }
         println!("No conversations matching '{query}'{hint}. Run 'tmz sync' first.");
         return Ok(());
     }
 
-    if ctx.common.json {
-        let json: Vec<serde_json::Value> = matches
-            .iter()
-            .map(|c| {
-                serde_json::json!({
-                    "id": c.id,
-                    "display_name": c.display_name,
-                    "product_type": c.product_type,
-                    "last_activity": c.last_activity,
-                })
-            })
-            .collect();
-        println!("{}", serde_json::to_string_pretty(&json)?);
-        return Ok(());
+    if let Some(format) = ctx.output_format() {
+        return print_structured(&conversation_records(&matches), format);
     }
 
     println!("{} conversation(s) matching '{query}':\n", matches.len());
@@ -1172,16 +5228,12 @@ async fn handle_find(
     Ok(())
 }
 
-async fn handle_alias(
-    ctx: &RuntimeContext,
-    name: &str,
-    target: Option<String>,
-    conv_type: Option<ConvTypeFilter>,
-) -> Result<()> {
+async fn handle_alias(ctx: &RuntimeContext, args: &AliasArgs) -> Result<()> {
+    let (name, conv_type) = (args.name.as_str(), args.conv_type);
     let db = ctx.open_cache().await?;
 
-    let conv_id = if let Some(ref t) = target {
-        if t.starts_with("19:") {
+    let conv_id = if let Some(ref t) = args.target {
+        if cache::is_conversation_id(t) {
             // Direct conversation ID - no filtering needed
             t.clone()
         } else {
@@ -1223,7 +5275,10 @@ async fn handle_alias(
         .first()
         .map_or("(unknown)", |c| c.display_name.as_str());
 
-    AppConfig::add_alias(&ctx.paths.config_file, name, &conv_id)?;
+    ctx.config.security.ensure_writable("writing aliases")?;
+    let written = AppConfig::add_alias(&ctx.paths.config_file, name, &conv_id);
+    ctx.audit().record("alias.write", Some(name), &written);
+    written?;
     println!("Alias '{name}' -> {display}");
     println!("  ID: {conv_id}");
     println!("  Written to: {}", ctx.paths.config_file.display());
@@ -1238,9 +5293,8 @@ async fn handle_teams(ctx: &RuntimeContext, cmd: TeamsSubcommand) -> Result<()>
         TeamsSubcommand::List => {
             let teams = client.list_teams().await?;
 
-            if ctx.common.json {
-                println!("{}", serde_json::to_string_pretty(&teams)?);
-                return Ok(());
+            if let Some(format) = ctx.output_format() {
+                return print_structured(&serde_json::Value::from(teams), format);
             }
 
             for team in &teams {
@@ -1259,9 +5313,8 @@ async fn handle_teams(ctx: &RuntimeContext, cmd: TeamsSubcommand) -> Result<()>
         TeamsSubcommand::Channels { team_id } => {
             let channels = client.list_channels(&team_id).await?;
 
-            if ctx.common.json {
-                println!("{}", serde_json::to_string_pretty(&channels)?);
-                return Ok(());
+            if let Some(format) = ctx.output_format() {
+                return print_structured(&serde_json::Value::from(channels), format);
             }
 
             for ch in &channels {
@@ -1275,6 +5328,113 @@ async fn handle_teams(ctx: &RuntimeContext, cmd: TeamsSubcommand) -> Result<()>
     }
 }
 
+async fn handle_channel(ctx: &RuntimeContext, cmd: ChannelCommand) -> Result<()> {
+    match cmd {
+        ChannelCommand::Post {
+            target,
+            message,
+            subject,
+            important,
+            markup,
+            silent,
+        } => {
+            ctx.config.security.ensure_writable("posting to channels")?;
+            lint_outgoing(ctx, &message)?;
+            let silent = silent || ctx.config.sending.silent;
+            if silent && important {
+                eprintln!("note: [sending] silent is on; the post is not marked important.");
+            }
+            let client = TeamsClient::new()?;
+            let (channel_id, label) = resolve_channel(&client, &target).await?;
+            let options = tmz_core::teams::PostOptions {
+                subject: subject.filter(|s| !s.trim().is_empty()),
+                importance: (important && !silent)
+                    .then_some(tmz_core::teams::MessageImportance::High),
+            };
+            let what = options
+                .subject
+                .as_ref()
+                .map_or_else(|| "post".to_string(), |s| format!("'{s}'"));
+            if ctx.common.dry_run {
+                println!("dry-run: would post {what} to {label}");
+                return Ok(());
+            }
+            let mut content = BodyFormat::from(markup).to_html(&message);
+            if silent {
+                content = markup::silence(&content);
+            }
+            let result = client.send_post(&channel_id, &content, &options).await;
+            ctx.audit()
+                .record("channel.post", Some(&channel_id), &result);
+            result?;
+            println!("Posted {what} to {label}");
+            Ok(())
+        }
+    }
+}
+
+/// Resolve `team/channel` (display names, case-insensitive; a unique
+/// prefix is enough) or a channel ID to the channel's ID and a label.
+async fn resolve_channel(client: &TeamsClient, target: &str) -> Result<(String, String)> {
+    if target.starts_with("19:") {
+        return Ok((target.to_string(), target.to_string()));
+    }
+    let (team_name, channel_name) = target
+        .split_once('/')
+        .ok_or_else(|| anyhow!("expected 'team/channel' or a channel ID, got '{target}'"))?;
+    let teams = client.list_teams().await?;
+    let team = pick_by_name(&teams, team_name, "team")?;
+    let team_id = team["id"].as_str().unwrap_or_default();
+    let channels = client.list_channels(team_id).await?;
+    let channel = pick_by_name(&channels, channel_name, "channel")?;
+    let id = channel["id"]
+        .as_str()
+        .ok_or_else(|| anyhow!("channel '{channel_name}' has no ID"))?;
+    Ok((
+        id.to_string(),
+        format!(
+            "{}/{}",
+            team["displayName"].as_str().unwrap_or(team_name),
+            channel["displayName"].as_str().unwrap_or(channel_name)
+        ),
+    ))
+}
+
+/// The Graph object whose `displayName` is `name`, or the only one starting
+/// with it (both case-insensitive).
+fn pick_by_name<'a>(
+    items: &'a [serde_json::Value],
+    name: &str,
+    kind: &str,
+) -> Result<&'a serde_json::Value> {
+    let name = name.trim().to_lowercase();
+    let display = |item: &serde_json::Value| {
+        item["displayName"]
+            .as_str()
+            .unwrap_or_default()
+            .to_lowercase()
+    };
+    if let Some(exact) = items.iter().find(|item| display(item) == name) {
+        return Ok(exact);
+    }
+    let matches: Vec<_> = items
+        .iter()
+        .filter(|item| display(item).starts_with(&name))
+        .collect();
+    match matches.as_slice() {
+        [one] => Ok(one),
+        [] => Err(anyhow!("no {kind} named '{name}'")),
+        _ => Err(anyhow!(
+            "'{name}' matches several {kind}s: {}",
+            matches
+                .iter()
+                .filter_map(|item| item["displayName"].as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
 async fn handle_service(ctx: &RuntimeContext, cmd: ServiceCommand) -> Result<()> {
     use tmz_core::daemon;
 
@@ -1292,7 +5452,7 @@ async fn handle_service(ctx: &RuntimeContext, cmd: ServiceCommand) -> Result<()>
             }
             service_start()
         }
-        ServiceCommand::Status => service_status(ctx),
+        ServiceCommand::Status => service_status(ctx).await,
         ServiceCommand::Enable => service_enable(),
         ServiceCommand::Disable => service_disable(),
         ServiceCommand::Run => daemon::run_daemon().await.map_err(|e| anyhow!("{e}")),
@@ -1322,7 +5482,11 @@ fn service_start() -> Result<()> {
         .append(true)
         .open(&log_path)?;
 
-    let child = std::process::Command::new(exe)
+    let mut command = std::process::Command::new(exe);
+    if let Some(account) = tmz_core::accounts::active() {
+        command.env(tmz_core::accounts::ACCOUNT_ENV, account);
+    }
+    let child = command
         .args(["service", "run"])
         .stdin(std::process::Stdio::null())
         .stdout(log_file.try_clone()?)
@@ -1334,7 +5498,39 @@ fn service_start() -> Result<()> {
     Ok(())
 }
 
-fn service_status(_ctx: &RuntimeContext) -> Result<()> {
+/// The daemon's link to the chat service, e.g. "up, 2 reconnects (last
+/// after a 41m outage)".
+fn connection_summary(stats: &tmz_core::live::ConnectionStats) -> String {
+    let outage = |secs: i64| {
+        if secs >= 3600 {
+            format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+        } else if secs >= 60 {
+            format!("{}m", secs / 60)
+        } else {
+            format!("{secs}s")
+        }
+    };
+    let link = match stats.down_since {
+        Some(since) => format!(
+            "down for {}, reconnecting (attempt {})",
+            outage(chrono::Utc::now().timestamp() - since),
+            stats.attempts
+        ),
+        None if stats.connected => "up".to_string(),
+        None => "not synced yet".to_string(),
+    };
+    match (stats.reconnects, stats.last_outage_secs) {
+        (0, _) => link,
+        (n, Some(secs)) => format!(
+            "{link}, {n} reconnect{} (last after a {} outage)",
+            if n == 1 { "" } else { "s" },
+            outage(secs)
+        ),
+        (n, None) => format!("{link}, {n} reconnects"),
+    }
+}
+
+async fn service_status(_ctx: &RuntimeContext) -> Result<()> {
     use tmz_core::daemon;
 
     if daemon::is_running()? {
@@ -1343,6 +5539,23 @@ fn service_status(_ctx: &RuntimeContext) -> Result<()> {
         println!("running  (pid={pid})");
         println!("log:     {}", log_path.display());
 
+        let socket = engine::socket_path()?;
+        match EngineClient::connect().await {
+            Some(mut client) => {
+                println!("engine:  {}", socket.display());
+                if let Ok(status) = client.status().await {
+                    if let Some(report) = status.last_sync {
+                        println!(
+                            "sync:    {} conversations, {} messages (last run)",
+                            report.conversations, report.messages
+                        );
+                    }
+                    println!("link:    {}", connection_summary(&status.connection));
+                }
+            }
+            None => println!("engine:  not reachable ({})", socket.display()),
+        }
+
         let auth = AuthManager::new()?;
         match auth.get_tokens() {
             Ok(tokens) => {
@@ -1369,6 +5582,12 @@ fn service_status(_ctx: &RuntimeContext) -> Result<()> {
 fn service_enable() -> Result<()> {
     use tmz_core::daemon;
 
+    if let Some(account) = tmz_core::accounts::active() {
+        return Err(anyhow!(
+            "the system service runs the default account; use 'tmz --account {account} service start' instead"
+        ));
+    }
+
     let exe =
         std::env::current_exe().map_err(|e| anyhow!("cannot determine executable path: {e}"))?;
     let exe_str = exe.to_string_lossy();
@@ -1428,72 +5647,544 @@ fn service_disable() -> Result<()> {
             println!("Service not installed.");
         }
     } else {
-        let _ = std::process::Command::new("systemctl")
-            .args(["--user", "disable", "--now", "tmz.service"])
-            .status();
-        let unit_path = home.join(".config/systemd/user/tmz.service");
-        if unit_path.exists() {
-            std::fs::remove_file(&unit_path)?;
-            let _ = std::process::Command::new("systemctl")
-                .args(["--user", "daemon-reload"])
-                .status();
+        let _ = std::process::Command::new("systemctl")
+            .args(["--user", "disable", "--now", "tmz.service"])
+            .status();
+        let unit_path = home.join(".config/systemd/user/tmz.service");
+        if unit_path.exists() {
+            std::fs::remove_file(&unit_path)?;
+            let _ = std::process::Command::new("systemctl")
+                .args(["--user", "daemon-reload"])
+                .status();
+        }
+        println!("Service disabled (systemd).");
+    }
+    Ok(())
+}
+
+fn handle_init(ctx: &RuntimeContext, cmd: InitCommand) -> Result<()> {
+    if ctx.paths.config_file.exists() && !(cmd.force || ctx.common.assume_yes) {
+        return Err(anyhow!(
+            "config already exists at {} (use --force to overwrite)",
+            ctx.paths.config_file.display()
+        ));
+    }
+    if ctx.common.dry_run {
+        log::info!(
+            "dry-run: would write default config to {}",
+            ctx.paths.config_file.display()
+        );
+        return Ok(());
+    }
+    write_default_config(&ctx.paths.config_file)
+}
+
+async fn handle_cache(ctx: &RuntimeContext, command: CacheCommand) -> Result<()> {
+    match command {
+        CacheCommand::Stats => {
+            let db = ctx.open_cache_read_only().await?;
+            let stats = db.stats().await?;
+            if ctx.common.json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                println!("Conversations: {}", stats.conversations);
+                println!("Messages:      {}", stats.messages);
+                println!(
+                    "Images:        {} ({})",
+                    stats.images,
+                    format_bytes(stats.image_bytes)
+                );
+            }
+        }
+        CacheCommand::Prune { days } => {
+            let db = ctx.open_cache().await?;
+            let pruned = db.prune_images(days).await;
+            ctx.audit()
+                .record("cache.prune", Some(&format!("images > {days}d")), &pruned);
+            let pruned = pruned?;
+            println!("Pruned {pruned} images older than {days} days.");
+            let stats = db.stats().await?;
+            println!(
+                "Remaining: {} images ({})",
+                stats.images,
+                format_bytes(stats.image_bytes)
+            );
+        }
+        CacheCommand::Retention { apply } => show_retention(ctx, apply).await?,
+        CacheCommand::IndexFiles { limit } => {
+            if !ctx.config.files.index {
+                return Err(anyhow!(
+                    "File indexing is disabled. Set [files] index = true in {}",
+                    ctx.paths.config_file.display()
+                ));
+            }
+            let db = ctx.open_cache().await?;
+            let client = TeamsClient::new()?;
+            eprint!("Downloading and indexing shared files... ");
+            let indexed =
+                tmz_core::files::index_pending(&db, &client, &ctx.config.files, limit).await?;
+            eprintln!("{indexed} files indexed.");
+        }
+        CacheCommand::Ocr { limit } => {
+            if !ctx.config.ocr.enabled {
+                return Err(anyhow!(
+                    "OCR is disabled. Set [ocr] enabled = true in {}",
+                    ctx.paths.config_file.display()
+                ));
+            }
+            let db = ctx.open_cache().await?;
+            eprint!("Running OCR over cached images... ");
+            let indexed = tmz_core::ocr::index_pending(&db, &ctx.config.ocr, limit).await?;
+            eprintln!("{indexed} images indexed.");
+        }
+        CacheCommand::Compact => compact_cache(ctx).await?,
+    }
+    Ok(())
+}
+
+async fn compact_cache(ctx: &RuntimeContext) -> Result<()> {
+    let db = ctx.open_cache().await?;
+    eprint!(
+        "{} raw JSON and vacuuming the cache... ",
+        if ctx.config.cache.compress_raw_json {
+            "Compressing"
+        } else {
+            "Decompressing"
+        }
+    );
+    let report = db.compact_raw_json().await;
+    ctx.audit().record("cache.compact", None, &report);
+    let report = report?;
+    eprintln!("done.");
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    println!("Rows rewritten:     {}", report.rewritten);
+    println!(
+        "Raw JSON:           {} -> {}",
+        format_bytes(report.raw_json_bytes_before),
+        format_bytes(report.raw_json_bytes_after)
+    );
+    println!(
+        "Database file:      {} -> {} ({} saved)",
+        format_bytes(report.file_bytes_before),
+        format_bytes(report.file_bytes_after),
+        format_bytes(report.file_bytes_before - report.file_bytes_after)
+    );
+    Ok(())
+}
+
+/// Outcome of a single `tmz doctor` check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckLevel {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl CheckLevel {
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+        }
+    }
+}
+
+/// `tmz cache retention`: list what `[retention]` prunes, and with
+/// `apply` prune it.
+async fn show_retention(ctx: &RuntimeContext, apply: bool) -> Result<()> {
+    let policy = tmz_core::retention::RetentionPolicy::new(&ctx.config);
+    if policy.keeps_everything() {
+        println!("No [retention] policy: cached messages are kept forever.");
+        return Ok(());
+    }
+    let apply = apply && !ctx.common.dry_run;
+    let db = ctx.open_cache().await?;
+    let result = tmz_core::retention::apply(&db, &policy, !apply).await;
+    if apply {
+        ctx.audit()
+            .record("cache.prune", Some("retention"), &result);
+    }
+    let mut pruned = result?;
+    pruned.sort_by_key(|p| std::cmp::Reverse(p.messages));
+
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&pruned)?);
+        return Ok(());
+    }
+    if pruned.is_empty() {
+        println!("Nothing to prune.");
+        return Ok(());
+    }
+    for p in &pruned {
+        println!(
+            "{:>7}  {}  {}",
+            p.messages,
+            dim(&format!("> {}d", p.days)),
+            truncate(&p.name, 60)
+        );
+    }
+    let total: u64 = pruned.iter().map(|p| p.messages).sum();
+    if apply {
+        println!(
+            "Pruned {total} messages from {} conversations.",
+            pruned.len()
+        );
+    } else {
+        println!(
+            "{total} messages in {} conversations are due; the next sync prunes them (or run with --apply).",
+            pruned.len()
+        );
+    }
+    Ok(())
+}
+
+async fn handle_doctor(ctx: &RuntimeContext) -> Result<()> {
+    let checks = doctor_checks(ctx).await;
+
+    if ctx.common.json {
+        let json: Vec<_> = checks
+            .iter()
+            .map(|(name, level, detail)| {
+                serde_json::json!({ "check": name, "status": level.label(), "detail": detail })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        for (name, level, detail) in &checks {
+            println!("{:<5} {name:<16} {detail}", level.label());
+        }
+    }
+
+    if checks
+        .iter()
+        .any(|(_, level, _)| *level == CheckLevel::Fail)
+    {
+        return Err(anyhow!("doctor found problems"));
+    }
+    Ok(())
+}
+
+async fn doctor_checks(ctx: &RuntimeContext) -> Vec<(&'static str, CheckLevel, String)> {
+    let mut checks: Vec<(&str, CheckLevel, String)> = Vec::new();
+
+    let config = &ctx.paths.config_file;
+    checks.push(if config.exists() {
+        ("config", CheckLevel::Ok, config.display().to_string())
+    } else {
+        (
+            "config",
+            CheckLevel::Warn,
+            format!("{} missing (run 'tmz init')", config.display()),
+        )
+    });
+
+    checks.push(tmz_core::teams::auth::find_auth_script().map_or_else(
+        |_| {
+            (
+                "auth script",
+                CheckLevel::Warn,
+                "not installed (only needed for 'tmz auth login --browser')".to_string(),
+            )
+        },
+        |path| ("auth script", CheckLevel::Ok, path.display().to_string()),
+    ));
+
+    checks.push(
+        match std::process::Command::new("node").arg("--version").output() {
+            Ok(out) if out.status.success() => (
+                "node",
+                CheckLevel::Ok,
+                String::from_utf8_lossy(&out.stdout).trim().to_string(),
+            ),
+            _ => (
+                "node",
+                CheckLevel::Warn,
+                "not found (only needed for 'tmz auth login --browser')".to_string(),
+            ),
+        },
+    );
+
+    checks.push(doctor_tokens());
+
+    if let Some(reason) = tmz_core::daemon::check_reauth_needed() {
+        checks.push(("reauth", CheckLevel::Warn, reason));
+    }
+
+    let profile = &ctx.paths.browser_profile_dir;
+    checks.push(if profile.exists() {
+        let size = tmz_core::paths::disk_usage(profile);
+        (
+            "browser profile",
+            CheckLevel::Ok,
+            format!(
+                "{} ({})",
+                profile.display(),
+                format_bytes(i64::try_from(size).unwrap_or(i64::MAX))
+            ),
+        )
+    } else {
+        (
+            "browser profile",
+            CheckLevel::Warn,
+            format!(
+                "{} missing (headless refresh needs a prior login)",
+                profile.display()
+            ),
+        )
+    });
+
+    checks.push(doctor_cache(ctx).await);
+
+    checks.push(match tmz_core::daemon::is_running() {
+        Ok(true) => ("daemon", CheckLevel::Ok, "running".to_string()),
+        Ok(false) => ("daemon", CheckLevel::Warn, "stopped".to_string()),
+        Err(e) => ("daemon", CheckLevel::Warn, e.to_string()),
+    });
+    checks
+}
+
+async fn handle_debug(ctx: &RuntimeContext, command: DebugCommand) -> Result<()> {
+    let (output, errors, log_lines) = match command {
+        DebugCommand::Bundle {
+            output,
+            errors,
+            log_lines,
+        } => (output, errors, log_lines),
+        DebugCommand::LastRequests { limit, failed } => {
+            return print_last_requests(ctx, limit, failed);
+        }
+    };
+    let output = output.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "tmz-debug-{}.tar.gz",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ))
+    });
+    let files = debug_bundle_files(ctx, errors, log_lines).await?;
+    if ctx.common.dry_run {
+        log::info!("dry-run: would write debug bundle to {}", output.display());
+        for file in &files {
+            println!("{}", file.name);
+        }
+        return Ok(());
+    }
+    let path = tmz_core::bundle::write_bundle(&files, &output)?;
+    println!("Wrote {}", path.display());
+    println!("Secrets, emails, and your home directory are masked; check it before attaching.");
+    Ok(())
+}
+
+/// `tmz debug last-requests`: the newest traced HTTP requests.
+fn print_last_requests(ctx: &RuntimeContext, limit: usize, failed: bool) -> Result<()> {
+    let is_failure = |r: &tmz_core::trace::TraceRecord| r.status.is_none_or(|s| s >= 400);
+    let records: Vec<_> = if failed {
+        let all = tmz_core::trace::last_requests(&ctx.paths.state_dir, usize::MAX)?;
+        let failures: Vec<_> = all.into_iter().filter(is_failure).collect();
+        let skip = failures.len().saturating_sub(limit);
+        failures.into_iter().skip(skip).collect()
+    } else {
+        tmz_core::trace::last_requests(&ctx.paths.state_dir, limit)?
+    };
+    if ctx.common.json {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
+    if records.is_empty() {
+        println!(
+            "No traced requests. Run a command with --trace-http or set [logging] trace_http = true."
+        );
+        return Ok(());
+    }
+    for r in &records {
+        let time = chrono::DateTime::parse_from_rfc3339(&r.time).map_or_else(
+            |_| r.time.clone(),
+            |t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%H:%M:%S%.3f")
+                    .to_string()
+            },
+        );
+        let status = r
+            .status
+            .map_or_else(|| "---".to_string(), |s| s.to_string());
+        let status = if is_failure(r) {
+            format!("\x1b[31m{status}\x1b[0m")
+        } else {
+            status
+        };
+        println!(
+            "{time} {:<6} {status} {:>6}ms  {}",
+            r.method,
+            r.latency_ms,
+            truncate(&r.url, 90)
+        );
+        let ids = r.request_id.as_ref().map_or_else(
+            || format!("client-request-id {}", r.client_request_id),
+            |id| format!("request-id {id}, client-request-id {}", r.client_request_id),
+        );
+        println!("             {}", dim(&ids));
+        if let Some(ref error) = r.error {
+            println!("             \x1b[31m{error}\x1b[0m");
         }
-        println!("Service disabled (systemd).");
     }
     Ok(())
 }
 
-fn handle_init(ctx: &RuntimeContext, cmd: InitCommand) -> Result<()> {
-    if ctx.paths.config_file.exists() && !(cmd.force || ctx.common.assume_yes) {
-        return Err(anyhow!(
-            "config already exists at {} (use --force to overwrite)",
-            ctx.paths.config_file.display()
+/// Collect the files of a debug bundle (redacted when written).
+async fn debug_bundle_files(
+    ctx: &RuntimeContext,
+    errors: usize,
+    log_lines: usize,
+) -> Result<Vec<BundleFile>> {
+    let mut files = vec![BundleFile::new(
+        "version.txt",
+        format!(
+            "tmz {}
+os: {} {}
+profile: {}
+",
+            env!("CARGO_PKG_VERSION"),
+            env::consts::OS,
+            env::consts::ARCH,
+            ctx.config.profile
+        ),
+    )];
+
+    let checks = doctor_checks(ctx).await;
+    files.push(BundleFile::new(
+        "doctor.txt",
+        checks
+            .iter()
+            .map(|(name, level, detail)| format!("{:<5} {name:<16} {detail}", level.label()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    ));
+    files.push(BundleFile::new(
+        "config.toml",
+        tmz_core::bundle::redact_config(&ctx.config)?,
+    ));
+    if ctx.paths.data_dir.join("cache.db").exists() {
+        let stats = ctx.open_cache_read_only().await?.stats().await?;
+        files.push(BundleFile::new(
+            "cache-stats.json",
+            serde_json::to_string_pretty(&stats)?,
         ));
     }
-    if ctx.common.dry_run {
-        log::info!(
-            "dry-run: would write default config to {}",
-            ctx.paths.config_file.display()
+
+    let mut logs = vec![("daemon.log", tmz_core::daemon::log_file_path()?)];
+    if let Some(ref file) = ctx.config.logging.file {
+        logs.push(("tmz.log", PathBuf::from(file)));
+    }
+    let mut events = Vec::new();
+    for (name, path) in logs {
+        let Some(tail) = tmz_core::bundle::tail_file(&path, log_lines) else {
+            continue;
+        };
+        events.extend(
+            tmz_core::bundle::error_events(&tail, errors)
+                .into_iter()
+                .map(|line| format!("{name}: {line}")),
         );
-        return Ok(());
+        files.push(BundleFile::new(name, tail));
     }
-    write_default_config(&ctx.paths.config_file)
+    events.extend(
+        ctx.audit()
+            .read(None)?
+            .into_iter()
+            .filter(|r| r.outcome != "ok")
+            .map(|r| {
+                format!(
+                    "audit: {} {} {} {}: {}",
+                    r.ts,
+                    r.source,
+                    r.action,
+                    r.target.unwrap_or_default(),
+                    r.detail.unwrap_or_default()
+                )
+            }),
+    );
+    files.push(BundleFile::new(
+        "errors.txt",
+        events[events.len().saturating_sub(errors)..].join("\n"),
+    ));
+    let trace = tmz_core::trace::trace_path(&ctx.paths.state_dir);
+    if let Some(tail) = tmz_core::bundle::tail_file(&trace, log_lines) {
+        files.push(BundleFile::new("http-trace.jsonl", tail));
+    }
+    Ok(files)
 }
 
-async fn handle_cache(ctx: &RuntimeContext, command: CacheCommand) -> Result<()> {
-    let db = ctx.open_cache().await?;
-    match command {
-        CacheCommand::Stats => {
-            let stats = db.stats().await?;
-            if ctx.common.json {
-                println!("{}", serde_json::to_string_pretty(&stats)?);
+fn doctor_tokens() -> (&'static str, CheckLevel, String) {
+    let Ok(auth) = AuthManager::new() else {
+        return (
+            "tokens",
+            CheckLevel::Fail,
+            "cannot resolve token storage".to_string(),
+        );
+    };
+    match auth.stored_tokens() {
+        Ok(tokens) => {
+            let missing: Vec<&str> = TokenAudience::ALL
+                .iter()
+                .filter(|a| tokens.token_for(**a).is_empty())
+                .map(|a| a.as_str())
+                .collect();
+            let expiry = format_expiry(tokens.expires_at);
+            if tokens.expires_at <= chrono::Utc::now().timestamp() {
+                ("tokens", CheckLevel::Warn, format!("expired {expiry}"))
+            } else if missing.is_empty() {
+                ("tokens", CheckLevel::Ok, format!("valid until {expiry}"))
             } else {
-                println!("Conversations: {}", stats.conversations);
-                println!("Messages:      {}", stats.messages);
-                println!(
-                    "Images:        {} ({})",
-                    stats.images,
-                    format_bytes(stats.image_bytes)
-                );
+                (
+                    "tokens",
+                    CheckLevel::Warn,
+                    format!("missing {} token(s)", missing.join(", ")),
+                )
             }
         }
-        CacheCommand::Prune { days } => {
-            let pruned = db.prune_images(days).await?;
-            println!("Pruned {pruned} images older than {days} days.");
-            let stats = db.stats().await?;
-            println!(
-                "Remaining: {} images ({})",
-                stats.images,
-                format_bytes(stats.image_bytes)
-            );
-        }
+        Err(_) => (
+            "tokens",
+            CheckLevel::Warn,
+            "none stored (run 'tmz auth login')".to_string(),
+        ),
+    }
+}
+
+async fn doctor_cache(ctx: &RuntimeContext) -> (&'static str, CheckLevel, String) {
+    let db_path = ctx.paths.data_dir.join("cache.db");
+    if !db_path.exists() {
+        return (
+            "cache",
+            CheckLevel::Warn,
+            format!("{} missing (run 'tmz sync')", db_path.display()),
+        );
+    }
+    let size = std::fs::metadata(&db_path).map_or(0, |m| m.len());
+    let size = format_bytes(i64::try_from(size).unwrap_or(i64::MAX));
+    match ctx.open_cache_read_only().await {
+        Ok(db) => match db.stats().await {
+            Ok(stats) => (
+                "cache",
+                CheckLevel::Ok,
+                format!(
+                    "{size}, {} conversations, {} messages",
+                    stats.conversations, stats.messages
+                ),
+            ),
+            Err(e) => ("cache", CheckLevel::Fail, e.to_string()),
+        },
+        Err(e) => ("cache", CheckLevel::Fail, e.to_string()),
     }
-    Ok(())
 }
 
 fn handle_config(ctx: &RuntimeContext, command: ConfigCommand) -> Result<()> {
     match command {
-        ConfigCommand::Show => {
+        ConfigCommand::Show { origin: true } => show_config_origins(ctx),
+        ConfigCommand::Show { origin: false } => {
             if ctx.common.json {
                 println!(
                     "{}",
@@ -1514,9 +6205,12 @@ fn handle_config(ctx: &RuntimeContext, command: ConfigCommand) -> Result<()> {
             if ctx.common.json {
                 let paths = serde_json::json!({
                     "config": ctx.paths.config_file,
+                    "system_config": ctx.paths.system_config_file,
+                    "project_config": ctx.paths.project_config_file,
                     "data": ctx.paths.data_dir,
                     "state": ctx.paths.state_dir,
                     "cache": cache_dir,
+                    "browser_profile": ctx.paths.browser_profile_dir,
                 });
                 println!(
                     "{}",
@@ -1527,9 +6221,19 @@ fn handle_config(ctx: &RuntimeContext, command: ConfigCommand) -> Result<()> {
                 println!("data:   {}", ctx.paths.data_dir.display());
                 println!("state:  {}", ctx.paths.state_dir.display());
                 println!("cache:  {}", cache_dir.display());
+                println!(
+                    "browser profile: {}",
+                    ctx.paths.browser_profile_dir.display()
+                );
+                println!("system config: {}", ctx.paths.system_config_file.display());
+                if let Some(ref project) = ctx.paths.project_config_file {
+                    println!("project config: {}", project.display());
+                }
             }
             Ok(())
         }
+        ConfigCommand::Env { active } => show_config_env(ctx, active),
+        ConfigCommand::Migrate => migrate_config(ctx),
         ConfigCommand::Schema => {
             println!("{}", include_str!("../../../examples/config.schema.json"));
             Ok(())
@@ -1542,9 +6246,161 @@ fn handle_config(ctx: &RuntimeContext, command: ConfigCommand) -> Result<()> {
                 );
                 return Ok(());
             }
-            write_default_config(&ctx.paths.config_file)
+            let written = write_default_config(&ctx.paths.config_file);
+            ctx.audit().record(
+                "config.reset",
+                Some(&ctx.paths.config_file.display().to_string()),
+                &written,
+            );
+            written
+        }
+    }
+}
+
+/// `tmz config show --origin`: each effective value with the layer that set it.
+fn show_config_origins(ctx: &RuntimeContext) -> Result<()> {
+    let layers = ctx.paths.config_layers();
+    let origins = ctx.config.origins(&layers)?;
+    if ctx.common.json {
+        let values: serde_json::Map<String, serde_json::Value> = origins
+            .into_iter()
+            .map(|(key, value, layer)| {
+                (key, serde_json::json!({ "value": value, "origin": layer }))
+            })
+            .collect();
+        let files: Vec<_> = layers
+            .iter()
+            .map(|(layer, path)| {
+                serde_json::json!({ "layer": layer, "path": path, "exists": path.is_file() })
+            })
+            .collect();
+        let json = serde_json::json!({ "files": files, "values": values });
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+
+    for (layer, path) in &layers {
+        let state = if path.is_file() { "" } else { "  (not found)" };
+        println!(
+            "\x1b[2m# {:<8} {}{state}\x1b[0m",
+            layer.label(),
+            path.display()
+        );
+    }
+    println!();
+    let width = origins
+        .iter()
+        .map(|(key, value, _)| key.len() + value.to_string().len() + 3)
+        .max()
+        .unwrap_or(0)
+        .min(60);
+    for (key, value, layer) in origins {
+        let line = format!("{key} = {value}");
+        let tint = if layer == tmz_core::ConfigLayer::Default {
+            "2"
+        } else {
+            "36"
+        };
+        println!("{line:<width$}  \x1b[{tint}m{}\x1b[0m", layer.label());
+    }
+    Ok(())
+}
+
+fn print_migration(report: &tmz_core::migrate::MigrationReport) {
+    let verb = if report.backup.is_some() {
+        "Migrated"
+    } else {
+        "Would migrate"
+    };
+    eprintln!(
+        "\x1b[33m{verb}\x1b[0m {} from config_version {} to {}:",
+        report.path.display(),
+        report.from,
+        report.to
+    );
+    for change in &report.changes {
+        eprintln!("  - {change}");
+    }
+    if let Some(ref backup) = report.backup {
+        eprintln!("  backup: {}", backup.display());
+    }
+}
+
+/// `tmz config migrate`: migrate every config layer and list unknown keys.
+fn migrate_config(ctx: &RuntimeContext) -> Result<()> {
+    let mut results = Vec::new();
+    for (layer, path) in ctx.paths.config_layers() {
+        if !path.is_file() {
+            continue;
+        }
+        let report = tmz_core::migrate::migrate_file(&path, ctx.common.dry_run)?;
+        // Check the keys as they are after migrating, also on a dry run.
+        let mut table: toml::Table = toml::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        tmz_core::migrate::migrate(&mut table);
+        let unknown = tmz_core::migrate::unknown_keys(&table);
+        results.push((layer, path, report, unknown));
+    }
+
+    if ctx.common.json {
+        let json: Vec<_> = results
+            .iter()
+            .map(|(layer, path, report, unknown)| {
+                serde_json::json!({
+                    "layer": layer,
+                    "path": path,
+                    "migration": report,
+                    "unknown_keys": unknown,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+    for (layer, path, report, unknown) in &results {
+        match report {
+            Some(report) => print_migration(report),
+            None => println!("{} ({}): nothing to migrate", path.display(), layer.label()),
+        }
+        for key in unknown {
+            println!("  \x1b[33munknown key\x1b[0m {key} (ignored)");
+        }
+    }
+    Ok(())
+}
+
+/// `tmz config env`: every environment override, marking the ones set.
+fn show_config_env(ctx: &RuntimeContext, active_only: bool) -> Result<()> {
+    let mut overrides = tmz_core::env_overrides();
+    let unknown = tmz_core::schema::unknown_env_vars(&overrides);
+    if active_only {
+        overrides.retain(tmz_core::EnvOverride::is_active);
+    }
+    if ctx.common.json {
+        let json = serde_json::json!({ "overrides": overrides, "unknown": unknown });
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+
+    let width = overrides.iter().map(|o| o.var.len()).max().unwrap_or(0);
+    for o in &overrides {
+        let state = if o.is_active() {
+            "\x1b[32mactive\x1b[0m"
+        } else {
+            "\x1b[2munset\x1b[0m"
+        };
+        println!("\x1b[1m{:<width$}\x1b[0m  {state}  {}", o.var, o.kind);
+        for (var, value) in &o.set {
+            println!("  {var}={value}");
+        }
+        if let Some(ref description) = o.description {
+            println!("  \x1b[2m{description}\x1b[0m");
         }
     }
+    for (var, value) in &unknown {
+        eprintln!("\x1b[33mwarning:\x1b[0m {var}={value} does not match any config value");
+    }
+    Ok(())
 }
 
 fn handle_completions(shell: Shell) {
@@ -1578,6 +6434,38 @@ fn print_conversation_list(convs: &[tmz_core::CachedConversation]) {
     }
 }
 
+/// Print `value` with `--json` or `--format`.
+fn print_structured(value: &serde_json::Value, format: OutputFormat) -> Result<()> {
+    print!("{}", tmz_core::output::render(value, format)?);
+    Ok(())
+}
+
+/// Conversations as flat records for structured output.
+fn conversation_records(convs: &[tmz_core::CachedConversation]) -> serde_json::Value {
+    convs
+        .iter()
+        .map(|c| {
+            serde_json::json!({
+                "id": c.id,
+                "display_name": c.display_name,
+                "product_type": c.product_type,
+                "last_activity": c.last_activity,
+            })
+        })
+        .collect()
+}
+
+/// `--plumbing`: `id<TAB>type<TAB>name<TAB>last_activity` per line, no
+/// colors or headers. The field order is stable; tabs and newlines in
+/// names become spaces.
+fn print_plumbing(convs: &[tmz_core::CachedConversation]) {
+    for c in convs {
+        let kind = format_chat_type(&c.product_type, &c.id).trim_matches(['[', ']']);
+        let name = c.display_name.replace(['\t', '\n', '\r'], " ");
+        println!("{}\t{kind}\t{name}\t{}", c.id, c.last_activity);
+    }
+}
+
 // ── Message rendering ────────────────────────────────────────────────
 //
 // Clean chat layout inspired by pi / opencode:
@@ -1619,13 +6507,24 @@ impl MessageGroup<'_> {
 }
 
 /// Group consecutive messages from the same sender on the same day.
-fn group_messages(messages: &[tmz_core::CachedMessage]) -> Vec<MessageGroup<'_>> {
+///
+/// System events are left out unless `[display] system_events` is on; each
+/// one forms its own group.
+fn group_messages<'a>(
+    messages: &'a [tmz_core::CachedMessage],
+    display: &DisplayConfig,
+) -> Vec<MessageGroup<'a>> {
     let mut groups: Vec<MessageGroup<'_>> = Vec::new();
 
     for msg in messages {
-        let same_sender = groups.last().is_some_and(|g| {
-            g.sender == msg.from_display_name && g.first_date() == extract_date(&msg.compose_time)
-        });
+        if msg.is_system_event() && !display.system_events {
+            continue;
+        }
+        let same_sender = !msg.is_system_event()
+            && groups.last().is_some_and(|g| {
+                g.sender == msg.from_display_name
+                    && g.first_date() == extract_date(&msg.compose_time)
+            });
 
         if same_sender {
             if let Some(last) = groups.last_mut() {
@@ -1675,7 +6574,21 @@ fn maybe_print_date_separator(date: &str, prev_date: Option<&str>) {
 ///   \u{2502} Message content here that wraps nicely
 ///   \u{2502} across multiple lines if needed
 /// ```
-fn print_bubble(group: &MessageGroup<'_>, prev: Option<&MessageGroup<'_>>) {
+fn print_bubble(
+    group: &MessageGroup<'_>,
+    prev: Option<&MessageGroup<'_>>,
+    display: &DisplayConfig,
+) {
+    if let [event] = group.messages.as_slice()
+        && event.is_system_event()
+    {
+        print_system_event(event, prev, display);
+        return;
+    }
+    if display.is_compact() {
+        print_compact_group(group, prev, display);
+        return;
+    }
     let prev_date = prev.map(MessageGroup::first_date);
     maybe_print_date_separator(&group.first_date(), prev_date.as_deref());
 
@@ -1685,6 +6598,10 @@ fn print_bubble(group: &MessageGroup<'_>, prev: Option<&MessageGroup<'_>>) {
         let content = msg.content.trim();
         let has_images = !tmz_core::kitty::extract_image_urls(&msg.content_html).is_empty();
 
+        if let Some(headline) = post_headline(msg) {
+            lines.push(headline);
+        }
+
         if !content.is_empty() {
             for line in content.lines() {
                 let trimmed = line.trim();
@@ -1706,15 +6623,12 @@ fn print_bubble(group: &MessageGroup<'_>, prev: Option<&MessageGroup<'_>>) {
     let w = term_width();
     let content_w = w.saturating_sub(6); // "  | " prefix + margin
 
-    // Bar color: cyan for self, yellow for others, dim for system
-    let bar_color = if name == "(system)" {
-        "2"
-    } else if group.is_from_me {
-        "36"
+    // Bar color: cyan for self, a per-sender color for others, dim for system
+    let (bar_color, name_color) = if name == "(system)" {
+        ("2".to_string(), "1;33".to_string())
     } else {
-        "33"
+        sender_sgr(name, group.is_from_me, display)
     };
-    let name_color = if group.is_from_me { "1;36" } else { "1;33" };
 
     // Blank line between groups (not after date separator)
     if prev.is_some() && prev_date.as_deref() == Some(&group.first_date()) {
@@ -1737,6 +6651,128 @@ fn print_bubble(group: &MessageGroup<'_>, prev: Option<&MessageGroup<'_>>) {
     }
 }
 
+/// Bold subject line of a channel post, with a red `!` if it is marked
+/// important.
+fn post_headline(msg: &tmz_core::CachedMessage) -> Option<String> {
+    let mark = if msg.important {
+        "\x1b[1;31m!\x1b[0m "
+    } else {
+        ""
+    };
+    match (msg.subject.as_str(), msg.important) {
+        ("", false) => None,
+        ("", true) => Some(format!("{mark}\x1b[1;31mImportant\x1b[0m")),
+        (subject, _) => Some(format!("{mark}\x1b[1m{subject}\x1b[0m")),
+    }
+}
+
+/// Render a system event as a dim `— Alice added Bob —` separator.
+fn print_system_event(
+    event: &tmz_core::CachedMessage,
+    prev: Option<&MessageGroup<'_>>,
+    display: &DisplayConfig,
+) {
+    let date = extract_date(&event.compose_time);
+    let prev_date = prev.map(MessageGroup::first_date);
+    if display.is_compact() {
+        if prev_date.as_deref() != Some(date.as_str()) {
+            let label = format_date_label(&date);
+            println!("\x1b[2m\u{2500}\u{2500} {label} \u{2500}\u{2500}\x1b[0m");
+        }
+    } else {
+        maybe_print_date_separator(&date, prev_date.as_deref());
+        if prev.is_some() && prev_date.as_deref() == Some(date.as_str()) {
+            println!();
+        }
+    }
+    let time = format_time_short(&event.compose_time);
+    println!("  \x1b[2m\u{2014} {} \u{2014} {time}\x1b[0m", event.content);
+}
+
+/// Render a message group as `HH:MM name: message` one-liners with no
+/// spacing, for `density = "compact"`.
+fn print_compact_group(
+    group: &MessageGroup<'_>,
+    prev: Option<&MessageGroup<'_>>,
+    display: &DisplayConfig,
+) {
+    let date = group.first_date();
+    if prev.map(MessageGroup::first_date).as_deref() != Some(date.as_str()) {
+        let label = format_date_label(&date);
+        println!("\x1b[2m\u{2500}\u{2500} {label} \u{2500}\u{2500}\x1b[0m");
+    }
+
+    let name = group.sender;
+    let name_color = if name == "(system)" {
+        "2".to_string()
+    } else {
+        sender_sgr(name, group.is_from_me, display).1
+    };
+    let w = term_width();
+
+    for msg in &group.messages {
+        let time = format_time_short(&msg.compose_time);
+        let mut lines: Vec<String> = post_headline(msg)
+            .into_iter()
+            .chain(
+                msg.content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(|l| shorten_urls(l, 50)),
+            )
+            .collect();
+        if lines.is_empty() {
+            if tmz_core::kitty::extract_image_urls(&msg.content_html).is_empty() {
+                continue;
+            }
+            lines.push("\x1b[2m[image]\x1b[0m".to_string());
+        }
+
+        let indent = visible_len(&time) + visible_len(name) + 3;
+        let wrapped = wrap_lines(&lines, w.saturating_sub(indent + 1).max(20));
+        for (i, line) in wrapped.iter().enumerate() {
+            if i == 0 {
+                println!("\x1b[2m{time}\x1b[0m \x1b[{name_color}m{name}\x1b[0m: {line}");
+            } else {
+                println!("{:indent$}{line}", "");
+            }
+        }
+    }
+}
+
+/// SGR codes for a sender's bar and (bold) name.
+fn sender_sgr(name: &str, is_from_me: bool, display: &DisplayConfig) -> (String, String) {
+    let code = if is_from_me {
+        tmz_core::colors::AnsiColor::Cyan.sgr()
+    } else {
+        display.sender_color(name).sgr()
+    };
+    (code.to_string(), format!("1;{code}"))
+}
+
+/// Print a one-line legend of the senders in `messages` with their colors.
+fn print_sender_legend(messages: &[tmz_core::CachedMessage], display: &DisplayConfig) {
+    let mut seen: Vec<(&str, bool)> = Vec::new();
+    for msg in messages {
+        let name = msg.from_display_name.as_str();
+        if !name.is_empty() && !seen.iter().any(|(n, _)| *n == name) {
+            seen.push((name, msg.is_from_me));
+        }
+    }
+    if seen.is_empty() {
+        return;
+    }
+    let legend = seen.iter().fold(String::new(), |mut out, (name, is_me)| {
+        use std::fmt::Write as _;
+        let (_, name_color) = sender_sgr(name, *is_me, display);
+        let _ = write!(out, "  \x1b[{name_color}m\u{25cf} {name}\x1b[0m");
+        out
+    });
+    println!("{legend}");
+    println!();
+}
+
 /// Highlight search query words in text using bold + underline.
 fn highlight_matches(text: &str, query_words: &[&str]) -> String {
     if query_words.is_empty() {
@@ -2043,3 +7079,178 @@ fn truncate(s: &str, max: usize) -> String {
 fn dim(s: &str) -> String {
     format!("\x1b[2m{s}\x1b[0m")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::LazyLock;
+    use tmz_core::testutil::TempDir;
+    use tmz_core::{OffHoursAction, OversizedAction};
+
+    /// Twice the message size limit of [`context`].
+    static LONG: LazyLock<String> = LazyLock::new(|| "word ".repeat(16));
+
+    #[derive(Parser)]
+    struct TestOpts {
+        #[command(flatten)]
+        common: CommonOpts,
+    }
+
+    fn context(dir: &TempDir, args: &[&str]) -> RuntimeContext {
+        let common =
+            TestOpts::parse_from(std::iter::once("tmz").chain(args.iter().copied())).common;
+        let mut config = AppConfig::default();
+        config.sending.max_message_bytes = 40;
+        config.sending.max_parts = 3;
+        RuntimeContext {
+            common,
+            paths: AppPaths {
+                config_file: dir.join("config.toml"),
+                system_config_file: dir.join("system.toml"),
+                project_config_file: None,
+                data_dir: dir.to_path_buf(),
+                state_dir: dir.to_path_buf(),
+                browser_profile_dir: dir.join("browser-profile"),
+            },
+            config,
+        }
+    }
+
+    fn oversized(dir: &TempDir, action: OversizedAction, args: &[&str]) -> RuntimeContext {
+        let mut ctx = context(dir, args);
+        ctx.config.sending.oversized = action;
+        ctx
+    }
+
+    fn send_opts(schedule: Option<&str>) -> SendOpts {
+        SendOpts {
+            schedule: schedule.map(str::to_string),
+            if_available: false,
+            reply_to: None,
+            markup: MessageFormat::Text,
+            silent: false,
+        }
+    }
+
+    fn parts(fit: MessageFit) -> Vec<String> {
+        match fit {
+            MessageFit::Parts(parts) => parts,
+            MessageFit::Attach => panic!("attached"),
+            MessageFit::Cancel => panic!("cancelled"),
+        }
+    }
+
+    #[test]
+    fn short_messages_are_sent_whole() {
+        let dir = TempDir::new("cli").unwrap();
+        let ctx = oversized(&dir, OversizedAction::Refuse, &[]);
+        assert_eq!(parts(fit_message(&ctx, "short").unwrap()), ["short"]);
+    }
+
+    #[test]
+    fn oversized_messages_can_be_refused() {
+        let dir = TempDir::new("cli").unwrap();
+        let ctx = oversized(&dir, OversizedAction::Refuse, &["--yes"]);
+        let err = fit_message(&ctx, &LONG).unwrap_err();
+        assert!(err.to_string().contains("max_message_bytes"), "{err}");
+    }
+
+    #[test]
+    fn oversized_messages_can_be_attached() {
+        let dir = TempDir::new("cli").unwrap();
+        let ctx = oversized(&dir, OversizedAction::Attach, &[]);
+        assert!(matches!(
+            fit_message(&ctx, &LONG).unwrap(),
+            MessageFit::Attach
+        ));
+    }
+
+    #[test]
+    fn oversized_messages_can_be_split() {
+        let dir = TempDir::new("cli").unwrap();
+        let ctx = oversized(&dir, OversizedAction::Split, &[]);
+        let sent = parts(fit_message(&ctx, &LONG).unwrap());
+        assert!(sent.len() > 1 && sent.len() <= 3, "{sent:?}");
+        assert!(sent.iter().all(|part| part.len() <= 40), "{sent:?}");
+    }
+
+    #[test]
+    fn splits_over_max_parts_are_attached_instead() {
+        let dir = TempDir::new("cli").unwrap();
+        let ctx = oversized(&dir, OversizedAction::Split, &[]);
+        assert!(matches!(
+            fit_message(&ctx, &"word ".repeat(60)).unwrap(),
+            MessageFit::Attach
+        ));
+    }
+
+    #[test]
+    fn yes_answers_the_oversized_question_with_split() {
+        let dir = TempDir::new("cli").unwrap();
+        let ctx = oversized(&dir, OversizedAction::Ask, &["--yes"]);
+        assert!(parts(fit_message(&ctx, &LONG).unwrap()).len() > 1);
+    }
+
+    #[test]
+    fn the_oversized_question_needs_a_terminal_or_yes() {
+        if io::stdin().is_terminal() {
+            return;
+        }
+        let dir = TempDir::new("cli").unwrap();
+        let ctx = oversized(&dir, OversizedAction::Ask, &[]);
+        let err = fit_message(&ctx, &LONG).unwrap_err();
+        assert!(err.to_string().contains("--yes"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn yes_skips_the_off_hours_check() {
+        let dir = TempDir::new("cli").unwrap();
+        let mut ctx = context(&dir, &["--yes"]);
+        ctx.config.sending.off_hours = OffHoursAction::Confirm;
+        let db = Cache::open(&dir.join("cache.db")).await.unwrap();
+        assert!(matches!(
+            confirm_off_hours(&ctx, &db, "8:orgid:ann").await.unwrap(),
+            OffHoursChoice::Send
+        ));
+    }
+
+    #[tokio::test]
+    async fn off_hours_checks_can_be_switched_off() {
+        let dir = TempDir::new("cli").unwrap();
+        let mut ctx = context(&dir, &[]);
+        ctx.config.sending.off_hours = OffHoursAction::Off;
+        let db = Cache::open(&dir.join("cache.db")).await.unwrap();
+        assert!(matches!(
+            confirm_off_hours(&ctx, &db, "8:orgid:ann").await.unwrap(),
+            OffHoursChoice::Send
+        ));
+    }
+
+    #[tokio::test]
+    async fn read_only_mode_refuses_to_send() {
+        let dir = TempDir::new("cli").unwrap();
+        let mut ctx = context(&dir, &["--yes"]);
+        ctx.config.security.read_only = true;
+        let db = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let err = send_text(&ctx, &db, "19:a@thread.v2", "hi", &send_opts(None))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("read-only"), "{err}");
+        assert!(db.list_outbox(true, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn scheduled_sends_wait_in_the_outbox() {
+        let dir = TempDir::new("cli").unwrap();
+        let ctx = context(&dir, &[]);
+        let db = Cache::open(&dir.join("cache.db")).await.unwrap();
+        send_text(&ctx, &db, "19:a@thread.v2", "hi", &send_opts(Some("in 2h")))
+            .await
+            .unwrap();
+        let queued = db.list_outbox(false, 10).await.unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].conversation_id, "19:a@thread.v2");
+        assert_eq!(queued[0].content, "hi");
+        assert_eq!(queued[0].status, "pending");
+    }
+}
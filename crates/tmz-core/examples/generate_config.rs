@@ -1,6 +1,6 @@
 //! Generate config.toml and config.schema.json to examples/ directory.
 //!
-//! Run with: cargo run -p tmz-core --example generate_config
+//! Run with: `cargo run -p tmz-core --example generate_config`
 
 use std::path::PathBuf;
 
@@ -16,7 +16,7 @@ fn main() -> anyhow::Result<()> {
     let workspace_root = crate_root
         .parent() // crates/
         .and_then(|p| p.parent()) // workspace root
-        .expect("could not find workspace root");
+        .ok_or_else(|| anyhow::anyhow!("could not find workspace root"))?;
 
     let examples_dir = workspace_root.join("examples");
 
@@ -0,0 +1,188 @@
+//! Named accounts for using several Teams tenants side by side.
+//!
+//! The default account keeps its tokens, cache, and browser profile directly
+//! in the tmz data, state, and cache directories. A named account
+//! (`tmz --account work ...`) gets its own copy of each below
+//! `accounts/<name>/` in those directories, so a work tenant and a guest
+//! tenant never share tokens or cached chats. The configuration file is
+//! shared by all accounts.
+//!
+//! The account is chosen once per process, before any path is resolved:
+//! with [`select`] (the CLI's `--account`), or else from the `TMZ_ACCOUNT`
+//! environment variable. Every `default_*_dir` function then points into it.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::CoreError;
+
+/// Directory below the data, state, and cache directories that holds the
+/// named accounts.
+pub const ACCOUNTS_DIR: &str = "accounts";
+
+/// Name that selects the default (unnamed) account.
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+/// Environment variable naming the account when [`select`] is not called.
+pub const ACCOUNT_ENV: &str = "TMZ_ACCOUNT";
+
+/// Longest accepted account name.
+const MAX_NAME_LEN: usize = 64;
+
+static ACTIVE: OnceLock<Option<String>> = OnceLock::new();
+
+/// Make `name` the account for the rest of the process.
+///
+/// [`DEFAULT_ACCOUNT`] selects the default account.
+///
+/// # Errors
+///
+/// Returns an error if the name is invalid or paths were already resolved
+/// for another account.
+pub fn select(name: &str) -> Result<(), CoreError> {
+    validate(name)?;
+    let wanted = (name != DEFAULT_ACCOUNT).then(|| name.to_string());
+    let chosen = ACTIVE.get_or_init(|| wanted.clone());
+    if *chosen == wanted {
+        Ok(())
+    } else {
+        Err(CoreError::Other(format!(
+            "account '{}' is already in use by this process",
+            chosen.as_deref().unwrap_or(DEFAULT_ACCOUNT)
+        )))
+    }
+}
+
+/// The selected named account, or `None` for the default account.
+///
+/// Falls back to `TMZ_ACCOUNT` when [`select`] was not called; an invalid
+/// value there is ignored with a warning.
+#[must_use]
+pub fn active() -> Option<&'static str> {
+    ACTIVE
+        .get_or_init(|| {
+            let name = std::env::var(ACCOUNT_ENV).ok()?;
+            if name.is_empty() || name == DEFAULT_ACCOUNT {
+                return None;
+            }
+            match validate(&name) {
+                Ok(()) => Some(name),
+                Err(e) => {
+                    log::warn!("ignoring {ACCOUNT_ENV}: {e}");
+                    None
+                }
+            }
+        })
+        .as_deref()
+}
+
+/// Check that `name` can be used as an account name: 1 to 64 ASCII letters,
+/// digits, `-`, `_`, or `.`, not starting with `.`.
+///
+/// # Errors
+///
+/// Returns [`CoreError::Config`] describing the problem.
+pub fn validate(name: &str) -> Result<(), CoreError> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(CoreError::Config(format!(
+            "account name must be 1 to {MAX_NAME_LEN} characters"
+        )));
+    }
+    if name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(CoreError::Config(format!(
+            "invalid account name '{name}': use letters, digits, '-', '_', or '.'"
+        )));
+    }
+    Ok(())
+}
+
+/// `base` scoped to the selected account.
+#[must_use]
+pub fn scope(base: PathBuf) -> PathBuf {
+    scoped(base, active())
+}
+
+/// `base` scoped to `account` (`None` is the default account).
+#[must_use]
+pub fn scoped(base: PathBuf, account: Option<&str>) -> PathBuf {
+    match account {
+        Some(name) => base.join(ACCOUNTS_DIR).join(name),
+        None => base,
+    }
+}
+
+/// Named accounts that have a state directory below `state_root` (the
+/// unscoped state directory), sorted by name.
+#[must_use]
+pub fn list(state_root: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(state_root.join(ACCOUNTS_DIR)) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(std::result::Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| validate(name).is_ok())
+        .collect();
+    names.sort();
+    names
+}
+
+/// The login command for the selected account, for hints in error messages.
+#[must_use]
+pub fn login_command() -> String {
+    active().map_or_else(
+        || "tmz auth login".to_string(),
+        |name| format!("tmz --account {name} auth login"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn valid_account_names_are_accepted() {
+        for ok in ["work", "guest-tenant", "a.b_c", "default"] {
+            assert!(validate(ok).is_ok(), "{ok}");
+        }
+    }
+
+    #[test]
+    fn unsafe_or_oversized_account_names_are_rejected() {
+        for bad in [
+            "",
+            "../work",
+            "a/b",
+            ".hidden",
+            "with space",
+            &"x".repeat(65),
+        ] {
+            assert!(validate(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn named_accounts_get_their_own_directories() {
+        let base = PathBuf::from("/state/tmz");
+        assert_eq!(scoped(base.clone(), None), base);
+        assert_eq!(
+            scoped(base, Some("work")),
+            PathBuf::from("/state/tmz/accounts/work")
+        );
+    }
+
+    #[test]
+    fn list_skips_invalid_account_directories() {
+        let dir = TempDir::new("accounts").unwrap();
+        for name in ["work", "guest", ".tmp"] {
+            std::fs::create_dir_all(dir.join(ACCOUNTS_DIR).join(name)).unwrap();
+        }
+        assert_eq!(list(&dir), ["guest", "work"]);
+    }
+}
@@ -0,0 +1,270 @@
+//! Scoped bearer tokens for the HTTP API.
+//!
+//! Besides its main token, `tmz-api` accepts tokens created with
+//! `tmz api token create`, each limited to what one client needs: a
+//! send-only token may only call `POST /send`, and a token with targets is
+//! send-only too and may only send to those conversations. A CI job that
+//! posts build results to one channel can thus leak its token without
+//! exposing the other chats, the config, or the daemon.
+//!
+//! Only the SHA-256 digest of each token is kept, in `api-tokens.json` in
+//! the state directory; the token itself is shown once when it is created.
+//! The file is read on every request, so created and revoked tokens take
+//! effect without restarting the server.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::CoreError;
+use crate::blobs::BlobStore;
+use crate::secrets::{self, constant_time_eq};
+
+/// Token store file name in the state directory.
+pub const TOKENS_FILE: &str = "api-tokens.json";
+
+/// Prefix of generated tokens, so they are recognizable in secret scanners.
+pub const TOKEN_PREFIX: &str = "tmz_";
+
+/// What a scoped token may do.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scope {
+    /// Only `POST /send` is allowed.
+    #[serde(default)]
+    pub send_only: bool,
+    /// Conversation IDs the token may send to; empty for any. A token
+    /// with targets may only call `POST /send`.
+    #[serde(default)]
+    pub targets: Vec<String>,
+}
+
+impl Scope {
+    /// Whether the token may call the route at `path`.
+    #[must_use]
+    pub fn allows_route(&self, path: &str) -> bool {
+        path == "/send" || (!self.send_only && self.targets.is_empty())
+    }
+
+    /// Whether the token may send to `conversation_id`.
+    #[must_use]
+    pub fn allows_target(&self, conversation_id: &str) -> bool {
+        self.targets.is_empty() || self.targets.iter().any(|id| id == conversation_id)
+    }
+
+    /// Short description for listings, such as `send-only, 1 target`.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.send_only {
+            parts.push("send-only".to_string());
+        }
+        match self.targets.len() {
+            0 => {}
+            1 => parts.push("1 target".to_string()),
+            n => parts.push(format!("{n} targets")),
+        }
+        if parts.is_empty() {
+            "full access".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// A stored scoped token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScopedToken {
+    /// Short identifier for `tmz api token revoke` (the start of the digest).
+    pub id: String,
+    /// Free-form label, such as the CI system using the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// SHA-256 digest of the token, as hex.
+    pub sha256: String,
+    /// What the token may do.
+    #[serde(flatten)]
+    pub scope: Scope,
+    /// When the token was created (RFC 3339, UTC).
+    pub created_at: String,
+}
+
+/// The scoped tokens of one state directory.
+#[derive(Debug, Clone)]
+pub struct TokenStore {
+    path: PathBuf,
+}
+
+impl TokenStore {
+    /// The store in `state_dir`.
+    #[must_use]
+    pub fn new(state_dir: &Path) -> Self {
+        Self {
+            path: state_dir.join(TOKENS_FILE),
+        }
+    }
+
+    /// Path of the store file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stored tokens, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    pub fn list(&self) -> Result<Vec<ScopedToken>, CoreError> {
+        let data = match std::fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_str(&data)
+            .map_err(|e| CoreError::Serialization(format!("parsing {}: {e}", self.path.display())))
+    }
+
+    /// Create a token with `scope`, returning its entry and the token
+    /// itself, which is not stored and cannot be shown again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no random token can be generated or the store
+    /// cannot be written.
+    pub fn create(
+        &self,
+        name: Option<&str>,
+        scope: Scope,
+    ) -> Result<(ScopedToken, String), CoreError> {
+        let secret = format!("{TOKEN_PREFIX}{}", secrets::random_hex(32)?);
+        let sha256 = BlobStore::digest(secret.as_bytes());
+        let entry = ScopedToken {
+            id: sha256[..8].to_string(),
+            name: name.map(str::to_string),
+            sha256,
+            scope,
+            created_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        };
+        let mut tokens = self.list()?;
+        tokens.push(entry.clone());
+        self.save(&tokens)?;
+        Ok((entry, secret))
+    }
+
+    /// Remove the token with `id`, returning whether it existed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be read or written.
+    pub fn revoke(&self, id: &str) -> Result<bool, CoreError> {
+        let mut tokens = self.list()?;
+        let before = tokens.len();
+        tokens.retain(|token| token.id != id);
+        if tokens.len() == before {
+            return Ok(false);
+        }
+        self.save(&tokens)?;
+        Ok(true)
+    }
+
+    /// The stored token matching `secret`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be read.
+    pub fn verify(&self, secret: &str) -> Result<Option<ScopedToken>, CoreError> {
+        if !secret.starts_with(TOKEN_PREFIX) {
+            return Ok(None);
+        }
+        let digest = BlobStore::digest(secret.as_bytes());
+        Ok(self
+            .list()?
+            .into_iter()
+            .find(|token| constant_time_eq(&token.sha256, &digest)))
+    }
+
+    fn save(&self, tokens: &[ScopedToken]) -> Result<(), CoreError> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(tokens)
+            .map_err(|e| CoreError::Serialization(format!("serializing tokens: {e}")))?;
+        let tmp = self.path.with_extension("json.tmp");
+        secrets::write_private(&tmp, json.as_bytes())?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn ops_only() -> Scope {
+        Scope {
+            send_only: true,
+            targets: vec!["19:ops@thread.tacv2".to_string()],
+        }
+    }
+
+    #[test]
+    fn tokens_are_stored_hashed() {
+        let dir = TempDir::new("apitokens").unwrap();
+        let store = TokenStore::new(&dir);
+        assert!(store.list().unwrap().is_empty());
+        let (entry, secret) = store.create(Some("ci"), ops_only()).unwrap();
+        assert!(secret.starts_with(TOKEN_PREFIX));
+        let stored = std::fs::read_to_string(store.path()).unwrap();
+        assert!(!stored.contains(&secret));
+        assert!(stored.contains(&entry.sha256));
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn verify_finds_the_token_and_its_scope() {
+        let dir = TempDir::new("apitokens-verify").unwrap();
+        let store = TokenStore::new(&dir);
+        let (entry, secret) = store.create(Some("ci"), ops_only()).unwrap();
+        let found = store.verify(&secret).unwrap().unwrap();
+        assert_eq!(found.id, entry.id);
+        assert_eq!(found.scope.describe(), "send-only, 1 target");
+        assert!(store.verify("tmz_wrong").unwrap().is_none());
+    }
+
+    #[test]
+    fn revoked_tokens_no_longer_verify() {
+        let dir = TempDir::new("apitokens-revoke").unwrap();
+        let store = TokenStore::new(&dir);
+        let (entry, secret) = store.create(None, Scope::default()).unwrap();
+        assert!(store.revoke(&entry.id).unwrap());
+        assert!(!store.revoke(&entry.id).unwrap());
+        assert!(store.verify(&secret).unwrap().is_none());
+    }
+
+    #[test]
+    fn send_only_scopes_allow_only_sending_to_their_targets() {
+        let scope = ops_only();
+        assert!(scope.allows_route("/send"));
+        assert!(!scope.allows_route("/conversations"));
+        assert!(scope.allows_target("19:ops@thread.tacv2"));
+        assert!(!scope.allows_target("19:hr@thread.v2"));
+    }
+
+    #[test]
+    fn targeted_scopes_keep_config_and_search_closed() {
+        let targeted = Scope {
+            send_only: false,
+            ..ops_only()
+        };
+        assert!(targeted.allows_route("/send"));
+        assert!(!targeted.allows_route("/config"));
+        assert!(!targeted.allows_route("/search"));
+    }
+
+    #[test]
+    fn the_default_scope_allows_everything() {
+        assert!(Scope::default().allows_target("19:hr@thread.v2"));
+        assert!(Scope::default().allows_route("/config"));
+    }
+}
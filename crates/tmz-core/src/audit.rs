@@ -0,0 +1,366 @@
+//! Append-only audit log of mutating actions.
+//!
+//! Every frontend records the actions that change something (sends,
+//! uploads, deletes, alias writes, auth events) with a timestamp, target,
+//! and outcome as one JSON line in `audit.jsonl` in the state directory.
+//! The log is rotated by size (`audit.1.jsonl` is the newest rotated file).
+//!
+//! With `[audit] sign = true`, each record carries an HMAC-SHA256 over the
+//! record and the previous record's signature, keyed by the secret in
+//! `key_env`, so edited, inserted, or removed lines are found by
+//! `tmz audit verify`. The chain restarts in each file.
+
+use crate::CoreError;
+use crate::config::AuditConfig;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt::Display;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Audit log file name in the state directory.
+pub const AUDIT_LOG_FILE: &str = "audit.jsonl";
+
+/// Bytes read from the end of the log to find the previous signature.
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// One audited action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the action finished (RFC 3339, UTC).
+    pub ts: String,
+    /// Which frontend acted: `cli`, `tui`, `mcp`, `api`, or `daemon`.
+    pub source: String,
+    /// What was done, such as `send`, `upload`, or `auth.login`.
+    pub action: String,
+    /// What it was done to (conversation ID, alias name, outbox id).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// `ok` or `error`.
+    pub outcome: String,
+    /// The error, or extra details such as a file name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Chained HMAC-SHA256 signature, when signing is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+/// A line that failed `tmz audit verify`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditProblem {
+    /// Log file containing the line.
+    pub file: PathBuf,
+    /// 1-based line number.
+    pub line: usize,
+    /// What is wrong with it.
+    pub reason: String,
+}
+
+/// Writer and reader for the audit log of one frontend.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    dir: PathBuf,
+    config: AuditConfig,
+    source: String,
+}
+
+impl AuditLog {
+    /// An audit log in `state_dir`, recording actions as `source`.
+    #[must_use]
+    pub fn new(state_dir: &Path, config: &AuditConfig, source: &str) -> Self {
+        Self {
+            dir: state_dir.to_path_buf(),
+            config: config.clone(),
+            source: source.to_string(),
+        }
+    }
+
+    /// Path of the current log file.
+    #[must_use]
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(AUDIT_LOG_FILE)
+    }
+
+    /// Record the outcome of `action` on `target`. Failures to write the
+    /// log are logged as warnings, never returned, so auditing cannot break
+    /// the action itself.
+    pub fn record<T, E: Display>(&self, action: &str, target: Option<&str>, result: &Result<T, E>) {
+        let (outcome, detail) = match result {
+            Ok(_) => ("ok", None),
+            Err(e) => ("error", Some(e.to_string())),
+        };
+        self.record_with(action, target, outcome, detail.as_deref());
+    }
+
+    /// Record `action` with an explicit outcome and detail.
+    pub fn record_with(
+        &self,
+        action: &str,
+        target: Option<&str>,
+        outcome: &str,
+        detail: Option<&str>,
+    ) {
+        if !self.config.enabled {
+            return;
+        }
+        let record = AuditRecord {
+            ts: Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            source: self.source.clone(),
+            action: action.to_string(),
+            target: target.map(str::to_string),
+            outcome: outcome.to_string(),
+            detail: detail.map(str::to_string),
+            sig: None,
+        };
+        if let Err(e) = self.append(record) {
+            log::warn!("writing audit log {}: {e}", self.path().display());
+        }
+    }
+
+    /// Append `record`, rotating and signing as configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the log cannot be rotated or written.
+    pub fn append(&self, mut record: AuditRecord) -> Result<(), CoreError> {
+        std::fs::create_dir_all(&self.dir)?;
+        self.rotate_if_full()?;
+        let path = self.path();
+        if self.config.sign {
+            match self.config.key() {
+                Some(key) => {
+                    let prev = last_signature(&path).unwrap_or_default();
+                    record.sig = Some(sign(&key, &prev, &record)?);
+                }
+                None => log::warn!(
+                    "[audit] sign is on but {} is not set; writing unsigned record",
+                    self.config.key_env
+                ),
+            }
+        }
+        let line =
+            serde_json::to_string(&record).map_err(|e| CoreError::Serialization(e.to_string()))?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+
+    /// Records at or after `since`, oldest first, across rotated files.
+    /// Lines that cannot be parsed are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a log file exists but cannot be read.
+    pub fn read(&self, since: Option<DateTime<Utc>>) -> Result<Vec<AuditRecord>, CoreError> {
+        let mut records = Vec::new();
+        for path in self.files() {
+            let text = std::fs::read_to_string(&path)?;
+            records.extend(
+                text.lines()
+                    .filter_map(|line| serde_json::from_str::<AuditRecord>(line).ok())
+                    .filter(|r| {
+                        since.is_none_or(|since| {
+                            DateTime::parse_from_rfc3339(&r.ts)
+                                .is_ok_and(|ts| ts.with_timezone(&Utc) >= since)
+                        })
+                    }),
+            );
+        }
+        Ok(records)
+    }
+
+    /// Check every record's signature. Returns the lines that fail.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signing key is not set or a log file cannot
+    /// be read.
+    pub fn verify(&self) -> Result<Vec<AuditProblem>, CoreError> {
+        let key = self.config.key().ok_or_else(|| {
+            CoreError::Config(format!("no audit signing key: set {}", self.config.key_env))
+        })?;
+        let mut problems = Vec::new();
+        for path in self.files() {
+            let text = std::fs::read_to_string(&path)?;
+            let mut prev = String::new();
+            for (i, line) in text.lines().enumerate() {
+                let reason = match serde_json::from_str::<AuditRecord>(line) {
+                    Err(e) => Some(format!("not a record: {e}")),
+                    Ok(mut record) => {
+                        let sig = record.sig.take().unwrap_or_default();
+                        let expected = sign(&key, &prev, &record)?;
+                        let reason = if sig.is_empty() {
+                            Some("unsigned".to_string())
+                        } else if sig == expected {
+                            None
+                        } else {
+                            Some("signature mismatch".to_string())
+                        };
+                        prev = sig;
+                        reason
+                    }
+                };
+                if let Some(reason) = reason {
+                    problems.push(AuditProblem {
+                        file: path.clone(),
+                        line: i + 1,
+                        reason,
+                    });
+                }
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Existing log files, oldest first.
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = (1..=self.config.keep)
+            .rev()
+            .map(|n| self.rotated(n))
+            .collect();
+        files.push(self.path());
+        files.retain(|p| p.exists());
+        files
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        self.dir.join(format!("audit.{n}.jsonl"))
+    }
+
+    /// Shift `audit.jsonl` to `audit.1.jsonl` (and older files up) once it
+    /// reaches `max_size_mb`, dropping files beyond `keep`.
+    fn rotate_if_full(&self) -> Result<(), CoreError> {
+        let path = self.path();
+        let max = self.config.max_size_mb.saturating_mul(1024 * 1024);
+        let size = std::fs::metadata(&path).map_or(0, |m| m.len());
+        if max == 0 || size < max {
+            return Ok(());
+        }
+        if self.config.keep == 0 {
+            std::fs::remove_file(&path)?;
+            return Ok(());
+        }
+        let oldest = self.rotated(self.config.keep);
+        if oldest.exists() {
+            std::fs::remove_file(&oldest)?;
+        }
+        for n in (1..self.config.keep).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated(n + 1))?;
+            }
+        }
+        std::fs::rename(&path, self.rotated(1))?;
+        Ok(())
+    }
+}
+
+/// HMAC-SHA256 over the previous signature and the unsigned record, as hex.
+fn sign(key: &str, prev: &str, record: &AuditRecord) -> Result<String, CoreError> {
+    let unsigned = AuditRecord {
+        sig: None,
+        ..record.clone()
+    };
+    let json =
+        serde_json::to_string(&unsigned).map_err(|e| CoreError::Serialization(e.to_string()))?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+        .map_err(|e| CoreError::Other(format!("audit signing key: {e}")))?;
+    mac.update(prev.as_bytes());
+    mac.update(b"\n");
+    mac.update(json.as_bytes());
+    Ok(crate::secrets::hex(&mac.finalize().into_bytes()))
+}
+
+/// Signature of the last record in the log at `path`, if any.
+fn last_signature(path: &Path) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))
+        .ok()?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).ok()?;
+    let tail = String::from_utf8_lossy(&tail);
+    let line = tail.lines().rfind(|l| !l.trim().is_empty())?;
+    serde_json::from_str::<AuditRecord>(line).ok()?.sig
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn signed() -> AuditConfig {
+        AuditConfig {
+            sign: true,
+            key_env: "TMZ_TEST_NO_SUCH_KEY".to_string(),
+            key: Some("s3cret".to_string()),
+            ..AuditConfig::default()
+        }
+    }
+
+    #[test]
+    fn records_outcomes_and_error_details() {
+        let dir = TempDir::new("audit").unwrap();
+        let log = AuditLog::new(&dir, &signed(), "cli");
+        log.record::<(), &str>("send", Some("19:a"), &Ok(()));
+        log.record::<(), &str>("upload", Some("19:a"), &Err("too large"));
+        log.record_with("auth.logout", None, "ok", None);
+
+        let records = log.read(None).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].outcome, "ok");
+        assert_eq!(records[1].outcome, "error");
+        assert_eq!(records[1].detail.as_deref(), Some("too large"));
+        let future = Utc::now() + chrono::Duration::hours(1);
+        assert!(log.read(Some(future)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn verify_finds_edited_lines() {
+        let dir = TempDir::new("audit-tamper").unwrap();
+        let log = AuditLog::new(&dir, &signed(), "cli");
+        log.record::<(), &str>("send", Some("19:a"), &Ok(()));
+        log.record::<(), &str>("send", Some("19:a"), &Ok(()));
+        assert!(log.verify().unwrap().is_empty());
+
+        let text = std::fs::read_to_string(log.path()).unwrap();
+        std::fs::write(log.path(), text.replacen("19:a", "19:b", 1)).unwrap();
+        let problems = log.verify().unwrap();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].line, 1);
+    }
+
+    #[test]
+    fn zero_max_size_never_rotates() {
+        let dir = TempDir::new("audit-unbounded").unwrap();
+        let config = AuditConfig {
+            max_size_mb: 0,
+            ..AuditConfig::default()
+        };
+        let log = AuditLog::new(&dir, &config, "cli");
+        std::fs::write(log.path(), vec![b'\n'; 1024 * 1024]).unwrap();
+        assert!(log.rotate_if_full().is_ok());
+        assert!(!dir.join("audit.1.jsonl").exists());
+    }
+
+    #[test]
+    fn full_logs_rotate_before_the_next_record() {
+        let dir = TempDir::new("audit-rotate").unwrap();
+        let tiny = AuditConfig {
+            max_size_mb: 1,
+            keep: 1,
+            ..AuditConfig::default()
+        };
+        let log = AuditLog::new(&dir, &tiny, "cli");
+        std::fs::write(log.path(), vec![b'\n'; 1024 * 1024]).unwrap();
+        log.record_with("send", None, "ok", None);
+        assert!(dir.join("audit.1.jsonl").exists());
+        assert_eq!(log.read(None).unwrap().len(), 1);
+    }
+}
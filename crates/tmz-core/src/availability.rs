@@ -0,0 +1,348 @@
+//! Whether someone is likely to respond: presence, working hours, and
+//! focus time.
+//!
+//! `tmz people show` and the TUI header of an open 1:1 chat combine Teams
+//! presence with the person's calendar, read through Graph's `getSchedule`
+//! (which also returns their Outlook working hours). When the calendar
+//! cannot be read, `[sending.working_hours]` stands in for the hours. Focus
+//! time is a calendar block whose subject mentions focus (Viva Insights
+//! books them as "Focus time"), or the presence activity `Focusing`.
+
+use crate::CoreError;
+use crate::config::WorkingHoursConfig;
+use crate::hours::{OffHours, WorkingHours};
+use crate::teams::TeamsClient;
+use crate::teams::models::UserPresence;
+use chrono::{DateTime, Duration, Local, NaiveDateTime, Utc};
+
+/// Hours of calendar read ahead.
+const LOOKAHEAD_HOURS: i64 = 12;
+
+/// A busy block from someone's calendar.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ScheduleItem {
+    /// `busy`, `tentative`, `oof`, `workingElsewhere`, or `free`.
+    pub status: String,
+    /// Event subject, if their calendar sharing shows it.
+    pub subject: Option<String>,
+    /// Start of the block.
+    pub start: DateTime<Utc>,
+    /// End of the block.
+    pub end: DateTime<Utc>,
+}
+
+impl ScheduleItem {
+    /// Whether this is focus time.
+    #[must_use]
+    pub fn is_focus(&self) -> bool {
+        self.subject
+            .as_deref()
+            .is_some_and(|s| s.to_lowercase().contains("focus"))
+    }
+
+    /// Whether the block covers `time`.
+    #[must_use]
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.start <= time && time < self.end
+    }
+}
+
+/// The items of a `getSchedule` result, sorted by start. Free blocks are
+/// left out.
+#[must_use]
+pub fn parse_schedule(value: &serde_json::Value) -> Vec<ScheduleItem> {
+    let time = |t: &serde_json::Value| {
+        let text = t["dateTime"].as_str()?;
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f")
+            .ok()
+            .map(|t| t.and_utc())
+    };
+    let mut items: Vec<ScheduleItem> = value["scheduleItems"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            Some(ScheduleItem {
+                status: item["status"].as_str()?.to_string(),
+                subject: item["subject"]
+                    .as_str()
+                    .filter(|s| !s.is_empty())
+                    .map(String::from),
+                start: time(&item["start"])?,
+                end: time(&item["end"])?,
+            })
+        })
+        .filter(|item| item.status != "free")
+        .collect();
+    items.sort_by_key(|item| item.start);
+    items
+}
+
+/// Someone's directory profile.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Profile {
+    /// Azure AD object ID.
+    pub id: String,
+    /// Display name.
+    pub name: String,
+    /// Email address.
+    pub email: Option<String>,
+    /// Job title.
+    pub job_title: Option<String>,
+}
+
+/// What is known about whether someone is around.
+#[derive(Debug, Clone, Default)]
+pub struct Availability {
+    /// Teams presence.
+    pub presence: Option<UserPresence>,
+    /// Working hours, from their calendar or `[sending.working_hours]`.
+    pub hours: Option<WorkingHours>,
+    /// Busy blocks from now on.
+    pub schedule: Vec<ScheduleItem>,
+}
+
+impl Availability {
+    /// The focus time block covering `now`.
+    #[must_use]
+    pub fn focus(&self, now: DateTime<Utc>) -> Option<&ScheduleItem> {
+        self.schedule
+            .iter()
+            .find(|item| item.is_focus() && item.contains(now))
+    }
+
+    /// Whether they are in focus time, by calendar or presence.
+    #[must_use]
+    pub fn is_focusing(&self, now: DateTime<Utc>) -> bool {
+        self.focus(now).is_some()
+            || self
+                .presence
+                .as_ref()
+                .is_some_and(|p| p.activity.as_deref() == Some("Focusing"))
+    }
+
+    /// The calendar block covering `now`.
+    #[must_use]
+    pub fn current(&self, now: DateTime<Utc>) -> Option<&ScheduleItem> {
+        self.schedule.iter().find(|item| item.contains(now))
+    }
+
+    /// `Some` if `now` is outside their working hours.
+    #[must_use]
+    pub fn off_hours(&self, now: DateTime<Utc>) -> Option<OffHours> {
+        self.hours.and_then(|hours| hours.check(now))
+    }
+
+    /// One line, e.g. `Do not disturb · focus time until 15:30`, with
+    /// times in local time.
+    #[must_use]
+    pub fn summary(&self, now: DateTime<Utc>) -> String {
+        let until = |t: DateTime<Utc>| t.with_timezone(&Local).format("%H:%M").to_string();
+        let mut parts = Vec::new();
+        if let Some(ref presence) = self.presence {
+            let mut label = presence.availability.label().to_string();
+            if let Some(activity) = presence.activity.as_deref().map(humanize)
+                && !activity.eq_ignore_ascii_case(&label)
+            {
+                label = format!("{label} ({activity})");
+            }
+            parts.push(label);
+        }
+        if let Some(block) = self.focus(now) {
+            parts.push(format!("focus time until {}", until(block.end)));
+        } else if let Some(block) = self.current(now) {
+            let what = match block.status.as_str() {
+                "oof" => "out of office",
+                "workingElsewhere" => "working elsewhere",
+                "tentative" => "tentatively busy",
+                _ => "busy",
+            };
+            parts.push(format!("{what} until {}", until(block.end)));
+        }
+        if let Some(off) = self.off_hours(now) {
+            parts.push(format!(
+                "outside working hours ({} for them)",
+                off.their_time.format("%a %H:%M")
+            ));
+        }
+        if parts.is_empty() {
+            "no presence or calendar information".to_string()
+        } else {
+            parts.join(" \u{b7} ")
+        }
+    }
+}
+
+/// `InACall` → `in a call`.
+fn humanize(activity: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in activity.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push(' ');
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+/// Look up `user_id`'s profile, presence, and calendar. Each source that
+/// cannot be read is left out; `fallback` supplies working hours when
+/// their calendar has none.
+///
+/// # Errors
+///
+/// Returns an error if `fallback` is invalid.
+pub async fn lookup(
+    client: &TeamsClient,
+    user_id: &str,
+    fallback: &WorkingHoursConfig,
+) -> Result<(Profile, Availability), CoreError> {
+    let user = client
+        .get_user(user_id)
+        .await
+        .inspect_err(|e| log::debug!("profile of {user_id}: {e}"))
+        .unwrap_or_default();
+    let field = |key: &str| {
+        user[key]
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+    };
+    let profile = Profile {
+        id: user_id.to_string(),
+        name: field("displayName").unwrap_or_else(|| user_id.to_string()),
+        email: field("mail").or_else(|| field("userPrincipalName")),
+        job_title: field("jobTitle"),
+    };
+
+    let now = Utc::now();
+    let schedule = async {
+        let email = profile.email.as_deref()?;
+        client
+            .get_schedule(email, now, now + Duration::hours(LOOKAHEAD_HOURS))
+            .await
+            .inspect_err(|e| log::debug!("calendar of {user_id}: {e}"))
+            .ok()
+    };
+    let (presence, schedule) = tokio::join!(client.get_user_presence(user_id), schedule);
+    let presence = presence
+        .inspect_err(|e| log::debug!("presence of {user_id}: {e}"))
+        .ok();
+
+    let outlook = schedule
+        .as_ref()
+        .filter(|_| fallback.lookup)
+        .and_then(|s| WorkingHours::from_outlook(&s["workingHours"]).ok());
+    let hours = match outlook {
+        Some(hours) => hours,
+        None => WorkingHours::from_config(fallback)?,
+    };
+    let availability = Availability {
+        presence,
+        hours: Some(hours),
+        schedule: schedule.as_ref().map(parse_schedule).unwrap_or_default(),
+    };
+    Ok((profile, availability))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::teams::models::PresenceStatus;
+    use chrono::TimeZone;
+
+    fn at(h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 10, h, m, 0).unwrap()
+    }
+
+    fn local(h: u32, m: u32) -> String {
+        at(h, m).with_timezone(&Local).format("%H:%M").to_string()
+    }
+
+    /// Focus time 13:00-14:30 and a 1:1 at 15:00-15:30 UTC, while in DND,
+    /// for someone working Berlin hours.
+    fn focusing() -> Availability {
+        let schedule = parse_schedule(&serde_json::json!({
+            "scheduleItems": [
+                {"status": "busy", "subject": "1:1",
+                 "start": {"dateTime": "2026-03-10T15:00:00.0000000", "timeZone": "UTC"},
+                 "end": {"dateTime": "2026-03-10T15:30:00.0000000", "timeZone": "UTC"}},
+                {"status": "busy", "subject": "Focus time",
+                 "start": {"dateTime": "2026-03-10T13:00:00.0000000", "timeZone": "UTC"},
+                 "end": {"dateTime": "2026-03-10T14:30:00.0000000", "timeZone": "UTC"}},
+                {"status": "free",
+                 "start": {"dateTime": "2026-03-10T14:30:00.0000000", "timeZone": "UTC"},
+                 "end": {"dateTime": "2026-03-10T15:00:00.0000000", "timeZone": "UTC"}}
+            ]
+        }));
+        let config = WorkingHoursConfig {
+            timezone: Some("Europe/Berlin".to_string()),
+            ..WorkingHoursConfig::default()
+        };
+        Availability {
+            presence: Some(UserPresence {
+                user_id: "u".to_string(),
+                availability: PresenceStatus::DoNotDisturb,
+                activity: Some("Focusing".to_string()),
+                status_message: None,
+                last_active: None,
+            }),
+            hours: Some(WorkingHours::from_config(&config).unwrap()),
+            schedule,
+        }
+    }
+
+    #[test]
+    fn schedules_keep_busy_items_sorted() {
+        let schedule = focusing().schedule;
+        assert_eq!(schedule.len(), 2);
+        assert!(schedule[0].is_focus());
+    }
+
+    #[test]
+    fn focus_time_is_named_with_its_end() {
+        let availability = focusing();
+        assert!(availability.focus(at(13, 30)).is_some());
+        assert_eq!(
+            availability.summary(at(13, 30)),
+            format!(
+                "Do not disturb (focusing) \u{b7} focus time until {}",
+                local(14, 30)
+            )
+        );
+    }
+
+    #[test]
+    fn meetings_show_as_busy_after_presence() {
+        let mut availability = focusing();
+        assert!(availability.is_focusing(at(15, 10)));
+        assert_eq!(
+            availability.summary(at(15, 10)),
+            format!(
+                "Do not disturb (focusing) \u{b7} busy until {}",
+                local(15, 30)
+            )
+        );
+        availability.presence = None;
+        assert_eq!(
+            availability.summary(at(15, 10)),
+            format!("busy until {}", local(15, 30))
+        );
+    }
+
+    #[test]
+    fn time_outside_working_hours_is_shown_in_their_zone() {
+        let mut availability = focusing();
+        availability.presence = None;
+        // 19:00 UTC is 20:00 in Berlin.
+        assert_eq!(
+            availability.summary(at(19, 0)),
+            "outside working hours (Tue 20:00 for them)"
+        );
+    }
+
+    #[test]
+    fn presence_activities_are_humanized() {
+        assert_eq!(humanize("InACall"), "in a call");
+    }
+}
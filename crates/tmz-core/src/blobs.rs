@@ -0,0 +1,106 @@
+//! Content-addressed file store for downloaded attachments.
+//!
+//! Binary data lives outside the `SQLite` cache, one file per distinct
+//! content, named by its SHA-256 digest and fanned out by the first two hex
+//! characters: `$XDG_CACHE_HOME/tmz/attachments/ab/abcdef…`. The cache keeps
+//! an index from URL to digest (see [`crate::cache::Cache::cache_image`]).
+
+use crate::CoreError;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Directory name of the attachment store inside the cache directory.
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+/// Content-addressed attachment files on disk.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+impl BlobStore {
+    /// Create a store rooted at `root`. The directory is created lazily.
+    #[must_use]
+    pub const fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Store rooted at the default location under the cache directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be determined.
+    pub fn default_location() -> Result<Self, CoreError> {
+        let cache_dir = crate::default_cache_dir()
+            .map_err(|e| CoreError::Path(format!("resolving cache dir: {e}")))?;
+        Ok(Self::new(cache_dir.join(ATTACHMENTS_DIR)))
+    }
+
+    /// Root directory of the store.
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// SHA-256 digest of `data` as lowercase hex.
+    #[must_use]
+    pub fn digest(data: &[u8]) -> String {
+        crate::secrets::hex(&Sha256::digest(data))
+    }
+
+    /// File path for a digest.
+    #[must_use]
+    pub fn path_for(&self, digest: &str) -> PathBuf {
+        let fanout = digest.get(..2).unwrap_or("00");
+        self.root.join(fanout).join(digest)
+    }
+
+    /// Write `data` and return its digest. Existing content is not rewritten.
+    ///
+    /// Writes go to a temporary file first and are renamed into place, so
+    /// readers never see a partial file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn put(&self, data: &[u8]) -> Result<String, CoreError> {
+        let digest = Self::digest(data);
+        let path = self.path_for(&digest);
+        if path.exists() {
+            return Ok(digest);
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(CoreError::Io)?;
+        }
+        let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+        std::fs::write(&tmp, data).map_err(CoreError::Io)?;
+        std::fs::rename(&tmp, &path).map_err(CoreError::Io)?;
+        Ok(digest)
+    }
+
+    /// Read the content for a digest, or `None` if the file is missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read.
+    pub fn get(&self, digest: &str) -> Result<Option<Vec<u8>>, CoreError> {
+        match std::fs::read(self.path_for(digest)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(CoreError::Io(e)),
+        }
+    }
+
+    /// Delete the file for a digest. Missing files are not an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be removed.
+    pub fn remove(&self, digest: &str) -> Result<(), CoreError> {
+        match std::fs::remove_file(self.path_for(digest)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(CoreError::Io(e)),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Redacted debug bundles for bug reports (`tmz debug bundle`).
+//!
+//! A bundle is a `.tar.gz` of plain-text files: version info, the config
+//! with secrets stripped, cache statistics, log tails, and recent error
+//! events. Everything that goes in passes through [`redact`] first, which
+//! masks tokens, keys, email addresses, and the home directory, so the
+//! archive can be attached to a public issue. The archive is written with
+//! the system `tar`.
+
+use crate::CoreError;
+use crate::config::AppConfig;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Replacement for redacted values.
+pub(crate) const REDACTED: &str = "<redacted>";
+
+/// Config keys whose values are secrets.
+pub(crate) const SECRET_KEYS: &[&str] = &[
+    "api_key",
+    "key",
+    "http_token",
+    "token",
+    "password",
+    "secret",
+];
+
+/// Patterns masked by [`redact`], with their replacements.
+const PATTERNS: &[(&str, &str)] = &[
+    // JWTs (access tokens, id tokens)
+    (
+        r"eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*",
+        "<jwt>",
+    ),
+    // Authorization headers and skype tokens
+    (
+        r"(?i)\b(bearer|skypetoken=|skypetoken\s|deepl-auth-key)\s*[A-Za-z0-9._~+/=-]{8,}",
+        "$1 <redacted>",
+    ),
+    // `token = ...`, `"api_key": "..."`, `secret=...`
+    (
+        r#"(?i)("?[a-z_]*(?:token|secret|password|api_key)[a-z_]*"?\s*[:=]\s*"?)[^"\s,}]{8,}"#,
+        "${1}<redacted>",
+    ),
+    (r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}", "<email>"),
+];
+
+static COMPILED: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    PATTERNS
+        .iter()
+        .filter_map(|(pattern, replacement)| Regex::new(pattern).ok().map(|re| (re, *replacement)))
+        .collect()
+});
+
+/// One file in a bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleFile {
+    /// File name inside the archive.
+    pub name: String,
+    /// File contents, redacted when written.
+    pub contents: String,
+}
+
+impl BundleFile {
+    /// A bundle file named `name`.
+    pub fn new(name: &str, contents: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            contents: contents.into(),
+        }
+    }
+}
+
+/// Mask tokens, keys, and email addresses in `text`, and shorten the home
+/// directory (which usually contains the user name) to `~`.
+#[must_use]
+pub fn redact(text: &str) -> String {
+    let text = COMPILED
+        .iter()
+        .fold(text.to_string(), |text, (re, replacement)| {
+            re.replace_all(&text, *replacement).into_owned()
+        });
+    match dirs::home_dir().and_then(|home| home.to_str().map(str::to_string)) {
+        Some(home) if home.len() > 1 => text.replace(&home, "~"),
+        _ => text,
+    }
+}
+
+/// The config as TOML with secrets and the targets of people aliases and
+/// alias groups removed.
+///
+/// # Errors
+///
+/// Returns an error if the config cannot be serialized.
+pub fn redact_config(config: &AppConfig) -> Result<String, CoreError> {
+    let mut value =
+        toml::Value::try_from(config).map_err(|e| CoreError::Serialization(e.to_string()))?;
+    strip_secrets(&mut value);
+    if let Some(people) = value.get_mut("people").and_then(toml::Value::as_table_mut) {
+        for target in people.iter_mut().map(|(_, v)| v) {
+            *target = toml::Value::String(REDACTED.to_string());
+        }
+    }
+    if let Some(groups) = value.get_mut("groups").and_then(toml::Value::as_table_mut) {
+        for members in groups.iter_mut().map(|(_, v)| v) {
+            *members = toml::Value::String(REDACTED.to_string());
+        }
+    }
+    let text =
+        toml::to_string_pretty(&value).map_err(|e| CoreError::Serialization(e.to_string()))?;
+    Ok(redact(&text))
+}
+
+/// The config as JSON with the values of secret keys (tokens, API keys,
+/// signing secrets) replaced, as served by the HTTP API's `GET /config`.
+///
+/// # Errors
+///
+/// Returns an error if the config cannot be serialized.
+pub fn config_without_secrets(config: &AppConfig) -> Result<serde_json::Value, CoreError> {
+    let mut value =
+        serde_json::to_value(config).map_err(|e| CoreError::Serialization(e.to_string()))?;
+    strip_json_secrets(&mut value);
+    Ok(value)
+}
+
+fn strip_json_secrets(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) && value.is_string() {
+                    *value = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    strip_json_secrets(value);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_json_secrets),
+        _ => {}
+    }
+}
+
+fn strip_secrets(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) && value.is_str() {
+                    *value = toml::Value::String(REDACTED.to_string());
+                } else {
+                    strip_secrets(value);
+                }
+            }
+        }
+        toml::Value::Array(items) => items.iter_mut().for_each(strip_secrets),
+        _ => {}
+    }
+}
+
+/// The last `limit` warning and error lines of a log.
+#[must_use]
+pub fn error_events(log: &str, limit: usize) -> Vec<&str> {
+    let events: Vec<&str> = log
+        .lines()
+        .filter(|line| {
+            line.contains(" ERROR ")
+                || line.contains(" WARN ")
+                || line.contains("[ERROR")
+                || line.contains("[WARN")
+                || line.starts_with("Error:")
+        })
+        .collect();
+    events[events.len().saturating_sub(limit)..].to_vec()
+}
+
+/// The last `lines` lines of a file, or `None` if it cannot be read.
+#[must_use]
+pub fn tail_file(path: &Path, lines: usize) -> Option<String> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let all: Vec<&str> = text.lines().collect();
+    Some(all[all.len().saturating_sub(lines)..].join("\n"))
+}
+
+/// Write `files` (redacted) into a `.tar.gz` at `output`, under a
+/// top-level directory named after the archive.
+///
+/// # Errors
+///
+/// Returns an error if the files cannot be staged or `tar` fails.
+pub fn write_bundle(files: &[BundleFile], output: &Path) -> Result<PathBuf, CoreError> {
+    let file_name = output
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| CoreError::Path(format!("invalid bundle path {}", output.display())))?;
+    let dir_name = file_name
+        .trim_end_matches(".tar.gz")
+        .trim_end_matches(".tgz");
+    let staging = std::env::temp_dir().join(format!("tmz-bundle-{}", std::process::id()));
+    let root = staging.join(dir_name);
+    std::fs::create_dir_all(&root)?;
+    for file in files {
+        std::fs::write(root.join(&file.name), redact(&file.contents))?;
+    }
+
+    let output = std::path::absolute(output)?;
+    let result = std::process::Command::new("tar")
+        .arg("-czf")
+        .arg(&output)
+        .arg("-C")
+        .arg(&staging)
+        .arg(dir_name)
+        .output();
+    let _ = std::fs::remove_dir_all(&staging);
+    let result = result.map_err(|e| CoreError::Other(format!("running tar: {e}")))?;
+    if !result.status.success() {
+        return Err(CoreError::Other(format!(
+            "tar failed: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "[2026-01-01T09:00:00Z INFO  tmz] synced 3 chats\n\
+                       [2026-01-01T09:01:00Z ERROR tmz] refresh failed: Authorization: Bearer abcdefghijkl123\n\
+                       [2026-01-01T09:02:00Z WARN  tmz] token=eyJhbGciOi.eyJzdWIiOi.c2ln for alex@example.com\n";
+
+    #[test]
+    fn keeps_the_latest_warnings_and_errors() {
+        assert_eq!(error_events(LOG, 10).len(), 2);
+        let last = error_events(LOG, 1);
+        assert_eq!(last.len(), 1);
+        assert!(last[0].contains("WARN"));
+    }
+
+    #[test]
+    fn redacts_tokens_and_emails_but_not_chat_ids() {
+        let redacted = redact(&error_events(LOG, 10).join("\n"));
+        assert!(redacted.contains("Bearer <redacted>"), "{redacted}");
+        assert!(redacted.contains("<jwt> for <email>"), "{redacted}");
+        assert!(!redacted.contains("abcdefghijkl123"));
+        assert!(redact("chat 19:abc@thread.v2").contains("19:abc@thread.v2"));
+    }
+
+    #[test]
+    fn redacted_config_hides_keys_people_and_groups() {
+        let mut config = AppConfig::default();
+        config.ai.translation.api_key = Some("deepl-secret-1234".to_string());
+        config.people.insert(
+            "alex".to_string(),
+            "19:secret-chat@unq.gbl.spaces".to_string(),
+        );
+        config.groups.insert(
+            "oncall".to_string(),
+            vec!["alex".to_string(), "19:secret-group@thread.v2".to_string()],
+        );
+        let text = redact_config(&config).unwrap();
+        assert!(!text.contains("deepl-secret-1234"));
+        assert!(!text.contains("secret-chat"));
+        assert!(!text.contains("secret-group"));
+        assert!(text.contains("alex = \"<redacted>\""), "{text}");
+        assert!(text.contains("oncall = \"<redacted>\""), "{text}");
+        assert!(text.contains("target_language = \"en\""));
+    }
+}
@@ -2,17 +2,66 @@
 //!
 //! Stores synced data locally for fast searching and offline access.
 //! The database lives at `$XDG_DATA_HOME/tmz/cache.db`.
+//!
+//! The daemon, TUI, and CLI may all have the database open at once. Every
+//! connection waits up to [`BUSY_TIMEOUT`] for locks, writes are retried with
+//! backoff when `SQLite` still reports `SQLITE_BUSY`, and pure-read commands
+//! can use [`Cache::open_read_only`] so they never take a write lock.
+//!
+//! With `[cache] compress_raw_json = true`, the raw JSON of conversations
+//! and messages is stored zstd-compressed as a BLOB and decompressed when
+//! read, so callers always see the JSON text. The conversation topic and
+//! read horizon that queries need are kept in their own columns.
+//! [`Cache::compact_raw_json`] rewrites existing rows after the setting
+//! changes.
 
 use crate::CoreError;
+use crate::blobs::BlobStore;
+use crate::config::{AppConfig, MessagesConfig};
+use crate::restrictions::Restrictions;
+use crate::teams::models::{Conversation, Message, MessageImportance};
 use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
 use sqlx::{Row, SqlitePool};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// How long a connection waits for a lock before failing with `SQLITE_BUSY`.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Extra attempts for a write that still fails with `SQLITE_BUSY`.
+const BUSY_RETRIES: u32 = 5;
+
+/// The magic number every zstd frame starts with; JSON text never does.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// zstd level for compressed raw JSON.
+const ZSTD_LEVEL: i32 = 3;
+
+/// WAL checkpoint outcome (see `PRAGMA wal_checkpoint`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointResult {
+    /// Whether the checkpoint was blocked by another connection.
+    pub busy: bool,
+    /// Pages in the WAL before the checkpoint.
+    pub log_pages: i64,
+    /// Pages written back to the database file.
+    pub checkpointed_pages: i64,
+}
+
+/// Default size cap for the attachment store, in bytes (512 megabytes).
+pub const DEFAULT_ATTACHMENT_LIMIT: u64 = 512 * 1024 * 1024;
 
 /// `SQLite` cache database.
 #[derive(Debug, Clone)]
 pub struct Cache {
     pool: SqlitePool,
+    blobs: BlobStore,
+    attachment_limit: u64,
+    compress_raw_json: Arc<AtomicBool>,
 }
 
 /// A cached conversation.
@@ -38,6 +87,9 @@ pub struct CachedConversation {
     pub member_names: String,
     /// Raw JSON from the API (for --json output).
     pub raw_json: String,
+    /// Encrypted chat or chat with a Skype account.
+    #[serde(default)]
+    pub restrictions: Restrictions,
 }
 
 /// A cached message.
@@ -59,10 +111,241 @@ pub struct CachedMessage {
     pub compose_time: String,
     /// Whether the message is from the current user.
     pub is_from_me: bool,
+    /// Whether the message @-mentions the current user.
+    #[serde(default)]
+    pub mentions_me: bool,
+    /// Whether the message type is not in `[messages] kept_types` and was
+    /// stored only because `keep_unknown` is on.
+    #[serde(default)]
+    pub unknown_type: bool,
+    /// Subject line of a channel post (empty if none).
+    #[serde(default)]
+    pub subject: String,
+    /// Whether the sender marked the message important or urgent.
+    #[serde(default)]
+    pub important: bool,
     /// Raw JSON from the API.
     pub raw_json: String,
 }
 
+/// A cached image waiting for OCR.
+#[derive(Debug, Clone)]
+pub struct OcrCandidate {
+    /// Message that shows the image.
+    pub message_id: String,
+    /// Conversation of the message.
+    pub conversation_id: String,
+    /// Image URL as referenced in the message HTML.
+    pub url: String,
+    /// Cached image file.
+    pub path: std::path::PathBuf,
+}
+
+/// A file shared in a message, waiting for text extraction.
+#[derive(Debug, Clone)]
+pub struct FileCandidate {
+    /// Message the file was shared in.
+    pub message_id: String,
+    /// Conversation of the message.
+    pub conversation_id: String,
+    /// File name as shown in Teams.
+    pub file_name: String,
+    /// File URL (`objectUrl`).
+    pub url: String,
+}
+
+/// Time spent in one conversation, summed by [`Cache::time_totals`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct TimeTotal {
+    /// Conversation the time was spent in.
+    pub conversation_id: String,
+    /// Its display name (empty if it is no longer cached).
+    pub name: String,
+    /// Total seconds.
+    pub seconds: i64,
+    /// Number of recorded stretches.
+    pub sessions: i64,
+}
+
+/// A message copied into the local saved items with `tmz save` or `s`
+/// in the TUI. The copy stays when the message is pruned or deleted.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SavedItem {
+    /// Saved item id, used by `tmz saved remove`.
+    pub id: i64,
+    /// Conversation the message was sent in.
+    pub conversation_id: String,
+    /// Its display name (empty if it is no longer cached).
+    pub conversation_name: String,
+    /// ID of the saved message.
+    pub message_id: String,
+    /// Sender display name.
+    pub from_display_name: String,
+    /// Plain text content.
+    pub content: String,
+    /// Original HTML content.
+    pub content_html: String,
+    /// When the message was composed (RFC 3339).
+    pub compose_time: String,
+    /// Why it was saved (may be empty).
+    pub note: String,
+    /// When it was saved (UTC, RFC 3339).
+    pub saved_at: String,
+}
+
+/// A follow-up reminder set with `tmz remind`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Reminder {
+    /// Reminder id, used by `tmz remind cancel`.
+    pub id: i64,
+    /// Conversation to come back to.
+    pub conversation_id: String,
+    /// What to do there (may be empty).
+    pub note: String,
+    /// When it was set (UTC, RFC 3339).
+    pub created_at: String,
+    /// When it is due (UTC, RFC 3339).
+    pub due_at: String,
+    /// `pending`, `fired`, or `cancelled`.
+    pub status: String,
+}
+
+/// Someone joining or leaving a conversation, from a thread activity event.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MembershipChange {
+    /// Conversation thread ID.
+    pub conversation_id: String,
+    /// The activity message recording the change.
+    pub message_id: String,
+    /// Member MRI (e.g. `8:orgid:...`), or the name when the event has none.
+    pub member_id: String,
+    /// Member display name, if the event carries it.
+    pub member_name: Option<String>,
+    /// Joined (or was added) rather than left (or was removed).
+    pub joined: bool,
+    /// Who added or removed the member; `None` when they joined or left
+    /// themselves.
+    pub actor: Option<String>,
+    /// When it happened (ISO 8601).
+    pub at: String,
+}
+
+impl MembershipChange {
+    /// Display name, or the member ID when the name is unknown.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.member_name.as_deref().unwrap_or(&self.member_id)
+    }
+}
+
+/// A shared file whose extracted text matches a search.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FileMatch {
+    /// File name as shown in Teams.
+    pub file_name: String,
+    /// File URL (`objectUrl`).
+    pub url: String,
+    /// Matching excerpt with hits wrapped in `[` and `]`.
+    pub snippet: String,
+    /// The message the file was shared in.
+    pub message: CachedMessage,
+    /// Display name of the conversation.
+    pub conversation_name: String,
+}
+
+/// A message translated into another language.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Translation {
+    /// Source language detected by the translation service (e.g. `de`).
+    pub detected_language: Option<String>,
+    /// Translated text.
+    pub text: String,
+}
+
+impl Translation {
+    /// Whether the source was already in `language` (comparing primary
+    /// subtags, so `en` matches `en-GB`), in which case the text is unchanged.
+    #[must_use]
+    pub fn is_from(&self, language: &str) -> bool {
+        let primary = |tag: &str| {
+            tag.split(['-', '_'])
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase()
+        };
+        self.detected_language
+            .as_deref()
+            .is_some_and(|detected| primary(detected) == primary(language))
+    }
+}
+
+/// An outgoing message: queued in the outbox for its undo window, or sent
+/// right away and tracked until Teams acknowledges it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OutboxItem {
+    /// Outbox id, used by `tmz outbox cancel`.
+    pub id: i64,
+    /// Destination conversation.
+    pub conversation_id: String,
+    /// Message text, or the path of an uploaded file.
+    pub content: String,
+    /// `text` or `file`.
+    pub kind: String,
+    /// When it was queued (UTC, RFC 3339).
+    pub queued_at: String,
+    /// When it is due to be sent (UTC, RFC 3339).
+    pub send_at: String,
+    /// `pending` (queued), `sending`, `uploading`, `sent`, `cancelled`, or
+    /// `failed`.
+    pub status: String,
+    /// Error message if sending failed.
+    pub error: Option<String>,
+    /// Message ID the service assigned once it accepted the message.
+    pub message_id: Option<String>,
+    /// When the service accepted it (UTC, RFC 3339).
+    pub sent_at: Option<String>,
+    /// User ID of the recipient whose presence has to turn Available
+    /// before it is sent (`tmz msg --if-available`).
+    pub until_available: Option<String>,
+}
+
+/// What an outgoing message carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutgoingKind {
+    /// A text message.
+    Text,
+    /// A file upload.
+    File,
+}
+
+impl OutgoingKind {
+    /// Name stored in the outbox's `kind` column.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::File => "file",
+        }
+    }
+}
+
+/// A person from the directory, stored as org chart lookups fetch them.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CachedPerson {
+    /// Azure AD object ID.
+    pub id: String,
+    /// Display name.
+    pub display_name: String,
+    /// Email address.
+    pub email: Option<String>,
+    /// Job title.
+    pub job_title: Option<String>,
+    /// Object ID of their manager, if known.
+    pub manager_id: Option<String>,
+    /// When the entry was last fetched (UTC, RFC 3339).
+    pub updated_at: String,
+}
+
 /// Search result combining message with conversation context.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct SearchResult {
@@ -72,6 +355,26 @@ pub struct SearchResult {
     pub conversation_name: String,
 }
 
+/// A conversation with unread messages, from [`Cache::unread_chats_since`].
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UnreadChat {
+    /// Display name of the conversation.
+    pub conversation_name: String,
+    /// Number of unread messages in the window.
+    pub unread: i64,
+    /// The newest of them.
+    pub newest: CachedMessage,
+}
+
+impl CachedMessage {
+    /// Whether this is a system event (member change, rename, call) rather
+    /// than something a person wrote.
+    #[must_use]
+    pub fn is_system_event(&self) -> bool {
+        is_system_event(&self.message_type)
+    }
+}
+
 impl Cache {
     /// Open or create the cache database at the given path.
     ///
@@ -79,6 +382,15 @@ impl Cache {
     ///
     /// Returns an error if the database cannot be opened or migrations fail.
     pub async fn open(db_path: &Path) -> Result<Self, CoreError> {
+        Self::open_with_blobs(db_path, BlobStore::default_location()?).await
+    }
+
+    /// Open or create the cache database, storing attachments in `blobs`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened or migrations fail.
+    pub async fn open_with_blobs(db_path: &Path, blobs: BlobStore) -> Result<Self, CoreError> {
         if let Some(parent) = db_path.parent() {
             std::fs::create_dir_all(parent).map_err(CoreError::Io)?;
         }
@@ -88,7 +400,8 @@ impl Cache {
             .map_err(|e| CoreError::Other(format!("invalid db path: {e}")))?
             .create_if_missing(true)
             .journal_mode(SqliteJournalMode::Wal)
-            .synchronous(SqliteSynchronous::Normal);
+            .synchronous(SqliteSynchronous::Normal)
+            .busy_timeout(BUSY_TIMEOUT);
 
         let pool = SqlitePoolOptions::new()
             .max_connections(4)
@@ -96,11 +409,85 @@ impl Cache {
             .await
             .map_err(|e| CoreError::Other(format!("opening cache db: {e}")))?;
 
-        let cache = Self { pool };
+        let cache = Self {
+            pool,
+            blobs,
+            attachment_limit: DEFAULT_ATTACHMENT_LIMIT,
+            compress_raw_json: Arc::default(),
+        };
         cache.run_migrations().await?;
         Ok(cache)
     }
 
+    /// Open an existing cache database without write access.
+    ///
+    /// Intended for commands that only read (listing, searching, stats):
+    /// they never contend for the write lock with the daemon. Migrations are
+    /// not run, so the database must already exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database does not exist or cannot be opened.
+    pub async fn open_read_only(db_path: &Path) -> Result<Self, CoreError> {
+        let db_url = format!("sqlite:{}", db_path.display());
+        let options = SqliteConnectOptions::from_str(&db_url)
+            .map_err(|e| CoreError::Other(format!("invalid db path: {e}")))?
+            .read_only(true)
+            .busy_timeout(BUSY_TIMEOUT);
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await
+            .map_err(|e| CoreError::Other(format!("opening cache db read-only: {e}")))?;
+
+        Ok(Self {
+            pool,
+            blobs: BlobStore::default_location()?,
+            attachment_limit: DEFAULT_ATTACHMENT_LIMIT,
+            compress_raw_json: Arc::default(),
+        })
+    }
+
+    /// Set the attachment store size cap in bytes (`0` disables eviction).
+    #[must_use]
+    pub const fn with_attachment_limit(mut self, max_bytes: u64) -> Self {
+        self.attachment_limit = max_bytes;
+        self
+    }
+
+    /// Store the raw JSON of conversations and messages written from now on
+    /// zstd-compressed.
+    #[must_use]
+    pub fn with_raw_json_compression(self, compress: bool) -> Self {
+        self.set_raw_json_compression(compress);
+        self
+    }
+
+    /// Switch raw JSON compression on or off for this cache and its clones,
+    /// e.g. after the config file changed.
+    pub fn set_raw_json_compression(&self, compress: bool) {
+        self.compress_raw_json.store(compress, Ordering::Relaxed);
+    }
+
+    /// Write the WAL back into the database file and truncate it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checkpoint cannot be run.
+    pub async fn checkpoint(&self) -> Result<CheckpointResult, CoreError> {
+        let row = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("checkpointing WAL: {e}")))?;
+
+        Ok(CheckpointResult {
+            busy: row.get::<i64, _>(0) != 0,
+            log_pages: row.get(1),
+            checkpointed_pages: row.get(2),
+        })
+    }
+
     #[expect(clippy::too_many_lines, reason = "sequential DDL statements")]
     async fn run_migrations(&self) -> Result<(), CoreError> {
         sqlx::query(
@@ -120,6 +507,11 @@ impl Cache {
         .execute(&self.pool)
         .await
         .map_err(|e| CoreError::Other(format!("creating conversations table: {e}")))?;
+        self.add_column_if_missing("conversations", "encrypted", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("conversations", "consumer", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_conversation_topic_and_horizon().await?;
 
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS messages (
@@ -139,6 +531,15 @@ impl Cache {
         .await
         .map_err(|e| CoreError::Other(format!("creating messages table: {e}")))?;
 
+        self.add_column_if_missing("messages", "mentions_me", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("messages", "unknown_type", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("messages", "subject", "TEXT NOT NULL DEFAULT ''")
+            .await?;
+        self.add_column_if_missing("messages", "important", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+
         // FTS5 virtual table for full-text search across messages
         sqlx::query(
             "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
@@ -202,19 +603,353 @@ impl Cache {
         .await
         .map_err(|e| CoreError::Other(format!("creating conversation name index: {e}")))?;
 
-        // Image cache: store downloaded images as blobs
+        // Index for the chat list, newest first
         sqlx::query(
-            "CREATE TABLE IF NOT EXISTS images (
+            "CREATE INDEX IF NOT EXISTS idx_conversations_activity
+             ON conversations(last_activity DESC)",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating conversation activity index: {e}")))?;
+
+        // Attachment index: URL -> content digest in the blob store
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS attachments (
                 url TEXT PRIMARY KEY,
-                data BLOB NOT NULL,
+                sha256 TEXT NOT NULL,
                 content_type TEXT NOT NULL DEFAULT 'image/png',
-                cached_at TEXT NOT NULL DEFAULT (datetime('now'))
+                size INTEGER NOT NULL DEFAULT 0,
+                cached_at TEXT NOT NULL DEFAULT (datetime('now')),
+                last_access TEXT NOT NULL DEFAULT (datetime('now'))
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating attachments table: {e}")))?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_attachments_sha256 ON attachments(sha256)")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("creating attachment index: {e}")))?;
+
+        // Text extracted from cached images by the optional OCR pass
+        sqlx::query(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS image_text USING fts5(
+                text, url UNINDEXED, message_id UNINDEXED, conversation_id UNINDEXED
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating image text table: {e}")))?;
+
+        // Text extracted from shared files when `[files] index` is on
+        sqlx::query(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS file_text USING fts5(
+                text, file_name, url UNINDEXED, message_id UNINDEXED, conversation_id UNINDEXED
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating file text table: {e}")))?;
+
+        // Conversations hidden locally with `tmz chats hide`
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS hidden_conversations (
+                conversation_id TEXT PRIMARY KEY,
+                hidden_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating hidden conversations table: {e}")))?;
+
+        // Local labels attached with `tmz chats tag`
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS conversation_tags (
+                conversation_id TEXT NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (conversation_id, tag)
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating conversation tags table: {e}")))?;
+
+        // Translations fetched with `tmz msg --translate`, per target language
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS translations (
+                conversation_id TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                target_language TEXT NOT NULL,
+                detected_language TEXT,
+                text TEXT NOT NULL,
+                translated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+                PRIMARY KEY (conversation_id, message_id, target_language)
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating translations table: {e}")))?;
+
+        // Messages held for `[sending] undo_window` before they are sent
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                conversation_id TEXT NOT NULL,
+                content TEXT NOT NULL,
+                queued_at TEXT NOT NULL,
+                send_at TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                error TEXT
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating outbox table: {e}")))?;
+        self.add_column_if_missing("outbox", "until_available", "TEXT")
+            .await?;
+        // Delivery state of every outgoing message, also those sent right away
+        self.add_column_if_missing("outbox", "kind", "TEXT NOT NULL DEFAULT 'text'")
+            .await?;
+        self.add_column_if_missing("outbox", "message_id", "TEXT")
+            .await?;
+        self.add_column_if_missing("outbox", "sent_at", "TEXT")
+            .await?;
+
+        // How often and how recently each conversation was opened, for the
+        // frecency-ranked quick switcher and `tmz msg -`
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS usage (
+                conversation_id TEXT PRIMARY KEY,
+                opens INTEGER NOT NULL DEFAULT 0,
+                score REAL NOT NULL DEFAULT 0,
+                last_opened INTEGER NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating usage table: {e}")))?;
+
+        // Time spent per conversation in the TUI (`[time_tracking]`)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS time_entries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                conversation_id TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                ended_at TEXT NOT NULL,
+                seconds INTEGER NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating time entries table: {e}")))?;
+
+        // Messages copied with `tmz save`
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS saved_items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                conversation_id TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                from_display_name TEXT NOT NULL,
+                content TEXT NOT NULL,
+                content_html TEXT NOT NULL,
+                compose_time TEXT NOT NULL,
+                note TEXT NOT NULL DEFAULT '',
+                saved_at TEXT NOT NULL,
+                UNIQUE (conversation_id, message_id)
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating saved items table: {e}")))?;
+
+        // Follow-up reminders set with `tmz remind`
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS reminders (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                conversation_id TEXT NOT NULL,
+                note TEXT NOT NULL DEFAULT '',
+                created_at TEXT NOT NULL,
+                due_at TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending'
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating reminders table: {e}")))?;
+
+        // Directory entries fetched by `tmz people manager/reports`
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS people (
+                id TEXT PRIMARY KEY,
+                display_name TEXT NOT NULL DEFAULT '',
+                email TEXT,
+                job_title TEXT,
+                manager_id TEXT,
+                updated_at TEXT NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating people table: {e}")))?;
+
+        // Joins and departures parsed from thread activity messages
+        let membership_existed: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'membership_events')",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("checking membership table: {e}")))?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS membership_events (
+                conversation_id TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                member_id TEXT NOT NULL,
+                member_name TEXT,
+                joined INTEGER NOT NULL,
+                actor TEXT,
+                at TEXT NOT NULL,
+                PRIMARY KEY (conversation_id, message_id, member_id)
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating membership table: {e}")))?;
+        if !membership_existed {
+            self.backfill_membership().await?;
+        }
+
+        // Conversations finished by an unfinished sync, so it can resume
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS sync_checkpoints (
+                conversation_id TEXT PRIMARY KEY,
+                run TEXT NOT NULL,
+                messages INTEGER NOT NULL DEFAULT 0,
+                synced_at INTEGER NOT NULL
+            )",
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("creating sync_checkpoints table: {e}")))?;
+
+        self.create_conversation_fts().await?;
+        self.migrate_legacy_images().await
+    }
+
+    /// Add a column to an existing table unless it is already there.
+    /// Returns whether it was added.
+    async fn add_column_if_missing(
+        &self,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<bool, CoreError> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("inspecting {table} columns: {e}")))?;
+        if !exists {
+            sqlx::query(&format!(
+                "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+            ))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("adding {table}.{column}: {e}")))?;
+        }
+        Ok(!exists)
+    }
+
+    /// Add the `topic` and `horizon` columns of conversations, filled from
+    /// their raw JSON, and drop the FTS triggers that read the topic from
+    /// the raw JSON so they are created again from the column.
+    async fn add_conversation_topic_and_horizon(&self) -> Result<(), CoreError> {
+        let added = self
+            .add_column_if_missing("conversations", "topic", "TEXT NOT NULL DEFAULT ''")
+            .await?;
+        self.add_column_if_missing("conversations", "horizon", "TEXT NOT NULL DEFAULT ''")
+            .await?;
+        if !added {
+            return Ok(());
+        }
+        for sql in [
+            "DROP TRIGGER IF EXISTS conversations_ai",
+            "DROP TRIGGER IF EXISTS conversations_au",
+            "UPDATE conversations SET
+                topic = COALESCE(json_extract(raw_json, '$.threadProperties.topic'), ''),
+                horizon = COALESCE(json_extract(raw_json, '$.properties.consumptionhorizon'), '')
+             WHERE json_valid(raw_json)",
+        ] {
+            sqlx::query(sql)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| CoreError::Other(format!("adding conversation topics: {e}")))?;
+        }
+        Ok(())
+    }
+
+    /// Create the conversation FTS index (names, topics, members) and its
+    /// triggers, backfilling it from existing rows on first creation.
+    async fn create_conversation_fts(&self) -> Result<(), CoreError> {
+        let existed: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'conversations_fts')",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("checking conversation FTS table: {e}")))?;
+
+        sqlx::query(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS conversations_fts USING fts5(
+                display_name, topic, member_names, conversation_id UNINDEXED
             )",
         )
         .execute(&self.pool)
         .await
-        .map_err(|e| CoreError::Other(format!("creating images table: {e}")))?;
+        .map_err(|e| CoreError::Other(format!("creating conversation FTS table: {e}")))?;
+
+        for (name, sql) in [
+            (
+                "insert",
+                "CREATE TRIGGER IF NOT EXISTS conversations_ai AFTER INSERT ON conversations BEGIN
+                    INSERT INTO conversations_fts(rowid, display_name, topic, member_names, conversation_id)
+                    VALUES (new.rowid, new.display_name, new.topic, new.member_names, new.id);
+                END",
+            ),
+            (
+                "delete",
+                "CREATE TRIGGER IF NOT EXISTS conversations_ad AFTER DELETE ON conversations BEGIN
+                    DELETE FROM conversations_fts WHERE rowid = old.rowid;
+                END",
+            ),
+            (
+                "update",
+                "CREATE TRIGGER IF NOT EXISTS conversations_au AFTER UPDATE ON conversations BEGIN
+                    DELETE FROM conversations_fts WHERE rowid = old.rowid;
+                    INSERT INTO conversations_fts(rowid, display_name, topic, member_names, conversation_id)
+                    VALUES (new.rowid, new.display_name, new.topic, new.member_names, new.id);
+                END",
+            ),
+        ] {
+            sqlx::query(sql)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| {
+                    CoreError::Other(format!("creating conversation FTS {name} trigger: {e}"))
+                })?;
+        }
 
+        if !existed {
+            sqlx::query(
+                "INSERT INTO conversations_fts(rowid, display_name, topic, member_names, conversation_id)
+                 SELECT rowid, display_name, topic, member_names, id FROM conversations",
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("backfilling conversation FTS: {e}")))?;
+        }
         Ok(())
     }
 
@@ -224,11 +959,26 @@ impl Cache {
     ///
     /// Returns an error if the database write fails.
     pub async fn upsert_conversation(&self, conv: &CachedConversation) -> Result<(), CoreError> {
-        sqlx::query(
-            "INSERT INTO conversations (id, display_name, thread_type, product_type,
+        let pool = &self.pool;
+        let raw: serde_json::Value = serde_json::from_str(&conv.raw_json).unwrap_or_default();
+        let topic = raw["threadProperties"]["topic"]
+            .as_str()
+            .unwrap_or_default();
+        let horizon = raw["properties"]["consumptionhorizon"]
+            .as_str()
+            .unwrap_or_default();
+        let compressed = self
+            .compress_raw_json
+            .load(Ordering::Relaxed)
+            .then(|| compress_text(&conv.raw_json))
+            .flatten();
+        let compressed = compressed.as_deref();
+        retry_on_busy(move || {
+            sqlx::query(
+                "INSERT INTO conversations (id, display_name, thread_type, product_type,
              last_message_preview, last_message_from, last_activity, messages_url,
-             member_names, raw_json)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             member_names, raw_json, encrypted, consumer, topic, horizon)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, ?), ?, ?, ?, ?)
              ON CONFLICT(id) DO UPDATE SET
                 display_name = excluded.display_name,
                 thread_type = excluded.thread_type,
@@ -237,20 +987,31 @@ impl Cache {
                 last_message_from = excluded.last_message_from,
                 last_activity = excluded.last_activity,
                 messages_url = excluded.messages_url,
-                member_names = excluded.member_names,
-                raw_json = excluded.raw_json",
-        )
-        .bind(&conv.id)
-        .bind(&conv.display_name)
-        .bind(&conv.thread_type)
-        .bind(&conv.product_type)
-        .bind(&conv.last_message_preview)
-        .bind(&conv.last_message_from)
-        .bind(&conv.last_activity)
-        .bind(&conv.messages_url)
-        .bind(&conv.member_names)
-        .bind(&conv.raw_json)
-        .execute(&self.pool)
+                member_names = CASE WHEN excluded.member_names = ''
+                    THEN conversations.member_names ELSE excluded.member_names END,
+                raw_json = excluded.raw_json,
+                encrypted = excluded.encrypted,
+                consumer = excluded.consumer,
+                topic = excluded.topic,
+                horizon = excluded.horizon",
+            )
+            .bind(&conv.id)
+            .bind(&conv.display_name)
+            .bind(&conv.thread_type)
+            .bind(&conv.product_type)
+            .bind(&conv.last_message_preview)
+            .bind(&conv.last_message_from)
+            .bind(&conv.last_activity)
+            .bind(&conv.messages_url)
+            .bind(&conv.member_names)
+            .bind(compressed)
+            .bind(&conv.raw_json)
+            .bind(conv.restrictions.encrypted)
+            .bind(conv.restrictions.consumer)
+            .bind(topic)
+            .bind(horizon)
+            .execute(pool)
+        })
         .await
         .map_err(|e| CoreError::Other(format!("upserting conversation: {e}")))?;
 
@@ -263,10 +1024,19 @@ impl Cache {
     ///
     /// Returns an error if the database write fails.
     pub async fn upsert_message(&self, msg: &CachedMessage) -> Result<(), CoreError> {
-        sqlx::query(
-            "INSERT INTO messages (id, conversation_id, from_display_name, content,
-             content_html, message_type, compose_time, is_from_me, raw_json)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        let pool = &self.pool;
+        let compressed = self
+            .compress_raw_json
+            .load(Ordering::Relaxed)
+            .then(|| compress_text(&msg.raw_json))
+            .flatten();
+        let compressed = compressed.as_deref();
+        retry_on_busy(move || {
+            sqlx::query(
+                "INSERT INTO messages (id, conversation_id, from_display_name, content,
+             content_html, message_type, compose_time, is_from_me, mentions_me, unknown_type,
+             subject, important, raw_json)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, ?))
              ON CONFLICT(id, conversation_id) DO UPDATE SET
                 from_display_name = excluded.from_display_name,
                 content = excluded.content,
@@ -274,604 +1044,4459 @@ impl Cache {
                 message_type = excluded.message_type,
                 compose_time = excluded.compose_time,
                 is_from_me = excluded.is_from_me,
+                mentions_me = excluded.mentions_me,
+                unknown_type = excluded.unknown_type,
+                subject = excluded.subject,
+                important = excluded.important,
                 raw_json = excluded.raw_json",
-        )
-        .bind(&msg.id)
-        .bind(&msg.conversation_id)
-        .bind(&msg.from_display_name)
-        .bind(&msg.content)
-        .bind(&msg.content_html)
-        .bind(&msg.message_type)
-        .bind(&msg.compose_time)
-        .bind(msg.is_from_me)
-        .bind(&msg.raw_json)
-        .execute(&self.pool)
+            )
+            .bind(&msg.id)
+            .bind(&msg.conversation_id)
+            .bind(&msg.from_display_name)
+            .bind(&msg.content)
+            .bind(&msg.content_html)
+            .bind(&msg.message_type)
+            .bind(&msg.compose_time)
+            .bind(msg.is_from_me)
+            .bind(msg.mentions_me)
+            .bind(msg.unknown_type)
+            .bind(&msg.subject)
+            .bind(msg.important)
+            .bind(compressed)
+            .bind(&msg.raw_json)
+            .execute(pool)
+        })
         .await
         .map_err(|e| CoreError::Other(format!("upserting message: {e}")))?;
 
+        let changes = membership_changes(msg);
+        if !changes.is_empty() {
+            self.store_membership_changes(&changes).await?;
+        }
         Ok(())
     }
 
-    /// List conversations, ordered by last activity.
+    /// Show `msg` as the last message of its conversation in the chat list,
+    /// unless a newer one is recorded there. Returns whether the
+    /// conversation is cached.
     ///
     /// # Errors
     ///
-    /// Returns an error if the database read fails.
-    pub async fn list_conversations(
-        &self,
-        limit: i64,
-    ) -> Result<Vec<CachedConversation>, CoreError> {
-        let rows = sqlx::query("SELECT * FROM conversations ORDER BY last_activity DESC LIMIT ?")
-            .bind(limit)
-            .fetch_all(&self.pool)
+    /// Returns an error if the database write fails.
+    pub async fn record_last_message(&self, msg: &CachedMessage) -> Result<bool, CoreError> {
+        let pool = &self.pool;
+        let result = retry_on_busy(move || {
+            sqlx::query(
+                "UPDATE conversations SET
+                    last_message_preview = CASE WHEN ?1 >= last_activity
+                        THEN ?2 ELSE last_message_preview END,
+                    last_message_from = CASE WHEN ?1 >= last_activity
+                        THEN ?3 ELSE last_message_from END,
+                    last_activity = MAX(last_activity, ?1)
+                 WHERE id = ?4",
+            )
+            .bind(&msg.compose_time)
+            .bind(&msg.content)
+            .bind(&msg.from_display_name)
+            .bind(&msg.conversation_id)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("recording last message: {e}")))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn store_membership_changes(
+        &self,
+        changes: &[MembershipChange],
+    ) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        for change in changes {
+            retry_on_busy(move || {
+                sqlx::query(
+                    "INSERT OR REPLACE INTO membership_events
+                     (conversation_id, message_id, member_id, member_name, joined, actor, at)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(&change.conversation_id)
+                .bind(&change.message_id)
+                .bind(&change.member_id)
+                .bind(&change.member_name)
+                .bind(change.joined)
+                .bind(&change.actor)
+                .bind(&change.at)
+                .execute(pool)
+            })
             .await
-            .map_err(|e| CoreError::Other(format!("listing conversations: {e}")))?;
+            .map_err(|e| CoreError::Other(format!("storing membership change: {e}")))?;
+        }
+        Ok(())
+    }
 
-        Ok(rows.iter().map(row_to_conversation).collect())
+    /// Joins and departures recorded for a conversation, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn membership_history(
+        &self,
+        conversation_id: &str,
+    ) -> Result<Vec<MembershipChange>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM membership_events WHERE conversation_id = ?
+             ORDER BY at, message_id, member_id",
+        )
+        .bind(conversation_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("reading membership history: {e}")))?;
+        Ok(rows
+            .iter()
+            .map(|row| MembershipChange {
+                conversation_id: row.get("conversation_id"),
+                message_id: row.get("message_id"),
+                member_id: row.get("member_id"),
+                member_name: row.get("member_name"),
+                joined: row.get("joined"),
+                actor: row.get("actor"),
+                at: row.get("at"),
+            })
+            .collect())
     }
 
-    /// Find a conversation by fuzzy matching on display name, member names, or ID.
+    /// Record the membership events of messages cached before the
+    /// `membership_events` table existed.
+    async fn backfill_membership(&self) -> Result<(), CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM messages WHERE message_type IN
+             ('ThreadActivity/AddMember', 'ThreadActivity/DeleteMember',
+              'ThreadActivity/MemberJoined', 'ThreadActivity/MemberLeft')",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("reading membership events: {e}")))?;
+        for row in &rows {
+            self.store_membership_changes(&membership_changes(&row_to_message(row)))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// List conversations, ordered by last activity. Hidden conversations
+    /// are left out.
     ///
     /// # Errors
     ///
     /// Returns an error if the database read fails.
-    pub async fn find_conversation(
+    pub async fn list_conversations(
         &self,
-        query: &str,
+        limit: i64,
+    ) -> Result<Vec<CachedConversation>, CoreError> {
+        self.list_conversations_with_hidden(limit, false).await
+    }
+
+    /// List conversations, ordered by last activity, optionally including
+    /// hidden ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn list_conversations_with_hidden(
+        &self,
+        limit: i64,
+        include_hidden: bool,
     ) -> Result<Vec<CachedConversation>, CoreError> {
-        let pattern = format!("%{query}%");
         let rows = sqlx::query(
             "SELECT * FROM conversations
-             WHERE display_name LIKE ?1 COLLATE NOCASE
-                OR member_names LIKE ?1 COLLATE NOCASE
-                OR id LIKE ?1 COLLATE NOCASE
+             WHERE ?1 OR id NOT IN (SELECT conversation_id FROM hidden_conversations)
              ORDER BY last_activity DESC
-             LIMIT 10",
+             LIMIT ?2",
         )
-        .bind(&pattern)
+        .bind(include_hidden)
+        .bind(limit)
         .fetch_all(&self.pool)
         .await
-        .map_err(|e| CoreError::Other(format!("finding conversation: {e}")))?;
+        .map_err(|e| CoreError::Other(format!("listing conversations: {e}")))?;
 
         Ok(rows.iter().map(row_to_conversation).collect())
     }
 
-    /// Get recent messages from a conversation.
+    /// Like [`Cache::list_conversations`], with only what a chat list draws:
+    /// no raw JSON, member names only for chats without a name, and previews
+    /// cut short. Much less to read on a large cache.
     ///
     /// # Errors
     ///
     /// Returns an error if the database read fails.
-    pub async fn get_messages(
+    pub async fn list_conversation_summaries(
         &self,
-        conversation_id: &str,
         limit: i64,
-    ) -> Result<Vec<CachedMessage>, CoreError> {
+    ) -> Result<Vec<CachedConversation>, CoreError> {
         let rows = sqlx::query(
-            "SELECT * FROM messages
-             WHERE conversation_id = ?
-             ORDER BY compose_time DESC
+            "SELECT id, display_name, thread_type, product_type,
+                    substr(last_message_preview, 1, 200) AS last_message_preview,
+                    last_message_from, last_activity, messages_url,
+                    CASE WHEN display_name = '' THEN member_names ELSE '' END AS member_names,
+                    '{}' AS raw_json, encrypted, consumer
+             FROM conversations
+             WHERE id NOT IN (SELECT conversation_id FROM hidden_conversations)
+             ORDER BY last_activity DESC
              LIMIT ?",
         )
-        .bind(conversation_id)
         .bind(limit)
         .fetch_all(&self.pool)
         .await
-        .map_err(|e| CoreError::Other(format!("getting messages: {e}")))?;
+        .map_err(|e| CoreError::Other(format!("listing conversation summaries: {e}")))?;
 
-        // Return in chronological order (oldest first)
-        let mut msgs: Vec<CachedMessage> = rows.iter().map(row_to_message).collect();
-        msgs.reverse();
-        Ok(msgs)
+        Ok(rows.iter().map(row_to_conversation).collect())
     }
 
-    /// Get the latest messages across the most recently active conversations.
-    ///
-    /// Returns messages grouped by conversation, ordered by last activity.
-    /// Each conversation returns up to `per_chat` most recent messages.
+    /// Hide a conversation locally. Returns `false` if it was already hidden.
     ///
     /// # Errors
     ///
-    /// Returns an error if the database read fails.
-    pub async fn latest_across_chats(
-        &self,
-        num_chats: i64,
-        per_chat: i64,
-    ) -> Result<Vec<(CachedConversation, Vec<CachedMessage>)>, CoreError> {
-        let convs = self.list_conversations(num_chats).await?;
-        let mut result = Vec::new();
-        for conv in convs {
-            let msgs = self.get_messages(&conv.id, per_chat).await?;
-            if !msgs.is_empty() {
-                result.push((conv, msgs));
-            }
-        }
-        Ok(result)
+    /// Returns an error if the database write fails.
+    pub async fn hide_conversation(&self, conversation_id: &str) -> Result<bool, CoreError> {
+        let pool = &self.pool;
+        let result = retry_on_busy(move || {
+            sqlx::query("INSERT OR IGNORE INTO hidden_conversations (conversation_id) VALUES (?)")
+                .bind(conversation_id)
+                .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("hiding conversation: {e}")))?;
+        Ok(result.rows_affected() > 0)
     }
 
-    /// Full-text search across all cached messages.
+    /// Unhide a conversation. Returns `false` if it was not hidden.
     ///
     /// # Errors
     ///
-    /// Returns an error if the database read fails.
-    pub async fn search(&self, query: &str, limit: i64) -> Result<Vec<SearchResult>, CoreError> {
-        let rows = sqlx::query(
-            "SELECT m.*, c.display_name AS conversation_name
-             FROM messages_fts fts
-             JOIN messages m ON m.rowid = fts.rowid
-             LEFT JOIN conversations c ON c.id = m.conversation_id
-             WHERE messages_fts MATCH ?
-             ORDER BY m.compose_time DESC
-             LIMIT ?",
-        )
-        .bind(query)
-        .bind(limit)
-        .fetch_all(&self.pool)
+    /// Returns an error if the database write fails.
+    pub async fn unhide_conversation(&self, conversation_id: &str) -> Result<bool, CoreError> {
+        let pool = &self.pool;
+        let result = retry_on_busy(move || {
+            sqlx::query("DELETE FROM hidden_conversations WHERE conversation_id = ?")
+                .bind(conversation_id)
+                .execute(pool)
+        })
         .await
-        .map_err(|e| CoreError::Other(format!("searching messages: {e}")))?;
-
-        Ok(rows
-            .iter()
-            .map(|row| SearchResult {
-                message: row_to_message(row),
-                conversation_name: row.get::<String, _>("conversation_name"),
-            })
-            .collect())
+        .map_err(|e| CoreError::Other(format!("unhiding conversation: {e}")))?;
+        Ok(result.rows_affected() > 0)
     }
 
-    /// Full-text search within a specific conversation.
+    /// Hidden conversations with when they were hidden, most recent first.
     ///
     /// # Errors
     ///
     /// Returns an error if the database read fails.
-    pub async fn search_in_conversation(
+    pub async fn list_hidden_conversations(
         &self,
-        query: &str,
-        conversation_id: &str,
-        limit: i64,
-    ) -> Result<Vec<SearchResult>, CoreError> {
+    ) -> Result<Vec<(CachedConversation, String)>, CoreError> {
         let rows = sqlx::query(
-            "SELECT m.*, c.display_name AS conversation_name
-             FROM messages_fts fts
-             JOIN messages m ON m.rowid = fts.rowid
-             LEFT JOIN conversations c ON c.id = m.conversation_id
-             WHERE messages_fts MATCH ?
-               AND m.conversation_id = ?
-             ORDER BY m.compose_time DESC
-             LIMIT ?",
+            "SELECT c.*, h.hidden_at
+             FROM hidden_conversations h
+             JOIN conversations c ON c.id = h.conversation_id
+             ORDER BY h.hidden_at DESC",
         )
-        .bind(query)
-        .bind(conversation_id)
-        .bind(limit)
         .fetch_all(&self.pool)
         .await
-        .map_err(|e| CoreError::Other(format!("searching messages: {e}")))?;
+        .map_err(|e| CoreError::Other(format!("listing hidden conversations: {e}")))?;
 
         Ok(rows
             .iter()
-            .map(|row| SearchResult {
-                message: row_to_message(row),
-                conversation_name: row.get::<String, _>("conversation_name"),
-            })
+            .map(|row| (row_to_conversation(row), row.get("hidden_at")))
             .collect())
     }
 
-    /// Get cache statistics.
+    /// IDs of all hidden conversations.
     ///
     /// # Errors
     ///
     /// Returns an error if the database read fails.
-    /// Store an image in the cache.
+    pub async fn hidden_conversation_ids(&self) -> Result<HashSet<String>, CoreError> {
+        sqlx::query_scalar("SELECT conversation_id FROM hidden_conversations")
+            .fetch_all(&self.pool)
+            .await
+            .map(|ids| ids.into_iter().collect())
+            .map_err(|e| CoreError::Other(format!("listing hidden conversations: {e}")))
+    }
+
+    /// Tag a conversation. Returns `false` if it already had the tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tag is not valid (see [`normalize_tag`]) or
+    /// the database write fails.
+    pub async fn tag_conversation(
+        &self,
+        conversation_id: &str,
+        tag: &str,
+    ) -> Result<bool, CoreError> {
+        let tag =
+            normalize_tag(tag).ok_or_else(|| CoreError::Other(format!("invalid tag '{tag}'")))?;
+        let pool = &self.pool;
+        let result = retry_on_busy(|| {
+            sqlx::query(
+                "INSERT OR IGNORE INTO conversation_tags (conversation_id, tag) VALUES (?, ?)",
+            )
+            .bind(conversation_id)
+            .bind(&tag)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("tagging conversation: {e}")))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Remove a tag from a conversation. Returns `false` if it did not have
+    /// the tag.
     ///
     /// # Errors
     ///
     /// Returns an error if the database write fails.
-    pub async fn cache_image(
+    pub async fn untag_conversation(
         &self,
-        url: &str,
-        data: &[u8],
-        content_type: &str,
-    ) -> Result<(), CoreError> {
-        sqlx::query(
-            "INSERT INTO images (url, data, content_type, cached_at)
-             VALUES (?, ?, ?, datetime('now'))
-             ON CONFLICT(url) DO UPDATE SET
-                data = excluded.data,
-                content_type = excluded.content_type,
-                cached_at = excluded.cached_at",
-        )
-        .bind(url)
-        .bind(data)
-        .bind(content_type)
-        .execute(&self.pool)
+        conversation_id: &str,
+        tag: &str,
+    ) -> Result<bool, CoreError> {
+        let tag = normalize_tag(tag).unwrap_or_default();
+        let pool = &self.pool;
+        let result = retry_on_busy(|| {
+            sqlx::query("DELETE FROM conversation_tags WHERE conversation_id = ? AND tag = ?")
+                .bind(conversation_id)
+                .bind(&tag)
+                .execute(pool)
+        })
         .await
-        .map_err(|e| CoreError::Other(format!("caching image: {e}")))?;
+        .map_err(|e| CoreError::Other(format!("untagging conversation: {e}")))?;
+        Ok(result.rows_affected() > 0)
+    }
 
-        Ok(())
+    /// Tags of every tagged conversation, keyed by conversation ID and
+    /// sorted, including the `(conversation ID, tag)` pairs in `shared`
+    /// (see [`crate::Config::shared_tags`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn conversation_tags(
+        &self,
+        shared: &[(String, String)],
+    ) -> Result<BTreeMap<String, Vec<String>>, CoreError> {
+        let rows: Vec<(String, String)> =
+            sqlx::query_as("SELECT conversation_id, tag FROM conversation_tags")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| CoreError::Other(format!("listing conversation tags: {e}")))?;
+        let mut tags: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (conversation_id, tag) in rows.into_iter().chain(shared.iter().cloned()) {
+            let entry = tags.entry(conversation_id).or_default();
+            if !entry.contains(&tag) {
+                entry.push(tag);
+            }
+        }
+        for entry in tags.values_mut() {
+            entry.sort();
+        }
+        Ok(tags)
     }
 
-    /// Retrieve a cached image by URL. Returns `None` if not cached.
+    /// IDs of the conversations tagged `tag`, in the cache or in `shared`.
     ///
     /// # Errors
     ///
     /// Returns an error if the database read fails.
-    pub async fn get_image(&self, url: &str) -> Result<Option<Vec<u8>>, CoreError> {
-        let row: Option<(Vec<u8>,)> = sqlx::query_as("SELECT data FROM images WHERE url = ?")
-            .bind(url)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|e| CoreError::Other(format!("getting cached image: {e}")))?;
+    pub async fn conversations_tagged(
+        &self,
+        tag: &str,
+        shared: &[(String, String)],
+    ) -> Result<HashSet<String>, CoreError> {
+        let tag = normalize_tag(tag).unwrap_or_default();
+        Ok(self
+            .conversation_tags(shared)
+            .await?
+            .into_iter()
+            .filter(|(_, tags)| tags.contains(&tag))
+            .map(|(id, _)| id)
+            .collect())
+    }
 
-        Ok(row.map(|(data,)| data))
+    /// Resolve `target`, a people alias from `config`, a conversation ID, or
+    /// a cached chat name, to one conversation. Aliases may point at an ID
+    /// or a name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn resolve_target(
+        &self,
+        config: &AppConfig,
+        target: &str,
+    ) -> Result<TargetMatch, CoreError> {
+        if let Some(id) = config.conversation_id(target) {
+            return Ok(TargetMatch::Found(id.to_string()));
+        }
+        let resolved = config.resolve_alias(target).unwrap_or(target);
+        let mut matches = self.find_conversation(resolved).await?;
+        Ok(match matches.len() {
+            0 => TargetMatch::NotFound,
+            1 => TargetMatch::Found(matches.remove(0).id),
+            _ => TargetMatch::Ambiguous(matches),
+        })
     }
 
-    /// Check if an image URL is already cached.
+    /// Find a conversation by fuzzy matching on display name, member names, or ID.
     ///
     /// # Errors
     ///
     /// Returns an error if the database read fails.
-    pub async fn has_image(&self, url: &str) -> Result<bool, CoreError> {
-        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM images WHERE url = ?")
-            .bind(url)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| CoreError::Other(format!("checking image cache: {e}")))?;
+    pub async fn find_conversation(
+        &self,
+        query: &str,
+    ) -> Result<Vec<CachedConversation>, CoreError> {
+        let pattern = format!("%{query}%");
+        let rows = sqlx::query(
+            "SELECT * FROM conversations
+             WHERE display_name LIKE ?1 COLLATE NOCASE
+                OR member_names LIKE ?1 COLLATE NOCASE
+                OR id LIKE ?1 COLLATE NOCASE
+             ORDER BY last_activity DESC
+             LIMIT 10",
+        )
+        .bind(&pattern)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("finding conversation: {e}")))?;
 
-        Ok(count > 0)
+        Ok(rows.iter().map(row_to_conversation).collect())
     }
 
-    /// Delete cached images older than the given number of days.
-    /// Returns the number of images pruned.
+    /// Get recent messages from a conversation.
     ///
     /// # Errors
     ///
-    /// Returns an error if the database write fails.
-    pub async fn prune_images(&self, older_than_days: u32) -> Result<u64, CoreError> {
-        let result = sqlx::query("DELETE FROM images WHERE cached_at < datetime('now', ?)")
-            .bind(format!("-{older_than_days} days"))
-            .execute(&self.pool)
-            .await
-            .map_err(|e| CoreError::Other(format!("pruning images: {e}")))?;
+    /// Returns an error if the database read fails.
+    pub async fn get_messages(
+        &self,
+        conversation_id: &str,
+        limit: i64,
+    ) -> Result<Vec<CachedMessage>, CoreError> {
+        self.recent_messages("*", conversation_id, limit).await
+    }
 
-        Ok(result.rows_affected())
+    /// Like [`Cache::get_messages`], without the raw JSON: `raw_json` is
+    /// `{}`, and [`Cache::get_message`] reads a message in full when it is
+    /// needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn get_messages_without_raw(
+        &self,
+        conversation_id: &str,
+        limit: i64,
+    ) -> Result<Vec<CachedMessage>, CoreError> {
+        self.recent_messages(
+            "id, conversation_id, from_display_name, content, content_html, message_type,
+             compose_time, is_from_me, mentions_me, unknown_type, subject, important,
+             '{}' AS raw_json",
+            conversation_id,
+            limit,
+        )
+        .await
     }
 
-    /// Get cache statistics.
+    /// Names of people other than you who sent cached messages, with how
+    /// many each sent, most first. Only names containing `query`
+    /// (case-insensitive), and with `conversation_id` only its senders.
     ///
     /// # Errors
     ///
     /// Returns an error if the database read fails.
-    pub async fn stats(&self) -> Result<CacheStats, CoreError> {
-        let conv_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM conversations")
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| CoreError::Other(format!("counting conversations: {e}")))?;
+    pub async fn senders_matching(
+        &self,
+        query: &str,
+        conversation_id: Option<&str>,
+    ) -> Result<Vec<(String, i64)>, CoreError> {
+        sqlx::query_as(
+            "SELECT from_display_name, COUNT(*) AS n FROM messages
+             WHERE is_from_me = 0 AND from_display_name != ''
+               AND from_display_name LIKE ?1
+               AND (?2 IS NULL OR conversation_id = ?2)
+             GROUP BY from_display_name
+             ORDER BY n DESC, from_display_name",
+        )
+        .bind(format!("%{query}%"))
+        .bind(conversation_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing senders: {e}")))
+    }
 
-        let msg_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+    /// Every message, without raw JSON, of the conversations `sender` wrote
+    /// in, grouped by conversation and oldest first within each. System
+    /// events are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn chats_with_sender(&self, sender: &str) -> Result<Vec<CachedMessage>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT id, conversation_id, from_display_name, '' AS content, '' AS content_html,
+                    message_type, compose_time, is_from_me, mentions_me, unknown_type,
+                    '' AS subject, important, '{}' AS raw_json
+             FROM messages
+             WHERE conversation_id IN
+                 (SELECT DISTINCT conversation_id FROM messages
+                  WHERE from_display_name = ? AND is_from_me = 0)
+               AND message_type NOT LIKE 'ThreadActivity/%' AND message_type != 'Event/Call'
+             ORDER BY conversation_id, compose_time",
+        )
+        .bind(sender)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("reading chats with {sender}: {e}")))?;
+        Ok(rows.iter().map(row_to_message).collect())
+    }
+
+    /// The newest `limit` messages `sender` wrote in any conversation,
+    /// oldest first, with their conversation names. `since` is an RFC 3339
+    /// UTC timestamp. Hidden conversations and system events are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn messages_from(
+        &self,
+        sender: &str,
+        since: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT m.*,
+                    COALESCE(NULLIF(c.display_name, ''), c.member_names, '') AS conversation_name
+             FROM messages m
+             LEFT JOIN conversations c ON c.id = m.conversation_id
+             WHERE m.from_display_name = ?1 AND m.is_from_me = 0
+               AND (?2 IS NULL OR m.compose_time >= ?2)
+               AND m.message_type NOT LIKE 'ThreadActivity/%' AND m.message_type != 'Event/Call'
+               AND m.conversation_id NOT IN (SELECT conversation_id FROM hidden_conversations)
+             ORDER BY m.compose_time DESC
+             LIMIT ?3",
+        )
+        .bind(sender)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("reading messages from {sender}: {e}")))?;
+        Ok(rows
+            .iter()
+            .rev()
+            .map(|row| SearchResult {
+                message: row_to_message(row),
+                conversation_name: row.get::<String, _>("conversation_name"),
+            })
+            .collect())
+    }
+
+    async fn recent_messages(
+        &self,
+        columns: &str,
+        conversation_id: &str,
+        limit: i64,
+    ) -> Result<Vec<CachedMessage>, CoreError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {columns} FROM messages
+             WHERE conversation_id = ?
+             ORDER BY compose_time DESC
+             LIMIT ?"
+        ))
+        .bind(conversation_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("getting messages: {e}")))?;
+
+        // Return in chronological order (oldest first)
+        let mut msgs: Vec<CachedMessage> = rows.iter().map(row_to_message).collect();
+        msgs.reverse();
+        Ok(msgs)
+    }
+
+    /// Get a single message by id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn get_message(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+    ) -> Result<Option<CachedMessage>, CoreError> {
+        let row = sqlx::query("SELECT * FROM messages WHERE conversation_id = ? AND id = ?")
+            .bind(conversation_id)
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("getting message: {e}")))?;
+        Ok(row.as_ref().map(row_to_message))
+    }
+
+    /// Get messages from a conversation within a time range, oldest first.
+    ///
+    /// `since` and `until` are RFC 3339 UTC timestamps compared against the
+    /// compose time; `until` is exclusive.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn get_messages_between(
+        &self,
+        conversation_id: &str,
+        since: Option<&str>,
+        until: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<CachedMessage>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM messages
+             WHERE conversation_id = ?1
+               AND (?2 IS NULL OR compose_time >= ?2)
+               AND (?3 IS NULL OR compose_time < ?3)
+             ORDER BY compose_time ASC
+             LIMIT ?4",
+        )
+        .bind(conversation_id)
+        .bind(since)
+        .bind(until)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("getting messages: {e}")))?;
+
+        Ok(rows.iter().map(row_to_message).collect())
+    }
+
+    /// Get the latest messages across the most recently active conversations.
+    ///
+    /// Returns messages grouped by conversation, ordered by last activity.
+    /// Each conversation returns up to `per_chat` most recent messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn latest_across_chats(
+        &self,
+        num_chats: i64,
+        per_chat: i64,
+    ) -> Result<Vec<(CachedConversation, Vec<CachedMessage>)>, CoreError> {
+        let convs = self.list_conversations(num_chats).await?;
+        let mut result = Vec::new();
+        for conv in convs {
+            let msgs = self.get_messages(&conv.id, per_chat).await?;
+            if !msgs.is_empty() {
+                result.push((conv, msgs));
+            }
+        }
+        Ok(result)
+    }
+
+    /// The newest messages of all conversations interleaved, newest first,
+    /// with their conversation names. Unlike [`Self::latest_across_chats`]
+    /// this pages: pass the compose time and ID of the last message of the
+    /// previous page as `before` to get the messages older than it. Hidden
+    /// conversations and system events are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn timeline(
+        &self,
+        before: Option<(&str, &str)>,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT m.*,
+                    COALESCE(NULLIF(c.display_name, ''), c.member_names, '') AS conversation_name
+             FROM messages m
+             LEFT JOIN conversations c ON c.id = m.conversation_id
+             WHERE (?1 IS NULL OR (m.compose_time, m.id) < (?1, ?2))
+               AND m.message_type NOT LIKE 'ThreadActivity/%' AND m.message_type != 'Event/Call'
+               AND m.conversation_id NOT IN (SELECT conversation_id FROM hidden_conversations)
+             ORDER BY m.compose_time DESC, m.id DESC
+             LIMIT ?3",
+        )
+        .bind(before.map(|(time, _)| time))
+        .bind(before.map(|(_, id)| id))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("reading the timeline: {e}")))?;
+        Ok(rows
+            .iter()
+            .map(|row| SearchResult {
+                message: row_to_message(row),
+                conversation_name: row.get::<String, _>("conversation_name"),
+            })
+            .collect())
+    }
+
+    /// Record the distinct senders seen in a conversation's cached messages
+    /// as its member names, so chats can be found by who is in them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn refresh_member_names(&self, conversation_id: &str) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || {
+            sqlx::query(
+                "UPDATE conversations SET member_names = (
+                    SELECT COALESCE(group_concat(name, ', '), '') FROM (
+                        SELECT DISTINCT from_display_name AS name FROM messages
+                        WHERE conversation_id = ?1 AND from_display_name != ''
+                        ORDER BY name
+                    )
+                 )
+                 WHERE id = ?1",
+            )
+            .bind(conversation_id)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("updating member names: {e}")))?;
+
+        Ok(())
+    }
+
+    /// Full-text search across conversation names, topics, and member names.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn search_conversations(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<CachedConversation>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT c.*
+             FROM conversations_fts fts
+             JOIN conversations c ON c.rowid = fts.rowid
+             WHERE conversations_fts MATCH ?
+               AND c.id NOT IN (SELECT conversation_id FROM hidden_conversations)
+             ORDER BY fts.rank, c.last_activity DESC
+             LIMIT ?",
+        )
+        .bind(query)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("searching conversations: {e}")))?;
+
+        Ok(rows.iter().map(row_to_conversation).collect())
+    }
+
+    /// Count messages matching a full-text query, optionally within one
+    /// conversation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn count_message_matches(
+        &self,
+        query: &str,
+        conversation_id: Option<&str>,
+    ) -> Result<i64, CoreError> {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM messages_fts fts
+             JOIN messages m ON m.rowid = fts.rowid
+             WHERE messages_fts MATCH ?1
+               AND (?2 IS NULL OR m.conversation_id = ?2)
+               AND (?2 IS NOT NULL OR m.conversation_id NOT IN
+                    (SELECT conversation_id FROM hidden_conversations))",
+        )
+        .bind(query)
+        .bind(conversation_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("counting message matches: {e}")))
+    }
+
+    /// Count conversations whose name, topic, or members match a query.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn count_conversation_matches(&self, query: &str) -> Result<i64, CoreError> {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM conversations_fts
+             WHERE conversations_fts MATCH ?
+               AND conversation_id NOT IN (SELECT conversation_id FROM hidden_conversations)",
+        )
+        .bind(query)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("counting conversation matches: {e}")))
+    }
+
+    /// Messages that @-mention the current user, newest first, optionally
+    /// narrowed by a full-text query and a conversation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn search_mentions(
+        &self,
+        query: Option<&str>,
+        conversation_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT m.*, COALESCE(c.display_name, '') AS conversation_name
+             FROM messages m
+             LEFT JOIN conversations c ON c.id = m.conversation_id
+             WHERE m.mentions_me = 1
+               AND (?1 IS NULL OR m.rowid IN
+                    (SELECT rowid FROM messages_fts WHERE messages_fts MATCH ?1))
+               AND (?2 IS NULL OR m.conversation_id = ?2)
+               AND (?2 IS NOT NULL OR m.conversation_id NOT IN
+                    (SELECT conversation_id FROM hidden_conversations))
+             ORDER BY m.compose_time DESC
+             LIMIT ?3",
+        )
+        .bind(query)
+        .bind(conversation_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("searching mentions: {e}")))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| SearchResult {
+                message: row_to_message(row),
+                conversation_name: row.get::<String, _>("conversation_name"),
+            })
+            .collect())
+    }
+
+    /// Count mentions of the current user that arrived after the
+    /// conversation's read horizon (`properties.consumptionhorizon`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn unread_mention_count(&self) -> Result<i64, CoreError> {
+        sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM messages m
+             JOIN conversations c ON c.id = m.conversation_id
+             WHERE m.mentions_me = 1
+               AND c.id NOT IN (SELECT conversation_id FROM hidden_conversations)
+               AND {UNREAD}"
+        ))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("counting unread mentions: {e}")))
+    }
+
+    /// Unread mentions of the current user composed at or after `since`
+    /// (an RFC 3339 UTC timestamp), newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn unread_mentions_since(
+        &self,
+        since: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, CoreError> {
+        let rows = sqlx::query(&format!(
+            "SELECT m.*, c.display_name AS conversation_name FROM messages m
+             JOIN conversations c ON c.id = m.conversation_id
+             WHERE m.mentions_me = 1 AND m.compose_time >= ?1
+               AND c.id NOT IN (SELECT conversation_id FROM hidden_conversations)
+               AND {UNREAD}
+             ORDER BY m.compose_time DESC
+             LIMIT ?2"
+        ))
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing unread mentions: {e}")))?;
+        Ok(rows
+            .iter()
+            .map(|row| SearchResult {
+                message: row_to_message(row),
+                conversation_name: row.get("conversation_name"),
+            })
+            .collect())
+    }
+
+    /// Number of unread messages from others in each conversation that has
+    /// any, by conversation ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn unread_counts(&self) -> Result<HashMap<String, i64>, CoreError> {
+        let rows = sqlx::query(&format!(
+            "SELECT m.conversation_id, COUNT(*) AS unread
+             FROM messages m
+             JOIN conversations c ON c.id = m.conversation_id
+             WHERE m.is_from_me = 0
+               AND m.message_type NOT LIKE 'ThreadActivity/%'
+               AND m.message_type != 'Event/Call'
+               AND {UNREAD}
+             GROUP BY m.conversation_id"
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("counting unread messages: {e}")))?;
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("conversation_id"), row.get("unread")))
+            .collect())
+    }
+
+    /// Conversations with unread messages from others composed at or after
+    /// `since`, busiest first, each with its newest unread message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn unread_chats_since(
+        &self,
+        since: &str,
+        limit: i64,
+    ) -> Result<Vec<UnreadChat>, CoreError> {
+        // With a single MAX(), SQLite takes the bare m.* columns from the
+        // row holding the maximum, i.e. the newest message.
+        let rows = sqlx::query(&format!(
+            "SELECT m.*, c.display_name AS conversation_name,
+                    COUNT(*) AS unread, MAX(m.compose_time) AS newest
+             FROM messages m
+             JOIN conversations c ON c.id = m.conversation_id
+             WHERE m.is_from_me = 0 AND m.compose_time >= ?1
+               AND m.message_type NOT LIKE 'ThreadActivity/%'
+               AND m.message_type != 'Event/Call'
+               AND c.id NOT IN (SELECT conversation_id FROM hidden_conversations)
+               AND {UNREAD}
+             GROUP BY m.conversation_id
+             ORDER BY unread DESC, newest DESC
+             LIMIT ?2"
+        ))
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing unread chats: {e}")))?;
+        Ok(rows
+            .iter()
+            .map(|row| UnreadChat {
+                conversation_name: row.get("conversation_name"),
+                unread: row.get("unread"),
+                newest: row_to_message(row),
+            })
+            .collect())
+    }
+
+    /// Full-text search across all cached messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn search(&self, query: &str, limit: i64) -> Result<Vec<SearchResult>, CoreError> {
+        let results = self.search_message_text(query, limit).await?;
+        Ok(self.with_image_matches(results, query, None, limit).await)
+    }
+
+    async fn search_message_text(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT m.*, c.display_name AS conversation_name
+             FROM messages_fts fts
+             JOIN messages m ON m.rowid = fts.rowid
+             LEFT JOIN conversations c ON c.id = m.conversation_id
+             WHERE messages_fts MATCH ?
+               AND m.conversation_id NOT IN (SELECT conversation_id FROM hidden_conversations)
+             ORDER BY m.compose_time DESC
+             LIMIT ?",
+        )
+        .bind(query)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("searching messages: {e}")))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| SearchResult {
+                message: row_to_message(row),
+                conversation_name: row.get::<String, _>("conversation_name"),
+            })
+            .collect())
+    }
+
+    /// Full-text search within a specific conversation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn search_in_conversation(
+        &self,
+        query: &str,
+        conversation_id: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, CoreError> {
+        let results = self
+            .search_conversation_text(query, conversation_id, limit)
+            .await?;
+        Ok(self
+            .with_image_matches(results, query, Some(conversation_id), limit)
+            .await)
+    }
+
+    async fn search_conversation_text(
+        &self,
+        query: &str,
+        conversation_id: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT m.*, c.display_name AS conversation_name
+             FROM messages_fts fts
+             JOIN messages m ON m.rowid = fts.rowid
+             LEFT JOIN conversations c ON c.id = m.conversation_id
+             WHERE messages_fts MATCH ?
+               AND m.conversation_id = ?
+             ORDER BY m.compose_time DESC
+             LIMIT ?",
+        )
+        .bind(query)
+        .bind(conversation_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("searching messages: {e}")))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| SearchResult {
+                message: row_to_message(row),
+                conversation_name: row.get::<String, _>("conversation_name"),
+            })
+            .collect())
+    }
+
+    /// Merge messages whose images contain `query` (see [`Cache::store_image_text`])
+    /// into text search results, newest first.
+    ///
+    /// Image text is searched separately so a query using message-only FTS
+    /// syntax (such as a column filter) still returns the text matches.
+    async fn with_image_matches(
+        &self,
+        mut results: Vec<SearchResult>,
+        query: &str,
+        conversation_id: Option<&str>,
+        limit: i64,
+    ) -> Vec<SearchResult> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT m.*, COALESCE(c.display_name, '') AS conversation_name
+             FROM image_text it
+             JOIN messages m ON m.id = it.message_id AND m.conversation_id = it.conversation_id
+             LEFT JOIN conversations c ON c.id = m.conversation_id
+             WHERE image_text MATCH ?1
+               AND (?2 IS NULL OR m.conversation_id = ?2)
+               AND (?2 IS NOT NULL OR m.conversation_id NOT IN
+                    (SELECT conversation_id FROM hidden_conversations))
+             ORDER BY m.compose_time DESC
+             LIMIT ?3",
+        )
+        .bind(query)
+        .bind(conversation_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await;
+        let rows = match rows {
+            Ok(rows) => rows,
+            Err(e) => {
+                log::debug!("image text search skipped: {e}");
+                return results;
+            }
+        };
+
+        for row in &rows {
+            let message = row_to_message(row);
+            let seen = results.iter().any(|r| {
+                r.message.id == message.id && r.message.conversation_id == message.conversation_id
+            });
+            if !seen {
+                results.push(SearchResult {
+                    message,
+                    conversation_name: row.get::<String, _>("conversation_name"),
+                });
+            }
+        }
+        results.sort_by(|a, b| b.message.compose_time.cmp(&a.message.compose_time));
+        results.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+        results
+    }
+
+    /// Cached images referenced by messages that have not been through OCR
+    /// yet, newest messages first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn pending_ocr_images(&self, limit: usize) -> Result<Vec<OcrCandidate>, CoreError> {
+        let done: HashSet<(String, String)> =
+            sqlx::query_as::<_, (String, String)>("SELECT message_id, url FROM image_text")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| CoreError::Other(format!("listing image text: {e}")))?
+                .into_iter()
+                .collect();
+
+        let rows = sqlx::query(
+            "SELECT id, conversation_id, content_html FROM messages
+             WHERE content_html LIKE '%<img %'
+             ORDER BY compose_time DESC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing messages with images: {e}")))?;
+
+        let mut pending = Vec::new();
+        for row in &rows {
+            let message_id: String = row.get("id");
+            let html: String = row.get("content_html");
+            for url in crate::kitty::extract_image_urls(&crate::lite::expand_html(&html)) {
+                if done.contains(&(message_id.clone(), url.clone())) {
+                    continue;
+                }
+                let Some(path) = self.image_path(&url).await? else {
+                    continue;
+                };
+                pending.push(OcrCandidate {
+                    message_id: message_id.clone(),
+                    conversation_id: row.get("conversation_id"),
+                    url,
+                    path,
+                });
+                if pending.len() >= limit {
+                    return Ok(pending);
+                }
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Store text extracted from an image so searches find the message.
+    /// Empty text is stored too, so the image is not processed again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn store_image_text(
+        &self,
+        image: &OcrCandidate,
+        text: &str,
+    ) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || {
+            sqlx::query(
+                "INSERT INTO image_text (text, url, message_id, conversation_id)
+                 VALUES (?, ?, ?, ?)",
+            )
+            .bind(text)
+            .bind(&image.url)
+            .bind(&image.message_id)
+            .bind(&image.conversation_id)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("storing image text: {e}")))?;
+        Ok(())
+    }
+
+    /// Files shared in cached messages that can be indexed (see
+    /// [`crate::config::FilesConfig::indexable_extension`]) and have not
+    /// been yet, newest messages first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn pending_files(
+        &self,
+        config: &crate::config::FilesConfig,
+        limit: usize,
+    ) -> Result<Vec<FileCandidate>, CoreError> {
+        let done: HashSet<(String, String)> =
+            sqlx::query_as::<_, (String, String)>("SELECT message_id, url FROM file_text")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| CoreError::Other(format!("listing file text: {e}")))?
+                .into_iter()
+                .collect();
+
+        let rows = sqlx::query(
+            "SELECT id, conversation_id, raw_json FROM messages
+             WHERE raw_json LIKE '%objectUrl%' OR raw_json LIKE '%fileUrl%'
+                OR typeof(raw_json) = 'blob'
+             ORDER BY compose_time DESC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing messages with files: {e}")))?;
+
+        let mut pending = Vec::new();
+        for row in &rows {
+            let message_id: String = row.get("id");
+            let raw = stored_raw_json(row.get("raw_json"));
+            for (file_name, url) in file_attachments(&raw) {
+                if config.indexable_extension(&file_name).is_none()
+                    || done.contains(&(message_id.clone(), url.clone()))
+                {
+                    continue;
+                }
+                pending.push(FileCandidate {
+                    message_id: message_id.clone(),
+                    conversation_id: row.get("conversation_id"),
+                    file_name,
+                    url,
+                });
+                if pending.len() >= limit {
+                    return Ok(pending);
+                }
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Store text extracted from a shared file so `search_files` finds it.
+    /// Empty text is stored too, so the file is not processed again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn store_file_text(&self, file: &FileCandidate, text: &str) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || {
+            sqlx::query(
+                "INSERT INTO file_text (text, file_name, url, message_id, conversation_id)
+                 VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(text)
+            .bind(&file.file_name)
+            .bind(&file.url)
+            .bind(&file.message_id)
+            .bind(&file.conversation_id)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("storing file text: {e}")))?;
+        Ok(())
+    }
+
+    /// Full-text search over the contents and names of indexed shared
+    /// files, optionally within one conversation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails or the query is not
+    /// valid FTS5 syntax.
+    pub async fn search_files(
+        &self,
+        query: &str,
+        conversation_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<FileMatch>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT m.*, COALESCE(c.display_name, '') AS conversation_name,
+                    ft.file_name AS file_name, ft.url AS file_url,
+                    snippet(file_text, 0, '[', ']', '...', 12) AS snippet
+             FROM file_text ft
+             JOIN messages m ON m.id = ft.message_id AND m.conversation_id = ft.conversation_id
+             LEFT JOIN conversations c ON c.id = m.conversation_id
+             WHERE file_text MATCH ?1
+               AND (?2 IS NULL OR m.conversation_id = ?2)
+               AND (?2 IS NOT NULL OR m.conversation_id NOT IN
+                    (SELECT conversation_id FROM hidden_conversations))
+             ORDER BY m.compose_time DESC
+             LIMIT ?3",
+        )
+        .bind(query)
+        .bind(conversation_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("searching files: {e}")))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| FileMatch {
+                file_name: row.get("file_name"),
+                url: row.get("file_url"),
+                snippet: row.get("snippet"),
+                message: row_to_message(row),
+                conversation_name: row.get("conversation_name"),
+            })
+            .collect())
+    }
+
+    /// Cached translations of a conversation's messages into
+    /// `target_language`, keyed by message id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn translations(
+        &self,
+        conversation_id: &str,
+        target_language: &str,
+    ) -> Result<HashMap<String, Translation>, CoreError> {
+        let rows = sqlx::query_as::<_, (String, Option<String>, String)>(
+            "SELECT message_id, detected_language, text FROM translations
+             WHERE conversation_id = ? AND target_language = ?",
+        )
+        .bind(conversation_id)
+        .bind(target_language)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("reading translations: {e}")))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, detected_language, text)| {
+                (
+                    id,
+                    Translation {
+                        detected_language,
+                        text,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    /// Store the translation of a message into `target_language`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn store_translation(
+        &self,
+        message: &CachedMessage,
+        target_language: &str,
+        translation: &Translation,
+    ) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || {
+            sqlx::query(
+                "INSERT OR REPLACE INTO translations
+                    (conversation_id, message_id, target_language, detected_language, text)
+                 VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(&message.conversation_id)
+            .bind(&message.id)
+            .bind(target_language)
+            .bind(&translation.detected_language)
+            .bind(&translation.text)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("storing translation: {e}")))?;
+        Ok(())
+    }
+
+    /// Put a message in the outbox, due after `delay`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn queue_outgoing(
+        &self,
+        conversation_id: &str,
+        content: &str,
+        delay: Duration,
+    ) -> Result<OutboxItem, CoreError> {
+        let now = chrono::Utc::now();
+        let send_at = now + chrono::Duration::from_std(delay).unwrap_or(chrono::TimeDelta::MAX);
+        let pool = &self.pool;
+        let row = retry_on_busy(move || {
+            sqlx::query(
+                "INSERT INTO outbox (conversation_id, content, queued_at, send_at)
+                 VALUES (?, ?, ?, ?)
+                 RETURNING *",
+            )
+            .bind(conversation_id)
+            .bind(content)
+            .bind(outbox_time(now))
+            .bind(outbox_time(send_at))
+            .fetch_one(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("queueing message: {e}")))?;
+        Ok(row_to_outbox(&row))
+    }
+
+    /// Put a message in the outbox that is held until the presence of
+    /// `user_id` is Available (see [`crate::outbox::release_available`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn queue_until_available(
+        &self,
+        conversation_id: &str,
+        content: &str,
+        user_id: &str,
+    ) -> Result<OutboxItem, CoreError> {
+        let now = outbox_time(chrono::Utc::now());
+        let now = now.as_str();
+        let pool = &self.pool;
+        let row = retry_on_busy(move || {
+            sqlx::query(
+                "INSERT INTO outbox (conversation_id, content, queued_at, send_at, until_available)
+                 VALUES (?1, ?2, ?3, ?3, ?4)
+                 RETURNING *",
+            )
+            .bind(conversation_id)
+            .bind(content)
+            .bind(now)
+            .bind(user_id)
+            .fetch_one(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("queueing message: {e}")))?;
+        Ok(row_to_outbox(&row))
+    }
+
+    /// Pending outbox messages held until their recipient is available.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn waiting_for_presence(&self) -> Result<Vec<OutboxItem>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM outbox
+             WHERE status = 'pending' AND until_available IS NOT NULL
+             ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing outbox: {e}")))?;
+        Ok(rows.iter().map(row_to_outbox).collect())
+    }
+
+    /// Stop holding a pending outbox message for its recipient's presence,
+    /// so it is sent on the next dispatch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn release_outgoing(&self, id: i64) -> Result<(), CoreError> {
+        let now = outbox_time(chrono::Utc::now());
+        let now = now.as_str();
+        let pool = &self.pool;
+        retry_on_busy(move || {
+            sqlx::query(
+                "UPDATE outbox SET until_available = NULL, send_at = ?
+                 WHERE id = ? AND status = 'pending'",
+            )
+            .bind(now)
+            .bind(id)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("updating outbox: {e}")))?;
+        Ok(())
+    }
+
+    /// Cancel a pending outbox message. Returns it, or `None` if there is
+    /// no such message or it is no longer pending.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn cancel_outgoing(&self, id: i64) -> Result<Option<OutboxItem>, CoreError> {
+        let pool = &self.pool;
+        let row = retry_on_busy(move || {
+            sqlx::query(
+                "UPDATE outbox SET status = 'cancelled'
+                 WHERE id = ? AND status = 'pending'
+                 RETURNING *",
+            )
+            .bind(id)
+            .fetch_optional(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("cancelling message: {e}")))?;
+        Ok(row.as_ref().map(row_to_outbox))
+    }
+
+    /// Claim pending outbox messages that are due (only `id`, if given) by
+    /// marking them `sending`. Each message is claimed by exactly one
+    /// caller, so several processes can dispatch the outbox safely.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn claim_due_outgoing(&self, id: Option<i64>) -> Result<Vec<OutboxItem>, CoreError> {
+        let now = outbox_time(chrono::Utc::now());
+        let now = now.as_str();
+        let pool = &self.pool;
+        let rows = retry_on_busy(move || {
+            sqlx::query(
+                "UPDATE outbox SET status = 'sending'
+                 WHERE status = 'pending' AND send_at <= ?1 AND (?2 IS NULL OR id = ?2)
+                   AND until_available IS NULL
+                 RETURNING *",
+            )
+            .bind(now)
+            .bind(id)
+            .fetch_all(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("claiming outbox messages: {e}")))?;
+        let mut items: Vec<OutboxItem> = rows.iter().map(row_to_outbox).collect();
+        items.sort_by_key(|item| item.id);
+        Ok(items)
+    }
+
+    /// Record a message that is sent right away (or a file being uploaded)
+    /// as `sending` (`uploading`), to finish with [`Cache::finish_outgoing`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn record_outgoing(
+        &self,
+        conversation_id: &str,
+        content: &str,
+        kind: OutgoingKind,
+    ) -> Result<OutboxItem, CoreError> {
+        let now = outbox_time(chrono::Utc::now());
+        let now = now.as_str();
+        let status = match kind {
+            OutgoingKind::Text => "sending",
+            OutgoingKind::File => "uploading",
+        };
+        let pool = &self.pool;
+        let row = retry_on_busy(move || {
+            sqlx::query(
+                "INSERT INTO outbox (conversation_id, content, kind, queued_at, send_at, status)
+                 VALUES (?1, ?2, ?3, ?4, ?4, ?5)
+                 RETURNING *",
+            )
+            .bind(conversation_id)
+            .bind(content)
+            .bind(kind.as_str())
+            .bind(now)
+            .bind(status)
+            .fetch_one(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("recording message: {e}")))?;
+        Ok(row_to_outbox(&row))
+    }
+
+    /// Record the outcome of sending a claimed or recorded message: `sent`
+    /// with the ID and time from the service's response (see
+    /// [`crate::outbox::acknowledgement`]), or `failed` with the error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn finish_outgoing(
+        &self,
+        id: i64,
+        outcome: &Result<serde_json::Value, CoreError>,
+    ) -> Result<(), CoreError> {
+        let (status, message_id, sent_at, error) = match outcome {
+            Ok(response) => {
+                let ack = crate::outbox::acknowledgement(response);
+                let sent_at = ack
+                    .sent_at
+                    .unwrap_or_else(|| outbox_time(chrono::Utc::now()));
+                ("sent", ack.message_id, Some(sent_at), None)
+            }
+            Err(e) => ("failed", None, None, Some(e.to_string())),
+        };
+        let (message_id, sent_at, error) =
+            (message_id.as_deref(), sent_at.as_deref(), error.as_deref());
+        let pool = &self.pool;
+        retry_on_busy(move || {
+            sqlx::query(
+                "UPDATE outbox SET status = ?, message_id = ?, sent_at = ?, error = ? WHERE id = ?",
+            )
+            .bind(status)
+            .bind(message_id)
+            .bind(sent_at)
+            .bind(error)
+            .bind(id)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("updating outbox: {e}")))?;
+        Ok(())
+    }
+
+    /// Outgoing messages of every state, newest first, only those to
+    /// `conversation_id` if given (`tmz outbox history`, the TUI's
+    /// delivery markers).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn outgoing_history(
+        &self,
+        conversation_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<OutboxItem>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM outbox
+             WHERE ?1 IS NULL OR conversation_id = ?1
+             ORDER BY id DESC
+             LIMIT ?2",
+        )
+        .bind(conversation_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing outbox: {e}")))?;
+        Ok(rows.iter().map(row_to_outbox).collect())
+    }
+
+    /// Outbox messages, newest first: only those still waiting unless
+    /// `include_done`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn list_outbox(
+        &self,
+        include_done: bool,
+        limit: i64,
+    ) -> Result<Vec<OutboxItem>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM outbox
+             WHERE ?1 OR status IN ('pending', 'sending', 'uploading')
+             ORDER BY id DESC
+             LIMIT ?2",
+        )
+        .bind(include_done)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing outbox: {e}")))?;
+        Ok(rows.iter().map(row_to_outbox).collect())
+    }
+
+    /// Record that a conversation was opened, for [`Cache::frecent_conversations`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn record_open(&self, conversation_id: &str) -> Result<(), CoreError> {
+        let now = chrono::Utc::now().timestamp();
+        let pool = &self.pool;
+        retry_on_busy(move || async move {
+            let mut tx = pool.begin().await?;
+            let previous: Option<(f64, i64)> =
+                sqlx::query_as("SELECT score, last_opened FROM usage WHERE conversation_id = ?")
+                    .bind(conversation_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            let score = previous.map_or(0.0, |(score, last)| decayed(score, now - last)) + 1.0;
+            sqlx::query(
+                "INSERT INTO usage (conversation_id, opens, score, last_opened)
+                 VALUES (?1, 1, ?2, ?3)
+                 ON CONFLICT(conversation_id) DO UPDATE
+                 SET opens = opens + 1, score = ?2, last_opened = ?3",
+            )
+            .bind(conversation_id)
+            .bind(score)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("recording conversation open: {e}")))
+    }
+
+    /// Opened conversations ranked by frecency: every open counts, and
+    /// counts halve every [`FRECENCY_HALF_LIFE`]. Hidden conversations are
+    /// left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn frecent_conversations(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<(CachedConversation, f64)>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT c.*, u.score AS usage_score, u.last_opened AS usage_last_opened
+             FROM usage u JOIN conversations c ON c.id = u.conversation_id
+             WHERE c.id NOT IN (SELECT conversation_id FROM hidden_conversations)",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("ranking conversations: {e}")))?;
+        let now = chrono::Utc::now().timestamp();
+        let mut ranked: Vec<(CachedConversation, f64)> = rows
+            .iter()
+            .map(|row| {
+                let score: f64 = row.get("usage_score");
+                let last: i64 = row.get("usage_last_opened");
+                (row_to_conversation(row), decayed(score, now - last))
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(limit);
+        Ok(ranked)
+    }
+
+    /// Store a stretch of time spent in a conversation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn record_time_entry(
+        &self,
+        entry: &crate::timetrack::TimeEntry,
+    ) -> Result<(), CoreError> {
+        let time =
+            |t: chrono::DateTime<chrono::Utc>| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        sqlx::query(
+            "INSERT INTO time_entries (conversation_id, started_at, ended_at, seconds)
+             VALUES (?, ?, ?, ?)",
+        )
+        .bind(&entry.conversation_id)
+        .bind(time(entry.started_at))
+        .bind(time(entry.ended_at))
+        .bind(entry.seconds())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("recording time entry: {e}")))?;
+        Ok(())
+    }
+
+    /// Time spent per conversation in stretches that ended at or after
+    /// `since` (RFC 3339; `None` for all), most time first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn time_totals(&self, since: Option<&str>) -> Result<Vec<TimeTotal>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT t.conversation_id, COALESCE(c.display_name, '') AS name,
+                    SUM(t.seconds) AS seconds, COUNT(*) AS sessions
+             FROM time_entries t LEFT JOIN conversations c ON c.id = t.conversation_id
+             WHERE ?1 IS NULL OR t.ended_at >= ?1
+             GROUP BY t.conversation_id
+             ORDER BY seconds DESC",
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("summing time entries: {e}")))?;
+        Ok(rows
+            .iter()
+            .map(|row| TimeTotal {
+                conversation_id: row.get("conversation_id"),
+                name: row.get("name"),
+                seconds: row.get("seconds"),
+                sessions: row.get("sessions"),
+            })
+            .collect())
+    }
+
+    /// Copy `msg` into the saved items with `note`. Saving a message again
+    /// replaces its note and moves it to the top.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn save_item(&self, msg: &CachedMessage, note: &str) -> Result<SavedItem, CoreError> {
+        let now = outbox_time(chrono::Utc::now());
+        let now = now.as_str();
+        let pool = &self.pool;
+        let id: i64 = retry_on_busy(move || {
+            sqlx::query_scalar(
+                "INSERT INTO saved_items (conversation_id, message_id, from_display_name,
+                     content, content_html, compose_time, note, saved_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                 ON CONFLICT (conversation_id, message_id) DO UPDATE SET
+                     note = excluded.note, saved_at = excluded.saved_at
+                 RETURNING id",
+            )
+            .bind(&msg.conversation_id)
+            .bind(&msg.id)
+            .bind(&msg.from_display_name)
+            .bind(&msg.content)
+            .bind(&msg.content_html)
+            .bind(&msg.compose_time)
+            .bind(note)
+            .bind(now)
+            .fetch_one(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("saving message: {e}")))?;
+        let row = sqlx::query(&format!("{SAVED_ITEMS} WHERE s.id = ?"))
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("reading saved item: {e}")))?;
+        Ok(row_to_saved_item(&row))
+    }
+
+    /// Saved items, most recently saved first. With `query`, only those
+    /// whose text, note, sender, or conversation name contain it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn list_saved_items(
+        &self,
+        query: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<SavedItem>, CoreError> {
+        let pattern = query.map(|q| format!("%{q}%"));
+        let rows = sqlx::query(&format!(
+            "{SAVED_ITEMS}
+             WHERE ?1 IS NULL OR s.content LIKE ?1 OR s.note LIKE ?1
+                OR s.from_display_name LIKE ?1 OR c.display_name LIKE ?1
+             ORDER BY s.saved_at DESC, s.id DESC
+             LIMIT ?2"
+        ))
+        .bind(pattern)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing saved items: {e}")))?;
+        Ok(rows.iter().map(row_to_saved_item).collect())
+    }
+
+    /// Remove a saved item. Returns `false` if there is no such item.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn remove_saved_item(&self, id: i64) -> Result<bool, CoreError> {
+        let pool = &self.pool;
+        let result = retry_on_busy(move || {
+            sqlx::query("DELETE FROM saved_items WHERE id = ?")
+                .bind(id)
+                .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("removing saved item: {e}")))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Set a reminder to come back to a conversation at `due_at`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn add_reminder(
+        &self,
+        conversation_id: &str,
+        note: &str,
+        due_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Reminder, CoreError> {
+        let now = outbox_time(chrono::Utc::now());
+        let due_at = outbox_time(due_at);
+        let (now, due_at) = (now.as_str(), due_at.as_str());
+        let pool = &self.pool;
+        let row = retry_on_busy(move || {
+            sqlx::query(
+                "INSERT INTO reminders (conversation_id, note, created_at, due_at)
+                 VALUES (?, ?, ?, ?)
+                 RETURNING *",
+            )
+            .bind(conversation_id)
+            .bind(note)
+            .bind(now)
+            .bind(due_at)
+            .fetch_one(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("storing reminder: {e}")))?;
+        Ok(row_to_reminder(&row))
+    }
+
+    /// Reminders, soonest first: only pending ones unless `include_done`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn list_reminders(
+        &self,
+        include_done: bool,
+        limit: i64,
+    ) -> Result<Vec<Reminder>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM reminders
+             WHERE ?1 OR status = 'pending'
+             ORDER BY due_at, id
+             LIMIT ?2",
+        )
+        .bind(include_done)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing reminders: {e}")))?;
+        Ok(rows.iter().map(row_to_reminder).collect())
+    }
+
+    /// Cancel a pending reminder. Returns it, or `None` if there is no
+    /// such reminder or it already fired.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn cancel_reminder(&self, id: i64) -> Result<Option<Reminder>, CoreError> {
+        let pool = &self.pool;
+        let row = retry_on_busy(move || {
+            sqlx::query(
+                "UPDATE reminders SET status = 'cancelled'
+                 WHERE id = ? AND status = 'pending'
+                 RETURNING *",
+            )
+            .bind(id)
+            .fetch_optional(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("cancelling reminder: {e}")))?;
+        Ok(row.as_ref().map(row_to_reminder))
+    }
+
+    /// Claim the pending reminders that are due by marking them `fired`.
+    /// Each reminder is claimed by exactly one caller.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn claim_due_reminders(&self) -> Result<Vec<Reminder>, CoreError> {
+        let now = outbox_time(chrono::Utc::now());
+        let now = now.as_str();
+        let pool = &self.pool;
+        let rows = retry_on_busy(move || {
+            sqlx::query(
+                "UPDATE reminders SET status = 'fired'
+                 WHERE status = 'pending' AND due_at <= ?
+                 RETURNING *",
+            )
+            .bind(now)
+            .fetch_all(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("claiming reminders: {e}")))?;
+        let mut reminders: Vec<Reminder> = rows.iter().map(row_to_reminder).collect();
+        reminders.sort_by_key(|reminder| reminder.id);
+        Ok(reminders)
+    }
+
+    /// Move a cached conversation's read horizon
+    /// (`properties.consumptionhorizon`), e.g. after marking it unread in
+    /// Teams, so unread counts follow before the next sync.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn set_consumption_horizon(
+        &self,
+        conversation_id: &str,
+        horizon: &str,
+    ) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || {
+            sqlx::query(
+                "UPDATE conversations
+                 SET horizon = ?1,
+                    raw_json = CASE WHEN typeof(raw_json) = 'text' AND json_valid(raw_json)
+                        THEN json_set(raw_json, '$.properties.consumptionhorizon', ?1)
+                        ELSE raw_json END
+                 WHERE id = ?2",
+            )
+            .bind(horizon)
+            .bind(conversation_id)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("updating read horizon: {e}")))?;
+        Ok(())
+    }
+
+    /// My display name, as seen on the newest cached message I sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn my_display_name(&self) -> Result<Option<String>, CoreError> {
+        sqlx::query_scalar(
+            "SELECT from_display_name FROM messages
+             WHERE is_from_me = 1 AND from_display_name != ''
+             ORDER BY compose_time DESC LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("reading my display name: {e}")))
+    }
+
+    /// Restrictions stored for a conversation (none if it is not cached).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn restrictions(&self, conversation_id: &str) -> Result<Restrictions, CoreError> {
+        let row = sqlx::query("SELECT encrypted, consumer FROM conversations WHERE id = ?")
+            .bind(conversation_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("reading conversation restrictions: {e}")))?;
+        Ok(row.map_or_else(Restrictions::default, |row| Restrictions {
+            encrypted: row.get("encrypted"),
+            consumer: row.get("consumer"),
+        }))
+    }
+
+    /// The conversation opened most recently, for `tmz msg -`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn last_opened_conversation(&self) -> Result<Option<String>, CoreError> {
+        sqlx::query_scalar("SELECT conversation_id FROM usage ORDER BY last_opened DESC LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("reading last conversation: {e}")))
+    }
+
+    /// Record that the sync identified by `run` finished a conversation,
+    /// storing `messages` messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn record_sync_checkpoint(
+        &self,
+        run: &str,
+        conversation_id: &str,
+        messages: u64,
+    ) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        let messages = i64::try_from(messages).unwrap_or(i64::MAX);
+        let now = chrono::Utc::now().timestamp();
+        retry_on_busy(move || {
+            sqlx::query(
+                "INSERT OR REPLACE INTO sync_checkpoints (conversation_id, run, messages, synced_at)
+                 VALUES (?, ?, ?, ?)",
+            )
+            .bind(conversation_id)
+            .bind(run)
+            .bind(messages)
+            .bind(now)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("recording sync checkpoint: {e}")))?;
+        Ok(())
+    }
+
+    /// Conversations the sync identified by `run` finished at or after
+    /// `since` (epoch seconds).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn sync_checkpoints(
+        &self,
+        run: &str,
+        since: i64,
+    ) -> Result<HashSet<String>, CoreError> {
+        let ids: Vec<String> = sqlx::query_scalar(
+            "SELECT conversation_id FROM sync_checkpoints WHERE run = ? AND synced_at >= ?",
+        )
+        .bind(run)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("reading sync checkpoints: {e}")))?;
+        Ok(ids.into_iter().collect())
+    }
+
+    /// Conversations and messages stored by the unfinished sync.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn sync_checkpoint_totals(&self) -> Result<(u64, u64), CoreError> {
+        let (conversations, messages): (i64, i64) =
+            sqlx::query_as("SELECT COUNT(*), COALESCE(SUM(messages), 0) FROM sync_checkpoints")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| CoreError::Other(format!("reading sync checkpoints: {e}")))?;
+        Ok((
+            u64::try_from(conversations).unwrap_or(0),
+            u64::try_from(messages).unwrap_or(0),
+        ))
+    }
+
+    /// Forget all sync checkpoints, when a sync finishes or starts over.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn clear_sync_checkpoints(&self) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || sqlx::query("DELETE FROM sync_checkpoints").execute(pool))
+            .await
+            .map_err(|e| CoreError::Other(format!("clearing sync checkpoints: {e}")))?;
+        Ok(())
+    }
+
+    /// Store a person. A known manager is kept when `person` has none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn upsert_person(&self, person: &CachedPerson) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || {
+            sqlx::query(
+                "INSERT INTO people (id, display_name, email, job_title, manager_id, updated_at)
+                 VALUES (?, ?, ?, ?, ?, ?)
+                 ON CONFLICT(id) DO UPDATE SET
+                    display_name = excluded.display_name,
+                    email = excluded.email,
+                    job_title = excluded.job_title,
+                    manager_id = COALESCE(excluded.manager_id, people.manager_id),
+                    updated_at = excluded.updated_at",
+            )
+            .bind(&person.id)
+            .bind(&person.display_name)
+            .bind(&person.email)
+            .bind(&person.job_title)
+            .bind(&person.manager_id)
+            .bind(&person.updated_at)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("storing person: {e}")))?;
+        Ok(())
+    }
+
+    /// Record `manager_id` (or no manager) for a stored person.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn set_manager(&self, id: &str, manager_id: Option<&str>) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || {
+            sqlx::query("UPDATE people SET manager_id = ? WHERE id = ?")
+                .bind(manager_id)
+                .bind(id)
+                .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("storing manager: {e}")))?;
+        Ok(())
+    }
+
+    /// Replace the stored direct reports of `manager_id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn set_reports(
+        &self,
+        manager_id: &str,
+        reports: &[CachedPerson],
+    ) -> Result<(), CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || async move {
+            let mut tx = pool.begin().await?;
+            sqlx::query("UPDATE people SET manager_id = NULL WHERE manager_id = ?")
+                .bind(manager_id)
+                .execute(&mut *tx)
+                .await?;
+            for person in reports {
+                sqlx::query(
+                    "INSERT INTO people (id, display_name, email, job_title, manager_id, updated_at)
+                     VALUES (?, ?, ?, ?, ?, ?)
+                     ON CONFLICT(id) DO UPDATE SET
+                        display_name = excluded.display_name,
+                        email = excluded.email,
+                        job_title = excluded.job_title,
+                        manager_id = excluded.manager_id,
+                        updated_at = excluded.updated_at",
+                )
+                .bind(&person.id)
+                .bind(&person.display_name)
+                .bind(&person.email)
+                .bind(&person.job_title)
+                .bind(manager_id)
+                .bind(&person.updated_at)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("storing reports: {e}")))
+    }
+
+    /// A stored person by object ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn get_person(&self, id: &str) -> Result<Option<CachedPerson>, CoreError> {
+        let row = sqlx::query("SELECT * FROM people WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("reading person: {e}")))?;
+        Ok(row.as_ref().map(row_to_person))
+    }
+
+    /// Stored people whose name or email contains `query`, exact matches
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn find_people(&self, query: &str) -> Result<Vec<CachedPerson>, CoreError> {
+        let rows = sqlx::query(
+            "SELECT * FROM people
+             WHERE display_name LIKE '%' || ?1 || '%' OR email LIKE '%' || ?1 || '%'
+             ORDER BY lower(display_name) = lower(?1) OR lower(email) = lower(?1) DESC,
+                      display_name",
+        )
+        .bind(query)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("finding people: {e}")))?;
+        Ok(rows.iter().map(row_to_person).collect())
+    }
+
+    /// Stored direct reports of `manager_id`, by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn reports_of(&self, manager_id: &str) -> Result<Vec<CachedPerson>, CoreError> {
+        let rows = sqlx::query("SELECT * FROM people WHERE manager_id = ? ORDER BY display_name")
+            .bind(manager_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("reading reports: {e}")))?;
+        Ok(rows.iter().map(row_to_person).collect())
+    }
+
+    /// Store an image in the cache.
+    ///
+    /// See [`Cache::cache_attachment`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file or the database write fails.
+    pub async fn cache_image(
+        &self,
+        url: &str,
+        data: &[u8],
+        content_type: &str,
+    ) -> Result<(), CoreError> {
+        self.cache_attachment(url, data, content_type).await
+    }
+
+    /// Store a downloaded attachment (image or shared file) in the cache.
+    ///
+    /// The bytes go to the content-addressed [`BlobStore`]; the database only
+    /// keeps the URL → digest index. If the store grows past the configured
+    /// limit, least recently used attachments are evicted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file or the database write fails.
+    pub async fn cache_attachment(
+        &self,
+        url: &str,
+        data: &[u8],
+        content_type: &str,
+    ) -> Result<(), CoreError> {
+        let digest = self.blobs.put(data)?;
+        let size = i64::try_from(data.len()).unwrap_or(i64::MAX);
+        let pool = &self.pool;
+        let digest_ref = &digest;
+        retry_on_busy(move || {
+            sqlx::query(
+                "INSERT INTO attachments (url, sha256, content_type, size, cached_at, last_access)
+                 VALUES (?, ?, ?, ?, datetime('now'), datetime('now'))
+                 ON CONFLICT(url) DO UPDATE SET
+                    sha256 = excluded.sha256,
+                    content_type = excluded.content_type,
+                    size = excluded.size,
+                    cached_at = excluded.cached_at,
+                    last_access = excluded.last_access",
+            )
+            .bind(url)
+            .bind(digest_ref)
+            .bind(content_type)
+            .bind(size)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("caching attachment: {e}")))?;
+
+        if self.attachment_limit > 0 {
+            self.evict_attachments(self.attachment_limit).await?;
+        }
+        Ok(())
+    }
+
+    /// Retrieve a cached image by URL. Returns `None` if not cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database or file read fails.
+    pub async fn get_image(&self, url: &str) -> Result<Option<Vec<u8>>, CoreError> {
+        let Some(path) = self.image_path(url).await? else {
+            return Ok(None);
+        };
+        let digest = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let data = self.blobs.get(&digest)?;
+        if data.is_some() {
+            // Best effort: read-only connections cannot record the access.
+            let _ =
+                sqlx::query("UPDATE attachments SET last_access = datetime('now') WHERE url = ?")
+                    .bind(url)
+                    .execute(&self.pool)
+                    .await;
+        }
+        Ok(data)
+    }
+
+    /// Path of a cached image on disk, for streaming without loading it.
+    /// Returns `None` if the URL is not cached or its file is missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn image_path(&self, url: &str) -> Result<Option<std::path::PathBuf>, CoreError> {
+        let digest: Option<String> =
+            sqlx::query_scalar("SELECT sha256 FROM attachments WHERE url = ?")
+                .bind(url)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| CoreError::Other(format!("getting cached image: {e}")))?;
+
+        Ok(digest
+            .map(|d| self.blobs.path_for(&d))
+            .filter(|p| p.exists()))
+    }
+
+    /// Check if an image URL is already cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn has_image(&self, url: &str) -> Result<bool, CoreError> {
+        Ok(self.image_path(url).await?.is_some())
+    }
+
+    /// Delete cached images older than the given number of days.
+    /// Returns the number of images pruned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn prune_images(&self, older_than_days: u32) -> Result<u64, CoreError> {
+        let pool = &self.pool;
+        let cutoff = format!("-{older_than_days} days");
+        let digests: Vec<String> = retry_on_busy(|| {
+            sqlx::query_scalar(
+                "DELETE FROM attachments WHERE cached_at < datetime('now', ?) RETURNING sha256",
+            )
+            .bind(&cutoff)
+            .fetch_all(pool)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("pruning images: {e}")))?;
+
+        let pruned = digests.len() as u64;
+        self.remove_unreferenced(digests).await?;
+        Ok(pruned)
+    }
+
+    /// Count a conversation's messages composed before `cutoff` (an
+    /// RFC 3339 UTC timestamp).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn count_messages_before(
+        &self,
+        conversation_id: &str,
+        cutoff: &str,
+    ) -> Result<u64, CoreError> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM messages WHERE conversation_id = ? AND compose_time < ?",
+        )
+        .bind(conversation_id)
+        .bind(cutoff)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("counting old messages: {e}")))?;
+        Ok(u64::try_from(count).unwrap_or(0))
+    }
+
+    /// Delete a conversation's messages composed before `cutoff` (an
+    /// RFC 3339 UTC timestamp), with their translations and the text
+    /// extracted from their images and files. Returns the number of
+    /// messages deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn delete_messages_before(
+        &self,
+        conversation_id: &str,
+        cutoff: &str,
+    ) -> Result<u64, CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || async move {
+            let mut tx = pool.begin().await?;
+            for table in ["translations", "image_text", "file_text"] {
+                sqlx::query(&format!(
+                    "DELETE FROM {table} WHERE conversation_id = ?1 AND message_id IN
+                     (SELECT id FROM messages WHERE conversation_id = ?1 AND compose_time < ?2)"
+                ))
+                .bind(conversation_id)
+                .bind(cutoff)
+                .execute(&mut *tx)
+                .await?;
+            }
+            let deleted =
+                sqlx::query("DELETE FROM messages WHERE conversation_id = ? AND compose_time < ?")
+                    .bind(conversation_id)
+                    .bind(cutoff)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+            tx.commit().await?;
+            Ok(deleted)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("pruning old messages: {e}")))
+    }
+
+    /// Delete one message with its translations and extracted text, after
+    /// it was deleted in Teams. Returns whether it was cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn delete_message(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+    ) -> Result<bool, CoreError> {
+        let pool = &self.pool;
+        retry_on_busy(move || async move {
+            let mut tx = pool.begin().await?;
+            for table in ["translations", "image_text", "file_text"] {
+                sqlx::query(&format!(
+                    "DELETE FROM {table} WHERE conversation_id = ? AND message_id = ?"
+                ))
+                .bind(conversation_id)
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            let deleted = sqlx::query("DELETE FROM messages WHERE conversation_id = ? AND id = ?")
+                .bind(conversation_id)
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected()
+                > 0;
+            tx.commit().await?;
+            Ok(deleted)
+        })
+        .await
+        .map_err(|e| CoreError::Other(format!("deleting message: {e}")))
+    }
+
+    /// Evict least recently used attachments until the store is at most
+    /// `max_bytes`. Returns the number of attachments evicted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database write fails.
+    pub async fn evict_attachments(&self, max_bytes: u64) -> Result<u64, CoreError> {
+        let limit = i64::try_from(max_bytes).unwrap_or(i64::MAX);
+        let mut total = self.attachment_bytes().await?;
+        if total <= limit {
+            return Ok(0);
+        }
+
+        let candidates: Vec<(String, String)> = sqlx::query_as(
+            "SELECT url, sha256 FROM attachments ORDER BY last_access ASC, cached_at ASC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("listing attachments: {e}")))?;
+
+        let mut evicted = 0u64;
+        for (url, digest) in candidates {
+            if total <= limit {
+                break;
+            }
+            let pool = &self.pool;
+            let url_ref = &url;
+            retry_on_busy(move || {
+                sqlx::query("DELETE FROM attachments WHERE url = ?")
+                    .bind(url_ref)
+                    .execute(pool)
+            })
+            .await
+            .map_err(|e| CoreError::Other(format!("evicting attachment: {e}")))?;
+            evicted += 1;
+            total -= self.remove_unreferenced(vec![digest]).await?;
+        }
+
+        log::debug!("evicted {evicted} attachments to stay under {max_bytes} bytes");
+        Ok(evicted)
+    }
+
+    /// Delete blob files no longer referenced by any URL. Returns bytes freed.
+    async fn remove_unreferenced(&self, mut digests: Vec<String>) -> Result<i64, CoreError> {
+        digests.sort();
+        digests.dedup();
+        let mut freed = 0;
+        for digest in digests {
+            let refs: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM attachments WHERE sha256 = ?")
+                .bind(&digest)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| CoreError::Other(format!("checking attachment refs: {e}")))?;
+            if refs == 0 {
+                let path = self.blobs.path_for(&digest);
+                let size = std::fs::metadata(&path).map_or(0, |m| m.len());
+                self.blobs.remove(&digest)?;
+                freed += i64::try_from(size).unwrap_or(0);
+            }
+        }
+        Ok(freed)
+    }
+
+    /// Bytes on disk used by distinct attachment contents.
+    async fn attachment_bytes(&self) -> Result<i64, CoreError> {
+        sqlx::query_scalar(
+            "SELECT COALESCE(SUM(size), 0) FROM
+             (SELECT MAX(size) AS size FROM attachments GROUP BY sha256)",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("measuring attachments: {e}")))
+    }
+
+    /// Move images stored as BLOBs by older versions into the blob store.
+    async fn migrate_legacy_images(&self) -> Result<(), CoreError> {
+        let has_legacy: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'images'",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("checking legacy images table: {e}")))?;
+        if has_legacy == 0 {
+            return Ok(());
+        }
+
+        let mut last_rowid = 0i64;
+        let mut moved = 0u64;
+        loop {
+            let rows = sqlx::query(
+                "SELECT rowid, url, data, content_type, cached_at FROM images
+                 WHERE rowid > ? ORDER BY rowid LIMIT 100",
+            )
+            .bind(last_rowid)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("reading legacy images: {e}")))?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            last_rowid = last.get("rowid");
+
+            for row in &rows {
+                let data: Vec<u8> = row.get("data");
+                let digest = self.blobs.put(&data)?;
+                sqlx::query(
+                    "INSERT OR IGNORE INTO attachments
+                     (url, sha256, content_type, size, cached_at, last_access)
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(row.get::<String, _>("url"))
+                .bind(&digest)
+                .bind(row.get::<String, _>("content_type"))
+                .bind(i64::try_from(data.len()).unwrap_or(i64::MAX))
+                .bind(row.get::<String, _>("cached_at"))
+                .bind(row.get::<String, _>("cached_at"))
+                .execute(&self.pool)
+                .await
+                .map_err(|e| CoreError::Other(format!("indexing migrated image: {e}")))?;
+                moved += 1;
+            }
+        }
+
+        sqlx::query("DROP TABLE images")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("dropping legacy images table: {e}")))?;
+        // Reclaim the space the BLOBs used.
+        sqlx::query("VACUUM")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("vacuuming cache: {e}")))?;
+
+        log::info!(
+            "moved {moved} cached images to {}",
+            self.blobs.root().display()
+        );
+        Ok(())
+    }
+
+    /// Rewrite the raw JSON of every cached conversation and message to
+    /// match the current compression setting (compressing or decompressing
+    /// it), then `VACUUM` so the database file shrinks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a database read or write fails.
+    pub async fn compact_raw_json(&self) -> Result<CompactReport, CoreError> {
+        let compress = self.compress_raw_json.load(Ordering::Relaxed);
+        let mut report = CompactReport {
+            compressed: compress,
+            rewritten: 0,
+            raw_json_bytes_before: self.raw_json_bytes().await?,
+            raw_json_bytes_after: 0,
+            file_bytes_before: self.file_bytes().await?,
+            file_bytes_after: 0,
+        };
+
+        for table in ["conversations", "messages"] {
+            report.rewritten += self.compact_table(table, compress).await?;
+        }
+
+        let pool = &self.pool;
+        retry_on_busy(move || sqlx::query("VACUUM").execute(pool))
+            .await
+            .map_err(|e| CoreError::Other(format!("vacuuming cache: {e}")))?;
+        report.raw_json_bytes_after = self.raw_json_bytes().await?;
+        report.file_bytes_after = self.file_bytes().await?;
+        Ok(report)
+    }
+
+    /// Rewrite the raw JSON of `table` for [`Cache::compact_raw_json`].
+    /// Returns how many rows changed.
+    async fn compact_table(&self, table: &str, compress: bool) -> Result<u64, CoreError> {
+        let select =
+            format!("SELECT rowid, raw_json FROM {table} WHERE rowid > ? ORDER BY rowid LIMIT 500");
+        let update = format!("UPDATE {table} SET raw_json = COALESCE(?, ?) WHERE rowid = ?");
+        let mut rewritten = 0;
+        let mut last_rowid = 0i64;
+        loop {
+            let rows = sqlx::query(&select)
+                .bind(last_rowid)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| CoreError::Other(format!("reading raw JSON: {e}")))?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            last_rowid = last.get("rowid");
+
+            let updates: Vec<_> = rows
+                .iter()
+                .filter_map(|row| {
+                    let stored: Vec<u8> = row.get("raw_json");
+                    let was_compressed = stored.starts_with(&ZSTD_MAGIC);
+                    let text = stored_raw_json(stored);
+                    let packed = if compress { compress_text(&text) } else { None };
+                    (packed.is_some() != was_compressed)
+                        .then(|| (row.get::<i64, _>("rowid"), packed, text))
+                })
+                .collect();
+            let pool = &self.pool;
+            let (update, updates) = (&update, &updates);
+            retry_on_busy(move || async move {
+                let mut tx = pool.begin().await?;
+                for (rowid, packed, text) in updates {
+                    sqlx::query(update)
+                        .bind(packed.as_deref())
+                        .bind(text)
+                        .bind(rowid)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                tx.commit().await
+            })
+            .await
+            .map_err(|e| CoreError::Other(format!("rewriting raw JSON: {e}")))?;
+            rewritten += updates.len() as u64;
+        }
+        Ok(rewritten)
+    }
+
+    /// Total stored size of the raw JSON of conversations and messages.
+    async fn raw_json_bytes(&self) -> Result<i64, CoreError> {
+        sqlx::query_scalar(
+            "SELECT (SELECT COALESCE(SUM(length(CAST(raw_json AS BLOB))), 0) FROM conversations)
+                + (SELECT COALESCE(SUM(length(CAST(raw_json AS BLOB))), 0) FROM messages)",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("measuring raw JSON: {e}")))
+    }
+
+    /// Size of the database file, from its page count.
+    async fn file_bytes(&self) -> Result<i64, CoreError> {
+        sqlx::query_scalar(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CoreError::Other(format!("measuring cache size: {e}")))
+    }
+
+    /// Get cache statistics.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database read fails.
+    pub async fn stats(&self) -> Result<CacheStats, CoreError> {
+        let conv_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM conversations")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("counting conversations: {e}")))?;
+
+        let msg_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| CoreError::Other(format!("counting messages: {e}")))?;
+
+        let img_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM attachments")
             .fetch_one(&self.pool)
             .await
-            .map_err(|e| CoreError::Other(format!("counting messages: {e}")))?;
+            .unwrap_or(0);
+
+        let img_bytes = self.attachment_bytes().await.unwrap_or(0);
+
+        Ok(CacheStats {
+            conversations: conv_count,
+            messages: msg_count,
+            images: img_count,
+            image_bytes: img_bytes,
+        })
+    }
+}
+
+/// Cache statistics.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct CacheStats {
+    /// Number of cached conversations.
+    pub conversations: i64,
+    /// Number of cached messages.
+    pub messages: i64,
+    /// Number of cached images.
+    pub images: i64,
+    /// Total size of cached images in bytes.
+    pub image_bytes: i64,
+}
+
+/// What [`Cache::compact_raw_json`] did.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct CompactReport {
+    /// Whether raw JSON is now stored compressed.
+    pub compressed: bool,
+    /// Conversations and messages whose raw JSON was rewritten.
+    pub rewritten: u64,
+    /// Stored size of all raw JSON before compaction, in bytes.
+    pub raw_json_bytes_before: i64,
+    /// Stored size of all raw JSON after compaction, in bytes.
+    pub raw_json_bytes_after: i64,
+    /// Database file size before compaction, in bytes.
+    pub file_bytes_before: i64,
+    /// Database file size after compaction, in bytes.
+    pub file_bytes_after: i64,
+}
+
+/// Whether an error is `SQLITE_BUSY` or `SQLITE_LOCKED` (including extended codes).
+fn is_busy(err: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(db) = err else {
+        return false;
+    };
+    db.code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, 5 | 6))
+}
+
+/// Run a write, retrying with exponential backoff while the database is busy.
+/// SQL condition: message `m` is newer than its conversation `c`'s read
+/// horizon (`properties.consumptionhorizon`).
+const UNREAD: &str = "CAST(m.id AS INTEGER)
+    > CAST(substr(c.horizon, 1, instr(c.horizon || ';', ';') - 1) AS INTEGER)";
+
+/// Time after which an open counts half as much in the frecency ranking.
+pub const FRECENCY_HALF_LIFE: Duration = Duration::from_hours(72);
+
+/// `score` decayed over `elapsed_secs` with [`FRECENCY_HALF_LIFE`].
+#[expect(clippy::cast_precision_loss, reason = "second precision is plenty")]
+fn decayed(score: f64, elapsed_secs: i64) -> f64 {
+    let half_lives = elapsed_secs.max(0) as f64 / FRECENCY_HALF_LIFE.as_secs_f64();
+    score * 0.5_f64.powf(half_lives)
+}
+
+async fn retry_on_busy<T, F, Fut>(mut op: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut + Send,
+    Fut: Future<Output = Result<T, sqlx::Error>> + Send,
+    T: Send,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(e) if attempt < BUSY_RETRIES && is_busy(&e) => {
+                attempt += 1;
+                let delay = Duration::from_millis(50 << attempt);
+                log::debug!("cache busy, retrying in {}ms: {e}", delay.as_millis());
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn row_to_conversation(row: &sqlx::sqlite::SqliteRow) -> CachedConversation {
+    CachedConversation {
+        id: row.get("id"),
+        display_name: row.get("display_name"),
+        thread_type: row.get("thread_type"),
+        product_type: row.get("product_type"),
+        last_message_preview: row.get("last_message_preview"),
+        last_message_from: row.get("last_message_from"),
+        last_activity: row.get("last_activity"),
+        messages_url: row.get("messages_url"),
+        member_names: row.get("member_names"),
+        raw_json: stored_raw_json(row.get("raw_json")),
+        restrictions: Restrictions {
+            encrypted: row.get("encrypted"),
+            consumer: row.get("consumer"),
+        },
+    }
+}
+
+fn row_to_outbox(row: &sqlx::sqlite::SqliteRow) -> OutboxItem {
+    OutboxItem {
+        id: row.get("id"),
+        conversation_id: row.get("conversation_id"),
+        content: row.get("content"),
+        kind: row.get("kind"),
+        queued_at: row.get("queued_at"),
+        send_at: row.get("send_at"),
+        status: row.get("status"),
+        error: row.get("error"),
+        until_available: row.get("until_available"),
+        message_id: row.get("message_id"),
+        sent_at: row.get("sent_at"),
+    }
+}
+
+/// Saved items with the name of their conversation, for `WHERE` clauses
+/// on `s` (saved item) and `c` (conversation).
+const SAVED_ITEMS: &str = "SELECT s.*,
+        COALESCE(NULLIF(c.display_name, ''), c.member_names, '') AS conversation_name
+     FROM saved_items s LEFT JOIN conversations c ON c.id = s.conversation_id";
+
+fn row_to_saved_item(row: &sqlx::sqlite::SqliteRow) -> SavedItem {
+    SavedItem {
+        id: row.get("id"),
+        conversation_id: row.get("conversation_id"),
+        conversation_name: row.get("conversation_name"),
+        message_id: row.get("message_id"),
+        from_display_name: row.get("from_display_name"),
+        content: row.get("content"),
+        content_html: row.get("content_html"),
+        compose_time: row.get("compose_time"),
+        note: row.get("note"),
+        saved_at: row.get("saved_at"),
+    }
+}
+
+fn row_to_reminder(row: &sqlx::sqlite::SqliteRow) -> Reminder {
+    Reminder {
+        id: row.get("id"),
+        conversation_id: row.get("conversation_id"),
+        note: row.get("note"),
+        created_at: row.get("created_at"),
+        due_at: row.get("due_at"),
+        status: row.get("status"),
+    }
+}
+
+fn row_to_person(row: &sqlx::sqlite::SqliteRow) -> CachedPerson {
+    CachedPerson {
+        id: row.get("id"),
+        display_name: row.get("display_name"),
+        email: row.get("email"),
+        job_title: row.get("job_title"),
+        manager_id: row.get("manager_id"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+/// `text` zstd-compressed, if that makes it smaller. Raw JSON and the
+/// message HTML of lite sync ([`crate::lite`]) share this codec.
+pub(crate) fn compress_text(text: &str) -> Option<Vec<u8>> {
+    zstd::encode_all(text.as_bytes(), ZSTD_LEVEL)
+        .ok()
+        .filter(|packed| packed.len() < text.len())
+}
+
+/// The text compressed by [`compress_text`], or `None` (with a warning) if
+/// `packed` does not decompress.
+pub(crate) fn decompress_text(packed: &[u8]) -> Option<String> {
+    match zstd::decode_all(packed) {
+        Ok(bytes) => Some(utf8_lossy(bytes)),
+        Err(e) => {
+            log::warn!("cannot decompress stored text: {e}");
+            None
+        }
+    }
+}
+
+/// The JSON text of a stored `raw_json` value, compressed or not.
+fn stored_raw_json(stored: Vec<u8>) -> String {
+    if stored.starts_with(&ZSTD_MAGIC) {
+        decompress_text(&stored).unwrap_or_else(|| "{}".to_string())
+    } else {
+        utf8_lossy(stored)
+    }
+}
+
+fn utf8_lossy(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+/// Outbox timestamps use a fixed format so they compare as strings.
+fn outbox_time(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn row_to_message(row: &sqlx::sqlite::SqliteRow) -> CachedMessage {
+    CachedMessage {
+        id: row.get("id"),
+        conversation_id: row.get("conversation_id"),
+        from_display_name: row.get("from_display_name"),
+        content: row.get("content"),
+        content_html: crate::lite::expand_html(row.get("content_html")).into_owned(),
+        message_type: row.get("message_type"),
+        compose_time: row.get("compose_time"),
+        is_from_me: row.get::<bool, _>("is_from_me"),
+        mentions_me: row.get::<bool, _>("mentions_me"),
+        unknown_type: row.get::<bool, _>("unknown_type"),
+        subject: row.get("subject"),
+        important: row.get::<bool, _>("important"),
+        raw_json: stored_raw_json(row.get("raw_json")),
+    }
+}
+
+/// What [`Cache::resolve_target`] found for a target.
+#[derive(Debug, Clone)]
+pub enum TargetMatch {
+    /// The conversation with this ID.
+    Found(String),
+    /// No cached conversation matches.
+    NotFound,
+    /// Several cached conversations match.
+    Ambiguous(Vec<CachedConversation>),
+}
+
+/// Whether `target` is a conversation ID rather than a name: `19:` for
+/// chats and channels, `48:` for notes and other self chats.
+#[must_use]
+pub fn is_conversation_id(target: &str) -> bool {
+    target.starts_with("19:") || target.starts_with("48:")
+}
+
+/// A conversation tag in its stored form: lowercase, without a leading
+/// `#`. `None` if nothing is left or it contains whitespace or commas.
+#[must_use]
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('#').to_lowercase();
+    (!tag.is_empty() && !tag.contains(|c: char| c.is_whitespace() || c == ',')).then_some(tag)
+}
+
+/// Convert Teams HTML message to readable plain text.
+///
+/// Handles block elements (`<p>`, `<br>`, `<div>`), strips quoted replies
+/// (`<blockquote>`), decodes HTML entities, and collapses whitespace.
+#[must_use]
+pub fn strip_html(html: &str) -> String {
+    // Pre-process: insert newlines for block-level elements
+    let mut s = html.to_string();
+
+    // Extract file info from URIObject tags before stripping
+    if s.contains("<URIObject") {
+        // Extract original file name if present
+        let file_name = extract_xml_attr(&s, "OriginalName", "v")
+            .or_else(|| extract_xml_attr(&s, "meta", "originalName"));
+        let file_size = extract_xml_attr(&s, "FileSize", "v");
+
+        if let Some(name) = file_name {
+            let size_str = file_size.map_or(String::new(), |sz| format!(" ({sz} bytes)"));
+            s = format!("[file: {name}{size_str}]");
+            // Early return - the URIObject is fully replaced
+            return s;
+        }
+        // If we can't parse it, fall through to normal stripping
+    }
+
+    // Remove blockquote sections entirely (quoted reply context)
+    while let Some(start) = s.find("<blockquote") {
+        if let Some(end) = s[start..].find("</blockquote>") {
+            s = format!(
+                "{}{}",
+                &s[..start],
+                &s[start + end + "</blockquote>".len()..]
+            );
+        } else {
+            break;
+        }
+    }
+
+    // Block-level tags -> newline
+    for tag in &["<br>", "<br/>", "<br />", "</p>", "</div>", "</li>"] {
+        s = s.replace(tag, "\n");
+    }
+
+    // Opening block tags that shouldn't add extra newlines
+    for tag_prefix in &["<p", "<div", "<li"] {
+        while let Some(pos) = s.find(tag_prefix) {
+            if let Some(end) = s[pos..].find('>') {
+                s = format!("{}{}", &s[..pos], &s[pos + end + 1..]);
+            } else {
+                break;
+            }
+        }
+    }
+
+    // Strip remaining HTML tags
+    let mut result = String::with_capacity(s.len());
+    let mut in_tag = false;
+    for ch in s.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => result.push(ch),
+            _ => {}
+        }
+    }
+
+    let result = decode_entities(&result);
+
+    // Clean up whitespace: collapse spaces within lines, trim blank lines
+    result
+        .lines()
+        .map(|line| {
+            // Collapse runs of spaces/tabs within each line
+            let mut collapsed = String::new();
+            let mut last_was_space = false;
+            for ch in line.chars() {
+                if ch == ' ' || ch == '\t' {
+                    if !last_was_space && !collapsed.is_empty() {
+                        collapsed.push(' ');
+                        last_was_space = true;
+                    }
+                } else {
+                    collapsed.push(ch);
+                    last_was_space = false;
+                }
+            }
+            collapsed.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Decode numeric HTML entities like `&#128077;` to their Unicode characters.
+/// Extract an attribute value from an XML-style tag.
+/// e.g. `extract_xml_attr(html, "OriginalName", "v")` finds `<OriginalName v="report.pdf"/>`.
+fn extract_xml_attr(html: &str, tag_name: &str, attr_name: &str) -> Option<String> {
+    let tag_start = html.find(&format!("<{tag_name}"))?;
+    let tag_region = &html[tag_start..];
+    let tag_end = tag_region.find('>')?;
+    let tag = &tag_region[..=tag_end];
+
+    let needle = format!("{attr_name}=\"");
+    let attr_start = tag.find(&needle)?;
+    let value_start = attr_start + needle.len();
+    let value_end = tag[value_start..].find('"')?;
+    Some(tag[value_start..value_start + value_end].to_string())
+}
+
+/// Decode the named HTML entities Teams emits plus numeric ones (`&#128077;`).
+pub(crate) fn decode_entities(s: &str) -> String {
+    let named = s
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ");
+    decode_numeric_entities(&named)
+}
+
+fn decode_numeric_entities(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch == '&' && chars.peek() == Some(&'#') {
+            chars.next(); // consume '#'
+            let mut num_str = String::new();
+            for digit in chars.by_ref() {
+                if digit == ';' {
+                    break;
+                }
+                num_str.push(digit);
+            }
+            if let Ok(code) = num_str.parse::<u32>()
+                && let Some(c) = char::from_u32(code)
+            {
+                result.push(c);
+                continue;
+            }
+            // Failed to parse - put it back as-is
+            result.push('&');
+            result.push('#');
+            result.push_str(&num_str);
+            result.push(';');
+        } else {
+            result.push(ch);
+        }
+    }
+
+    result
+}
+
+/// Turn a conversation from the chat service into a `CachedConversation`.
+#[must_use]
+pub fn parse_conversation(conv: &Conversation) -> CachedConversation {
+    let last_message = conv.last_message.as_deref();
+    let last_from = last_message.map_or("", Message::sender_name);
+
+    // Build display name: use topic for channels, member names for chats
+    let display_name = match conv.topic {
+        Some(ref topic) => topic.clone(),
+        // For 1:1 and group chats, use the last message sender or conversation type
+        None if last_from.is_empty() => conv.product_type.clone(),
+        None => last_from.to_string(),
+    };
+
+    let last_preview = last_message.map_or_else(String::new, |lm| strip_html(&lm.content));
+    let last_activity = last_message.map_or_else(String::new, |lm| lm.compose_time.clone());
+
+    CachedConversation {
+        id: conv.id.clone(),
+        display_name,
+        thread_type: conv.thread_type.clone(),
+        product_type: conv.product_type.clone(),
+        last_message_preview: last_preview,
+        last_message_from: last_from.to_string(),
+        last_activity,
+        messages_url: conv.messages_url.clone(),
+        member_names: String::new(), // populated during sync if members fetched
+        raw_json: serde_json::to_string(&conv.raw).unwrap_or_default(),
+        restrictions: Restrictions::detect(&conv.raw),
+    }
+}
+
+/// Turn a message from the chat service into a `CachedMessage`.
+///
+/// # Arguments
+///
+/// * `msg` - The message from [`crate::TeamsClient::get_chat_messages`]
+/// * `conversation_id` - The conversation/thread ID
+/// * `my_name` - Optional display name of the current user, used when
+///   `imdisplayname` is empty but `isFromMe` is true
+/// * `types` - Which message types to keep (`[messages]` config)
+///
+/// Returns `None` for message types that are skipped.
+#[must_use]
+pub fn parse_message(
+    msg: &Message,
+    conversation_id: &str,
+    my_name: Option<&str>,
+    types: &MessagesConfig,
+) -> Option<CachedMessage> {
+    let msg_type = msg.message_type.as_str();
+    let raw_json = serde_json::to_string(&msg.raw).unwrap_or_default();
+
+    // Thread activity (members, renames, calls) is kept as a system event
+    if let Some(description) = describe_system_event(msg) {
+        return Some(CachedMessage {
+            id: msg.id.clone(),
+            conversation_id: conversation_id.to_string(),
+            from_display_name: String::new(),
+            content: description,
+            content_html: msg.content.clone(),
+            message_type: msg_type.to_string(),
+            compose_time: msg.compose_time.clone(),
+            is_from_me: msg.is_from_me,
+            mentions_me: false,
+            unknown_type: false,
+            subject: String::new(),
+            important: false,
+            raw_json,
+        });
+    }
+
+    let unknown_type = !types.is_kept(msg_type);
+    if unknown_type && !types.keeps_unknown(msg_type) {
+        return None;
+    }
+
+    let mut content = strip_html(&msg.content);
+    if unknown_type && content.is_empty() {
+        content = format!("[{msg_type}]");
+    }
+    let important = matches!(
+        msg.importance,
+        Some(MessageImportance::High | MessageImportance::Urgent)
+    );
+
+    // Get sender name - use my_name if isFromMe and imdisplayname is empty
+    let from_name = Some(msg.sender_name())
+        .filter(|s| !s.is_empty())
+        .or_else(|| my_name.filter(|_| msg.is_from_me))
+        .unwrap_or_default()
+        .to_string();
+
+    Some(CachedMessage {
+        id: msg.id.clone(),
+        conversation_id: conversation_id.to_string(),
+        from_display_name: from_name,
+        content,
+        content_html: msg.content.clone(),
+        message_type: msg_type.to_string(),
+        compose_time: msg.compose_time.clone(),
+        is_from_me: msg.is_from_me,
+        mentions_me: msg.mentions_me,
+        unknown_type,
+        subject: msg.subject.clone().unwrap_or_default(),
+        important,
+        raw_json,
+    })
+}
+
+/// Log how many messages of each type a sync skipped (see
+/// [`MessagesConfig`]), so new types Microsoft introduces do not go unnoticed.
+pub fn log_skipped_types(skipped: &BTreeMap<String, u64>) {
+    if skipped.is_empty() {
+        return;
+    }
+    let counts = skipped
+        .iter()
+        .map(|(kind, n)| format!("{kind}={n}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    log::info!("sync skipped message types: {counts}");
+}
+
+/// Whether a message type is a system event (see [`describe_system_event`]).
+#[must_use]
+pub fn is_system_event(message_type: &str) -> bool {
+    message_type.starts_with("ThreadActivity/") || message_type == "Event/Call"
+}
+
+/// Describe a thread activity or call message, e.g. "Alice added Bob".
+///
+/// Returns `None` for ordinary chat messages and for activity types that
+/// carry nothing worth showing.
+#[must_use]
+pub fn describe_system_event(msg: &Message) -> Option<String> {
+    let msg_type = msg.message_type.as_str();
+    if !is_system_event(msg_type) {
+        return None;
+    }
+    let xml = msg.content.as_str();
+
+    let actor = xml_blocks(xml, "detailedinitiatorinfo")
+        .first()
+        .and_then(|info| xml_text(info, "friendlyName"))
+        .or_else(|| {
+            Some(msg.sender_name())
+                .filter(|n| !n.is_empty())
+                .map(ToString::to_string)
+        })
+        .unwrap_or_else(|| {
+            if msg.is_from_me {
+                "You".to_string()
+            } else {
+                "Someone".to_string()
+            }
+        });
+    let targets = || {
+        let names: Vec<String> = xml_blocks(xml, "detailedtargetinfo")
+            .iter()
+            .filter_map(|info| xml_text(info, "friendlyName"))
+            .collect();
+        if names.is_empty() {
+            match xml_blocks(xml, "target").len() {
+                0 | 1 => "someone".to_string(),
+                n => format!("{n} people"),
+            }
+        } else {
+            names.join(", ")
+        }
+    };
+
+    let description = match msg_type {
+        "ThreadActivity/AddMember" => format!("{actor} added {}", targets()),
+        "ThreadActivity/DeleteMember" => format!("{actor} removed {}", targets()),
+        "ThreadActivity/MemberJoined" => format!("{} joined", targets()),
+        "ThreadActivity/MemberLeft" => format!("{} left", targets()),
+        "ThreadActivity/TopicUpdate" => match xml_text(xml, "value") {
+            Some(topic) if !topic.is_empty() => format!("{actor} renamed the chat to \"{topic}\""),
+            _ => format!("{actor} removed the chat name"),
+        },
+        "ThreadActivity/PictureUpdate" => format!("{actor} changed the chat picture"),
+        "Event/Call" => {
+            if xml.contains("<ended/>") || xml.contains("type=\"ended\"") {
+                let secs = xml_text(xml, "duration")
+                    .and_then(|d| d.parse::<f64>().ok())
+                    .unwrap_or(0.0) as u64;
+                if secs > 0 {
+                    format!("Call ended after {}m {}s", secs / 60, secs % 60)
+                } else {
+                    "Call ended".to_string()
+                }
+            } else {
+                format!("{actor} started a call")
+            }
+        }
+        _ => return None,
+    };
+    Some(description)
+}
+
+/// The joins and departures recorded by a cached thread activity message;
+/// empty for every other message.
+#[must_use]
+pub fn membership_changes(msg: &CachedMessage) -> Vec<MembershipChange> {
+    let (joined, by_actor) = match msg.message_type.as_str() {
+        "ThreadActivity/AddMember" => (true, true),
+        "ThreadActivity/DeleteMember" => (false, true),
+        "ThreadActivity/MemberJoined" => (true, false),
+        "ThreadActivity/MemberLeft" => (false, false),
+        _ => return Vec::new(),
+    };
+    let xml = msg.content_html.as_str();
+    let actor = by_actor
+        .then(|| {
+            xml_blocks(xml, "detailedinitiatorinfo")
+                .first()
+                .and_then(|info| xml_text(info, "friendlyName"))
+        })
+        .flatten();
+    let targets: Vec<String> = xml_blocks(xml, "target")
+        .iter()
+        .map(|t| decode_entities(t.trim()))
+        .collect();
+    let details = xml_blocks(xml, "detailedtargetinfo");
+
+    let members: Vec<(String, Option<String>)> = if details.is_empty() {
+        targets.into_iter().map(|id| (id, None)).collect()
+    } else {
+        details
+            .iter()
+            .enumerate()
+            .map(|(i, info)| {
+                let name = xml_text(info, "friendlyName").filter(|n| !n.is_empty());
+                let id = xml_text(info, "id")
+                    .or_else(|| targets.get(i).cloned())
+                    .or_else(|| name.clone())
+                    .unwrap_or_default();
+                (id, name)
+            })
+            .collect()
+    };
+    members
+        .into_iter()
+        .filter(|(id, _)| !id.is_empty())
+        .map(|(member_id, member_name)| MembershipChange {
+            conversation_id: msg.conversation_id.clone(),
+            message_id: msg.id.clone(),
+            member_id,
+            member_name,
+            joined,
+            actor: actor.clone(),
+            at: msg.compose_time.clone(),
+        })
+        .collect()
+}
+
+/// The last change of each member in `history` (oldest first) that left
+/// them in the conversation, in order of joining.
+#[must_use]
+pub fn present_members(history: &[MembershipChange]) -> Vec<&MembershipChange> {
+    let mut last: Vec<&MembershipChange> = Vec::new();
+    for change in history {
+        last.retain(|c| c.member_id != change.member_id);
+        last.push(change);
+    }
+    last.retain(|c| c.joined);
+    last
+}
+
+/// Inner text of the first `<tag>...</tag>` element, entity-decoded.
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    xml_blocks(xml, tag)
+        .first()
+        .map(|inner| decode_entities(inner.trim()))
+}
+
+/// Inner contents of every `<tag ...>...</tag>` element.
+fn xml_blocks<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{tag}");
+    let close = format!("</{tag}>");
+    let mut blocks = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        // Skip longer tag names sharing the prefix (e.g. <targetinfo> for <target>)
+        if !after.starts_with(['>', ' ', '/']) {
+            rest = after;
+            continue;
+        }
+        let Some(body_start) = after.find('>') else {
+            break;
+        };
+        let body = &after[body_start + 1..];
+        let Some(end) = body.find(&close) else {
+            break;
+        };
+        blocks.push(&body[..end]);
+        rest = &body[end + close.len()..];
+    }
+    blocks
+}
+
+/// File attachments (`properties.files`) of a raw message as
+/// `(name, url)` pairs.
+///
+/// Like mentions, the list is either a JSON array or a string containing one.
+#[must_use]
+pub fn file_attachments(raw_json: &str) -> Vec<(String, String)> {
+    let Ok(msg) = serde_json::from_str::<serde_json::Value>(raw_json) else {
+        return Vec::new();
+    };
+    let files = &msg["properties"]["files"];
+    let parsed;
+    let files = match files {
+        serde_json::Value::String(s) => {
+            parsed = serde_json::from_str::<serde_json::Value>(s).unwrap_or_default();
+            &parsed
+        }
+        other => other,
+    };
+    files
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|f| {
+                    let url = f["objectUrl"].as_str().or_else(|| f["fileUrl"].as_str())?;
+                    let name = f["fileName"]
+                        .as_str()
+                        .or_else(|| f["title"].as_str())
+                        .unwrap_or(url);
+                    Some((name.to_string(), url.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// MRIs (e.g. `8:orgid:<oid>`) of the people @-mentioned in a Teams message.
+///
+/// Mentions live in `properties.mentions`, which the chat service returns
+/// either as a JSON array or as a string containing one.
+#[must_use]
+pub fn mentioned_mris(msg: &serde_json::Value) -> Vec<String> {
+    let mentions = &msg["properties"]["mentions"];
+    let parsed;
+    let list = match mentions {
+        serde_json::Value::String(s) => {
+            parsed = serde_json::from_str::<serde_json::Value>(s).unwrap_or_default();
+            &parsed
+        }
+        other => other,
+    };
+    list.as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|m| m["mri"].as_str().map(ToString::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn conversation(id: &str) -> CachedConversation {
+        CachedConversation {
+            id: id.to_string(),
+            display_name: "Test chat".to_string(),
+            thread_type: "chat".to_string(),
+            product_type: "GroupChat".to_string(),
+            last_message_preview: String::new(),
+            last_message_from: String::new(),
+            last_activity: "2026-01-01T00:00:00Z".to_string(),
+            messages_url: String::new(),
+            member_names: String::new(),
+            raw_json: "{}".to_string(),
+            restrictions: Restrictions::default(),
+        }
+    }
+
+    /// A cache in `dir` with its attachments next to it.
+    async fn open(dir: &TempDir) -> Cache {
+        Cache::open_with_blobs(&dir.join("cache.db"), BlobStore::new(dir.join("a")))
+            .await
+            .unwrap()
+    }
+
+    /// Parse `raw` as a message of `conversation_id` with default settings.
+    fn parsed(raw: serde_json::Value, conversation_id: &str) -> CachedMessage {
+        parse_message(
+            &raw.into(),
+            conversation_id,
+            None,
+            &MessagesConfig::default(),
+        )
+        .unwrap()
+    }
+
+    /// A plain text message.
+    fn text(id: &str, content: &str, time: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "messagetype": "Text",
+            "content": content,
+            "composetime": time,
+        })
+    }
+
+    #[tokio::test]
+    async fn read_only_connections_see_writes_and_reject_their_own() {
+        let dir = TempDir::new("cache-test").unwrap();
+        let db_path = dir.join("cache.db");
+        let writer = Cache::open(&db_path).await.unwrap();
+        writer
+            .upsert_conversation(&conversation("19:a@thread.v2"))
+            .await
+            .unwrap();
+
+        let reader = Cache::open_read_only(&db_path).await.unwrap();
+        assert_eq!(reader.list_conversations(10).await.unwrap().len(), 1);
+        assert!(
+            reader
+                .upsert_conversation(&conversation("19:b@thread.v2"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn checkpoints_report_the_log_pages_written_back() {
+        let dir = TempDir::new("cache-checkpoint").unwrap();
+        let cache = open(&dir).await;
+        cache
+            .upsert_conversation(&conversation("19:a@thread.v2"))
+            .await
+            .unwrap();
+        let checkpoint = cache.checkpoint().await.unwrap();
+        assert!(checkpoint.checkpointed_pages <= checkpoint.log_pages);
+    }
+
+    #[tokio::test]
+    async fn legacy_image_blobs_are_migrated_to_files() {
+        let dir = TempDir::new("blob-test").unwrap();
+        let db_path = dir.join("cache.db");
+        let blobs = BlobStore::new(dir.join("attachments"));
+
+        // A database from before the blob store, with one BLOB image.
+        {
+            let legacy = Cache::open_with_blobs(&db_path, blobs.clone())
+                .await
+                .unwrap();
+            sqlx::query(
+                "CREATE TABLE images (url TEXT PRIMARY KEY, data BLOB NOT NULL,
+                 content_type TEXT NOT NULL, cached_at TEXT NOT NULL)",
+            )
+            .execute(&legacy.pool)
+            .await
+            .unwrap();
+            sqlx::query("INSERT INTO images VALUES ('https://x/old.png', x'0102', 'image/png', datetime('now'))")
+                .execute(&legacy.pool)
+                .await
+                .unwrap();
+        }
+
+        let cache = Cache::open_with_blobs(&db_path, blobs).await.unwrap();
+        assert_eq!(
+            cache.get_image("https://x/old.png").await.unwrap(),
+            Some(vec![1, 2])
+        );
+    }
+
+    #[tokio::test]
+    async fn identical_attachments_share_one_file() {
+        let dir = TempDir::new("blob-dedup").unwrap();
+        let cache = open(&dir).await.with_attachment_limit(0);
+        for url in ["https://x/a.png", "https://x/b.png"] {
+            cache
+                .cache_image(url, &[7; 100], "image/png")
+                .await
+                .unwrap();
+        }
+        let path = cache.image_path("https://x/a.png").await.unwrap().unwrap();
+        assert_eq!(
+            Some(path),
+            cache.image_path("https://x/b.png").await.unwrap()
+        );
+        assert_eq!(cache.stats().await.unwrap().image_bytes, 100);
+    }
+
+    #[tokio::test]
+    async fn eviction_drops_the_oldest_attachments_first() {
+        let dir = TempDir::new("blob-evict").unwrap();
+        let cache = open(&dir).await.with_attachment_limit(0);
+        cache
+            .cache_image("https://x/a.png", &[1; 40], "image/png")
+            .await
+            .unwrap();
+        cache
+            .cache_image("https://x/b.png", &[2; 40], "image/png")
+            .await
+            .unwrap();
+        cache.evict_attachments(50).await.unwrap();
+        assert!(cache.stats().await.unwrap().image_bytes <= 50);
+        assert!(!cache.has_image("https://x/a.png").await.unwrap());
+        assert!(cache.has_image("https://x/b.png").await.unwrap());
+    }
+
+    /// A named channel with a topic and a DM whose member names come from
+    /// its messages.
+    async fn searchable_conversations(dir: &TempDir) -> Cache {
+        let cache = open(dir).await;
+        let mut channel = conversation("19:channel");
+        channel.display_name = "Release planning".to_string();
+        channel.raw_json = r#"{"threadProperties":{"topic":"Quarterly roadmap"}}"#.to_string();
+        cache.upsert_conversation(&channel).await.unwrap();
+        cache
+            .upsert_conversation(&conversation("19:dm"))
+            .await
+            .unwrap();
+
+        let mut raw = text("1", "hello", "2026-01-01T00:00:00Z");
+        raw["imdisplayname"] = "Alex Example".into();
+        cache.upsert_message(&parsed(raw, "19:dm")).await.unwrap();
+        cache.refresh_member_names("19:dm").await.unwrap();
+        // A later sync without member data keeps the names.
+        cache
+            .upsert_conversation(&conversation("19:dm"))
+            .await
+            .unwrap();
+        cache
+    }
+
+    fn ids(found: Vec<CachedConversation>) -> Vec<String> {
+        found.into_iter().map(|c| c.id).collect()
+    }
+
+    #[tokio::test]
+    async fn conversations_are_searchable_by_name_topic_and_members() {
+        let dir = TempDir::new("conv-fts").unwrap();
+        let cache = searchable_conversations(&dir).await;
+        assert_eq!(
+            ids(cache.search_conversations("roadmap", 10).await.unwrap()),
+            ["19:channel"]
+        );
+        assert_eq!(
+            ids(cache.search_conversations("planning", 10).await.unwrap()),
+            ["19:channel"]
+        );
+        assert_eq!(
+            ids(cache.search_conversations("alex", 10).await.unwrap()),
+            ["19:dm"]
+        );
+    }
+
+    #[tokio::test]
+    async fn hidden_conversations_drop_out_of_listings_and_global_search() {
+        let dir = TempDir::new("conv-hidden").unwrap();
+        let cache = searchable_conversations(&dir).await;
+        assert!(cache.hide_conversation("19:dm").await.unwrap());
+        assert!(!cache.hide_conversation("19:dm").await.unwrap());
+        assert!(
+            cache
+                .search_conversations("alex", 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(cache.search("hello", 10).await.unwrap().is_empty());
+        assert_eq!(cache.count_message_matches("hello", None).await.unwrap(), 0);
+        assert_eq!(
+            ids(cache.list_conversations(10).await.unwrap()),
+            ["19:channel"]
+        );
+        assert_eq!(cache.list_hidden_conversations().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn hidden_conversations_stay_searchable_on_their_own() {
+        let dir = TempDir::new("conv-hidden-scoped").unwrap();
+        let cache = searchable_conversations(&dir).await;
+        cache.hide_conversation("19:dm").await.unwrap();
+        assert_eq!(
+            cache
+                .count_message_matches("hello", Some("19:dm"))
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn unhidden_conversations_are_listed_again() {
+        let dir = TempDir::new("conv-unhide").unwrap();
+        let cache = searchable_conversations(&dir).await;
+        cache.hide_conversation("19:dm").await.unwrap();
+        assert!(cache.unhide_conversation("19:dm").await.unwrap());
+        assert!(!cache.unhide_conversation("19:dm").await.unwrap());
+        assert_eq!(cache.list_conversations(10).await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn tags_are_normalized() {
+        assert_eq!(normalize_tag(" #Project-X ").as_deref(), Some("project-x"));
+        assert_eq!(normalize_tag("two words"), None);
+        assert_eq!(normalize_tag(""), None);
+    }
+
+    #[tokio::test]
+    async fn tagging_twice_adds_the_tag_once() {
+        let dir = TempDir::new("tags").unwrap();
+        let cache = open(&dir).await;
+        assert!(cache.tag_conversation("19:a", "#Project-X").await.unwrap());
+        assert!(!cache.tag_conversation("19:a", "project-x").await.unwrap());
+        assert!(cache.tag_conversation("19:b", "").await.is_err());
+        assert_eq!(
+            cache.conversation_tags(&[]).await.unwrap()["19:a"],
+            ["project-x"]
+        );
+    }
+
+    #[tokio::test]
+    async fn local_tags_merge_with_shared_ones() {
+        let dir = TempDir::new("tags-shared").unwrap();
+        let cache = open(&dir).await;
+        cache.tag_conversation("19:a", "project-x").await.unwrap();
+        cache.tag_conversation("19:a", "ops").await.unwrap();
+        let shared = [
+            ("19:a".to_string(), "ops".to_string()),
+            ("19:b".to_string(), "ops".to_string()),
+        ];
+        let tags = cache.conversation_tags(&shared).await.unwrap();
+        assert_eq!(tags["19:a"], ["ops", "project-x"]);
+        assert_eq!(tags["19:b"], ["ops"]);
+        assert_eq!(
+            cache
+                .conversations_tagged("#OPS", &shared)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn untagging_normalizes_the_tag() {
+        let dir = TempDir::new("tags-untag").unwrap();
+        let cache = open(&dir).await;
+        cache.tag_conversation("19:a", "project-x").await.unwrap();
+        cache.tag_conversation("19:a", "ops").await.unwrap();
+        assert!(cache.untag_conversation("19:a", "Project-X").await.unwrap());
+        assert!(!cache.untag_conversation("19:a", "project-x").await.unwrap());
+        assert_eq!(cache.conversation_tags(&[]).await.unwrap()["19:a"], ["ops"]);
+    }
+
+    #[test]
+    fn mentions_parse_from_strings_and_arrays() {
+        let stringly = serde_json::json!({
+            "properties": {"mentions": "[{\"mri\":\"8:orgid:me\",\"displayName\":\"Me\"}]"}
+        });
+        let array = serde_json::json!({"properties": {"mentions": [{"mri": "8:orgid:you"}]}});
+        assert_eq!(mentioned_mris(&stringly), ["8:orgid:me"]);
+        assert_eq!(mentioned_mris(&array), ["8:orgid:you"]);
+        assert!(mentioned_mris(&serde_json::json!({})).is_empty());
+    }
+
+    #[tokio::test]
+    async fn mentions_are_counted_against_the_read_horizon() {
+        let dir = TempDir::new("mentions").unwrap();
+        let cache = open(&dir).await;
+        let mut conv = conversation("19:team");
+        conv.raw_json = r#"{"properties":{"consumptionhorizon":"1000;1000;0"}}"#.to_string();
+        cache.upsert_conversation(&conv).await.unwrap();
+
+        for (id, content, mentions_me) in [
+            ("900", "old ping", true),
+            ("2000", "new ping", true),
+            ("3000", "chatter", false),
+        ] {
+            let mut raw = text(id, content, &format!("2026-01-01T00:00:0{}Z", id.len()));
+            raw["mentionsMe"] = mentions_me.into();
+            cache.upsert_message(&parsed(raw, "19:team")).await.unwrap();
+        }
+
+        assert_eq!(
+            cache.search_mentions(None, None, 10).await.unwrap().len(),
+            2
+        );
+        let filtered = cache.search_mentions(Some("new"), None, 10).await.unwrap();
+        assert_eq!(filtered[0].message.id, "2000");
+        assert_eq!(cache.unread_mention_count().await.unwrap(), 1);
+        let unread = cache.unread_counts().await.unwrap();
+        assert_eq!(unread.get("19:team"), Some(&2));
+    }
+
+    /// Two conversations named "Ops …", and aliases for an ID and a name.
+    async fn targets(dir: &TempDir) -> (Cache, AppConfig) {
+        let cache = open(dir).await;
+        for (id, name) in [("19:a", "Ops daily"), ("19:b", "Ops weekly")] {
+            let mut conv = conversation(id);
+            conv.display_name = name.to_string();
+            cache.upsert_conversation(&conv).await.unwrap();
+        }
+        let mut config = AppConfig::default();
+        config
+            .people
+            .insert("notes".to_string(), "48:notes".to_string());
+        config
+            .people
+            .insert("daily".to_string(), "Ops daily".to_string());
+        (cache, config)
+    }
+
+    fn found(target: TargetMatch) -> String {
+        match target {
+            TargetMatch::Found(id) => id,
+            other => panic!("expected one conversation, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ids_and_aliases_of_ids_resolve_without_a_lookup() {
+        let dir = TempDir::new("targets").unwrap();
+        let (cache, config) = targets(&dir).await;
+        let by_id = cache.resolve_target(&config, "48:notes").await.unwrap();
+        assert_eq!(found(by_id), "48:notes");
+        let by_alias = cache.resolve_target(&config, "Notes").await.unwrap();
+        assert_eq!(found(by_alias), "48:notes");
+    }
+
+    #[tokio::test]
+    async fn aliases_of_names_resolve_through_the_cache() {
+        let dir = TempDir::new("targets-names").unwrap();
+        let (cache, config) = targets(&dir).await;
+        let daily = cache.resolve_target(&config, "daily").await.unwrap();
+        assert_eq!(found(daily), "19:a");
+    }
+
+    #[tokio::test]
+    async fn unknown_and_ambiguous_targets_are_reported() {
+        let dir = TempDir::new("targets-unresolved").unwrap();
+        let (cache, config) = targets(&dir).await;
+        assert!(matches!(
+            cache.resolve_target(&config, "nope").await.unwrap(),
+            TargetMatch::NotFound
+        ));
+        assert!(matches!(
+            cache.resolve_target(&config, "ops").await.unwrap(),
+            TargetMatch::Ambiguous(matches) if matches.len() == 2
+        ));
+    }
+
+    #[test]
+    fn frecency_halves_every_three_days() {
+        assert!((decayed(4.0, 72 * 3600) - 2.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn conversations_are_ranked_by_frecency() {
+        let dir = TempDir::new("usage").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        for id in ["19:a", "19:b", "19:c"] {
+            cache.upsert_conversation(&conversation(id)).await.unwrap();
+        }
+        assert_eq!(cache.last_opened_conversation().await.unwrap(), None);
+        for id in ["19:a", "19:b", "19:a", "19:c"] {
+            cache.record_open(id).await.unwrap();
+        }
+        // An old open of b no longer counts for much.
+        sqlx::query("UPDATE usage SET last_opened = last_opened - 30 * 86400 WHERE conversation_id = '19:b'")
+            .execute(&cache.pool)
+            .await
+            .unwrap();
+        cache.hide_conversation("19:c").await.unwrap();
+
+        let ranked = cache.frecent_conversations(10).await.unwrap();
+        let ids: Vec<&str> = ranked.iter().map(|(c, _)| c.id.as_str()).collect();
+        assert_eq!(ids, ["19:a", "19:b"]);
+        assert!(ranked[0].1 > 1.9 && ranked[1].1 < 0.01);
+        assert!(cache.last_opened_conversation().await.unwrap().is_some());
+    }
+
+    /// A conversation with a topic, a read horizon, and raw JSON long
+    /// enough to be worth compressing.
+    fn compressible_conversation() -> CachedConversation {
+        let mut conv = conversation("19:z");
+        let members = "[{\"id\":\"8:orgid:someone\",\"role\":\"User\"}]".repeat(20);
+        conv.raw_json = serde_json::json!({
+            "id": "19:z",
+            "threadProperties": {"topic": "Quarterly roadmap", "members": members},
+            "properties": {"consumptionhorizon": "1000;1000;0"},
+        })
+        .to_string();
+        conv
+    }
+
+    async fn raw_json_kinds(cache: &Cache, table: &str) -> Vec<String> {
+        sqlx::query_scalar(&format!("SELECT typeof(raw_json) FROM {table} ORDER BY id"))
+            .fetch_all(&cache.pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn compressed_conversations_read_back_unchanged() {
+        let dir = TempDir::new("zstd-conv").unwrap();
+        let cache = open(&dir).await.with_raw_json_compression(true);
+        let conv = compressible_conversation();
+        cache.upsert_conversation(&conv).await.unwrap();
+        assert_eq!(raw_json_kinds(&cache, "conversations").await, ["blob"]);
+        let found = cache.find_conversation("19:z").await.unwrap();
+        assert_eq!(found[0].raw_json, conv.raw_json);
+    }
+
+    #[tokio::test]
+    async fn compressed_conversations_keep_topic_and_read_horizon() {
+        let dir = TempDir::new("zstd-conv-columns").unwrap();
+        let cache = open(&dir).await.with_raw_json_compression(true);
+        cache
+            .upsert_conversation(&compressible_conversation())
+            .await
+            .unwrap();
+        let hits = cache.search_conversations("roadmap", 10).await.unwrap();
+        assert_eq!(hits.len(), 1);
 
-        let img_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM images")
-            .fetch_one(&self.pool)
+        let msg = parsed(text("1500", "hello", "2026-01-01T00:00:00Z"), "19:z");
+        cache.upsert_message(&msg).await.unwrap();
+        assert_eq!(cache.unread_counts().await.unwrap().get("19:z"), Some(&1));
+        cache
+            .set_consumption_horizon("19:z", "2000;2000;0")
             .await
-            .unwrap_or(0);
+            .unwrap();
+        assert!(cache.unread_counts().await.unwrap().is_empty());
+    }
 
-        let img_bytes: i64 =
-            sqlx::query_scalar("SELECT COALESCE(SUM(LENGTH(data)), 0) FROM images")
-                .fetch_one(&self.pool)
-                .await
-                .unwrap_or(0);
+    #[tokio::test]
+    async fn compaction_decompresses_conversations() {
+        let dir = TempDir::new("zstd-conv-compact").unwrap();
+        let cache = open(&dir).await.with_raw_json_compression(true);
+        let conv = compressible_conversation();
+        cache.upsert_conversation(&conv).await.unwrap();
 
-        Ok(CacheStats {
-            conversations: conv_count,
-            messages: msg_count,
-            images: img_count,
-            image_bytes: img_bytes,
+        cache.set_raw_json_compression(false);
+        let report = cache.compact_raw_json().await.unwrap();
+        assert_eq!(report.rewritten, 1);
+        assert_eq!(raw_json_kinds(&cache, "conversations").await, ["text"]);
+        let found = cache.find_conversation("19:z").await.unwrap();
+        assert_eq!(found[0].raw_json, conv.raw_json);
+    }
+
+    /// A message with a shared file, as Teams sends it.
+    fn file_message(id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "messagetype": "RichText/Html",
+            "content": "<p>see attached</p>",
+            "composetime": "2026-01-01T00:00:00Z",
+            "properties": {"files": format!(
+                "[{{\"fileName\":\"plan-{id}.pdf\",\"objectUrl\":\"https://contoso.sharepoint.com/plan-{id}.pdf\"}}]"
+            )},
+            "amsreferences": ["a", "b", "c"],
         })
     }
-}
 
-/// Cache statistics.
-#[derive(Debug, Clone, Copy, serde::Serialize)]
-pub struct CacheStats {
-    /// Number of cached conversations.
-    pub conversations: i64,
-    /// Number of cached messages.
-    pub messages: i64,
-    /// Number of cached images.
-    pub images: i64,
-    /// Total size of cached images in bytes.
-    pub image_bytes: i64,
-}
+    #[tokio::test]
+    async fn compressed_messages_read_back_unchanged() {
+        let dir = TempDir::new("zstd").unwrap();
+        let cache = open(&dir).await;
+        cache
+            .upsert_message(&parsed(file_message("1"), "19:z"))
+            .await
+            .unwrap();
+        cache.set_raw_json_compression(true);
+        cache
+            .upsert_message(&parsed(file_message("2"), "19:z"))
+            .await
+            .unwrap();
+        assert_eq!(raw_json_kinds(&cache, "messages").await, ["text", "blob"]);
 
-fn row_to_conversation(row: &sqlx::sqlite::SqliteRow) -> CachedConversation {
-    CachedConversation {
-        id: row.get("id"),
-        display_name: row.get("display_name"),
-        thread_type: row.get("thread_type"),
-        product_type: row.get("product_type"),
-        last_message_preview: row.get("last_message_preview"),
-        last_message_from: row.get("last_message_from"),
-        last_activity: row.get("last_activity"),
-        messages_url: row.get("messages_url"),
-        member_names: row.get("member_names"),
-        raw_json: row.get("raw_json"),
+        let msg = cache.get_message("19:z", "2").await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&msg.raw_json).unwrap(),
+            file_message("2")
+        );
+        let files = cache
+            .pending_files(&crate::config::FilesConfig::default(), 10)
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 2);
     }
-}
 
-fn row_to_message(row: &sqlx::sqlite::SqliteRow) -> CachedMessage {
-    CachedMessage {
-        id: row.get("id"),
-        conversation_id: row.get("conversation_id"),
-        from_display_name: row.get("from_display_name"),
-        content: row.get("content"),
-        content_html: row.get("content_html"),
-        message_type: row.get("message_type"),
-        compose_time: row.get("compose_time"),
-        is_from_me: row.get::<bool, _>("is_from_me"),
-        raw_json: row.get("raw_json"),
+    #[tokio::test]
+    async fn compaction_rewrites_messages_in_the_configured_form() {
+        let dir = TempDir::new("zstd-compact").unwrap();
+        let cache = open(&dir).await;
+        for id in ["1", "2"] {
+            cache
+                .upsert_message(&parsed(file_message(id), "19:z"))
+                .await
+                .unwrap();
+        }
+
+        cache.set_raw_json_compression(true);
+        let report = cache.compact_raw_json().await.unwrap();
+        assert_eq!(report.rewritten, 2);
+        assert!(report.raw_json_bytes_after < report.raw_json_bytes_before);
+        assert_eq!(raw_json_kinds(&cache, "messages").await, ["blob", "blob"]);
+        assert_eq!(cache.compact_raw_json().await.unwrap().rewritten, 0);
+
+        cache.set_raw_json_compression(false);
+        assert_eq!(cache.compact_raw_json().await.unwrap().rewritten, 2);
+        assert_eq!(raw_json_kinds(&cache, "messages").await, ["text", "text"]);
     }
-}
 
-/// Convert Teams HTML message to readable plain text.
-///
-/// Handles block elements (`<p>`, `<br>`, `<div>`), strips quoted replies
-/// (`<blockquote>`), decodes HTML entities, and collapses whitespace.
-#[must_use]
-pub fn strip_html(html: &str) -> String {
-    // Pre-process: insert newlines for block-level elements
-    let mut s = html.to_string();
+    #[tokio::test]
+    async fn summaries_leave_out_raw_json_and_long_previews() {
+        let dir = TempDir::new("window").unwrap();
+        let cache = open(&dir).await;
+        let mut named = conversation("19:named");
+        named.member_names = "Ann, Bob".to_string();
+        named.raw_json = r#"{"threadProperties":{"topic":"Ops"}}"#.to_string();
+        named.last_message_preview = "x".repeat(500);
+        let mut unnamed = conversation("19:unnamed");
+        unnamed.display_name = String::new();
+        unnamed.member_names = "Cy, Dee".to_string();
+        unnamed.last_activity = "2026-01-02T00:00:00Z".to_string();
+        cache.upsert_conversation(&named).await.unwrap();
+        cache.upsert_conversation(&unnamed).await.unwrap();
 
-    // Extract file info from URIObject tags before stripping
-    if s.contains("<URIObject") {
-        // Extract original file name if present
-        let file_name = extract_xml_attr(&s, "OriginalName", "v")
-            .or_else(|| extract_xml_attr(&s, "meta", "originalName"));
-        let file_size = extract_xml_attr(&s, "FileSize", "v");
+        let summaries = cache.list_conversation_summaries(10).await.unwrap();
+        assert_eq!(summaries[0].id, "19:unnamed");
+        assert_eq!(summaries[0].member_names, "Cy, Dee");
+        assert_eq!(summaries[1].member_names, "");
+        assert_eq!(summaries[1].raw_json, "{}");
+        assert_eq!(summaries[1].last_message_preview.len(), 200);
+        assert_eq!(cache.list_conversation_summaries(1).await.unwrap().len(), 1);
+    }
 
-        if let Some(name) = file_name {
-            let size_str = file_size.map_or(String::new(), |sz| format!(" ({sz} bytes)"));
-            s = format!("[file: {name}{size_str}]");
-            // Early return - the URIObject is fully replaced
-            return s;
-        }
-        // If we can't parse it, fall through to normal stripping
+    #[tokio::test]
+    async fn message_windows_leave_out_raw_json() {
+        let dir = TempDir::new("window-messages").unwrap();
+        let cache = open(&dir).await;
+        let msg = parsed(text("1", "hello", "2026-01-01T00:00:00Z"), "19:named");
+        cache.upsert_message(&msg).await.unwrap();
+        let window = cache
+            .get_messages_without_raw("19:named", 10)
+            .await
+            .unwrap();
+        assert_eq!(window[0].content, "hello");
+        assert_eq!(window[0].raw_json, "{}");
+        let full = cache.get_message("19:named", "1").await.unwrap().unwrap();
+        assert_eq!(full.raw_json, msg.raw_json);
     }
 
-    // Remove blockquote sections entirely (quoted reply context)
-    while let Some(start) = s.find("<blockquote") {
-        if let Some(end) = s[start..].find("</blockquote>") {
-            s = format!(
-                "{}{}",
-                &s[..start],
-                &s[start + end + "</blockquote>".len()..]
-            );
-        } else {
-            break;
-        }
+    #[tokio::test]
+    async fn channel_post_subjects_and_importance_are_stored() {
+        let dir = TempDir::new("subject").unwrap();
+        let cache = open(&dir).await;
+        let raw = serde_json::json!({
+            "id": "1",
+            "messagetype": "RichText/Html",
+            "content": "<p>Out now</p>",
+            "composetime": "2026-01-01T00:00:00Z",
+            "properties": { "subject": " Release 1.2 ", "importance": "high" },
+        });
+        let post = parsed(raw, "19:chan");
+        assert_eq!(post.subject, "Release 1.2");
+        assert!(post.important);
+        cache.upsert_message(&post).await.unwrap();
+
+        let window = cache.get_messages_without_raw("19:chan", 10).await.unwrap();
+        assert_eq!(window[0].subject, "Release 1.2");
+        assert!(window[0].important);
     }
 
-    // Block-level tags -> newline
-    for tag in &["<br>", "<br/>", "<br />", "</p>", "</div>", "</li>"] {
-        s = s.replace(tag, "\n");
+    #[test]
+    fn plain_messages_have_no_subject_or_importance() {
+        let plain = parsed(
+            serde_json::json!({ "id": "2", "messagetype": "Text", "content": "hi" }),
+            "19:chan",
+        );
+        assert!(plain.subject.is_empty() && !plain.important);
     }
 
-    // Opening block tags that shouldn't add extra newlines
-    for tag_prefix in &["<p", "<div", "<li"] {
-        while let Some(pos) = s.find(tag_prefix) {
-            if let Some(end) = s[pos..].find('>') {
-                s = format!("{}{}", &s[..pos], &s[pos + end + 1..]);
-            } else {
-                break;
-            }
+    #[tokio::test]
+    async fn deleted_messages_leave_the_cache_and_search() {
+        let dir = TempDir::new("delete").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        for (id, content) in [("1", "keep this"), ("2", "oops wrong chat")] {
+            let time = format!("2026-01-01T00:00:0{id}Z");
+            cache
+                .upsert_message(&parsed(text(id, content, &time), "19:a"))
+                .await
+                .unwrap();
         }
+
+        assert!(cache.delete_message("19:a", "2").await.unwrap());
+        assert!(!cache.delete_message("19:a", "2").await.unwrap());
+        assert!(cache.get_message("19:a", "2").await.unwrap().is_none());
+        assert!(cache.search("oops", 10).await.unwrap().is_empty());
+        assert_eq!(cache.get_messages("19:a", 10).await.unwrap().len(), 1);
     }
 
-    // Strip remaining HTML tags
-    let mut result = String::with_capacity(s.len());
-    let mut in_tag = false;
-    for ch in s.chars() {
-        match ch {
-            '<' => in_tag = true,
-            '>' => in_tag = false,
-            _ if !in_tag => result.push(ch),
-            _ => {}
-        }
+    /// A cache holding one message from Ann in "Test chat".
+    async fn saved_fixture(dir: &TempDir) -> (Cache, CachedMessage) {
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        cache
+            .upsert_conversation(&conversation("19:ops"))
+            .await
+            .unwrap();
+        let mut raw = text(
+            "1",
+            "deploy steps: run make release",
+            "2026-01-01T00:00:00Z",
+        );
+        raw["imdisplayname"] = "Ann".into();
+        let msg = parsed(raw, "19:ops");
+        cache.upsert_message(&msg).await.unwrap();
+        (cache, msg)
     }
 
-    // Decode HTML entities
-    let result = result
-        .replace("&amp;", "&")
-        .replace("&lt;", "<")
-        .replace("&gt;", ">")
-        .replace("&quot;", "\"")
-        .replace("&#39;", "'")
-        .replace("&nbsp;", " ");
+    #[tokio::test]
+    async fn saving_again_keeps_one_item_with_the_latest_note() {
+        let dir = TempDir::new("saved").unwrap();
+        let (cache, msg) = saved_fixture(&dir).await;
+        let saved = cache.save_item(&msg, "").await.unwrap();
+        assert_eq!(saved.from_display_name, "Ann");
+        assert_eq!(saved.conversation_name, "Test chat");
+        let again = cache.save_item(&msg, "runbook").await.unwrap();
+        assert_eq!(again.id, saved.id);
+        assert_eq!(again.note, "runbook");
+        assert_eq!(cache.list_saved_items(None, 10).await.unwrap().len(), 1);
+    }
 
-    // Decode numeric entities (&#128077; etc.)
-    let result = decode_numeric_entities(&result);
+    #[tokio::test]
+    async fn saved_items_outlive_their_message() {
+        let dir = TempDir::new("saved-deleted").unwrap();
+        let (cache, msg) = saved_fixture(&dir).await;
+        cache.save_item(&msg, "").await.unwrap();
+        cache.delete_message("19:ops", "1").await.unwrap();
+        let items = cache.list_saved_items(None, 10).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].content, "deploy steps: run make release");
+    }
 
-    // Clean up whitespace: collapse spaces within lines, trim blank lines
-    result
-        .lines()
-        .map(|line| {
-            // Collapse runs of spaces/tabs within each line
-            let mut collapsed = String::new();
-            let mut last_was_space = false;
-            for ch in line.chars() {
-                if ch == ' ' || ch == '\t' {
-                    if !last_was_space && !collapsed.is_empty() {
-                        collapsed.push(' ');
-                        last_was_space = true;
-                    }
-                } else {
-                    collapsed.push(ch);
-                    last_was_space = false;
-                }
-            }
-            collapsed.trim_end().to_string()
-        })
-        .collect::<Vec<_>>()
-        .join("\n")
-        .trim()
-        .to_string()
-}
+    #[tokio::test]
+    async fn saved_items_are_searchable_by_note() {
+        let dir = TempDir::new("saved-search").unwrap();
+        let (cache, msg) = saved_fixture(&dir).await;
+        cache.save_item(&msg, "runbook").await.unwrap();
+        assert_eq!(
+            cache
+                .list_saved_items(Some("RUNBOOK"), 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(
+            cache
+                .list_saved_items(Some("lunch"), 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
 
-/// Decode numeric HTML entities like `&#128077;` to their Unicode characters.
-/// Extract an attribute value from an XML-style tag.
-/// e.g. `extract_xml_attr(html, "OriginalName", "v")` finds `<OriginalName v="report.pdf"/>`.
-fn extract_xml_attr(html: &str, tag_name: &str, attr_name: &str) -> Option<String> {
-    let tag_start = html.find(&format!("<{tag_name}"))?;
-    let tag_region = &html[tag_start..];
-    let tag_end = tag_region.find('>')?;
-    let tag = &tag_region[..=tag_end];
+    #[tokio::test]
+    async fn removing_a_saved_item_reports_whether_it_existed() {
+        let dir = TempDir::new("saved-remove").unwrap();
+        let (cache, msg) = saved_fixture(&dir).await;
+        let saved = cache.save_item(&msg, "").await.unwrap();
+        assert!(cache.remove_saved_item(saved.id).await.unwrap());
+        assert!(!cache.remove_saved_item(saved.id).await.unwrap());
+        assert!(cache.list_saved_items(None, 10).await.unwrap().is_empty());
+    }
 
-    let needle = format!("{attr_name}=\"");
-    let attr_start = tag.find(&needle)?;
-    let value_start = attr_start + needle.len();
-    let value_end = tag[value_start..].find('"')?;
-    Some(tag[value_start..value_start + value_end].to_string())
-}
+    /// Messages from Ann and Bo across a DM, a group, and a hidden chat.
+    async fn feed_fixture(dir: &TempDir) -> Cache {
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        for conv in ["19:dm", "19:group", "19:hidden"] {
+            cache
+                .upsert_conversation(&conversation(conv))
+                .await
+                .unwrap();
+        }
+        cache.hide_conversation("19:hidden").await.unwrap();
+        for (id, conv, from, time) in [
+            ("1", "19:dm", "Ann", "2026-01-01T09:00:00Z"),
+            ("2", "19:group", "Ann", "2026-01-01T10:00:00Z"),
+            ("3", "19:group", "Bo", "2026-01-01T11:00:00Z"),
+            ("4", "19:dm", "Ann", "2026-01-02T08:00:00Z"),
+            ("5", "19:hidden", "Ann", "2026-01-02T09:00:00Z"),
+        ] {
+            let mut raw = text(id, &format!("message {id}"), time);
+            raw["imdisplayname"] = from.into();
+            cache.upsert_message(&parsed(raw, conv)).await.unwrap();
+        }
+        cache
+    }
 
-fn decode_numeric_entities(s: &str) -> String {
-    let mut result = String::with_capacity(s.len());
-    let mut chars = s.chars().peekable();
+    #[tokio::test]
+    async fn person_feed_interleaves_their_visible_chats() {
+        let dir = TempDir::new("feed").unwrap();
+        let cache = feed_fixture(&dir).await;
+        let feed = cache.messages_from("Ann", None, 10).await.unwrap();
+        let ids: Vec<&str> = feed.iter().map(|r| r.message.id.as_str()).collect();
+        assert_eq!(ids, ["1", "2", "4"]);
+        assert_eq!(feed[0].conversation_name, "Test chat");
+        let recent = cache
+            .messages_from("Ann", Some("2026-01-01T09:30:00Z"), 1)
+            .await
+            .unwrap();
+        assert_eq!(recent[0].message.id, "4");
+    }
 
-    while let Some(ch) = chars.next() {
-        if ch == '&' && chars.peek() == Some(&'#') {
-            chars.next(); // consume '#'
-            let mut num_str = String::new();
-            for digit in chars.by_ref() {
-                if digit == ';' {
-                    break;
-                }
-                num_str.push(digit);
-            }
-            if let Ok(code) = num_str.parse::<u32>()
-                && let Some(c) = char::from_u32(code)
-            {
-                result.push(c);
-                continue;
-            }
-            // Failed to parse - put it back as-is
-            result.push('&');
-            result.push('#');
-            result.push_str(&num_str);
-            result.push(';');
-        } else {
-            result.push(ch);
+    #[tokio::test]
+    async fn timeline_pages_through_everyone_newest_first() {
+        let dir = TempDir::new("timeline").unwrap();
+        let cache = feed_fixture(&dir).await;
+        let page = cache.timeline(None, 2).await.unwrap();
+        let ids: Vec<&str> = page.iter().map(|r| r.message.id.as_str()).collect();
+        assert_eq!(ids, ["4", "3"]);
+        let last = &page[1].message;
+        let older = cache
+            .timeline(Some((&last.compose_time, &last.id)), 5)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = older.iter().map(|r| r.message.id.as_str()).collect();
+        assert_eq!(ids, ["2", "1"]);
+    }
+
+    /// Checkpoints of two chats in one run and one in another.
+    async fn checkpoints(dir: &TempDir) -> Cache {
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        for (run, conv, messages) in [
+            ("30:50", "19:a", 12),
+            ("30:50", "19:b", 3),
+            ("full:50", "19:c", 7),
+        ] {
+            cache
+                .record_sync_checkpoint(run, conv, messages)
+                .await
+                .unwrap();
         }
+        cache
     }
 
-    result
-}
+    #[tokio::test]
+    async fn sync_checkpoints_are_scoped_to_run_and_age() {
+        let dir = TempDir::new("sync-ckpt").unwrap();
+        let cache = checkpoints(&dir).await;
+        let done = cache.sync_checkpoints("30:50", 0).await.unwrap();
+        assert_eq!(
+            done,
+            HashSet::from(["19:a".to_string(), "19:b".to_string()])
+        );
+        let future = chrono::Utc::now().timestamp() + 60;
+        assert!(
+            cache
+                .sync_checkpoints("30:50", future)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
 
-/// Parse a Teams API conversation JSON object into a `CachedConversation`.
-#[must_use]
-pub fn parse_conversation(conv: &serde_json::Value) -> CachedConversation {
-    let id = conv["id"].as_str().unwrap_or("").to_string();
-    let tp = &conv["threadProperties"];
-    let lm = &conv["lastMessage"];
+    #[tokio::test]
+    async fn clearing_sync_checkpoints_resets_the_totals() {
+        let dir = TempDir::new("sync-ckpt-clear").unwrap();
+        let cache = checkpoints(&dir).await;
+        assert_eq!(cache.sync_checkpoint_totals().await.unwrap(), (3, 22));
+        cache.clear_sync_checkpoints().await.unwrap();
+        assert_eq!(cache.sync_checkpoint_totals().await.unwrap(), (0, 0));
+    }
 
-    let topic = tp["topic"].as_str().unwrap_or("");
-    let product_type = tp["productThreadType"].as_str().unwrap_or("");
-    let thread_type = tp["threadType"].as_str().unwrap_or("");
+    fn event(kind: &str, content: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": "1",
+            "messagetype": kind,
+            "content": content,
+            "composetime": "2026-01-01T09:00:00Z",
+        })
+    }
 
-    // Build display name: use topic for channels, member names for chats
-    let display_name = if topic.is_empty() {
-        // For 1:1 and group chats, use the last message sender or conversation type
-        let from_name = lm["imdisplayname"].as_str().unwrap_or("");
-        if from_name.is_empty() {
-            product_type.to_string()
-        } else {
-            from_name.to_string()
-        }
-    } else {
-        topic.to_string()
-    };
+    /// Alice adding Bob to a chat.
+    fn member_added() -> CachedMessage {
+        parsed(
+            event(
+                "ThreadActivity/AddMember",
+                "<addmember><eventtime>1</eventtime>\
+                 <detailedinitiatorinfo><friendlyName>Alice</friendlyName></detailedinitiatorinfo>\
+                 <target>8:orgid:b</target>\
+                 <detailedtargetinfo><friendlyName>Bob</friendlyName></detailedtargetinfo>\
+                 </addmember>",
+            ),
+            "19:x",
+        )
+    }
 
-    let last_content = lm["content"].as_str().unwrap_or("");
-    let last_preview = strip_html(last_content);
-    let last_from = lm["imdisplayname"].as_str().unwrap_or("").to_string();
-    let last_activity = lm["composetime"].as_str().unwrap_or("").to_string();
-    let messages_url = conv["messages"].as_str().unwrap_or("").to_string();
+    #[test]
+    fn member_events_are_described_and_tagged() {
+        let msg = member_added();
+        assert!(msg.is_system_event());
+        assert_eq!(msg.content, "Alice added Bob");
+        assert_eq!(msg.message_type, "ThreadActivity/AddMember");
 
-    let raw_json = serde_json::to_string(conv).unwrap_or_default();
+        let changes = membership_changes(&msg);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].member_id, "8:orgid:b");
+        assert_eq!(changes[0].name(), "Bob");
+        assert_eq!(changes[0].actor.as_deref(), Some("Alice"));
+        assert!(changes[0].joined);
+    }
 
-    CachedConversation {
-        id,
-        display_name,
-        thread_type: thread_type.to_string(),
-        product_type: product_type.to_string(),
-        last_message_preview: last_preview,
-        last_message_from: last_from,
-        last_activity,
-        messages_url,
-        member_names: String::new(), // populated during sync if members fetched
-        raw_json,
+    #[test]
+    fn present_members_follow_the_latest_change() {
+        let joined = membership_changes(&member_added()).remove(0);
+        let mut left = joined.clone();
+        left.joined = false;
+        let mut carol = joined.clone();
+        carol.member_id = "8:orgid:c".to_string();
+        assert_eq!(present_members(&[joined, carol.clone(), left]), [&carol]);
     }
-}
 
-/// Parse a Teams API message JSON object into a `CachedMessage`.
-///
-/// # Arguments
-///
-/// * `msg` - The JSON message object from the Teams API
-/// * `conversation_id` - The conversation/thread ID
-/// * `my_name` - Optional display name of the current user, used when
-///   `imdisplayname` is empty but `isFromMe` is true
-#[must_use]
-pub fn parse_message(
-    msg: &serde_json::Value,
-    conversation_id: &str,
-    my_name: Option<&str>,
-) -> Option<CachedMessage> {
-    let msg_type = msg["messagetype"].as_str().unwrap_or("");
-
-    // Skip system/control messages, keep text, rich text, and file/media messages
-    if !matches!(
-        msg_type,
-        "RichText/Html"
-            | "Text"
-            | "RichText"
-            | "RichText/UriObject"
-            | "RichText/Media_GenericFile"
-            | "RichText/Media_Card"
-    ) {
-        return None;
+    #[test]
+    fn topic_and_call_events_are_described() {
+        let renamed = event(
+            "ThreadActivity/TopicUpdate",
+            "<topicupdate><initiator>8:orgid:a</initiator><value>Q3 &amp; Q4</value></topicupdate>",
+        );
+        assert_eq!(
+            describe_system_event(&renamed.into()).as_deref(),
+            Some("Someone renamed the chat to \"Q3 & Q4\"")
+        );
+
+        let call = event(
+            "Event/Call",
+            "<partlist type=\"ended\"><part><duration>125</duration></part></partlist><ended/>",
+        );
+        assert_eq!(
+            describe_system_event(&call.into()).as_deref(),
+            Some("Call ended after 2m 5s")
+        );
+        assert!(describe_system_event(&event("Text", "hi").into()).is_none());
     }
 
-    let id = msg["id"].as_str().unwrap_or("").to_string();
-    let content_html = msg["content"].as_str().unwrap_or("").to_string();
-    let content = strip_html(&content_html);
-    let compose_time = msg["composetime"].as_str().unwrap_or("").to_string();
-    let is_from_me = msg["isFromMe"].as_bool().unwrap_or(false);
+    fn typed(kind: &str, content: &str) -> serde_json::Value {
+        serde_json::json!({"id": "1", "messagetype": kind, "content": content})
+    }
 
-    // Get sender name - use my_name if isFromMe and imdisplayname is empty
-    let from_name = msg["imdisplayname"]
-        .as_str()
-        .filter(|s| !s.is_empty())
-        .map(std::string::ToString::to_string)
-        .or_else(|| {
-            if is_from_me {
-                my_name.map(std::string::ToString::to_string)
-            } else {
-                None
-            }
-        })
-        .unwrap_or_default();
+    #[test]
+    fn unknown_message_types_are_dropped_by_default() {
+        let types = MessagesConfig::default();
+        let hi = parse_message(&typed("Text", "hi").into(), "19:x", None, &types).unwrap();
+        assert!(!hi.unknown_type);
+        let poll = typed("RichText/Media_Poll", "");
+        assert!(parse_message(&poll.into(), "19:x", None, &types).is_none());
+    }
 
-    let raw_json = serde_json::to_string(msg).unwrap_or_default();
+    #[test]
+    fn keep_unknown_keeps_unknown_types_but_not_control_messages() {
+        let types = MessagesConfig {
+            keep_unknown: true,
+            ..MessagesConfig::default()
+        };
+        let poll = typed("RichText/Media_Poll", "");
+        let poll = parse_message(&poll.into(), "19:x", None, &types).unwrap();
+        assert!(poll.unknown_type);
+        assert_eq!(poll.content, "[RichText/Media_Poll]");
+        let typing = typed("Control/Typing", "");
+        assert!(parse_message(&typing.into(), "19:x", None, &types).is_none());
+    }
 
-    Some(CachedMessage {
-        id,
-        conversation_id: conversation_id.to_string(),
-        from_display_name: from_name,
-        content,
-        content_html,
-        message_type: msg_type.to_string(),
-        compose_time,
-        is_from_me,
-        raw_json,
-    })
+    #[test]
+    fn kept_types_replace_the_allowlist() {
+        let types = MessagesConfig {
+            kept_types: vec!["RichText/Media_Poll".to_string()],
+            ..MessagesConfig::default()
+        };
+        let poll = typed("RichText/Media_Poll", "q");
+        assert!(parse_message(&poll.into(), "19:x", None, &types).is_some());
+        let hi = typed("Text", "hi");
+        assert!(parse_message(&hi.into(), "19:x", None, &types).is_none());
+    }
 }
@@ -0,0 +1,110 @@
+//! Stable per-sender colors for group chats.
+//!
+//! Colors are taken from the terminal's 16-color ANSI palette so they follow
+//! the user's terminal theme. A sender always gets the same color: the name
+//! is hashed (FNV-1a, stable across runs and platforms) into the configured
+//! palette.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// One of the terminal's standard ANSI colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum AnsiColor {
+    /// ANSI red.
+    Red,
+    /// ANSI green.
+    Green,
+    /// ANSI yellow.
+    Yellow,
+    /// ANSI blue.
+    Blue,
+    /// ANSI magenta.
+    Magenta,
+    /// ANSI cyan.
+    Cyan,
+    /// ANSI bright red.
+    BrightRed,
+    /// ANSI bright green.
+    BrightGreen,
+    /// ANSI bright yellow.
+    BrightYellow,
+    /// ANSI bright blue.
+    BrightBlue,
+    /// ANSI bright magenta.
+    BrightMagenta,
+    /// ANSI bright cyan.
+    BrightCyan,
+}
+
+impl AnsiColor {
+    /// SGR foreground code (e.g. `33` for yellow, `93` for bright yellow).
+    #[must_use]
+    pub const fn sgr(self) -> u8 {
+        match self {
+            Self::Red => 31,
+            Self::Green => 32,
+            Self::Yellow => 33,
+            Self::Blue => 34,
+            Self::Magenta => 35,
+            Self::Cyan => 36,
+            Self::BrightRed => 91,
+            Self::BrightGreen => 92,
+            Self::BrightYellow => 93,
+            Self::BrightBlue => 94,
+            Self::BrightMagenta => 95,
+            Self::BrightCyan => 96,
+        }
+    }
+}
+
+/// Default sender palette. Cyan is left out because it marks your own
+/// messages.
+#[must_use]
+pub fn default_palette() -> Vec<AnsiColor> {
+    vec![
+        AnsiColor::Yellow,
+        AnsiColor::Green,
+        AnsiColor::Magenta,
+        AnsiColor::Blue,
+        AnsiColor::Red,
+        AnsiColor::BrightYellow,
+        AnsiColor::BrightGreen,
+        AnsiColor::BrightMagenta,
+        AnsiColor::BrightBlue,
+        AnsiColor::BrightRed,
+    ]
+}
+
+/// Color for a sender name, or `None` if the palette is empty.
+#[must_use]
+pub fn sender_color(name: &str, palette: &[AnsiColor]) -> Option<AnsiColor> {
+    if palette.is_empty() {
+        return None;
+    }
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    palette.get((hash % palette.len() as u64) as usize).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sender_colors_are_stable_and_spread() {
+        let palette = default_palette();
+        let alice = sender_color("Alice Example", &palette);
+        assert_eq!(alice, sender_color("Alice Example", &palette));
+        assert_eq!(sender_color("anyone", &[]), None);
+
+        let names = ["Alice", "Bob", "Carol", "Dave", "Erin", "Frank", "Grace"];
+        let distinct: std::collections::HashSet<_> = names
+            .iter()
+            .filter_map(|n| sender_color(n, &palette))
+            .collect();
+        assert!(distinct.len() >= 4, "colors should spread: {distinct:?}");
+    }
+}
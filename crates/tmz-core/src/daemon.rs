@@ -4,15 +4,28 @@
 //! - **Token refresh**: headless Playwright every ~50 minutes
 //! - **Conversation sync**: pull conversations + messages into `SQLite` cache
 //!
+//! plus the jobs configured under `[[schedule]]` (see [`crate::schedule`])
+//! and reminders set with `tmz remind` (see [`crate::remind`]). Between
+//! syncs, messages pushed by the chat service are cached as they arrive
+//! (see [`Engine::listen`]).
+//! Changes to the config file are picked up while running (see
+//! [`crate::reload`]).
+//!
+//! Both run through the shared [`Engine`], which the daemon also serves on
+//! the engine socket for other frontends.
+//!
 //! State files:
 //! - `$XDG_STATE_HOME/tmz/tmz.pid` - daemon PID
 //! - `$XDG_STATE_HOME/tmz/tmz.log` - daemon log output
-
-use crate::CoreError;
-use crate::cache::{Cache, parse_conversation, parse_message};
-use crate::teams::auth::AuthManager;
-use crate::teams::client::TeamsClient;
-use std::path::PathBuf;
+//! - `$XDG_STATE_HOME/tmz/tmz.sock` - engine socket
+
+use crate::audit::AuditLog;
+use crate::engine::{Engine, SyncOptions};
+use crate::reload::ConfigWatcher;
+use crate::schedule::Scheduler;
+use crate::{AppConfig, AppPaths, CoreError};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Default interval between token refreshes (50 minutes).
@@ -22,12 +35,36 @@ const TOKEN_REFRESH_INTERVAL: Duration = Duration::from_mins(50);
 /// Default interval between sync runs (5 minutes).
 const SYNC_INTERVAL: Duration = Duration::from_mins(5);
 
+/// Default interval between WAL checkpoints (15 minutes).
+const CHECKPOINT_INTERVAL: Duration = Duration::from_mins(15);
+
+/// How often the outbox is checked for messages past their undo window.
+const OUTBOX_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the presence of recipients of `--if-available` messages is
+/// checked.
+const PRESENCE_INTERVAL: Duration = Duration::from_mins(1);
+
+/// How often reminders set with `tmz remind` are checked.
+const REMINDER_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How often the `[[schedule]]` jobs are checked for a matching minute.
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How often the link to the chat service is checked for staleness, e.g.
+/// after the machine wakes from sleep (see [`crate::live`]).
+const LIVENESS_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Number of top conversations to sync messages for.
 const SYNC_TOP_CHATS: i64 = 30;
 
 /// Number of messages per conversation to sync.
 const SYNC_MESSAGES_PER_CHAT: i32 = 50;
 
+/// Number of messages per conversation fetched by the gap-fill sync after
+/// an outage (the most the chat service returns in one page).
+const GAP_FILL_MESSAGES_PER_CHAT: i32 = 200;
+
 // ─── Reauth notice ───────────────────────────────────────────────────
 //
 // When the daemon's headless token refresh fails (SSO session expired),
@@ -214,12 +251,31 @@ pub fn stop_daemon() -> Result<(), CoreError> {
 
 /// Run the daemon loop (foreground). Call this after daemonizing.
 ///
+/// The daemon owns the shared [`Engine`] and serves it on the engine socket
+/// (see [`crate::engine::socket_path`]) so other frontends can forward sync
+/// and send requests instead of opening their own cache and client.
+///
 /// # Errors
 ///
 /// Returns an error if initialization fails.
 pub async fn run_daemon() -> Result<(), CoreError> {
     write_pid()?;
 
+    let data_dir = crate::default_data_dir()
+        .map_err(|e| CoreError::Path(format!("resolving data dir: {e}")))?;
+    let (mut config, state_dir) = load_config()?;
+    crate::teams::endpoints::configure(&config.endpoints);
+    crate::network::configure(&config.network)?;
+    crate::retry::configure(&config.runtime.retries);
+    crate::ratelimit::configure(&config.runtime.rate_limit);
+    let mut scheduler = Scheduler::new(&config.schedule);
+    let engine = Engine::open(&data_dir).await?;
+    engine.reconfigure(
+        &config,
+        Some(AuditLog::new(&state_dir, &config.audit, "daemon")),
+    );
+    let engine = Arc::new(engine);
+
     let (shutdown_tx, mut shutdown_rx) = tokio::sync::watch::channel(false);
 
     tokio::spawn(async move {
@@ -227,31 +283,78 @@ pub async fn run_daemon() -> Result<(), CoreError> {
         let _ = shutdown_tx.send(true);
     });
 
+    #[cfg(unix)]
+    serve_engine(&engine, shutdown_rx.clone())?;
+    listen_for_events(&engine, shutdown_rx.clone());
+
+    let mut watcher = watch_config();
+
     log::info!("daemon started (pid={})", std::process::id());
 
     let mut token_interval = tokio::time::interval(TOKEN_REFRESH_INTERVAL);
     let mut sync_interval = tokio::time::interval(SYNC_INTERVAL);
+    let mut checkpoint_interval = tokio::time::interval(CHECKPOINT_INTERVAL);
+    let mut outbox_interval = tokio::time::interval(OUTBOX_INTERVAL);
+    let mut presence_interval = tokio::time::interval(PRESENCE_INTERVAL);
+    let mut schedule_interval = tokio::time::interval(SCHEDULE_INTERVAL);
+    let mut reminder_interval = tokio::time::interval(REMINDER_INTERVAL);
+    let mut liveness_interval = tokio::time::interval(LIVENESS_INTERVAL);
     // Consume the first immediate tick, then run initial tasks
     token_interval.tick().await;
     sync_interval.tick().await;
-    let mut refresh_paused = !do_token_refresh().await;
-    do_sync().await;
+    checkpoint_interval.tick().await;
+    let mut refresh_paused = !do_token_refresh(&engine).await;
+    // Set while the link is down: when to try again (see `crate::live`).
+    let mut reconnect_at = do_sync(&engine).await;
 
     loop {
         tokio::select! {
             _ = token_interval.tick() => {
-                if refresh_paused {
-                    log::info!("token refresh paused (SSO expired). Run 'tmz auth login'.");
-                } else {
-                    let ok = do_token_refresh().await;
-                    if !ok {
-                        refresh_paused = true;
-                        log::warn!("token refresh failed, pausing. Run 'tmz auth login' to re-authenticate.");
+                refresh_paused = do_scheduled_refresh(&engine, refresh_paused).await;
+            }
+            _ = sync_interval.tick(), if reconnect_at.is_none() => {
+                reconnect_at = do_sync(&engine).await;
+            }
+            _ = liveness_interval.tick(), if reconnect_at.is_none() => {
+                if engine.connection_stale() {
+                    log::info!("no contact with the chat service for a while, reconnecting");
+                    reconnect_at = do_sync(&engine).await;
+                    sync_interval.reset();
+                }
+            }
+            () = sleep_until(reconnect_at) => {
+                reconnect_at = do_sync(&engine).await;
+                sync_interval.reset();
+            }
+            _ = checkpoint_interval.tick() => {
+                do_checkpoint(&engine).await;
+            }
+            _ = outbox_interval.tick() => {
+                do_outbox(&engine).await;
+            }
+            _ = presence_interval.tick() => {
+                do_presence(&engine).await;
+            }
+            _ = reminder_interval.tick() => {
+                do_reminders(&engine).await;
+            }
+            _ = schedule_interval.tick(), if !scheduler.is_empty() => {
+                for job in scheduler.due(&chrono::Local::now()) {
+                    log::info!("running scheduled job '{}'", job.name);
+                    if let Err(e) = engine.run_job(&job).await {
+                        log::warn!("scheduled job '{}' failed: {e}", job.name);
                     }
                 }
             }
-            _ = sync_interval.tick() => {
-                do_sync().await;
+            () = async {
+                match watcher {
+                    Some((ref mut watcher, _)) => watcher.changed().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                if let Some((_, ref paths)) = watcher {
+                    reload_config(paths, &mut config, &engine, &mut scheduler, &state_dir);
+                }
             }
             _ = shutdown_rx.changed() => {
                 log::info!("shutdown signal received");
@@ -260,27 +363,135 @@ pub async fn run_daemon() -> Result<(), CoreError> {
         }
     }
 
+    do_checkpoint(&engine).await;
     remove_pid()?;
     log::info!("daemon stopped");
     Ok(())
 }
 
-// ─── Periodic tasks ──────────────────────────────────────────────────
+/// Sleep until `at`, or forever if it is `None`.
+async fn sleep_until(at: Option<tokio::time::Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
 
-/// Attempt headless token refresh. Returns `true` on success.
-async fn do_token_refresh() -> bool {
-    log::info!("refreshing tokens...");
-    let auth = match AuthManager::new() {
-        Ok(a) => a,
+/// Serve `engine` on the engine socket until `shutdown` fires.
+#[cfg(unix)]
+fn serve_engine(
+    engine: &Arc<Engine>,
+    shutdown: tokio::sync::watch::Receiver<bool>,
+) -> Result<(), CoreError> {
+    let socket = crate::engine::socket_path()?;
+    let engine = Arc::clone(engine);
+    tokio::spawn(async move {
+        if let Err(e) = crate::engine::serve(engine, socket, shutdown).await {
+            log::error!("engine socket failed: {e}");
+        }
+    });
+    Ok(())
+}
+
+/// Cache pushed messages between syncs until `shutdown` fires.
+fn listen_for_events(engine: &Arc<Engine>, shutdown: tokio::sync::watch::Receiver<bool>) {
+    let engine = Arc::clone(engine);
+    tokio::spawn(async move { engine.listen(shutdown).await });
+}
+
+/// The daemon's config files.
+fn config_paths() -> anyhow::Result<AppPaths> {
+    let mut paths = AppPaths::discover(None)?;
+    // The daemon serves every project; `.tmz.toml` belongs to commands run
+    // in it.
+    paths.project_config_file = None;
+    Ok(paths)
+}
+
+/// The config and state directory, falling back to defaults if the config
+/// cannot be loaded.
+fn load_config() -> Result<(AppConfig, PathBuf), CoreError> {
+    let loaded = config_paths().and_then(|paths| {
+        let config = AppConfig::load(&paths, false)?;
+        let paths = paths.apply_overrides(&config)?;
+        Ok((config, paths.state_dir))
+    });
+    match loaded {
+        Ok(loaded) => Ok(loaded),
         Err(e) => {
-            log::error!("failed to create auth manager: {e}");
-            return false;
+            log::warn!("loading config: {e}; using defaults");
+            let state_dir = crate::default_state_dir()
+                .map_err(|e| CoreError::Path(format!("resolving state dir: {e}")))?;
+            Ok((AppConfig::default(), state_dir))
+        }
+    }
+}
+
+/// A watcher on the config files, or `None` (logged) if it cannot start.
+fn watch_config() -> Option<(ConfigWatcher, AppPaths)> {
+    let watched = config_paths()
+        .map_err(|e| CoreError::Path(e.to_string()))
+        .and_then(|paths| {
+            let files: Vec<PathBuf> = paths.config_layers().into_iter().map(|(_, f)| f).collect();
+            Ok((ConfigWatcher::new(&files)?, paths))
+        });
+    watched
+        .inspect_err(|e| log::warn!("config changes need a restart: {e}"))
+        .ok()
+}
+
+/// Reload the config after a file changed and apply what can change while
+/// running. An invalid config is logged and the current one kept.
+fn reload_config(
+    paths: &AppPaths,
+    config: &mut AppConfig,
+    engine: &Engine,
+    scheduler: &mut Scheduler,
+    state_dir: &Path,
+) {
+    let new = match AppConfig::load_layers(&paths.config_layers(), None) {
+        Ok(new) => new,
+        Err(e) => {
+            log::warn!("config changed but cannot be loaded, keeping the current one: {e}");
+            return;
         }
     };
+    let changes = crate::reload::apply(config, new);
+    if changes.is_empty() {
+        return;
+    }
+    if !changes.applied.is_empty() {
+        log::info!("config reloaded: {}", changes.applied.join(", "));
+    }
+    if !changes.restart.is_empty() {
+        log::warn!(
+            "restart the daemon to apply: {}",
+            changes.restart.join(", ")
+        );
+    }
+    if changes.touches("schedule") {
+        scheduler.replace_jobs(&config.schedule);
+    }
+    if changes.touches("runtime.retries") {
+        crate::retry::configure(&config.runtime.retries);
+    }
+    if changes.touches("runtime.rate_limit") {
+        crate::ratelimit::configure(&config.runtime.rate_limit);
+    }
+    engine.reconfigure(
+        config,
+        Some(AuditLog::new(state_dir, &config.audit, "daemon")),
+    );
+}
 
-    match auth.refresh_tokens().await {
-        Ok(tokens) => {
-            let remaining = tokens.expires_at - chrono::Utc::now().timestamp();
+// ─── Periodic tasks ──────────────────────────────────────────────────
+
+/// Attempt headless token refresh. Returns `true` on success.
+async fn do_token_refresh(engine: &Engine) -> bool {
+    log::info!("refreshing tokens...");
+    match engine.refresh_tokens().await {
+        Ok(expires_at) => {
+            let remaining = expires_at - chrono::Utc::now().timestamp();
             log::info!("tokens refreshed (expires in {remaining}s)");
             if let Err(e) = clear_reauth_needed() {
                 log::warn!("failed to clear reauth notice: {e}");
@@ -297,92 +508,108 @@ async fn do_token_refresh() -> bool {
     }
 }
 
-async fn do_sync() {
-    log::info!("syncing conversations...");
-
-    let client = match TeamsClient::new() {
-        Ok(c) => c,
-        Err(e) => {
-            log::error!("failed to create client: {e}");
-            return;
-        }
-    };
-
-    let cache_dir: PathBuf = match crate::default_data_dir() {
-        Ok(d) => d,
-        Err(e) => {
-            log::error!("failed to resolve data dir: {e}");
-            return;
-        }
-    };
+/// Refresh tokens on schedule unless an earlier failure paused refreshing.
+/// Returns whether refreshing is paused afterwards.
+async fn do_scheduled_refresh(engine: &Engine, paused: bool) -> bool {
+    if paused {
+        log::info!("token refresh paused (SSO expired). Run 'tmz auth login'.");
+        return true;
+    }
+    let ok = do_token_refresh(engine).await;
+    if !ok {
+        log::warn!("token refresh failed, pausing. Run 'tmz auth login' to re-authenticate.");
+    }
+    !ok
+}
 
-    let cache = match Cache::open(&cache_dir.join("cache.db")).await {
-        Ok(c) => c,
-        Err(e) => {
-            log::error!("failed to open cache: {e}");
-            return;
-        }
+/// Sync, then gap-fill if the sync ended an outage. Returns when to retry
+/// if the sync failed.
+async fn do_sync(engine: &Engine) -> Option<tokio::time::Instant> {
+    log::info!("syncing conversations...");
+    let opts = SyncOptions {
+        chats: SYNC_TOP_CHATS,
+        per_chat: SYNC_MESSAGES_PER_CHAT,
+        ..SyncOptions::default()
     };
+    match engine.sync(opts).await {
+        Ok(report) => log::info!(
+            "synced {} conversations, {} messages across {} chats",
+            report.conversations,
+            report.messages,
+            report.chats
+        ),
+        Err(e) => log::error!("sync failed: {e}"),
+    }
+    if let Some(outage) = engine.take_gap_fill() {
+        do_gap_fill(engine, &outage).await;
+    }
+    let delay = engine.reconnect_delay()?;
+    log::info!(
+        "retrying in {}s (attempt {})",
+        delay.as_secs(),
+        engine.connection().attempts
+    );
+    Some(tokio::time::Instant::now() + delay)
+}
 
-    // Fetch conversations
-    let conversations: serde_json::Value = match client.list_chats().await {
-        Ok(c) => c,
-        Err(e) => {
-            log::error!("failed to list conversations: {e}");
-            return;
-        }
+/// Fetch deeper message history after an outage so messages that arrived
+/// while the link was down are cached too.
+async fn do_gap_fill(engine: &Engine, outage: &crate::live::Outage) {
+    log::info!(
+        "gap-filling {}s outage since {}",
+        outage.duration.num_seconds(),
+        outage.since.to_rfc3339()
+    );
+    let opts = SyncOptions {
+        chats: SYNC_TOP_CHATS,
+        per_chat: GAP_FILL_MESSAGES_PER_CHAT,
+        ..SyncOptions::default()
     };
-
-    let empty_arr = Vec::new();
-    let convs = conversations.as_array().unwrap_or(&empty_arr);
-    let mut synced_convs = 0;
-
-    for conv_json in convs {
-        let conv = parse_conversation(conv_json);
-        if let Err(e) = cache.upsert_conversation(&conv).await {
-            log::error!("failed to upsert conversation: {e}");
-        } else {
-            synced_convs += 1;
-        }
+    match engine.sync(opts).await {
+        Ok(report) => log::info!("gap-fill synced {} messages", report.messages),
+        Err(e) => log::warn!("gap-fill sync failed: {e}"),
     }
+}
 
-    log::info!("synced {synced_convs} conversations");
+/// Send outbox messages past their undo window.
+async fn do_outbox(engine: &Engine) {
+    match engine.dispatch_outbox().await {
+        Ok(0) => {}
+        Ok(n) => log::info!("sent {n} outbox messages"),
+        Err(e) => log::warn!("outbox dispatch failed: {e}"),
+    }
+}
 
-    // Fetch messages for top N recent conversations
-    let top = match cache.list_conversations(SYNC_TOP_CHATS).await {
-        Ok(c) => c,
-        Err(e) => {
-            log::error!("failed to list cached conversations: {e}");
-            return;
-        }
-    };
+/// Release `--if-available` messages whose recipients are now available.
+async fn do_presence(engine: &Engine) {
+    match engine.release_available_outbox().await {
+        Ok(0) => {}
+        Ok(n) => log::info!("releasing {n} outbox messages, recipients are available"),
+        Err(e) => log::warn!("checking recipients' presence failed: {e}"),
+    }
+}
 
-    let mut synced_msgs = 0;
-    for conv in &top {
-        match client
-            .get_chat_messages(&conv.id, Some(SYNC_MESSAGES_PER_CHAT))
-            .await
-        {
-            Ok(data) => {
-                let empty_msgs = Vec::new();
-                let msgs = data.as_array().unwrap_or(&empty_msgs);
-                for msg_json in msgs {
-                    if let Some(msg) = parse_message(msg_json, &conv.id, None) {
-                        if let Err(e) = cache.upsert_message(&msg).await {
-                            log::error!("failed to upsert message: {e}");
-                        } else {
-                            synced_msgs += 1;
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                log::warn!("failed to sync messages for {}: {e}", conv.display_name);
-            }
-        }
+/// Fire reminders that are due.
+async fn do_reminders(engine: &Engine) {
+    match engine.fire_reminders().await {
+        Ok(0) => {}
+        Ok(n) => log::info!("fired {n} reminders"),
+        Err(e) => log::warn!("checking reminders failed: {e}"),
     }
+}
 
-    log::info!("synced {synced_msgs} messages across {} chats", top.len());
+/// Fold the WAL back into the database so it doesn't grow without bound
+/// while other processes keep read transactions open.
+async fn do_checkpoint(engine: &Engine) {
+    match engine.cache().checkpoint().await {
+        Ok(r) if r.busy => log::debug!("WAL checkpoint deferred: cache busy"),
+        Ok(r) => log::debug!(
+            "WAL checkpoint: {}/{} pages written back",
+            r.checkpointed_pages,
+            r.log_pages
+        ),
+        Err(e) => log::warn!("WAL checkpoint failed: {e}"),
+    }
 }
 
 // ─── Service file generators ─────────────────────────────────────────
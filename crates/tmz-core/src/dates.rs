@@ -0,0 +1,234 @@
+//! Parsing of human-friendly points in time for `--since`/`--until` flags.
+//!
+//! Accepted forms (case-insensitive, interpreted in local time):
+//! - RFC 3339 timestamps and `YYYY-MM-DD` dates
+//! - `now`, `today`, `yesterday`
+//! - weekday names, optionally prefixed with `last` (`monday`, `last friday`)
+//! - relative offsets: `3d`, `2w`, `12h`, `30m`, or `3 days ago`
+//!
+//! Send times for `--schedule` look forward instead; see [`parse_send_time`].
+
+use crate::CoreError;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+
+/// Parse a point in time relative to `now`.
+///
+/// Dates and weekday names resolve to the start of that day.
+///
+/// # Errors
+///
+/// Returns an error if the input matches none of the accepted forms.
+pub fn parse_point_in_time(input: &str, now: DateTime<Local>) -> Result<DateTime<Utc>, CoreError> {
+    let text = input.trim().to_lowercase();
+
+    if let Ok(ts) = DateTime::parse_from_rfc3339(input.trim()) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+        return start_of_day(date);
+    }
+
+    let today = now.date_naive();
+    match text.as_str() {
+        "now" => return Ok(now.with_timezone(&Utc)),
+        "today" => return start_of_day(today),
+        "yesterday" => return start_of_day(today - Duration::days(1)),
+        _ => {}
+    }
+
+    let day_name = text.strip_prefix("last ").unwrap_or(&text);
+    if let Ok(weekday) = day_name.parse::<Weekday>() {
+        // Most recent such day strictly before today.
+        let back =
+            (7 + today.weekday().num_days_from_monday() - weekday.num_days_from_monday() - 1) % 7
+                + 1;
+        return start_of_day(today - Duration::days(i64::from(back)));
+    }
+
+    parse_offset(&text)
+        .map(|offset| (now - offset).with_timezone(&Utc))
+        .ok_or_else(|| {
+            CoreError::Other(format!(
+                "unrecognized time '{input}' (try YYYY-MM-DD, yesterday, last monday, 3d, or 2 weeks ago)"
+            ))
+        })
+}
+
+/// Parse a future send time relative to `now`: `09:00` (the next 9am),
+/// `tomorrow 09:00`, `mon 09:00` (the next Monday, today included if 9am
+/// is still ahead), `in 2h` or `2h`, or an RFC 3339 timestamp.
+///
+/// # Errors
+///
+/// Returns an error if the input matches none of these forms or lies in
+/// the past.
+pub fn parse_send_time(input: &str, now: DateTime<Local>) -> Result<DateTime<Utc>, CoreError> {
+    let text = input.trim().to_lowercase();
+    let invalid = || {
+        CoreError::Other(format!(
+            "unrecognized send time '{input}' (try 09:00, tomorrow 09:00, mon 09:00, or in 2h)"
+        ))
+    };
+
+    let at = if let Ok(ts) = DateTime::parse_from_rfc3339(input.trim()) {
+        ts.with_timezone(&Utc)
+    } else if let Some(offset) = parse_offset(text.strip_prefix("in ").unwrap_or(&text)) {
+        (now + offset).with_timezone(&Utc)
+    } else {
+        let (day, time) = text.rsplit_once(' ').unwrap_or(("", &text));
+        let time = NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| invalid())?;
+        let today = now.date_naive();
+        let dates: Vec<NaiveDate> = match day {
+            "" | "today" => vec![today, today + Duration::days(1)],
+            "tomorrow" => vec![today + Duration::days(1)],
+            name => {
+                let weekday = name.parse::<Weekday>().map_err(|_| invalid())?;
+                (0..8)
+                    .map(|n| today + Duration::days(n))
+                    .filter(|d| d.weekday() == weekday)
+                    .collect()
+            }
+        };
+        dates
+            .into_iter()
+            .filter_map(|date| Local.from_local_datetime(&date.and_time(time)).earliest())
+            .find(|t| *t > now)
+            .ok_or_else(invalid)?
+            .with_timezone(&Utc)
+    };
+    if at < now.with_timezone(&Utc) {
+        return Err(CoreError::Other(format!(
+            "send time '{input}' is in the past"
+        )));
+    }
+    Ok(at)
+}
+
+/// Parse `3d`, `12h`, `3 days ago`, `2 weeks`, etc. into a duration.
+fn parse_offset(text: &str) -> Option<Duration> {
+    let text = text.strip_suffix(" ago").unwrap_or(text).trim();
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = text.split_at(split);
+    let amount: i64 = amount.parse().ok()?;
+    let unit = unit.trim();
+    let unit = unit
+        .strip_suffix('s')
+        .filter(|u| u.len() > 1)
+        .unwrap_or(unit);
+    match unit {
+        "s" | "sec" | "second" => Some(Duration::seconds(amount)),
+        "m" | "min" | "minute" => Some(Duration::minutes(amount)),
+        "h" | "hr" | "hour" => Some(Duration::hours(amount)),
+        "d" | "day" => Some(Duration::days(amount)),
+        "w" | "wk" | "week" => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+/// Parse a duration setting such as `10s`, `2m`, or `1 hour`. A bare `0`
+/// is accepted as zero.
+///
+/// # Errors
+///
+/// Returns an error if the input is not a non-negative duration.
+pub fn parse_duration(input: &str) -> Result<std::time::Duration, CoreError> {
+    let text = input.trim().to_lowercase();
+    if text == "0" {
+        return Ok(std::time::Duration::ZERO);
+    }
+    parse_offset(&text)
+        .and_then(|d| d.to_std().ok())
+        .ok_or_else(|| {
+            CoreError::Config(format!("invalid duration '{input}' (try 10s, 2m, or 1h)"))
+        })
+}
+
+fn start_of_day(date: NaiveDate) -> Result<DateTime<Utc>, CoreError> {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|dt| Local.from_local_datetime(&dt).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| CoreError::Other(format!("invalid local date {date}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday afternoon.
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 3, 11, 15, 30, 0).unwrap()
+    }
+
+    fn day(y: i32, m: u32, d: u32) -> DateTime<Utc> {
+        start_of_day(NaiveDate::from_ymd_opt(y, m, d).unwrap()).unwrap()
+    }
+
+    fn at(d: u32, h: u32, m: u32) -> DateTime<Utc> {
+        Local
+            .with_ymd_and_hms(2026, 3, d, h, m, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn named_days_are_the_most_recent_past_one() {
+        assert_eq!(
+            parse_point_in_time("last monday", now()).unwrap(),
+            day(2026, 3, 9)
+        );
+        assert_eq!(
+            parse_point_in_time("Wednesday", now()).unwrap(),
+            day(2026, 3, 4)
+        );
+        assert_eq!(
+            parse_point_in_time("yesterday", now()).unwrap(),
+            day(2026, 3, 10)
+        );
+        assert_eq!(
+            parse_point_in_time("2026-01-02", now()).unwrap(),
+            day(2026, 1, 2)
+        );
+    }
+
+    #[test]
+    fn relative_points_count_back_from_now() {
+        assert_eq!(
+            parse_point_in_time("3 days ago", now()).unwrap(),
+            (now() - Duration::days(3)).with_timezone(&Utc)
+        );
+        assert_eq!(
+            parse_point_in_time("12h", now()).unwrap(),
+            (now() - Duration::hours(12)).with_timezone(&Utc)
+        );
+        assert!(parse_point_in_time("someday", now()).is_err());
+    }
+
+    #[test]
+    fn durations_must_not_be_negative() {
+        assert_eq!(
+            parse_duration("10s").unwrap(),
+            std::time::Duration::from_secs(10)
+        );
+        assert_eq!(parse_duration("0").unwrap(), std::time::Duration::ZERO);
+        assert!(parse_duration("-5s").is_err());
+    }
+
+    #[test]
+    fn send_times_are_the_next_matching_time() {
+        assert_eq!(parse_send_time("16:00", now()).unwrap(), at(11, 16, 0));
+        assert_eq!(parse_send_time("09:00", now()).unwrap(), at(12, 9, 0));
+        assert_eq!(
+            parse_send_time("tomorrow 08:30", now()).unwrap(),
+            at(12, 8, 30)
+        );
+        assert_eq!(parse_send_time("Mon 09:00", now()).unwrap(), at(16, 9, 0));
+        assert_eq!(parse_send_time("wed 09:00", now()).unwrap(), at(18, 9, 0));
+        assert_eq!(parse_send_time("in 2h", now()).unwrap(), at(11, 17, 30));
+    }
+
+    #[test]
+    fn send_times_in_the_past_or_unknown_are_rejected() {
+        assert!(parse_send_time("2026-03-01T09:00:00Z", now()).is_err());
+        assert!(parse_send_time("teatime", now()).is_err());
+    }
+}
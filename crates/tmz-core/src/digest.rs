@@ -0,0 +1,231 @@
+//! Digests of what needs attention: unread mentions and busy chats.
+//!
+//! Built from the cache, so the result is only as fresh as the last sync;
+//! the daemon, which runs the scheduled `digest` job (see
+//! [`crate::schedule`]), keeps it current. "Unread" uses each
+//! conversation's read horizon, the same as the TUI's mention count.
+
+use crate::CoreError;
+use crate::cache::{Cache, SearchResult, UnreadChat};
+use chrono::{DateTime, Local, Utc};
+
+/// Mentions and chats listed at most.
+const MAX_ITEMS: i64 = 20;
+
+/// Characters of message text shown per line.
+const PREVIEW_CHARS: usize = 100;
+
+/// Unread mentions and chats since a point in time.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Digest {
+    /// Start of the window.
+    pub since: DateTime<Utc>,
+    /// Unread messages that @-mention you, newest first.
+    pub mentions: Vec<SearchResult>,
+    /// Conversations with unread messages, busiest first.
+    pub chats: Vec<UnreadChat>,
+}
+
+impl Digest {
+    /// Collect the digest for messages composed since `since`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache cannot be read.
+    pub async fn compile(cache: &Cache, since: DateTime<Utc>) -> Result<Self, CoreError> {
+        let from = since.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        Ok(Self {
+            since,
+            mentions: cache.unread_mentions_since(&from, MAX_ITEMS).await?,
+            chats: cache.unread_chats_since(&from, MAX_ITEMS).await?,
+        })
+    }
+
+    /// Whether there is nothing unread.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.mentions.is_empty() && self.chats.is_empty()
+    }
+
+    /// One line for a notification, e.g. `2 mentions, 14 unread in 3 chats`.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            return "Nothing unread".to_string();
+        }
+        let unread: i64 = self.chats.iter().map(|c| c.unread).sum();
+        format!(
+            "{} mention{}, {unread} unread in {} chat{}",
+            self.mentions.len(),
+            plural(self.mentions.len()),
+            self.chats.len(),
+            plural(self.chats.len()),
+        )
+    }
+
+    /// The digest as Markdown, with times in local time.
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            format!(
+                "# Digest since {}",
+                self.since.with_timezone(&Local).format("%a %d %b %H:%M")
+            ),
+            String::new(),
+            self.summary(),
+        ];
+        if !self.mentions.is_empty() {
+            lines.extend([String::new(), "## Mentions".to_string()]);
+            for m in &self.mentions {
+                lines.push(format!(
+                    "- {} {} in {}: {}",
+                    local_time(&m.message.compose_time),
+                    m.message.from_display_name,
+                    m.conversation_name,
+                    preview(&m.message.content)
+                ));
+            }
+        }
+        if !self.chats.is_empty() {
+            lines.extend([String::new(), "## Unread".to_string()]);
+            for c in &self.chats {
+                lines.push(format!(
+                    "- {} ({} new), latest from {}: {}",
+                    c.conversation_name,
+                    c.unread,
+                    c.newest.from_display_name,
+                    preview(&c.newest.content)
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// The digest as Teams message HTML.
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        let lines: Vec<String> = self
+            .to_text()
+            .lines()
+            .map(|line| {
+                line.replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;")
+            })
+            .collect();
+        lines.join("<br>")
+    }
+}
+
+const fn plural(n: usize) -> &'static str {
+    if n == 1 { "" } else { "s" }
+}
+
+fn local_time(compose_time: &str) -> String {
+    DateTime::parse_from_rfc3339(compose_time).map_or_else(
+        |_| compose_time.to_string(),
+        |t| t.with_timezone(&Local).format("%a %H:%M").to_string(),
+    )
+}
+
+fn preview(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() > PREVIEW_CHARS {
+        let cut: String = text.chars().take(PREVIEW_CHARS).collect();
+        format!("{cut}\u{2026}")
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CachedConversation, parse_message};
+    use crate::testutil::TempDir;
+
+    /// A digest since March 1st of a read mention, an unread one, and two
+    /// new messages in a second chat.
+    async fn compiled(dir: &TempDir) -> Digest {
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        for (id, horizon) in [("19:ops", "1000"), ("19:dev", "0")] {
+            cache
+                .upsert_conversation(&CachedConversation {
+                    id: id.to_string(),
+                    display_name: id.trim_start_matches("19:").to_string(),
+                    thread_type: "chat".to_string(),
+                    product_type: String::new(),
+                    last_message_preview: String::new(),
+                    last_message_from: String::new(),
+                    last_activity: String::new(),
+                    messages_url: String::new(),
+                    member_names: String::new(),
+                    raw_json: format!(
+                        r#"{{"properties":{{"consumptionhorizon":"{horizon};{horizon};0"}}}}"#
+                    ),
+                    restrictions: crate::restrictions::Restrictions::default(),
+                })
+                .await
+                .unwrap();
+        }
+        let messages = [
+            (
+                "19:ops",
+                "900",
+                "already read @me",
+                true,
+                "2026-03-02T08:00:00Z",
+            ),
+            (
+                "19:ops",
+                "2000",
+                "deploy & ship?",
+                true,
+                "2026-03-02T09:00:00Z",
+            ),
+            ("19:dev", "10", "too old", false, "2026-02-01T09:00:00Z"),
+            ("19:dev", "11", "lunch?", false, "2026-03-02T10:00:00Z"),
+            ("19:dev", "12", "anyone?", false, "2026-03-02T11:00:00Z"),
+        ];
+        for (conv, id, text, mentions_me, time) in messages {
+            let raw = serde_json::json!({
+                "id": id,
+                "messagetype": "Text",
+                "content": text,
+                "imdisplayname": "Sam",
+                "composetime": time,
+                "mentionsMe": mentions_me,
+            });
+            let msg =
+                parse_message(&raw.into(), conv, None, &crate::MessagesConfig::default()).unwrap();
+            cache.upsert_message(&msg).await.unwrap();
+        }
+
+        let since = "2026-03-01T00:00:00Z".parse().unwrap();
+        Digest::compile(&cache, since).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn lists_unread_mentions_and_busy_chats() {
+        let dir = TempDir::new("digest").unwrap();
+        let digest = compiled(&dir).await;
+        assert_eq!(digest.mentions.len(), 1);
+        assert_eq!(digest.chats.len(), 2);
+        assert_eq!(digest.chats[0].conversation_name, "dev");
+        assert_eq!(digest.chats[0].unread, 2);
+        assert_eq!(digest.chats[0].newest.content, "anyone?");
+        assert_eq!(digest.summary(), "1 mention, 3 unread in 2 chats");
+    }
+
+    #[tokio::test]
+    async fn renders_as_text_and_escaped_html() {
+        let dir = TempDir::new("digest-render").unwrap();
+        let digest = compiled(&dir).await;
+        assert!(
+            digest
+                .to_text()
+                .contains("- dev (2 new), latest from Sam: anyone?")
+        );
+        assert!(digest.to_html().contains("deploy &amp; ship?<br>"));
+    }
+}
@@ -0,0 +1,1599 @@
+//! Shared engine owning sync, sending, and event subscriptions.
+//!
+//! The daemon runs one [`Engine`] and exposes it on a Unix socket
+//! (`$XDG_STATE_HOME/tmz/tmz.sock`). Frontends (CLI, TUI, MCP, API) connect
+//! with [`EngineClient`] and forward sync and send requests to it, so only one
+//! process refreshes tokens and writes to the `SQLite` cache. When no daemon is
+//! running, frontends fall back to a local [`Engine`].
+//!
+//! The wire protocol is newline-delimited JSON: each request line is an
+//! [`EngineRequest`], answered by one [`EngineResponse`] line. After a
+//! successful `subscribe` request the connection only carries
+//! [`EngineEvent`] lines.
+
+use crate::CoreError;
+use crate::audit::AuditLog;
+use crate::cache::{
+    Cache, CachedConversation, CachedMessage, log_skipped_types, parse_conversation, parse_message,
+};
+use crate::config::{
+    AppConfig, ConversationHook, FilesConfig, HooksConfig, JobKind, JobOutput, MessagesConfig,
+    NotificationRule, OcrConfig, ScheduledJob,
+};
+use crate::digest::Digest;
+use crate::hooks::PendingHook;
+use crate::live::{Backoff, Connection, ConnectionStats, Outage};
+use crate::notifications::PendingNotification;
+use crate::retention::RetentionPolicy;
+use crate::teams::api::TeamsApi;
+use crate::teams::auth::AuthManager;
+use crate::teams::client::TeamsClient;
+use crate::teams::models::LiveEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast;
+
+/// Capacity of the event broadcast channel.
+const EVENT_CAPACITY: usize = 256;
+
+/// What to sync.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SyncOptions {
+    /// Sync messages for all conversations, not just the top `chats`.
+    pub full: bool,
+    /// Number of most recently active conversations to sync messages for.
+    pub chats: i64,
+    /// Number of messages to fetch per conversation.
+    pub per_chat: i32,
+    /// Download and cache inline images referenced by synced messages.
+    pub images: bool,
+    /// Lite sync (see [`crate::lite`]).
+    pub lite: bool,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            full: false,
+            chats: 30,
+            per_chat: 50,
+            images: false,
+            lite: false,
+        }
+    }
+}
+
+impl SyncOptions {
+    /// These options as a lite sync: at most [`crate::lite::PER_CHAT`]
+    /// messages per chat and no images.
+    #[must_use]
+    pub fn into_lite(self) -> Self {
+        Self {
+            per_chat: self.per_chat.min(crate::lite::PER_CHAT),
+            images: false,
+            lite: true,
+            ..self
+        }
+    }
+
+    /// Identifies syncs with these options in the checkpoint table, so only
+    /// a rerun of the same sync resumes.
+    fn run_key(&self) -> String {
+        if self.full {
+            format!("full:{}", self.per_chat)
+        } else {
+            format!("{}:{}", self.chats, self.per_chat)
+        }
+    }
+}
+
+/// How often [`Engine::listen`] checks whether `[sync] live` was turned on.
+const LIVE_IDLE: Duration = Duration::from_secs(30);
+
+/// Checkpoints older than this (seconds) are stale: an interrupted sync
+/// rerun later starts over instead of resuming.
+const RESUME_WINDOW_SECS: i64 = 3600;
+
+/// Counts from a completed sync.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Conversations upserted.
+    pub conversations: u64,
+    /// Conversations whose messages were fetched.
+    pub chats: u64,
+    /// Messages upserted.
+    pub messages: u64,
+    /// Images downloaded into the cache.
+    pub images: u64,
+    /// Cached images whose text was extracted by OCR.
+    #[serde(default)]
+    pub images_ocr: u64,
+    /// Shared files whose text was extracted and indexed.
+    #[serde(default)]
+    pub files_indexed: u64,
+    /// Conversations skipped because an interrupted run of the same sync
+    /// already fetched them.
+    #[serde(default)]
+    pub resumed: u64,
+    /// Cached messages deleted by `[retention]`.
+    #[serde(default)]
+    pub pruned: u64,
+    /// New messages posted to conversation webhooks (`[hooks]`).
+    #[serde(default)]
+    pub hooks: u64,
+    /// Desktop notifications shown for new messages (`[[notifications]]`).
+    #[serde(default)]
+    pub notified: u64,
+}
+
+/// Progress notifications emitted while a sync runs.
+#[derive(Debug, Clone)]
+pub enum SyncProgress {
+    /// The conversation list was synced.
+    Conversations(u64),
+    /// Resuming an interrupted sync that already fetched `done`
+    /// conversations.
+    Resuming {
+        /// Conversations fetched before the interruption.
+        done: usize,
+    },
+    /// Fetching messages for conversation `index` (1-based) of `total`.
+    Chat {
+        /// Position in the batch (1-based).
+        index: usize,
+        /// Number of conversations in the batch.
+        total: usize,
+        /// Display name (or ID) of the conversation.
+        name: String,
+    },
+    /// Downloading image `index` (1-based) of `total`.
+    Image {
+        /// Position in the batch (1-based).
+        index: usize,
+        /// Number of images to download.
+        total: usize,
+    },
+}
+
+/// Events broadcast to subscribers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EngineEvent {
+    /// A sync run started.
+    SyncStarted,
+    /// A sync run finished.
+    SyncFinished {
+        /// Counts from the run.
+        report: SyncReport,
+    },
+    /// A sync run failed.
+    SyncFailed {
+        /// Error description.
+        error: String,
+    },
+    /// A message was sent through the engine.
+    MessageSent {
+        /// Target conversation.
+        conversation_id: String,
+    },
+    /// Tokens were refreshed.
+    TokensRefreshed {
+        /// New expiry (Unix seconds).
+        expires_at: i64,
+    },
+    /// Token refresh failed and the user must log in again.
+    ReauthNeeded {
+        /// Why the refresh failed.
+        reason: String,
+    },
+    /// Contact with the chat service is back after an outage (see
+    /// [`crate::live`]).
+    Reconnected {
+        /// How long the outage lasted, in seconds.
+        outage_secs: i64,
+    },
+    /// A message pushed by the chat service was cached (see
+    /// [`Engine::listen`]).
+    MessageReceived {
+        /// Its conversation.
+        conversation_id: String,
+        /// The message ID.
+        message_id: String,
+    },
+}
+
+/// Request sent to the engine socket.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum EngineRequest {
+    /// Report engine status.
+    Status,
+    /// Run a sync and return its [`SyncReport`].
+    Sync(SyncOptions),
+    /// Send a message.
+    Send {
+        /// Target conversation ID.
+        conversation_id: String,
+        /// Message content (HTML).
+        content: String,
+    },
+    /// Switch the connection to event streaming.
+    Subscribe,
+}
+
+/// Response to an [`EngineRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineResponse {
+    /// Whether the request succeeded.
+    pub ok: bool,
+    /// Request-specific result payload.
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub result: serde_json::Value,
+    /// Error description when `ok` is false.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Engine status returned by [`EngineRequest::Status`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EngineStatus {
+    /// Daemon process ID.
+    pub pid: u32,
+    /// Token expiry (Unix seconds), if tokens are stored.
+    pub token_expires_at: Option<i64>,
+    /// Last successful sync report.
+    pub last_sync: Option<SyncReport>,
+    /// Reconnect metrics of the link to the chat service.
+    #[serde(default)]
+    pub connection: ConnectionStats,
+}
+
+/// Owner of the cache, the Teams client, and the event stream.
+#[derive(Debug)]
+pub struct Engine {
+    cache: Cache,
+    events: broadcast::Sender<EngineEvent>,
+    last_sync: tokio::sync::Mutex<Option<SyncReport>>,
+    link: Mutex<Link>,
+    settings: RwLock<Settings>,
+}
+
+/// Liveness of the link to the chat service, updated by every sync.
+#[derive(Debug, Default)]
+struct Link {
+    connection: Connection,
+    /// Backoff before the next attempt after a failed sync.
+    retry_in: Option<Duration>,
+    /// Outage ended by the last sync, waiting for a gap-fill sync.
+    gap_fill: Option<Outage>,
+}
+
+/// Where new messages of one conversation go (see [`Engine::targets`]).
+#[derive(Debug)]
+struct Targets<'a> {
+    hook: Option<&'a ConversationHook>,
+    notify: bool,
+}
+
+/// New messages of a sync or push batch, passed on once they are cached.
+#[derive(Debug, Default)]
+struct Incoming {
+    hooked: Vec<PendingHook>,
+    notices: Vec<PendingNotification>,
+}
+
+/// Settings from the config, replaced by [`Engine::reconfigure`].
+#[derive(Debug, Clone, Default)]
+struct Settings {
+    message_types: MessagesConfig,
+    ocr: OcrConfig,
+    files: FilesConfig,
+    retention: RetentionPolicy,
+    read_only: bool,
+    lite: bool,
+    live: bool,
+    hooks: HooksConfig,
+    notifications: Vec<NotificationRule>,
+    audit: Option<AuditLog>,
+}
+
+impl Engine {
+    /// Open the engine on the cache database in `data_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache cannot be opened.
+    pub async fn open(data_dir: &Path) -> Result<Self, CoreError> {
+        Ok(Self::new(Cache::open(&data_dir.join("cache.db")).await?))
+    }
+
+    /// Create an engine around an already opened cache.
+    #[must_use]
+    pub fn new(cache: Cache) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            cache,
+            events,
+            last_sync: tokio::sync::Mutex::new(None),
+            link: Mutex::new(Link::default()),
+            settings: RwLock::new(Settings::default()),
+        }
+    }
+
+    fn settings(&self) -> Settings {
+        self.settings
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn link(&self) -> std::sync::MutexGuard<'_, Link> {
+        self.link.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn settings_mut(&mut self) -> &mut Settings {
+        self.settings
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace the settings taken from `config`, as the `with_*` builders
+    /// set them, after the config file changed.
+    pub fn reconfigure(&self, config: &AppConfig, audit: Option<AuditLog>) {
+        self.cache
+            .set_raw_json_compression(config.cache.compress_raw_json);
+        let settings = Settings {
+            message_types: config.messages.clone(),
+            ocr: config.ocr.clone(),
+            files: config.files.clone(),
+            retention: RetentionPolicy::new(config),
+            read_only: config.security.is_read_only(),
+            lite: config.sync.lite,
+            live: config.sync.live,
+            hooks: config.hooks.clone(),
+            notifications: config.notifications.clone(),
+            audit,
+        };
+        *self
+            .settings
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = settings;
+    }
+
+    /// Set which message types sync keeps (`[messages]` config).
+    #[must_use]
+    pub fn with_message_types(mut self, types: MessagesConfig) -> Self {
+        self.settings_mut().message_types = types;
+        self
+    }
+
+    /// Set the OCR pass run after each sync (`[ocr]` config).
+    #[must_use]
+    pub fn with_ocr(mut self, ocr: OcrConfig) -> Self {
+        self.settings_mut().ocr = ocr;
+        self
+    }
+
+    /// Set the shared-file indexing pass run after each sync (`[files]`
+    /// config).
+    #[must_use]
+    pub fn with_files(mut self, files: FilesConfig) -> Self {
+        self.settings_mut().files = files;
+        self
+    }
+
+    /// Set how long synced messages are kept (`[retention]` config).
+    #[must_use]
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.settings_mut().retention = retention;
+        self
+    }
+
+    /// Refuse sends (`[security] read_only`).
+    #[must_use]
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.settings_mut().read_only = read_only;
+        self
+    }
+
+    /// Make every sync a lite sync (`[sync] lite`).
+    #[must_use]
+    pub fn with_lite(mut self, lite: bool) -> Self {
+        self.settings_mut().lite = lite;
+        self
+    }
+
+    /// Post new messages of some conversations to webhooks (`[hooks]`
+    /// config).
+    #[must_use]
+    pub fn with_hooks(mut self, hooks: HooksConfig) -> Self {
+        self.settings_mut().hooks = hooks;
+        self
+    }
+
+    /// Show desktop notifications for new messages matching `rules`
+    /// (`[[notifications]]` config).
+    #[must_use]
+    pub fn with_notifications(mut self, rules: Vec<NotificationRule>) -> Self {
+        self.settings_mut().notifications = rules;
+        self
+    }
+
+    /// Record outbox sends and token refreshes in `audit`.
+    #[must_use]
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.settings_mut().audit = Some(audit);
+        self
+    }
+
+    /// The engine's cache handle.
+    #[must_use]
+    pub const fn cache(&self) -> &Cache {
+        &self.cache
+    }
+
+    /// Subscribe to engine events.
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<EngineEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, event: EngineEvent) {
+        // No subscribers is not an error.
+        let _ = self.events.send(event);
+    }
+
+    /// Current status.
+    pub async fn status(&self) -> EngineStatus {
+        let token_expires_at = AuthManager::new()
+            .ok()
+            .and_then(|auth| auth.get_tokens().ok())
+            .map(|t| t.expires_at);
+        EngineStatus {
+            pid: std::process::id(),
+            token_expires_at,
+            last_sync: *self.last_sync.lock().await,
+            connection: self.connection(),
+        }
+    }
+
+    /// Reconnect metrics of the link to the chat service.
+    #[must_use]
+    pub fn connection(&self) -> ConnectionStats {
+        self.link().connection.stats()
+    }
+
+    /// Whether the link to the chat service is down or has gone quiet for
+    /// too long (e.g. across a suspend), so a sync should run now.
+    #[must_use]
+    pub fn connection_stale(&self) -> bool {
+        self.link().connection.is_stale(chrono::Utc::now())
+    }
+
+    /// How long to back off before retrying the sync that just failed.
+    #[must_use]
+    pub fn reconnect_delay(&self) -> Option<Duration> {
+        self.link().retry_in.take()
+    }
+
+    /// The outage the last sync ended, if a gap-fill sync for it is still
+    /// owed. Returns it only once.
+    #[must_use]
+    pub fn take_gap_fill(&self) -> Option<Outage> {
+        self.link().gap_fill.take()
+    }
+
+    /// Record the outcome of a sync in the link's liveness.
+    fn record_contact(&self, ok: bool) {
+        let now = chrono::Utc::now();
+        let mut link = self.link();
+        if ok {
+            link.retry_in = None;
+            if let Some(outage) = link.connection.contact(now) {
+                let outage_secs = outage.duration.num_seconds();
+                log::info!("reconnected to the chat service after {outage_secs}s");
+                link.gap_fill = Some(outage);
+                drop(link);
+                self.emit(EngineEvent::Reconnected { outage_secs });
+            }
+        } else {
+            link.retry_in = Some(link.connection.failed(now));
+        }
+    }
+
+    /// Run the headless token refresh and broadcast the outcome.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the refresh fails.
+    pub async fn refresh_tokens(&self) -> Result<i64, CoreError> {
+        let result = async {
+            let auth = AuthManager::new()
+                .map_err(|e| CoreError::Other(format!("creating auth manager: {e}")))?;
+            auth.refresh_tokens()
+                .await
+                .map_err(|e| CoreError::Auth(e.to_string()))
+        }
+        .await;
+        if let Some(ref audit) = self.settings().audit {
+            audit.record("auth.refresh", None, &result);
+        }
+
+        match result {
+            Ok(tokens) => {
+                self.emit(EngineEvent::TokensRefreshed {
+                    expires_at: tokens.expires_at,
+                });
+                Ok(tokens.expires_at)
+            }
+            Err(e) => {
+                self.emit(EngineEvent::ReauthNeeded {
+                    reason: e.to_string(),
+                });
+                Err(e)
+            }
+        }
+    }
+
+    /// Sync conversations and messages into the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the conversation list cannot be fetched or the
+    /// cache cannot be written.
+    pub async fn sync(&self, opts: SyncOptions) -> Result<SyncReport, CoreError> {
+        self.sync_with_progress(opts, &mut |_| {}).await
+    }
+
+    /// Like [`Engine::sync`], reporting progress through a callback.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the conversation list cannot be fetched or the
+    /// cache cannot be written.
+    pub async fn sync_with_progress(
+        &self,
+        opts: SyncOptions,
+        progress: &mut (dyn FnMut(SyncProgress) + Send),
+    ) -> Result<SyncReport, CoreError> {
+        match TeamsClient::new() {
+            Ok(client) => self.sync_with_client(&client, opts, progress).await,
+            Err(e) => {
+                self.emit(EngineEvent::SyncStarted);
+                Err(self.sync_failed(e))
+            }
+        }
+    }
+
+    /// Like [`Engine::sync_with_progress`], talking to Teams through
+    /// `client` (e.g. a [`crate::teams::mock::MockTeamsClient`] in tests).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the conversation list cannot be fetched or the
+    /// cache cannot be written.
+    pub async fn sync_with_client(
+        &self,
+        client: &impl TeamsApi,
+        opts: SyncOptions,
+        progress: &mut (dyn FnMut(SyncProgress) + Send),
+    ) -> Result<SyncReport, CoreError> {
+        let opts = if opts.lite || self.settings().lite {
+            opts.into_lite()
+        } else {
+            opts
+        };
+        self.emit(EngineEvent::SyncStarted);
+        match self.run_sync(client, opts, progress).await {
+            Ok(report) => {
+                *self.last_sync.lock().await = Some(report);
+                self.record_contact(true);
+                self.emit(EngineEvent::SyncFinished { report });
+                Ok(report)
+            }
+            Err(e) => Err(self.sync_failed(e)),
+        }
+    }
+
+    /// Record and broadcast a failed sync.
+    fn sync_failed(&self, e: CoreError) -> CoreError {
+        self.record_contact(false);
+        self.emit(EngineEvent::SyncFailed {
+            error: e.to_string(),
+        });
+        e
+    }
+
+    async fn run_sync(
+        &self,
+        client: &impl TeamsApi,
+        opts: SyncOptions,
+        progress: &mut (dyn FnMut(SyncProgress) + Send),
+    ) -> Result<SyncReport, CoreError> {
+        let cache = &self.cache;
+        let settings = self.settings();
+        let mut report = SyncReport::default();
+
+        let my_name: Option<String> = client
+            .get_me()
+            .await
+            .ok()
+            .and_then(|u| u["displayName"].as_str().map(ToString::to_string));
+
+        for conv in client.list_chats().await? {
+            let mut conv = parse_conversation(&conv);
+            if opts.lite {
+                crate::lite::slim_conversation(&mut conv);
+            }
+            cache.upsert_conversation(&conv).await?;
+            report.conversations += 1;
+        }
+        progress(SyncProgress::Conversations(report.conversations));
+
+        let limit = if opts.full {
+            i64::try_from(report.conversations).unwrap_or(i64::MAX)
+        } else {
+            opts.chats
+        };
+        if limit <= 0 {
+            return Ok(report);
+        }
+
+        let run = opts.run_key();
+        let (top, resumed) = self.unsynced_conversations(&run, limit).await?;
+        report.resumed = resumed;
+        if resumed > 0 {
+            progress(SyncProgress::Resuming {
+                done: usize::try_from(resumed).unwrap_or(usize::MAX),
+            });
+        }
+        let total = top.len();
+        let mut image_urls: Vec<String> = Vec::new();
+        let mut skipped: BTreeMap<String, u64> = BTreeMap::new();
+        let mut incoming = Incoming::default();
+
+        for (i, conv) in top.iter().enumerate() {
+            let name = if conv.display_name.is_empty() {
+                conv.id.clone()
+            } else {
+                conv.display_name.clone()
+            };
+            progress(SyncProgress::Chat {
+                index: i + 1,
+                total,
+                name,
+            });
+
+            let messages = match client
+                .get_chat_messages(&conv.id, Some(opts.per_chat))
+                .await
+            {
+                Ok(d) => d,
+                Err(e) => {
+                    log::warn!("failed to sync messages for {}: {e}", conv.id);
+                    continue;
+                }
+            };
+            report.chats += 1;
+
+            let targets = self.targets(&settings, &conv.id).await?;
+            let mut stored = 0;
+            for msg in &messages {
+                let Some(mut cached) =
+                    parse_message(msg, &conv.id, my_name.as_deref(), &settings.message_types)
+                else {
+                    *skipped.entry(msg.message_type.clone()).or_default() += 1;
+                    continue;
+                };
+                if opts.images {
+                    image_urls.extend(self.uncached_images(&cached.content_html).await);
+                }
+                self.queue_incoming(
+                    &settings,
+                    &targets,
+                    Some(&conv.display_name),
+                    &cached,
+                    &mut incoming,
+                )
+                .await?;
+                if opts.lite {
+                    crate::lite::slim_message(&mut cached);
+                }
+                cache.upsert_message(&cached).await?;
+                stored += 1;
+            }
+            report.messages += stored;
+            cache.refresh_member_names(&conv.id).await?;
+            cache.record_sync_checkpoint(&run, &conv.id, stored).await?;
+        }
+        cache.clear_sync_checkpoints().await?;
+        log_skipped_types(&skipped);
+
+        report.images = self.download_images(client, &image_urls, progress).await;
+        report.hooks = self.post_hooks(&settings, incoming.hooked).await;
+        report.notified = show_notifications(incoming.notices);
+
+        self.finish_sync(client, &settings, &mut report).await;
+        Ok(report)
+    }
+
+    /// Prune messages past `[retention]`, then run the OCR and file indexing
+    /// passes that are turned on.
+    async fn finish_sync(
+        &self,
+        client: &impl TeamsApi,
+        settings: &Settings,
+        report: &mut SyncReport,
+    ) {
+        match crate::retention::apply(&self.cache, &settings.retention, false).await {
+            Ok(pruned) => {
+                report.pruned = pruned.iter().map(|p| p.messages).sum();
+                for p in &pruned {
+                    log::info!(
+                        "retention: pruned {} messages older than {} days from {}",
+                        p.messages,
+                        p.days,
+                        p.name
+                    );
+                }
+            }
+            Err(e) => log::warn!("retention pass failed: {e}"),
+        }
+
+        if settings.ocr.enabled {
+            let limit = settings.ocr.max_images_per_sync;
+            match crate::ocr::index_pending(&self.cache, &settings.ocr, limit).await {
+                Ok(n) => report.images_ocr = n,
+                Err(e) => log::warn!("OCR pass failed: {e}"),
+            }
+        }
+
+        if settings.files.index {
+            let limit = settings.files.max_files_per_sync;
+            match crate::files::index_pending(&self.cache, client, &settings.files, limit).await {
+                Ok(n) => report.files_indexed = n,
+                Err(e) => log::warn!("file indexing pass failed: {e}"),
+            }
+        }
+    }
+
+    /// The top `limit` conversations, minus those an interrupted run of the
+    /// sync `run` already finished, and how many were skipped.
+    async fn unsynced_conversations(
+        &self,
+        run: &str,
+        limit: i64,
+    ) -> Result<(Vec<CachedConversation>, u64), CoreError> {
+        let since = chrono::Utc::now().timestamp() - RESUME_WINDOW_SECS;
+        let done = self.cache.sync_checkpoints(run, since).await?;
+        if done.is_empty() {
+            // Nothing to resume; drop checkpoints of other or stale runs.
+            self.cache.clear_sync_checkpoints().await?;
+        }
+        let (skipped, pending): (Vec<_>, Vec<_>) = self
+            .cache
+            .list_conversations(limit)
+            .await?
+            .into_iter()
+            .partition(|conv| done.contains(&conv.id));
+        Ok((pending, skipped.len() as u64))
+    }
+
+    /// The webhook of conversation `conv_id`, if it has one and was cached
+    /// before: a hook only sees messages that arrive after that, not the
+    /// history.
+    async fn hook_for<'a>(
+        &self,
+        hooks: &'a HooksConfig,
+        conv_id: &str,
+    ) -> Result<Option<&'a ConversationHook>, CoreError> {
+        match hooks.conversations.get(conv_id) {
+            Some(hook) if self.has_history(conv_id).await? => Ok(Some(hook)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Where new messages of `conv_id` go: its webhook, and the
+    /// notification rules if there are some. Like hooks, rules only see
+    /// conversations cached before, so a first sync does not notify about
+    /// the history.
+    async fn targets<'a>(
+        &self,
+        settings: &'a Settings,
+        conv_id: &str,
+    ) -> Result<Targets<'a>, CoreError> {
+        Ok(Targets {
+            hook: self.hook_for(&settings.hooks, conv_id).await?,
+            notify: !settings.notifications.is_empty() && self.has_history(conv_id).await?,
+        })
+    }
+
+    /// Queue the synced message `msg` for its conversation's webhook and
+    /// notification rules, unless it is cached already. `conv_name` is
+    /// looked up when `None`.
+    async fn queue_incoming(
+        &self,
+        settings: &Settings,
+        targets: &Targets<'_>,
+        conv_name: Option<&str>,
+        msg: &CachedMessage,
+        incoming: &mut Incoming,
+    ) -> Result<(), CoreError> {
+        let hook = self.hook_wants(targets.hook, msg).await?;
+        let notify = targets.notify && self.is_new(msg).await?;
+        if !(hook || notify) {
+            return Ok(());
+        }
+        let name = match conv_name {
+            Some(name) => name.to_string(),
+            None => self.conversation_name(&msg.conversation_id).await?,
+        };
+        if hook {
+            incoming.hooked.push(PendingHook::new(&name, msg));
+        }
+        if notify {
+            incoming.notices.extend(crate::notifications::notification(
+                &settings.notifications,
+                &name,
+                msg,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether `conv_id` has cached messages.
+    async fn has_history(&self, conv_id: &str) -> Result<bool, CoreError> {
+        Ok(!self.cache.get_messages(conv_id, 1).await?.is_empty())
+    }
+
+    /// Whether the synced message `msg` is not cached yet.
+    async fn is_new(&self, msg: &CachedMessage) -> Result<bool, CoreError> {
+        Ok(self
+            .cache
+            .get_message(&msg.conversation_id, &msg.id)
+            .await?
+            .is_none())
+    }
+
+    /// Whether `hook` should receive the synced message `msg`: it is not
+    /// cached yet, and not your own unless the hook wants those too.
+    async fn hook_wants(
+        &self,
+        hook: Option<&ConversationHook>,
+        msg: &CachedMessage,
+    ) -> Result<bool, CoreError> {
+        let Some(hook) = hook else {
+            return Ok(false);
+        };
+        Ok((hook.include_own || !msg.is_from_me) && self.is_new(msg).await?)
+    }
+
+    /// Post new messages to their conversations' webhooks, oldest first,
+    /// returning how many were delivered.
+    async fn post_hooks(&self, settings: &Settings, mut pending: Vec<PendingHook>) -> u64 {
+        pending.sort_by(|a, b| a.message.compose_time.cmp(&b.message.compose_time));
+        let mut delivered = 0;
+        for item in pending {
+            let conv = item.message.conversation_id.as_str();
+            let Some(hook) = settings.hooks.conversations.get(conv) else {
+                continue;
+            };
+            let body = crate::hooks::payload(&item.conversation_name, &item.message).to_string();
+            let result = crate::hooks::deliver(&settings.hooks, hook, body).await;
+            if let Some(ref audit) = settings.audit {
+                audit.record("hook.post", Some(conv), &result);
+            }
+            match result {
+                Ok(()) => delivered += 1,
+                Err(e) => log::warn!("hook for {conv}: {e}"),
+            }
+        }
+        delivered
+    }
+
+    /// Image URLs in `html` that are not cached yet.
+    async fn uncached_images(&self, html: &str) -> Vec<String> {
+        let mut urls = Vec::new();
+        for url in crate::kitty::extract_image_urls(html) {
+            if !self.cache.has_image(&url).await.unwrap_or(true) {
+                urls.push(url);
+            }
+        }
+        urls
+    }
+
+    /// Download images into the cache, returning how many were stored.
+    async fn download_images(
+        &self,
+        client: &impl TeamsApi,
+        urls: &[String],
+        progress: &mut (dyn FnMut(SyncProgress) + Send),
+    ) -> u64 {
+        let mut stored = 0;
+        for (i, url) in urls.iter().enumerate() {
+            progress(SyncProgress::Image {
+                index: i + 1,
+                total: urls.len(),
+            });
+            match client.download_image(url).await {
+                Ok(data) => {
+                    let content_type = if url.contains("imgpsh") {
+                        "image/jpeg"
+                    } else {
+                        "image/png"
+                    };
+                    if self
+                        .cache
+                        .cache_image(url, &data, content_type)
+                        .await
+                        .is_ok()
+                    {
+                        stored += 1;
+                    }
+                }
+                Err(e) => log::debug!("image download failed: {e}"),
+            }
+        }
+        stored
+    }
+
+    /// Cache messages as the chat service pushes them, until `shutdown`
+    /// fires, so they show up without waiting for the next sync. Failed
+    /// polls are retried with a [`Backoff`]. Nothing is polled while
+    /// `[sync] live` is off.
+    pub async fn listen(&self, shutdown: tokio::sync::watch::Receiver<bool>) {
+        match TeamsClient::new() {
+            Ok(client) => self.listen_with_client(&client, shutdown).await,
+            Err(e) => log::warn!("live events unavailable: {e}"),
+        }
+    }
+
+    /// Like [`Engine::listen`], talking to Teams through `client`.
+    pub async fn listen_with_client(
+        &self,
+        client: &impl TeamsApi,
+        mut shutdown: tokio::sync::watch::Receiver<bool>,
+    ) {
+        let mut backoff = Backoff::default();
+        loop {
+            let wait = if self.settings().live {
+                let polled = tokio::select! {
+                    polled = self.poll_live(client) => polled,
+                    _ = shutdown.changed() => return,
+                };
+                match polled {
+                    Ok(_) => {
+                        backoff.reset();
+                        Duration::ZERO
+                    }
+                    Err(e) => {
+                        let delay = backoff.next_delay();
+                        log::warn!("live events failed, retrying in {}s: {e}", delay.as_secs());
+                        delay
+                    }
+                }
+            } else {
+                LIVE_IDLE
+            };
+            if !wait.is_zero() {
+                tokio::select! {
+                    () = tokio::time::sleep(wait) => {}
+                    _ = shutdown.changed() => return,
+                }
+            }
+        }
+    }
+
+    /// Wait for one batch of pushed changes and cache its messages,
+    /// returning how many were stored. Messages of conversations not cached
+    /// yet fetch the conversation list first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the poll fails or the cache cannot be written.
+    pub async fn poll_live(&self, client: &impl TeamsApi) -> Result<usize, CoreError> {
+        let events = client.poll_events().await?;
+        self.record_contact(true);
+        let settings = self.settings();
+        let my_name = self.cache.my_display_name().await?;
+        let mut incoming = Incoming::default();
+        let mut received = Vec::new();
+        let mut listed = false;
+        for event in events {
+            let msg = match event {
+                LiveEvent::Message(msg) if !msg.conversation_id.is_empty() => msg,
+                LiveEvent::Message(_) => continue,
+                LiveEvent::Other(kind) => {
+                    log::trace!("live event {kind}");
+                    continue;
+                }
+            };
+            let conv_id = msg.conversation_id.clone();
+            let Some(cached) =
+                parse_message(&msg, &conv_id, my_name.as_deref(), &settings.message_types)
+            else {
+                continue;
+            };
+            let targets = self.targets(&settings, &conv_id).await?;
+            self.queue_incoming(&settings, &targets, None, &cached, &mut incoming)
+                .await?;
+            self.cache.upsert_message(&cached).await?;
+            if !self.cache.record_last_message(&cached).await? && !listed {
+                // A new chat: list it so it shows up with its name.
+                listed = true;
+                for conv in client.list_chats().await? {
+                    self.cache
+                        .upsert_conversation(&parse_conversation(&conv))
+                        .await?;
+                }
+                self.cache.refresh_member_names(&conv_id).await?;
+            }
+            received.push((conv_id, cached.id));
+        }
+        self.post_hooks(&settings, incoming.hooked).await;
+        show_notifications(incoming.notices);
+        let stored = received.len();
+        for (conversation_id, message_id) in received {
+            self.emit(EngineEvent::MessageReceived {
+                conversation_id,
+                message_id,
+            });
+        }
+        Ok(stored)
+    }
+
+    /// Display name of the cached conversation `conv_id`.
+    async fn conversation_name(&self, conv_id: &str) -> Result<String, CoreError> {
+        Ok(self
+            .cache
+            .find_conversation(conv_id)
+            .await?
+            .into_iter()
+            .find(|conv| conv.id == conv_id)
+            .map(|conv| conv.display_name)
+            .unwrap_or_default())
+    }
+
+    /// Send a message and broadcast [`EngineEvent::MessageSent`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if read-only mode is on, not authenticated, or the
+    /// request fails.
+    pub async fn send_message(
+        &self,
+        conversation_id: &str,
+        content: &str,
+    ) -> Result<serde_json::Value, CoreError> {
+        if self.settings().read_only {
+            return Err(CoreError::ReadOnly("sending messages".to_string()));
+        }
+        self.send_message_with(&TeamsClient::new()?, conversation_id, content)
+            .await
+    }
+
+    /// Like [`Engine::send_message`], sending through `client`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if read-only mode is on or the request fails.
+    pub async fn send_message_with(
+        &self,
+        client: &impl TeamsApi,
+        conversation_id: &str,
+        content: &str,
+    ) -> Result<serde_json::Value, CoreError> {
+        if self.settings().read_only {
+            return Err(CoreError::ReadOnly("sending messages".to_string()));
+        }
+        let result = client.send_message(conversation_id, content).await?;
+        self.emit(EngineEvent::MessageSent {
+            conversation_id: conversation_id.to_string(),
+        });
+        Ok(result)
+    }
+
+    /// Send outbox messages whose undo window has passed (see
+    /// [`crate::outbox`]). Returns how many were sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the outbox cannot be read or updated.
+    pub async fn dispatch_outbox(&self) -> Result<usize, CoreError> {
+        let sent = crate::outbox::dispatch_due(&self.cache, None, |conv, content| async move {
+            let sent = self.send_message(&conv, &content).await;
+            if let Some(ref audit) = self.settings().audit {
+                audit.record("send", Some(&conv), &sent);
+            }
+            sent
+        })
+        .await?;
+        Ok(sent.len())
+    }
+
+    /// Release outbox messages held for recipients who are now Available
+    /// (see [`crate::outbox::release_available`]). Returns how many were
+    /// released.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the outbox cannot be read or updated.
+    pub async fn release_available_outbox(&self) -> Result<usize, CoreError> {
+        crate::outbox::release_available(&self.cache, |user| async move {
+            let presence = TeamsClient::new()?.get_user_presence(&user).await?;
+            Ok(presence.availability)
+        })
+        .await
+    }
+
+    /// Fire due reminders (see [`crate::remind`]): notify and mark their
+    /// conversations unread again. Returns how many fired.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reminders cannot be read or updated.
+    pub async fn fire_reminders(&self) -> Result<usize, CoreError> {
+        let fired = crate::remind::fire_due(
+            &self.cache,
+            crate::schedule::notify,
+            |conv, horizon| async move {
+                TeamsClient::new()?
+                    .set_consumption_horizon(&conv, &horizon)
+                    .await
+            },
+        )
+        .await?;
+        Ok(fired.len())
+    }
+
+    /// Run a scheduled job (see [`crate::schedule`]). Digests with nothing
+    /// unread are written to files but not sent or shown.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the job's window is invalid, the cache cannot
+    /// be read, or delivery fails.
+    pub async fn run_job(&self, job: &ScheduledJob) -> Result<(), CoreError> {
+        match job.job {
+            JobKind::Digest => {
+                let since = crate::dates::parse_point_in_time(&job.since, chrono::Local::now())?;
+                let digest = Digest::compile(&self.cache, since).await?;
+                match job.output {
+                    JobOutput::File => {
+                        let path = crate::schedule::output_path(job)?;
+                        if let Some(parent) = path.parent() {
+                            std::fs::create_dir_all(parent)?;
+                        }
+                        std::fs::write(&path, digest.to_text())?;
+                    }
+                    _ if digest.is_empty() => {}
+                    JobOutput::Notify => {
+                        crate::schedule::notify(&format!("tmz {}", job.name), &digest.summary())?;
+                    }
+                    JobOutput::Chat => {
+                        let chat = job.chat.as_deref().unwrap_or(crate::schedule::NOTES_CHAT);
+                        let sent = self.send_message(chat, &digest.to_html()).await;
+                        if let Some(ref audit) = self.settings().audit {
+                            audit.record("send", Some(chat), &sent);
+                        }
+                        sent?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle one request from a socket client.
+    async fn handle(&self, request: EngineRequest) -> EngineResponse {
+        let result = match request {
+            EngineRequest::Status => to_value(&self.status().await),
+            EngineRequest::Sync(opts) => self.sync(opts).await.and_then(|r| to_value(&r)),
+            EngineRequest::Send {
+                conversation_id,
+                content,
+            } => self.send_message(&conversation_id, &content).await,
+            EngineRequest::Subscribe => Ok(serde_json::Value::Null),
+        };
+        match result {
+            Ok(result) => EngineResponse {
+                ok: true,
+                result,
+                error: None,
+            },
+            Err(e) => EngineResponse {
+                ok: false,
+                result: serde_json::Value::Null,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Show pending desktop notifications, returning how many were shown.
+fn show_notifications(pending: Vec<PendingNotification>) -> u64 {
+    let mut shown = 0;
+    for notice in pending {
+        match crate::schedule::notify(&notice.title, &notice.body) {
+            Ok(()) => shown += 1,
+            Err(e) => log::warn!("notification failed: {e}"),
+        }
+    }
+    shown
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<serde_json::Value, CoreError> {
+    serde_json::to_value(value).map_err(|e| CoreError::Serialization(e.to_string()))
+}
+
+async fn write_line<W, T>(writer: &mut W, value: &T) -> Result<(), CoreError>
+where
+    W: AsyncWrite + Unpin + Send + ?Sized,
+    T: Serialize + Sync,
+{
+    let mut line =
+        serde_json::to_string(value).map_err(|e| CoreError::Serialization(e.to_string()))?;
+    line.push('\n');
+    writer
+        .write_all(line.as_bytes())
+        .await
+        .map_err(CoreError::Io)
+}
+
+// ─── Socket server ───────────────────────────────────────────────────
+
+/// Path of the engine socket.
+///
+/// # Errors
+///
+/// Returns an error if the state directory cannot be determined.
+pub fn socket_path() -> Result<PathBuf, CoreError> {
+    let state_dir = crate::default_state_dir()
+        .map_err(|e| CoreError::Path(format!("resolving state dir: {e}")))?;
+    Ok(state_dir.join("tmz.sock"))
+}
+
+/// Bind a Unix socket at `path` that only the owner can connect to.
+///
+/// The socket is bound inside a fresh 0700 directory, restricted to 0600
+/// and only then moved to `path`, so no other user can connect before its
+/// mode is set.
+#[cfg(unix)]
+fn bind_private(path: &Path) -> std::io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt as _, PermissionsExt as _};
+
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(parent)?;
+    let staging = parent.join(format!(".tmz-sock-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("tmz.sock");
+    let bound = tokio::net::UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    bound
+}
+
+/// Serve the engine on a Unix socket until `shutdown` flips to `true`.
+///
+/// A stale socket file from a previous run is replaced.
+///
+/// # Errors
+///
+/// Returns an error if the socket cannot be bound.
+#[cfg(unix)]
+pub async fn serve(
+    engine: std::sync::Arc<Engine>,
+    path: PathBuf,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+) -> Result<(), CoreError> {
+    let listener = bind_private(&path).map_err(CoreError::Io)?;
+    log::info!("engine listening on {}", path.display());
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    let engine = std::sync::Arc::clone(&engine);
+                    tokio::spawn(async move {
+                        let (read, write) = stream.into_split();
+                        if let Err(e) = handle_connection(&engine, read, write).await {
+                            log::debug!("engine client disconnected: {e}");
+                        }
+                    });
+                }
+                Err(e) => log::warn!("engine accept failed: {e}"),
+            },
+            _ = shutdown.changed() => break,
+        }
+    }
+
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn handle_connection<R, W>(engine: &Engine, read: R, mut write: W) -> Result<(), CoreError>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    let mut lines = BufReader::new(read).lines();
+    while let Some(line) = lines.next_line().await.map_err(CoreError::Io)? {
+        let request: EngineRequest = match serde_json::from_str(&line) {
+            Ok(r) => r,
+            Err(e) => {
+                let response = EngineResponse {
+                    ok: false,
+                    result: serde_json::Value::Null,
+                    error: Some(format!("invalid request: {e}")),
+                };
+                write_line(&mut write, &response).await?;
+                continue;
+            }
+        };
+
+        if request == EngineRequest::Subscribe {
+            let mut events = engine.subscribe();
+            write_line(&mut write, &engine.handle(request).await).await?;
+            loop {
+                match events.recv().await {
+                    Ok(event) => write_line(&mut write, &event).await?,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        log::debug!("engine subscriber lagged by {n} events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                }
+            }
+        }
+
+        write_line(&mut write, &engine.handle(request).await).await?;
+    }
+    Ok(())
+}
+
+// ─── Client ──────────────────────────────────────────────────────────
+
+type BoxedReader = BufReader<Box<dyn AsyncRead + Unpin + Send>>;
+type BoxedWriter = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// Connection to a running engine.
+pub struct EngineClient {
+    reader: BoxedReader,
+    writer: BoxedWriter,
+}
+
+impl std::fmt::Debug for EngineClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EngineClient").finish_non_exhaustive()
+    }
+}
+
+impl EngineClient {
+    /// Connect to the daemon's engine socket.
+    ///
+    /// Returns `None` when no daemon is listening (or on platforms without
+    /// Unix sockets), in which case callers should use a local [`Engine`].
+    pub async fn connect() -> Option<Self> {
+        let path = socket_path().ok()?;
+        Self::connect_to(&path).await
+    }
+
+    /// Connect to an engine socket at a specific path.
+    #[cfg(unix)]
+    pub async fn connect_to(path: &Path) -> Option<Self> {
+        let stream = tokio::net::UnixStream::connect(path).await.ok()?;
+        let (read, write) = stream.into_split();
+        Some(Self {
+            reader: BufReader::new(Box::new(read)),
+            writer: Box::new(write),
+        })
+    }
+
+    /// Connect to an engine socket at a specific path.
+    #[cfg(not(unix))]
+    pub async fn connect_to(_path: &Path) -> Option<Self> {
+        None
+    }
+
+    async fn read_line(&mut self) -> Result<String, CoreError> {
+        let mut line = String::new();
+        let n = self
+            .reader
+            .read_line(&mut line)
+            .await
+            .map_err(CoreError::Io)?;
+        if n == 0 {
+            return Err(CoreError::Other("engine closed the connection".to_string()));
+        }
+        Ok(line)
+    }
+
+    /// Send a request and wait for its response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails or the engine reports one.
+    pub async fn request(
+        &mut self,
+        request: &EngineRequest,
+    ) -> Result<serde_json::Value, CoreError> {
+        write_line(&mut self.writer, request).await?;
+        let line = self.read_line().await?;
+        let response: EngineResponse = serde_json::from_str(&line)
+            .map_err(|e| CoreError::Serialization(format!("parsing engine response: {e}")))?;
+        if response.ok {
+            return Ok(response.result);
+        }
+        let error = response
+            .error
+            .unwrap_or_else(|| "engine request failed".to_string());
+        // Keep auth failures recognizable, so frontends can offer a login.
+        if let Some(reason) = error.strip_prefix("authentication error: ") {
+            return Err(CoreError::Auth(reason.to_string()));
+        }
+        Err(CoreError::Other(error))
+    }
+
+    /// Engine status.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    pub async fn status(&mut self) -> Result<EngineStatus, CoreError> {
+        let value = self.request(&EngineRequest::Status).await?;
+        serde_json::from_value(value).map_err(|e| CoreError::Serialization(e.to_string()))
+    }
+
+    /// Run a sync in the engine.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sync fails.
+    pub async fn sync(&mut self, opts: SyncOptions) -> Result<SyncReport, CoreError> {
+        let value = self.request(&EngineRequest::Sync(opts)).await?;
+        serde_json::from_value(value).map_err(|e| CoreError::Serialization(e.to_string()))
+    }
+
+    /// Send a message through the engine.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending fails.
+    pub async fn send_message(
+        &mut self,
+        conversation_id: &str,
+        content: &str,
+    ) -> Result<serde_json::Value, CoreError> {
+        self.request(&EngineRequest::Send {
+            conversation_id: conversation_id.to_string(),
+            content: content.to_string(),
+        })
+        .await
+    }
+
+    /// Switch this connection to event streaming.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the subscription is rejected.
+    pub async fn subscribe(mut self) -> Result<EventStream, CoreError> {
+        self.request(&EngineRequest::Subscribe).await?;
+        Ok(EventStream { client: self })
+    }
+}
+
+/// Stream of [`EngineEvent`]s from a subscribed connection.
+#[derive(Debug)]
+pub struct EventStream {
+    client: EngineClient,
+}
+
+impl EventStream {
+    /// Wait for the next event.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection closes or a line cannot be parsed.
+    pub async fn next_event(&mut self) -> Result<EngineEvent, CoreError> {
+        let line = self.client.read_line().await?;
+        serde_json::from_str(&line)
+            .map_err(|e| CoreError::Serialization(format!("parsing engine event: {e}")))
+    }
+}
+
+// ─── Frontend helpers ────────────────────────────────────────────────
+
+/// Send a message via the daemon when it is running, otherwise directly.
+///
+/// # Errors
+///
+/// Returns an error if sending fails.
+pub async fn send_message(
+    conversation_id: &str,
+    content: &str,
+) -> Result<serde_json::Value, CoreError> {
+    if let Some(mut client) = EngineClient::connect().await {
+        return client.send_message(conversation_id, content).await;
+    }
+    TeamsClient::new()?
+        .send_message(conversation_id, content)
+        .await
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn request_wire_format_is_stable() {
+        let req = EngineRequest::Send {
+            conversation_id: "19:abc@thread.v2".to_string(),
+            content: "hi".to_string(),
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(
+            json,
+            r#"{"method":"send","params":{"conversation_id":"19:abc@thread.v2","content":"hi"}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<EngineRequest>(r#"{"method":"status"}"#).unwrap(),
+            EngineRequest::Status
+        );
+    }
+
+    #[tokio::test]
+    async fn socket_round_trip_and_subscription() {
+        let dir = TempDir::new("engine-test").unwrap();
+        let sock = dir.join("tmz.sock");
+
+        let engine = std::sync::Arc::new(Engine::open(&dir).await.unwrap());
+        let (tx, rx) = tokio::sync::watch::channel(false);
+        let server = tokio::spawn(serve(std::sync::Arc::clone(&engine), sock.clone(), rx));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            let mode = std::fs::metadata(&sock).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let mut client = EngineClient::connect_to(&sock).await.unwrap();
+        let status = client.status().await.unwrap();
+        assert_eq!(status.pid, std::process::id());
+
+        let mut events = EngineClient::connect_to(&sock)
+            .await
+            .unwrap()
+            .subscribe()
+            .await
+            .unwrap();
+        engine.emit(EngineEvent::SyncStarted);
+        assert_eq!(events.next_event().await.unwrap(), EngineEvent::SyncStarted);
+
+        tx.send(true).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_only_engine_refuses_sends() {
+        let dir = TempDir::new("engine-ro").unwrap();
+        let engine = Engine::open(&dir).await.unwrap().with_read_only(true);
+        let err = engine.send_message("19:a", "hi").await.unwrap_err();
+        assert!(matches!(err, CoreError::ReadOnly(_)), "{err}");
+
+        let config = crate::SecurityConfig { read_only: true };
+        assert!(config.ensure_writable("writing aliases").is_err());
+    }
+}
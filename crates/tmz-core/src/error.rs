@@ -1,5 +1,7 @@
 //! Error types for the core library.
 
+use std::fmt;
+
 use thiserror::Error;
 
 /// Core library error type.
@@ -31,12 +33,194 @@ pub enum CoreError {
 
     /// An API or HTTP error.
     #[error("API error: {0}")]
-    Api(String),
+    Api(ApiError),
+
+    /// A mutating operation refused because read-only mode is on.
+    #[error("read-only mode: {0} is disabled ([security] read_only / TMZ_READ_ONLY)")]
+    ReadOnly(String),
+
+    /// An action the conversation does not support (encrypted chats, chats
+    /// with Skype accounts).
+    #[error("not available in this conversation: {0}")]
+    Restricted(String),
 
     /// A generic error for other cases.
     #[error("error: {0}")]
     Other(String),
 }
 
+impl CoreError {
+    /// An API error with just a message.
+    pub fn api(message: impl Into<String>) -> Self {
+        Self::Api(ApiError::new(message))
+    }
+
+    /// The service's request ID of a failed API call, to quote in support
+    /// tickets.
+    #[must_use]
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            Self::Api(error) => error.request_id.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// A failed API request, with what the service's error payload says about
+/// it.
+///
+/// The chat service answers with `{"errorCode": ..., "message": ...}`,
+/// Graph with `{"error": {"code", "message", "innerError": {"request-id"}}}`,
+/// and gateways sometimes with an HTML page. [`ApiError::from_response`]
+/// keeps the code, message, and request ID and drops the rest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApiError {
+    /// What was being done (`send message`), or the whole message for
+    /// errors without a response.
+    pub context: String,
+    /// HTTP status code.
+    pub status: Option<u16>,
+    /// Service error code (`ErrorAccessDenied`, `209`).
+    pub code: Option<String>,
+    /// Service error message.
+    pub message: Option<String>,
+    /// Request ID for support tickets.
+    pub request_id: Option<String>,
+}
+
+impl ApiError {
+    /// An error with just a message.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            context: message.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Parse the error body of a failed response.
+    #[must_use]
+    pub fn from_response(context: &str, status: u16, body: &str) -> Self {
+        let mut error = Self {
+            context: context.to_string(),
+            status: Some(status),
+            ..Self::default()
+        };
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+            error.message = summarize_text(body);
+            return error;
+        };
+        let payload = if json["error"].is_object() {
+            &json["error"]
+        } else {
+            &json
+        };
+        error.code = string_field(payload, &["code", "errorCode", "error"]);
+        error.message = string_field(payload, &["message", "errorMessage", "error_description"]);
+        error.request_id = string_field(&payload["innerError"], &["request-id", "requestId"])
+            .or_else(|| string_field(&payload["innererror"], &["request-id", "requestId"]))
+            .or_else(|| string_field(&json, &["requestId", "request-id"]));
+        error
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.context)?;
+        if let Some(status) = self.status {
+            write!(f, " failed: {status}")?;
+            if let Some(reason) = reqwest::StatusCode::from_u16(status)
+                .ok()
+                .and_then(|s| s.canonical_reason())
+            {
+                write!(f, " {reason}")?;
+            }
+        }
+        match (&self.code, &self.message) {
+            (Some(code), Some(message)) => write!(f, " - {code}: {message}")?,
+            (Some(text), None) | (None, Some(text)) => write!(f, " - {text}")?,
+            (None, None) => {}
+        }
+        if let Some(ref id) = self.request_id {
+            write!(f, " (request-id {id})")?;
+        }
+        Ok(())
+    }
+}
+
+/// The first of `keys` in `value` holding a string or number.
+fn string_field(value: &serde_json::Value, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| match &value[*key] {
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+/// The title of an HTML error page, or the first line of a text body,
+/// shortened.
+fn summarize_text(body: &str) -> Option<String> {
+    const MAX_CHARS: usize = 200;
+    let body = body.trim();
+    let text = if body.starts_with('<') {
+        let lower = body.to_ascii_lowercase();
+        let start = lower.find("<title>")? + "<title>".len();
+        let end = start + lower[start..].find("</title>")?;
+        body[start..end].trim()
+    } else {
+        body.lines().next()?.trim()
+    };
+    if text.is_empty() {
+        return None;
+    }
+    Some(if text.chars().count() > MAX_CHARS {
+        let cut: String = text.chars().take(MAX_CHARS).collect();
+        format!("{cut}...")
+    } else {
+        text.to_string()
+    })
+}
+
 /// Result type alias using `CoreError`.
 pub type Result<T> = std::result::Result<T, CoreError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_service_error_bodies() {
+        let graph = ApiError::from_response(
+            "get user",
+            403,
+            r#"{"error":{"code":"Authorization_RequestDenied","message":"Insufficient privileges.","innerError":{"date":"2026-10-16T09:00:00","request-id":"8d1c-42","client-request-id":"8d1c-42"}}}"#,
+        );
+        assert_eq!(
+            graph.to_string(),
+            "get user failed: 403 Forbidden - Authorization_RequestDenied: Insufficient privileges. (request-id 8d1c-42)"
+        );
+        assert_eq!(CoreError::Api(graph).request_id(), Some("8d1c-42"));
+
+        let chat = ApiError::from_response(
+            "send message",
+            400,
+            r#"{"errorCode":209,"message":"Cannot send to this thread"}"#,
+        );
+        assert_eq!(chat.code.as_deref(), Some("209"));
+        assert_eq!(
+            chat.to_string(),
+            "send message failed: 400 Bad Request - 209: Cannot send to this thread"
+        );
+
+        let html = ApiError::from_response(
+            "list chats",
+            502,
+            "<html><head><title>502 Bad Gateway</title></head><body>...</body></html>",
+        );
+        assert_eq!(html.message.as_deref(), Some("502 Bad Gateway"));
+        assert_eq!(ApiError::from_response("authz", 500, "").message, None);
+        assert_eq!(
+            CoreError::api("no user 'x'").to_string(),
+            "API error: no user 'x'"
+        );
+    }
+}
@@ -0,0 +1,693 @@
+//! Export of cached conversations to Markdown, mail, or an image folder.
+//!
+//! Produces paste-ready notes: one `##` header per day, bold sender names,
+//! code blocks and links preserved, and attachments listed as links.
+//! [`messages_to_mbox`] and [`export_eml`] write messages as RFC 5322 mail
+//! (one message per Teams message, the conversation as subject) so mail
+//! archival and search tools such as notmuch and mu can index the history.
+//! [`export_images`] writes the images shared in a conversation to a
+//! folder, taking them from the image cache where possible.
+
+use crate::CoreError;
+use crate::cache::{Cache, CachedMessage, decode_entities, file_attachments};
+use crate::teams::client::TeamsClient;
+use base64::Engine as _;
+use chrono::{DateTime, TimeZone, Utc};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Render messages (oldest first) as a Markdown document titled `title`,
+/// with days and times shown in `tz`.
+#[must_use]
+pub fn messages_to_markdown<Tz: TimeZone>(
+    title: &str,
+    messages: &[CachedMessage],
+    tz: &Tz,
+) -> String {
+    let mut out = format!("# {title}\n");
+    let mut current_day = String::new();
+
+    for msg in messages {
+        let when = DateTime::parse_from_rfc3339(&msg.compose_time)
+            .ok()
+            .map(|t| t.with_timezone(tz).naive_local());
+        let day = when.map_or_else(
+            || msg.compose_time.get(..10).unwrap_or_default().to_string(),
+            |t| t.format("%Y-%m-%d (%A)").to_string(),
+        );
+        if day != current_day {
+            let _ = write!(out, "\n## {day}\n");
+            current_day = day;
+        }
+
+        if msg.is_system_event() {
+            let _ = write!(out, "\n_\u{2014} {} \u{2014}_\n", msg.content);
+            continue;
+        }
+
+        let sender = if msg.from_display_name.is_empty() {
+            "(system)"
+        } else {
+            msg.from_display_name.as_str()
+        };
+        let time = when.map_or_else(String::new, |t| t.format("%H:%M").to_string());
+        let _ = write!(out, "\n**{sender}** _{time}_\n\n");
+        if !msg.subject.is_empty() {
+            let mark = if msg.important { "\u{2757} " } else { "" };
+            let _ = write!(out, "### {mark}{}\n\n", msg.subject);
+        }
+
+        let body = html_to_markdown(&msg.content_html);
+        if !body.is_empty() {
+            out.push_str(&body);
+            out.push('\n');
+        }
+        for (name, url) in file_attachments(&msg.raw_json) {
+            let _ = writeln!(out, "\n- [{name}]({url})");
+        }
+    }
+    out
+}
+
+/// Domain of the made-up sender addresses and message IDs in mail exports.
+const MAIL_DOMAIN: &str = "teams.invalid";
+
+/// Render messages (oldest first) of the conversation `title` as an mbox
+/// (mboxrd) file. System events are left out.
+#[must_use]
+pub fn messages_to_mbox(title: &str, messages: &[CachedMessage]) -> String {
+    let mut out = String::new();
+    for msg in messages.iter().filter(|m| !m.is_system_event()) {
+        let when = compose_time(msg).unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "From {} {}",
+            sender_address(msg),
+            when.format("%a %b %e %H:%M:%S %Y")
+        );
+        for line in message_to_eml(title, msg).lines() {
+            // mboxrd: quote body lines that would read as a separator.
+            if line.trim_start_matches('>').starts_with("From ") {
+                out.push('>');
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Write each message (except system events) to `dir` as
+/// `<date>_<time>_<id>.eml`, returning how many were written.
+///
+/// # Errors
+///
+/// Returns an error if `dir` cannot be created or a file cannot be written.
+pub fn export_eml(title: &str, messages: &[CachedMessage], dir: &Path) -> Result<usize, CoreError> {
+    std::fs::create_dir_all(dir)?;
+    let mut written = 0;
+    for msg in messages.iter().filter(|m| !m.is_system_event()) {
+        let stamp = compose_time(msg).map_or_else(
+            || "unknown".to_string(),
+            |t| t.format("%Y-%m-%d_%H%M%S").to_string(),
+        );
+        let id: String = msg.id.chars().filter(char::is_ascii_alphanumeric).collect();
+        std::fs::write(
+            dir.join(format!("{stamp}_{id}.eml")),
+            message_to_eml(title, msg),
+        )?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// `msg` of the conversation `title` as an RFC 5322 message with a plain
+/// text (Markdown) body.
+///
+/// The sender address and `Message-ID` are derived from Teams IDs under
+/// the reserved `teams.invalid` domain, so exporting a conversation again
+/// yields the same IDs and archives can deduplicate.
+#[must_use]
+pub fn message_to_eml(title: &str, msg: &CachedMessage) -> String {
+    let sender = if msg.from_display_name.is_empty() {
+        "(system)"
+    } else {
+        msg.from_display_name.as_str()
+    };
+    let subject = if msg.subject.is_empty() {
+        title.to_string()
+    } else {
+        format!("{title}: {}", msg.subject)
+    };
+    let conversation = mail_id_part(&msg.conversation_id);
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "From: {} <{}>",
+        encode_header(&quote_name(sender)),
+        sender_address(msg)
+    );
+    if let Some(when) = compose_time(msg) {
+        let _ = writeln!(out, "Date: {}", when.to_rfc2822());
+    }
+    let _ = writeln!(out, "Subject: {}", encode_header(&subject));
+    let _ = writeln!(
+        out,
+        "Message-ID: <{}.{conversation}@{MAIL_DOMAIN}>",
+        mail_id_part(&msg.id)
+    );
+    // A shared reference threads a conversation in mail clients.
+    let _ = writeln!(out, "References: <{conversation}@{MAIL_DOMAIN}>");
+    let _ = writeln!(out, "X-Tmz-Conversation: {}", msg.conversation_id);
+    if msg.important {
+        out.push_str("Importance: high\n");
+    }
+    out.push_str("MIME-Version: 1.0\n");
+    out.push_str("Content-Type: text/plain; charset=utf-8\n");
+    out.push_str("Content-Transfer-Encoding: 8bit\n\n");
+
+    out.push_str(&html_to_markdown(&msg.content_html));
+    out.push('\n');
+    for (name, url) in file_attachments(&msg.raw_json) {
+        let _ = writeln!(out, "\n- {name}: {url}");
+    }
+    out
+}
+
+fn compose_time(msg: &CachedMessage) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&msg.compose_time)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Made-up address of the sender: the user ID from the message's `from`
+/// contact URL (`.../contacts/8:orgid:<id>`) at [`MAIL_DOMAIN`].
+fn sender_address(msg: &CachedMessage) -> String {
+    let from = serde_json::from_str::<serde_json::Value>(&msg.raw_json)
+        .ok()
+        .and_then(|raw| raw["from"].as_str().map(str::to_string))
+        .unwrap_or_default();
+    let mri = from.rsplit('/').next().unwrap_or_default();
+    let id = mri.rsplit(':').next().unwrap_or_default();
+    let local = mail_id_part(id);
+    let local = if local.is_empty() { "unknown" } else { &local };
+    format!("{local}@{MAIL_DOMAIN}")
+}
+
+/// `id` reduced to characters allowed in an address or message ID.
+fn mail_id_part(id: &str) -> String {
+    id.trim_start_matches("19:")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '.'
+            }
+        })
+        .collect::<String>()
+        .trim_matches('.')
+        .to_string()
+}
+
+/// Display name as a quoted string when it contains special characters.
+fn quote_name(name: &str) -> String {
+    if name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == ' ' || c == '-')
+    {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Header value, as RFC 2047 encoded words if it is not plain ASCII.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    // Keep encoded words under 76 characters without splitting characters.
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in value.chars() {
+        if chunk.len() + c.len_utf8() > 45 {
+            words.push(std::mem::take(&mut chunk));
+        }
+        chunk.push(c);
+    }
+    words.push(chunk);
+    words
+        .iter()
+        .map(|word| {
+            format!(
+                "=?utf-8?B?{}?=",
+                base64::engine::general_purpose::STANDARD.encode(word)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n ")
+}
+
+/// Counts from [`export_images`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ImageExport {
+    /// Images written to the folder.
+    pub written: usize,
+    /// Of those, images that had to be downloaded (and are now cached).
+    pub downloaded: usize,
+    /// Images skipped because the file already exists.
+    pub existing: usize,
+    /// Images that were not cached and could not be downloaded.
+    pub failed: usize,
+}
+
+/// Write the images shared in `messages` to `dir` as
+/// `<date>_<time>_<sender>_<n>.<ext>`, with times shown in `tz`.
+///
+/// Cached images are copied; others are downloaded with `client` (if
+/// given) and added to the cache. Files that already exist are skipped, so
+/// re-running an export only fetches what is new.
+///
+/// # Errors
+///
+/// Returns an error if `dir` cannot be created or a file cannot be written.
+pub async fn export_images<Tz: TimeZone + Sync>(
+    cache: &Cache,
+    client: Option<&TeamsClient>,
+    messages: &[CachedMessage],
+    dir: &Path,
+    tz: &Tz,
+) -> Result<ImageExport, CoreError> {
+    std::fs::create_dir_all(dir)?;
+    let mut report = ImageExport::default();
+    for msg in messages {
+        let urls = crate::kitty::extract_image_urls(&msg.content_html);
+        for (i, url) in urls.iter().enumerate() {
+            let stem = image_stem(msg, i + 1, tz);
+            if existing_image(dir, &stem).is_some() {
+                report.existing += 1;
+                continue;
+            }
+            let data = match cache.get_image(url).await? {
+                Some(data) => data,
+                None => match download(cache, client, url).await {
+                    Ok(data) => {
+                        report.downloaded += 1;
+                        data
+                    }
+                    Err(e) => {
+                        log::warn!("image {url}: {e}");
+                        report.failed += 1;
+                        continue;
+                    }
+                },
+            };
+            let path = dir.join(format!("{stem}.{}", image_extension(&data)));
+            std::fs::write(&path, &data)?;
+            report.written += 1;
+        }
+    }
+    Ok(report)
+}
+
+async fn download(
+    cache: &Cache,
+    client: Option<&TeamsClient>,
+    url: &str,
+) -> Result<Vec<u8>, CoreError> {
+    let client = client.ok_or_else(|| CoreError::Auth("not cached and not logged in".into()))?;
+    let data = client.download_image(url).await?;
+    let content_type = format!("image/{}", image_extension(&data));
+    if let Err(e) = cache.cache_image(url, &data, &content_type).await {
+        log::debug!("caching {url}: {e}");
+    }
+    Ok(data)
+}
+
+/// File name without extension for the `n`th image of `msg`.
+fn image_stem<Tz: TimeZone>(msg: &CachedMessage, n: usize, tz: &Tz) -> String {
+    let when = DateTime::parse_from_rfc3339(&msg.compose_time).map_or_else(
+        |_| {
+            msg.compose_time
+                .chars()
+                .filter(char::is_ascii_digit)
+                .collect()
+        },
+        |t| {
+            t.with_timezone(tz)
+                .naive_local()
+                .format("%Y-%m-%d_%H%M%S")
+                .to_string()
+        },
+    );
+    let mut sender = String::new();
+    for c in msg.from_display_name.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            sender.push(c);
+        } else if !sender.is_empty() && !sender.ends_with('-') {
+            sender.push('-');
+        }
+    }
+    let sender = sender.trim_end_matches('-');
+    let sender = if sender.is_empty() { "unknown" } else { sender };
+    format!("{when}_{sender}_{n}")
+}
+
+/// An already exported file for `stem`, whatever its extension.
+fn existing_image(dir: &Path, stem: &str) -> Option<PathBuf> {
+    ["jpg", "png", "gif", "webp"]
+        .iter()
+        .map(|ext| dir.join(format!("{stem}.{ext}")))
+        .find(|p| p.exists())
+}
+
+/// File extension for image bytes, by magic number (JPEG if unknown).
+fn image_extension(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x89PNG") {
+        "png"
+    } else if data.starts_with(b"GIF8") {
+        "gif"
+    } else if data.len() > 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        "webp"
+    } else {
+        "jpg"
+    }
+}
+
+/// Convert Teams message HTML to Markdown.
+///
+/// Keeps paragraphs, line breaks, bold/italic, inline code, `<pre>` blocks
+/// (as fenced code), links, images, and list items. Quoted reply context
+/// (`<blockquote>`) is dropped, as in [`crate::cache::strip_html`].
+#[must_use]
+pub fn html_to_markdown(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut pending_href: Vec<Option<String>> = Vec::new();
+    let mut in_pre = false;
+    let mut quote_depth = 0usize;
+
+    while let Some(lt) = rest.find('<') {
+        if quote_depth == 0 {
+            push_text(&mut out, &rest[..lt], in_pre);
+        }
+        let Some(gt) = rest[lt..].find('>') else {
+            rest = &rest[lt..];
+            break;
+        };
+        let tag = &rest[lt + 1..lt + gt];
+        rest = &rest[lt + gt + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        if name == "blockquote" {
+            quote_depth = if closing {
+                quote_depth.saturating_sub(1)
+            } else {
+                quote_depth + 1
+            };
+            continue;
+        }
+        if quote_depth > 0 {
+            continue;
+        }
+
+        match (name.as_str(), closing) {
+            ("br", _) | ("p" | "div" | "li", true) => out.push('\n'),
+            ("b" | "strong", _) => out.push_str("**"),
+            ("i" | "em", _) => out.push('_'),
+            ("s" | "strike" | "del", _) => out.push_str("~~"),
+            ("code", _) if !in_pre => out.push('`'),
+            ("pre", false) => {
+                in_pre = true;
+                ensure_newline(&mut out);
+                out.push_str("```\n");
+            }
+            ("pre", true) => {
+                in_pre = false;
+                ensure_newline(&mut out);
+                out.push_str("```\n");
+            }
+            ("li", false) => {
+                ensure_newline(&mut out);
+                out.push_str("- ");
+            }
+            ("a", false) => {
+                pending_href.push(attr(tag, "href"));
+                out.push('[');
+            }
+            ("a", true) => match pending_href.pop().flatten() {
+                Some(href) => {
+                    let _ = write!(out, "]({href})");
+                }
+                None => out.push(']'),
+            },
+            ("img", _) => {
+                if let Some(src) = attr(tag, "src") {
+                    let alt = attr(tag, "alt").unwrap_or_else(|| "image".to_string());
+                    let _ = write!(out, "![{alt}]({src})");
+                }
+            }
+            _ => {}
+        }
+    }
+    if quote_depth == 0 {
+        push_text(&mut out, rest, in_pre);
+    }
+
+    tidy(&out)
+}
+
+/// Append decoded text; outside `<pre>`, collapse whitespace like HTML does.
+fn push_text(out: &mut String, text: &str, in_pre: bool) {
+    let decoded = decode_entities(text);
+    if in_pre {
+        out.push_str(&decoded);
+        return;
+    }
+    let mut last_space = out.ends_with([' ', '\n']) || out.is_empty();
+    for ch in decoded.chars() {
+        if ch.is_whitespace() {
+            if !last_space {
+                out.push(' ');
+                last_space = true;
+            }
+        } else {
+            out.push(ch);
+            last_space = false;
+        }
+    }
+}
+
+fn ensure_newline(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Value of an HTML attribute in a raw tag (`a href="..."`).
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let needle = format!("{name}=");
+    let start = tag.find(&needle)? + needle.len();
+    let value = &tag[start..];
+    let quote = value.chars().next()?;
+    let value = if quote == '"' || quote == '\'' {
+        let inner = &value[1..];
+        &inner[..inner.find(quote)?]
+    } else {
+        value.split_whitespace().next()?
+    };
+    Some(decode_entities(value))
+}
+
+/// Trim trailing spaces and collapse runs of blank lines, leaving fenced
+/// code untouched.
+fn tidy(markdown: &str) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut in_fence = false;
+    let mut blank_run = 0;
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        let line = if in_fence { line } else { line.trim_end() };
+        if line.trim().is_empty() && !in_fence {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn converts_teams_html_to_markdown() {
+        let html = "<p>Hi <b>team</b>, see <a href=\"https://x.test/a?b=1&amp;c=2\">the doc</a></p>\
+                    <blockquote>old quote</blockquote>\
+                    <pre><code>fn main() {\n    run();\n}</code></pre>\
+                    <ul><li>one</li><li>two &amp; three</li></ul>";
+        assert_eq!(
+            html_to_markdown(html),
+            "Hi **team**, see [the doc](https://x.test/a?b=1&c=2)\n```\nfn main() {\n    run();\n}\n```\n- one\n- two & three"
+        );
+    }
+
+    /// A cache holding the first of two images in one message.
+    async fn images(dir: &TempDir) -> (Cache, CachedMessage) {
+        let blobs = crate::blobs::BlobStore::new(dir.join("attachments"));
+        let cache = Cache::open_with_blobs(&dir.join("cache.db"), blobs)
+            .await
+            .unwrap();
+        let img = |url: &str| {
+            format!("<img itemtype=\"http://schema.skype.com/AMSImage\" src=\"{url}\">")
+        };
+        let msg = CachedMessage {
+            id: "1".to_string(),
+            conversation_id: "19:x".to_string(),
+            from_display_name: "Ana María O'Neil".to_string(),
+            content: String::new(),
+            content_html: format!("{}{}", img("https://x.test/a"), img("https://x.test/b")),
+            message_type: "RichText/Html".to_string(),
+            compose_time: "2026-03-09T09:00:05.000Z".to_string(),
+            is_from_me: false,
+            mentions_me: false,
+            unknown_type: false,
+            subject: String::new(),
+            important: false,
+            raw_json: "{}".to_string(),
+        };
+        cache
+            .cache_image("https://x.test/a", b"\x89PNG\r\n", "image/png")
+            .await
+            .unwrap();
+        (cache, msg)
+    }
+
+    #[tokio::test]
+    async fn exports_cached_images_named_after_sender_and_time() {
+        let dir = TempDir::new("export-img").unwrap();
+        let (cache, msg) = images(&dir).await;
+        let out = dir.join("pics");
+        let report = export_images(&cache, None, &[msg], &out, &chrono::Utc)
+            .await
+            .unwrap();
+        assert_eq!((report.written, report.failed), (1, 1));
+        assert!(
+            out.join("2026-03-09_090005_ana-maría-o-neil_1.png")
+                .exists()
+        );
+    }
+
+    #[tokio::test]
+    async fn exports_each_image_once() {
+        let dir = TempDir::new("export-img-again").unwrap();
+        let (cache, msg) = images(&dir).await;
+        let out = dir.join("pics");
+        let msgs = [msg];
+        export_images(&cache, None, &msgs, &out, &chrono::Utc)
+            .await
+            .unwrap();
+        let again = export_images(&cache, None, &msgs, &out, &chrono::Utc)
+            .await
+            .unwrap();
+        assert_eq!((again.written, again.existing, again.failed), (0, 1, 1));
+    }
+
+    /// An important message from an `orgid` sender with a non-ASCII name.
+    fn release_note() -> CachedMessage {
+        CachedMessage {
+            id: "1773046805000".to_string(),
+            conversation_id: "19:abc@thread.v2".to_string(),
+            from_display_name: "Ana María".to_string(),
+            content: String::new(),
+            content_html: "<p>From now on, <b>ship</b> it</p>".to_string(),
+            message_type: "RichText/Html".to_string(),
+            compose_time: "2026-03-09T09:00:05.000Z".to_string(),
+            is_from_me: false,
+            mentions_me: false,
+            unknown_type: false,
+            subject: String::new(),
+            important: true,
+            raw_json: r#"{"from":"https://x.test/v1/users/ME/contacts/8:orgid:0a1b-2c"}"#
+                .to_string(),
+        }
+    }
+
+    #[test]
+    fn exports_messages_as_mail() {
+        assert_eq!(
+            message_to_eml("Release", &release_note()),
+            "From: =?utf-8?B?QW5hIE1hcsOtYQ==?= <0a1b-2c@teams.invalid>\n\
+             Date: Mon, 9 Mar 2026 09:00:05 +0000\n\
+             Subject: Release\n\
+             Message-ID: <1773046805000.abc.thread.v2@teams.invalid>\n\
+             References: <abc.thread.v2@teams.invalid>\n\
+             X-Tmz-Conversation: 19:abc@thread.v2\n\
+             Importance: high\n\
+             MIME-Version: 1.0\n\
+             Content-Type: text/plain; charset=utf-8\n\
+             Content-Transfer-Encoding: 8bit\n\
+             \n\
+             From now on, **ship** it\n"
+        );
+    }
+
+    #[test]
+    fn mbox_separates_messages_and_quotes_from_lines() {
+        let mbox = messages_to_mbox("Release", &[release_note()]);
+        assert!(mbox.starts_with("From 0a1b-2c@teams.invalid Mon Mar  9 09:00:05 2026\n"));
+        assert!(mbox.contains("\n>From now on"));
+    }
+
+    #[test]
+    fn groups_messages_by_day_with_attachments() {
+        let msg = |time: &str, from: &str, html: &str, raw: &str| CachedMessage {
+            id: time.to_string(),
+            conversation_id: "19:x".to_string(),
+            from_display_name: from.to_string(),
+            content: String::new(),
+            content_html: html.to_string(),
+            message_type: "RichText/Html".to_string(),
+            compose_time: time.to_string(),
+            is_from_me: false,
+            mentions_me: false,
+            unknown_type: false,
+            subject: String::new(),
+            important: false,
+            raw_json: raw.to_string(),
+        };
+        let files = r#"{"properties":{"files":"[{\"fileName\":\"plan.pdf\",\"objectUrl\":\"https://x.test/plan.pdf\"}]"}}"#;
+        let md = messages_to_markdown(
+            "Standup",
+            &[
+                msg("2026-03-09T09:00:00.000Z", "Ana", "<p>Morning</p>", "{}"),
+                msg("2026-03-10T09:05:00.000Z", "Ben", "<p>Plan</p>", files),
+            ],
+            &chrono::Utc,
+        );
+        assert_eq!(
+            md,
+            "# Standup\n\n## 2026-03-09 (Monday)\n\n**Ana** _09:00_\n\nMorning\n\n\
+             ## 2026-03-10 (Tuesday)\n\n**Ben** _09:05_\n\nPlan\n\n- [plan.pdf](https://x.test/plan.pdf)\n"
+        );
+    }
+}
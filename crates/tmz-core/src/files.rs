@@ -0,0 +1,219 @@
+//! Optional full-text indexing of files shared in chats.
+//!
+//! When `[files] index = true`, PDFs, Office documents, and text files
+//! attached to cached messages are downloaded into the attachment cache,
+//! their text is extracted with the configured commands (`pdftotext`,
+//! `pandoc`, ...), and stored in the cache's `file_text` index, so
+//! `tmz search --include-files` finds documents by their contents.
+
+use crate::CoreError;
+use crate::cache::{Cache, FileCandidate};
+use crate::config::FilesConfig;
+use crate::teams::api::TeamsApi;
+use std::path::Path;
+
+/// Extractor argument replaced with the path of the file.
+const PATH_ARG: &str = "{path}";
+
+/// Extract text from the file at `path`, treating it as type `ext`.
+///
+/// # Errors
+///
+/// Returns an error if there is no extractor for `ext`, or if the extractor
+/// cannot be run or fails.
+pub async fn extract_text(
+    config: &FilesConfig,
+    ext: &str,
+    path: &Path,
+) -> Result<String, CoreError> {
+    if config.text_extensions.iter().any(|t| t == ext) {
+        let bytes = tokio::fs::read(path).await?;
+        return Ok(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    let command = config
+        .extractors
+        .get(ext)
+        .filter(|c| !c.is_empty())
+        .ok_or_else(|| CoreError::Config(format!("no [files] extractor for .{ext} files")))?;
+    let path = path.display().to_string();
+    let args: Vec<String> = command[1..]
+        .iter()
+        .map(|arg| arg.replace(PATH_ARG, &path))
+        .collect();
+    let output = tokio::process::Command::new(&command[0])
+        .args(&args)
+        .output()
+        .await
+        .map_err(|e| CoreError::Other(format!("running {}: {e}", command[0])))?;
+    if !output.status.success() {
+        return Err(CoreError::Other(format!(
+            "{} failed on {path}: {}",
+            command[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Download and index up to `limit` shared files that have not been
+/// processed yet. Returns the number of files indexed.
+///
+/// Files already in the attachment cache are not downloaded again. Files
+/// over `max_file_mb` are recorded with no text so they are not retried;
+/// other failures are logged and retried on the next pass.
+///
+/// # Errors
+///
+/// Returns an error if the cache cannot be read or written, or if every
+/// file failed (usually a missing extractor or no Graph access).
+pub async fn index_pending(
+    cache: &Cache,
+    client: &impl TeamsApi,
+    config: &FilesConfig,
+    limit: usize,
+) -> Result<u64, CoreError> {
+    let pending = cache.pending_files(config, limit).await?;
+    let mut indexed = 0u64;
+    let mut last_error = None;
+    for file in &pending {
+        match index_file(cache, client, config, file).await {
+            Ok(()) => indexed += 1,
+            Err(e) => {
+                log::warn!("indexing {} failed: {e}", file.file_name);
+                last_error = Some(e);
+            }
+        }
+    }
+    match last_error {
+        Some(e) if indexed == 0 => Err(e),
+        _ => Ok(indexed),
+    }
+}
+
+async fn index_file(
+    cache: &Cache,
+    client: &impl TeamsApi,
+    config: &FilesConfig,
+    file: &FileCandidate,
+) -> Result<(), CoreError> {
+    let Some(ext) = config.indexable_extension(&file.file_name) else {
+        return Ok(());
+    };
+    if cache.image_path(&file.url).await?.is_none() {
+        let max_bytes = config.max_file_mb.saturating_mul(1024 * 1024);
+        let Some(data) = client.download_shared_file(&file.url, max_bytes).await? else {
+            log::info!(
+                "skipping {}: larger than {} MB",
+                file.file_name,
+                config.max_file_mb
+            );
+            return cache.store_file_text(file, "").await;
+        };
+        cache
+            .cache_attachment(&file.url, &data, "application/octet-stream")
+            .await?;
+    }
+    let path = cache
+        .image_path(&file.url)
+        .await?
+        .ok_or_else(|| CoreError::Other(format!("{} missing from cache", file.file_name)))?;
+    let text = extract_text(config, &ext, &path).await?;
+    cache.store_file_text(file, &normalize(&text)).await
+}
+
+/// Collapse layout whitespace into single-spaced text.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[cfg(unix)]
+    const BUDGET: &str = "https://x.sharepoint.test/Shared%20Documents/budget.pdf";
+
+    /// A cache with one message sharing a PDF and a HEIC photo, and files
+    /// settings that extract PDFs with a fixed script.
+    async fn shared_files(dir: &TempDir) -> (Cache, FilesConfig) {
+        let blobs = crate::blobs::BlobStore::new(dir.join("attachments"));
+        let cache = Cache::open_with_blobs(&dir.join("cache.db"), blobs)
+            .await
+            .unwrap();
+        let raw = serde_json::json!({
+            "id": "1",
+            "messagetype": "RichText/Html",
+            "content": "<p>numbers attached</p>",
+            "composetime": "2026-01-01T09:00:00Z",
+            "properties": {
+                "files": format!("[{{\"fileName\":\"budget.pdf\",\"objectUrl\":\"{BUDGET}\"}},\
+                                  {{\"fileName\":\"photo.heic\",\"objectUrl\":\"https://x.test/p\"}}]"),
+            },
+        });
+        let msg = crate::cache::parse_message(
+            &raw.into(),
+            "19:x",
+            None,
+            &crate::MessagesConfig::default(),
+        )
+        .unwrap();
+        cache.upsert_message(&msg).await.unwrap();
+
+        let config = FilesConfig {
+            index: true,
+            extractors: [(
+                "pdf".to_string(),
+                vec![
+                    "sh".into(),
+                    "-c".into(),
+                    "echo 'Q3 forecast:\n  travel  budget'".into(),
+                ],
+            )]
+            .into(),
+            ..FilesConfig::default()
+        };
+        (cache, config)
+    }
+
+    #[tokio::test]
+    async fn only_indexable_files_are_pending() {
+        let dir = TempDir::new("files").unwrap();
+        let (cache, config) = shared_files(&dir).await;
+        let pending = cache.pending_files(&config, 10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].file_name, "budget.pdf");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn extracted_text_is_searchable() {
+        let dir = TempDir::new("files-search").unwrap();
+        let (cache, config) = shared_files(&dir).await;
+        let pending = cache.pending_files(&config, 10).await.unwrap();
+        cache
+            .cache_attachment(BUDGET, b"%PDF", "application/pdf")
+            .await
+            .unwrap();
+        let path = cache.image_path(BUDGET).await.unwrap().unwrap();
+        let text = extract_text(&config, "pdf", &path).await.unwrap();
+        cache
+            .store_file_text(&pending[0], &normalize(&text))
+            .await
+            .unwrap();
+        assert!(cache.pending_files(&config, 10).await.unwrap().is_empty());
+
+        let found = cache.search_files("travel", None, 10).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].message.id, "1");
+        assert_eq!(found[0].snippet, "Q3 forecast: [travel] budget");
+        assert_eq!(
+            cache
+                .search_files("file_name:budget", None, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! Inline images in the terminal.
+//!
+//! Three protocols are supported: Kitty graphics (Kitty, `WezTerm`,
+//! Ghostty), iTerm2 inline images (iTerm2, `WezTerm`, mintty), and Sixel
+//! (foot, mlterm, Konsole, Windows Terminal 1.22+, and tmux built with
+//! sixel support). `[display] images` forces one or turns images off; with
+//! `auto`, [`detect`] asks the terminal: a Kitty graphics query, `XTGETTCAP`
+//! and `XTVERSION` for its name, and `DA1`, whose answer lists Sixel support
+//! and ends the probe. Terminals that do not answer in time, and Windows
+//! consoles, fall back to environment variables.
+//!
+//! Inside tmux, queries and Kitty or iTerm2 images go through DCS
+//! passthrough (`set -g allow-passthrough on`); Sixel is drawn by tmux
+//! itself.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use crate::config::ImageMode;
+
+/// How long to wait for the terminal to answer the probe.
+#[cfg(unix)]
+const PROBE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Extra wait in tmux after `DA1`, which tmux answers before the outer
+/// terminal's replies come through.
+#[cfg(unix)]
+const TMUX_GRACE: Duration = Duration::from_millis(50);
+
+/// Largest width and height of a Sixel image, in pixels.
+const SIXEL_MAX_PX: u32 = 480;
+
+/// Width of iTerm2 images, in terminal columns (like the Kitty renderer).
+const ITERM_COLS: u32 = 60;
+
+/// Kitty graphics query for a 1x1 image; supporting terminals answer
+/// `_Gi=31;OK`.
+const KITTY_QUERY: &str = "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\";
+
+/// `XTGETTCAP` for the terminal name (`TN`, hex encoded).
+const XTGETTCAP_NAME: &str = "\x1bP+q544e\x1b\\";
+
+/// `XTVERSION`, answered with the terminal's name and version.
+const XTVERSION: &str = "\x1b[>0q";
+
+/// Primary device attributes; every terminal answers it.
+const DA1: &str = "\x1b[c";
+
+/// A terminal image protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageProtocol {
+    /// Kitty graphics protocol.
+    Kitty,
+    /// Sixel graphics.
+    Sixel,
+    /// iTerm2 inline images.
+    Iterm,
+}
+
+/// The protocol to show images with, or `None` when images are off or the
+/// terminal supports none. `auto` probes the terminal once per process.
+#[must_use]
+pub fn detect(mode: ImageMode) -> Option<ImageProtocol> {
+    static AUTO: OnceLock<Option<ImageProtocol>> = OnceLock::new();
+    match mode {
+        ImageMode::Off => None,
+        ImageMode::Kitty => Some(ImageProtocol::Kitty),
+        ImageMode::Sixel => Some(ImageProtocol::Sixel),
+        ImageMode::Iterm => Some(ImageProtocol::Iterm),
+        ImageMode::Auto => *AUTO.get_or_init(|| {
+            if !io::IsTerminal::is_terminal(&io::stdout()) {
+                return None;
+            }
+            let probed = probe().and_then(|replies| replies.protocol());
+            log::debug!("terminal image probe: {probed:?}");
+            probed.or_else(from_env)
+        }),
+    }
+}
+
+/// Show an image (PNG, JPEG, GIF, or `WebP`) below the cursor.
+///
+/// # Errors
+///
+/// Returns an error if the image cannot be decoded (Sixel only) or stdout
+/// cannot be written to.
+pub fn display_image(protocol: ImageProtocol, data: &[u8]) -> io::Result<()> {
+    if data.is_empty() {
+        return Ok(());
+    }
+    match protocol {
+        ImageProtocol::Kitty => crate::kitty::display_image(data),
+        ImageProtocol::Iterm => {
+            let mut stdout = io::stdout().lock();
+            write!(stdout, "{}", passthrough(&iterm_escape(data)))?;
+            writeln!(stdout)?;
+            stdout.flush()
+        }
+        ImageProtocol::Sixel => {
+            let image = image::load_from_memory(data).map_err(io::Error::other)?;
+            let mut stdout = io::stdout().lock();
+            write!(stdout, "{}", sixel_escape(&image))?;
+            writeln!(stdout)?;
+            stdout.flush()
+        }
+    }
+}
+
+/// What the terminal answered to the probe.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Replies {
+    kitty: bool,
+    sixel: bool,
+    name: Option<String>,
+}
+
+impl Replies {
+    /// Parse the bytes the terminal sent back.
+    fn parse(bytes: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(bytes);
+        let kitty = text.contains("\x1b_Gi=31;OK");
+        let sixel = text
+            .split("\x1b[?")
+            .skip(1)
+            .filter_map(|reply| reply.split_once('c'))
+            .any(|(attrs, _)| attrs.split(';').any(|a| a == "4"));
+        let tcap = text
+            .split_once("\x1bP1+r544e=")
+            .or_else(|| text.split_once("\x1bP1+r544E="))
+            .and_then(|(_, rest)| rest.split_once('\x1b'))
+            .and_then(|(hex, _)| decode_hex(hex));
+        let version = text
+            .split_once("\x1bP>|")
+            .and_then(|(_, rest)| rest.split_once('\x1b'))
+            .map(|(name, _)| name.to_string());
+        Self {
+            kitty,
+            sixel,
+            name: tcap.or(version),
+        }
+    }
+
+    fn protocol(&self) -> Option<ImageProtocol> {
+        let name = self.name.as_deref().unwrap_or("").to_lowercase();
+        if self.kitty {
+            Some(ImageProtocol::Kitty)
+        } else if name.contains("iterm") || name.contains("mintty") {
+            Some(ImageProtocol::Iterm)
+        } else if self.sixel {
+            Some(ImageProtocol::Sixel)
+        } else if name.contains("wezterm") {
+            Some(ImageProtocol::Iterm)
+        } else {
+            None
+        }
+    }
+}
+
+/// Guess the protocol from environment variables.
+fn from_env() -> Option<ImageProtocol> {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+    let term = var("TERM");
+    let program = var("TERM_PROGRAM");
+    if crate::kitty::is_supported() {
+        Some(ImageProtocol::Kitty)
+    } else if program == "iTerm.app" || program == "mintty" || var("LC_TERMINAL") == "iTerm2" {
+        Some(ImageProtocol::Iterm)
+    } else if std::env::var_os("WT_SESSION").is_some()
+        || term.contains("foot")
+        || term.contains("mlterm")
+        || std::env::var_os("KONSOLE_VERSION").is_some()
+    {
+        Some(ImageProtocol::Sixel)
+    } else {
+        None
+    }
+}
+
+/// Send the queries to the controlling terminal and collect the replies
+/// until `DA1` is answered or the timeout passes.
+#[cfg(unix)]
+fn probe() -> Option<Replies> {
+    use rustix::termios::{OptionalActions, tcgetattr, tcsetattr};
+
+    let tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let original = tcgetattr(&tty).ok()?;
+    let mut raw = original.clone();
+    raw.make_raw();
+    tcsetattr(&tty, OptionalActions::Now, &raw).ok()?;
+    let replies = query(&tty);
+    if let Err(e) = tcsetattr(&tty, OptionalActions::Now, &original) {
+        log::warn!("restoring terminal mode after image probe: {e}");
+    }
+    replies.ok().map(|bytes| Replies::parse(&bytes))
+}
+
+#[cfg(not(unix))]
+const fn probe() -> Option<Replies> {
+    None
+}
+
+#[cfg(unix)]
+fn query(tty: &std::fs::File) -> io::Result<Vec<u8>> {
+    use rustix::event::{PollFd, PollFlags, Timespec, poll};
+    use std::io::Read as _;
+    use std::time::Instant;
+
+    let mut writer = tty;
+    write!(
+        writer,
+        "{}{}{}{DA1}",
+        passthrough(KITTY_QUERY),
+        passthrough(XTGETTCAP_NAME),
+        passthrough(XTVERSION)
+    )?;
+    writer.flush()?;
+
+    let mut deadline = Instant::now() + PROBE_TIMEOUT;
+    let mut replies = Vec::new();
+    let mut answered = false;
+    let mut chunk = [0u8; 256];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        let timeout = Timespec::try_from(left).map_err(io::Error::other)?;
+        let mut fds = [PollFd::new(tty, PollFlags::IN)];
+        if poll(&mut fds, Some(&timeout))? == 0 {
+            break;
+        }
+        let mut reader = tty;
+        let n = reader.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        replies.extend_from_slice(&chunk[..n]);
+        if !answered && ends_with_da1(&replies) {
+            answered = true;
+            if std::env::var_os("TMUX").is_none() {
+                break;
+            }
+            deadline = Instant::now() + TMUX_GRACE;
+        }
+    }
+    Ok(replies)
+}
+
+/// Whether `bytes` contain a complete `DA1` answer (`ESC [ ? ... c`).
+#[cfg(unix)]
+fn ends_with_da1(bytes: &[u8]) -> bool {
+    String::from_utf8_lossy(bytes)
+        .split("\x1b[?")
+        .skip(1)
+        .any(|reply| {
+            reply
+                .split_once('c')
+                .is_some_and(|(attrs, _)| attrs.bytes().all(|b| b.is_ascii_digit() || b == b';'))
+        })
+}
+
+/// Wrap an escape sequence in tmux DCS passthrough when inside tmux.
+fn passthrough(seq: &str) -> String {
+    if std::env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b"))
+    } else {
+        seq.to_string()
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<String> {
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// iTerm2 inline image escape for `data`.
+fn iterm_escape(data: &[u8]) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={ITERM_COLS};preserveAspectRatio=1:{}\x07",
+        data.len(),
+        BASE64.encode(data)
+    )
+}
+
+/// Sixel escape for `image`, scaled down to [`SIXEL_MAX_PX`] and reduced to
+/// a 6x6x6 color cube. Transparent pixels are left unpainted.
+fn sixel_escape(image: &image::DynamicImage) -> String {
+    let image = if image.width() > SIXEL_MAX_PX || image.height() > SIXEL_MAX_PX {
+        image.thumbnail(SIXEL_MAX_PX, SIXEL_MAX_PX)
+    } else {
+        image.clone()
+    };
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let color = |x: u32, y: u32| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        (a >= 128).then(|| cube_index(r) * 36 + cube_index(g) * 6 + cube_index(b))
+    };
+
+    let mut out = format!("\x1bP0;1;0q\"1;1;{width};{height}");
+    for index in 0..216u8 {
+        let level = |i: u8| u32::from(i) * 100 / 5;
+        let _ = write!(
+            out,
+            "#{index};2;{};{};{}",
+            level(index / 36),
+            level(index / 6 % 6),
+            level(index % 6)
+        );
+    }
+    for band in (0..height).step_by(6) {
+        let rows = band..(band + 6).min(height);
+        let mut used = [false; 216];
+        for y in rows.clone() {
+            for x in 0..width {
+                if let Some(c) = color(x, y) {
+                    used[usize::from(c)] = true;
+                }
+            }
+        }
+        for (index, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+            let sixels: Vec<u8> = (0..width)
+                .map(|x| {
+                    rows.clone().fold(0u8, |bits, y| {
+                        if color(x, y).map(usize::from) == Some(index) {
+                            bits | 1 << (y - band)
+                        } else {
+                            bits
+                        }
+                    })
+                })
+                .collect();
+            let _ = write!(out, "#{index}");
+            push_run_length(&mut out, &sixels);
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+/// Nearest of the six levels of the color cube.
+fn cube_index(value: u8) -> u8 {
+    ((u16::from(value) * 5 + 127) / 255) as u8
+}
+
+/// Append sixel characters for `bits`, run-length encoding repeats.
+fn push_run_length(out: &mut String, bits: &[u8]) {
+    let mut rest = bits;
+    while let Some(&first) = rest.first() {
+        let run = rest.iter().take_while(|&&b| b == first).count();
+        let ch = char::from(0x3f + first);
+        if run > 3 {
+            let _ = write!(out, "!{run}{ch}");
+        } else {
+            out.extend(std::iter::repeat_n(ch, run));
+        }
+        rest = &rest[run..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kitty_is_detected_from_its_graphics_reply() {
+        let kitty = Replies::parse(
+            b"\x1b_Gi=31;OK\x1b\\\x1bP1+r544e=787465726d2d6b69747479\x1b\\\x1b[?62;22c",
+        );
+        assert_eq!(kitty.name.as_deref(), Some("xterm-kitty"));
+        assert_eq!(kitty.protocol(), Some(ImageProtocol::Kitty));
+    }
+
+    #[test]
+    fn sixel_is_detected_from_device_attributes() {
+        let foot = Replies::parse(b"\x1bP0+r\x1b\\\x1b[?62;4;22c");
+        assert!(foot.sixel && !foot.kitty);
+        assert_eq!(foot.protocol(), Some(ImageProtocol::Sixel));
+    }
+
+    #[test]
+    fn iterm_is_detected_from_its_version_reply() {
+        let iterm = Replies::parse(b"\x1bP>|iTerm2 3.5.0\x1b\\\x1b[?62;4c");
+        assert_eq!(iterm.protocol(), Some(ImageProtocol::Iterm));
+    }
+
+    #[test]
+    fn plain_terminals_get_no_protocol() {
+        assert_eq!(Replies::parse(b"\x1b[?1;2c").protocol(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn probing_stops_at_the_device_attributes_reply() {
+        assert!(ends_with_da1(b"\x1b_Gi=31;OK\x1b\\\x1b[?62;4c"));
+        assert!(!ends_with_da1(b"\x1b[?62;4"));
+    }
+
+    #[test]
+    fn sixel_encodes_columns_of_six_pixels() {
+        let mut image = image::RgbaImage::new(4, 7);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            *pixel = if x < 2 && y < 6 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 0, 0])
+            };
+        }
+        let sixel = sixel_escape(&image::DynamicImage::ImageRgba8(image));
+        assert!(sixel.starts_with("\x1bP0;1;0q\"1;1;4;7#0;2;0;0;0"));
+        // Red is cube index 180; two full columns, then two empty ones.
+        assert!(sixel.ends_with("#180~~??$--\x1b\\"));
+    }
+
+    #[test]
+    fn sixel_runs_are_length_encoded() {
+        let mut out = String::new();
+        push_run_length(&mut out, &[1, 1, 1, 1, 1, 2]);
+        assert_eq!(out, "!5@A");
+    }
+}
@@ -0,0 +1,254 @@
+//! Quick checks of what a frontend needs to work: valid tokens, a running
+//! daemon that reaches Teams, and a cache that is not empty or stale.
+//!
+//! [`check`] gathers the facts (stored tokens, the daemon's PID file and
+//! engine status, the newest cached activity) without network requests, so
+//! it is cheap enough to run on every start. [`assess`] turns them into
+//! [`Problem`]s, most severe first, which the TUI shows as banners with the
+//! key that fixes each one.
+
+use chrono::{DateTime, Utc};
+
+use crate::cache::Cache;
+use crate::live::ConnectionStats;
+
+/// Without new activity for this long, and no daemon keeping it current,
+/// the cache counts as stale.
+pub const STALE_CACHE_AFTER: chrono::Duration = chrono::Duration::hours(24);
+
+/// Something that keeps tmz from showing current data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// No tokens are stored.
+    NotLoggedIn,
+    /// The stored tokens expired.
+    TokensExpired,
+    /// The daemon's headless refresh failed (see
+    /// [`crate::daemon::check_reauth_needed`]).
+    ReauthNeeded(String),
+    /// The daemon is not running, so nothing syncs in the background.
+    DaemonNotRunning,
+    /// The daemon runs but cannot reach Teams, since `since` (Unix seconds).
+    DaemonOffline {
+        /// Start of the outage.
+        since: Option<i64>,
+    },
+    /// Nothing has been synced yet.
+    CacheEmpty,
+    /// The newest cached activity is older than [`STALE_CACHE_AFTER`].
+    CacheStale {
+        /// Age of the newest cached activity.
+        age: chrono::Duration,
+    },
+}
+
+impl Problem {
+    /// Whether logging in again fixes this problem.
+    #[must_use]
+    pub const fn needs_login(&self) -> bool {
+        matches!(
+            self,
+            Self::NotLoggedIn | Self::TokensExpired | Self::ReauthNeeded(_)
+        )
+    }
+
+    /// Short description, such as `tokens expired`.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match self {
+            Self::NotLoggedIn => "not logged in".to_string(),
+            Self::TokensExpired => "tokens expired".to_string(),
+            Self::ReauthNeeded(_) => "session expired, token refresh failed".to_string(),
+            Self::DaemonNotRunning => {
+                "daemon not running, nothing syncs in the background".to_string()
+            }
+            Self::DaemonOffline { since } => since
+                .and_then(|s| DateTime::from_timestamp(s, 0))
+                .map_or_else(
+                    || "daemon cannot reach Teams".to_string(),
+                    |since| {
+                        format!(
+                            "daemon cannot reach Teams since {}",
+                            ago(Utc::now() - since)
+                        )
+                    },
+                ),
+            Self::CacheEmpty => "cache is empty".to_string(),
+            Self::CacheStale { age } => format!("cache last updated {}", ago(*age)),
+        }
+    }
+}
+
+/// `5m ago`, `3h ago`, `2d ago`.
+fn ago(age: chrono::Duration) -> String {
+    if age.num_days() > 0 {
+        format!("{}d ago", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{}h ago", age.num_hours())
+    } else {
+        format!("{}m ago", age.num_minutes().max(0))
+    }
+}
+
+/// What [`assess`] looks at.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Facts {
+    /// Expiry of the stored tokens (Unix seconds), if any are stored.
+    pub token_expires_at: Option<i64>,
+    /// Why the daemon asks for a new login, if it does.
+    pub reauth_reason: Option<String>,
+    /// Whether the daemon process is alive.
+    pub daemon_running: bool,
+    /// The daemon's link to Teams, if its engine answered.
+    pub connection: Option<ConnectionStats>,
+    /// Whether the cache holds any conversation.
+    pub cache_has_data: bool,
+    /// Newest activity in the cache.
+    pub newest_activity: Option<DateTime<Utc>>,
+}
+
+/// Problems shown by `facts` at `now`, most severe first.
+#[must_use]
+pub fn assess(facts: &Facts, now: DateTime<Utc>) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    match facts.token_expires_at {
+        None => problems.push(Problem::NotLoggedIn),
+        Some(at) if at <= now.timestamp() => problems.push(Problem::TokensExpired),
+        Some(_) => {
+            if let Some(ref reason) = facts.reauth_reason {
+                problems.push(Problem::ReauthNeeded(reason.clone()));
+            }
+        }
+    }
+    let daemon_online = match facts.connection {
+        _ if !facts.daemon_running => {
+            problems.push(Problem::DaemonNotRunning);
+            false
+        }
+        Some(stats) if !stats.connected && stats.down_since.is_some() => {
+            problems.push(Problem::DaemonOffline {
+                since: stats.down_since,
+            });
+            false
+        }
+        _ => true,
+    };
+    if !facts.cache_has_data {
+        problems.push(Problem::CacheEmpty);
+    } else if !daemon_online
+        && let Some(newest) = facts.newest_activity
+        && now - newest > STALE_CACHE_AFTER
+    {
+        problems.push(Problem::CacheStale { age: now - newest });
+    }
+    problems
+}
+
+/// Gather the [`Facts`] about this machine, reading `cache` if it is open.
+pub async fn check(cache: Option<&Cache>) -> Facts {
+    let token_expires_at = crate::AuthManager::new()
+        .ok()
+        .and_then(|auth| auth.stored_tokens().ok())
+        .map(|tokens| tokens.expires_at);
+    let connection = match crate::EngineClient::connect().await {
+        Some(mut client) => client.status().await.ok().map(|status| status.connection),
+        None => None,
+    };
+    let newest = match cache {
+        Some(cache) => cache
+            .list_conversation_summaries(1)
+            .await
+            .ok()
+            .and_then(|convs| convs.into_iter().next()),
+        None => None,
+    };
+    Facts {
+        token_expires_at,
+        reauth_reason: crate::daemon::check_reauth_needed(),
+        daemon_running: connection.is_some() || crate::daemon::is_running().unwrap_or(false),
+        connection,
+        cache_has_data: newest.is_some(),
+        newest_activity: newest
+            .and_then(|conv| DateTime::parse_from_rfc3339(&conv.last_activity).ok())
+            .map(|at| at.to_utc()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-05-01T09:00:00Z")
+            .unwrap()
+            .to_utc()
+    }
+
+    /// Valid tokens, a connected daemon, and a cache last active 3 days ago.
+    fn healthy() -> Facts {
+        Facts {
+            token_expires_at: Some(now().timestamp() + 3600),
+            reauth_reason: None,
+            daemon_running: true,
+            connection: Some(ConnectionStats {
+                connected: true,
+                ..ConnectionStats::default()
+            }),
+            cache_has_data: true,
+            newest_activity: Some(now() - chrono::Duration::days(3)),
+        }
+    }
+
+    #[test]
+    fn a_running_daemon_keeps_quiet_caches_current() {
+        assert!(assess(&healthy(), now()).is_empty());
+    }
+
+    #[test]
+    fn problems_are_ordered_most_severe_first() {
+        let stopped = Facts {
+            token_expires_at: Some(now().timestamp() - 1),
+            daemon_running: false,
+            connection: None,
+            ..healthy()
+        };
+        let problems = assess(&stopped, now());
+        assert_eq!(
+            problems,
+            [
+                Problem::TokensExpired,
+                Problem::DaemonNotRunning,
+                Problem::CacheStale {
+                    age: chrono::Duration::days(3)
+                },
+            ]
+        );
+        assert!(problems[0].needs_login());
+        assert_eq!(problems[2].describe(), "cache last updated 3d ago");
+    }
+
+    #[test]
+    fn missing_login_offline_daemon_and_empty_cache_are_reported() {
+        let offline = Facts {
+            token_expires_at: None,
+            connection: Some(ConnectionStats {
+                connected: false,
+                down_since: Some(now().timestamp() - 600),
+                ..ConnectionStats::default()
+            }),
+            cache_has_data: false,
+            newest_activity: None,
+            ..healthy()
+        };
+        assert_eq!(
+            assess(&offline, now()),
+            [
+                Problem::NotLoggedIn,
+                Problem::DaemonOffline {
+                    since: Some(now().timestamp() - 600)
+                },
+                Problem::CacheEmpty,
+            ]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Per-conversation webhooks for incoming messages.
+//!
+//! A conversation with a `[hooks.conversations."<id>"]` entry has every new
+//! message that sync finds posted to the hook's URL as JSON, so a channel
+//! can feed a ticketing or chat-ops system. Messages already in the cache
+//! are never posted again, and a conversation's first sync only fills the
+//! cache, so turning on a hook does not replay the history.
+//!
+//! With a secret (the hook's `secret` or `[hooks] secret_env`), each
+//! request carries `X-Tmz-Signature: sha256=<hex>`, an HMAC-SHA256 of the
+//! body, which receivers check before trusting the payload. Failed
+//! deliveries are retried with exponential backoff up to `[hooks] attempts`
+//! times and then dropped with a warning.
+
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use reqwest::StatusCode;
+use serde_json::json;
+use sha2::Sha256;
+
+use crate::CoreError;
+use crate::cache::CachedMessage;
+use crate::config::{ConversationHook, HooksConfig};
+use crate::trace::TracedSend;
+
+/// Header carrying the body's HMAC-SHA256 signature.
+pub const SIGNATURE_HEADER: &str = "X-Tmz-Signature";
+
+/// Header naming the event (`message`).
+pub const EVENT_HEADER: &str = "X-Tmz-Event";
+
+/// Delay before the first retry; doubled for each further one.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// A new message waiting to be posted to its conversation's webhook.
+#[derive(Debug, Clone)]
+pub struct PendingHook {
+    /// Display name of the conversation.
+    pub conversation_name: String,
+    /// The message.
+    pub message: CachedMessage,
+}
+
+impl PendingHook {
+    /// `message` of the conversation named `conversation_name`.
+    #[must_use]
+    pub fn new(conversation_name: &str, message: &CachedMessage) -> Self {
+        Self {
+            conversation_name: conversation_name.to_string(),
+            message: message.clone(),
+        }
+    }
+}
+
+/// JSON body posted for `message` in the conversation `conversation_name`.
+#[must_use]
+pub fn payload(conversation_name: &str, message: &CachedMessage) -> serde_json::Value {
+    json!({
+        "event": "message",
+        "conversation": {
+            "id": message.conversation_id,
+            "name": conversation_name,
+        },
+        "message": {
+            "id": message.id,
+            "from": message.from_display_name,
+            "content": message.content,
+            "content_html": message.content_html,
+            "compose_time": message.compose_time,
+            "subject": message.subject,
+            "important": message.important,
+            "mentions_me": message.mentions_me,
+            "is_from_me": message.is_from_me,
+        },
+    })
+}
+
+/// `sha256=<hex>` HMAC-SHA256 signature of `body` keyed by `secret`.
+///
+/// # Errors
+///
+/// Returns an error if the key cannot be used for HMAC.
+pub fn sign(secret: &str, body: &[u8]) -> Result<String, CoreError> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| CoreError::Config(format!("hook signing secret: {e}")))?;
+    mac.update(body);
+    Ok(format!(
+        "sha256={}",
+        crate::secrets::hex(&mac.finalize().into_bytes())
+    ))
+}
+
+/// Whether a response with `status` is worth retrying.
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// POST `body` to `hook`, retrying failures as configured.
+///
+/// # Errors
+///
+/// Returns an error if the last attempt fails or the hook rejects the
+/// request with a status that is not retried.
+pub async fn deliver(
+    config: &HooksConfig,
+    hook: &ConversationHook,
+    body: String,
+) -> Result<(), CoreError> {
+    let client = crate::network::client_builder()
+        .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+        .build()
+        .map_err(|e| CoreError::Other(format!("building webhook client: {e}")))?;
+    let signature = config
+        .secret(hook)
+        .map(|secret| sign(&secret, body.as_bytes()))
+        .transpose()?;
+
+    let attempts = config.attempts.max(1);
+    let mut delay = FIRST_RETRY_DELAY;
+    for attempt in 1..=attempts {
+        let mut request = client
+            .post(&hook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, "message")
+            .body(body.clone());
+        if let Some(ref signature) = signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        let error = match request.send_traced().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) if !is_retryable(response.status()) => {
+                return Err(CoreError::api(format!(
+                    "webhook {} answered {}",
+                    hook.url,
+                    response.status()
+                )));
+            }
+            Ok(response) => format!("answered {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        if attempt == attempts {
+            return Err(CoreError::api(format!(
+                "webhook {} failed after {attempts} attempts: {error}",
+                hook.url
+            )));
+        }
+        log::debug!(
+            "webhook {} attempt {attempt} failed ({error}), retrying in {delay:?}",
+            hook.url
+        );
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads_are_signed_with_hmac_sha256() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog").unwrap(),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried() {
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn payloads_carry_subject_and_importance_but_not_raw_json() {
+        let message = CachedMessage {
+            id: "1700000000000".to_string(),
+            conversation_id: "19:incidents@thread.tacv2".to_string(),
+            from_display_name: "Alex".to_string(),
+            content: "db down".to_string(),
+            content_html: "<p>db down</p>".to_string(),
+            message_type: "RichText/Html".to_string(),
+            compose_time: "2024-05-01T10:00:00Z".to_string(),
+            is_from_me: false,
+            mentions_me: false,
+            unknown_type: false,
+            subject: "Outage".to_string(),
+            important: true,
+            raw_json: "{}".to_string(),
+        };
+        let body = payload("Incidents", &message);
+        assert_eq!(body["conversation"]["name"], "Incidents");
+        assert_eq!(body["message"]["subject"], "Outage");
+        assert_eq!(body["message"]["important"], true);
+        assert!(body["message"].get("raw_json").is_none());
+    }
+
+    #[test]
+    fn a_hook_secret_overrides_the_shared_one() {
+        let hook = ConversationHook {
+            url: "https://example.invalid/hook".to_string(),
+            secret: Some("own".to_string()),
+            include_own: false,
+        };
+        assert_eq!(HooksConfig::default().secret(&hook).as_deref(), Some("own"));
+    }
+}
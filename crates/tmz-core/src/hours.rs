@@ -0,0 +1,440 @@
+//! Recipients' working hours, for out-of-hours send warnings.
+//!
+//! Before a 1:1 message goes out, frontends look up the other person's
+//! Outlook working hours through Graph (`mailboxSettings/workingHours`),
+//! falling back to `[sending.working_hours]` when the tenant does not let
+//! the Teams token read them. `[sending] off_hours` decides whether a send
+//! outside those hours goes through, is warned about, needs confirmation,
+//! or is refused. The suggested alternative is `tmz msg --schedule`, which
+//! holds the message in the outbox until their day starts.
+
+use crate::CoreError;
+use crate::config::{OffHoursAction, SendingConfig, WorkingHoursConfig};
+use crate::teams::TeamsClient;
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+
+/// Outlook's Windows time zone names and their IANA equivalents.
+const WINDOWS_ZONES: &[(&str, &str)] = &[
+    ("UTC", "UTC"),
+    ("GMT Standard Time", "Europe/London"),
+    ("Greenwich Standard Time", "Atlantic/Reykjavik"),
+    ("W. Europe Standard Time", "Europe/Berlin"),
+    ("Romance Standard Time", "Europe/Paris"),
+    ("Central Europe Standard Time", "Europe/Budapest"),
+    ("Central European Standard Time", "Europe/Warsaw"),
+    ("E. Europe Standard Time", "Europe/Chisinau"),
+    ("FLE Standard Time", "Europe/Kyiv"),
+    ("GTB Standard Time", "Europe/Bucharest"),
+    ("Turkey Standard Time", "Europe/Istanbul"),
+    ("Russian Standard Time", "Europe/Moscow"),
+    ("Israel Standard Time", "Asia/Jerusalem"),
+    ("South Africa Standard Time", "Africa/Johannesburg"),
+    ("Arabian Standard Time", "Asia/Dubai"),
+    ("India Standard Time", "Asia/Kolkata"),
+    ("China Standard Time", "Asia/Shanghai"),
+    ("Singapore Standard Time", "Asia/Singapore"),
+    ("Tokyo Standard Time", "Asia/Tokyo"),
+    ("Korea Standard Time", "Asia/Seoul"),
+    ("AUS Eastern Standard Time", "Australia/Sydney"),
+    ("New Zealand Standard Time", "Pacific/Auckland"),
+    ("Eastern Standard Time", "America/New_York"),
+    ("Central Standard Time", "America/Chicago"),
+    ("Mountain Standard Time", "America/Denver"),
+    ("US Mountain Standard Time", "America/Phoenix"),
+    ("Pacific Standard Time", "America/Los_Angeles"),
+    ("Alaskan Standard Time", "America/Anchorage"),
+    ("Hawaiian Standard Time", "Pacific/Honolulu"),
+    ("Atlantic Standard Time", "America/Halifax"),
+    ("E. South America Standard Time", "America/Sao_Paulo"),
+];
+
+const DAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Where a set of working hours came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoursSource {
+    /// The recipient's Outlook mailbox settings.
+    Outlook,
+    /// `[sending.working_hours]`.
+    Config,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Zone {
+    Local,
+    Named(Tz),
+}
+
+/// Working days and hours in a time zone. A start after the end is a shift
+/// that runs past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkingHours {
+    /// Working days, Monday first.
+    days: [bool; 7],
+    start: NaiveTime,
+    end: NaiveTime,
+    zone: Zone,
+    /// Where the hours came from.
+    pub source: HoursSource,
+}
+
+impl WorkingHours {
+    /// Working hours from `[sending.working_hours]`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for unknown days, times, or time zones.
+    pub fn from_config(config: &WorkingHoursConfig) -> Result<Self, CoreError> {
+        let zone = match config.timezone.as_deref() {
+            Some(name) => Zone::Named(name.parse().map_err(|_| {
+                CoreError::Config(format!(
+                    "unknown time zone '{name}' in [sending.working_hours]"
+                ))
+            })?),
+            None => Zone::Local,
+        };
+        Self::new(
+            config.days.iter().map(String::as_str),
+            &config.start,
+            &config.end,
+            zone,
+            HoursSource::Config,
+        )
+        .map_err(|e| CoreError::Config(format!("invalid [sending.working_hours]: {e}")))
+    }
+
+    /// Working hours from a Graph `workingHours` object.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the object is incomplete or its time zone is
+    /// not known.
+    pub fn from_outlook(value: &serde_json::Value) -> Result<Self, CoreError> {
+        let name = value["timeZone"]["name"].as_str().unwrap_or_default();
+        let zone = name
+            .parse::<Tz>()
+            .ok()
+            .or_else(|| {
+                WINDOWS_ZONES
+                    .iter()
+                    .find(|(windows, _)| *windows == name)
+                    .and_then(|(_, iana)| iana.parse().ok())
+            })
+            .ok_or_else(|| CoreError::api(format!("unknown Outlook time zone '{name}'")))?;
+        let days = value["daysOfWeek"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(serde_json::Value::as_str);
+        Self::new(
+            days,
+            value["startTime"].as_str().unwrap_or_default(),
+            value["endTime"].as_str().unwrap_or_default(),
+            Zone::Named(zone),
+            HoursSource::Outlook,
+        )
+        .map_err(|e| CoreError::api(format!("invalid Outlook working hours: {e}")))
+    }
+
+    fn new<'a>(
+        day_names: impl IntoIterator<Item = &'a str>,
+        start: &str,
+        end: &str,
+        zone: Zone,
+        source: HoursSource,
+    ) -> Result<Self, String> {
+        let mut days = [false; 7];
+        for name in day_names {
+            let day: Weekday = name.parse().map_err(|_| format!("unknown day '{name}'"))?;
+            days[day.num_days_from_monday() as usize] = true;
+        }
+        Ok(Self {
+            days,
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+            zone,
+            source,
+        })
+    }
+
+    fn local(&self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self.zone {
+            Zone::Local => time.with_timezone(&Local).naive_local(),
+            Zone::Named(tz) => time.with_timezone(&tz).naive_local(),
+        }
+    }
+
+    fn utc(&self, time: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self.zone {
+            Zone::Local => Local
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+        }
+    }
+
+    const fn works_on(&self, day: Weekday) -> bool {
+        self.days[day.num_days_from_monday() as usize]
+    }
+
+    /// Whether `time` falls within working hours.
+    #[must_use]
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        let local = self.local(time);
+        let (day, clock) = (local.weekday(), local.time());
+        if self.start <= self.end {
+            self.works_on(day) && self.start <= clock && clock < self.end
+        } else {
+            (self.works_on(day) && clock >= self.start)
+                || (self.works_on(day.pred()) && clock < self.end)
+        }
+    }
+
+    /// When the next working day starts after `time`.
+    #[must_use]
+    pub fn next_start(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = self.local(time);
+        (0..=7)
+            .map(|n| local.date() + Duration::days(n))
+            .filter(|date| self.works_on(date.weekday()))
+            .map(|date| date.and_time(self.start))
+            .find(|start| *start > local)
+            .and_then(|start| self.utc(start))
+    }
+
+    /// The hours as text, e.g. `Mon-Fri 09:00-17:00 Europe/Berlin`.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let mut runs: Vec<String> = Vec::new();
+        let mut day = 0;
+        while day < 7 {
+            if !self.days[day] {
+                day += 1;
+                continue;
+            }
+            let first = day;
+            while day + 1 < 7 && self.days[day + 1] {
+                day += 1;
+            }
+            runs.push(if first == day {
+                DAY_NAMES[day].to_string()
+            } else {
+                format!("{}-{}", DAY_NAMES[first], DAY_NAMES[day])
+            });
+            day += 1;
+        }
+        let zone = match self.zone {
+            Zone::Local => "local time".to_string(),
+            Zone::Named(tz) => tz.name().to_string(),
+        };
+        format!(
+            "{} {}-{} {zone}",
+            if runs.is_empty() {
+                "no days".to_string()
+            } else {
+                runs.join(",")
+            },
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+        )
+    }
+
+    /// `Some` if `now` is outside working hours.
+    #[must_use]
+    pub fn check(&self, now: DateTime<Utc>) -> Option<OffHours> {
+        if self.contains(now) {
+            return None;
+        }
+        Some(OffHours {
+            their_time: self.local(now),
+            next_start: self.next_start(now),
+            hours: *self,
+        })
+    }
+}
+
+fn parse_time(text: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(text, "%H:%M:%S%.f")
+        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+        .map_err(|_| format!("invalid time '{text}' (expected HH:MM)"))
+}
+
+/// A send that would land outside the recipient's working hours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffHours {
+    /// The recipient's working hours.
+    pub hours: WorkingHours,
+    /// The recipient's local time now.
+    pub their_time: NaiveDateTime,
+    /// When their next working day starts.
+    pub next_start: Option<DateTime<Utc>>,
+}
+
+impl OffHours {
+    /// Explanation naming the recipient, e.g. `it is Sat 22:14 for Alex
+    /// (working hours Mon-Fri 09:00-17:00 Europe/Berlin, from Outlook)`.
+    #[must_use]
+    pub fn describe(&self, who: &str) -> String {
+        let source = match self.hours.source {
+            HoursSource::Outlook => "from Outlook",
+            HoursSource::Config => "assumed",
+        };
+        format!(
+            "it is {} for {who} (working hours {}, {source})",
+            self.their_time.format("%a %H:%M"),
+            self.hours.describe()
+        )
+    }
+
+    /// A `--schedule` value for the start of their next working day, in
+    /// local time: `09:00`, `tomorrow 09:00`, or `mon 09:00`.
+    #[must_use]
+    pub fn schedule_hint(&self, now: DateTime<Local>) -> Option<String> {
+        let start = self.next_start?.with_timezone(&Local);
+        let time = start.format("%H:%M");
+        let days = (start.date_naive() - now.date_naive()).num_days();
+        Some(match days {
+            0 => time.to_string(),
+            1 => format!("tomorrow {time}"),
+            _ => format!("{} {time}", start.format("%a").to_string().to_lowercase()),
+        })
+    }
+}
+
+/// The other participant's object ID in a 1:1 chat
+/// (`19:<oid>_<oid>@unq.gbl.spaces`), or `None` for other conversations.
+#[must_use]
+pub fn one_on_one_peer<'a>(conversation_id: &'a str, me: &str) -> Option<&'a str> {
+    let pair = conversation_id
+        .strip_prefix("19:")?
+        .strip_suffix("@unq.gbl.spaces")?;
+    let (a, b) = pair.split_once('_')?;
+    match (a == me, b == me) {
+        (true, false) => Some(b),
+        (false, true) => Some(a),
+        _ => None,
+    }
+}
+
+/// Check whether sending to `conversation_id` now would land outside the
+/// recipient's working hours. Only 1:1 chats are checked; `None` means
+/// the send may go ahead (also when `off_hours = "off"`).
+///
+/// # Errors
+///
+/// Returns an error if not authenticated or `[sending.working_hours]` is
+/// invalid.
+pub async fn check_recipient(
+    config: &SendingConfig,
+    conversation_id: &str,
+) -> Result<Option<OffHours>, CoreError> {
+    if config.off_hours == OffHoursAction::Off {
+        return Ok(None);
+    }
+    let client = TeamsClient::new()?;
+    let me = client.user_id().await?;
+    let Some(peer) = one_on_one_peer(conversation_id, &me) else {
+        return Ok(None);
+    };
+    let outlook = if config.working_hours.lookup {
+        match client.get_working_hours(peer).await {
+            Ok(value) => WorkingHours::from_outlook(&value),
+            Err(e) => Err(e),
+        }
+        .inspect_err(|e| log::debug!("working hours of {peer}: {e}"))
+        .ok()
+    } else {
+        None
+    };
+    let hours = match outlook {
+        Some(hours) => hours,
+        None => WorkingHours::from_config(&config.working_hours)?,
+    };
+    Ok(hours.check(Utc::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(d: u32, h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, d, h, m, 0).unwrap()
+    }
+
+    /// Outlook's Mon-Fri 08:00-17:00 Pacific.
+    fn pacific() -> WorkingHours {
+        WorkingHours::from_outlook(&serde_json::json!({
+            "daysOfWeek": ["monday", "tuesday", "wednesday", "thursday", "friday"],
+            "startTime": "08:00:00.0000000",
+            "endTime": "17:00:00.0000000",
+            "timeZone": {"name": "Pacific Standard Time"}
+        }))
+        .unwrap()
+    }
+
+    fn night_shift() -> WorkingHoursConfig {
+        WorkingHoursConfig {
+            days: vec!["sat".to_string(), "sun".to_string(), "wed".to_string()],
+            start: "22:00".to_string(),
+            end: "06:00".to_string(),
+            timezone: Some("UTC".to_string()),
+            ..WorkingHoursConfig::default()
+        }
+    }
+
+    #[test]
+    fn checks_outlook_hours_in_their_time_zone() {
+        let hours = pacific();
+        assert_eq!(hours.describe(), "Mon-Fri 08:00-17:00 America/Los_Angeles");
+        // Mon 2026-03-09 16:00 UTC is 09:00 in Los Angeles (PDT).
+        assert!(hours.contains(utc(9, 16, 0)));
+        assert!(!hours.contains(utc(9, 14, 0)));
+    }
+
+    #[test]
+    fn off_hours_name_the_next_start() {
+        // Fri 2026-03-13 18:00 in Los Angeles: next start is Monday 08:00.
+        let off = pacific().check(utc(14, 1, 0)).unwrap();
+        assert_eq!(off.next_start, Some(utc(16, 15, 0)));
+        assert!(
+            off.describe("Alex")
+                .starts_with("it is Fri 18:00 for Alex (working hours Mon-Fri"),
+            "{}",
+            off.describe("Alex")
+        );
+    }
+
+    #[test]
+    fn overnight_hours_belong_to_the_day_they_start() {
+        let night = WorkingHours::from_config(&night_shift()).unwrap();
+        assert_eq!(night.describe(), "Wed,Sat-Sun 22:00-06:00 UTC");
+        // Thursday 05:00 is the end of Wednesday's shift.
+        assert!(night.contains(utc(12, 5, 0)));
+        assert!(!night.contains(utc(12, 7, 0)));
+    }
+
+    #[test]
+    fn unknown_time_zones_are_rejected() {
+        let config = WorkingHoursConfig {
+            timezone: Some("Mars/Olympus".to_string()),
+            ..night_shift()
+        };
+        assert!(WorkingHours::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn one_on_one_chats_name_the_other_person() {
+        assert_eq!(
+            one_on_one_peer("19:aaa_bbb@unq.gbl.spaces", "bbb"),
+            Some("aaa")
+        );
+        assert_eq!(one_on_one_peer("19:abc@thread.v2", "bbb"), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! When a contact is active and how fast they answer, from cached history.
+//!
+//! [`contact_activity`] buckets a contact's messages by weekday and hour in
+//! a time zone, giving the heatmap `tmz stats person` draws, and measures
+//! their response latency: the time from a message of yours to their next
+//! message in the same chat, when nobody else wrote in between. Longer
+//! gaps than [`MAX_RESPONSE`] start a new conversation rather than answer
+//! the old one, so they are left out.
+
+use chrono::{DateTime, Datelike, TimeZone, Timelike};
+use serde::Serialize;
+
+use crate::cache::CachedMessage;
+
+/// Replies later than this are not counted as responses.
+pub const MAX_RESPONSE: chrono::Duration = chrono::Duration::days(3);
+
+/// Weekday labels of the heatmap rows, Monday first.
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Activity of one contact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ContactActivity {
+    /// Display name the messages were matched on.
+    pub name: String,
+    /// Number of their messages.
+    pub messages: u32,
+    /// Their messages per weekday (Monday first) and hour.
+    pub heatmap: [[u32; 24]; 7],
+    /// Their response latencies to your messages, in seconds, shortest first.
+    pub response_secs: Vec<i64>,
+}
+
+impl ContactActivity {
+    /// Median response latency in seconds.
+    #[must_use]
+    pub fn median_response(&self) -> Option<i64> {
+        let n = self.response_secs.len();
+        match n {
+            0 => None,
+            _ if n % 2 == 1 => Some(self.response_secs[n / 2]),
+            _ => Some(i64::midpoint(
+                self.response_secs[n / 2 - 1],
+                self.response_secs[n / 2],
+            )),
+        }
+    }
+
+    /// The `n` busiest (weekday, hour) slots with their message counts,
+    /// busiest first; good times to reach them.
+    #[must_use]
+    pub fn peak_hours(&self, n: usize) -> Vec<(usize, usize, u32)> {
+        let mut slots: Vec<(usize, usize, u32)> = self
+            .heatmap
+            .iter()
+            .enumerate()
+            .flat_map(|(day, hours)| {
+                hours
+                    .iter()
+                    .enumerate()
+                    .map(move |(hour, &count)| (day, hour, count))
+            })
+            .filter(|&(_, _, count)| count > 0)
+            .collect();
+        slots.sort_by(|a, b| b.2.cmp(&a.2).then((a.0, a.1).cmp(&(b.0, b.1))));
+        slots.truncate(n);
+        slots
+    }
+}
+
+/// Activity of `name` in `messages`, which are grouped by conversation and
+/// oldest first within each (as [`crate::Cache::chats_with_sender`]
+/// returns them). Hours are taken in `tz`.
+#[must_use]
+pub fn contact_activity<Tz: TimeZone>(
+    name: &str,
+    messages: &[CachedMessage],
+    tz: &Tz,
+) -> ContactActivity {
+    let mut activity = ContactActivity {
+        name: name.to_string(),
+        messages: 0,
+        heatmap: [[0; 24]; 7],
+        response_secs: Vec::new(),
+    };
+    // Your oldest message still waiting for an answer, per conversation.
+    let mut waiting: Option<(&str, DateTime<chrono::FixedOffset>)> = None;
+    for msg in messages {
+        let Ok(at) = DateTime::parse_from_rfc3339(&msg.compose_time) else {
+            continue;
+        };
+        if waiting.is_some_and(|(conv, _)| conv != msg.conversation_id) {
+            waiting = None;
+        }
+        if msg.is_from_me {
+            waiting = waiting.or(Some((&msg.conversation_id, at)));
+            continue;
+        }
+        if msg.from_display_name != name {
+            waiting = None;
+            continue;
+        }
+        let local = at.with_timezone(tz);
+        let day = local.weekday().num_days_from_monday() as usize;
+        activity.heatmap[day][local.hour() as usize] += 1;
+        activity.messages += 1;
+        if let Some((_, asked)) = waiting.take() {
+            let latency = at - asked;
+            if latency <= MAX_RESPONSE {
+                activity.response_secs.push(latency.num_seconds().max(0));
+            }
+        }
+    }
+    activity.response_secs.sort_unstable();
+    activity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(conv: &str, from: &str, time: &str) -> CachedMessage {
+        CachedMessage {
+            id: time.to_string(),
+            conversation_id: conv.to_string(),
+            from_display_name: from.to_string(),
+            content: String::new(),
+            content_html: String::new(),
+            message_type: "Text".to_string(),
+            compose_time: time.to_string(),
+            is_from_me: from == "Me",
+            mentions_me: false,
+            unknown_type: false,
+            subject: String::new(),
+            important: false,
+            raw_json: "{}".to_string(),
+        }
+    }
+
+    #[test]
+    fn buckets_messages_and_measures_replies_to_me() {
+        let messages = [
+            // 1:1 chat: two questions, answered after 10 minutes, then
+            // after 30 minutes.
+            msg("19:dm", "Me", "2024-05-06T09:00:00Z"),
+            msg("19:dm", "Me", "2024-05-06T09:05:00Z"),
+            msg("19:dm", "Dana Lee", "2024-05-06T09:10:00Z"),
+            msg("19:dm", "Dana Lee", "2024-05-06T09:11:00Z"),
+            msg("19:dm", "Me", "2024-05-07T14:00:00Z"),
+            msg("19:dm", "Dana Lee", "2024-05-07T14:30:00Z"),
+            // A week-old question is not answered by a new message.
+            msg("19:dm", "Me", "2024-05-08T10:00:00Z"),
+            msg("19:dm", "Dana Lee", "2024-05-13T09:30:00Z"),
+            // Someone else answered first, and the last question is still open.
+            msg("19:group", "Me", "2024-05-06T08:00:00Z"),
+            msg("19:group", "Sam", "2024-05-06T08:01:00Z"),
+            msg("19:group", "Dana Lee", "2024-05-06T08:02:00Z"),
+            msg("19:group", "Me", "2024-05-06T16:00:00Z"),
+        ];
+        let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        let activity = contact_activity("Dana Lee", &messages, &tz);
+
+        assert_eq!(activity.messages, 5);
+        assert_eq!(activity.response_secs, [600, 1800]);
+        assert_eq!(activity.median_response(), Some(1200));
+        // Monday 09:10Z is 11:10 at UTC+2.
+        assert_eq!(activity.heatmap[0][11], 3);
+        assert_eq!(activity.heatmap[0][10], 1);
+        assert_eq!(activity.heatmap[1][16], 1);
+        assert_eq!(activity.peak_hours(2), [(0, 11, 3), (0, 10, 1)]);
+    }
+}
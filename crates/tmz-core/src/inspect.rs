@@ -0,0 +1,224 @@
+//! Annotated pretty-printing of raw Teams message JSON.
+//!
+//! Used by `tmz msg --inspect` and the TUI inspector when debugging parsing.
+//! Output is a list of lines made of typed tokens so each frontend can apply
+//! its own highlighting. Known fields get a trailing `// note`, and
+//! JSON-encoded strings (Teams stores `properties.mentions`, `files`, and
+//! friends that way) are shown decoded.
+
+use serde_json::Value;
+
+/// What a token represents, for highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Object key, including quotes.
+    Key,
+    /// String value, including quotes.
+    String,
+    /// Number value.
+    Number,
+    /// `true`, `false`, or `null`.
+    Literal,
+    /// Indentation, braces, colons, and commas.
+    Punct,
+    /// Field annotation.
+    Note,
+}
+
+/// A piece of an inspector line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// Token kind.
+    pub kind: TokenKind,
+    /// Text to print.
+    pub text: String,
+}
+
+/// Pretty-print `value` as annotated, tokenized lines.
+#[must_use]
+pub fn inspect_lines(value: &Value) -> Vec<Vec<Token>> {
+    let mut printer = Printer::default();
+    printer.value(value, "", 0, None, false);
+    printer.lines
+}
+
+/// Description of a known field, by dotted path (`[]` marks array items).
+#[must_use]
+pub fn field_note(path: &str) -> Option<&'static str> {
+    let note = match path {
+        "id" => "message id (ms timestamp of arrival)",
+        "clientmessageid" => "id chosen by the sending client",
+        "version" => "bumped on every edit (ms timestamp)",
+        "conversationid" => "chat or channel thread id",
+        "conversationLink" => "URL of the conversation",
+        "type" => "resource type",
+        "messagetype" => "Text, RichText/Html, ThreadActivity/*, Event/Call, ...",
+        "contenttype" => "content encoding",
+        "content" => "body (HTML for RichText/Html, XML for system events)",
+        "from" => "sender contact URL, ends in the sender MRI",
+        "imdisplayname" => "sender display name",
+        "composetime" => "when it was written (UTC)",
+        "originalarrivaltime" => "when the server received it (UTC)",
+        "amsreferences" => "ids of inline images in the media store",
+        "isFromMe" => "added by tmz: sent by you",
+        "mentionsMe" => "added by tmz: @-mentions you",
+        "properties.mentions" => "@-mentions",
+        "properties.mentions[].mri" => "mentioned user or tag id",
+        "properties.mentions[].displayName" => "text shown for the mention",
+        "properties.files" => "file attachments",
+        "properties.files[].fileName" => "attachment name",
+        "properties.files[].objectUrl" => "attachment download URL",
+        "properties.emotions" => "reactions, by key with users",
+        "properties.edittime" => "set when edited (ms timestamp)",
+        "properties.deletetime" => "set when deleted (ms timestamp)",
+        "properties.subject" => "post subject (channels)",
+        "properties.importance" => "set for important or urgent messages",
+        "properties.cards" => "Adaptive Card attachments",
+        "properties.links" => "link previews",
+        _ => return None,
+    };
+    Some(note)
+}
+
+#[derive(Default)]
+struct Printer {
+    lines: Vec<Vec<Token>>,
+    line: Vec<Token>,
+}
+
+impl Printer {
+    fn push(&mut self, kind: TokenKind, text: impl Into<String>) {
+        self.line.push(Token {
+            kind,
+            text: text.into(),
+        });
+    }
+
+    fn note(&mut self, note: Option<&str>) {
+        if let Some(note) = note {
+            self.push(TokenKind::Note, format!("  // {note}"));
+        }
+    }
+
+    fn newline(&mut self) {
+        self.lines.push(std::mem::take(&mut self.line));
+    }
+
+    /// Write `value` (the rest of the current line, and any nested lines),
+    /// followed by a comma if `comma` and the annotation for `path`.
+    fn value(&mut self, value: &Value, path: &str, indent: usize, note: Option<&str>, comma: bool) {
+        let note = note.or_else(|| field_note(path));
+        let comma = if comma { "," } else { "" };
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                self.push(TokenKind::Punct, "{");
+                self.note(note);
+                self.newline();
+                for (i, (key, child)) in map.iter().enumerate() {
+                    self.push(TokenKind::Punct, "  ".repeat(indent + 1));
+                    self.push(TokenKind::Key, Value::String(key.clone()).to_string());
+                    self.push(TokenKind::Punct, ": ");
+                    let child_path = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{path}.{key}")
+                    };
+                    self.value(child, &child_path, indent + 1, None, i + 1 < map.len());
+                }
+                self.push(
+                    TokenKind::Punct,
+                    format!("{}}}{comma}", "  ".repeat(indent)),
+                );
+                self.newline();
+            }
+            Value::Array(items) if !items.is_empty() => {
+                self.push(TokenKind::Punct, "[");
+                self.note(note);
+                self.newline();
+                let item_path = format!("{path}[]");
+                for (i, item) in items.iter().enumerate() {
+                    self.push(TokenKind::Punct, "  ".repeat(indent + 1));
+                    self.value(item, &item_path, indent + 1, None, i + 1 < items.len());
+                }
+                self.push(TokenKind::Punct, format!("{}]{comma}", "  ".repeat(indent)));
+                self.newline();
+            }
+            Value::String(s) => {
+                if let Some(decoded) = decode_json_string(s) {
+                    let note = note.map_or_else(
+                        || "JSON string, shown decoded".to_string(),
+                        |n| format!("{n} (JSON string, shown decoded)"),
+                    );
+                    self.value(&decoded, path, indent, Some(&note), comma == ",");
+                    return;
+                }
+                self.push(TokenKind::String, value.to_string());
+                self.scalar_end(comma, note);
+            }
+            Value::Number(_) => {
+                self.push(TokenKind::Number, value.to_string());
+                self.scalar_end(comma, note);
+            }
+            Value::Bool(_) | Value::Null | Value::Object(_) | Value::Array(_) => {
+                let kind = if value.is_boolean() || value.is_null() {
+                    TokenKind::Literal
+                } else {
+                    TokenKind::Punct
+                };
+                self.push(kind, value.to_string());
+                self.scalar_end(comma, note);
+            }
+        }
+    }
+
+    fn scalar_end(&mut self, comma: &str, note: Option<&str>) {
+        if !comma.is_empty() {
+            self.push(TokenKind::Punct, comma);
+        }
+        self.note(note);
+        self.newline();
+    }
+}
+
+/// Parse a string holding a non-empty JSON object or array.
+fn decode_json_string(s: &str) -> Option<Value> {
+    let trimmed = s.trim_start();
+    if !trimmed.starts_with(['{', '[']) {
+        return None;
+    }
+    serde_json::from_str::<Value>(s).ok().filter(|v| {
+        v.as_object().is_some_and(|m| !m.is_empty()) || v.as_array().is_some_and(|a| !a.is_empty())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(lines: &[Vec<Token>]) -> String {
+        lines
+            .iter()
+            .map(|line| line.iter().map(|t| t.text.as_str()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn annotates_known_fields_and_decodes_json_strings() {
+        let raw = serde_json::json!({
+            "id": "1700000000000",
+            "properties": {"mentions": "[{\"mri\":\"8:orgid:me\"}]", "custom": 1},
+            "mentionsMe": true,
+        });
+        let lines = inspect_lines(&raw);
+        assert_eq!(
+            render(&lines),
+            "{\n  \"id\": \"1700000000000\",  // message id (ms timestamp of arrival)\n  \
+             \"mentionsMe\": true,  // added by tmz: @-mentions you\n  \"properties\": {\n    \
+             \"custom\": 1,\n    \"mentions\": [  // @-mentions (JSON string, shown decoded)\n      {\n        \
+             \"mri\": \"8:orgid:me\"  // mentioned user or tag id\n      }\n    ]\n  }\n}"
+        );
+        assert_eq!(lines[1][1].kind, TokenKind::Key);
+        assert_eq!(lines[1][3].kind, TokenKind::String);
+    }
+}
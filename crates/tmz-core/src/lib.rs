@@ -4,25 +4,117 @@
 //! - Configuration loading and management
 //! - XDG-compliant path resolution
 //! - Schema and example config generation
+//! - Versioned migration of renamed config keys
+//! - Hot reload of the config in the daemon and TUI
 //! - Teams API client and authentication
+//! - Government (GCC High, `DoD`) and China cloud endpoints
+//! - Outbound HTTP proxy configuration
+//! - Retries of transient request failures with jittered backoff
+//! - Client-side request rate limiting that honors `Retry-After`
+//! - Named accounts with separate tokens and caches
 //! - `SQLite` cache for offline search and fast access
+//! - Per-conversation retention of cached messages
+//! - Lite sync for metered connections
+//! - Optional OCR of cached images for search
+//! - Inline images over the Kitty, iTerm2, and Sixel protocols
+//! - Annotated inspection of raw message JSON
+//! - Message reactions
+//! - Detection of encrypted chats and chats with Skype accounts
+//! - Slash commands (`/me`, `/code`, `/react`, ...) in composed messages
+//! - Plain text and Markdown message bodies converted to Teams HTML
+//! - Shared sync/send engine served by the daemon over IPC
+//! - Reconnects with backoff and gap-fill syncs after sleep or network loss
+//! - Startup health checks of auth, the daemon, and cache freshness
+//! - Scheduled daemon jobs such as the unread digest
+//! - Follow-up reminders that mark chats unread again
+//! - Signed per-conversation webhooks for incoming messages
+//! - Desktop notification rules for mentions, chats, and keywords
+//! - Time spent per conversation in the TUI, with hooks for time trackers
+//! - Recipients' working hours for out-of-hours send warnings
+//! - Presence, calendar focus time, and working hours of people
+//! - Org chart lookups of managers and direct reports
+//! - Activity heatmaps and response times of contacts
+//! - JSON, NUON, CSV, and YAML output of list commands
+//! - Opt-in HTTP request tracing
+//! - Scoped, hashed bearer tokens for the HTTP API
+//! - Owner-only token files and constant-time token comparison
+//! - User scripts in Rhai defining custom CLI and TUI actions
 //! - Common types and error handling
 
+pub mod accounts;
+pub mod apitokens;
+pub mod audit;
+pub mod availability;
+pub mod blobs;
+pub mod bundle;
 pub mod cache;
+pub mod colors;
+pub mod composer;
 pub mod config;
 pub mod daemon;
+pub mod dates;
+pub mod digest;
+pub mod engine;
 pub mod error;
+pub mod export;
+pub mod files;
+pub mod graphics;
+pub mod health;
+pub mod hooks;
+pub mod hours;
+pub mod insights;
+pub mod inspect;
 pub mod kitty;
+pub mod lite;
+pub mod live;
+pub mod markup;
+pub mod migrate;
+pub mod network;
+pub mod notifications;
+pub mod ocr;
+pub mod org;
+pub mod outbox;
+pub mod output;
 pub mod paths;
+pub mod ratelimit;
+pub mod reactions;
+pub mod reload;
+pub mod remind;
+pub mod restrictions;
+pub mod retention;
+pub mod retry;
+pub mod schedule;
 pub mod schema;
+pub mod scripting;
+pub mod secrets;
+pub mod slash;
+pub mod statusbar;
 pub mod teams;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testutil;
+pub mod timetrack;
+pub mod trace;
+pub mod translate;
 
-pub use cache::{Cache, CachedConversation, CachedMessage, SearchResult};
-pub use config::{AppConfig, LogLevel, LoggingConfig, PathsConfig, RuntimeConfig};
-pub use error::{CoreError, Result};
-pub use paths::{AppPaths, default_cache_dir, default_data_dir, default_state_dir};
-pub use schema::{generate_example_config, generate_schema, write_generated_files};
-pub use teams::{AuthManager, TeamsClient, TeamsTokens};
+pub use cache::{Cache, CachedConversation, CachedMessage, SearchResult, Translation};
+pub use config::{
+    AiConfig, ApiConfig, AppConfig, AuditConfig, CacheConfig, Cloud, ComposerConfig, ConfigLayer,
+    ConversationHook, Density, DisplayConfig, EndpointsConfig, FilesConfig, HooksConfig, ImageMode,
+    JobKind, JobOutput, LogLevel, LoggingConfig, McpConfig, MessagesConfig, NetworkConfig,
+    NotificationRule, OffHoursAction, OversizedAction, PathsConfig, RetentionConfig, RuntimeConfig,
+    SavedSearch, ScheduledJob, ScriptingConfig, SearchScope, SecurityConfig, SendingConfig,
+    StatusBarConfig, SyncConfig, TimeTrackingConfig, WorkingHoursConfig,
+};
+pub use engine::{Engine, EngineClient, EngineEvent, SyncOptions, SyncReport};
+pub use error::{ApiError, CoreError, Result};
+pub use output::OutputFormat;
+pub use paths::{
+    AppPaths, default_browser_profile_dir, default_cache_dir, default_data_dir, default_state_dir,
+};
+pub use schema::{
+    EnvOverride, env_overrides, generate_example_config, generate_schema, write_generated_files,
+};
+pub use teams::{AuthManager, TeamsClient, TeamsTokens, TokenAudience};
 
 /// Application name used for config directories and environment prefix.
 pub const APP_NAME: &str = "tmz";
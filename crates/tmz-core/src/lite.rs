@@ -0,0 +1,155 @@
+//! Lite sync for metered connections and small machines.
+//!
+//! With `[sync] lite = true` or `tmz sync --lite`, sync fetches fewer
+//! messages per chat, skips image downloads, and stores less: the raw JSON
+//! of conversations and messages is cut down to the fields tmz reads back
+//! (chat topic, read horizon, message sender, shared files), and long
+//! message HTML is zstd-compressed like raw JSON in the cache
+//! (`[cache] compress_raw_json`). [`expand_html`] undoes the
+//! compression when messages are read, so the rest of tmz never sees it.
+
+use std::borrow::Cow;
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use crate::cache::{CachedConversation, CachedMessage, compress_text, decompress_text};
+
+/// Messages fetched per conversation in a lite sync, at most.
+pub const PER_CHAT: i32 = 15;
+
+/// Marks compressed HTML; Teams content never starts with a control
+/// character.
+const COMPRESSED_PREFIX: &str = "\u{1}z";
+
+/// HTML shorter than this is stored as is.
+const MIN_COMPRESS_LEN: usize = 256;
+
+/// Cut the stored raw JSON of a conversation down to its topic and read
+/// horizon.
+pub fn slim_conversation(conv: &mut CachedConversation) {
+    let Ok(raw) = serde_json::from_str::<serde_json::Value>(&conv.raw_json) else {
+        return;
+    };
+    let mut slim = serde_json::json!({ "id": raw["id"] });
+    if let Some(topic) = raw["threadProperties"]["topic"].as_str() {
+        slim["threadProperties"] = serde_json::json!({ "topic": topic });
+    }
+    if let Some(horizon) = raw["properties"]["consumptionhorizon"].as_str() {
+        slim["properties"] = serde_json::json!({ "consumptionhorizon": horizon });
+    }
+    conv.raw_json = slim.to_string();
+}
+
+/// Cut the stored raw JSON of a message down to its sender (quoted by
+/// replies) and shared files, and compress its HTML.
+pub fn slim_message(msg: &mut CachedMessage) {
+    let raw = serde_json::from_str::<serde_json::Value>(&msg.raw_json).unwrap_or_default();
+    let mut slim = serde_json::Map::new();
+    if let Some(from) = raw["from"].as_str() {
+        slim.insert("from".to_string(), from.into());
+    }
+    let files = &raw["properties"]["files"];
+    if !files.is_null() {
+        slim.insert(
+            "properties".to_string(),
+            serde_json::json!({ "files": files }),
+        );
+    }
+    msg.raw_json = serde_json::Value::Object(slim).to_string();
+    msg.content_html = compress_html(&msg.content_html);
+}
+
+/// Compress `html` if it is long enough to gain from it.
+#[must_use]
+pub fn compress_html(html: &str) -> String {
+    if html.len() < MIN_COMPRESS_LEN || html.starts_with(COMPRESSED_PREFIX) {
+        return html.to_string();
+    }
+    let Some(bytes) = compress_text(html) else {
+        return html.to_string();
+    };
+    let packed = format!("{COMPRESSED_PREFIX}{}", BASE64.encode(bytes));
+    if packed.len() < html.len() {
+        packed
+    } else {
+        html.to_string()
+    }
+}
+
+/// The HTML stored by [`compress_html`], decompressed if needed.
+#[must_use]
+pub fn expand_html(stored: &str) -> Cow<'_, str> {
+    let Some(encoded) = stored.strip_prefix(COMPRESSED_PREFIX) else {
+        return Cow::Borrowed(stored);
+    };
+    BASE64
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| decompress_text(&bytes))
+        .map_or_else(
+            || {
+                log::warn!("cannot decompress stored message HTML");
+                Cow::Borrowed(stored)
+            },
+            Cow::Owned,
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_html_is_compressed_and_round_trips() {
+        let html = format!("<p>{}</p>", "status update, all green. ".repeat(40));
+        let packed = compress_html(&html);
+        assert!(packed.starts_with(COMPRESSED_PREFIX));
+        assert!(packed.len() < html.len() / 3);
+        assert_eq!(expand_html(&packed), html);
+    }
+
+    #[test]
+    fn short_html_is_stored_as_is() {
+        assert_eq!(compress_html("<p>hi</p>"), "<p>hi</p>");
+        assert_eq!(expand_html("<p>hi</p>"), "<p>hi</p>");
+    }
+
+    #[test]
+    fn slim_conversations_keep_topic_and_read_horizon() {
+        let mut conv = crate::cache::parse_conversation(&serde_json::json!({
+            "id": "19:a@thread.v2",
+            "threadProperties": {"topic": "Ops", "productThreadType": "GroupChat", "members": "[...]"},
+            "properties": {"consumptionhorizon": "1;2;3", "alerts": "true"},
+            "lastMessage": {"content": "hello", "imdisplayname": "Ann"}
+        }).into());
+        slim_conversation(&mut conv);
+        assert_eq!(
+            conv.raw_json,
+            r#"{"id":"19:a@thread.v2","properties":{"consumptionhorizon":"1;2;3"},"threadProperties":{"topic":"Ops"}}"#
+        );
+        assert_eq!(conv.product_type, "GroupChat");
+    }
+
+    #[test]
+    fn slim_messages_keep_only_the_sender() {
+        let mut msg = crate::cache::CachedMessage {
+            id: "1".to_string(),
+            conversation_id: "19:a@thread.v2".to_string(),
+            from_display_name: "Ann".to_string(),
+            content: "hi".to_string(),
+            content_html: "<p>hi</p>".to_string(),
+            message_type: "RichText/Html".to_string(),
+            compose_time: "2024-05-01T10:00:00Z".to_string(),
+            is_from_me: false,
+            mentions_me: false,
+            unknown_type: false,
+            subject: String::new(),
+            important: false,
+            raw_json: r#"{"from":"https://x/contacts/8:orgid:ann","properties":{"emotions":"[]"}}"#
+                .to_string(),
+        };
+        slim_message(&mut msg);
+        assert_eq!(msg.raw_json, r#"{"from":"https://x/contacts/8:orgid:ann"}"#);
+    }
+}
@@ -0,0 +1,251 @@
+//! Staying connected to the chat service across sleep and network changes.
+//!
+//! The daemon's link to Teams is its periodic sync and the push-event
+//! subscription (see [`crate::engine::Engine::listen`]). Either link
+//! drops when the laptop sleeps or the network switches, and nothing fails
+//! loudly: the next request may simply come hours later. [`Connection`]
+//! therefore treats the link as down after a failed contact and also once
+//! the wall clock (which keeps running while the machine sleeps) shows no
+//! successful contact for [`STALE_AFTER`]. Reconnect attempts are spaced by
+//! an exponential [`Backoff`], and the first successful contact afterwards
+//! hands back the [`Outage`] so the caller can gap-fill the messages missed
+//! during it. [`ConnectionStats`] are reported by `tmz service status`.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Without a successful contact for this long, the link counts as stale
+/// (two missed daemon syncs plus some slack).
+pub const STALE_AFTER: Duration = Duration::from_mins(12);
+
+/// Delay before the first reconnect attempt.
+pub const BACKOFF_BASE: Duration = Duration::from_secs(5);
+
+/// Longest delay between reconnect attempts.
+pub const BACKOFF_MAX: Duration = Duration::from_mins(5);
+
+/// Exponentially growing delays between reconnect attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    /// Delays starting at `base` and doubling up to `max`.
+    #[must_use]
+    pub const fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            attempt: 0,
+        }
+    }
+
+    /// The delay before the next attempt; each call doubles it.
+    pub fn next_delay(&mut self) -> Duration {
+        let factor = 2u32.saturating_pow(self.attempt.min(16));
+        self.attempt = self.attempt.saturating_add(1);
+        self.base.saturating_mul(factor).min(self.max)
+    }
+
+    /// Start over from `base` after a successful attempt.
+    pub const fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Attempts made since the last reset.
+    #[must_use]
+    pub const fn attempts(&self) -> u32 {
+        self.attempt
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(BACKOFF_BASE, BACKOFF_MAX)
+    }
+}
+
+/// A stretch of time without contact, ended by a reconnect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outage {
+    /// Last successful contact before the outage (or the first failure if
+    /// there was none).
+    pub since: DateTime<Utc>,
+    /// How long it lasted.
+    pub duration: chrono::Duration,
+}
+
+/// Reconnect metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionStats {
+    /// Whether the last contact succeeded and is recent.
+    pub connected: bool,
+    /// Start of the current outage (Unix seconds), if down.
+    pub down_since: Option<i64>,
+    /// Failed attempts in the current outage.
+    pub attempts: u32,
+    /// Reconnects after an outage since the daemon started.
+    pub reconnects: u64,
+    /// Failed contacts since the daemon started.
+    pub failures: u64,
+    /// When the last reconnect happened (Unix seconds).
+    pub last_reconnect_at: Option<i64>,
+    /// How long the last outage lasted, in seconds.
+    pub last_outage_secs: Option<i64>,
+}
+
+/// Liveness of the link to the chat service.
+#[derive(Debug, Clone, Copy)]
+pub struct Connection {
+    stale_after: chrono::Duration,
+    last_contact: Option<DateTime<Utc>>,
+    down_since: Option<DateTime<Utc>>,
+    backoff: Backoff,
+    stats: ConnectionStats,
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Self::new(STALE_AFTER, Backoff::default())
+    }
+}
+
+impl Connection {
+    /// A link that is stale after `stale_after` without contact and retries
+    /// with `backoff`.
+    #[must_use]
+    pub fn new(stale_after: Duration, backoff: Backoff) -> Self {
+        Self {
+            stale_after: chrono::Duration::from_std(stale_after).unwrap_or(chrono::Duration::MAX),
+            last_contact: None,
+            down_since: None,
+            backoff,
+            stats: ConnectionStats::default(),
+        }
+    }
+
+    /// Whether a reconnect is due at `now`: a contact failed, or the last
+    /// successful one is older than the staleness limit.
+    #[must_use]
+    pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.down_since.is_some()
+            || self
+                .last_contact
+                .is_some_and(|last| now - last > self.stale_after)
+    }
+
+    /// Record a successful contact at `now`, returning the outage it ended
+    /// (after failures, or after a silent gap such as a suspend).
+    pub fn contact(&mut self, now: DateTime<Utc>) -> Option<Outage> {
+        let since = self.down_since.take().or_else(|| {
+            self.last_contact
+                .filter(|&last| now - last > self.stale_after)
+        });
+        self.last_contact = Some(now);
+        self.backoff.reset();
+        self.stats.connected = true;
+        self.stats.down_since = None;
+        self.stats.attempts = 0;
+
+        let since = since?;
+        let outage = Outage {
+            since,
+            duration: now - since,
+        };
+        self.stats.reconnects += 1;
+        self.stats.last_reconnect_at = Some(now.timestamp());
+        self.stats.last_outage_secs = Some(outage.duration.num_seconds());
+        Some(outage)
+    }
+
+    /// Record a failed contact at `now`, returning how long to wait before
+    /// the next attempt.
+    pub fn failed(&mut self, now: DateTime<Utc>) -> Duration {
+        let since = *self
+            .down_since
+            .get_or_insert_with(|| self.last_contact.unwrap_or(now));
+        self.stats.connected = false;
+        self.stats.down_since = Some(since.timestamp());
+        self.stats.failures += 1;
+        let delay = self.backoff.next_delay();
+        self.stats.attempts = self.backoff.attempts();
+        delay
+    }
+
+    /// Metrics for status reports.
+    #[must_use]
+    pub const fn stats(&self) -> ConnectionStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(mins: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-05-01T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            + chrono::Duration::minutes(mins)
+    }
+
+    fn connection() -> Connection {
+        Connection::new(Duration::from_mins(12), Backoff::default())
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let mut backoff = Backoff::new(Duration::from_secs(5), Duration::from_mins(1));
+        let delays: Vec<u64> = (0..6).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, [5, 10, 20, 40, 60, 60]);
+    }
+
+    #[test]
+    fn links_go_stale_without_contact() {
+        let mut conn = connection();
+        assert_eq!(conn.contact(at(0)), None);
+        assert!(!conn.is_stale(at(10)));
+        assert!(conn.is_stale(at(13)));
+        assert_eq!(conn.contact(at(5)), None);
+    }
+
+    #[test]
+    fn failures_back_off_until_a_contact_ends_the_outage() {
+        let mut conn = connection();
+        conn.contact(at(5));
+        assert_eq!(conn.failed(at(10)), Duration::from_secs(5));
+        assert_eq!(conn.failed(at(11)), Duration::from_secs(10));
+        assert!(conn.is_stale(at(11)));
+        assert_eq!(conn.stats().down_since, Some(at(5).timestamp()));
+
+        let outage = conn.contact(at(12)).unwrap();
+        assert_eq!(outage.since, at(5));
+        assert_eq!(outage.duration, chrono::Duration::minutes(7));
+        // The contact reset the backoff.
+        assert_eq!(conn.failed(at(13)), Duration::from_secs(5));
+
+        let stats = conn.stats();
+        assert!(!stats.connected);
+        assert_eq!((stats.reconnects, stats.failures), (1, 3));
+    }
+
+    #[test]
+    fn a_silent_gap_such_as_a_suspend_is_an_outage() {
+        let mut conn = connection();
+        conn.contact(at(14));
+        assert!(conn.is_stale(at(74)));
+        let outage = conn.contact(at(74)).unwrap();
+        assert_eq!(outage.duration, chrono::Duration::hours(1));
+
+        let stats = conn.stats();
+        assert!(stats.connected);
+        assert_eq!((stats.reconnects, stats.failures), (1, 0));
+        assert_eq!(stats.last_outage_secs, Some(3600));
+    }
+}
@@ -0,0 +1,763 @@
+//! Conversion of message bodies into the HTML that Teams stores.
+//!
+//! Teams sends every message as `RichText/Html`, so message text has to be
+//! HTML. Frontends accept plain text (escaped, the default), HTML, or a
+//! small Markdown subset: `**bold**`, `*italic*` or `_italic_`,
+//! `` `code` ``, `[links](url)`, fenced code blocks, `#` headings, and
+//! `-`/`*` bullet lists. Everything else is kept as text, with line breaks
+//! preserved.
+//!
+//! HTML bodies go through [`sanitize_html`], which keeps the formatting
+//! tags Teams renders and drops scripts, event handlers, and links that are
+//! not `http`, `https`, or `mailto`. The Markdown converter only produces
+//! tags from that set.
+//!
+//! [`quote_reply`] wraps a message as a reply quoting an earlier one, and
+//! [`silence`] makes sure a message cannot notify anyone.
+
+use std::fmt::Write as _;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::CachedMessage;
+
+/// Longest preview of the quoted message in a reply, in characters.
+const QUOTE_PREVIEW_CHARS: usize = 200;
+
+/// Tags [`sanitize_html`] keeps.
+const ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "at",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "del",
+    "div",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "hr",
+    "i",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "span",
+    "strike",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+
+/// Tags [`sanitize_html`] drops together with their content.
+const DROPPED_TAGS: &[&str] = &[
+    "embed", "iframe", "math", "noscript", "object", "script", "style", "svg", "template",
+];
+
+/// Attributes [`sanitize_html`] keeps; the `item*` ones carry reply quotes.
+const ALLOWED_ATTRIBUTES: &[&str] = &[
+    "href",
+    "id",
+    "itemid",
+    "itemprop",
+    "itemscope",
+    "itemtype",
+    "title",
+];
+
+/// Elements without a closing tag.
+const VOID_TAGS: &[&str] = &["br", "col", "hr", "img", "wbr"];
+
+/// Block elements, which may not be nested in a `<p>`.
+const BLOCK_TAGS: &[&str] = &[
+    "blockquote",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "hr",
+    "ol",
+    "p",
+    "pre",
+    "table",
+    "ul",
+];
+
+/// Words that notify a whole chat, team, or channel after an `@`.
+const BROADCAST_WORDS: &[&str] = &["channel", "everyone", "here", "team"];
+
+/// Format of a message body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyFormat {
+    /// Plain text: HTML special characters are escaped.
+    #[default]
+    Text,
+    /// HTML, reduced to the tags Teams renders by [`sanitize_html`].
+    Html,
+    /// The Markdown subset described in the module docs.
+    #[serde(alias = "md")]
+    Markdown,
+}
+
+impl BodyFormat {
+    /// `body` as message HTML.
+    #[must_use]
+    pub fn to_html(self, body: &str) -> String {
+        match self {
+            Self::Text => escape_html(body).replace('\n', "<br>"),
+            Self::Html => sanitize_html(body),
+            Self::Markdown => markdown_to_html(body),
+        }
+    }
+}
+
+/// Escape text for the HTML content of a message.
+#[must_use]
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Plain text of message HTML, the reverse of [`BodyFormat::Text`]: `<br>`
+/// and the ends of paragraphs, list items, and code blocks become line
+/// breaks, other tags are dropped, and entities are decoded.
+#[must_use]
+pub fn html_to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim_end_matches('/').trim();
+        let tag = tag.to_ascii_lowercase();
+        if tag == "br"
+            || matches!(
+                tag.as_str(),
+                "/p" | "/li" | "/div" | "/pre" | "/h1" | "/h2" | "/h3"
+            )
+        {
+            out.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    crate::cache::decode_entities(out.trim_end_matches('\n'))
+}
+
+/// Reduce `html` to the tags and attributes Teams renders in a message.
+///
+/// Allowed tags keep their allowed attributes, re-quoted and escaped;
+/// links other than `http`, `https`, and `mailto` lose their `href`.
+/// Scripts, styles, and embedded content are dropped with their content,
+/// comments are dropped, and other tags are dropped while their text is
+/// kept. A `<`, `>`, or `&` that is not part of a tag or an entity is
+/// escaped.
+#[must_use]
+pub fn sanitize_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '<' if rest.starts_with("<!--") => {
+                rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            }
+            '<' => match parse_tag(rest) {
+                Some((tag, len)) if !tag.closing && DROPPED_TAGS.contains(&tag.name.as_str()) => {
+                    rest = skip_element(&rest[len..], &tag.name);
+                }
+                Some((tag, len)) => {
+                    if ALLOWED_TAGS.contains(&tag.name.as_str()) {
+                        write_tag(&mut out, &tag);
+                    }
+                    rest = &rest[len..];
+                }
+                None => {
+                    out.push_str("&lt;");
+                    rest = &rest[1..];
+                }
+            },
+            '>' => {
+                out.push_str("&gt;");
+                rest = &rest[1..];
+            }
+            '&' => {
+                let len = entity_len(rest).unwrap_or(0);
+                if len == 0 {
+                    out.push_str("&amp;");
+                    rest = &rest[1..];
+                } else {
+                    out.push_str(&rest[..len]);
+                    rest = &rest[len..];
+                }
+            }
+            c => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
+
+/// Message HTML that notifies nobody, for `--silent` sends.
+///
+/// Mention entities (`<at>` tags and Mention spans, as copied from Teams)
+/// are replaced by their text, and a word joiner after the `@` of
+/// `@channel`, `@team`, `@everyone`, and `@here` keeps them from being read
+/// as mentions while they look the same.
+#[must_use]
+pub fn silence(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        defuse_broadcasts(&rest[..start], &mut out);
+        rest = &rest[start..];
+        match parse_tag(rest) {
+            Some((tag, len)) if !tag.closing && is_mention(&tag) => {
+                // Keep what the mention says, without the entity.
+                let body = &rest[len..];
+                let close = body
+                    .to_ascii_lowercase()
+                    .find(&format!("</{}", tag.name))
+                    .unwrap_or(body.len());
+                out.push_str(&silence(&body[..close]));
+                rest = body[close..]
+                    .find('>')
+                    .map_or("", |end| &body[close + end + 1..]);
+            }
+            Some((_, len)) => {
+                out.push_str(&rest[..len]);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    defuse_broadcasts(rest, &mut out);
+    out
+}
+
+/// Whether `tag` opens a mention entity.
+fn is_mention(tag: &Tag) -> bool {
+    tag.name == "at"
+        || tag
+            .attrs
+            .iter()
+            .any(|(name, value)| name == "itemtype" && value.ends_with("schema.skype.com/Mention"))
+}
+
+/// Append `text` to `out` with a word joiner after the `@` of broadcast
+/// words such as `@channel`. Addresses like `ops@here.example` are left
+/// alone.
+fn defuse_broadcasts(text: &str, out: &mut String) {
+    let mut prev: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        out.push(c);
+        if c == '@' && !prev.is_some_and(char::is_alphanumeric) {
+            let after = &text[i + 1..];
+            let broadcast = BROADCAST_WORDS.iter().any(|word| {
+                after
+                    .get(..word.len())
+                    .is_some_and(|w| w.eq_ignore_ascii_case(word))
+                    && !after[word.len()..]
+                        .chars()
+                        .next()
+                        .is_some_and(char::is_alphanumeric)
+            });
+            if broadcast {
+                out.push('\u{2060}');
+            }
+        }
+        prev = Some(c);
+    }
+}
+
+/// An HTML tag read by [`parse_tag`].
+struct Tag {
+    /// Lowercase tag name.
+    name: String,
+    closing: bool,
+    /// Lowercase attribute names with their raw values.
+    attrs: Vec<(String, String)>,
+}
+
+/// The tag at the start of `html` (which starts with `<`) and its length in
+/// bytes, or `None` if the `<` does not start a tag.
+fn parse_tag(html: &str) -> Option<(Tag, usize)> {
+    let bytes = html.as_bytes();
+    let closing = bytes.get(1) == Some(&b'/');
+    let start = if closing { 2 } else { 1 };
+    if !bytes.get(start)?.is_ascii_alphabetic() {
+        return None;
+    }
+    let mut i = start;
+    while bytes.get(i).is_some_and(u8::is_ascii_alphanumeric) {
+        i += 1;
+    }
+    let mut tag = Tag {
+        name: html[start..i].to_ascii_lowercase(),
+        closing,
+        attrs: Vec::new(),
+    };
+    loop {
+        while bytes.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        match *bytes.get(i)? {
+            b'>' => return Some((tag, i + 1)),
+            b'<' => return None,
+            b'/' | b'=' => {
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let name_start = i;
+        while bytes
+            .get(i)
+            .is_some_and(|&b| !b.is_ascii_whitespace() && !matches!(b, b'=' | b'>' | b'/' | b'<'))
+        {
+            i += 1;
+        }
+        let name = html[name_start..i].to_ascii_lowercase();
+        while bytes.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        let mut value = "";
+        if bytes.get(i) == Some(&b'=') {
+            i += 1;
+            while bytes.get(i).is_some_and(u8::is_ascii_whitespace) {
+                i += 1;
+            }
+            let quote = *bytes.get(i)?;
+            if matches!(quote, b'"' | b'\'') {
+                let len = html[i + 1..].find(char::from(quote))?;
+                value = &html[i + 1..i + 1 + len];
+                i += len + 2;
+            } else {
+                let value_start = i;
+                while bytes
+                    .get(i)
+                    .is_some_and(|&b| !b.is_ascii_whitespace() && b != b'>')
+                {
+                    i += 1;
+                }
+                value = &html[value_start..i];
+            }
+        }
+        tag.attrs.push((name, value.to_string()));
+    }
+}
+
+/// Write an allowed tag with its allowed attributes.
+fn write_tag(out: &mut String, tag: &Tag) {
+    if tag.closing {
+        let _ = write!(out, "</{}>", tag.name);
+        return;
+    }
+    out.push('<');
+    out.push_str(&tag.name);
+    for (name, value) in &tag.attrs {
+        if !ALLOWED_ATTRIBUTES.contains(&name.as_str()) {
+            continue;
+        }
+        let value = crate::cache::decode_entities(value);
+        if name == "href" && !is_safe_link(&value) {
+            continue;
+        }
+        let _ = write!(out, " {name}=\"{}\"", escape_html(&value));
+    }
+    out.push('>');
+}
+
+/// Whether `url` is a link Teams may open.
+fn is_safe_link(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    ["https://", "http://", "mailto:"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+/// `html` after the end tag of the `name` element, or nothing if it is not
+/// closed.
+fn skip_element<'a>(html: &'a str, name: &str) -> &'a str {
+    let Some(close) = html.to_ascii_lowercase().find(&format!("</{name}")) else {
+        return "";
+    };
+    html[close..]
+        .find('>')
+        .map_or("", |end| &html[close + end + 1..])
+}
+
+/// Length of the entity (`&amp;`, `&#39;`, `&#x1F44D;`) at the start of
+/// `html`, which starts with `&`.
+fn entity_len(html: &str) -> Option<usize> {
+    let end = html[1..].find(';').filter(|&end| (1..=10).contains(&end))?;
+    let name = &html[1..=end];
+    let valid = match name.as_bytes() {
+        [b'#', b'x' | b'X', hex @ ..] => !hex.is_empty() && hex.iter().all(u8::is_ascii_hexdigit),
+        [b'#', num @ ..] => !num.is_empty() && num.iter().all(u8::is_ascii_digit),
+        name => name.iter().all(u8::is_ascii_alphanumeric),
+    };
+    valid.then_some(end + 2)
+}
+
+/// `reply` (message HTML) as a reply quoting `quoted`.
+///
+/// This is the `<blockquote>` form the Teams clients render as a reply card
+/// with the sender, time, and a preview of the quoted message. The sender's
+/// MRI is read from the quoted message's raw JSON; without it (as in caches
+/// from before lite sync kept it) the quote names the sender only. Inline
+/// reply HTML is wrapped in a paragraph, block HTML is appended as it is.
+#[must_use]
+pub fn quote_reply(quoted: &CachedMessage, reply: &str) -> String {
+    let raw: serde_json::Value = serde_json::from_str(&quoted.raw_json).unwrap_or_default();
+    let mri = raw["from"]
+        .as_str()
+        .and_then(|from| from.rsplit('/').next())
+        .filter(|mri| !mri.is_empty())
+        .map(|mri| format!(" itemid=\"{}\"", escape_html(mri)))
+        .unwrap_or_default();
+    let reply = if has_block(reply) {
+        reply.to_string()
+    } else {
+        format!("<p>{reply}</p>")
+    };
+    let mut preview: String = quoted
+        .content
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let Some((cut, _)) = preview.char_indices().nth(QUOTE_PREVIEW_CHARS) {
+        preview.truncate(cut);
+        preview.push('\u{2026}');
+    }
+    let id = escape_html(&quoted.id);
+    format!(
+        "<blockquote itemscope=\"\" itemtype=\"http://schema.skype.com/Reply\" itemid=\"{id}\">\
+         <strong itemprop=\"mri\"{mri}>{name}</strong>\
+         <span itemprop=\"time\" itemid=\"{id}\"></span>\
+         <p itemprop=\"preview\">{preview}</p>\
+         </blockquote>{reply}",
+        name = escape_html(&quoted.from_display_name),
+        preview = escape_html(&preview),
+    )
+}
+
+/// Whether `html` contains a block element, such as a paragraph or list.
+fn has_block(html: &str) -> bool {
+    html.match_indices('<').any(|(i, _)| {
+        parse_tag(&html[i..]).is_some_and(|(tag, _)| BLOCK_TAGS.contains(&tag.name.as_str()))
+    })
+}
+
+/// Follow the elements `html` opens and closes on `open`, a stack of
+/// element names and their opening tags. Void elements are not tracked,
+/// and a closing tag also closes the elements left open inside it.
+pub(crate) fn track_open_elements(html: &str, open: &mut Vec<(String, String)>) {
+    for (i, _) in html.match_indices('<') {
+        let Some((tag, len)) = parse_tag(&html[i..]) else {
+            continue;
+        };
+        if tag.closing {
+            if let Some(pos) = open.iter().rposition(|(name, _)| *name == tag.name) {
+                open.truncate(pos);
+            }
+        } else if !VOID_TAGS.contains(&tag.name.as_str()) {
+            open.push((tag.name, html[i..i + len].to_string()));
+        }
+    }
+}
+
+/// Convert the supported Markdown subset to HTML.
+#[must_use]
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut blocks: Vec<String> = Vec::new();
+    let mut paragraph: Vec<String> = Vec::new();
+    let mut list: Vec<String> = Vec::new();
+    let mut code: Option<Vec<&str>> = None;
+
+    let flush = |blocks: &mut Vec<String>, paragraph: &mut Vec<String>, list: &mut Vec<String>| {
+        if !paragraph.is_empty() {
+            blocks.push(format!("<p>{}</p>", paragraph.join("<br>")));
+            paragraph.clear();
+        }
+        if !list.is_empty() {
+            blocks.push(format!("<ul>{}</ul>", list.concat()));
+            list.clear();
+        }
+    };
+
+    for line in markdown.lines() {
+        if let Some(ref mut lines) = code {
+            if line.trim_start().starts_with("```") {
+                blocks.push(format!("<pre>{}</pre>", escape_html(&lines.join("\n"))));
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            flush(&mut blocks, &mut paragraph, &mut list);
+            code = Some(Vec::new());
+        } else if trimmed.is_empty() {
+            flush(&mut blocks, &mut paragraph, &mut list);
+        } else if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            if !paragraph.is_empty() {
+                flush(&mut blocks, &mut paragraph, &mut list);
+            }
+            list.push(format!("<li>{}</li>", inline(item)));
+        } else if let Some((level, heading)) = heading(trimmed) {
+            flush(&mut blocks, &mut paragraph, &mut list);
+            blocks.push(format!("<h{level}>{}</h{level}>", inline(heading)));
+        } else {
+            if !list.is_empty() {
+                flush(&mut blocks, &mut paragraph, &mut list);
+            }
+            paragraph.push(inline(trimmed));
+        }
+    }
+    if let Some(lines) = code {
+        // Unclosed fence: keep what was there.
+        blocks.push(format!("<pre>{}</pre>", escape_html(&lines.join("\n"))));
+    }
+    flush(&mut blocks, &mut paragraph, &mut list);
+    blocks.concat()
+}
+
+/// Level and text of a `#` to `###` heading line.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=3).contains(&level).then_some((level, text.trim()))
+}
+
+/// Inline Markdown of one line as HTML.
+fn inline(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 16);
+    let mut rest = text;
+    let mut in_word = false;
+    while let Some(c) = rest.chars().next() {
+        if let Some((html, len)) = inline_span(rest, in_word) {
+            out.push_str(&html);
+            rest = &rest[len..];
+            in_word = false;
+        } else {
+            out.push_str(&escape_html(&rest[..c.len_utf8()]));
+            rest = &rest[c.len_utf8()..];
+            in_word = c.is_alphanumeric();
+        }
+    }
+    out
+}
+
+/// The span starting at `text`, as HTML and the number of bytes it covers.
+/// `_` does not start emphasis inside a word (`in_word`), as in
+/// `snake_case`.
+fn inline_span(text: &str, in_word: bool) -> Option<(String, usize)> {
+    if let Some(body) = text.strip_prefix('`') {
+        let end = body.find('`')?;
+        return Some((
+            format!("<code>{}</code>", escape_html(&body[..end])),
+            end + 2,
+        ));
+    }
+    if let Some(body) = text.strip_prefix("**") {
+        let end = body.find("**").filter(|&end| end > 0)?;
+        return Some((format!("<b>{}</b>", inline(&body[..end])), end + 4));
+    }
+    for marker in ['*', '_'] {
+        if marker == '_' && in_word {
+            return None;
+        }
+        if let Some(body) = text.strip_prefix(marker) {
+            let end = body.find(marker).filter(|&end| end > 0)?;
+            if body.starts_with(' ') {
+                return None;
+            }
+            return Some((format!("<i>{}</i>", inline(&body[..end])), end + 2));
+        }
+    }
+    if let Some(body) = text.strip_prefix('[') {
+        let close = body.find("](")?;
+        let url_len = body[close + 2..].find(')')?;
+        let url = &body[close + 2..close + 2 + url_len];
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            return None;
+        }
+        return Some((
+            format!(
+                "<a href=\"{}\">{}</a>",
+                escape_html(url),
+                inline(&body[..close])
+            ),
+            close + url_len + 4,
+        ));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bodies_convert_to_message_html() {
+        assert_eq!(BodyFormat::Text.to_html("a < b\nnext"), "a &lt; b<br>next");
+        assert_eq!(BodyFormat::Html.to_html("<b>x</b>"), "<b>x</b>");
+
+        let md = "# Build **failed**\n\
+                  Job `test` on [main](https://ci.example/1) _again_\n\
+                  second line\n\
+                  \n\
+                  - one\n\
+                  * two\n\
+                  ```\n\
+                  a <b>\n\
+                  ```\n\
+                  2 * 3 * 4 and snake_case_name";
+        assert_eq!(
+            BodyFormat::Markdown.to_html(md),
+            "<h1>Build <b>failed</b></h1>\
+             <p>Job <code>test</code> on <a href=\"https://ci.example/1\">main</a> <i>again</i><br>second line</p>\
+             <ul><li>one</li><li>two</li></ul>\
+             <pre>a &lt;b&gt;</pre>\
+             <p>2 * 3 * 4 and snake_case_name</p>"
+        );
+    }
+
+    #[test]
+    fn markdown_links_must_be_web_links() {
+        assert_eq!(
+            markdown_to_html("[x](javascript:alert(1))"),
+            "<p>[x](javascript:alert(1))</p>"
+        );
+    }
+
+    #[test]
+    fn plain_text_is_escaped_and_round_trips() {
+        let text = "if a < b && c > d: \"ok\"\n<script>alert(1)</script> &amp;";
+        let html = BodyFormat::Text.to_html(text);
+        assert_eq!(
+            html,
+            "if a &lt; b &amp;&amp; c &gt; d: &quot;ok&quot;<br>&lt;script&gt;alert(1)&lt;/script&gt; &amp;amp;"
+        );
+        assert_eq!(html_to_text(&html), text);
+    }
+
+    #[test]
+    fn html_is_sanitized_to_the_tags_teams_renders() {
+        let dirty = "<p onclick=\"x()\">Hi <B>there</B><script>alert(1)</script>\
+                     <style>p{}</style><!-- note --><img src=x onerror=alert(1)>\
+                     <a href=\"javascript:alert(1)\">bad</a> <a href='https://x.test/?a=1&amp;b=2' target=_blank>ok</a>\
+                     <iframe src=\"https://x.test\">hidden</iframe><form><input value=1>kept</form>\
+                     1 < 2 & 3 > 2 &copy; &#39; &bogus </p><a href=\"x\" title=\"a\"b\"";
+        assert_eq!(
+            BodyFormat::Html.to_html(dirty),
+            "<p>Hi <b>there</b><a>bad</a> <a href=\"https://x.test/?a=1&amp;b=2\">ok</a>\
+             kept1 &lt; 2 &amp; 3 &gt; 2 &copy; &#39; &amp;bogus </p>&lt;a href=\"x\" title=\"a\"b\""
+        );
+    }
+
+    #[test]
+    fn markdown_only_produces_tags_the_sanitizer_keeps() {
+        let md = "# T\n**b** *i* `c < d` [x](https://x.test/\"onmouseover=\"a)\n\n- <li>\n```\n</pre><script>\n```";
+        let html = markdown_to_html(md);
+        assert!(!html.contains("<script") && !html.contains("<li><li>"));
+        assert_eq!(sanitize_html(&html), html);
+    }
+
+    #[test]
+    fn silenced_messages_keep_text_but_drop_mentions() {
+        let html = "<p>FYI <at id=\"0\">Dana Lee</at> and \
+                    <span itemscope=\"\" itemtype=\"http://schema.skype.com/Mention\" itemid=\"1\">General</span>: \
+                    @channel @Here, @teams, mail ops@here.example <a href=\"mailto:x@everyone.example\">x</a></p>";
+        assert_eq!(
+            silence(html),
+            "<p>FYI Dana Lee and General: @\u{2060}channel @\u{2060}Here, @teams, \
+             mail ops@here.example <a href=\"mailto:x@everyone.example\">x</a></p>"
+        );
+        assert_eq!(silence("a < b &amp; @team"), "a < b &amp; @\u{2060}team");
+    }
+
+    /// A long message from Dana, whose raw JSON names their MRI.
+    fn quoted() -> CachedMessage {
+        CachedMessage {
+            id: "1714981234567".to_string(),
+            conversation_id: "19:dm".to_string(),
+            from_display_name: "Dana <Ops>".to_string(),
+            content: format!("Can you   review\nthe deploy? {}", "x".repeat(300)),
+            content_html: String::new(),
+            message_type: "RichText/Html".to_string(),
+            compose_time: "2024-05-06T08:00:34.567Z".to_string(),
+            is_from_me: false,
+            mentions_me: false,
+            unknown_type: false,
+            subject: String::new(),
+            important: false,
+            raw_json: r#"{"from":"https://emea.ng.msg.teams.microsoft.com/v1/users/ME/contacts/8:orgid:abc"}"#
+                .to_string(),
+        }
+    }
+
+    #[test]
+    fn replies_quote_sender_and_preview() {
+        let html = quote_reply(&quoted(), "sure");
+        assert!(html.starts_with(
+            "<blockquote itemscope=\"\" itemtype=\"http://schema.skype.com/Reply\" itemid=\"1714981234567\">\
+             <strong itemprop=\"mri\" itemid=\"8:orgid:abc\">Dana &lt;Ops&gt;</strong>\
+             <span itemprop=\"time\" itemid=\"1714981234567\"></span>\
+             <p itemprop=\"preview\">Can you review the deploy? xxx"
+        ));
+        assert!(html.ends_with("x\u{2026}</p></blockquote><p>sure</p>"));
+    }
+
+    #[test]
+    fn block_replies_follow_the_quote_unwrapped() {
+        let block = markdown_to_html("Looks good:\n\n- ship it");
+        let html = quote_reply(&quoted(), &block);
+        assert!(html.ends_with(&format!("</blockquote>{block}")), "{html}");
+    }
+
+    #[test]
+    fn quotes_without_raw_json_name_the_sender_only() {
+        let lite = CachedMessage {
+            raw_json: "{}".to_string(),
+            ..quoted()
+        };
+        assert!(
+            quote_reply(&lite, "ok").contains("<strong itemprop=\"mri\">Dana &lt;Ops&gt;</strong>")
+        );
+    }
+}
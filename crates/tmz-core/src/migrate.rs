@@ -0,0 +1,300 @@
+//! Versioned config migrations.
+//!
+//! Config files are plain TOML, and keys renamed or restructured between
+//! releases would otherwise be ignored without a word. Each file records
+//! its layout in `config_version` (absent means 0). When the user config is
+//! older than [`CONFIG_VERSION`], the pending migrations rewrite it after
+//! the original is copied to `config.toml.v{N}.bak`, and every applied
+//! change is appended to `config-migrations.log` next to it. Keys the
+//! schema does not know are reported by [`unknown_keys`].
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::CoreError;
+use crate::schema::{config_schema, resolve_schema};
+
+/// Current config layout version.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Name of the log of applied migrations, next to the config file.
+pub const MIGRATION_LOG: &str = "config-migrations.log";
+
+/// A rewrite from the previous layout version to `to`.
+struct Migration {
+    to: u32,
+    apply: fn(&mut toml::Table) -> Vec<String>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    to: 1,
+    apply: rename_ui_to_display,
+}];
+
+/// v1: `[ui]` became `[display]`, at the top level and in profiles.
+fn rename_ui_to_display(table: &mut toml::Table) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut rename = |table: &mut toml::Table, prefix: &str| {
+        let Some(toml::Value::Table(ui)) = table.remove("ui") else {
+            return;
+        };
+        let display = table
+            .entry("display")
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if let toml::Value::Table(display) = display {
+            for (key, value) in ui {
+                // Keys already under [display] win.
+                display.entry(key).or_insert(value);
+            }
+        }
+        changes.push(format!("renamed [{prefix}ui] to [{prefix}display]"));
+    };
+    rename(table, "");
+    if let Some(toml::Value::Table(profiles)) = table.get_mut("profiles") {
+        for (name, profile) in profiles.iter_mut() {
+            if let toml::Value::Table(profile) = profile {
+                rename(profile, &format!("profiles.{name}."));
+            }
+        }
+    }
+    changes
+}
+
+/// The `config_version` of a parsed config file.
+#[must_use]
+pub fn version_of(table: &toml::Table) -> u32 {
+    table
+        .get("config_version")
+        .and_then(toml::Value::as_integer)
+        .and_then(|v| u32::try_from(v).ok())
+        .unwrap_or(0)
+}
+
+/// Apply the migrations after `table`'s version and set it to
+/// [`CONFIG_VERSION`]. Returns the changes made, empty if there were none.
+pub fn migrate(table: &mut toml::Table) -> Vec<String> {
+    let from = version_of(table);
+    let mut changes = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.to > from) {
+        changes.extend((migration.apply)(table));
+    }
+    if from < CONFIG_VERSION {
+        table.insert(
+            "config_version".to_string(),
+            toml::Value::Integer(i64::from(CONFIG_VERSION)),
+        );
+    }
+    changes
+}
+
+/// A migration applied to a config file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct MigrationReport {
+    /// The migrated file.
+    pub path: PathBuf,
+    /// Version before.
+    pub from: u32,
+    /// Version after.
+    pub to: u32,
+    /// Copy of the file before migrating (`None` on a dry run).
+    pub backup: Option<PathBuf>,
+    /// What changed.
+    pub changes: Vec<String>,
+}
+
+/// Migrate the config file at `path` if it is older than
+/// [`CONFIG_VERSION`] and a migration changes it. Files without changes
+/// are left alone, comments included; on a `dry_run` nothing is written.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, parsed, or written, or was
+/// written by a newer version.
+pub fn migrate_file(path: &Path, dry_run: bool) -> Result<Option<MigrationReport>, CoreError> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(path)?;
+    let mut table: toml::Table = toml::from_str(&text)
+        .map_err(|e| CoreError::Config(format!("parsing {}: {e}", path.display())))?;
+    let from = version_of(&table);
+    if from > CONFIG_VERSION {
+        return Err(CoreError::Config(format!(
+            "{} has config_version {from}, newer than this tmz supports ({CONFIG_VERSION}); upgrade tmz",
+            path.display()
+        )));
+    }
+    let changes = migrate(&mut table);
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    let mut report = MigrationReport {
+        path: path.to_path_buf(),
+        from,
+        to: CONFIG_VERSION,
+        backup: None,
+        changes,
+    };
+    if dry_run {
+        return Ok(Some(report));
+    }
+
+    let backup = path.with_file_name(format!(
+        "{}.v{from}.bak",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    std::fs::copy(path, &backup)?;
+    let output =
+        toml::to_string_pretty(&table).map_err(|e| CoreError::Serialization(e.to_string()))?;
+    std::fs::write(path, output)?;
+
+    let mut entry = String::new();
+    let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let _ = writeln!(
+        entry,
+        "{now} {} v{from} -> v{CONFIG_VERSION} (backup: {})",
+        path.display(),
+        backup.display()
+    );
+    for change in &report.changes {
+        let _ = writeln!(entry, "  - {change}");
+    }
+    let log = path.with_file_name(MIGRATION_LOG);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log)?;
+    std::io::Write::write_all(&mut file, entry.as_bytes())?;
+
+    report.backup = Some(backup);
+    Ok(Some(report))
+}
+
+/// Dotted keys in `table` that the config schema does not know, and so
+/// are ignored when loading.
+#[must_use]
+pub fn unknown_keys(table: &toml::Table) -> Vec<String> {
+    let root = config_schema();
+    let mut unknown = Vec::new();
+    walk_keys(&root, &root, table, "", &mut unknown);
+    unknown
+}
+
+fn walk_keys(
+    root: &serde_json::Value,
+    schema: &serde_json::Value,
+    table: &toml::Table,
+    prefix: &str,
+    unknown: &mut Vec<String>,
+) {
+    let schema = if schema["$ref"] == "#" {
+        root
+    } else {
+        resolve_schema(root, schema)
+    };
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        let child = schema["properties"].get(key).or_else(|| {
+            schema["additionalProperties"]
+                .as_object()
+                .map(|_| &schema["additionalProperties"])
+        });
+        let Some(child) = child else {
+            if !key.starts_with('$') {
+                unknown.push(path);
+            }
+            continue;
+        };
+        let child_resolved = if child["$ref"] == "#" {
+            root
+        } else {
+            resolve_schema(root, child)
+        };
+        match value {
+            toml::Value::Table(inner) => walk_keys(root, child, inner, &path, unknown),
+            toml::Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if let toml::Value::Table(inner) = item {
+                        let items_schema = &child_resolved["items"];
+                        walk_keys(root, items_schema, inner, &format!("{path}[{i}]"), unknown);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    /// A v0 config using `[ui]`, with an override in `[display]`, a
+    /// profile, and a misspelled key.
+    fn legacy_config(dir: &TempDir) -> PathBuf {
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            "[ui]\nsender_colors = false\ndensity = \"compact\"\n\
+             [display]\ndensity = \"comfortable\"\n\
+             [profiles.work.ui]\nsystem_events = false\n\
+             [logging]\nlevel = \"info\"\ncolour = true\n",
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn dry_runs_report_without_writing() {
+        let dir = TempDir::new("migrate-dry").unwrap();
+        let path = legacy_config(&dir);
+        let report = migrate_file(&path, true).unwrap().unwrap();
+        assert_eq!((report.from, report.to), (0, CONFIG_VERSION));
+        assert_eq!(report.backup, None);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("[ui]"));
+    }
+
+    #[test]
+    fn migrates_ui_to_display_with_a_backup() {
+        let dir = TempDir::new("migrate").unwrap();
+        let path = legacy_config(&dir);
+        let report = migrate_file(&path, false).unwrap().unwrap();
+        assert_eq!(
+            report.changes,
+            [
+                "renamed [ui] to [display]",
+                "renamed [profiles.work.ui] to [profiles.work.display]"
+            ]
+        );
+        assert!(report.backup.unwrap().is_file());
+        let table: toml::Table = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(version_of(&table), CONFIG_VERSION);
+        assert_eq!(table["display"]["density"].as_str(), Some("comfortable"));
+        assert_eq!(table["display"]["sender_colors"].as_bool(), Some(false));
+        assert!(table.get("ui").is_none());
+        let log = std::fs::read_to_string(dir.join(MIGRATION_LOG)).unwrap();
+        assert!(log.contains("v0 -> v1"));
+    }
+
+    #[test]
+    fn current_configs_need_no_migration() {
+        let dir = TempDir::new("migrate-current").unwrap();
+        let path = legacy_config(&dir);
+        migrate_file(&path, false).unwrap();
+        assert_eq!(migrate_file(&path, false).unwrap(), None);
+    }
+
+    #[test]
+    fn unknown_keys_are_listed() {
+        let dir = TempDir::new("migrate-unknown").unwrap();
+        let path = legacy_config(&dir);
+        migrate_file(&path, false).unwrap();
+        let table: toml::Table = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(unknown_keys(&table), ["logging.colour"]);
+    }
+}
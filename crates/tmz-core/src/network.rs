@@ -0,0 +1,190 @@
+//! Outbound proxy for every HTTP client tmz creates.
+//!
+//! Without configuration, the standard `HTTPS_PROXY`, `HTTP_PROXY`,
+//! `ALL_PROXY`, and `NO_PROXY` environment variables are honored (reqwest's
+//! default). `[network] proxy` overrides them with one proxy for all
+//! requests, and `[network] no_proxy` lists hosts reached directly (also
+//! when the proxy itself comes from the environment). The
+//! setting is fixed once per process by [`configure`]; [`client_builder`]
+//! applies it, and [`browser_proxy`] passes it on to the login browser.
+
+use std::sync::OnceLock;
+
+use reqwest::{ClientBuilder, NoProxy, Proxy};
+
+use crate::CoreError;
+use crate::config::NetworkConfig;
+
+static PROXY: OnceLock<Option<Proxy>> = OnceLock::new();
+static CONFIG: OnceLock<NetworkConfig> = OnceLock::new();
+
+/// Environment variables naming a proxy, in order of preference.
+const PROXY_ENV: &[&str] = &[
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+/// Environment variables listing hosts that bypass the proxy.
+const NO_PROXY_ENV: &[&str] = &["NO_PROXY", "no_proxy"];
+
+/// Use the proxy from `config` for the rest of the process.
+///
+/// Only the first call has an effect; later calls (e.g. after a config
+/// reload) keep the proxy already in use.
+///
+/// # Errors
+///
+/// Returns [`CoreError::Config`] if `[network] proxy` is not a valid proxy
+/// URL.
+pub fn configure(config: &NetworkConfig) -> Result<(), CoreError> {
+    let proxy = build_proxy(config, env_var(PROXY_ENV))?;
+    let _ = PROXY.set(proxy);
+    let _ = CONFIG.set(config.clone());
+    Ok(())
+}
+
+/// The proxy to set on clients, or `None` to leave the environment to
+/// reqwest.
+fn build_proxy(
+    config: &NetworkConfig,
+    env_proxy: Option<String>,
+) -> Result<Option<Proxy>, CoreError> {
+    let configured = config.proxy.clone().filter(|url| !url.trim().is_empty());
+    if configured.is_none() && config.no_proxy.is_empty() {
+        return Ok(None);
+    }
+    let Some(url) = configured.or(env_proxy) else {
+        return Ok(None);
+    };
+    let proxy = Proxy::all(url.trim())
+        .map_err(|e| CoreError::Config(format!("invalid proxy '{url}': {e}")))?;
+    let no_proxy = if config.no_proxy.is_empty() {
+        NoProxy::from_env()
+    } else {
+        NoProxy::from_string(&config.no_proxy.join(","))
+    };
+    Ok(Some(proxy.no_proxy(no_proxy)))
+}
+
+/// A reqwest client builder using the configured proxy.
+pub fn client_builder() -> ClientBuilder {
+    let builder = reqwest::Client::builder();
+    match PROXY.get() {
+        Some(Some(proxy)) => builder.proxy(proxy.clone()),
+        _ => builder,
+    }
+}
+
+/// A reqwest client using the configured proxy and no timeout.
+///
+/// # Errors
+///
+/// Returns an error if the client cannot be created.
+pub fn client() -> Result<reqwest::Client, CoreError> {
+    client_builder()
+        .build()
+        .map_err(|e| CoreError::Other(format!("creating HTTP client: {e}")))
+}
+
+/// Proxy URL and bypass list (comma-separated) for the login browser:
+/// `[network]` if set, otherwise the environment.
+#[must_use]
+pub fn browser_proxy() -> Option<(String, String)> {
+    let config = CONFIG.get().cloned().unwrap_or_default();
+    resolve_browser_proxy(&config, env_var(PROXY_ENV), env_var(NO_PROXY_ENV))
+}
+
+/// Value of the first of `names` that is set and not empty.
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+}
+
+fn resolve_browser_proxy(
+    config: &NetworkConfig,
+    env_proxy: Option<String>,
+    env_no_proxy: Option<String>,
+) -> Option<(String, String)> {
+    let server = config
+        .proxy
+        .clone()
+        .filter(|url| !url.trim().is_empty())
+        .or(env_proxy)?;
+    let bypass = if config.no_proxy.is_empty() {
+        env_no_proxy.unwrap_or_default()
+    } else {
+        config.no_proxy.join(",")
+    };
+    Some((server.trim().to_string(), bypass))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> String {
+        "http://env-proxy:3128".to_string()
+    }
+
+    fn env_bypass() -> String {
+        "localhost".to_string()
+    }
+
+    fn corp() -> NetworkConfig {
+        NetworkConfig {
+            proxy: Some("http://user:pw@proxy.corp:8080".into()),
+            no_proxy: vec![".corp".into(), "10.0.0.0/8".into()],
+        }
+    }
+
+    #[test]
+    fn without_config_the_environment_is_used() {
+        let unset = NetworkConfig::default();
+        // reqwest reads the environment itself.
+        assert!(build_proxy(&unset, Some(env())).unwrap().is_none());
+        assert_eq!(
+            resolve_browser_proxy(&unset, Some(env()), Some(env_bypass())),
+            Some(("http://env-proxy:3128".into(), "localhost".into()))
+        );
+        assert_eq!(
+            resolve_browser_proxy(&unset, None, Some(env_bypass())),
+            None
+        );
+    }
+
+    #[test]
+    fn config_proxy_wins_over_the_environment() {
+        assert!(build_proxy(&corp(), None).unwrap().is_some());
+        assert_eq!(
+            resolve_browser_proxy(&corp(), Some(env()), Some(env_bypass())),
+            Some((
+                "http://user:pw@proxy.corp:8080".into(),
+                ".corp,10.0.0.0/8".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn bypass_lists_apply_to_the_environment_proxy() {
+        let bypass_only = NetworkConfig {
+            proxy: None,
+            no_proxy: vec!["localhost".into()],
+        };
+        assert!(build_proxy(&bypass_only, Some(env())).unwrap().is_some());
+        assert!(build_proxy(&bypass_only, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_proxy_urls_are_rejected() {
+        let invalid = NetworkConfig {
+            proxy: Some("http://[bad".into()),
+            no_proxy: Vec::new(),
+        };
+        assert!(build_proxy(&invalid, None).is_err());
+    }
+}
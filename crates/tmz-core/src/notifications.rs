@@ -0,0 +1,143 @@
+//! Desktop notification rules for new messages (`[[notifications]]`).
+//!
+//! Each rule targets messages by mention (`mentions_me`), conversation,
+//! keywords, and importance; a new message from someone else that matches
+//! any rule shows one desktop notification, titled after the first rule it
+//! matched. As with webhooks, only messages sync finds for the first time
+//! are considered, and a conversation's first sync only fills the cache.
+
+use crate::cache::CachedMessage;
+use crate::config::NotificationRule;
+
+/// Longest message excerpt shown in a notification, in characters.
+const EXCERPT_CHARS: usize = 200;
+
+/// A desktop notification waiting to be shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingNotification {
+    /// Title: the rule name and the conversation.
+    pub title: String,
+    /// Body: the sender and an excerpt of the message.
+    pub body: String,
+}
+
+/// Whether `msg` satisfies every condition of `rule`.
+#[must_use]
+pub fn matches(rule: &NotificationRule, msg: &CachedMessage) -> bool {
+    if msg.is_from_me || (rule.mentions_me && !msg.mentions_me) {
+        return false;
+    }
+    if rule.important && !msg.important {
+        return false;
+    }
+    if !rule.conversations.is_empty() && !rule.conversations.contains(&msg.conversation_id) {
+        return false;
+    }
+    if rule.keywords.is_empty() {
+        return true;
+    }
+    let text = msg.content.to_lowercase();
+    rule.keywords
+        .iter()
+        .any(|word| text.contains(&word.to_lowercase()))
+}
+
+/// The notification for `msg` in the conversation `conversation_name`, if
+/// any of `rules` matches it.
+#[must_use]
+pub fn notification(
+    rules: &[NotificationRule],
+    conversation_name: &str,
+    msg: &CachedMessage,
+) -> Option<PendingNotification> {
+    let rule = rules.iter().find(|rule| matches(rule, msg))?;
+    let title = if rule.name.is_empty() {
+        conversation_name.to_string()
+    } else {
+        format!("{}: {conversation_name}", rule.name)
+    };
+    let excerpt: String = msg.content.chars().take(EXCERPT_CHARS).collect();
+    Some(PendingNotification {
+        title,
+        body: format!("{}: {}", msg.from_display_name, excerpt.trim()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> CachedMessage {
+        CachedMessage {
+            id: "1700000000000".to_string(),
+            conversation_id: "19:ops@thread.tacv2".to_string(),
+            from_display_name: "Alex".to_string(),
+            content: "Deploy is RED again".to_string(),
+            content_html: "<p>Deploy is RED again</p>".to_string(),
+            message_type: "RichText/Html".to_string(),
+            compose_time: "2024-05-01T10:00:00Z".to_string(),
+            is_from_me: false,
+            mentions_me: false,
+            unknown_type: false,
+            subject: String::new(),
+            important: false,
+            raw_json: "{}".to_string(),
+        }
+    }
+
+    fn mentions() -> NotificationRule {
+        NotificationRule {
+            name: "Mentions".to_string(),
+            mentions_me: true,
+            ..NotificationRule::default()
+        }
+    }
+
+    fn deploys() -> NotificationRule {
+        NotificationRule {
+            name: "Deploys".to_string(),
+            conversations: vec!["19:ops@thread.tacv2".to_string()],
+            keywords: vec!["red".to_string()],
+            ..NotificationRule::default()
+        }
+    }
+
+    #[test]
+    fn mention_rules_need_a_mention() {
+        let mut msg = message();
+        assert!(!matches(&mentions(), &msg));
+        msg.mentions_me = true;
+        assert!(matches(&mentions(), &msg));
+    }
+
+    #[test]
+    fn keywords_match_case_insensitively_in_listed_conversations() {
+        let mut msg = message();
+        assert!(matches(&deploys(), &msg));
+        msg.conversation_id = "19:other@thread.v2".to_string();
+        assert!(!matches(&deploys(), &msg));
+    }
+
+    #[test]
+    fn the_first_matching_rule_titles_the_notification() {
+        let rules = [mentions(), deploys()];
+        let mut msg = message();
+        let shown = notification(&rules, "Ops", &msg).unwrap();
+        assert_eq!(shown.title, "Deploys: Ops");
+        assert_eq!(shown.body, "Alex: Deploy is RED again");
+
+        msg.mentions_me = true;
+        assert_eq!(
+            notification(&rules, "Ops", &msg).unwrap().title,
+            "Mentions: Ops"
+        );
+    }
+
+    #[test]
+    fn own_messages_never_notify() {
+        let mut msg = message();
+        msg.mentions_me = true;
+        msg.is_from_me = true;
+        assert_eq!(notification(&[mentions(), deploys()], "Ops", &msg), None);
+    }
+}
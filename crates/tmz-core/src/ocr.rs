@@ -0,0 +1,177 @@
+//! Optional OCR of cached images.
+//!
+//! Lots of shared content is screenshots. When `[ocr] enabled = true`, text
+//! is extracted from cached images with `tesseract` or an HTTP endpoint and
+//! stored in the cache's `image_text` index, so message searches also match
+//! text inside images.
+
+use crate::CoreError;
+use crate::cache::Cache;
+use crate::config::{OcrConfig, OcrEngine};
+use crate::trace::TracedSend;
+use std::path::Path;
+
+/// Extract text from the image file at `path`.
+///
+/// # Errors
+///
+/// Returns an error if the OCR backend is unavailable or fails.
+pub async fn extract_text(config: &OcrConfig, path: &Path) -> Result<String, CoreError> {
+    match config.engine {
+        OcrEngine::Tesseract => tesseract(config, path).await,
+        OcrEngine::Http => http(config, path).await,
+    }
+}
+
+async fn tesseract(config: &OcrConfig, path: &Path) -> Result<String, CoreError> {
+    let output = tokio::process::Command::new(&config.tesseract)
+        .arg(path)
+        .arg("stdout")
+        .args(["-l", &config.languages])
+        .output()
+        .await
+        .map_err(|e| CoreError::Other(format!("running {}: {e}", config.tesseract)))?;
+    if !output.status.success() {
+        return Err(CoreError::Other(format!(
+            "tesseract failed on {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+async fn http(config: &OcrConfig, path: &Path) -> Result<String, CoreError> {
+    let endpoint = config.endpoint.as_deref().ok_or_else(|| {
+        CoreError::Config("[ocr] engine = \"http\" needs an endpoint".to_string())
+    })?;
+    let bytes = tokio::fs::read(path).await?;
+    let response = crate::network::client()?
+        .post(endpoint)
+        .header("Content-Type", "application/octet-stream")
+        .body(bytes)
+        .send_traced()
+        .await
+        .map_err(|e| CoreError::api(format!("OCR request failed: {e}")))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| CoreError::api(format!("reading OCR response: {e}")))?;
+    if !status.is_success() {
+        log::debug!("OCR failed with {status}: {body}");
+        return Err(CoreError::Api(crate::error::ApiError::from_response(
+            "OCR",
+            status.as_u16(),
+            &body,
+        )));
+    }
+    Ok(text_from_response(&body))
+}
+
+/// Plain-text body, or the `text` field of a JSON body.
+fn text_from_response(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| v["text"].as_str().map(ToString::to_string))
+        .unwrap_or_else(|| body.to_string())
+}
+
+/// Run OCR over up to `limit` cached images that have not been processed
+/// yet. Returns the number of images indexed.
+///
+/// Images that fail are logged and retried on the next pass.
+///
+/// # Errors
+///
+/// Returns an error if the cache cannot be read or written, or if every
+/// image failed (usually a missing `tesseract` or unreachable endpoint).
+pub async fn index_pending(
+    cache: &Cache,
+    config: &OcrConfig,
+    limit: usize,
+) -> Result<u64, CoreError> {
+    let pending = cache.pending_ocr_images(limit).await?;
+    let mut indexed = 0u64;
+    let mut last_error = None;
+    for image in &pending {
+        match extract_text(config, &image.path).await {
+            Ok(text) => {
+                cache.store_image_text(image, &normalize(&text)).await?;
+                indexed += 1;
+            }
+            Err(e) => {
+                log::warn!("OCR failed for {}: {e}", image.url);
+                last_error = Some(e);
+            }
+        }
+    }
+    match last_error {
+        Some(e) if indexed == 0 => Err(e),
+        _ => Ok(indexed),
+    }
+}
+
+/// Collapse the line noise OCR produces into single-spaced text.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn indexes_cached_images_and_finds_their_text() {
+        let dir = TempDir::new("ocr").unwrap();
+        let blobs = crate::blobs::BlobStore::new(dir.join("attachments"));
+        let cache = Cache::open_with_blobs(&dir.join("cache.db"), blobs)
+            .await
+            .unwrap();
+        let url = "https://x.test/img/screenshot.png";
+        let raw = serde_json::json!({
+            "id": "1",
+            "messagetype": "RichText/Html",
+            "content": format!("<p>see this</p><img itemtype=\"http://schema.skype.com/AMSImage\" src=\"{url}\">"),
+            "composetime": "2026-01-01T09:00:00Z",
+        });
+        let msg = crate::cache::parse_message(
+            &raw.into(),
+            "19:x",
+            None,
+            &crate::MessagesConfig::default(),
+        )
+        .unwrap();
+        cache.upsert_message(&msg).await.unwrap();
+        cache.cache_image(url, b"png", "image/png").await.unwrap();
+
+        // A fake tesseract that prints fixed text for any image.
+        let script = dir.join("fake-tesseract");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\necho 'Error 42:\n  disk   quota exceeded'\n",
+        )
+        .unwrap();
+        let mut perms = std::fs::metadata(&script).unwrap().permissions();
+        std::os::unix::fs::PermissionsExt::set_mode(&mut perms, 0o755);
+        std::fs::set_permissions(&script, perms).unwrap();
+        let config = OcrConfig {
+            enabled: true,
+            tesseract: script.display().to_string(),
+            ..OcrConfig::default()
+        };
+
+        assert_eq!(index_pending(&cache, &config, 10).await.unwrap(), 1);
+        assert_eq!(index_pending(&cache, &config, 10).await.unwrap(), 0);
+        let found = cache.search("quota", 10).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].message.id, "1");
+    }
+
+    #[test]
+    fn json_responses_yield_their_text() {
+        assert_eq!(text_from_response(r#"{"text":"from json"}"#), "from json");
+    }
+}
@@ -0,0 +1,276 @@
+//! Org chart lookups (`tmz people manager` and `tmz people reports`).
+//!
+//! Managers and direct reports come from Graph's `manager` and
+//! `directReports` relations. Everyone fetched is stored in the cache's
+//! `people` table, so names resolve without a Graph search next time and
+//! lookups still answer from the cache when Graph cannot be reached.
+
+use crate::CoreError;
+use crate::cache::{Cache, CachedPerson};
+use crate::teams::TeamsClient;
+
+/// Most managers followed up a chain.
+pub const MAX_CHAIN: usize = 12;
+
+/// Where an answer came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// Fetched from Graph just now.
+    Graph,
+    /// Read from the cache because Graph failed.
+    Cache,
+}
+
+/// A person from a Graph user object.
+#[must_use]
+pub fn person_from_graph(value: &serde_json::Value) -> Option<CachedPerson> {
+    let field = |key: &str| {
+        value[key]
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+    };
+    Some(CachedPerson {
+        id: field("id")?,
+        display_name: field("displayName").unwrap_or_default(),
+        email: field("mail").or_else(|| field("userPrincipalName")),
+        job_title: field("jobTitle"),
+        manager_id: None,
+        updated_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    })
+}
+
+async fn fetch_user(
+    client: &TeamsClient,
+    cache: &Cache,
+    id: &str,
+) -> Result<CachedPerson, CoreError> {
+    let person = person_from_graph(&client.get_user(id).await?)
+        .ok_or_else(|| CoreError::api(format!("no user '{id}'")))?;
+    cache.upsert_person(&person).await?;
+    Ok(person)
+}
+
+/// People matching `query`.
+///
+/// `query` is `me`, an email address or object ID, or part of a name.
+/// Names are looked up among stored people first, then with a Graph
+/// search. An exact name or email match is returned alone.
+///
+/// # Errors
+///
+/// Returns an error if a Graph lookup is needed and fails.
+pub async fn resolve(
+    client: &TeamsClient,
+    cache: &Cache,
+    query: &str,
+) -> Result<Vec<CachedPerson>, CoreError> {
+    let query = query.trim();
+    if query.eq_ignore_ascii_case("me") {
+        let me = client.user_id().await?;
+        return Ok(vec![fetch_user(client, cache, &me).await?]);
+    }
+    let is_object_id = query.len() == 36 && query.matches('-').count() == 4;
+    if query.contains('@') || is_object_id {
+        return Ok(vec![fetch_user(client, cache, query).await?]);
+    }
+
+    let exact = |p: &CachedPerson| {
+        p.display_name.eq_ignore_ascii_case(query)
+            || p.email
+                .as_deref()
+                .is_some_and(|e| e.eq_ignore_ascii_case(query))
+    };
+    let mut found = cache.find_people(query).await?;
+    if found.is_empty() {
+        for value in client.search_users(query).await? {
+            if let Some(person) = person_from_graph(&value) {
+                cache.upsert_person(&person).await?;
+                found.push(person);
+            }
+        }
+    }
+    if let Some(person) = found.iter().find(|p| exact(p)) {
+        return Ok(vec![person.clone()]);
+    }
+    Ok(found)
+}
+
+/// `person`'s manager, or `None` at the top of the org.
+///
+/// # Errors
+///
+/// Returns an error if Graph fails and the cache does not know the manager.
+pub async fn manager(
+    client: &TeamsClient,
+    cache: &Cache,
+    person: &CachedPerson,
+) -> Result<(Option<CachedPerson>, Origin), CoreError> {
+    match client.get_manager(&person.id).await {
+        Ok(value) => {
+            let manager = value.as_ref().and_then(person_from_graph);
+            if let Some(ref manager) = manager {
+                cache.upsert_person(manager).await?;
+            }
+            cache.upsert_person(person).await?;
+            cache
+                .set_manager(&person.id, manager.as_ref().map(|m| m.id.as_str()))
+                .await?;
+            Ok((manager, Origin::Graph))
+        }
+        Err(e) => {
+            let stored = cache.get_person(&person.id).await?;
+            let Some(manager_id) = stored.and_then(|p| p.manager_id) else {
+                return Err(e);
+            };
+            log::debug!("manager of {}: {e}; using the cache", person.id);
+            Ok((cache.get_person(&manager_id).await?, Origin::Cache))
+        }
+    }
+}
+
+/// `person`'s managers, nearest first, up to [`MAX_CHAIN`].
+///
+/// # Errors
+///
+/// Returns an error if the first manager cannot be looked up.
+pub async fn chain(
+    client: &TeamsClient,
+    cache: &Cache,
+    person: &CachedPerson,
+) -> Result<(Vec<CachedPerson>, Origin), CoreError> {
+    let mut chain: Vec<CachedPerson> = Vec::new();
+    let mut origin = Origin::Graph;
+    let mut current = person.clone();
+    while chain.len() < MAX_CHAIN {
+        let (next, from) = match manager(client, cache, &current).await {
+            Ok(found) => found,
+            Err(e) if chain.is_empty() => return Err(e),
+            Err(e) => {
+                log::debug!("manager of {}: {e}", current.id);
+                break;
+            }
+        };
+        if from == Origin::Cache {
+            origin = Origin::Cache;
+        }
+        match next {
+            Some(next) if next.id != person.id && !chain.iter().any(|p| p.id == next.id) => {
+                chain.push(next.clone());
+                current = next;
+            }
+            _ => break,
+        }
+    }
+    Ok((chain, origin))
+}
+
+/// `person`'s direct reports, by name.
+///
+/// # Errors
+///
+/// Returns an error if Graph fails and the cache has no reports stored.
+pub async fn reports(
+    client: &TeamsClient,
+    cache: &Cache,
+    person: &CachedPerson,
+) -> Result<(Vec<CachedPerson>, Origin), CoreError> {
+    match client.get_direct_reports(&person.id).await {
+        Ok(values) => {
+            let mut reports: Vec<CachedPerson> =
+                values.iter().filter_map(person_from_graph).collect();
+            reports.sort_by(|a, b| a.display_name.cmp(&b.display_name));
+            cache.upsert_person(person).await?;
+            cache.set_reports(&person.id, &reports).await?;
+            Ok((reports, Origin::Graph))
+        }
+        Err(e) => {
+            let stored = cache.reports_of(&person.id).await?;
+            if stored.is_empty() {
+                return Err(e);
+            }
+            log::debug!("reports of {}: {e}; using the cache", person.id);
+            Ok((stored, Origin::Cache))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn person(id: &str, name: &str) -> CachedPerson {
+        person_from_graph(&serde_json::json!({
+            "id": id,
+            "displayName": name,
+            "mail": null,
+            "userPrincipalName": format!("{id}@example.com"),
+            "jobTitle": "",
+        }))
+        .unwrap()
+    }
+
+    fn names(people: Vec<CachedPerson>) -> Vec<String> {
+        people.into_iter().map(|p| p.display_name).collect()
+    }
+
+    /// Dana, with Bo and Alex reporting to them.
+    async fn team(dir: &TempDir) -> Cache {
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        cache.upsert_person(&person("d", "Dana Lee")).await.unwrap();
+        cache
+            .set_reports("d", &[person("b", "Bo"), person("a", "Alex Schmidt")])
+            .await
+            .unwrap();
+        cache
+    }
+
+    #[test]
+    fn graph_users_fall_back_to_their_upn_and_drop_empty_titles() {
+        let dana = person("d", "Dana Lee");
+        assert_eq!(dana.email.as_deref(), Some("d@example.com"));
+        assert_eq!(dana.job_title, None);
+    }
+
+    #[tokio::test]
+    async fn reports_are_listed_by_name() {
+        let dir = TempDir::new("org").unwrap();
+        let cache = team(&dir).await;
+        assert_eq!(
+            names(cache.reports_of("d").await.unwrap()),
+            ["Alex Schmidt", "Bo"]
+        );
+    }
+
+    #[tokio::test]
+    async fn upserts_without_a_manager_keep_the_known_one() {
+        let dir = TempDir::new("org-manager").unwrap();
+        let cache = team(&dir).await;
+        cache
+            .upsert_person(&person("a", "Alex Schmidt"))
+            .await
+            .unwrap();
+        let alex = cache.get_person("a").await.unwrap().unwrap();
+        assert_eq!(alex.manager_id.as_deref(), Some("d"));
+    }
+
+    #[tokio::test]
+    async fn replacing_reports_drops_people_who_moved() {
+        let dir = TempDir::new("org-moved").unwrap();
+        let cache = team(&dir).await;
+        cache.set_reports("d", &[person("b", "Bo")]).await.unwrap();
+        assert_eq!(names(cache.reports_of("d").await.unwrap()), ["Bo"]);
+    }
+
+    #[tokio::test]
+    async fn people_are_found_by_name() {
+        let dir = TempDir::new("org-find").unwrap();
+        let cache = team(&dir).await;
+        assert_eq!(
+            names(cache.find_people("alex").await.unwrap()),
+            ["Alex Schmidt"]
+        );
+        assert_eq!(names(cache.find_people("bo").await.unwrap())[0], "Bo");
+    }
+}
@@ -0,0 +1,326 @@
+//! Undo-send outbox.
+//!
+//! With `[sending] undo_window` set, frontends queue outgoing messages in the
+//! cache's `outbox` table instead of sending them. Whoever is running when a
+//! message falls due sends it: the daemon checks every second, the TUI on
+//! each tick, and `tmz msg` waits out the window itself when no daemon is
+//! running. Until then `tmz outbox cancel <id>` (or `Ctrl+Z` in the TUI)
+//! can take it back. Claiming is atomic, so a message is sent at most once.
+//!
+//! Messages queued with `tmz msg --if-available` are held until the
+//! recipient's presence turns Available; the daemon polls the presence of
+//! everyone waited for and releases their messages to the next dispatch.
+//!
+//! Messages sent right away go through the same table via [`track`], so
+//! every outgoing message has a delivery state (queued, sending or
+//! uploading, sent, failed) and, once Teams accepts it, the message ID and
+//! arrival time from [`acknowledgement`]. `tmz outbox history` lists them,
+//! and the TUI marks acknowledged messages with a check mark.
+
+use crate::CoreError;
+use crate::cache::{Cache, OutboxItem, OutgoingKind};
+use crate::teams::models::PresenceStatus;
+use chrono::{SecondsFormat, TimeZone as _, Utc};
+use std::future::Future;
+
+/// What the service reported on accepting a message.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Acknowledgement {
+    /// Message ID assigned by the service.
+    pub message_id: Option<String>,
+    /// When it arrived (UTC, RFC 3339).
+    pub sent_at: Option<String>,
+}
+
+/// Message ID and arrival time from the response to a send.
+///
+/// The chat service answers with `{"OriginalArrivalTime": <ms>}` and the
+/// message's URL in `Location`, which [`crate::TeamsClient`] adds as `id`.
+/// Chat message IDs are the arrival time, so it stands in when `id` is
+/// missing.
+#[must_use]
+pub fn acknowledgement(response: &serde_json::Value) -> Acknowledgement {
+    let arrival = response["OriginalArrivalTime"].as_i64();
+    let message_id = response["id"]
+        .as_str()
+        .map(str::to_string)
+        .or_else(|| arrival.map(|ms| ms.to_string()));
+    let sent_at = arrival
+        .and_then(|ms| Utc.timestamp_millis_opt(ms).single())
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true));
+    Acknowledgement {
+        message_id,
+        sent_at,
+    }
+}
+
+/// Send a message right away with `send`, recording it in the outbox of
+/// `cache` (if any) as `sending` (or `uploading` for files) and then as
+/// `sent` or `failed`.
+///
+/// Failing to record is logged and does not stop the send.
+///
+/// # Errors
+///
+/// Returns the error of `send`.
+pub async fn track<Fut>(
+    cache: Option<&Cache>,
+    conversation_id: &str,
+    content: &str,
+    kind: OutgoingKind,
+    send: Fut,
+) -> Result<serde_json::Value, CoreError>
+where
+    Fut: Future<Output = Result<serde_json::Value, CoreError>>,
+{
+    let item = match cache {
+        Some(cache) => match cache.record_outgoing(conversation_id, content, kind).await {
+            Ok(item) => Some(item),
+            Err(e) => {
+                log::warn!("recording outgoing message: {e}");
+                None
+            }
+        },
+        None => None,
+    };
+    let result = send.await;
+    if let (Some(cache), Some(item)) = (cache, item)
+        && let Err(e) = cache.finish_outgoing(item.id, &result).await
+    {
+        log::warn!("recording delivery of message {}: {e}", item.id);
+    }
+    result
+}
+
+/// Send due outbox messages (only `id`, if given) with `send_one`, recording
+/// each outcome. Returns the messages that were sent.
+///
+/// Failed sends are logged and marked `failed`; they are not retried. Sent
+/// ones keep the message ID and time the service acknowledged.
+///
+/// # Errors
+///
+/// Returns an error if the outbox cannot be read or updated.
+pub async fn dispatch_due<F, Fut>(
+    cache: &Cache,
+    id: Option<i64>,
+    send_one: F,
+) -> Result<Vec<OutboxItem>, CoreError>
+where
+    F: Fn(String, String) -> Fut,
+    Fut: Future<Output = Result<serde_json::Value, CoreError>>,
+{
+    let mut sent = Vec::new();
+    for mut item in cache.claim_due_outgoing(id).await? {
+        let result = send_one(item.conversation_id.clone(), item.content.clone()).await;
+        cache.finish_outgoing(item.id, &result).await?;
+        match result {
+            Ok(response) => {
+                let ack = acknowledgement(&response);
+                item.status = "sent".to_string();
+                item.message_id = ack.message_id;
+                item.sent_at = ack.sent_at;
+                sent.push(item);
+            }
+            Err(e) => log::warn!("sending outbox message {} failed: {e}", item.id),
+        }
+    }
+    Ok(sent)
+}
+
+/// Release the messages held for recipients who are now Available.
+///
+/// Each recipient's presence is looked up once with `presence_of`; failed
+/// lookups are logged and retried next time. Returns how many messages
+/// were released.
+///
+/// # Errors
+///
+/// Returns an error if the outbox cannot be read or updated.
+pub async fn release_available<F, Fut>(cache: &Cache, presence_of: F) -> Result<usize, CoreError>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<PresenceStatus, CoreError>>,
+{
+    let waiting = cache.waiting_for_presence().await?;
+    let mut users: Vec<&str> = waiting
+        .iter()
+        .filter_map(|item| item.until_available.as_deref())
+        .collect();
+    users.sort_unstable();
+    users.dedup();
+
+    let mut released = 0;
+    for user in users {
+        match presence_of(user.to_string()).await {
+            Ok(PresenceStatus::Available) => {}
+            Ok(_) => continue,
+            Err(e) => {
+                log::warn!("checking presence of {user}: {e}");
+                continue;
+            }
+        }
+        for item in &waiting {
+            if item.until_available.as_deref() == Some(user) {
+                cache.release_outgoing(item.id).await?;
+                released += 1;
+            }
+        }
+    }
+    Ok(released)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn sends_due_messages_once() {
+        let dir = TempDir::new("outbox").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let due = cache
+            .queue_outgoing("19:a", "now", Duration::ZERO)
+            .await
+            .unwrap();
+        let later = cache
+            .queue_outgoing("19:a", "later", Duration::from_hours(1))
+            .await
+            .unwrap();
+
+        let calls = Mutex::new(Vec::new());
+        let record = |conv: String, text: String| {
+            calls.lock().unwrap().push(format!("{conv}: {text}"));
+            async { Ok(serde_json::Value::Null) }
+        };
+        let sent = dispatch_due(&cache, None, record).await.unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].id, due.id);
+        assert!(dispatch_due(&cache, None, record).await.unwrap().is_empty());
+        assert_eq!(*calls.lock().unwrap(), ["19:a: now"]);
+
+        let waiting = cache.list_outbox(false, 10).await.unwrap();
+        assert_eq!(waiting.len(), 1);
+        assert_eq!(waiting[0].id, later.id);
+        assert_eq!(cache.list_outbox(true, 10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn cancelled_messages_are_never_sent() {
+        let dir = TempDir::new("outbox-cancel").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let undone = cache
+            .queue_outgoing("19:b", "oops", Duration::ZERO)
+            .await
+            .unwrap();
+        let cancelled = cache.cancel_outgoing(undone.id).await.unwrap().unwrap();
+        assert_eq!(cancelled.content, "oops");
+        assert!(cache.cancel_outgoing(undone.id).await.unwrap().is_none());
+
+        let sent = dispatch_due(&cache, None, |_, _| async {
+            unreachable!("cancelled messages are not sent")
+        })
+        .await
+        .unwrap();
+        assert!(sent.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tracked_sends_record_the_message_id_and_time() {
+        let dir = TempDir::new("outbox-track").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let response = serde_json::json!({
+            "OriginalArrivalTime": 1_772_442_000_123_i64,
+            "id": "1772442000123",
+        });
+        let sent = track(Some(&cache), "19:a", "hello", OutgoingKind::Text, async {
+            let inflight = cache.list_outbox(false, 10).await.unwrap();
+            assert_eq!(inflight[0].status, "sending");
+            Ok(response)
+        })
+        .await;
+        assert!(sent.is_ok());
+
+        let history = cache.outgoing_history(Some("19:a"), 10).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].status, "sent");
+        assert_eq!(history[0].message_id.as_deref(), Some("1772442000123"));
+        assert_eq!(
+            history[0].sent_at.as_deref(),
+            Some("2026-03-02T09:00:00.123Z")
+        );
+    }
+
+    #[tokio::test]
+    async fn tracked_failures_keep_the_error() {
+        let dir = TempDir::new("outbox-track-failed").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let failed = track(
+            Some(&cache),
+            "19:b",
+            "/tmp/report.pdf",
+            OutgoingKind::File,
+            async { Err(CoreError::api("upload failed")) },
+        )
+        .await;
+        assert!(failed.is_err());
+
+        let history = cache.outgoing_history(None, 10).await.unwrap();
+        assert_eq!(
+            (history[0].status.as_str(), history[0].kind.as_str()),
+            ("failed", "file")
+        );
+        assert!(
+            history[0]
+                .error
+                .as_deref()
+                .unwrap()
+                .contains("upload failed")
+        );
+    }
+
+    #[test]
+    fn without_an_id_the_arrival_time_is_the_message_id() {
+        let ack = acknowledgement(&serde_json::json!({ "OriginalArrivalTime": 1000 }));
+        assert_eq!(ack.message_id.as_deref(), Some("1000"));
+    }
+
+    #[tokio::test]
+    async fn holds_messages_until_the_recipient_is_available() {
+        let dir = TempDir::new("outbox-presence").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let busy = cache
+            .queue_until_available("19:a", "quick q", "user-a")
+            .await
+            .unwrap();
+        let free = cache
+            .queue_until_available("19:b", "ping", "user-b")
+            .await
+            .unwrap();
+        assert!(cache.claim_due_outgoing(None).await.unwrap().is_empty());
+
+        let lookups = Mutex::new(0);
+        let presence = |user: String| {
+            *lookups.lock().unwrap() += 1;
+            async move {
+                Ok(if user == "user-b" {
+                    PresenceStatus::Available
+                } else {
+                    PresenceStatus::DoNotDisturb
+                })
+            }
+        };
+        assert_eq!(release_available(&cache, presence).await.unwrap(), 1);
+        assert_eq!(*lookups.lock().unwrap(), 2);
+        let claimed = cache.claim_due_outgoing(None).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].id, free.id);
+        assert_eq!(claimed[0].until_available, None);
+
+        let waiting = cache.waiting_for_presence().await.unwrap();
+        assert_eq!(waiting.len(), 1);
+        assert_eq!(waiting[0].id, busy.id);
+    }
+}
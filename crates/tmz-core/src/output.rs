@@ -0,0 +1,220 @@
+//! Structured output of list commands.
+//!
+//! `--json` and `--format nuon|csv|yaml` go through [`render`], so the
+//! same records reach `jq`, Nushell (`from nuon`), `PowerShell`
+//! (`ConvertFrom-Csv`), and YAML tooling.
+
+use std::fmt::Write as _;
+
+use serde_json::Value;
+
+use crate::CoreError;
+
+/// A structured output format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Pretty-printed JSON.
+    Json,
+    /// Nushell object notation.
+    Nuon,
+    /// RFC 4180 CSV with a header row; nested fields become `parent.child`
+    /// columns.
+    Csv,
+    /// YAML.
+    Yaml,
+}
+
+/// Render `value` in `format`, ending with a newline.
+///
+/// # Errors
+///
+/// Returns an error if `format` is CSV and `value` is not a list of
+/// records, or if YAML serialization fails.
+pub fn render(value: &Value, format: OutputFormat) -> Result<String, CoreError> {
+    let mut out = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(value)
+            .map_err(|e| CoreError::Serialization(e.to_string()))?,
+        OutputFormat::Nuon => {
+            let mut out = String::new();
+            write_nuon(&mut out, value);
+            out
+        }
+        OutputFormat::Csv => to_csv(value)?,
+        OutputFormat::Yaml => {
+            serde_yaml::to_string(value).map_err(|e| CoreError::Serialization(e.to_string()))?
+        }
+    };
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn write_nuon(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(&n.to_string()),
+        Value::String(s) => write_nuon_string(out, s),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_nuon(out, item);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            out.push('{');
+            for (i, (key, item)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                if is_bare_key(key) {
+                    out.push_str(key);
+                } else {
+                    write_nuon_string(out, key);
+                }
+                out.push_str(": ");
+                write_nuon(out, item);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// Keys Nushell reads without quotes.
+fn is_bare_key(key: &str) -> bool {
+    key.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn write_nuon_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{{{:x}}}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn to_csv(value: &Value) -> Result<String, CoreError> {
+    let records = value
+        .as_array()
+        .ok_or_else(|| CoreError::Other("CSV output needs a single list of results".to_string()))?;
+    let rows: Vec<Vec<(String, String)>> = records
+        .iter()
+        .map(|record| {
+            let mut fields = Vec::new();
+            flatten("", record, &mut fields);
+            fields
+        })
+        .collect();
+
+    // Columns in order of first appearance across all rows.
+    let mut columns: Vec<&str> = Vec::new();
+    for row in &rows {
+        for (key, _) in row {
+            if !columns.contains(&key.as_str()) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    write_csv_row(&mut out, columns.iter().copied());
+    for row in &rows {
+        write_csv_row(
+            &mut out,
+            columns.iter().map(|column| {
+                row.iter()
+                    .find(|(key, _)| key == column)
+                    .map_or("", |(_, v)| v.as_str())
+            }),
+        );
+    }
+    Ok(out)
+}
+
+/// Flatten nested objects into `parent.child` fields. Arrays are kept as
+/// JSON text and null becomes an empty field.
+fn flatten(prefix: &str, value: &Value, fields: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, item) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten(&key, item, fields);
+            }
+        }
+        Value::Null => fields.push((prefix.to_string(), String::new())),
+        Value::String(s) => fields.push((prefix.to_string(), s.clone())),
+        other => fields.push((prefix.to_string(), other.to_string())),
+    }
+}
+
+fn write_csv_row<'a>(out: &mut String, fields: impl Iterator<Item = &'a str>) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_records_in_each_format() {
+        let value = serde_json::json!([
+            {"id": "19:a", "display name": "Ops, \"core\"", "message": {"content": "hi\nthere"}},
+            {"id": "19:b", "unread": 2, "tags": ["x"], "message": null},
+        ]);
+
+        assert_eq!(
+            render(&value, OutputFormat::Nuon).unwrap(),
+            "[{\"display name\": \"Ops, \\\"core\\\"\", id: \"19:a\", message: {content: \"hi\\nthere\"}}, \
+             {id: \"19:b\", message: null, tags: [\"x\"], unread: 2}]\n"
+        );
+        assert_eq!(
+            render(&value, OutputFormat::Csv).unwrap(),
+            "display name,id,message.content,message,tags,unread\r\n\
+             \"Ops, \"\"core\"\"\",19:a,\"hi\nthere\",,,\r\n\
+             ,19:b,,,\"[\"\"x\"\"]\",2\r\n"
+        );
+        let yaml = render(&value, OutputFormat::Yaml).unwrap();
+        assert!(
+            yaml.contains("- display name: Ops, \"core\"\n  id: 19:a\n"),
+            "{yaml}"
+        );
+        assert!(render(&serde_json::json!({"chats": []}), OutputFormat::Csv).is_err());
+    }
+}
@@ -6,6 +6,8 @@ use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result, anyhow};
 
+use crate::accounts;
+use crate::config::ConfigLayer;
 use crate::{APP_NAME, AppConfig};
 
 /// Application paths for config, data, and state directories.
@@ -13,12 +15,28 @@ use crate::{APP_NAME, AppConfig};
 pub struct AppPaths {
     /// Path to the configuration file.
     pub config_file: PathBuf,
+    /// System-wide configuration, merged below the user's.
+    pub system_config_file: PathBuf,
+    /// Project-local configuration from the working directory, merged
+    /// above the user's.
+    pub project_config_file: Option<PathBuf>,
     /// Directory for persistent application data.
     pub data_dir: PathBuf,
     /// Directory for application state files.
     pub state_dir: PathBuf,
+    /// Persistent Playwright browser profile holding the SSO session.
+    pub browser_profile_dir: PathBuf,
 }
 
+/// Name of the browser profile directory inside the state directory.
+const BROWSER_PROFILE_DIR: &str = "browser-profile";
+
+/// System-wide configuration file shared by all users.
+pub const SYSTEM_CONFIG_FILE: &str = "/etc/tmz/config.toml";
+
+/// Name of the project-local configuration file.
+pub const PROJECT_CONFIG_FILE: &str = ".tmz.toml";
+
 impl AppPaths {
     /// Discover application paths, optionally overriding the config file location.
     ///
@@ -47,14 +65,37 @@ impl AppPaths {
 
         let data_dir = default_data_dir()?;
         let state_dir = default_state_dir()?;
+        let browser_profile_dir = state_dir.join(BROWSER_PROFILE_DIR);
+
+        let project_config_file = env::current_dir()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+            .ok()
+            .filter(|path| path.is_file());
 
         Ok(Self {
             config_file,
+            system_config_file: PathBuf::from(SYSTEM_CONFIG_FILE),
+            project_config_file,
             data_dir,
             state_dir,
+            browser_profile_dir,
         })
     }
 
+    /// Configuration files in merge order, lowest precedence first. Files
+    /// that do not exist are included and skipped when loading.
+    #[must_use]
+    pub fn config_layers(&self) -> Vec<(ConfigLayer, PathBuf)> {
+        let mut layers = vec![
+            (ConfigLayer::System, self.system_config_file.clone()),
+            (ConfigLayer::User, self.config_file.clone()),
+        ];
+        if let Some(ref project) = self.project_config_file {
+            layers.push((ConfigLayer::Project, project.clone()));
+        }
+        layers
+    }
+
     /// Apply path overrides from configuration.
     ///
     /// # Errors
@@ -62,10 +103,11 @@ impl AppPaths {
     /// Returns an error if override paths cannot be expanded.
     pub fn apply_overrides(mut self, cfg: &AppConfig) -> Result<Self> {
         if let Some(ref data_override) = cfg.paths.data_dir {
-            self.data_dir = expand_str_path(data_override)?;
+            self.data_dir = accounts::scope(expand_str_path(data_override)?);
         }
         if let Some(ref state_override) = cfg.paths.state_dir {
-            self.state_dir = expand_str_path(state_override)?;
+            self.state_dir = accounts::scope(expand_str_path(state_override)?);
+            self.browser_profile_dir = self.state_dir.join(BROWSER_PROFILE_DIR);
         }
         Ok(self)
     }
@@ -147,12 +189,22 @@ pub fn default_config_dir() -> Result<PathBuf> {
         .ok_or_else(|| anyhow!("unable to determine configuration directory"))
 }
 
-/// Get the default data directory (`XDG_DATA_HOME` or fallback).
+/// Get the default data directory (`XDG_DATA_HOME` or fallback) of
+/// the selected account (see [`crate::accounts`]).
 ///
 /// # Errors
 ///
 /// Returns an error if the home directory cannot be determined.
 pub fn default_data_dir() -> Result<PathBuf> {
+    Ok(accounts::scope(base_data_dir()?))
+}
+
+/// The data directory shared by all accounts.
+///
+/// # Errors
+///
+/// Returns an error if the home directory cannot be determined.
+pub fn base_data_dir() -> Result<PathBuf> {
     if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|v| !v.is_empty()) {
         return Ok(PathBuf::from(dir).join(APP_NAME));
     }
@@ -167,12 +219,22 @@ pub fn default_data_dir() -> Result<PathBuf> {
         .ok_or_else(|| anyhow!("unable to determine data directory"))
 }
 
-/// Get the default state directory (`XDG_STATE_HOME` or fallback).
+/// Get the default state directory (`XDG_STATE_HOME` or fallback) of
+/// the selected account (see [`crate::accounts`]).
 ///
 /// # Errors
 ///
 /// Returns an error if the home directory cannot be determined.
 pub fn default_state_dir() -> Result<PathBuf> {
+    Ok(accounts::scope(base_state_dir()?))
+}
+
+/// The state directory shared by all accounts.
+///
+/// # Errors
+///
+/// Returns an error if the home directory cannot be determined.
+pub fn base_state_dir() -> Result<PathBuf> {
     if let Some(dir) = env::var_os("XDG_STATE_HOME").filter(|v| !v.is_empty()) {
         return Ok(PathBuf::from(dir).join(APP_NAME));
     }
@@ -187,12 +249,22 @@ pub fn default_state_dir() -> Result<PathBuf> {
         .ok_or_else(|| anyhow!("unable to determine state directory"))
 }
 
-/// Get the default cache directory (`XDG_CACHE_HOME` or fallback).
+/// Get the default cache directory (`XDG_CACHE_HOME` or fallback) of
+/// the selected account (see [`crate::accounts`]).
 ///
 /// # Errors
 ///
 /// Returns an error if the home directory cannot be determined.
 pub fn default_cache_dir() -> Result<PathBuf> {
+    Ok(accounts::scope(base_cache_dir()?))
+}
+
+/// The cache directory shared by all accounts.
+///
+/// # Errors
+///
+/// Returns an error if the home directory cannot be determined.
+pub fn base_cache_dir() -> Result<PathBuf> {
     if let Some(dir) = env::var_os("XDG_CACHE_HOME").filter(|v| !v.is_empty()) {
         return Ok(PathBuf::from(dir).join(APP_NAME));
     }
@@ -207,6 +279,34 @@ pub fn default_cache_dir() -> Result<PathBuf> {
         .ok_or_else(|| anyhow!("unable to determine cache directory"))
 }
 
+/// Get the default browser profile directory (inside the state directory).
+///
+/// # Errors
+///
+/// Returns an error if the home directory cannot be determined.
+pub fn default_browser_profile_dir() -> Result<PathBuf> {
+    Ok(default_state_dir()?.join(BROWSER_PROFILE_DIR))
+}
+
+/// Total size in bytes of all files below `path` (0 if it does not exist).
+///
+/// Symlinks are not followed; unreadable entries are skipped.
+#[must_use]
+pub fn disk_usage(path: &Path) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path).map_or(0, |entries| {
+        entries
+            .filter_map(std::result::Result::ok)
+            .map(|entry| disk_usage(&entry.path()))
+            .sum()
+    })
+}
+
 /// Write the default configuration file to the specified path.
 ///
 /// # Errors
@@ -0,0 +1,166 @@
+//! Client-side rate limiting of requests to Teams and Graph.
+//!
+//! The chat service throttles accounts that send many requests in a short
+//! time, as a full `tmz sync` does, and answers `429 Too Many Requests` for
+//! a while after. Each host therefore gets a token bucket: `burst` requests
+//! go out at once, later ones are spaced to `[runtime.rate_limit]
+//! requests_per_second`. A `429` [pauses](pause) the host for its
+//! `Retry-After`, holding back every request to it, not just the one
+//! being retried (see [`crate::retry`]).
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::config::RateLimitConfig;
+
+static REQUESTS_PER_SECOND: AtomicU32 = AtomicU32::new(5);
+static BURST: AtomicU32 = AtomicU32::new(10);
+
+static BUCKETS: Mutex<BTreeMap<String, Bucket>> = Mutex::new(BTreeMap::new());
+
+/// Use `config` for the requests this process sends from now on.
+pub fn configure(config: &RateLimitConfig) {
+    REQUESTS_PER_SECOND.store(config.requests_per_second, Ordering::Relaxed);
+    BURST.store(config.burst.max(1), Ordering::Relaxed);
+}
+
+/// Wait until a request to `host` may be sent.
+pub(crate) async fn acquire(host: &str) {
+    let rate = REQUESTS_PER_SECOND.load(Ordering::Relaxed);
+    let burst = BURST.load(Ordering::Relaxed);
+    let delay = with_bucket(host, |bucket| {
+        if rate == 0 {
+            bucket.paused_for(Instant::now())
+        } else {
+            bucket.reserve(f64::from(rate), f64::from(burst), Instant::now())
+        }
+    });
+    if delay.is_zero() {
+        return;
+    }
+    log::debug!(
+        "rate limit: holding a request to {host} for {}ms",
+        delay.as_millis()
+    );
+    tokio::time::sleep(delay).await;
+    // A 429 may have paused the host while this request waited.
+    let paused = with_bucket(host, |bucket| bucket.paused_for(Instant::now()));
+    if !paused.is_zero() {
+        tokio::time::sleep(paused).await;
+    }
+}
+
+/// Hold back requests to `host` for `duration`, after it throttled us.
+pub(crate) fn pause(host: &str, duration: Duration) {
+    log::info!(
+        "{host} is throttling requests, pausing for {}s",
+        duration.as_secs()
+    );
+    with_bucket(host, |bucket| bucket.pause(Instant::now() + duration));
+}
+
+fn with_bucket<T>(host: &str, f: impl FnOnce(&mut Bucket) -> T) -> T {
+    f(BUCKETS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(host.to_string())
+        .or_insert_with(|| Bucket::new(f64::from(BURST.load(Ordering::Relaxed)), Instant::now())))
+}
+
+/// Token bucket of one host. Tokens go negative while requests queue, and
+/// do not refill before `updated`, which a pause moves into the future.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl Bucket {
+    const fn new(tokens: f64, now: Instant) -> Self {
+        Self {
+            tokens,
+            updated: now,
+            paused_until: None,
+        }
+    }
+
+    /// Take a token at `now`, returning how long to wait before using it.
+    fn reserve(&mut self, rate: f64, burst: f64, now: Instant) -> Duration {
+        if now > self.updated {
+            let elapsed = (now - self.updated).as_secs_f64();
+            self.tokens = elapsed.mul_add(rate, self.tokens).min(burst);
+            self.updated = now;
+        }
+        self.tokens -= 1.0;
+        let queued = if self.tokens < 0.0 {
+            Duration::from_secs_f64(-self.tokens / rate)
+        } else {
+            Duration::ZERO
+        };
+        self.updated.saturating_duration_since(now) + queued
+    }
+
+    /// Stop handing out tokens until `until`.
+    fn pause(&mut self, until: Instant) {
+        self.tokens = self.tokens.min(0.0);
+        self.updated = self.updated.max(until);
+        self.paused_until = Some(self.paused_until.map_or(until, |paused| paused.max(until)));
+    }
+
+    /// Time left of a pause at `now`.
+    fn paused_for(&self, now: Instant) -> Duration {
+        self.paused_until
+            .map_or(Duration::ZERO, |until| until.saturating_duration_since(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ms: u64) -> Instant {
+        static START: std::sync::LazyLock<Instant> = std::sync::LazyLock::new(Instant::now);
+        *START + Duration::from_millis(ms)
+    }
+
+    /// Reserve at 2 requests per second with a burst of 3, in milliseconds.
+    fn reserve(bucket: &mut Bucket, ms: u64) -> u128 {
+        bucket.reserve(2.0, 3.0, at(ms)).as_millis()
+    }
+
+    #[test]
+    fn bursts_then_spaces_requests() {
+        let mut bucket = Bucket::new(3.0, at(0));
+        for _ in 0..3 {
+            assert_eq!(reserve(&mut bucket, 0), 0);
+        }
+        assert_eq!(reserve(&mut bucket, 0), 500);
+        assert_eq!(reserve(&mut bucket, 0), 1000);
+        assert_eq!(reserve(&mut bucket, 1000), 500);
+    }
+
+    #[test]
+    fn idle_time_refills_up_to_the_burst_only() {
+        let mut bucket = Bucket::new(0.0, at(0));
+        for _ in 0..3 {
+            assert_eq!(reserve(&mut bucket, 10_000), 0);
+        }
+        assert_eq!(reserve(&mut bucket, 10_000), 500);
+    }
+
+    #[test]
+    fn throttling_pauses_then_resumes_from_an_empty_bucket() {
+        // A 429 with Retry-After: 5.
+        let mut bucket = Bucket::new(3.0, at(0));
+        bucket.pause(at(5000));
+        assert_eq!(bucket.paused_for(at(2000)).as_millis(), 3000);
+        assert_eq!(reserve(&mut bucket, 0), 5500);
+        assert_eq!(reserve(&mut bucket, 5000), 1000);
+        assert_eq!(bucket.paused_for(at(6000)).as_millis(), 0);
+    }
+}
@@ -0,0 +1,129 @@
+//! Message reactions: the emoji behind Teams' emotion keys, and the choices
+//! offered by the TUI reaction picker.
+//!
+//! Teams names the six classic reactions (`like`, `heart`, ...) and names
+//! the extended ones after their code point, e.g. `1f680_rocket`, so any key
+//! of that form can be shown as its emoji.
+
+/// The classic Teams reactions, in the order Teams shows them.
+pub const COMMON: &[(&str, &str)] = &[
+    ("like", "\u{1f44d}"),
+    ("heart", "\u{2764}\u{fe0f}"),
+    ("laugh", "\u{1f606}"),
+    ("surprised", "\u{1f62e}"),
+    ("sad", "\u{1f622}"),
+    ("angry", "\u{1f621}"),
+];
+
+/// Other names accepted for the classic reactions.
+const NAMES: &[(&str, &str)] = &[
+    ("thumbsup", "like"),
+    ("+1", "like"),
+    ("love", "heart"),
+    ("haha", "laugh"),
+    ("wow", "surprised"),
+    ("cry", "sad"),
+];
+
+/// How many recently used reactions are remembered.
+pub const MAX_RECENT: usize = 8;
+
+/// The emoji for a reaction key, if it is a classic reaction or a
+/// `<codepoint>_<name>` key.
+#[must_use]
+pub fn emoji(key: &str) -> Option<String> {
+    if let Some((_, emoji)) = COMMON.iter().find(|(k, _)| *k == key) {
+        return Some((*emoji).to_string());
+    }
+    let (code, _) = key.split_once('_')?;
+    u32::from_str_radix(code, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .map(String::from)
+}
+
+/// The reaction key for `input`: a key itself (`like`, `1f680_rocket`),
+/// another name of a classic reaction (`thumbsup`), or its emoji.
+#[must_use]
+pub fn key_for(input: &str) -> Option<String> {
+    let input = input.trim();
+    if let Some((_, key)) = NAMES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(input))
+    {
+        return Some((*key).to_string());
+    }
+    let bare = |s: &str| s.replace('\u{fe0f}', "");
+    if let Some((key, _)) = COMMON
+        .iter()
+        .find(|(key, emoji)| *key == input || bare(emoji) == bare(input))
+    {
+        return Some((*key).to_string());
+    }
+    emoji(input).map(|_| input.to_string())
+}
+
+/// Reaction keys for the picker: recently used first, then the classic
+/// reactions, then `custom` (`[display] reactions`), without duplicates.
+#[must_use]
+pub fn picker_keys(recent: &[String], custom: &[String]) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    let all = recent
+        .iter()
+        .map(String::as_str)
+        .chain(COMMON.iter().map(|(k, _)| *k))
+        .chain(custom.iter().map(String::as_str));
+    for key in all {
+        let key = key.trim();
+        if !key.is_empty() && !keys.iter().any(|k| k == key) {
+            keys.push(key.to_string());
+        }
+    }
+    keys
+}
+
+/// Move `key` to the front of `recent`, keeping at most [`MAX_RECENT`].
+pub fn remember(recent: &mut Vec<String>, key: &str) {
+    recent.retain(|k| k != key);
+    recent.insert(0, key.to_string());
+    recent.truncate(MAX_RECENT);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_unicode_keys_map_to_emoji() {
+        assert_eq!(emoji("like").as_deref(), Some("\u{1f44d}"));
+        assert_eq!(emoji("1f680_rocket").as_deref(), Some("\u{1f680}"));
+        assert_eq!(emoji("shrug"), None);
+    }
+
+    #[test]
+    fn emoji_and_aliases_map_to_keys() {
+        assert_eq!(key_for("\u{2764}").as_deref(), Some("heart"));
+        assert_eq!(key_for("ThumbsUp").as_deref(), Some("like"));
+        assert_eq!(key_for("1f680_rocket").as_deref(), Some("1f680_rocket"));
+        assert_eq!(key_for("\u{1f680}"), None);
+    }
+
+    #[test]
+    fn recent_reactions_move_to_the_front_once() {
+        let mut recent = Vec::new();
+        remember(&mut recent, "laugh");
+        remember(&mut recent, "1f680_rocket");
+        remember(&mut recent, "laugh");
+        assert_eq!(recent, ["laugh", "1f680_rocket"]);
+    }
+
+    #[test]
+    fn the_picker_orders_recent_common_and_custom_keys() {
+        let recent = ["laugh".to_string(), "1f680_rocket".to_string()];
+        let custom = vec!["1f680_rocket".to_string(), "1f389_partypopper".to_string()];
+        let keys = picker_keys(&recent, &custom);
+        assert_eq!(keys.len(), 8);
+        assert_eq!(&keys[..3], ["laugh", "1f680_rocket", "like"]);
+        assert_eq!(keys.last().map(String::as_str), Some("1f389_partypopper"));
+    }
+}
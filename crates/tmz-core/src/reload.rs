@@ -0,0 +1,215 @@
+//! Hot reload of the configuration in the daemon and the TUI.
+//!
+//! [`ConfigWatcher`] watches the directories of the config files (editors
+//! often save by writing a new file and renaming it) and reports when one of
+//! the files changed. The caller reloads the config and passes it to
+//! [`apply`], which takes over every changed setting except those listed in
+//! [`RESTART_KEYS`]: the data and state paths, the service endpoints, the
+//! proxy, and the log file stay as they were until the process is
+//! restarted.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::CoreError;
+use crate::config::{AppConfig, flatten};
+
+/// Config keys (and the keys below them) that only take effect on restart.
+pub const RESTART_KEYS: &[&str] = &["paths", "endpoints", "network", "logging.file"];
+
+/// How long to wait for an editor to finish saving before reloading.
+const SETTLE: Duration = Duration::from_millis(250);
+
+/// Keys that differ between two configs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigChanges {
+    /// Keys whose new values are in effect.
+    pub applied: Vec<String>,
+    /// Keys that keep their old values until a restart.
+    pub restart: Vec<String>,
+}
+
+impl ConfigChanges {
+    /// Whether nothing changed.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.restart.is_empty()
+    }
+
+    /// Whether a change to `section` (a key or a key prefix such as
+    /// `schedule`) was applied.
+    #[must_use]
+    pub fn touches(&self, section: &str) -> bool {
+        self.applied.iter().any(|key| is_under(key, section))
+    }
+}
+
+fn is_under(key: &str, section: &str) -> bool {
+    key == section
+        || key
+            .strip_prefix(section)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// The keys that differ between `old` and `new`. Profile sections are
+/// left out; the selected one shows up in the keys it overrides.
+#[must_use]
+pub fn changes(old: &AppConfig, new: &AppConfig) -> ConfigChanges {
+    let flat = |config: &AppConfig| -> BTreeMap<String, serde_json::Value> {
+        serde_json::to_value(config)
+            .map(|value| {
+                flatten(&value)
+                    .into_iter()
+                    // Empty tables are containers, not values.
+                    .filter(|(_, v)| v.as_object().is_none_or(|m| !m.is_empty()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let (old, new) = (flat(old), flat(new));
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut out = ConfigChanges::default();
+    for key in keys {
+        if is_under(key, "profiles") || old.get(key) == new.get(key) {
+            continue;
+        }
+        if RESTART_KEYS.iter().any(|section| is_under(key, section)) {
+            out.restart.push(key.clone());
+        } else {
+            out.applied.push(key.clone());
+        }
+    }
+    out
+}
+
+/// Replace `current` with `new`, keeping the settings in [`RESTART_KEYS`].
+/// Returns what changed.
+pub fn apply(current: &mut AppConfig, mut new: AppConfig) -> ConfigChanges {
+    let changes = changes(current, &new);
+    // Mirrors RESTART_KEYS.
+    new.paths = std::mem::take(&mut current.paths);
+    new.endpoints = std::mem::take(&mut current.endpoints);
+    new.network = std::mem::take(&mut current.network);
+    new.logging.file = current.logging.file.take();
+    *current = new;
+    changes
+}
+
+/// Watches config files for changes.
+pub struct ConfigWatcher {
+    // Dropping the watcher stops the notifications.
+    _watcher: notify::RecommendedWatcher,
+    rx: mpsc::UnboundedReceiver<()>,
+}
+
+impl std::fmt::Debug for ConfigWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfigWatcher").finish_non_exhaustive()
+    }
+}
+
+impl ConfigWatcher {
+    /// Watch `files`. Files in directories that do not exist are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the platform's file watcher cannot be started.
+    pub fn new(files: &[PathBuf]) -> Result<Self, CoreError> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let targets: Vec<PathBuf> = files.to_vec();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else { return };
+                if matches!(event.kind, EventKind::Access(_)) {
+                    return;
+                }
+                if event
+                    .paths
+                    .iter()
+                    .any(|path| targets.iter().any(|t| same_file(t, path)))
+                {
+                    let _ = tx.send(());
+                }
+            })
+            .map_err(|e| CoreError::Other(format!("starting config watcher: {e}")))?;
+
+        let mut dirs: Vec<&Path> = files.iter().filter_map(|f| f.parent()).collect();
+        dirs.sort();
+        dirs.dedup();
+        for dir in dirs.into_iter().filter(|dir| dir.is_dir()) {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .map_err(|e| CoreError::Other(format!("watching {}: {e}", dir.display())))?;
+        }
+        Ok(Self {
+            _watcher: watcher,
+            rx,
+        })
+    }
+
+    /// Whether a file changed since the last call, without waiting.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        while self.rx.try_recv().is_ok() {
+            changed = true;
+        }
+        changed
+    }
+
+    /// Wait until a file changed and the writes have settled.
+    pub async fn changed(&mut self) {
+        if self.rx.recv().await.is_none() {
+            // The watcher is gone; never report a change.
+            std::future::pending::<()>().await;
+        }
+        tokio::time::sleep(SETTLE).await;
+        self.poll();
+    }
+}
+
+/// Whether `a` and `b` name the same file, comparing the file name and the
+/// parent directory (which may be canonicalized in events).
+fn same_file(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+    a.file_name() == b.file_name()
+        && match (a.parent(), b.parent()) {
+            (Some(pa), Some(pb)) => pa == pb || pa.canonicalize().ok().as_deref() == Some(pb),
+            _ => false,
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_safe_changes_and_holds_paths() {
+        let mut current = AppConfig::default();
+        current.people.insert("alex".into(), "Alex Schmidt".into());
+
+        let mut new = current.clone();
+        new.people.insert("dana".into(), "Dana Lee".into());
+        new.display.sender_colors = !new.display.sender_colors;
+        new.paths.data_dir = Some("/elsewhere".into());
+        new.profiles.insert("work".into(), toml::Table::new());
+
+        let changes = apply(&mut current, new);
+        assert_eq!(changes.applied, ["display.sender_colors", "people.dana"]);
+        assert_eq!(changes.restart, ["paths.data_dir"]);
+        assert!(changes.touches("people"));
+        assert!(!changes.touches("peo"));
+        assert_eq!(current.resolve_alias("dana"), Some("Dana Lee"));
+        assert_eq!(current.paths.data_dir, None);
+
+        assert!(super::changes(&current, &current.clone()).is_empty());
+    }
+}
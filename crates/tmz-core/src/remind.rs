@@ -0,0 +1,187 @@
+//! Follow-up reminders (`tmz remind`).
+//!
+//! `tmz remind <target> --in 2h "reply to Alice"` stores a reminder in the
+//! cache's `reminders` table. The daemon checks for due reminders every few
+//! seconds; each one shows a desktop notification and marks its
+//! conversation unread again, in Teams and in the cache, so it shows up
+//! with the other unread chats. `tmz remind list` and `tmz remind cancel
+//! <id>` manage them. Claiming is atomic, so a reminder fires at most once.
+
+use crate::CoreError;
+use crate::cache::{Cache, Reminder};
+use std::future::Future;
+
+/// Fire the due reminders: `notify` shows each one (title and body), and
+/// `mark_unread` moves the read horizon of its conversation in Teams to
+/// the given value. Returns the reminders that fired.
+///
+/// Failed notifications and Teams updates are logged; the reminder still
+/// counts as fired.
+///
+/// # Errors
+///
+/// Returns an error if the reminders cannot be read or updated.
+pub async fn fire_due<N, F, Fut>(
+    cache: &Cache,
+    notify: N,
+    mark_unread: F,
+) -> Result<Vec<Reminder>, CoreError>
+where
+    N: Fn(&str, &str) -> Result<(), CoreError>,
+    F: Fn(String, String) -> Fut,
+    Fut: Future<Output = Result<(), CoreError>>,
+{
+    let reminders = cache.claim_due_reminders().await?;
+    for reminder in &reminders {
+        let conv = &reminder.conversation_id;
+        let name = cache
+            .find_conversation(conv)
+            .await?
+            .first()
+            .map_or_else(|| conv.clone(), |c| c.display_name.clone());
+        let body = if reminder.note.is_empty() {
+            "Follow up on this conversation"
+        } else {
+            &reminder.note
+        };
+        if let Err(e) = notify(&format!("Reminder: {name}"), body) {
+            log::warn!("showing reminder {} failed: {e}", reminder.id);
+        }
+
+        let newest = cache.get_messages_without_raw(conv, 1).await?;
+        let Some(horizon) = newest.first().and_then(|m| unread_horizon(&m.id)) else {
+            continue;
+        };
+        match mark_unread(conv.clone(), horizon.clone()).await {
+            Ok(()) => cache.set_consumption_horizon(conv, &horizon).await?,
+            Err(e) => log::warn!(
+                "marking {name} unread for reminder {} failed: {e}",
+                reminder.id
+            ),
+        }
+    }
+    Ok(reminders)
+}
+
+/// Read horizon that leaves the message `message_id` (and nothing older)
+/// unread, or `None` if the id is not numeric.
+#[must_use]
+pub fn unread_horizon(message_id: &str) -> Option<String> {
+    let before = message_id.parse::<i64>().ok()?.checked_sub(1)?;
+    let now = chrono::Utc::now().timestamp_millis();
+    Some(format!("{before};{now};0"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CachedConversation, parse_message};
+    use crate::testutil::TempDir;
+    use std::sync::Mutex;
+
+    /// "Ops", read up to Alice's message asking for a review.
+    async fn read_chat(dir: &TempDir) -> Cache {
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        cache
+            .upsert_conversation(&CachedConversation {
+                id: "19:ops".to_string(),
+                display_name: "Ops".to_string(),
+                thread_type: "chat".to_string(),
+                product_type: String::new(),
+                last_message_preview: String::new(),
+                last_message_from: String::new(),
+                last_activity: String::new(),
+                messages_url: String::new(),
+                member_names: String::new(),
+                raw_json: r#"{"properties":{"consumptionhorizon":"2000;2000;0"}}"#.to_string(),
+                restrictions: crate::restrictions::Restrictions::default(),
+            })
+            .await
+            .unwrap();
+        let raw = serde_json::json!({
+            "id": "2000",
+            "messagetype": "Text",
+            "content": "can you review?",
+            "imdisplayname": "Alice",
+            "composetime": "2026-03-02T09:00:00Z",
+        });
+        let msg = parse_message(
+            &raw.into(),
+            "19:ops",
+            None,
+            &crate::MessagesConfig::default(),
+        )
+        .unwrap();
+        cache.upsert_message(&msg).await.unwrap();
+        assert!(cache.unread_counts().await.unwrap().is_empty());
+        cache
+    }
+
+    fn past() -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc::now() - chrono::Duration::minutes(1)
+    }
+
+    fn later() -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc::now() + chrono::Duration::hours(2)
+    }
+
+    #[tokio::test]
+    async fn due_reminders_notify_and_mark_the_chat_unread() {
+        let dir = TempDir::new("remind").unwrap();
+        let cache = read_chat(&dir).await;
+        let due = cache
+            .add_reminder("19:ops", "reply to Alice", past())
+            .await
+            .unwrap();
+        cache.add_reminder("19:ops", "", later()).await.unwrap();
+
+        let shown = Mutex::new(Vec::new());
+        let marked = Mutex::new(Vec::new());
+        let fired = fire_due(
+            &cache,
+            |title, body| {
+                shown.lock().unwrap().push(format!("{title}: {body}"));
+                Ok(())
+            },
+            |conv, horizon| {
+                marked.lock().unwrap().push((conv, horizon));
+                async { Ok(()) }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].id, due.id);
+        assert_eq!(*shown.lock().unwrap(), ["Reminder: Ops: reply to Alice"]);
+        let marked = marked.into_inner().unwrap();
+        assert_eq!(marked[0].0, "19:ops");
+        assert!(marked[0].1.starts_with("1999;"));
+        assert_eq!(cache.unread_counts().await.unwrap()["19:ops"], 1);
+    }
+
+    #[tokio::test]
+    async fn reminders_fire_once() {
+        let dir = TempDir::new("remind-once").unwrap();
+        let cache = read_chat(&dir).await;
+        cache.add_reminder("19:ops", "", past()).await.unwrap();
+        let fire = || fire_due(&cache, |_, _| Ok(()), |_, _| async { Ok(()) });
+        assert_eq!(fire().await.unwrap().len(), 1);
+        assert!(fire().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_pending_reminders_can_be_cancelled() {
+        let dir = TempDir::new("remind-cancel").unwrap();
+        let cache = read_chat(&dir).await;
+        let due = cache.add_reminder("19:ops", "", past()).await.unwrap();
+        let pending = cache.add_reminder("19:ops", "", later()).await.unwrap();
+        fire_due(&cache, |_, _| Ok(()), |_, _| async { Ok(()) })
+            .await
+            .unwrap();
+
+        assert!(cache.cancel_reminder(pending.id).await.unwrap().is_some());
+        assert!(cache.cancel_reminder(due.id).await.unwrap().is_none());
+        assert!(cache.list_reminders(false, 10).await.unwrap().is_empty());
+        assert_eq!(cache.list_reminders(true, 10).await.unwrap().len(), 2);
+    }
+}
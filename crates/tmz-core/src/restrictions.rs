@@ -0,0 +1,172 @@
+//! Conversations where some actions are not available.
+//!
+//! Chats protected by a sensitivity label (encrypted chats) and chats with
+//! Skype consumer accounts go through the same chat service as any other,
+//! but it refuses reactions and file uploads in them with a bare `403`.
+//! [`Restrictions::detect`] reads these properties from a conversation's
+//! JSON, the cache stores them with the conversation, and the frontends
+//! show a banner and refuse the unsupported actions up front.
+
+use serde::{Deserialize, Serialize};
+
+use crate::CoreError;
+
+/// Properties of a conversation that limit what can be done in it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Restrictions {
+    /// The chat is encrypted or carries a sensitivity label.
+    pub encrypted: bool,
+    /// The chat is with a Skype (consumer) account.
+    pub consumer: bool,
+}
+
+/// An action that restricted conversations do not support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Adding a reaction to a message.
+    React,
+    /// Uploading a file.
+    Upload,
+}
+
+impl Action {
+    const fn plural(self) -> &'static str {
+        match self {
+            Self::React => "reactions",
+            Self::Upload => "file uploads",
+        }
+    }
+}
+
+/// Thread properties that mark an encrypted or labelled chat.
+const ENCRYPTION_PROPERTIES: &[&str] = &[
+    "sensitivityLabelId",
+    "sensitivityLabel",
+    "isEncrypted",
+    "encryptionEnabled",
+];
+
+impl Restrictions {
+    /// Read the restrictions of a conversation from its chat service JSON.
+    #[must_use]
+    pub fn detect(conv: &serde_json::Value) -> Self {
+        let tp = &conv["threadProperties"];
+        let encrypted = ENCRYPTION_PROPERTIES.iter().any(|key| is_set(&tp[*key]));
+        let last_sender = conv["lastMessage"]["from"]
+            .as_str()
+            .and_then(|from| from.rsplit('/').next())
+            .unwrap_or("");
+        let consumer =
+            conv["id"].as_str().is_some_and(is_consumer_mri) || is_consumer_mri(last_sender);
+        Self {
+            encrypted,
+            consumer,
+        }
+    }
+
+    /// Guess the restriction behind a `403` response from its body.
+    #[must_use]
+    pub fn from_error_body(body: &str) -> Self {
+        let body = body.to_lowercase();
+        Self {
+            encrypted: body.contains("encrypt") || body.contains("sensitivity"),
+            consumer: body.contains("consumer") || body.contains("8:live:"),
+        }
+    }
+
+    /// What kind of conversation this is, for messages.
+    #[must_use]
+    pub const fn kind(self) -> Option<&'static str> {
+        match (self.encrypted, self.consumer) {
+            (true, _) => Some("encrypted chat"),
+            (false, true) => Some("chat with a Skype account"),
+            (false, false) => None,
+        }
+    }
+
+    /// The banner to show above the conversation, if any.
+    #[must_use]
+    pub fn banner(self) -> Option<String> {
+        let kind = self.kind()?;
+        let mut kind = kind.to_string();
+        if let Some(first) = kind.get_mut(..1) {
+            first.make_ascii_uppercase();
+        }
+        Some(format!(
+            "{kind}: {} and {} are not available",
+            Action::React.plural(),
+            Action::Upload.plural()
+        ))
+    }
+
+    /// Refuse `action` if this conversation does not support it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CoreError::Restricted`] if a restriction applies.
+    pub fn check(self, action: Action) -> Result<(), CoreError> {
+        self.kind().map_or(Ok(()), |kind| {
+            Err(CoreError::Restricted(format!(
+                "{} ({kind})",
+                action.plural()
+            )))
+        })
+    }
+}
+
+/// Whether a thread property is present and not switched off.
+fn is_set(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Bool(b) => *b,
+        serde_json::Value::String(s) => !s.is_empty() && s != "false" && s != "0",
+        serde_json::Value::Null => false,
+        _ => true,
+    }
+}
+
+/// Whether `mri` is a Skype consumer account: `8:live:...`, or a legacy
+/// Skype name like `8:echo123`.
+fn is_consumer_mri(mri: &str) -> bool {
+    mri.strip_prefix("8:")
+        .is_some_and(|rest| rest.starts_with("live:") || (!rest.is_empty() && !rest.contains(':')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_encrypted_and_consumer_chats() {
+        let plain = serde_json::json!({
+            "id": "19:abc@thread.v2",
+            "threadProperties": {"topic": "Ops", "isEncrypted": "false"},
+            "lastMessage": {"from": "https://emea.ng.msg.teams.microsoft.com/v1/users/ME/contacts/8:orgid:123"}
+        });
+        let labelled = serde_json::json!({
+            "id": "19:def@thread.v2",
+            "threadProperties": {"sensitivityLabelId": "0e5ff3b4-0000"}
+        });
+        let consumer = serde_json::json!({
+            "id": "19:ghi@unq.gbl.spaces",
+            "lastMessage": {"from": "https://emea.ng.msg.teams.microsoft.com/v1/users/ME/contacts/8:live:.cid.42"}
+        });
+
+        assert_eq!(Restrictions::detect(&plain), Restrictions::default());
+        assert!(Restrictions::default().check(Action::React).is_ok());
+
+        let labelled = Restrictions::detect(&labelled);
+        assert!(labelled.encrypted && !labelled.consumer);
+        assert_eq!(
+            labelled.banner().as_deref(),
+            Some("Encrypted chat: reactions and file uploads are not available")
+        );
+
+        let consumer = Restrictions::detect(&consumer);
+        assert!(consumer.consumer && !consumer.encrypted);
+        assert_eq!(
+            consumer.check(Action::Upload).unwrap_err().to_string(),
+            "not available in this conversation: file uploads (chat with a Skype account)"
+        );
+        assert!(is_consumer_mri("8:echo123") && !is_consumer_mri("8:orgid:1"));
+    }
+}
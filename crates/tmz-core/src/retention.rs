@@ -0,0 +1,212 @@
+//! Retention of cached messages.
+//!
+//! `[retention] days` prunes cached messages older than that many days in
+//! every conversation, and `[retention.chats]` overrides it per
+//! conversation, so a sensitive chat can be kept for a week while the rest
+//! are kept forever (`0`). The engine applies the policy after every sync,
+//! so a running daemon enforces it; `tmz cache retention` reports what it
+//! would prune.
+
+use crate::CoreError;
+use crate::cache::{Cache, CachedConversation};
+use crate::config::AppConfig;
+
+/// `[retention]` with override keys resolved through the aliases.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    days: u32,
+    // Lowercased conversation IDs or display names.
+    chats: Vec<(String, u32)>,
+}
+
+impl RetentionPolicy {
+    /// The policy configured in `config`.
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Self {
+        let chats = config
+            .retention
+            .chats
+            .iter()
+            .map(|(key, &days)| {
+                let target = config.resolve_alias(key).unwrap_or(key);
+                (target.to_lowercase(), days)
+            })
+            .collect();
+        Self {
+            days: config.retention.days,
+            chats,
+        }
+    }
+
+    /// Whether the policy keeps every message.
+    #[must_use]
+    pub fn keeps_everything(&self) -> bool {
+        self.days == 0 && self.chats.iter().all(|&(_, days)| days == 0)
+    }
+
+    /// Days to keep the messages of `conv` (0 for forever). Overrides match
+    /// the conversation ID or the display name, ignoring case.
+    #[must_use]
+    pub fn days_for(&self, conv: &CachedConversation) -> u32 {
+        let id = conv.id.to_lowercase();
+        let name = conv.display_name.to_lowercase();
+        self.chats
+            .iter()
+            .find(|(key, _)| *key == id || (!name.is_empty() && *key == name))
+            .map_or(self.days, |&(_, days)| days)
+    }
+}
+
+/// Messages pruned (or due) in one conversation.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Pruned {
+    /// Conversation ID.
+    pub conversation_id: String,
+    /// Display name (or ID).
+    pub name: String,
+    /// Days the conversation's messages are kept.
+    pub days: u32,
+    /// Messages older than that.
+    pub messages: u64,
+}
+
+/// Prune messages older than `policy` allows in every cached conversation,
+/// hidden ones included. With `dry_run`, only count them. Conversations
+/// with nothing to prune are left out of the result.
+///
+/// # Errors
+///
+/// Returns an error if the cache cannot be read or written.
+pub async fn apply(
+    cache: &Cache,
+    policy: &RetentionPolicy,
+    dry_run: bool,
+) -> Result<Vec<Pruned>, CoreError> {
+    if policy.keeps_everything() {
+        return Ok(Vec::new());
+    }
+    let now = chrono::Utc::now();
+    let mut pruned = Vec::new();
+    for conv in cache.list_conversations_with_hidden(i64::MAX, true).await? {
+        let days = policy.days_for(&conv);
+        if days == 0 {
+            continue;
+        }
+        let cutoff = (now - chrono::Duration::days(i64::from(days)))
+            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        let messages = if dry_run {
+            cache.count_messages_before(&conv.id, &cutoff).await?
+        } else {
+            cache.delete_messages_before(&conv.id, &cutoff).await?
+        };
+        if messages > 0 {
+            let name = if conv.display_name.is_empty() {
+                conv.id.clone()
+            } else {
+                conv.display_name.clone()
+            };
+            pruned.push(Pruned {
+                conversation_id: conv.id,
+                name,
+                days,
+                messages,
+            });
+        }
+    }
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CachedMessage;
+    use crate::testutil::TempDir;
+
+    /// HR, Team, and a DM with Dana, each with messages 2, 20, and 400
+    /// days old.
+    async fn chats(dir: &TempDir) -> Cache {
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        for (id, name) in [("19:hr", "HR"), ("19:team", "Team"), ("19:dm", "Dana")] {
+            cache
+                .upsert_conversation(&CachedConversation {
+                    id: id.to_string(),
+                    display_name: name.to_string(),
+                    thread_type: "chat".to_string(),
+                    product_type: "GroupChat".to_string(),
+                    last_message_preview: String::new(),
+                    last_message_from: String::new(),
+                    last_activity: "2026-01-01T00:00:00Z".to_string(),
+                    messages_url: String::new(),
+                    member_names: String::new(),
+                    raw_json: "{}".to_string(),
+                    restrictions: crate::restrictions::Restrictions::default(),
+                })
+                .await
+                .unwrap();
+            for (n, age) in [(1, 2), (2, 20), (3, 400)] {
+                let time = chrono::Utc::now() - chrono::Duration::days(age);
+                cache
+                    .upsert_message(&CachedMessage {
+                        id: n.to_string(),
+                        conversation_id: id.to_string(),
+                        from_display_name: "Alex".to_string(),
+                        content: format!("salary review {n}"),
+                        content_html: String::new(),
+                        message_type: "Text".to_string(),
+                        compose_time: time.to_rfc3339(),
+                        is_from_me: false,
+                        mentions_me: false,
+                        unknown_type: false,
+                        subject: String::new(),
+                        important: false,
+                        raw_json: "{}".to_string(),
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+        cache
+    }
+
+    /// A year by default, a week for HR (by alias), and forever for Dana.
+    fn policy() -> RetentionPolicy {
+        let mut config = AppConfig::default();
+        config.people.insert("hr".into(), "19:hr".into());
+        config.retention.days = 365;
+        config.retention.chats.insert("HR".into(), 7);
+        config.retention.chats.insert("dana".into(), 0);
+        RetentionPolicy::new(&config)
+    }
+
+    #[test]
+    fn the_default_policy_keeps_everything() {
+        assert!(RetentionPolicy::new(&AppConfig::default()).keeps_everything());
+    }
+
+    #[tokio::test]
+    async fn dry_runs_count_per_conversation_without_deleting() {
+        let dir = TempDir::new("retention").unwrap();
+        let cache = chats(&dir).await;
+        let due = apply(&cache, &policy(), true).await.unwrap();
+        let mut summary: Vec<(&str, u32, u64)> = due
+            .iter()
+            .map(|p| (p.conversation_id.as_str(), p.days, p.messages))
+            .collect();
+        summary.sort_unstable();
+        assert_eq!(summary, [("19:hr", 7, 2), ("19:team", 365, 1)]);
+        assert_eq!(cache.get_messages("19:hr", 10).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn prunes_per_conversation() {
+        let dir = TempDir::new("retention-prune").unwrap();
+        let cache = chats(&dir).await;
+        let policy = policy();
+        assert_eq!(apply(&cache, &policy, false).await.unwrap().len(), 2);
+        assert_eq!(cache.get_messages("19:hr", 10).await.unwrap().len(), 1);
+        assert_eq!(cache.get_messages("19:team", 10).await.unwrap().len(), 2);
+        assert_eq!(cache.get_messages("19:dm", 10).await.unwrap().len(), 3);
+        assert_eq!(cache.search("salary", 10).await.unwrap().len(), 6);
+        assert!(apply(&cache, &policy, true).await.unwrap().is_empty());
+    }
+}
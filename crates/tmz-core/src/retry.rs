@@ -0,0 +1,246 @@
+//! Retrying requests to Teams and Graph that failed for transient reasons.
+//!
+//! A dropped connection, a timeout, a `5xx`, or `429 Too Many Requests`
+//! is retried up to `[runtime.retries] attempts` times, waiting
+//! `base_delay_ms` doubled per retry (capped at `max_delay_ms`, with up to
+//! half of it taken off at random so parallel clients spread out). A
+//! `Retry-After` header (seconds or an HTTP date) stretches the wait up to
+//! `max_retry_after_secs`; a longer one fails the request, and a `429` also
+//! pauses the whole host (see [`crate::ratelimit`]). Requests that change something
+//! (`POST`, `PATCH`) are only retried when the service cannot have acted on
+//! them: connection failures, `429`, and `503`. Each attempt is traced
+//! separately (see [`crate::trace`]).
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+use crate::config::RetryConfig;
+use crate::trace::TracedSend;
+
+static ATTEMPTS: AtomicU32 = AtomicU32::new(3);
+static BASE_DELAY_MS: AtomicU64 = AtomicU64::new(500);
+static MAX_DELAY_MS: AtomicU64 = AtomicU64::new(10_000);
+static MAX_RETRY_AFTER_SECS: AtomicU64 = AtomicU64::new(120);
+
+/// Use `config` for the requests this process sends from now on.
+pub fn configure(config: &RetryConfig) {
+    ATTEMPTS.store(config.attempts, Ordering::Relaxed);
+    BASE_DELAY_MS.store(config.base_delay_ms.max(1), Ordering::Relaxed);
+    MAX_DELAY_MS.store(config.max_delay_ms.max(1), Ordering::Relaxed);
+    MAX_RETRY_AFTER_SECS.store(config.max_retry_after_secs, Ordering::Relaxed);
+}
+
+fn current() -> RetryConfig {
+    RetryConfig {
+        attempts: ATTEMPTS.load(Ordering::Relaxed),
+        base_delay_ms: BASE_DELAY_MS.load(Ordering::Relaxed),
+        max_delay_ms: MAX_DELAY_MS.load(Ordering::Relaxed),
+        max_retry_after_secs: MAX_RETRY_AFTER_SECS.load(Ordering::Relaxed),
+    }
+}
+
+/// Sending a request with retries.
+pub(crate) trait RetryingSend {
+    /// Like [`TracedSend::send_traced`], retrying transient failures and
+    /// keeping to the rate limit of the request's host.
+    fn send_retrying(self) -> impl Future<Output = reqwest::Result<Response>> + Send;
+}
+
+impl RetryingSend for RequestBuilder {
+    async fn send_retrying(self) -> reqwest::Result<Response> {
+        let config = current();
+        let request = self.try_clone().and_then(|builder| builder.build().ok());
+        let idempotent = request
+            .as_ref()
+            .is_some_and(|request| request.method().is_idempotent());
+        let host = request
+            .as_ref()
+            .and_then(|request| request.url().host_str().map(str::to_string))
+            .unwrap_or_default();
+        let mut retry = 0;
+        loop {
+            crate::ratelimit::acquire(&host).await;
+            // Streaming bodies cannot be cloned and are sent once.
+            let Some(attempt) = self.try_clone().filter(|_| retry < config.attempts) else {
+                return self.send_traced().await;
+            };
+            let result = attempt.send_traced().await;
+            let failure = match result {
+                Ok(ref response) => Failure::Status(response.status()),
+                Err(ref e) if e.is_connect() => Failure::Connect,
+                Err(ref e) if e.is_timeout() || e.is_request() => Failure::Network,
+                Err(_) => return result,
+            };
+            if !failure.is_transient(idempotent) {
+                return result;
+            }
+            let retry_after = result.as_ref().ok().and_then(retry_after);
+            let Some(delay) = backoff(&config, retry, retry_after, jitter(retry)) else {
+                log::warn!(
+                    "{host} asked to wait {}s before retrying, longer than max_retry_after_secs",
+                    retry_after.unwrap_or_default().as_secs()
+                );
+                return result;
+            };
+            if failure == Failure::Status(StatusCode::TOO_MANY_REQUESTS) {
+                crate::ratelimit::pause(&host, delay);
+            }
+            match result {
+                Ok(ref response) => log::debug!(
+                    "{} answered {}, retrying in {}ms",
+                    response.url(),
+                    response.status(),
+                    delay.as_millis()
+                ),
+                Err(ref e) => log::debug!("{e}, retrying in {}ms", delay.as_millis()),
+            }
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+/// How an attempt went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// The connection could not be made; nothing reached the service.
+    Connect,
+    /// The connection broke or timed out after the request went out.
+    Network,
+    /// The service answered.
+    Status(StatusCode),
+}
+
+impl Failure {
+    /// Whether the attempt is worth repeating.
+    fn is_transient(self, idempotent: bool) -> bool {
+        match self {
+            Self::Connect => true,
+            Self::Network => idempotent,
+            Self::Status(status) => match status {
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => true,
+                StatusCode::NOT_IMPLEMENTED => false,
+                _ => idempotent && status.is_server_error(),
+            },
+        }
+    }
+}
+
+/// The `Retry-After` header of `response`.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?;
+    parse_retry_after(value, chrono::Utc::now())
+}
+
+/// A `Retry-After` value, either seconds or an HTTP date, as a wait from
+/// `now`.
+fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.to_utc() - now).to_std().unwrap_or_default())
+}
+
+/// The wait before retry number `retry` (from 0): the base delay doubled
+/// per retry and capped, minus `jitter` (0.0 to 1.0) of half of it, but at
+/// least `retry_after`. `None` if `retry_after` is over the configured
+/// maximum.
+fn backoff(
+    config: &RetryConfig,
+    retry: u32,
+    retry_after: Option<Duration>,
+    jitter: f64,
+) -> Option<Duration> {
+    let full = Duration::from_millis(config.base_delay_ms)
+        .saturating_mul(2u32.saturating_pow(retry.min(16)))
+        .min(Duration::from_millis(config.max_delay_ms));
+    let delay = full.mul_f64(jitter.clamp(0.0, 1.0).mul_add(-0.5, 1.0));
+    match retry_after {
+        Some(after) if after > Duration::from_secs(config.max_retry_after_secs) => None,
+        Some(after) => Some(delay.max(after)),
+        None => Some(delay),
+    }
+}
+
+/// A pseudo-random number from 0.0 to 1.0.
+fn jitter(retry: u32) -> f64 {
+    let n = RandomState::new().hash_one(retry) >> 11;
+    n as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: u16) -> Failure {
+        Failure::Status(StatusCode::from_u16(code).unwrap())
+    }
+
+    /// Milliseconds to wait before retry `retry` with up to 5 attempts,
+    /// 500ms doubling to 3s, and `Retry-After` honored up to a minute.
+    fn ms(retry: u32, after: Option<Duration>, jitter: f64) -> Option<u128> {
+        let config = RetryConfig {
+            attempts: 5,
+            base_delay_ms: 500,
+            max_delay_ms: 3000,
+            max_retry_after_secs: 60,
+        };
+        backoff(&config, retry, after, jitter).map(|delay| delay.as_millis())
+    }
+
+    #[test]
+    fn network_failures_are_transient_only_for_idempotent_requests() {
+        assert!(Failure::Connect.is_transient(false));
+        assert!(Failure::Network.is_transient(true));
+        assert!(!Failure::Network.is_transient(false));
+        assert!(status(502).is_transient(true));
+        assert!(!status(502).is_transient(false));
+    }
+
+    #[test]
+    fn throttling_and_unavailability_are_always_transient() {
+        assert!(status(503).is_transient(false));
+        assert!(status(429).is_transient(false));
+        assert!(!status(501).is_transient(true));
+        assert!(!status(404).is_transient(true));
+        assert!(!status(200).is_transient(true));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap_with_jitter() {
+        assert_eq!(ms(0, None, 0.0), Some(500));
+        assert_eq!(ms(1, None, 0.0), Some(1000));
+        assert_eq!(ms(2, None, 1.0), Some(1000));
+        assert_eq!(ms(4, None, 0.0), Some(3000));
+        assert!((0.0..=1.0).contains(&jitter(1)));
+    }
+
+    #[test]
+    fn retry_after_wins_unless_it_is_too_long() {
+        assert_eq!(ms(0, Some(Duration::from_secs(2)), 0.0), Some(2000));
+        assert_eq!(ms(0, Some(Duration::from_secs(30)), 0.0), Some(30_000));
+        assert_eq!(ms(0, Some(Duration::from_mins(2)), 0.0), None);
+    }
+
+    #[test]
+    fn retry_after_is_read_as_seconds_or_a_date() {
+        let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:27:30Z")
+            .unwrap()
+            .to_utc();
+        let after = |value| parse_retry_after(value, now).map(|d| d.as_secs());
+        assert_eq!(after(" 7 "), Some(7));
+        assert_eq!(after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(30));
+        assert_eq!(after("Wed, 21 Oct 2015 07:00:00 GMT"), Some(0));
+        assert_eq!(after("soon"), None);
+    }
+}
@@ -0,0 +1,304 @@
+//! Scheduled daemon jobs (`[[schedule]]`).
+//!
+//! Each entry pairs a five-field cron expression (`minute hour day month
+//! weekday`, local time) with a job. The daemon checks the schedule every
+//! few seconds and runs each due entry once per matching minute; minutes
+//! missed while the machine slept are skipped, not caught up. The only job
+//! so far is `digest` (see [`crate::digest`]), delivered as a desktop
+//! notification, a file, or a message to a chat.
+
+use crate::CoreError;
+use crate::config::ScheduledJob;
+use chrono::{DateTime, Datelike, Local, Timelike};
+use std::path::PathBuf;
+
+/// Conversation id of your own "notes to self" chat in Teams.
+pub const NOTES_CHAT: &str = "48:notes";
+
+/// A parsed cron expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether day and weekday were both restricted; cron then runs on
+    /// either, not only on both.
+    either_day: bool,
+}
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+impl Cron {
+    /// Parse `minute hour day month weekday`. Fields take `*`, numbers,
+    /// ranges (`1-5`), lists (`1,15`), and steps (`*/15`, `9-17/2`); month
+    /// and weekday also take names (`jan`, `mon-fri`), and weekday `7` is
+    /// Sunday like `0`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the field that could not be parsed.
+    pub fn parse(expr: &str) -> Result<Self, CoreError> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(CoreError::Config(format!(
+                "invalid cron '{expr}': expected 5 fields (minute hour day month weekday)"
+            )));
+        };
+        let field = |text: &str, min: u32, max: u32, names: &[&str]| {
+            parse_field(text, min, max, names)
+                .map_err(|e| CoreError::Config(format!("invalid cron '{expr}': {e}")))
+        };
+        let mut weekdays = field(weekday, 0, 7, WEEKDAYS)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: field(minute, 0, 59, &[])?,
+            hours: field(hour, 0, 23, &[])?,
+            days: field(day, 1, 31, &[])?,
+            months: field(month, 1, 12, MONTHS)?,
+            weekdays,
+            either_day: day != "*" && weekday != "*",
+        })
+    }
+
+    /// Whether the schedule fires in the minute of `time`.
+    #[must_use]
+    pub fn matches(&self, time: &DateTime<Local>) -> bool {
+        let has = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+        let day_ok = if self.either_day {
+            day || weekday
+        } else {
+            day && weekday
+        };
+        has(self.minutes, time.minute())
+            && has(self.hours, time.hour())
+            && has(self.months, time.month())
+            && day_ok
+    }
+}
+
+/// Bitmask of the values a cron field allows.
+fn parse_field(text: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let lower = s.to_ascii_lowercase();
+        let n = match names.iter().position(|n| *n == lower) {
+            // Month names start at 1, weekday names at 0.
+            Some(i) => u32::try_from(i).unwrap_or(0) + min,
+            None => s.parse().map_err(|_| format!("'{s}' is not a number"))?,
+        };
+        if (min..=max).contains(&n) {
+            Ok(n)
+        } else {
+            Err(format!("{n} is outside {min}-{max}"))
+        }
+    };
+    let mut mask = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid step in '{part}'"))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (value(a)?, value(b)?),
+                None if step > 1 => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if start > end {
+            return Err(format!("range '{range}' runs backwards"));
+        }
+        for n in (start..=end).step_by(step as usize) {
+            mask |= 1 << n;
+        }
+    }
+    Ok(mask)
+}
+
+/// The configured jobs with their parsed schedules; tracks which minute
+/// was last checked so each entry runs once per matching minute.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    jobs: Vec<(ScheduledJob, Cron)>,
+    last_minute: Option<i64>,
+}
+
+impl Scheduler {
+    /// Parse the schedules of `jobs`. Entries with an invalid cron
+    /// expression are logged and left out.
+    #[must_use]
+    pub fn new(jobs: &[ScheduledJob]) -> Self {
+        let jobs = jobs
+            .iter()
+            .filter_map(|job| match Cron::parse(&job.cron) {
+                Ok(cron) => Some((job.clone(), cron)),
+                Err(e) => {
+                    log::warn!("skipping scheduled job '{}': {e}", job.name);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            jobs,
+            last_minute: None,
+        }
+    }
+
+    /// Whether there is nothing scheduled.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Replace the jobs after the config changed, keeping track of the
+    /// minute already run so no job runs twice in it.
+    pub fn replace_jobs(&mut self, jobs: &[ScheduledJob]) {
+        *self = Self {
+            last_minute: self.last_minute,
+            ..Self::new(jobs)
+        };
+    }
+
+    /// Jobs due at `now`, unless this minute was already checked.
+    pub fn due(&mut self, now: &DateTime<Local>) -> Vec<ScheduledJob> {
+        let minute = now.timestamp().div_euclid(60);
+        if self.last_minute == Some(minute) {
+            return Vec::new();
+        }
+        self.last_minute = Some(minute);
+        self.jobs
+            .iter()
+            .filter(|(_, cron)| cron.matches(now))
+            .map(|(job, _)| job.clone())
+            .collect()
+    }
+}
+
+/// Show a desktop notification with `notify-send` (or `osascript` on
+/// macOS).
+///
+/// # Errors
+///
+/// Returns an error if the notifier cannot be run or fails.
+pub fn notify(title: &str, body: &str) -> Result<(), CoreError> {
+    let mut command = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {} with title {}",
+            apple_string(body),
+            apple_string(title)
+        );
+        let mut command = std::process::Command::new("osascript");
+        command.arg("-e").arg(script);
+        command
+    } else {
+        let mut command = std::process::Command::new("notify-send");
+        command.arg("--app-name=tmz").arg(title).arg(body);
+        command
+    };
+    let output = command
+        .output()
+        .map_err(|e| CoreError::Other(format!("running notifier: {e}")))?;
+    if !output.status.success() {
+        return Err(CoreError::Other(format!(
+            "notifier failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn apple_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The file a job writes to: its `path` with `~` expanded and `{date}`
+/// replaced by today's date.
+///
+/// # Errors
+///
+/// Returns an error if the job has no path or it cannot be expanded.
+pub fn output_path(job: &ScheduledJob) -> Result<PathBuf, CoreError> {
+    let path = job.path.as_deref().ok_or_else(|| {
+        CoreError::Config(format!(
+            "scheduled job '{}' writes a file but has no path",
+            job.name
+        ))
+    })?;
+    let path = path.replace("{date}", &Local::now().format("%Y-%m-%d").to_string());
+    crate::paths::expand_str_path(&path).map_err(|e| CoreError::Path(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(d: u32, h: u32, m: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 3, d, h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn cron_fields_match_like_cron() {
+        // 2026-03-02 is a Monday, 2026-03-07 a Saturday.
+        let weekdays = Cron::parse("0 9 * * mon-fri").unwrap();
+        assert!(weekdays.matches(&at(2, 9, 0)));
+        assert!(!weekdays.matches(&at(2, 9, 1)));
+        assert!(!weekdays.matches(&at(7, 9, 0)));
+    }
+
+    #[test]
+    fn steps_apply_within_ranges() {
+        let quarter = Cron::parse("*/15 9-17/4 * * *").unwrap();
+        assert!(quarter.matches(&at(7, 13, 45)));
+        assert!(!quarter.matches(&at(7, 11, 45)));
+    }
+
+    #[test]
+    fn restricted_day_and_weekday_either_fire() {
+        let either = Cron::parse("30 8 1,15 * 0").unwrap();
+        assert!(either.matches(&at(1, 8, 30)) && either.matches(&at(8, 8, 30)));
+        assert!(!either.matches(&at(2, 8, 30)));
+        // 7 is Sunday, like 0.
+        assert!(Cron::parse("0 0 * * 7").unwrap().matches(&at(1, 0, 0)));
+    }
+
+    #[test]
+    fn malformed_expressions_are_rejected() {
+        for bad in [
+            "0 9 * *",
+            "60 * * * *",
+            "0 9 * * funday",
+            "5-1 * * * *",
+            "*/0 * * * *",
+        ] {
+            assert!(Cron::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn jobs_run_once_per_matching_minute() {
+        let job = ScheduledJob {
+            cron: "* * * * *".to_string(),
+            ..ScheduledJob::default()
+        };
+        let mut scheduler = Scheduler::new(&[job]);
+        assert_eq!(scheduler.due(&at(2, 9, 0)).len(), 1);
+        assert!(scheduler.due(&at(2, 9, 0)).is_empty());
+        assert_eq!(scheduler.due(&at(2, 9, 1)).len(), 1);
+    }
+}
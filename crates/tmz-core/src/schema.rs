@@ -2,7 +2,8 @@
 //!
 //! This module provides functions to generate JSON schemas and example TOML
 //! configurations from the config struct definitions. Generated files are
-//! validated against the examples/ directory in tests.
+//! validated against the examples/ directory in tests. The same schema lists
+//! the `TMZ__*` environment variables that override config values.
 
 use std::fmt::Write as _;
 use std::fs;
@@ -13,7 +14,9 @@ use schemars::Schema;
 use schemars::generate::SchemaSettings;
 use serde_json::json;
 
+use crate::bundle::{REDACTED, SECRET_KEYS};
 use crate::config::AppConfig;
+use crate::env_prefix;
 
 /// Generated schema filename.
 pub const SCHEMA_FILENAME: &str = "config.schema.json";
@@ -93,6 +96,200 @@ pub fn generate_example_config(project_name: &str) -> Result<String> {
     Ok(output)
 }
 
+/// An environment variable that overrides a config value.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct EnvOverride {
+    /// Variable name. `<NAME>` stands for a key of a table such as
+    /// `[people]`.
+    pub var: String,
+    /// Dotted config key.
+    pub key: String,
+    /// Value type, or the accepted values of an enum.
+    pub kind: String,
+    /// Description from the config docs.
+    pub description: Option<String>,
+    /// Variables currently set for this override, with their values
+    /// (secrets redacted).
+    pub set: Vec<(String, String)>,
+}
+
+impl EnvOverride {
+    /// Whether a variable for this override is set.
+    #[must_use]
+    pub const fn is_active(&self) -> bool {
+        !self.set.is_empty()
+    }
+
+    fn matches(&self, var: &str) -> bool {
+        let mut want = self.var.split("__");
+        let mut have = var.split("__");
+        loop {
+            match (want.next(), have.next()) {
+                (None, None) => return true,
+                (Some("<NAME>"), Some(seg)) if !seg.is_empty() => {}
+                (Some(a), Some(b)) if a.eq_ignore_ascii_case(b) => {}
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// Every config value settable through the environment, derived from the
+/// `AppConfig` schema, with the variables currently set. Lists such as
+/// `[[schedule]]` cannot be set this way and are left out.
+#[must_use]
+pub fn env_overrides() -> Vec<EnvOverride> {
+    let schema = config_schema();
+    let mut out = Vec::new();
+    walk_env(&schema, &schema, &mut Vec::new(), None, &mut out);
+    out.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let prefix = format!("{}__", env_prefix());
+    let vars: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| name.starts_with(&prefix))
+        .collect();
+    for entry in &mut out {
+        let secret = entry
+            .key
+            .rsplit('.')
+            .next()
+            .is_some_and(|last| SECRET_KEYS.contains(&last));
+        entry.set = vars
+            .iter()
+            .filter(|(name, _)| entry.matches(name))
+            .map(|(name, value)| {
+                let value = if secret { REDACTED } else { value.as_str() };
+                (name.clone(), value.to_string())
+            })
+            .collect();
+    }
+    out
+}
+
+/// `TMZ__*` variables that set no known config value, such as typos.
+#[must_use]
+pub fn unknown_env_vars(overrides: &[EnvOverride]) -> Vec<(String, String)> {
+    let prefix = format!("{}__", env_prefix());
+    let mut unknown: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| name.starts_with(&prefix))
+        .filter(|(name, _)| !overrides.iter().any(|o| o.matches(name)))
+        .collect();
+    unknown.sort();
+    unknown
+}
+
+/// The `AppConfig` schema as JSON.
+pub(crate) fn config_schema() -> serde_json::Value {
+    let schema = SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<AppConfig>();
+    serde_json::to_value(schema).unwrap_or_default()
+}
+
+/// Follow `$ref`, single-entry `allOf`, and nullable `anyOf` wrappers.
+pub(crate) fn resolve_schema<'a>(
+    root: &'a serde_json::Value,
+    schema: &'a serde_json::Value,
+) -> &'a serde_json::Value {
+    if let Some(name) = schema["$ref"]
+        .as_str()
+        .and_then(|r| r.strip_prefix("#/definitions/"))
+    {
+        return resolve_schema(root, &root["definitions"][name]);
+    }
+    for wrapper in ["allOf", "anyOf"] {
+        if let Some(variants) = schema[wrapper].as_array() {
+            let mut inner = variants.iter().filter(|v| v["type"] != "null");
+            if let (Some(only), None) = (inner.next(), inner.next()) {
+                return resolve_schema(root, only);
+            }
+        }
+    }
+    schema
+}
+
+fn walk_env(
+    root: &serde_json::Value,
+    schema: &serde_json::Value,
+    path: &mut Vec<String>,
+    description: Option<&str>,
+    out: &mut Vec<EnvOverride>,
+) {
+    // Profiles repeat the whole config; they are selected, not set.
+    if schema["$ref"] == "#" {
+        return;
+    }
+    let resolved = resolve_schema(root, schema);
+    let description = description.or_else(|| resolved["description"].as_str());
+    let types: Vec<&str> = match &resolved["type"] {
+        serde_json::Value::String(t) => vec![t.as_str()],
+        serde_json::Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+        _ => Vec::new(),
+    };
+
+    if let Some(props) = resolved["properties"].as_object() {
+        for (name, child) in props {
+            if name.starts_with('$') {
+                continue;
+            }
+            path.push(name.clone());
+            walk_env(root, child, path, child["description"].as_str(), out);
+            path.pop();
+        }
+        return;
+    }
+    if resolved["additionalProperties"].is_object() {
+        path.push("<NAME>".to_string());
+        walk_env(
+            root,
+            &resolved["additionalProperties"],
+            path,
+            description,
+            out,
+        );
+        path.pop();
+        return;
+    }
+    if types.contains(&"array") || path.is_empty() {
+        return;
+    }
+
+    let consts: Vec<&str> = resolved["oneOf"]
+        .as_array()
+        .or_else(|| resolved["enum"].as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v["const"].as_str().or_else(|| v.as_str()))
+        .collect();
+    let kind = if consts.is_empty() {
+        types
+            .iter()
+            .find(|t| **t != "null")
+            .copied()
+            .unwrap_or("string")
+            .to_string()
+    } else {
+        consts.join("|")
+    };
+    let var = std::iter::once(env_prefix())
+        .chain(path.iter().map(|seg| {
+            if seg == "<NAME>" {
+                seg.clone()
+            } else {
+                seg.to_uppercase()
+            }
+        }))
+        .collect::<Vec<_>>()
+        .join("__");
+    out.push(EnvOverride {
+        var,
+        key: path.join("."),
+        kind,
+        description: description.map(|d| d.split_whitespace().collect::<Vec<_>>().join(" ")),
+        set: Vec::new(),
+    });
+}
+
 /// Write generated files to a directory.
 ///
 /// # Errors
@@ -202,6 +399,39 @@ mod tests {
         assert!(config.contains("$schema"));
     }
 
+    fn find_override(var: &str) -> Option<EnvOverride> {
+        env_overrides().into_iter().find(|o| o.var == var)
+    }
+
+    #[test]
+    fn lists_env_overrides_from_the_schema() {
+        let level = find_override("TMZ__LOGGING__LEVEL").expect("logging.level");
+        assert_eq!(level.key, "logging.level");
+        assert_eq!(level.kind, "error|warn|info|debug|trace");
+        assert_eq!(
+            find_override("TMZ__SENDING__WORKING_HOURS__TIMEZONE").map(|o| o.kind),
+            Some("string".to_string())
+        );
+        assert!(find_override("TMZ__PEOPLE__<NAME>").is_some());
+    }
+
+    #[test]
+    fn lists_and_profiles_have_no_env_overrides() {
+        assert!(find_override("TMZ__SENDING__WORKING_HOURS__DAYS").is_none());
+        let overrides = env_overrides();
+        assert!(overrides.iter().all(|o| !o.var.contains('$')));
+        assert!(!overrides.iter().any(|o| o.key.starts_with("profiles")));
+    }
+
+    #[test]
+    fn map_overrides_match_one_level_of_names() {
+        let level = find_override("TMZ__LOGGING__LEVEL").unwrap();
+        assert!(level.matches("TMZ__LOGGING__LEVEL"));
+        let alias = find_override("TMZ__PEOPLE__<NAME>").unwrap();
+        assert!(alias.matches("TMZ__PEOPLE__ALEX"));
+        assert!(!alias.matches("TMZ__PEOPLE__ALEX__EXTRA"));
+    }
+
     #[test]
     fn validate_examples_are_up_to_date() {
         // Find the examples directory relative to the crate root
@@ -216,12 +446,11 @@ mod tests {
 
         let examples_dir = workspace_root.join("examples");
 
-        if !examples_dir.exists() {
-            panic!(
-                "examples/ directory not found at {}. Create it and run 'just generate-config'.",
-                examples_dir.display()
-            );
-        }
+        assert!(
+            examples_dir.exists(),
+            "examples/ directory not found at {}. Create it and run 'just generate-config'.",
+            examples_dir.display()
+        );
 
         validate_against_examples(&examples_dir, APP_NAME, REPO_URL)
             .expect("examples/ files are out of date");
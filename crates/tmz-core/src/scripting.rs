@@ -0,0 +1,538 @@
+//! User scripts: custom actions written in [Rhai](https://rhai.rs).
+//!
+//! `scripts.rhai` next to the config file (or `[scripting] file`) defines
+//! actions as functions taking one `ctx` parameter; `private` functions
+//! and functions with other parameters are helpers. `tmz script run
+//! <action>` and the TUI's `!` picker run an action with `ctx` describing
+//! where it was started: `chat`, `chat_name`, and `message` (IDs, or `()`)
+//! and the extra `args`. A `///` comment above the function describes it
+//! in listings.
+//!
+//! Scripts reach tmz only through the functions registered here:
+//!
+//! - `chats(limit)`, `find(name)`, `messages(chat, limit)`, and
+//!   `search(query, limit)` read the cache
+//! - `send(chat, markdown)` and `react(chat, message, reaction)` need
+//!   `[scripting] allow_send`
+//! - `run(program, args)` and `run(program, args, input)` return the
+//!   program's output and need `[scripting] allow_commands`
+//! - `plain(html)` and `markdown(html)` render message HTML
+//!
+//! Rhai itself cannot read files or open connections, `import` is
+//! disabled, and `[scripting] max_operations` stops runaway loops.
+
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use rhai::{AST, Array, Dynamic, EvalAltResult, FnAccess, Map};
+
+use crate::CoreError;
+use crate::audit::AuditLog;
+use crate::cache::{Cache, CachedConversation, CachedMessage, OutgoingKind};
+use crate::config::{AppConfig, ScriptingConfig, SecurityConfig};
+use crate::restrictions::Action;
+
+/// Default script file name in the config directory.
+pub const SCRIPT_FILE: &str = "scripts.rhai";
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// An action defined by the script.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ScriptAction {
+    /// Function name, used to run it.
+    pub name: String,
+    /// First line of its `///` comment.
+    pub description: String,
+}
+
+/// Where an action was started.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActionContext {
+    /// Selected conversation ID.
+    pub chat: Option<String>,
+    /// Its display name.
+    pub chat_name: Option<String>,
+    /// Selected message ID.
+    pub message: Option<String>,
+    /// Extra arguments from the command line.
+    pub args: Vec<String>,
+}
+
+/// The compiled user script.
+#[derive(Debug, Clone)]
+pub struct Scripts {
+    path: PathBuf,
+    ast: Arc<AST>,
+    config: ScriptingConfig,
+    security: SecurityConfig,
+    audit: Option<AuditLog>,
+}
+
+/// Path of the user script: `[scripting] file`, or [`SCRIPT_FILE`] in
+/// `config_dir`.
+#[must_use]
+pub fn script_path(config: &ScriptingConfig, config_dir: &Path) -> PathBuf {
+    config
+        .file
+        .as_deref()
+        .and_then(|file| crate::paths::expand_str_path(file).ok())
+        .unwrap_or_else(|| config_dir.join(SCRIPT_FILE))
+}
+
+impl Scripts {
+    /// Compile the script at `path`, or `None` if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the script cannot be read or does not compile.
+    pub fn load(path: &Path, config: &AppConfig) -> Result<Option<Self>, CoreError> {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let ast = sandboxed_engine(&config.scripting)
+            .compile(source)
+            .map_err(|e| CoreError::Config(format!("{}: {e}", path.display())))?;
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            ast: Arc::new(ast),
+            config: config.scripting.clone(),
+            security: config.security,
+            audit: None,
+        }))
+    }
+
+    /// Record sends and reactions in `audit`.
+    #[must_use]
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Path the script was loaded from.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Actions the script defines, by name.
+    #[must_use]
+    pub fn actions(&self) -> Vec<ScriptAction> {
+        let mut actions: Vec<ScriptAction> = self
+            .ast
+            .iter_functions()
+            .filter(|f| f.access != FnAccess::Private && f.params.len() == 1)
+            .map(|f| ScriptAction {
+                name: f.name.to_string(),
+                description: f
+                    .comments
+                    .iter()
+                    .find_map(|line| line.strip_prefix("///"))
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            })
+            .collect();
+        actions.sort_by(|a, b| a.name.cmp(&b.name));
+        actions
+    }
+
+    /// Run `action`, returning what it printed and, last, what it returned
+    /// unless that was `()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such action or it fails.
+    pub async fn run(
+        &self,
+        cache: &Cache,
+        action: &str,
+        ctx: ActionContext,
+    ) -> Result<Vec<String>, CoreError> {
+        if !self.actions().iter().any(|a| a.name == action) {
+            return Err(CoreError::Config(format!(
+                "no action '{action}' in {}",
+                self.path.display()
+            )));
+        }
+        let host = Host {
+            cache: cache.clone(),
+            handle: tokio::runtime::Handle::current(),
+            config: self.config.clone(),
+            security: self.security,
+            audit: self.audit.clone(),
+        };
+        let ast = Arc::clone(&self.ast);
+        let action = action.to_string();
+        tokio::task::spawn_blocking(move || host.call(&ast, &action, ctx))
+            .await
+            .map_err(|e| CoreError::Other(format!("script action: {e}")))?
+    }
+}
+
+/// An engine without file access or imports, within `config`'s limits.
+fn sandboxed_engine(config: &ScriptingConfig) -> rhai::Engine {
+    let mut engine = rhai::Engine::new();
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_operations(config.max_operations);
+    engine.set_max_call_levels(64);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(10 << 20);
+    engine.set_max_array_size(100_000);
+    engine.set_max_map_size(10_000);
+    engine
+}
+
+/// What the registered functions reach, moved into the blocking thread
+/// running an action.
+#[derive(Clone)]
+struct Host {
+    cache: Cache,
+    handle: tokio::runtime::Handle,
+    config: ScriptingConfig,
+    security: SecurityConfig,
+    audit: Option<AuditLog>,
+}
+
+impl Host {
+    fn call(self, ast: &AST, action: &str, ctx: ActionContext) -> Result<Vec<String>, CoreError> {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = sandboxed_engine(&self.config);
+        let printed = Arc::clone(&output);
+        engine.on_print(move |line| {
+            printed
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(line.to_string());
+        });
+        self.register(&mut engine);
+
+        let returned = engine
+            .call_fn::<Dynamic>(&mut rhai::Scope::new(), ast, action, (context_map(ctx),))
+            .map_err(|e| CoreError::Other(format!("{action}: {e}")))?;
+        let mut lines = std::mem::take(&mut *output.lock().unwrap_or_else(PoisonError::into_inner));
+        if !returned.is_unit() {
+            lines.push(returned.to_string());
+        }
+        Ok(lines)
+    }
+
+    fn register(&self, engine: &mut rhai::Engine) {
+        let host = self.clone();
+        engine.register_fn("chats", move |limit: i64| -> ScriptResult<Array> {
+            let convs = host.block(host.cache.list_conversations(limit))?;
+            Ok(convs.iter().map(conversation_map).collect())
+        });
+        let host = self.clone();
+        engine.register_fn("find", move |name: &str| -> ScriptResult<Array> {
+            let convs = host.block(host.cache.find_conversation(name))?;
+            Ok(convs.iter().map(conversation_map).collect())
+        });
+        let host = self.clone();
+        engine.register_fn(
+            "messages",
+            move |chat: &str, limit: i64| -> ScriptResult<Array> {
+                let messages = host.block(host.cache.get_messages(chat, limit))?;
+                Ok(messages.iter().map(message_map).collect())
+            },
+        );
+        let host = self.clone();
+        engine.register_fn(
+            "search",
+            move |query: &str, limit: i64| -> ScriptResult<Array> {
+                let results = host.block(host.cache.search(query, limit))?;
+                Ok(results
+                    .iter()
+                    .map(|r| {
+                        let mut map = message_map(&r.message).cast::<Map>();
+                        map.insert("chat_name".into(), r.conversation_name.clone().into());
+                        map.into()
+                    })
+                    .collect())
+            },
+        );
+        let host = self.clone();
+        engine.register_fn("send", move |chat: &str, text: &str| host.send(chat, text));
+        let host = self.clone();
+        engine.register_fn("react", move |chat: &str, message: &str, reaction: &str| {
+            host.react(chat, message, reaction)
+        });
+        let config = self.config.clone();
+        engine.register_fn("run", move |program: &str, args: Array| {
+            run_program(&config, program, args, "")
+        });
+        let config = self.config.clone();
+        engine.register_fn("run", move |program: &str, args: Array, input: &str| {
+            run_program(&config, program, args, input)
+        });
+        engine.register_fn("plain", |html: &str| crate::cache::strip_html(html));
+        engine.register_fn("markdown", |html: &str| {
+            crate::export::html_to_markdown(html)
+        });
+    }
+
+    /// Wait for `future` on the caller's runtime.
+    fn block<T>(&self, future: impl Future<Output = Result<T, CoreError>>) -> ScriptResult<T> {
+        self.handle
+            .block_on(future)
+            .map_err(|e| e.to_string().into())
+    }
+
+    /// Whether scripts may act on `chat` in Teams, for an action that
+    /// restricted chats may not support.
+    fn check_write(&self, chat: &str, action: Option<Action>, what: &str) -> ScriptResult<()> {
+        if !self.config.allow_send {
+            return Err(format!("{what} needs [scripting] allow_send = true").into());
+        }
+        self.security
+            .ensure_writable(what)
+            .map_err(|e| e.to_string())?;
+        if let Some(action) = action {
+            let restrictions = self.block(self.cache.restrictions(chat))?;
+            restrictions.check(action).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn send(&self, chat: &str, text: &str) -> ScriptResult<String> {
+        self.check_write(chat, None, "sending messages")?;
+        let html = crate::markup::markdown_to_html(text);
+        let result = self.handle.block_on(crate::outbox::track(
+            Some(&self.cache),
+            chat,
+            &html,
+            OutgoingKind::Text,
+            crate::engine::send_message(chat, &html),
+        ));
+        if let Some(ref audit) = self.audit {
+            audit.record("script.send", Some(chat), &result);
+        }
+        let sent = result.map_err(|e| e.to_string())?;
+        Ok(sent
+            .get("id")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    fn react(&self, chat: &str, message: &str, reaction: &str) -> ScriptResult<()> {
+        self.check_write(chat, Some(Action::React), "adding reactions")?;
+        let key = crate::reactions::key_for(reaction)
+            .ok_or_else(|| format!("unknown reaction '{reaction}'"))?;
+        let result = self.handle.block_on(async {
+            crate::TeamsClient::new()?
+                .add_reaction(chat, message, &key)
+                .await
+        });
+        if let Some(ref audit) = self.audit {
+            audit.record("script.react", Some(chat), &result);
+        }
+        result.map_err(|e| e.to_string().into())
+    }
+}
+
+/// `run()`: start `program` with `args`, write `input` to its stdin, and
+/// return its stdout.
+fn run_program(
+    config: &ScriptingConfig,
+    program: &str,
+    args: Array,
+    input: &str,
+) -> ScriptResult<String> {
+    if !config.allow_commands {
+        return Err("run() needs [scripting] allow_commands = true".into());
+    }
+    let args: Vec<String> = args.into_iter().map(|arg| arg.to_string()).collect();
+    let mut child = Command::new(program)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("starting {program}: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_string();
+        // Written from a thread so a program that answers before reading
+        // all input cannot block on a full pipe.
+        std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("running {program}: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{program} failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn context_map(ctx: ActionContext) -> Map {
+    let optional = |value: Option<String>| value.map_or(Dynamic::UNIT, Dynamic::from);
+    let mut map = Map::new();
+    map.insert("chat".into(), optional(ctx.chat));
+    map.insert("chat_name".into(), optional(ctx.chat_name));
+    map.insert("message".into(), optional(ctx.message));
+    map.insert(
+        "args".into(),
+        ctx.args
+            .into_iter()
+            .map(Dynamic::from)
+            .collect::<Array>()
+            .into(),
+    );
+    map
+}
+
+fn conversation_map(conv: &CachedConversation) -> Dynamic {
+    let name = if conv.display_name.is_empty() {
+        &conv.member_names
+    } else {
+        &conv.display_name
+    };
+    let mut map = Map::new();
+    map.insert("id".into(), conv.id.clone().into());
+    map.insert("name".into(), name.clone().into());
+    map.insert("type".into(), conv.thread_type.clone().into());
+    map.insert("last_activity".into(), conv.last_activity.clone().into());
+    map.insert("preview".into(), conv.last_message_preview.clone().into());
+    map.into()
+}
+
+fn message_map(msg: &CachedMessage) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("id".into(), msg.id.clone().into());
+    map.insert("chat".into(), msg.conversation_id.clone().into());
+    map.insert("from".into(), msg.from_display_name.clone().into());
+    map.insert("text".into(), msg.content.clone().into());
+    map.insert("html".into(), msg.content_html.clone().into());
+    map.insert("time".into(), msg.compose_time.clone().into());
+    map.insert("mine".into(), msg.is_from_me.into());
+    map.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    const SCRIPT: &str = r#"
+/// Count cached chats
+fn count(ctx) {
+    print(`in ${ctx.chat}`);
+    chats(10).len()
+}
+
+private fn helper(ctx) { 1 }
+
+fn add(a, b) { a + b }
+
+fn post(ctx) { send(ctx.chat, "**hi**") }
+
+fn spin(ctx) { loop {} }
+
+fn shell(ctx) { run("echo", ctx.args) }
+"#;
+
+    /// The scripts of `SCRIPT`, loaded with `config`, and a context for
+    /// chat `19:a` with one argument.
+    fn load(dir: &TempDir, config: &AppConfig) -> (Scripts, ActionContext) {
+        let path = dir.join(SCRIPT_FILE);
+        std::fs::write(&path, SCRIPT).unwrap();
+        let ctx = ActionContext {
+            chat: Some("19:a".to_string()),
+            args: vec!["hello".to_string()],
+            ..ActionContext::default()
+        };
+        (Scripts::load(&path, config).unwrap().unwrap(), ctx)
+    }
+
+    fn limited() -> AppConfig {
+        let mut config = AppConfig::default();
+        config.scripting.max_operations = 10_000;
+        config
+    }
+
+    fn err(result: Result<Vec<String>, CoreError>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn without_a_script_file_there_are_no_actions() {
+        let dir = TempDir::new("scripting-none").unwrap();
+        let path = dir.join(SCRIPT_FILE);
+        assert!(Scripts::load(&path, &limited()).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn public_functions_taking_a_context_are_actions() {
+        let dir = TempDir::new("scripting").unwrap();
+        let (scripts, _) = load(&dir, &limited());
+        let names: Vec<String> = scripts.actions().into_iter().map(|a| a.name).collect();
+        assert_eq!(names, ["count", "post", "shell", "spin"]);
+        assert_eq!(scripts.actions()[0].description, "Count cached chats");
+    }
+
+    #[tokio::test]
+    async fn actions_return_their_output_and_result() {
+        let dir = TempDir::new("scripting-run").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let (scripts, ctx) = load(&dir, &limited());
+        let out = scripts.run(&cache, "count", ctx).await.unwrap();
+        assert_eq!(out, ["in 19:a", "0"]);
+    }
+
+    #[tokio::test]
+    async fn private_functions_cannot_be_run() {
+        let dir = TempDir::new("scripting-private").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let (scripts, ctx) = load(&dir, &limited());
+        assert!(err(scripts.run(&cache, "helper", ctx).await).contains("no action"));
+    }
+
+    #[tokio::test]
+    async fn sending_needs_allow_send() {
+        let dir = TempDir::new("scripting-send").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let (scripts, ctx) = load(&dir, &limited());
+        assert!(err(scripts.run(&cache, "post", ctx).await).contains("allow_send"));
+    }
+
+    #[tokio::test]
+    async fn commands_need_allow_commands() {
+        let dir = TempDir::new("scripting-commands").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let (scripts, ctx) = load(&dir, &limited());
+        assert!(err(scripts.run(&cache, "shell", ctx).await).contains("allow_commands"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn allowed_commands_return_their_output() {
+        let dir = TempDir::new("scripting-commands-allowed").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let mut config = limited();
+        config.scripting.allow_commands = true;
+        let (scripts, ctx) = load(&dir, &config);
+        assert_eq!(
+            scripts.run(&cache, "shell", ctx).await.unwrap(),
+            ["hello\n"]
+        );
+    }
+
+    #[tokio::test]
+    async fn runaway_loops_are_cut_short() {
+        let dir = TempDir::new("scripting-loop").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let (scripts, ctx) = load(&dir, &limited());
+        assert!(err(scripts.run(&cache, "spin", ctx).await).contains("operations"));
+    }
+}
@@ -0,0 +1,135 @@
+//! Generated secrets: hex encoding, constant-time comparison, and files
+//! only the owner can read.
+//!
+//! The bearer tokens of `tmz-api` and `tmz-mcp` are generated on first use
+//! and kept in the state directory. Their files are created with mode 0600
+//! rather than restricted after writing, so no other local user can read
+//! the secret in between.
+
+use std::io::Write as _;
+use std::path::Path;
+
+use crate::CoreError;
+
+/// Lowercase hex of `bytes`.
+#[must_use]
+pub fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut out, b| {
+            use std::fmt::Write as _;
+            let _ = write!(out, "{b:02x}");
+            out
+        })
+}
+
+/// Compare without short-circuiting on the first differing byte.
+#[must_use]
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// `len` random bytes as lowercase hex.
+///
+/// # Errors
+///
+/// Returns an error if the system random source is unavailable.
+pub fn random_hex(len: usize) -> Result<String, CoreError> {
+    let mut bytes = vec![0u8; len];
+    getrandom::fill(&mut bytes).map_err(|e| CoreError::Other(format!("generating token: {e}")))?;
+    Ok(hex(&bytes))
+}
+
+/// Write `contents` to `path`, readable and writable by the owner only.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be created or written.
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt as _, PermissionsExt as _};
+        options.mode(0o600);
+        let file = options.open(path)?;
+        // An existing file keeps its mode on open; tighten it before writing.
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        write_all(file, contents)
+    }
+    #[cfg(not(unix))]
+    {
+        write_all(options.open(path)?, contents)
+    }
+}
+
+fn write_all(mut file: std::fs::File, contents: &[u8]) -> std::io::Result<()> {
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// The token stored in `path`, generating and storing one on first use.
+/// Returns the token and whether it was generated.
+///
+/// # Errors
+///
+/// Returns an error if no token can be generated or the file cannot be
+/// written.
+pub fn persisted_token(path: &Path) -> Result<(String, bool), CoreError> {
+    if let Ok(existing) = std::fs::read_to_string(path) {
+        let existing = existing.trim();
+        if !existing.is_empty() {
+            return Ok((existing.to_string(), false));
+        }
+    }
+
+    let token = random_hex(32)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    write_private(path, token.as_bytes())?;
+    Ok((token, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn hex_is_lowercase_and_zero_padded() {
+        assert_eq!(hex(&[0x00, 0xab, 0x7f]), "00ab7f");
+    }
+
+    #[test]
+    fn constant_time_eq_compares_whole_strings() {
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(!constant_time_eq("secret", "secreT"));
+        assert!(!constant_time_eq("secret", "secret2"));
+    }
+
+    #[test]
+    fn generated_tokens_are_reused() {
+        let dir = TempDir::new("secrets").unwrap();
+        let path = dir.join("token");
+        let (token, generated) = persisted_token(&path).unwrap();
+        assert!(generated);
+        assert_eq!(token.len(), 64);
+        assert_eq!(persisted_token(&path).unwrap(), (token, false));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn generated_tokens_are_private() {
+        use std::os::unix::fs::PermissionsExt as _;
+        let dir = TempDir::new("secrets").unwrap();
+        let path = dir.join("token");
+        persisted_token(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
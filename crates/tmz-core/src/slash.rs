@@ -0,0 +1,161 @@
+//! Slash commands in composed messages.
+//!
+//! The TUI composer and `tmz msg` read a message starting with `/me`,
+//! `/code`, `/file`, `/react`, or `/shrug` as a quick action. Any other
+//! leading `/` is sent as written, and `//` sends a message that starts
+//! with a literal `/`.
+
+use std::path::PathBuf;
+
+use crate::CoreError;
+use crate::markup::escape_html;
+
+/// The slash commands, for help texts.
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("/me <action>", "italic action line with your name"),
+    ("/code <text>", "send as a preformatted code block"),
+    ("/file <path>", "upload a file"),
+    (
+        "/react <emoji>",
+        "react to the newest message from someone else",
+    ),
+    ("/shrug [text]", "append \u{af}\\_(\u{30c4})_/\u{af}"),
+];
+
+/// What a slash command asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlashCommand {
+    /// Send this message content (HTML) instead of the input.
+    Send(String),
+    /// Upload the file at this path.
+    File(PathBuf),
+    /// React to the newest message from someone else with this reaction
+    /// key.
+    React(String),
+}
+
+/// Parse `input` as a slash command. `my_name` is used by `/me`. Returns
+/// `None` for ordinary messages.
+///
+/// # Errors
+///
+/// Returns an error if a command is missing its argument or `/react` gets
+/// an unknown reaction.
+pub fn parse(input: &str, my_name: Option<&str>) -> Result<Option<SlashCommand>, CoreError> {
+    let Some(rest) = input.strip_prefix('/') else {
+        return Ok(None);
+    };
+    if rest.starts_with('/') {
+        return Ok(Some(SlashCommand::Send(escape_html(rest))));
+    }
+    let (name, arg) = rest
+        .split_once(char::is_whitespace)
+        .map_or((rest, ""), |(name, arg)| (name, arg.trim()));
+    let required = |usage: &str| {
+        if arg.is_empty() {
+            Err(CoreError::Other(format!("usage: {usage}")))
+        } else {
+            Ok(arg)
+        }
+    };
+    let command = match name {
+        "me" => {
+            let action = escape_html(required("/me <action>")?);
+            let line = my_name.map_or_else(
+                || action.clone(),
+                |me| format!("{} {action}", escape_html(me)),
+            );
+            SlashCommand::Send(format!("<i>{line}</i>"))
+        }
+        "code" => SlashCommand::Send(format!(
+            "<pre>{}</pre>",
+            escape_html(required("/code <text>")?)
+        )),
+        "shrug" => {
+            let shrug = "\u{af}\\_(\u{30c4})_/\u{af}";
+            SlashCommand::Send(if arg.is_empty() {
+                shrug.to_string()
+            } else {
+                format!("{} {shrug}", escape_html(arg))
+            })
+        }
+        "file" => {
+            let path = required("/file <path>")?;
+            SlashCommand::File(PathBuf::from(shellexpand::tilde(path).as_ref()))
+        }
+        "react" => {
+            let reaction = required("/react <emoji>")?;
+            let key = crate::reactions::key_for(reaction)
+                .ok_or_else(|| CoreError::Other(format!("unknown reaction '{reaction}'")))?;
+            SlashCommand::React(key)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(html: &str) -> SlashCommand {
+        SlashCommand::Send(html.to_string())
+    }
+
+    #[test]
+    fn text_and_paths_are_not_commands() {
+        assert_eq!(parse("hello", None).unwrap(), None);
+        assert_eq!(parse("/usr/bin is full", None).unwrap(), None);
+    }
+
+    #[test]
+    fn a_double_slash_sends_the_text_escaped() {
+        assert_eq!(parse("//me too", None).unwrap(), Some(send("/me too")));
+        assert_eq!(parse("//a <b>", None).unwrap(), Some(send("/a &lt;b&gt;")));
+    }
+
+    #[test]
+    fn formatting_commands_expand_to_html() {
+        assert_eq!(
+            parse("/me waves", Some("Alex")).unwrap(),
+            Some(send("<i>Alex waves</i>"))
+        );
+        assert_eq!(
+            parse("/code if a < b {\n  go()\n}", None).unwrap(),
+            Some(send("<pre>if a &lt; b {\n  go()\n}</pre>"))
+        );
+        assert_eq!(
+            parse("/shrug no idea", None).unwrap(),
+            Some(send("no idea \u{af}\\_(\u{30c4})_/\u{af}"))
+        );
+    }
+
+    #[test]
+    fn react_and_file_become_actions() {
+        assert_eq!(
+            parse("/react \u{1f44d}", None).unwrap(),
+            Some(SlashCommand::React("like".to_string()))
+        );
+        assert_eq!(
+            parse("/file /tmp/report.pdf", None).unwrap(),
+            Some(SlashCommand::File(PathBuf::from("/tmp/report.pdf")))
+        );
+    }
+
+    #[test]
+    fn missing_or_unknown_arguments_are_errors() {
+        assert!(parse("/me", None).is_err());
+        assert!(parse("/react banana", None).is_err());
+    }
+
+    #[test]
+    fn expanded_html_is_silenced_like_any_message() {
+        let Some(SlashCommand::Send(html)) = parse("/me @channel", Some("Alex")).unwrap() else {
+            panic!("/me should send a message");
+        };
+        assert_eq!(
+            crate::markup::silence(&html),
+            "<i>Alex @\u{2060}channel</i>"
+        );
+    }
+}
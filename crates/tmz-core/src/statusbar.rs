@@ -0,0 +1,228 @@
+//! Status bar templates (`[statusbar] format`).
+//!
+//! A template mixes literal text with `{name}` segments that the TUI fills
+//! in (and styles) on every frame, plus tmux-style conditionals:
+//! `{?name:text}` shows `text` only when the segment is not empty, and
+//! `{?name:text|other}` shows `other` instead when it is. Templates are
+//! parsed once at startup, so a typo is reported before anything is drawn.
+
+use crate::CoreError;
+
+/// The built-in layout.
+pub const DEFAULT_FORMAT: &str =
+    "{mode} {token} │ {sync} │ {?unread:{unread} }{status}  [{profile}]  {keys}";
+
+/// Segment names a template may use.
+pub const FIELDS: &[&str] = &[
+    "mode", "token", "sync", "unread", "status", "profile", "keys", "chat", "outbox", "readonly",
+    "time",
+];
+
+/// One piece of a parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// Literal text.
+    Text(String),
+    /// A named segment.
+    Field(String),
+    /// `{?field:then|otherwise}`.
+    When {
+        /// Segment whose emptiness is tested.
+        field: String,
+        /// Shown when the segment is not empty.
+        then: Vec<Self>,
+        /// Shown when it is.
+        otherwise: Vec<Self>,
+    },
+}
+
+/// A parsed status bar template.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusFormat {
+    segments: Vec<Segment>,
+}
+
+impl StatusFormat {
+    /// A template that renders nothing.
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            segments: Vec::new(),
+        }
+    }
+
+    /// Parse a template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for unbalanced braces or unknown segment names.
+    pub fn parse(template: &str) -> Result<Self, CoreError> {
+        let mut chars = template.chars().peekable();
+        let (segments, end) = parse_until(&mut chars, false)
+            .map_err(|e| CoreError::Config(format!("invalid [statusbar] format: {e}")))?;
+        debug_assert!(end.is_none());
+        Ok(Self { segments })
+    }
+
+    /// The parsed segments.
+    #[must_use]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Render the template: `field` gives a segment's rendering, or `None`
+    /// when it is empty, and `text` renders literal text.
+    pub fn render<T>(
+        &self,
+        field: &impl Fn(&str) -> Option<T>,
+        text: &impl Fn(&str) -> T,
+    ) -> Vec<T> {
+        let mut out = Vec::new();
+        render_into(&self.segments, field, text, &mut out);
+        out
+    }
+}
+
+fn render_into<T>(
+    segments: &[Segment],
+    field: &impl Fn(&str) -> Option<T>,
+    text: &impl Fn(&str) -> T,
+    out: &mut Vec<T>,
+) {
+    for segment in segments {
+        match segment {
+            Segment::Text(s) => out.push(text(s)),
+            Segment::Field(name) => out.extend(field(name)),
+            Segment::When {
+                field: name,
+                then,
+                otherwise,
+            } => {
+                let branch = if field(name).is_some() {
+                    then
+                } else {
+                    otherwise
+                };
+                render_into(branch, field, text, out);
+            }
+        }
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+/// Parse up to the end of input or, inside a conditional, up to its `|` or
+/// closing `}`, which is returned.
+fn parse_until(
+    chars: &mut Chars<'_>,
+    in_when: bool,
+) -> Result<(Vec<Segment>, Option<char>), String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') && !in_when => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                if !literal.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut literal)));
+                }
+                segments.push(parse_segment(chars)?);
+            }
+            '}' | '|' if in_when => {
+                if !literal.is_empty() {
+                    segments.push(Segment::Text(literal));
+                }
+                return Ok((segments, Some(c)));
+            }
+            '}' => return Err("unmatched `}` (write `}}` for a literal brace)".to_string()),
+            _ => literal.push(c),
+        }
+    }
+    if in_when {
+        return Err("unclosed `{?...}`".to_string());
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Text(literal));
+    }
+    Ok((segments, None))
+}
+
+/// Parse a `{name}` or `{?name:...}` after its opening brace.
+fn parse_segment(chars: &mut Chars<'_>) -> Result<Segment, String> {
+    let conditional = chars.next_if_eq(&'?').is_some();
+    let mut name = String::new();
+    loop {
+        match chars.next() {
+            Some('}') if !conditional => return Ok(Segment::Field(known(name)?)),
+            Some(':') if conditional => break,
+            Some(c) if c.is_ascii_alphanumeric() || c == '_' => name.push(c),
+            Some(c) => return Err(format!("unexpected `{c}` in `{{{name}`")),
+            None => return Err(format!("unclosed `{{{name}`")),
+        }
+    }
+    let field = known(name)?;
+    let (then, end) = parse_until(chars, true)?;
+    let otherwise = if end == Some('|') {
+        match parse_until(chars, true)? {
+            (segments, Some('}')) => segments,
+            _ => return Err(format!("`{{?{field}:...}}` has more than one `|`")),
+        }
+    } else {
+        Vec::new()
+    };
+    Ok(Segment::When {
+        field,
+        then,
+        otherwise,
+    })
+}
+
+fn known(name: String) -> Result<String, String> {
+    if FIELDS.contains(&name.as_str()) {
+        Ok(name)
+    } else {
+        Err(format!(
+            "unknown segment `{name}` (known: {})",
+            FIELDS.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_fields_and_conditionals() {
+        assert!(StatusFormat::parse(DEFAULT_FORMAT).is_ok());
+        let format =
+            StatusFormat::parse("{mode} {?unread:{unread} |all read }{{x}} {?chat:in {chat}}")
+                .unwrap();
+        let render = |values: &[(&str, &str)]| {
+            let field = |name: &str| {
+                values
+                    .iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| (*v).to_string())
+            };
+            format.render(&field, &str::to_string).concat()
+        };
+        assert_eq!(render(&[("mode", "N"), ("unread", "@2")]), "N @2 {x} ");
+        assert_eq!(
+            render(&[("mode", "N"), ("chat", "ops")]),
+            "N all read {x} in ops"
+        );
+
+        for bad in ["{nope}", "{mode", "{?sync:x", "a } b", "{?sync:a|b|c}"] {
+            let err = StatusFormat::parse(bad).unwrap_err().to_string();
+            assert!(err.contains("[statusbar] format"), "{bad}: {err}");
+        }
+    }
+}
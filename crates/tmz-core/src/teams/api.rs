@@ -0,0 +1,107 @@
+//! The Teams calls the sync engine depends on, as a trait.
+//!
+//! [`crate::TeamsClient`] implements [`TeamsApi`] against the live services;
+//! [`crate::teams::mock::MockTeamsClient`] implements it from fixture JSON,
+//! so [`crate::engine::Engine::sync_with_client`] and the code built on it
+//! can be exercised without tokens or network access.
+
+use std::future::Future;
+
+use crate::CoreError;
+use crate::teams::client::TeamsClient;
+use crate::teams::models::{Conversation, LiveEvent, Message, PostOptions};
+
+/// Teams operations used by sync, push events, and sending.
+pub trait TeamsApi: Send + Sync {
+    /// The signed-in user's Graph profile (`displayName`, `id`, ...).
+    fn get_me(&self) -> impl Future<Output = Result<serde_json::Value, CoreError>> + Send;
+
+    /// The user's conversations (see [`TeamsClient::list_chats`]).
+    fn list_chats(&self) -> impl Future<Output = Result<Vec<Conversation>, CoreError>> + Send;
+
+    /// Recent messages of a conversation, with `is_from_me` and
+    /// `mentions_me` set (see [`TeamsClient::get_chat_messages`]).
+    fn get_chat_messages(
+        &self,
+        conversation_id: &str,
+        page_size: Option<i32>,
+    ) -> impl Future<Output = Result<Vec<Message>, CoreError>> + Send;
+
+    /// Changes pushed by the chat service, waiting until some arrive or
+    /// the poll times out (see [`TeamsClient::poll_events`]).
+    fn poll_events(&self) -> impl Future<Output = Result<Vec<LiveEvent>, CoreError>> + Send;
+
+    /// Send a message with a subject or importance; the response carries
+    /// the new message's `id` (see [`crate::outbox::acknowledgement`]).
+    fn send_post(
+        &self,
+        conversation_id: &str,
+        content: &str,
+        options: &PostOptions,
+    ) -> impl Future<Output = Result<serde_json::Value, CoreError>> + Send;
+
+    /// Send a plain message.
+    fn send_message(
+        &self,
+        conversation_id: &str,
+        content: &str,
+    ) -> impl Future<Output = Result<serde_json::Value, CoreError>> + Send {
+        async move {
+            self.send_post(conversation_id, content, &PostOptions::default())
+                .await
+        }
+    }
+
+    /// Bytes of an image attached to a message.
+    fn download_image(&self, url: &str) -> impl Future<Output = Result<Vec<u8>, CoreError>> + Send;
+
+    /// Bytes of a file shared in chat, or `None` if it is over `max_bytes`.
+    fn download_shared_file(
+        &self,
+        url: &str,
+        max_bytes: u64,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, CoreError>> + Send;
+}
+
+impl TeamsApi for TeamsClient {
+    async fn get_me(&self) -> Result<serde_json::Value, CoreError> {
+        Self::get_me(self).await
+    }
+
+    async fn list_chats(&self) -> Result<Vec<Conversation>, CoreError> {
+        Self::list_chats(self).await
+    }
+
+    async fn get_chat_messages(
+        &self,
+        conversation_id: &str,
+        page_size: Option<i32>,
+    ) -> Result<Vec<Message>, CoreError> {
+        Self::get_chat_messages(self, conversation_id, page_size).await
+    }
+
+    async fn poll_events(&self) -> Result<Vec<LiveEvent>, CoreError> {
+        Self::poll_events(self).await
+    }
+
+    async fn send_post(
+        &self,
+        conversation_id: &str,
+        content: &str,
+        options: &PostOptions,
+    ) -> Result<serde_json::Value, CoreError> {
+        Self::send_post(self, conversation_id, content, options).await
+    }
+
+    async fn download_image(&self, url: &str) -> Result<Vec<u8>, CoreError> {
+        Self::download_image(self, url).await
+    }
+
+    async fn download_shared_file(
+        &self,
+        url: &str,
+        max_bytes: u64,
+    ) -> Result<Option<Vec<u8>>, CoreError> {
+        Self::download_shared_file(self, url, max_bytes).await
+    }
+}
@@ -5,14 +5,18 @@
 //! 2. Automatically extracting MSAL access tokens from localStorage
 //! 3. Storing tokens to `$XDG_STATE_HOME/tmz/tokens.json`
 //!
-//! After the first interactive login, SSO cookies are cached in a persistent
-//! browser profile. Subsequent token refreshes run headlessly - no user
-//! interaction required until the SSO session itself expires.
+//! The login also captures the MSAL refresh token of the Teams web app, which
+//! later refreshes redeem directly at the token endpoint. SSO cookies are
+//! cached in a persistent browser profile as well, so when the refresh token
+//! has expired, the browser runs headlessly instead - no user interaction
+//! required until the SSO session itself expires.
 
 use crate::CoreError;
-use crate::teams::models::TeamsTokens;
+use crate::teams::device_code::{self, DeviceCode};
+use crate::teams::models::{TeamsTokens, TokenAudience};
 use crate::teams::storage::TokenStorage;
 use serde_json::Value;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
@@ -30,24 +34,38 @@ pub enum AuthenticationError {
     /// JWT parsing error.
     #[error("JWT parsing error: {0}")]
     JwtError(String),
+
+    /// A token that is valid by expiry was rejected by its service.
+    #[error(
+        "{0} token was rejected by the service (revoked or issued for another tenant). Run 'tmz auth login' to re-authenticate."
+    )]
+    TokenRejected(TokenAudience),
 }
 
 /// Handles Teams authentication and token management.
+///
+/// Tokens and the browser profile belong to the account selected with
+/// [`crate::accounts::select`], or the default account.
 #[derive(Debug)]
 pub struct AuthManager {
     storage: TokenStorage,
+    browser_profile_dir: PathBuf,
+    account: Option<String>,
 }
 
 /// How far before expiry to trigger a refresh (5 minutes).
 const REFRESH_BUFFER_SECS: i64 = 300;
 
+/// How far ahead of expiry commands that talk to Teams refresh the tokens
+/// before starting (15 minutes), so a long sync does not have to stop for a
+/// refresh halfway through.
+pub const REFRESH_AHEAD_SECS: i64 = 900;
+
 /// Timeout for headless refresh (seconds). SSO with cached cookies
 /// completes in a few seconds; if it takes longer, the session is stale.
 const HEADLESS_TIMEOUT_SECS: u64 = 30;
 
 impl AuthManager {
-    /// Teams web client URL.
-    pub const TEAMS_URL: &str = "https://teams.microsoft.com/v2";
     /// Client ID for Teams web application.
     pub const TEAMS_CLIENT_ID: &str = "5e3ce6c0-2b1f-4285-8d4b-75ee78787346";
 
@@ -57,11 +75,57 @@ impl AuthManager {
     ///
     /// Returns an error if the state directory cannot be determined.
     pub fn new() -> Result<Self, AuthenticationError> {
+        let browser_profile_dir = crate::default_browser_profile_dir()
+            .map_err(|e| CoreError::Path(format!("resolving browser profile dir: {e}")))?;
         Ok(Self {
             storage: TokenStorage::new().map_err(AuthenticationError::StorageError)?,
+            browser_profile_dir,
+            account: crate::accounts::active().map(str::to_string),
         })
     }
 
+    /// Name of the account whose tokens this manager uses (`None` for the
+    /// default account).
+    #[must_use]
+    pub fn account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+
+    /// Path of the token file.
+    #[must_use]
+    pub fn token_path(&self) -> &Path {
+        self.storage.path()
+    }
+
+    /// Use a specific browser profile directory instead of the default.
+    #[must_use]
+    pub fn with_browser_profile(mut self, dir: PathBuf) -> Self {
+        self.browser_profile_dir = dir;
+        self
+    }
+
+    /// Persistent browser profile directory used for SSO.
+    #[must_use]
+    pub fn browser_profile_dir(&self) -> &Path {
+        &self.browser_profile_dir
+    }
+
+    /// Delete the persistent browser profile.
+    ///
+    /// Returns the number of bytes freed. The next browser login starts
+    /// from a clean profile and re-seeds the SSO session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the profile exists but cannot be removed.
+    pub fn reset_browser_profile(&self) -> Result<u64, AuthenticationError> {
+        let freed = crate::paths::disk_usage(&self.browser_profile_dir);
+        if self.browser_profile_dir.exists() {
+            std::fs::remove_dir_all(&self.browser_profile_dir).map_err(CoreError::Io)?;
+        }
+        Ok(freed)
+    }
+
     /// Check if we have valid cached tokens.
     ///
     /// # Errors
@@ -92,6 +156,7 @@ impl AuthManager {
 
         let mut cmd = tokio::process::Command::new("node");
         cmd.arg(&script_path);
+        cmd.arg("--profile-dir").arg(&self.browser_profile_dir);
 
         if fresh {
             cmd.arg("--fresh");
@@ -105,6 +170,25 @@ impl AuthManager {
             cmd.arg("--headless");
         }
 
+        let endpoints = crate::teams::endpoints::current();
+        cmd.arg("--endpoints").arg(
+            serde_json::json!({
+                "login": endpoints.login,
+                "teams": endpoints.teams,
+                "skype": endpoints.skype,
+                "chat": endpoints.chat,
+                "graph": endpoints.graph,
+                "presence": endpoints.presence,
+            })
+            .to_string(),
+        );
+
+        // Passed in the environment so proxy credentials stay out of the
+        // process list.
+        if let Some((server, bypass)) = crate::network::browser_proxy() {
+            cmd.env("TMZ_PROXY", server).env("TMZ_PROXY_BYPASS", bypass);
+        }
+
         // stdout = token JSON, stderr = progress/log messages.
         // Always capture stderr so we can detect Playwright import errors
         // and show actionable hints. In interactive mode, captured stderr
@@ -191,25 +275,161 @@ impl AuthManager {
         self.store_tokens_from_browser(&local_storage)
     }
 
-    /// Silently refresh tokens using cached SSO cookies.
+    /// Silently refresh tokens.
     ///
-    /// Runs the browser headlessly with a short timeout. If the SSO session
-    /// is still valid, fresh tokens are extracted without user interaction.
+    /// Redeems the stored refresh token (from a device code or browser login)
+    /// first. Without one, or when it is rejected, runs the browser headlessly
+    /// with a short timeout, which extracts fresh tokens if the SSO session is
+    /// still valid; that needs Node.js and the auth script, so it is skipped
+    /// when they are missing.
     ///
     /// # Errors
     ///
-    /// Returns an error if headless refresh fails (SSO session expired).
+    /// Returns an error if neither refresh works.
     pub async fn refresh_tokens(&self) -> Result<TeamsTokens, AuthenticationError> {
+        if let Ok(stored) = self.storage.load_tokens()
+            && !stored.refresh_token.is_empty()
+        {
+            log::debug!("refreshing tokens with stored refresh token");
+            match self
+                .refresh_with_refresh_token(&stored.refresh_token, &stored.refresh_client_id)
+                .await
+            {
+                Ok(tokens) => return Ok(tokens),
+                Err(e) => log::debug!("refresh token redemption failed: {e}"),
+            }
+        }
+
+        if find_auth_script().is_err() {
+            return Err(AuthenticationError::TokenExtractionError(
+                "token refresh failed and browser login is not installed. \
+                 Run 'tmz auth login' to sign in again."
+                    .to_string(),
+            ));
+        }
         log::debug!("attempting headless token refresh");
         self.browser_login(Some(HEADLESS_TIMEOUT_SECS), true, false)
             .await
     }
 
+    /// Start a device code login, the built-in login flow.
+    ///
+    /// Show the returned verification URL and user code to the user, then
+    /// call [`Self::complete_device_login`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the authorization endpoint rejects the request.
+    pub async fn start_device_login(&self) -> Result<DeviceCode, AuthenticationError> {
+        device_code::start().await
+    }
+
+    /// Wait for a device code login to complete and store the tokens.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the login fails, expires, or is declined.
+    pub async fn complete_device_login(
+        &self,
+        code: &DeviceCode,
+    ) -> Result<TeamsTokens, AuthenticationError> {
+        let skype = device_code::poll(code).await?;
+        let refresh_token = skype.refresh_token.ok_or_else(|| {
+            AuthenticationError::TokenExtractionError(
+                "device code login returned no refresh token".to_string(),
+            )
+        })?;
+        self.store_tokens_from_refresh_token(skype.access_token, refresh_token, String::new())
+            .await
+    }
+
+    /// Refresh every audience by redeeming a stored refresh token issued to
+    /// `client_id` (empty for the device code client).
+    async fn refresh_with_refresh_token(
+        &self,
+        refresh_token: &str,
+        client_id: &str,
+    ) -> Result<TeamsTokens, AuthenticationError> {
+        let skype = device_code::redeem_refresh_token(
+            refresh_token,
+            refresh_client(client_id),
+            TokenAudience::Skype,
+        )
+        .await?;
+        let refresh_token = skype
+            .refresh_token
+            .unwrap_or_else(|| refresh_token.to_string());
+        self.store_tokens_from_refresh_token(
+            skype.access_token,
+            refresh_token,
+            client_id.to_string(),
+        )
+        .await
+    }
+
+    /// Redeem the refresh token for the non-skype audiences and store all tokens.
+    ///
+    /// Audiences that cannot be obtained are left empty.
+    async fn store_tokens_from_refresh_token(
+        &self,
+        skype_token: String,
+        mut refresh_token: String,
+        refresh_client_id: String,
+    ) -> Result<TeamsTokens, AuthenticationError> {
+        let mut others = [String::new(), String::new(), String::new()];
+        let audiences = [
+            TokenAudience::Chat,
+            TokenAudience::Graph,
+            TokenAudience::Presence,
+        ];
+        for (slot, audience) in others.iter_mut().zip(audiences) {
+            match device_code::redeem_refresh_token(
+                &refresh_token,
+                refresh_client(&refresh_client_id),
+                audience,
+            )
+            .await
+            {
+                Ok(response) => {
+                    *slot = response.access_token;
+                    if let Some(rotated) = response.refresh_token {
+                        refresh_token = rotated;
+                    }
+                }
+                Err(e) => log::warn!("could not obtain {audience} token: {e}"),
+            }
+        }
+        let [chat_token, graph_token, presence_token] = others;
+
+        let (tenant_id, user_id, upn, expires_at) = derive_identity_from_tokens([
+            skype_token.as_str(),
+            chat_token.as_str(),
+            graph_token.as_str(),
+            presence_token.as_str(),
+        ]);
+
+        let tokens = TeamsTokens {
+            skype_token,
+            chat_token,
+            graph_token,
+            presence_token,
+            tenant_id,
+            user_id,
+            user_principal_name: upn,
+            expires_at,
+            refresh_token,
+            refresh_client_id,
+        };
+
+        self.storage.store_tokens(&tokens)?;
+        Ok(tokens)
+    }
+
     /// Get valid tokens, auto-refreshing if expired or about to expire.
     ///
     /// Resolution order:
     /// 1. Return cached tokens if still valid (with buffer)
-    /// 2. Attempt headless refresh via cached SSO cookies
+    /// 2. Attempt a silent refresh (see [`Self::refresh_tokens`])
     /// 3. Fail with a message to run `tmz auth login`
     ///
     /// # Errors
@@ -235,21 +455,82 @@ impl AuthManager {
                             );
                             Ok(tokens)
                         } else {
-                            Err(AuthenticationError::TokenExtractionError(
-                                "tokens expired and headless refresh failed. Run 'tmz auth login'."
-                                    .to_string(),
-                            ))
+                            Err(AuthenticationError::TokenExtractionError(format!(
+                                "tokens expired and refresh failed. Run '{}'.",
+                                crate::accounts::login_command()
+                            )))
                         }
                     }
                 }
             }
-            Err(CoreError::SecretNotFound(_)) => Err(AuthenticationError::TokenExtractionError(
-                "not authenticated. Run 'tmz auth login' first.".to_string(),
-            )),
+            Err(CoreError::SecretNotFound(_)) => {
+                Err(AuthenticationError::TokenExtractionError(format!(
+                    "not authenticated. Run '{}' first.",
+                    crate::accounts::login_command()
+                )))
+            }
             Err(e) => Err(AuthenticationError::StorageError(e)),
         }
     }
 
+    /// Handle a token that the service rejected despite not being expired.
+    ///
+    /// Attempts a headless refresh and returns the refreshed tokens if a new
+    /// token for `audience` was obtained. The stored token is only
+    /// invalidated when a refresh succeeded but could not replace it, so a
+    /// failed refresh after a transient `401` keeps the user signed in.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AuthenticationError::TokenRejected`] if no replacement token
+    /// could be obtained, so callers can show a re-login message.
+    pub async fn handle_rejected_token(
+        &self,
+        audience: TokenAudience,
+    ) -> Result<TeamsTokens, AuthenticationError> {
+        let rejected = self
+            .storage
+            .load_tokens()
+            .map(|t| t.token_for(audience).to_string())
+            .unwrap_or_default();
+
+        log::warn!("{audience} token rejected by service, refreshing");
+
+        match self.refresh_tokens().await {
+            Ok(fresh) => {
+                let token = fresh.token_for(audience);
+                if token.is_empty() || token == rejected {
+                    self.storage.invalidate_token(audience)?;
+                    Err(AuthenticationError::TokenRejected(audience))
+                } else {
+                    Ok(fresh)
+                }
+            }
+            Err(e) => {
+                log::debug!("targeted refresh for {audience} token failed: {e}");
+                Err(AuthenticationError::TokenRejected(audience))
+            }
+        }
+    }
+
+    /// Load stored tokens as-is, without checking expiry or refreshing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no tokens are stored or the file cannot be read.
+    pub fn stored_tokens(&self) -> Result<TeamsTokens, AuthenticationError> {
+        Ok(self.storage.load_tokens()?)
+    }
+
+    /// Seconds left before the stored tokens expire, if they are still valid
+    /// but expire within `window` seconds.
+    #[must_use]
+    pub fn expiring_within(&self, window: i64) -> Option<i64> {
+        let tokens = self.storage.load_tokens().ok()?;
+        let left = tokens.expires_at - now_epoch();
+        (left > 0 && left <= window).then_some(left)
+    }
+
     /// Get cached tokens without auto-refresh. Returns error if expired.
     ///
     /// # Errors
@@ -260,9 +541,10 @@ impl AuthManager {
         let now = now_epoch();
 
         if tokens.expires_at < now {
-            return Err(AuthenticationError::TokenExtractionError(
-                "tokens expired. Run 'tmz auth login' or any command to auto-refresh.".to_string(),
-            ));
+            return Err(AuthenticationError::TokenExtractionError(format!(
+                "tokens expired. Run '{}' or any command to auto-refresh.",
+                crate::accounts::login_command()
+            )));
         }
 
         Ok(tokens)
@@ -271,7 +553,8 @@ impl AuthManager {
     /// Store tokens from the new script output format.
     ///
     /// The script outputs `{ "skype_token", "chat_token", "graph_token",
-    /// "presence_token", "expires_in" }`.
+    /// "presence_token", "refresh_token" }`; the refresh token is the Teams
+    /// web app's MSAL refresh token and may be empty.
     ///
     /// # Errors
     ///
@@ -293,6 +576,7 @@ impl AuthManager {
         let chat_token = get_optional("chat_token");
         let graph_token = get_optional("graph_token");
         let presence_token = get_optional("presence_token");
+        let refresh_token = get_optional("refresh_token");
 
         let (tenant_id, user_id, upn, expires_at) = derive_identity_from_tokens([
             skype_token.as_str(),
@@ -310,6 +594,8 @@ impl AuthManager {
             user_id,
             user_principal_name: upn,
             expires_at,
+            refresh_client_id: web_client_id(&refresh_token),
+            refresh_token,
         };
 
         self.storage.store_tokens(&tokens)?;
@@ -325,12 +611,11 @@ impl AuthManager {
         &self,
         local_storage: &std::collections::HashMap<String, String>,
     ) -> Result<TeamsTokens, AuthenticationError> {
-        let skype_token = Self::extract_resource_token(local_storage, "api.spaces.skype.com")?;
-        let chat_token =
-            Self::extract_resource_token(local_storage, "chatsvcagg.teams.microsoft.com")?;
-        let graph_token = Self::extract_resource_token(local_storage, "graph.microsoft.com")?;
-        let presence_token =
-            Self::extract_resource_token(local_storage, "presence.teams.microsoft.com")?;
+        let [skype_token, chat_token, graph_token, presence_token] = TokenAudience::ALL
+            .map(|audience| Self::extract_resource_token(local_storage, audience.resource()));
+        let (skype_token, chat_token, graph_token, presence_token) =
+            (skype_token?, chat_token?, graph_token?, presence_token?);
+        let refresh_token = Self::extract_refresh_token(local_storage).unwrap_or_default();
 
         let (tenant_id, user_id, upn, expires_at) = derive_identity_from_tokens([
             skype_token.as_str(),
@@ -348,6 +633,8 @@ impl AuthManager {
             user_id,
             user_principal_name: upn,
             expires_at,
+            refresh_client_id: web_client_id(&refresh_token),
+            refresh_token,
         };
 
         self.storage.store_tokens(&tokens)?;
@@ -378,12 +665,103 @@ impl AuthManager {
             user_id,
             user_principal_name: upn,
             expires_at,
+            refresh_token: String::new(),
+            refresh_client_id: String::new(),
         };
 
         self.storage.store_tokens(&tokens)?;
         Ok(tokens)
     }
 
+    /// Import tokens from the official Teams desktop client.
+    ///
+    /// Scans `dirs` (or the platform default locations when empty) for
+    /// plain-text access tokens and stores them. A skype token is required;
+    /// the other audiences are optional.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no client profile is found, no usable skype token
+    /// is stored in plain text, or storing the tokens fails.
+    pub fn import_from_teams_desktop(
+        &self,
+        dirs: &[PathBuf],
+    ) -> Result<TeamsTokens, AuthenticationError> {
+        let dirs = if dirs.is_empty() {
+            crate::teams::desktop::teams_desktop_dirs()
+        } else {
+            dirs.to_vec()
+        };
+        if dirs.is_empty() {
+            return Err(AuthenticationError::TokenExtractionError(
+                "no Teams desktop client profile found. Use --path to point at it.".to_string(),
+            ));
+        }
+
+        let found = crate::teams::desktop::scan_for_tokens(&dirs);
+        for audience in TokenAudience::ALL {
+            log::debug!(
+                "desktop import: {audience} token {}",
+                if found.get(audience).is_empty() {
+                    "not found"
+                } else {
+                    "found"
+                }
+            );
+        }
+
+        let skype = found.get(TokenAudience::Skype);
+        if skype.is_empty() {
+            return Err(AuthenticationError::TokenExtractionError(format!(
+                "no unexpired plain-text skype token found in {}. The client may encrypt its token cache; use 'tmz auth login' instead.",
+                dirs.iter()
+                    .map(|d| d.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        self.store_tokens(
+            skype,
+            found.get(TokenAudience::Chat),
+            found.get(TokenAudience::Graph),
+            found.get(TokenAudience::Presence),
+        )
+    }
+
+    /// The stored tokens encrypted with `passphrase`, for `tmz auth import
+    /// --file` on another machine (see [`crate::teams::transfer`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no tokens are stored or encryption fails.
+    pub fn export_tokens(&self, passphrase: &str) -> Result<String, AuthenticationError> {
+        let tokens = self.storage.load_tokens()?;
+        Ok(crate::teams::transfer::seal(&tokens, passphrase)?)
+    }
+
+    /// Decrypt tokens exported with [`Self::export_tokens`] and store them,
+    /// replacing the current ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the passphrase is wrong, the data holds no
+    /// tokens, or storing them fails.
+    pub fn import_tokens(
+        &self,
+        data: &[u8],
+        passphrase: &str,
+    ) -> Result<TeamsTokens, AuthenticationError> {
+        let tokens = crate::teams::transfer::open(data, passphrase)?;
+        if tokens.skype_token.is_empty() {
+            return Err(AuthenticationError::TokenExtractionError(
+                "the exported file has no skype token".to_string(),
+            ));
+        }
+        self.storage.store_tokens(&tokens)?;
+        Ok(tokens)
+    }
+
     /// Logout - clear stored tokens.
     ///
     /// # Errors
@@ -404,10 +782,8 @@ impl AuthManager {
             .iter()
             .filter_map(|(key, value)| {
                 let key_lower = key.to_lowercase();
-                (key_lower.contains("accesstoken")
-                    && key_lower.contains("login.windows.net")
-                    && key_lower.contains(&resource_lower))
-                .then_some((key.as_str(), value.as_str()))
+                (key_lower.contains("accesstoken") && key_lower.contains(&resource_lower))
+                    .then_some((key.as_str(), value.as_str()))
             })
             .collect();
 
@@ -459,6 +835,37 @@ impl AuthManager {
         )))
     }
 
+    /// The MSAL refresh token of the Teams web app from a localStorage dump.
+    ///
+    /// MSAL stores it under a `...-<login host>-refreshtoken-<client>--` key
+    /// (`login.windows.net` in the public cloud) as JSON with the token in
+    /// `secret`.
+    fn extract_refresh_token(
+        local_storage: &std::collections::HashMap<String, String>,
+    ) -> Option<String> {
+        let mut candidates: Vec<(&String, &String)> = local_storage
+            .iter()
+            .filter(|(key, _)| {
+                let key = key.to_lowercase();
+                key.contains("refreshtoken")
+            })
+            .collect();
+        candidates.sort_by_key(|(key, _)| {
+            (
+                !key.to_lowercase().contains(Self::TEAMS_CLIENT_ID),
+                key.len(),
+            )
+        });
+        candidates.into_iter().find_map(|(_, value)| {
+            serde_json::from_str::<Value>(value)
+                .ok()?
+                .get("secret")?
+                .as_str()
+                .filter(|secret| !secret.is_empty())
+                .map(str::to_string)
+        })
+    }
+
     fn extract_access_token(raw_value: &str) -> Result<String, AuthenticationError> {
         extract_jwt_from_str(raw_value, 5).ok_or_else(|| {
             AuthenticationError::TokenExtractionError(
@@ -468,6 +875,25 @@ impl AuthManager {
     }
 }
 
+/// Client ID to redeem a refresh token with; empty means the device code
+/// client.
+const fn refresh_client(client_id: &str) -> &str {
+    if client_id.is_empty() {
+        device_code::DEVICE_CODE_CLIENT_ID
+    } else {
+        client_id
+    }
+}
+
+/// Client ID recorded for a refresh token captured from the Teams web app.
+fn web_client_id(refresh_token: &str) -> String {
+    if refresh_token.is_empty() {
+        String::new()
+    } else {
+        AuthManager::TEAMS_CLIENT_ID.to_string()
+    }
+}
+
 fn looks_like_jwt(value: &str) -> bool {
     let value = normalize_token_candidate(value);
     if value.contains(' ') {
@@ -593,6 +1019,38 @@ fn derive_identity_from_tokens<'a>(
     )
 }
 
+/// Decode the claims of a JWT without verifying its signature.
+pub(crate) fn jwt_claims(token: &str) -> Option<Value> {
+    let token = normalize_token_candidate(token);
+    let segment = token.split('.').nth(1)?.replace('-', "+").replace('_', "/");
+    let payload = base64_decode(&segment).ok()?;
+    serde_json::from_str(&payload).ok()
+}
+
+/// Expiry (`exp` claim) of a single access token, if it is a decodable JWT.
+#[must_use]
+pub fn token_expires_at(token: &str) -> Option<i64> {
+    parse_token_claims(token).ok().map(|(_, _, _, exp)| exp)
+}
+
+/// Delegated scopes (`scp` claim) of a single access token, or its app
+/// roles if it has none. Empty if the token is not a decodable JWT.
+#[must_use]
+pub fn token_scopes(token: &str) -> Vec<String> {
+    let Some(claims) = jwt_claims(token) else {
+        return Vec::new();
+    };
+    if let Some(scp) = claims["scp"].as_str() {
+        return scp.split_whitespace().map(String::from).collect();
+    }
+    claims["roles"].as_array().map_or_else(Vec::new, |roles| {
+        roles
+            .iter()
+            .filter_map(|role| role.as_str().map(String::from))
+            .collect()
+    })
+}
+
 fn now_epoch() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -644,7 +1102,11 @@ fn parse_token_claims(token: &str) -> Result<(String, String, String, i64), Auth
 /// 3. Same directory as the `tmz` binary (Windows zip layout)
 /// 4. System install (`/usr/share/tmz/`, e.g. AUR)
 /// 5. Development: walk up from binary to find `scripts/` directory
-fn find_auth_script() -> Result<std::path::PathBuf, AuthenticationError> {
+///
+/// # Errors
+///
+/// Returns an error if the script cannot be found in any location.
+pub fn find_auth_script() -> Result<std::path::PathBuf, AuthenticationError> {
     const SCRIPT_NAME: &str = "teams-auth.mjs";
 
     // 1. Explicit env override
@@ -656,7 +1118,7 @@ fn find_auth_script() -> Result<std::path::PathBuf, AuthenticationError> {
     }
 
     // 2. Installed location: $XDG_DATA_HOME/tmz/teams-auth.mjs
-    if let Ok(data_dir) = crate::default_data_dir() {
+    if let Ok(data_dir) = crate::paths::base_data_dir() {
         let candidate = data_dir.join(SCRIPT_NAME);
         if candidate.exists() {
             return Ok(candidate);
@@ -707,3 +1169,67 @@ fn base64_decode(input: &str) -> Result<String, Box<dyn std::error::Error>> {
     let decoded = base64::engine::general_purpose::STANDARD.decode(padded)?;
     Ok(String::from_utf8(decoded)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn refresh_token_prefers_the_teams_web_client_entry() {
+        let mut local_storage = HashMap::new();
+        local_storage.insert(
+            "uid.tid-login.windows.net-refreshtoken-1--".to_string(),
+            r#"{"credentialType":"RefreshToken","secret":"family-rt"}"#.to_string(),
+        );
+        local_storage.insert(
+            format!(
+                "uid.tid-login.windows.net-refreshtoken-{}--",
+                AuthManager::TEAMS_CLIENT_ID
+            ),
+            r#"{"credentialType":"RefreshToken","secret":"teams-rt"}"#.to_string(),
+        );
+        local_storage.insert(
+            "uid.tid-login.windows.net-accesstoken-x".to_string(),
+            r#"{"secret":"not-a-refresh-token"}"#.to_string(),
+        );
+        assert_eq!(
+            AuthManager::extract_refresh_token(&local_storage).as_deref(),
+            Some("teams-rt")
+        );
+
+        local_storage.retain(|key, _| !key.contains(AuthManager::TEAMS_CLIENT_ID));
+        assert_eq!(
+            AuthManager::extract_refresh_token(&local_storage).as_deref(),
+            Some("family-rt")
+        );
+        local_storage.clear();
+        assert_eq!(AuthManager::extract_refresh_token(&local_storage), None);
+
+        assert_eq!(refresh_client(""), device_code::DEVICE_CODE_CLIENT_ID);
+        assert_eq!(web_client_id("rt"), AuthManager::TEAMS_CLIENT_ID);
+        assert!(web_client_id("").is_empty());
+    }
+
+    #[test]
+    fn scopes_come_from_the_scp_or_roles_claim() {
+        use base64::Engine;
+        let jwt = |claims: &str| {
+            let b64 = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+            format!(
+                "{}.{}.c2lnbmF0dXJlc2lnbmF0dXJlc2lnbmF0dXJl",
+                b64.encode(r#"{"alg":"RS256","typ":"JWT"}"#),
+                b64.encode(claims)
+            )
+        };
+        assert_eq!(
+            token_scopes(&jwt(r#"{"scp":"User.Read  Chat.ReadWrite","exp":1}"#)),
+            ["User.Read", "Chat.ReadWrite"]
+        );
+        assert_eq!(
+            token_scopes(&jwt(r#"{"roles":["Presence.Read.All"]}"#)),
+            ["Presence.Read.All"]
+        );
+        assert!(token_scopes("opaque-token").is_empty());
+    }
+}
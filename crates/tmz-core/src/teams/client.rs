@@ -8,21 +8,58 @@
 //!
 //! Graph API is still used for operations where it has sufficient scopes
 //! (e.g., listing joined teams, channels).
+//!
+//! A `401` from any service is treated as a rejection of the token for that
+//! audience: a headless refresh is attempted and the request is retried once
+//! before asking for a re-login. A failed refresh keeps the stored tokens.
+//!
+//! New messages can also be pushed: [`TeamsClient::poll_events`] registers
+//! an endpoint with the chat service and long-polls it for events.
 
 use crate::CoreError;
-use crate::teams::auth::AuthManager;
-use crate::teams::models::{PresenceStatus, TeamsSession, UserPresence};
-use reqwest::Client;
+use crate::error::ApiError;
+use crate::retry::RetryingSend;
+use crate::teams::auth::{AuthManager, AuthenticationError};
+use crate::teams::endpoints;
+use crate::teams::models::{
+    Conversation, LiveEvent, Message, PostOptions, PresenceStatus, TeamsSession, TokenAudience,
+    UserPresence,
+};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 
 /// Teams API client.
 #[derive(Debug)]
 pub struct TeamsClient {
     http_client: Client,
     auth: AuthManager,
+    subscription: tokio::sync::Mutex<Option<Subscription>>,
 }
 
-/// Authz endpoint for exchanging MSAL token for skypeToken.
-const AUTHZ_URL: &str = "https://teams.microsoft.com/api/authsvc/v1.0/authz";
+/// Graph user fields requested for people lookups.
+const PERSON_FIELDS: &str = "id,displayName,mail,userPrincipalName,jobTitle";
+
+/// Resources the event subscription asks the chat service to push.
+const EVENT_RESOURCES: [&str; 3] = [
+    "/v1/users/ME/conversations/ALL/properties",
+    "/v1/users/ME/conversations/ALL/messages",
+    "/v1/threads/ALL",
+];
+
+/// Features the event endpoint announces, as the Teams web app does.
+const ENDPOINT_FEATURES: &str = "Agent,Presence2015,MessageProperties,CustomUserProperties,NotificationStream,SupportsSkipRosterFromThreads";
+
+/// Longest a poll may take. The service answers after about 30 seconds
+/// when nothing happened.
+const POLL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(90);
+
+/// An endpoint registered for push events, with the session it belongs to.
+#[derive(Debug)]
+struct Subscription {
+    session: TeamsSession,
+    endpoint_id: String,
+    /// `Set-RegistrationToken` of the registration, sent with each poll.
+    registration_token: Option<String>,
+}
 
 impl TeamsClient {
     /// Create a new Teams client.
@@ -31,7 +68,7 @@ impl TeamsClient {
     ///
     /// Returns an error if HTTP client creation fails.
     pub fn new() -> Result<Self, CoreError> {
-        let http_client = Client::builder()
+        let http_client = crate::network::client_builder()
             .timeout(std::time::Duration::from_mins(1))
             .build()
             .map_err(|e| CoreError::Other(format!("creating HTTP client: {e}")))?;
@@ -39,7 +76,11 @@ impl TeamsClient {
         let auth = AuthManager::new()
             .map_err(|e| CoreError::Other(format!("creating auth manager: {e}")))?;
 
-        Ok(Self { http_client, auth })
+        Ok(Self {
+            http_client,
+            auth,
+            subscription: tokio::sync::Mutex::new(None),
+        })
     }
 
     /// Check if authenticated and tokens are valid.
@@ -61,6 +102,57 @@ impl TeamsClient {
             .map_err(|e| CoreError::Auth(format!("not authenticated: {e}")))
     }
 
+    /// Send a request authorized with the stored token for `audience`.
+    ///
+    /// On `401` the token is refreshed and the request retried once.
+    async fn send_authorized<F>(
+        &self,
+        audience: TokenAudience,
+        build: F,
+    ) -> Result<Response, CoreError>
+    where
+        F: Fn(&str) -> RequestBuilder + Sync,
+    {
+        let tokens = self.valid_tokens().await?;
+        let (response, _) = retry_unauthorized(
+            tokens.token_for(audience).to_string(),
+            |token: &String| send(build(token)),
+            || async {
+                let fresh = self
+                    .auth
+                    .handle_rejected_token(audience)
+                    .await
+                    .map_err(|e| CoreError::Auth(e.to_string()))?;
+                Ok(fresh.token_for(audience).to_string())
+            },
+        )
+        .await?;
+        Ok(response)
+    }
+
+    /// Send a chat service request authorized with a fresh session.
+    ///
+    /// On `401` the skype token is refreshed and the request retried once
+    /// with a new session.
+    async fn send_with_session<F>(&self, build: F) -> Result<(Response, TeamsSession), CoreError>
+    where
+        F: Fn(&TeamsSession) -> RequestBuilder + Sync,
+    {
+        let session = self.get_session().await?;
+        retry_unauthorized(
+            session,
+            |session: &TeamsSession| send(build(session)),
+            || async {
+                self.auth
+                    .handle_rejected_token(TokenAudience::Skype)
+                    .await
+                    .map_err(|e| CoreError::Auth(e.to_string()))?;
+                self.get_session().await
+            },
+        )
+        .await
+    }
+
     /// Exchange the MSAL skype access token for a Teams session.
     ///
     /// Calls `POST /api/authsvc/v1.0/authz` to get a skypeToken and
@@ -70,21 +162,17 @@ impl TeamsClient {
     ///
     /// Returns an error if not authenticated or the authz call fails.
     pub async fn get_session(&self) -> Result<TeamsSession, CoreError> {
-        let tokens = self.valid_tokens().await?;
-
         let response = self
-            .http_client
-            .post(AUTHZ_URL)
-            .bearer_auth(&tokens.skype_token)
-            .header("Content-Length", "0")
-            .send()
-            .await
-            .map_err(|e| CoreError::Api(format!("authz request failed: {e}")))?;
+            .send_authorized(TokenAudience::Skype, |token| {
+                self.http_client
+                    .post(endpoints::current().authz_url())
+                    .bearer_auth(token)
+                    .header("Content-Length", "0")
+            })
+            .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!("authz failed: {status} - {text}")));
+            return Err(response_error("authz", TokenAudience::Skype, response).await);
         }
 
         let settings: serde_json::Value = response
@@ -94,12 +182,12 @@ impl TeamsClient {
 
         let skype_token = settings["tokens"]["skypeToken"]
             .as_str()
-            .ok_or_else(|| CoreError::Api("missing skypeToken in authz response".to_string()))?
+            .ok_or_else(|| CoreError::api("missing skypeToken in authz response".to_string()))?
             .to_string();
 
         let chat_service_url = settings["regionGtms"]["chatService"]
             .as_str()
-            .ok_or_else(|| CoreError::Api("missing chatService URL in authz response".to_string()))?
+            .ok_or_else(|| CoreError::api("missing chatService URL in authz response".to_string()))?
             .to_string();
 
         let teams_and_channels_service_url = settings["regionGtms"]["teamsAndChannelsService"]
@@ -127,37 +215,38 @@ impl TeamsClient {
     ///
     /// # Errors
     ///
-    /// Returns an error if not authenticated or request fails.
-    pub async fn list_chats(&self) -> Result<serde_json::Value, CoreError> {
-        let session = self.get_session().await?;
-        let url = format!(
-            "{}/v1/users/ME/conversations?view=msnp24Equivalent&pageSize=500",
-            session.chat_service_url
-        );
-
-        let response = self
-            .http_client
-            .get(&url)
-            .header(
-                "Authentication",
-                format!("skypetoken={}", session.skype_token),
-            )
-            .send()
-            .await
-            .map_err(|e| CoreError::Api(format!("request failed: {e}")))?;
+    /// Returns an error if not authenticated, the request fails, or the
+    /// response holds no conversation list.
+    pub async fn list_chats(&self) -> Result<Vec<Conversation>, CoreError> {
+        let (response, _) = self
+            .send_with_session(|session| {
+                let url = format!(
+                    "{}/v1/users/ME/conversations?view=msnp24Equivalent&pageSize=500",
+                    session.chat_service_url
+                );
+                self.http_client.get(url).header(
+                    "Authentication",
+                    format!("skypetoken={}", session.skype_token),
+                )
+            })
+            .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!(
-                "list chats failed: {status} - {text}"
-            )));
+            return Err(response_error("list chats", TokenAudience::Skype, response).await);
         }
 
-        response
-            .json::<serde_json::Value>()
+        let mut data: serde_json::Value = response
+            .json()
             .await
-            .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))
+            .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))?;
+        match data["conversations"].take() {
+            serde_json::Value::Array(conversations) => {
+                Ok(conversations.into_iter().map(Conversation::from).collect())
+            }
+            _ => Err(CoreError::api(
+                "unexpected API response: missing conversations array".to_string(),
+            )),
+        }
     }
 
     /// Get messages from a conversation.
@@ -167,6 +256,9 @@ impl TeamsClient {
     /// * `conversation_id` - The conversation thread ID (e.g., `19:xxx@thread.v2`)
     /// * `page_size` - Number of messages to fetch (default: 200)
     ///
+    /// Messages come back with `is_from_me` and `mentions_me` set for the
+    /// signed-in user.
+    ///
     /// # Errors
     ///
     /// Returns an error if not authenticated or request fails.
@@ -174,32 +266,24 @@ impl TeamsClient {
         &self,
         conversation_id: &str,
         page_size: Option<i32>,
-    ) -> Result<serde_json::Value, CoreError> {
-        let session = self.get_session().await?;
+    ) -> Result<Vec<Message>, CoreError> {
         let size = page_size.unwrap_or(200);
-        let url = format!(
-            "{}/v1/users/ME/conversations/{}/messages?startTime=0&view=msnp24Equivalent&pageSize={size}",
-            session.chat_service_url,
-            urlencoding::encode(conversation_id)
-        );
-
-        let response = self
-            .http_client
-            .get(&url)
-            .header(
-                "Authentication",
-                format!("skypetoken={}", session.skype_token),
-            )
-            .send()
-            .await
-            .map_err(|e| CoreError::Api(format!("request failed: {e}")))?;
+        let (response, session) = self
+            .send_with_session(|session| {
+                let url = format!(
+                    "{}/v1/users/ME/conversations/{}/messages?startTime=0&view=msnp24Equivalent&pageSize={size}",
+                    session.chat_service_url,
+                    urlencoding::encode(conversation_id)
+                );
+                self.http_client.get(url).header(
+                    "Authentication",
+                    format!("skypetoken={}", session.skype_token),
+                )
+            })
+            .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!(
-                "get messages failed: {status} - {text}"
-            )));
+            return Err(response_error("get messages", TokenAudience::Skype, response).await);
         }
 
         let mut data: serde_json::Value = response
@@ -207,20 +291,13 @@ impl TeamsClient {
             .await
             .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))?;
 
-        // Mark messages from the current user
-        if let Some(messages) = data
-            .get_mut("messages")
-            .and_then(serde_json::Value::as_array_mut)
-        {
-            for msg in messages {
-                let is_from_me = msg["from"]
-                    .as_str()
-                    .is_some_and(|from| from.ends_with(&session.skype_id));
-                msg["isFromMe"] = serde_json::Value::Bool(is_from_me);
-            }
-        }
-
-        Ok(data)
+        let serde_json::Value::Array(messages) = data["messages"].take() else {
+            return Ok(Vec::new());
+        };
+        Ok(messages
+            .into_iter()
+            .map(|msg| mark_own_message(msg, &session.skype_id))
+            .collect())
     }
 
     /// Send a message to a conversation.
@@ -233,42 +310,359 @@ impl TeamsClient {
         conversation_id: &str,
         content: &str,
     ) -> Result<serde_json::Value, CoreError> {
-        let session = self.get_session().await?;
-        let url = format!(
-            "{}/v1/users/ME/conversations/{}/messages",
-            session.chat_service_url,
-            urlencoding::encode(conversation_id)
-        );
+        self.send_post(conversation_id, content, &PostOptions::default())
+            .await
+    }
 
-        let body = serde_json::json!({
+    /// Send a message with a subject or importance, e.g. an announcement
+    /// in a channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn send_post(
+        &self,
+        conversation_id: &str,
+        content: &str,
+        options: &PostOptions,
+    ) -> Result<serde_json::Value, CoreError> {
+        let mut body = serde_json::json!({
             "messagetype": "RichText/Html",
             "content": content
         });
+        if let Some(ref subject) = options.subject {
+            body["properties"]["subject"] = subject.as_str().into();
+        }
+        if let Some(importance) = options.importance {
+            body["properties"]["importance"] = importance.as_str().into();
+        }
 
-        let response = self
+        let (response, _) = self
+            .send_with_session(|session| {
+                let url = format!(
+                    "{}/v1/users/ME/conversations/{}/messages",
+                    session.chat_service_url,
+                    urlencoding::encode(conversation_id)
+                );
+                self.http_client
+                    .post(url)
+                    .header(
+                        "Authentication",
+                        format!("skypetoken={}", session.skype_token),
+                    )
+                    .json(&body)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("send message", TokenAudience::Skype, response).await);
+        }
+
+        sent_message(response).await
+    }
+
+    /// Add a reaction to a message. `key` is a Teams emotion key such as
+    /// `like` or `1f680_rocket` (see [`crate::reactions`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn add_reaction(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+        key: &str,
+    ) -> Result<(), CoreError> {
+        let body = serde_json::json!({
+            "emotions": { "key": key, "value": chrono::Utc::now().timestamp_millis() }
+        });
+
+        let (response, _) = self
+            .send_with_session(|session| {
+                let url = format!(
+                    "{}/v1/users/ME/conversations/{}/messages/{}/properties?name=emotions",
+                    session.chat_service_url,
+                    urlencoding::encode(conversation_id),
+                    urlencoding::encode(message_id)
+                );
+                self.http_client
+                    .put(url)
+                    .header(
+                        "Authentication",
+                        format!("skypetoken={}", session.skype_token),
+                    )
+                    .json(&body)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("add reaction", TokenAudience::Skype, response).await);
+        }
+        Ok(())
+    }
+
+    /// Remove your reaction `key` from a message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn remove_reaction(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+        key: &str,
+    ) -> Result<(), CoreError> {
+        let body = serde_json::json!({ "emotions": { "key": key } });
+
+        let (response, _) = self
+            .send_with_session(|session| {
+                let url = format!(
+                    "{}/v1/users/ME/conversations/{}/messages/{}/properties?name=emotions",
+                    session.chat_service_url,
+                    urlencoding::encode(conversation_id),
+                    urlencoding::encode(message_id)
+                );
+                self.http_client
+                    .delete(url)
+                    .header(
+                        "Authentication",
+                        format!("skypetoken={}", session.skype_token),
+                    )
+                    .json(&body)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("remove reaction", TokenAudience::Skype, response).await);
+        }
+        Ok(())
+    }
+
+    /// Delete a message you sent. Like deleting in Teams, this is a soft
+    /// delete: the message stays in the thread as "This message has been
+    /// deleted" and can be undone from the Teams client.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails, for example
+    /// because the message is someone else's.
+    pub async fn delete_message(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+    ) -> Result<(), CoreError> {
+        let (response, _) = self
+            .send_with_session(|session| {
+                let url = format!(
+                    "{}/v1/users/ME/conversations/{}/messages/{}?behavior=softDelete",
+                    session.chat_service_url,
+                    urlencoding::encode(conversation_id),
+                    urlencoding::encode(message_id)
+                );
+                self.http_client.delete(url).header(
+                    "Authentication",
+                    format!("skypetoken={}", session.skype_token),
+                )
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("delete message", TokenAudience::Skype, response).await);
+        }
+        Ok(())
+    }
+
+    /// Hide or unhide a conversation in Teams itself (the chat list's
+    /// "Hide" action), by setting its `hidden` property.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn set_conversation_hidden(
+        &self,
+        conversation_id: &str,
+        hidden: bool,
+    ) -> Result<(), CoreError> {
+        let body = serde_json::json!({ "hidden": hidden.to_string() });
+
+        let (response, _) = self
+            .send_with_session(|session| {
+                let url = format!(
+                    "{}/v1/users/ME/conversations/{}/properties?name=hidden",
+                    session.chat_service_url,
+                    urlencoding::encode(conversation_id)
+                );
+                self.http_client
+                    .put(url)
+                    .header(
+                        "Authentication",
+                        format!("skypetoken={}", session.skype_token),
+                    )
+                    .json(&body)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("set hidden", TokenAudience::Skype, response).await);
+        }
+        Ok(())
+    }
+
+    /// Move the read horizon of a conversation (its `consumptionhorizon`
+    /// property), e.g. back before the newest message to mark it unread.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn set_consumption_horizon(
+        &self,
+        conversation_id: &str,
+        horizon: &str,
+    ) -> Result<(), CoreError> {
+        let body = serde_json::json!({ "consumptionhorizon": horizon });
+
+        let (response, _) = self
+            .send_with_session(|session| {
+                let url = format!(
+                    "{}/v1/users/ME/conversations/{}/properties?name=consumptionhorizon",
+                    session.chat_service_url,
+                    urlencoding::encode(conversation_id)
+                );
+                self.http_client
+                    .put(url)
+                    .header(
+                        "Authentication",
+                        format!("skypetoken={}", session.skype_token),
+                    )
+                    .json(&body)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("set read horizon", TokenAudience::Skype, response).await);
+        }
+        Ok(())
+    }
+
+    /// Wait for changes pushed by the chat service.
+    ///
+    /// The first call registers an endpoint subscribed to message and
+    /// conversation changes over `HttpLongPoll`, the channel the Teams web
+    /// app falls back to without its Trouter websocket. Each call then holds
+    /// a poll open until events arrive, or returns an empty list when the
+    /// service ends the poll after about 30 seconds. When the endpoint
+    /// expires or its session is rejected, the next call registers a new
+    /// one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or the request fails.
+    pub async fn poll_events(&self) -> Result<Vec<LiveEvent>, CoreError> {
+        let mut subscription = self.subscription.lock().await;
+        let current = match subscription.take() {
+            Some(current) if current.session.expires_at - 300 > chrono::Utc::now().timestamp() => {
+                current
+            }
+            _ => self.register_endpoint().await?,
+        };
+        let url = format!(
+            "{}/v1/users/ME/endpoints/{}/subscriptions/0/poll",
+            current.session.chat_service_url,
+            urlencoding::encode(&current.endpoint_id)
+        );
+        let mut request = self
             .http_client
-            .post(&url)
+            .post(url)
             .header(
                 "Authentication",
-                format!("skypetoken={}", session.skype_token),
+                format!("skypetoken={}", current.session.skype_token),
             )
-            .json(&body)
-            .send()
+            .header("Content-Length", "0")
+            .timeout(POLL_TIMEOUT);
+        if let Some(ref token) = current.registration_token {
+            request = request.header("RegistrationToken", token);
+        }
+        let response = request
+            .send_retrying()
             .await
-            .map_err(|e| CoreError::Api(format!("request failed: {e}")))?;
+            .map_err(|e| CoreError::api(format!("request failed: {e}")))?;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!(
-                "send message failed: {status} - {text}"
-            )));
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::NOT_FOUND => {
+                log::debug!("event endpoint expired, registering a new one");
+                return Ok(Vec::new());
+            }
+            StatusCode::UNAUTHORIZED => {
+                self.auth
+                    .handle_rejected_token(TokenAudience::Skype)
+                    .await
+                    .map_err(|e| CoreError::Auth(e.to_string()))?;
+                return Ok(Vec::new());
+            }
+            _ => return Err(response_error("poll events", TokenAudience::Skype, response).await),
         }
-
-        response
-            .json::<serde_json::Value>()
+        let text = response
+            .text()
             .await
-            .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))
+            .map_err(|e| CoreError::api(format!("reading events: {e}")))?;
+        // A poll that timed out has an empty body.
+        let mut body: serde_json::Value = if text.trim().is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::from_str(&text)
+                .map_err(|e| CoreError::Serialization(format!("parsing events: {e}")))?
+        };
+        let events = parse_events(&mut body, &current.session.skype_id);
+        *subscription = Some(current);
+        drop(subscription);
+        Ok(events)
+    }
+
+    /// Register a new endpoint for push events.
+    async fn register_endpoint(&self) -> Result<Subscription, CoreError> {
+        let endpoint_id = endpoint_id()?;
+        let body = serde_json::json!({
+            "startingTimeSpan": 0,
+            "endpointFeatures": ENDPOINT_FEATURES,
+            "subscriptions": [{
+                "channelType": "HttpLongPoll",
+                "interestedResources": EVENT_RESOURCES,
+            }],
+        });
+        let (response, session) = self
+            .send_with_session(|session| {
+                let url = format!(
+                    "{}/v1/users/ME/endpoints/{}",
+                    session.chat_service_url,
+                    urlencoding::encode(&endpoint_id)
+                );
+                self.http_client
+                    .put(url)
+                    .header(
+                        "Authentication",
+                        format!("skypetoken={}", session.skype_token),
+                    )
+                    .json(&body)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(
+                response_error("register for events", TokenAudience::Skype, response).await,
+            );
+        }
+        let registration_token = response
+            .headers()
+            .get("Set-RegistrationToken")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        log::debug!("registered event endpoint {endpoint_id}");
+        Ok(Subscription {
+            session,
+            endpoint_id,
+            registration_token,
+        })
     }
 
     /// Send a file to a conversation.
@@ -320,7 +714,7 @@ impl TeamsClient {
                 )
                 .await?;
 
-            let obj_url = format!("https://api.asm.skype.com/v1/objects/{obj_id}");
+            let obj_url = format!("{}/v1/objects/{obj_id}", endpoints::current().asm_url());
             let (msg_type, msg_content) =
                 build_file_message(&obj_id, &obj_url, &file_name, file_size, true);
 
@@ -385,7 +779,7 @@ impl TeamsClient {
 
         let web_url = upload_result["webUrl"]
             .as_str()
-            .ok_or_else(|| CoreError::Api("missing webUrl in upload response".to_string()))?;
+            .ok_or_else(|| CoreError::api("missing webUrl in upload response".to_string()))?;
 
         let file_size = file_bytes.len();
         let size_kb = file_size / 1024;
@@ -395,7 +789,7 @@ impl TeamsClient {
             format!("{file_size} B")
         };
 
-        let content = format!(r#"<p><a href="{web_url}">{file_name}</a> ({size_str})</p>"#,);
+        let content = format!(r#"<p><a href="{web_url}">{file_name}</a> ({size_str})</p>"#);
 
         self.send_raw_message(conversation_id, session, "RichText/Html", &content)
             .await
@@ -412,8 +806,10 @@ impl TeamsClient {
         // Upload to /tmz-uploads/<filename> in the user's OneDrive
         let raw_path = format!("tmz-uploads/{file_name}");
         let encoded_path = urlencoding::encode(&raw_path);
-        let upload_url =
-            format!("https://graph.microsoft.com/v1.0/me/drive/root:/{encoded_path}:/content");
+        let upload_url = format!(
+            "{}/me/drive/root:/{encoded_path}:/content",
+            endpoints::current().graph_api()
+        );
 
         let response = self
             .http_client
@@ -421,16 +817,12 @@ impl TeamsClient {
             .bearer_auth(graph_token)
             .header("Content-Type", "application/octet-stream")
             .body(file_bytes.to_vec())
-            .send()
+            .send_retrying()
             .await
-            .map_err(|e| CoreError::Api(format!("OneDrive upload failed: {e}")))?;
+            .map_err(|e| CoreError::api(format!("OneDrive upload failed: {e}")))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!(
-                "OneDrive upload failed: {status} - {text}"
-            )));
+            return Err(response_error("OneDrive upload", TokenAudience::Graph, response).await);
         }
 
         // Create a sharing link so the recipient can access the file
@@ -441,11 +833,13 @@ impl TeamsClient {
 
         let item_id = item["id"]
             .as_str()
-            .ok_or_else(|| CoreError::Api("missing item id".to_string()))?;
+            .ok_or_else(|| CoreError::api("missing item id".to_string()))?;
 
         // Create an organization-wide sharing link
-        let share_url =
-            format!("https://graph.microsoft.com/v1.0/me/drive/items/{item_id}/createLink");
+        let share_url = format!(
+            "{}/me/drive/items/{item_id}/createLink",
+            endpoints::current().graph_api()
+        );
         let share_body = serde_json::json!({
             "type": "view",
             "scope": "organization"
@@ -456,9 +850,9 @@ impl TeamsClient {
             .post(&share_url)
             .bearer_auth(graph_token)
             .json(&share_body)
-            .send()
+            .send_retrying()
             .await
-            .map_err(|e| CoreError::Api(format!("creating share link: {e}")))?;
+            .map_err(|e| CoreError::api(format!("creating share link: {e}")))?;
 
         if share_resp.status().is_success() {
             let share_data: serde_json::Value = share_resp.json().await.unwrap_or_default();
@@ -501,23 +895,19 @@ impl TeamsClient {
 
         let resp = self
             .http_client
-            .post("https://api.asm.skype.com/v1/objects")
+            .post(format!("{}/v1/objects", endpoints::current().asm_url()))
             .header(
                 "Authorization",
                 format!("skype_token {}", session.skype_token),
             )
             .header("X-Client-Version", "0/0.0.0.0")
             .json(&meta)
-            .send()
+            .send_retrying()
             .await
-            .map_err(|e| CoreError::Api(format!("creating ASM object: {e}")))?;
+            .map_err(|e| CoreError::api(format!("creating ASM object: {e}")))?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!(
-                "ASM create failed: {status} - {text}"
-            )));
+            return Err(response_error("ASM create", TokenAudience::Skype, resp).await);
         }
 
         let obj_data: serde_json::Value = resp
@@ -527,13 +917,15 @@ impl TeamsClient {
 
         let obj_id = obj_data["id"]
             .as_str()
-            .ok_or_else(|| CoreError::Api("missing object id".to_string()))?
+            .ok_or_else(|| CoreError::api("missing object id".to_string()))?
             .to_string();
 
         // Upload binary content
         let content_path = if is_image { "imgpsh" } else { "original" };
-        let upload_url =
-            format!("https://api.asm.skype.com/v1/objects/{obj_id}/content/{content_path}");
+        let upload_url = format!(
+            "{}/v1/objects/{obj_id}/content/{content_path}",
+            endpoints::current().asm_url()
+        );
 
         let upload_resp = self
             .http_client
@@ -544,14 +936,12 @@ impl TeamsClient {
             )
             .header("Content-Type", mime_for_ext(ext))
             .body(file_bytes.to_vec())
-            .send()
+            .send_retrying()
             .await
-            .map_err(|e| CoreError::Api(format!("uploading content: {e}")))?;
+            .map_err(|e| CoreError::api(format!("uploading content: {e}")))?;
 
         if !upload_resp.status().is_success() {
-            let status = upload_resp.status();
-            let text = upload_resp.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!("upload failed: {status} - {text}")));
+            return Err(response_error("upload", TokenAudience::Skype, upload_resp).await);
         }
 
         Ok(obj_id)
@@ -583,20 +973,15 @@ impl TeamsClient {
                 format!("skypetoken={}", session.skype_token),
             )
             .json(&body)
-            .send()
+            .send_retrying()
             .await
-            .map_err(|e| CoreError::Api(format!("request failed: {e}")))?;
+            .map_err(|e| CoreError::api(format!("request failed: {e}")))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!("send failed: {status} - {text}")));
+            return Err(response_error("send", TokenAudience::Skype, response).await);
         }
 
-        response
-            .json::<serde_json::Value>()
-            .await
-            .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))
+        sent_message(response).await
     }
 
     /// List user's joined teams via Graph API.
@@ -607,24 +992,16 @@ impl TeamsClient {
     ///
     /// Returns an error if not authenticated or request fails.
     pub async fn list_teams(&self) -> Result<Vec<serde_json::Value>, CoreError> {
-        let tokens = self.valid_tokens().await?;
-
-        let url = "https://graph.microsoft.com/v1.0/me/joinedTeams";
+        let url = format!("{}/me/joinedTeams", endpoints::current().graph_api());
 
         let response = self
-            .http_client
-            .get(url)
-            .bearer_auth(&tokens.graph_token)
-            .send()
-            .await
-            .map_err(|e| CoreError::Api(format!("request failed: {e}")))?;
+            .send_authorized(TokenAudience::Graph, |token| {
+                self.http_client.get(&url).bearer_auth(token)
+            })
+            .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!(
-                "list teams failed: {status} - {text}"
-            )));
+            return Err(response_error("list teams", TokenAudience::Graph, response).await);
         }
 
         let data: serde_json::Value = response
@@ -643,27 +1020,20 @@ impl TeamsClient {
     ///
     /// Returns an error if not authenticated or request fails.
     pub async fn list_channels(&self, team_id: &str) -> Result<Vec<serde_json::Value>, CoreError> {
-        let tokens = self.valid_tokens().await?;
-
         let url = format!(
-            "https://graph.microsoft.com/v1.0/teams/{}/channels",
+            "{}/teams/{}/channels",
+            endpoints::current().graph_api(),
             urlencoding::encode(team_id)
         );
 
         let response = self
-            .http_client
-            .get(&url)
-            .bearer_auth(&tokens.graph_token)
-            .send()
-            .await
-            .map_err(|e| CoreError::Api(format!("request failed: {e}")))?;
+            .send_authorized(TokenAudience::Graph, |token| {
+                self.http_client.get(&url).bearer_auth(token)
+            })
+            .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!(
-                "list channels failed: {status} - {text}"
-            )));
+            return Err(response_error("list channels", TokenAudience::Graph, response).await);
         }
 
         let data: serde_json::Value = response
@@ -687,7 +1057,7 @@ impl TeamsClient {
         _team_id: &str,
         channel_id: &str,
         page_size: Option<i32>,
-    ) -> Result<serde_json::Value, CoreError> {
+    ) -> Result<Vec<Message>, CoreError> {
         // Channel conversations use the same native API with the channel thread ID
         self.get_chat_messages(channel_id, page_size).await
     }
@@ -698,27 +1068,20 @@ impl TeamsClient {
     ///
     /// Returns an error if not authenticated or request fails.
     pub async fn get_user_presence(&self, user_id: &str) -> Result<UserPresence, CoreError> {
-        let tokens = self.valid_tokens().await?;
-
         let url = format!(
-            "https://presence.teams.microsoft.com/v1/presence/{}",
+            "{}/v1/presence/{}",
+            endpoints::current().presence_url(),
             urlencoding::encode(user_id)
         );
 
         let response = self
-            .http_client
-            .get(&url)
-            .bearer_auth(&tokens.presence_token)
-            .send()
-            .await
-            .map_err(|e| CoreError::Api(format!("request failed: {e}")))?;
+            .send_authorized(TokenAudience::Presence, |token| {
+                self.http_client.get(&url).bearer_auth(token)
+            })
+            .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!(
-                "presence failed: {status} - {text}"
-            )));
+            return Err(response_error("presence", TokenAudience::Presence, response).await);
         }
 
         let data: serde_json::Value = response
@@ -742,34 +1105,310 @@ impl TeamsClient {
         })
     }
 
+    /// Set your own presence, as the status menu in Teams does. It lasts
+    /// until `until`, or until [`Self::reset_presence`] without one.
+    /// `Offline` appears offline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for `Unknown`, if not authenticated, or if the
+    /// request fails.
+    pub async fn set_presence(
+        &self,
+        status: PresenceStatus,
+        until: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<(), CoreError> {
+        let availability = match status {
+            PresenceStatus::Available => "Available",
+            PresenceStatus::Busy => "Busy",
+            PresenceStatus::DoNotDisturb => "DoNotDisturb",
+            PresenceStatus::Away => "Away",
+            PresenceStatus::Offline => "Offline",
+            PresenceStatus::Unknown => {
+                return Err(CoreError::Other(
+                    "presence can only be set to a known status".to_string(),
+                ));
+            }
+        };
+        let mut body = serde_json::json!({ "availability": availability });
+        if let Some(until) = until {
+            body["desiredExpirationTime"] = until
+                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+                .into();
+        }
+        let url = format!(
+            "{}/v1/me/forceavailability/",
+            endpoints::current().presence_url()
+        );
+
+        let response = self
+            .send_authorized(TokenAudience::Presence, |token| {
+                self.http_client.put(&url).bearer_auth(token).json(&body)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("set presence", TokenAudience::Presence, response).await);
+        }
+        Ok(())
+    }
+
+    /// Drop a presence set with [`Self::set_presence`], so Teams works it
+    /// out from your activity and calendar again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn reset_presence(&self) -> Result<(), CoreError> {
+        let url = format!(
+            "{}/v1/me/forceavailability/",
+            endpoints::current().presence_url()
+        );
+
+        let response = self
+            .send_authorized(TokenAudience::Presence, |token| {
+                self.http_client.delete(&url).bearer_auth(token)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("reset presence", TokenAudience::Presence, response).await);
+        }
+        Ok(())
+    }
+
     /// Get current user info via Graph API.
     ///
     /// # Errors
     ///
     /// Returns an error if not authenticated or request fails.
     pub async fn get_me(&self) -> Result<serde_json::Value, CoreError> {
-        let tokens = self.valid_tokens().await?;
+        let response = self
+            .send_authorized(TokenAudience::Graph, |token| {
+                self.http_client
+                    .get(format!("{}/me", endpoints::current().graph_api()))
+                    .bearer_auth(token)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("get me", TokenAudience::Graph, response).await);
+        }
+
+        response
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))
+    }
+
+    /// Object ID of the signed-in user, from the stored tokens.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated.
+    pub async fn user_id(&self) -> Result<String, CoreError> {
+        Ok(self.valid_tokens().await?.user_id)
+    }
+
+    /// Get a user's Outlook working hours (`mailboxSettings/workingHours`)
+    /// via Graph API. Tenants often do not grant the Teams token access to
+    /// other people's mailbox settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn get_working_hours(&self, user_id: &str) -> Result<serde_json::Value, CoreError> {
+        let url = format!(
+            "{}/users/{}/mailboxSettings/workingHours",
+            endpoints::current().graph_api(),
+            urlencoding::encode(user_id)
+        );
 
         let response = self
-            .http_client
-            .get("https://graph.microsoft.com/v1.0/me")
-            .bearer_auth(&tokens.graph_token)
-            .send()
+            .send_authorized(TokenAudience::Graph, |token| {
+                self.http_client.get(&url).bearer_auth(token)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("working hours", TokenAudience::Graph, response).await);
+        }
+
+        response
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))
+    }
+
+    /// Get a user's directory profile (name, email, job title) via Graph
+    /// API.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn get_user(&self, user_id: &str) -> Result<serde_json::Value, CoreError> {
+        let url = format!(
+            "{}/users/{}?$select={PERSON_FIELDS}",
+            endpoints::current().graph_api(),
+            urlencoding::encode(user_id)
+        );
+
+        let response = self
+            .send_authorized(TokenAudience::Graph, |token| {
+                self.http_client.get(&url).bearer_auth(token)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("get user", TokenAudience::Graph, response).await);
+        }
+
+        response
+            .json::<serde_json::Value>()
             .await
-            .map_err(|e| CoreError::Api(format!("request failed: {e}")))?;
+            .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))
+    }
 
+    /// Get a user's manager via Graph API, or `None` at the top of the org.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn get_manager(&self, user_id: &str) -> Result<Option<serde_json::Value>, CoreError> {
+        let url = format!(
+            "{}/users/{}/manager?$select={PERSON_FIELDS}",
+            endpoints::current().graph_api(),
+            urlencoding::encode(user_id)
+        );
+
+        let response = self
+            .send_authorized(TokenAudience::Graph, |token| {
+                self.http_client.get(&url).bearer_auth(token)
+            })
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
         if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(CoreError::Api(format!("get me failed: {status} - {text}")));
+            return Err(response_error("get manager", TokenAudience::Graph, response).await);
         }
 
         response
             .json::<serde_json::Value>()
             .await
+            .map(Some)
             .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))
     }
 
+    /// List a user's direct reports via Graph API.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn get_direct_reports(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<serde_json::Value>, CoreError> {
+        let url = format!(
+            "{}/users/{}/directReports?$select={PERSON_FIELDS}&$top=999",
+            endpoints::current().graph_api(),
+            urlencoding::encode(user_id)
+        );
+        self.graph_list("list direct reports", &url).await
+    }
+
+    /// Find users whose display name or email starts with `prefix` via
+    /// Graph API.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn search_users(&self, prefix: &str) -> Result<Vec<serde_json::Value>, CoreError> {
+        let prefix = prefix.replace('\'', "''");
+        let filter = format!(
+            "startswith(displayName,'{prefix}') or startswith(mail,'{prefix}') or startswith(userPrincipalName,'{prefix}')"
+        );
+        let url = format!(
+            "{}/users?$filter={}&$select={PERSON_FIELDS}&$top=20",
+            endpoints::current().graph_api(),
+            urlencoding::encode(&filter)
+        );
+        self.graph_list("search users", &url).await
+    }
+
+    /// GET a Graph collection and return its `value` array.
+    async fn graph_list(&self, what: &str, url: &str) -> Result<Vec<serde_json::Value>, CoreError> {
+        let response = self
+            .send_authorized(TokenAudience::Graph, |token| {
+                self.http_client.get(url).bearer_auth(token)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error(what, TokenAudience::Graph, response).await);
+        }
+
+        let data: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))?;
+
+        Ok(data["value"].as_array().cloned().unwrap_or_default())
+    }
+
+    /// Get a user's free/busy calendar between `start` and `end` via the
+    /// Graph `getSchedule` action. The result carries their working hours
+    /// as well as the schedule items, with subjects where the calendar's
+    /// sharing allows.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated or request fails.
+    pub async fn get_schedule(
+        &self,
+        email: &str,
+        start: chrono::DateTime<chrono::Utc>,
+        end: chrono::DateTime<chrono::Utc>,
+    ) -> Result<serde_json::Value, CoreError> {
+        let url = format!(
+            "{}/me/calendar/getSchedule",
+            endpoints::current().graph_api()
+        );
+        let time = |t: chrono::DateTime<chrono::Utc>| {
+            serde_json::json!({
+                "dateTime": t.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                "timeZone": "UTC",
+            })
+        };
+        let body = serde_json::json!({
+            "schedules": [email],
+            "startTime": time(start),
+            "endTime": time(end),
+            "availabilityViewInterval": 30,
+        });
+
+        let response = self
+            .send_authorized(TokenAudience::Graph, |token| {
+                self.http_client.post(&url).bearer_auth(token).json(&body)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("get schedule", TokenAudience::Graph, response).await);
+        }
+
+        let data: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))?;
+        let schedule = data["value"][0].clone();
+        if let Some(message) = schedule["error"]["message"].as_str() {
+            return Err(CoreError::api(format!("get schedule failed: {message}")));
+        }
+        Ok(schedule)
+    }
+
     /// Download an image from a Teams/ASM URL.
     ///
     /// These URLs require the skype token for authentication.
@@ -789,24 +1428,203 @@ impl TeamsClient {
                 "Authorization",
                 format!("skype_token {}", session.skype_token),
             )
-            .send()
+            .send_retrying()
             .await
-            .map_err(|e| CoreError::Api(format!("image download failed: {e}")))?;
+            .map_err(|e| CoreError::api(format!("image download failed: {e}")))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            return Err(CoreError::Api(format!("image download failed: {status}")));
+            return Err(response_error("image download", TokenAudience::Skype, response).await);
         }
 
         response
             .bytes()
             .await
             .map(|b| b.to_vec())
-            .map_err(|e| CoreError::Api(format!("reading image bytes: {e}")))
+            .map_err(|e| CoreError::api(format!("reading image bytes: {e}")))
+    }
+
+    /// Download a file shared in chat (a `SharePoint`/`OneDrive` `objectUrl`)
+    /// through the Graph shares API.
+    ///
+    /// Returns `None` without downloading the body if the server reports a
+    /// size over `max_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if not authenticated, the request fails, or the file
+    /// is not accessible.
+    pub async fn download_shared_file(
+        &self,
+        url: &str,
+        max_bytes: u64,
+    ) -> Result<Option<Vec<u8>>, CoreError> {
+        use base64::Engine;
+
+        let share_id = format!(
+            "u!{}",
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(url)
+        );
+        let content_url = format!(
+            "{}/shares/{share_id}/driveItem/content",
+            endpoints::current().graph_api()
+        );
+
+        let response = self
+            .send_authorized(TokenAudience::Graph, |token| {
+                self.http_client.get(&content_url).bearer_auth(token)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(response_error("file download", TokenAudience::Graph, response).await);
+        }
+        if response.content_length().is_some_and(|len| len > max_bytes) {
+            return Ok(None);
+        }
+
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| CoreError::api(format!("reading file bytes: {e}")))?;
+        if bytes.len() as u64 > max_bytes {
+            return Ok(None);
+        }
+        Ok(Some(bytes.to_vec()))
     }
 }
 
-/// Decode a skypeToken JWT to extract skype ID and expiry.
+/// Mark a message from and mentioning the user with MRI `skype_id` with
+/// `isFromMe` and `mentionsMe`.
+pub(crate) fn mark_own_message(mut msg: serde_json::Value, skype_id: &str) -> Message {
+    let is_from_me = msg["from"]
+        .as_str()
+        .is_some_and(|from| from.ends_with(skype_id));
+    let mentions_me = crate::cache::mentioned_mris(&msg)
+        .iter()
+        .any(|mri| mri.ends_with(skype_id));
+    msg["isFromMe"] = serde_json::Value::Bool(is_from_me);
+    msg["mentionsMe"] = serde_json::Value::Bool(mentions_me);
+    Message::from(msg)
+}
+
+/// The events of a poll response (`{"eventMessages": [...]}`), with
+/// messages marked as sent by or mentioning `skype_id`.
+pub(crate) fn parse_events(body: &mut serde_json::Value, skype_id: &str) -> Vec<LiveEvent> {
+    let serde_json::Value::Array(events) = body["eventMessages"].take() else {
+        return Vec::new();
+    };
+    events
+        .into_iter()
+        .map(|mut event| {
+            let kind = event["resourceType"].as_str().unwrap_or_default();
+            if !matches!(kind, "NewMessage" | "MessageUpdate") {
+                return LiveEvent::Other(kind.to_string());
+            }
+            let mut message = event["resource"].take();
+            if message["conversationid"].as_str().is_none_or(str::is_empty) {
+                // Older events only link the conversation.
+                let conversation = message["conversationLink"]
+                    .as_str()
+                    .and_then(|link| link.rsplit('/').next())
+                    .unwrap_or_default()
+                    .to_string();
+                message["conversationid"] = conversation.into();
+            }
+            LiveEvent::Message(Box::new(mark_own_message(message, skype_id)))
+        })
+        .collect()
+}
+
+/// A new endpoint ID, a random UUID in braces as the web app uses.
+fn endpoint_id() -> Result<String, CoreError> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| CoreError::Other(format!("generating endpoint ID: {e}")))?;
+    // Version 4, variant 1.
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = crate::secrets::hex(&bytes);
+    Ok(format!(
+        "{{{}-{}-{}-{}-{}}}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+/// The body of the response to a sent message, with the message ID from
+/// its `Location` header (`.../messages/<id>`) added as `id`.
+async fn sent_message(response: Response) -> Result<serde_json::Value, CoreError> {
+    let id = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .and_then(|location| location.rsplit('/').next())
+        .map(str::to_string);
+    let mut body = response
+        .json::<serde_json::Value>()
+        .await
+        .map_err(|e| CoreError::Serialization(format!("parsing response: {e}")))?;
+    if let (Some(id), Some(fields)) = (id.filter(|id| !id.is_empty()), body.as_object_mut()) {
+        fields.entry("id").or_insert(id.into());
+    }
+    Ok(body)
+}
+
+/// Send a built request through the retrying transport.
+async fn send(request: RequestBuilder) -> Result<Response, CoreError> {
+    request
+        .send_retrying()
+        .await
+        .map_err(|e| CoreError::api(format!("request failed: {e}")))
+}
+
+/// Send with `credential` and, if the service answers `401`, once more with
+/// the credential `refresh` obtains. A second `401` is returned as-is.
+async fn retry_unauthorized<T, S, SF, R, RF>(
+    credential: T,
+    attempt: S,
+    refresh: R,
+) -> Result<(Response, T), CoreError>
+where
+    S: Fn(&T) -> SF,
+    SF: Future<Output = Result<Response, CoreError>>,
+    R: FnOnce() -> RF,
+    RF: Future<Output = Result<T, CoreError>>,
+{
+    let response = attempt(&credential).await?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok((response, credential));
+    }
+    let credential = refresh().await?;
+    let response = attempt(&credential).await?;
+    Ok((response, credential))
+}
+
+/// Map a failed response to an error, turning `401` into a re-login hint.
+/// The body is parsed into an [`ApiError`]; the raw body is only logged.
+async fn response_error(what: &str, audience: TokenAudience, response: Response) -> CoreError {
+    let status = response.status();
+    let header_request_id = crate::trace::response_request_id(response.headers());
+    let text = response.text().await.unwrap_or_default();
+    log::debug!("{what} failed with {status}: {text}");
+    if status == StatusCode::UNAUTHORIZED {
+        return CoreError::Auth(AuthenticationError::TokenRejected(audience).to_string());
+    }
+    if status == StatusCode::FORBIDDEN
+        && let Some(kind) = crate::restrictions::Restrictions::from_error_body(&text).kind()
+    {
+        return CoreError::Restricted(format!("{what} ({kind})"));
+    }
+    let mut error = ApiError::from_response(what, status.as_u16(), &text);
+    if error.request_id.is_none() {
+        error.request_id = header_request_id;
+    }
+    CoreError::Api(error)
+}
+
 /// Build the XML message body for a file or image upload.
 fn build_file_message(
     obj_id: &str,
@@ -821,7 +1639,7 @@ fn build_file_message(
         );
         ("RichText/Html".to_string(), content)
     } else {
-        let download_url = format!("https://api.asm.skype.com/s/i?{obj_id}");
+        let download_url = format!("{}/s/i?{obj_id}", endpoints::current().asm_url());
         let size_kb = file_size / 1024;
         let size_str = if size_kb > 0 {
             format!("{size_kb} KB")
@@ -841,7 +1659,7 @@ fn build_media_generic_file_message(
     file_name: &str,
     file_size: usize,
 ) -> (String, String) {
-    let obj_url = format!("https://api.asm.skype.com/v1/objects/{obj_id}");
+    let obj_url = format!("{}/v1/objects/{obj_id}", endpoints::current().asm_url());
     let thumb_url = format!("{obj_url}/views/thumbnail");
     let login_url = format!("https://login.skype.com/login/sso?go=webclient.xmm&docid={obj_id}");
     let content = format!(
@@ -876,6 +1694,7 @@ fn mime_for_ext(ext: &str) -> &'static str {
     }
 }
 
+/// Decode a skypeToken JWT to extract skype ID and expiry.
 fn decode_skype_token(token: &str) -> Result<(String, i64, i64), CoreError> {
     use base64::Engine;
 
@@ -906,3 +1725,68 @@ fn decode_skype_token(token: &str) -> Result<(String, i64, i64), CoreError> {
 
     Ok((skype_id, iat, exp))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Answer each attempt with the next status, recording the credential used.
+    async fn run(
+        statuses: &[u16],
+        refresh_to: Result<&str, ()>,
+    ) -> (Result<u16, CoreError>, Vec<String>, usize) {
+        let sent = Mutex::new(Vec::new());
+        let refreshes = Mutex::new(0);
+        let result = retry_unauthorized(
+            "stale".to_string(),
+            |token: &String| {
+                let status = {
+                    let mut sent = sent.lock().unwrap();
+                    sent.push(token.clone());
+                    statuses[sent.len() - 1]
+                };
+                async move {
+                    Ok(Response::from(
+                        http::Response::builder().status(status).body("").unwrap(),
+                    ))
+                }
+            },
+            || async {
+                *refreshes.lock().unwrap() += 1;
+                refresh_to
+                    .map(str::to_string)
+                    .map_err(|()| CoreError::Auth("refresh failed".to_string()))
+            },
+        )
+        .await
+        .map(|(response, _)| response.status().as_u16());
+        (
+            result,
+            sent.into_inner().unwrap(),
+            refreshes.into_inner().unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn unauthorized_refreshes_and_retries_once() {
+        let (status, sent, refreshes) = run(&[200], Ok("fresh")).await;
+        assert_eq!(status.unwrap(), 200);
+        assert_eq!((sent, refreshes), (vec!["stale".to_string()], 0));
+
+        let (status, sent, refreshes) = run(&[401, 200], Ok("fresh")).await;
+        assert_eq!(status.unwrap(), 200);
+        assert_eq!(
+            (sent, refreshes),
+            (vec!["stale".to_string(), "fresh".to_string()], 1)
+        );
+
+        let (status, sent, refreshes) = run(&[401, 401], Ok("fresh")).await;
+        assert_eq!(status.unwrap(), 401);
+        assert_eq!((sent.len(), refreshes), (2, 1));
+
+        let (status, sent, refreshes) = run(&[401], Err(())).await;
+        assert!(matches!(status, Err(CoreError::Auth(_))));
+        assert_eq!((sent.len(), refreshes), (1, 1));
+    }
+}
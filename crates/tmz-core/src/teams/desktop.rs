@@ -0,0 +1,244 @@
+//! Token import from the official Teams desktop client.
+//!
+//! Fallback for environments where the Playwright login cannot run. The
+//! desktop client (classic Electron or the WebView2-based "new Teams") keeps
+//! MSAL access tokens in its Local Storage `LevelDB` files and some auth
+//! cookies in its `Cookies` `SQLite` database. Where these values are stored
+//! in plain text, they are picked up by scanning the files for JWTs and
+//! classifying each token by its `aud` claim.
+//!
+//! Encrypted values (DPAPI/Keychain protected cookies, encrypted MSAL cache
+//! entries) are never decrypted; if nothing usable is stored in plain text
+//! the import fails and browser login remains the only option.
+
+use crate::teams::models::TokenAudience;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Files larger than this are skipped (bytes).
+const MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Subdirectories of a client profile that may hold tokens.
+const TOKEN_SUBDIRS: &[&str] = &[
+    "Local Storage",
+    "Session Storage",
+    "IndexedDB",
+    "Network",
+    "Default/Local Storage",
+    "Default/Network",
+    "EBWebView/Default/Local Storage",
+    "EBWebView/Default/Network",
+];
+
+/// Plain cookie databases inside a client profile.
+const COOKIE_FILES: &[&str] = &["Cookies", "Default/Cookies", "EBWebView/Default/Cookies"];
+
+/// Access tokens found in a Teams desktop profile, one per audience.
+#[derive(Debug, Clone, Default)]
+pub struct DesktopTokens {
+    /// Best (latest-expiring) token per audience.
+    pub tokens: HashMap<TokenAudience, String>,
+    /// Profile directories that were scanned.
+    pub scanned: Vec<PathBuf>,
+}
+
+impl DesktopTokens {
+    /// Token for an audience, or an empty string if none was found.
+    #[must_use]
+    pub fn get(&self, audience: TokenAudience) -> &str {
+        self.tokens.get(&audience).map_or("", String::as_str)
+    }
+}
+
+/// Platform-specific profile directories of the official Teams clients.
+///
+/// Only directories that exist are returned.
+#[must_use]
+pub fn teams_desktop_dirs() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+
+    if cfg!(target_os = "windows") {
+        if let Some(roaming) = dirs::config_dir() {
+            candidates.push(roaming.join("Microsoft").join("Teams"));
+        }
+        if let Some(local) = dirs::data_local_dir() {
+            candidates.push(
+                local
+                    .join("Packages")
+                    .join("MSTeams_8wekyb3d8bbwe")
+                    .join("LocalCache")
+                    .join("Microsoft")
+                    .join("MSTeams"),
+            );
+        }
+    } else if cfg!(target_os = "macos") {
+        if let Some(home) = dirs::home_dir() {
+            candidates.push(home.join("Library/Application Support/Microsoft/Teams"));
+            candidates.push(home.join(
+                "Library/Containers/com.microsoft.teams2/Data/Library/Application Support/Microsoft/MSTeams",
+            ));
+        }
+    } else if let Some(config) = dirs::config_dir() {
+        candidates.push(config.join("Microsoft").join("Microsoft Teams"));
+        candidates.push(config.join("teams-for-linux"));
+    }
+
+    candidates.into_iter().filter(|d| d.is_dir()).collect()
+}
+
+/// Scan Teams desktop profile directories for usable access tokens.
+///
+/// Expired tokens are ignored. When several tokens exist for the same
+/// audience, the one that expires last wins.
+#[must_use]
+pub fn scan_for_tokens(dirs: &[PathBuf]) -> DesktopTokens {
+    let now = chrono::Utc::now().timestamp();
+    let mut best: HashMap<TokenAudience, (i64, String)> = HashMap::new();
+    let mut scanned = Vec::new();
+
+    for dir in dirs {
+        let mut files = Vec::new();
+        for sub in TOKEN_SUBDIRS {
+            collect_files(&dir.join(sub), &mut files, 4);
+        }
+        for name in COOKIE_FILES {
+            let path = dir.join(name);
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+        scanned.push(dir.clone());
+
+        for file in files {
+            let Ok(bytes) = std::fs::read(&file) else {
+                log::debug!("skipping unreadable {}", file.display());
+                continue;
+            };
+            for token in extract_jwts(&bytes) {
+                let Some((audience, exp)) = classify_token(&token) else {
+                    continue;
+                };
+                if exp <= now {
+                    continue;
+                }
+                let entry = best.entry(audience).or_insert((0, String::new()));
+                if exp > entry.0 {
+                    *entry = (exp, token);
+                }
+            }
+        }
+    }
+
+    DesktopTokens {
+        tokens: best.into_iter().map(|(aud, (_, tok))| (aud, tok)).collect(),
+        scanned,
+    }
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>, depth: usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(std::result::Result::ok) {
+        let path = entry.path();
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_dir() {
+            if depth > 0 {
+                collect_files(&path, out, depth - 1);
+            }
+        } else if meta.len() <= MAX_FILE_SIZE {
+            out.push(path);
+        }
+    }
+}
+
+/// Find JWT-shaped substrings (`eyJ...` with three segments) in raw bytes.
+fn extract_jwts(bytes: &[u8]) -> Vec<String> {
+    let is_token_byte = |b: u8| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.';
+    let mut found = Vec::new();
+    let mut i = 0;
+
+    while i + 3 < bytes.len() {
+        if &bytes[i..i + 3] != b"eyJ" {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && is_token_byte(bytes[i]) {
+            i += 1;
+        }
+        let candidate = &bytes[start..i];
+        let segments = candidate.split(|b| *b == b'.').collect::<Vec<_>>();
+        if segments.len() == 3
+            && segments.iter().all(|s| !s.is_empty())
+            && candidate.len() > 100
+            && let Ok(text) = std::str::from_utf8(candidate)
+        {
+            found.push(text.to_string());
+        }
+    }
+
+    found.sort();
+    found.dedup();
+    found
+}
+
+/// Determine a token's audience and expiry from its claims.
+fn classify_token(token: &str) -> Option<(TokenAudience, i64)> {
+    let claims = crate::teams::auth::jwt_claims(token)?;
+    let exp = claims["exp"].as_i64()?;
+    let aud = claims["aud"].as_str()?.to_lowercase();
+
+    let audience = if aud == "00000003-0000-0000-c000-000000000000" {
+        TokenAudience::Graph
+    } else {
+        TokenAudience::ALL
+            .into_iter()
+            .find(|audience| aud.contains(audience.resource()))?
+    };
+
+    Some((audience, exp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+
+    fn fake_jwt(aud: &str, exp: i64) -> String {
+        let b64 = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let header = b64.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let payload = b64.encode(format!(
+            r#"{{"aud":"{aud}","exp":{exp},"tid":"tenant","oid":"user","upn":"me@example.com"}}"#
+        ));
+        format!("{header}.{payload}.c2lnbmF0dXJlc2lnbmF0dXJlc2lnbmF0dXJl")
+    }
+
+    #[test]
+    fn extracts_and_classifies_tokens_from_raw_bytes() {
+        let graph = fake_jwt("https://graph.microsoft.com", 4_102_444_800);
+        let skype = fake_jwt("https://api.spaces.skype.com", 4_102_444_800);
+        let mut blob = b"\x00\x01leveldb-junk\"secret\":\"".to_vec();
+        blob.extend_from_slice(graph.as_bytes());
+        blob.extend_from_slice(b"\"\x00more junk eyJnotatoken ");
+        blob.extend_from_slice(skype.as_bytes());
+
+        let mut found = extract_jwts(&blob);
+        found.sort();
+        let mut expected = vec![graph.clone(), skype.clone()];
+        expected.sort();
+        assert_eq!(found, expected);
+
+        assert_eq!(
+            classify_token(&graph),
+            Some((TokenAudience::Graph, 4_102_444_800))
+        );
+        assert_eq!(
+            classify_token(&skype),
+            Some((TokenAudience::Skype, 4_102_444_800))
+        );
+        assert_eq!(classify_token(&fake_jwt("https://example.com", 1)), None);
+    }
+}
@@ -0,0 +1,221 @@
+//! OAuth 2.0 device authorization grant against Microsoft Entra ID.
+//!
+//! This is the default `tmz auth login` flow and needs neither Node.js nor
+//! Playwright: the user opens the verification URL in any browser, on this
+//! machine or another device (e.g. a phone), enters the short code, and tmz
+//! polls the token endpoint until the login completes. The Playwright login
+//! remains available with `--browser`.
+//!
+//! The first token is requested for the Skype audience together with
+//! `offline_access`; the returned refresh token is then redeemed for the
+//! remaining audiences and kept for silent refresh.
+
+use crate::teams::auth::{AuthManager, AuthenticationError};
+use crate::teams::endpoints;
+use crate::teams::models::TokenAudience;
+use crate::trace::TracedSend;
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Public client ID of the Teams desktop/mobile apps (device-code capable).
+pub const DEVICE_CODE_CLIENT_ID: &str = "1fec8e78-bce4-4aaf-ab1b-5451cc387264";
+
+/// Grant type for device code polling.
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Pending device code login returned by the authorization endpoint.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceCode {
+    /// Opaque code used when polling for tokens.
+    pub device_code: String,
+    /// Short code the user enters on the verification page.
+    pub user_code: String,
+    /// Page where the user enters the code.
+    pub verification_uri: String,
+    /// Seconds until the codes expire.
+    pub expires_in: u64,
+    /// Minimum polling interval in seconds.
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// Human-readable instructions from the identity provider.
+    #[serde(default)]
+    pub message: String,
+}
+
+const fn default_interval() -> u64 {
+    5
+}
+
+/// Successful token endpoint response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenResponse {
+    /// Access token for the requested scope.
+    pub access_token: String,
+    /// Refresh token (present when `offline_access` was requested).
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// Access token lifetime in seconds.
+    #[serde(default)]
+    pub expires_in: i64,
+}
+
+/// OAuth error body from the token endpoint.
+#[derive(Debug, Deserialize)]
+struct OAuthError {
+    error: String,
+    #[serde(default)]
+    error_description: String,
+}
+
+/// OAuth scope string for an audience.
+#[must_use]
+pub fn scope_for(audience: TokenAudience) -> String {
+    format!("https://{}/.default offline_access", audience.resource())
+}
+
+fn http_client() -> Result<Client, AuthenticationError> {
+    crate::network::client_builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| {
+            AuthenticationError::TokenExtractionError(format!("creating HTTP client: {e}"))
+        })
+}
+
+/// Start a device code login for the Skype audience.
+///
+/// # Errors
+///
+/// Returns an error if the authorization endpoint rejects the request.
+pub async fn start() -> Result<DeviceCode, AuthenticationError> {
+    let scope = format!("{} openid profile", scope_for(TokenAudience::Skype));
+    let response = http_client()?
+        .post(format!("{}/devicecode", endpoints::current().authority()))
+        .form(&[("client_id", DEVICE_CODE_CLIENT_ID), ("scope", &scope)])
+        .send_traced()
+        .await
+        .map_err(|e| {
+            AuthenticationError::TokenExtractionError(format!("device code request: {e}"))
+        })?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(AuthenticationError::TokenExtractionError(format!(
+            "device code request failed: {status} - {text}"
+        )));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| AuthenticationError::TokenExtractionError(format!("parsing device code: {e}")))
+}
+
+/// Poll the token endpoint until the user completes (or abandons) the login.
+///
+/// # Errors
+///
+/// Returns an error if the code expires, the user declines, or the token
+/// endpoint returns an unexpected error.
+pub async fn poll(code: &DeviceCode) -> Result<TokenResponse, AuthenticationError> {
+    let http = http_client()?;
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(code.expires_in);
+    let mut interval = code.interval.max(1);
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        if tokio::time::Instant::now() >= deadline {
+            return Err(AuthenticationError::TokenExtractionError(
+                "device code expired before login completed".to_string(),
+            ));
+        }
+
+        let response = http
+            .post(format!("{}/token", endpoints::current().authority()))
+            .form(&[
+                ("grant_type", DEVICE_CODE_GRANT),
+                ("client_id", DEVICE_CODE_CLIENT_ID),
+                ("device_code", code.device_code.as_str()),
+            ])
+            .send_traced()
+            .await
+            .map_err(|e| AuthenticationError::TokenExtractionError(format!("token poll: {e}")))?;
+
+        if response.status().is_success() {
+            return response.json().await.map_err(|e| {
+                AuthenticationError::TokenExtractionError(format!("parsing token response: {e}"))
+            });
+        }
+
+        let err: OAuthError = response.json().await.map_err(|e| {
+            AuthenticationError::TokenExtractionError(format!("parsing token error: {e}"))
+        })?;
+        match err.error.as_str() {
+            "authorization_pending" => {}
+            "slow_down" => interval += 5,
+            "authorization_declined" => {
+                return Err(AuthenticationError::TokenExtractionError(
+                    "login was declined on the other device".to_string(),
+                ));
+            }
+            "expired_token" => {
+                return Err(AuthenticationError::TokenExtractionError(
+                    "device code expired before login completed".to_string(),
+                ));
+            }
+            _ => {
+                return Err(AuthenticationError::TokenExtractionError(format!(
+                    "device code login failed: {} - {}",
+                    err.error, err.error_description
+                )));
+            }
+        }
+    }
+}
+
+/// Redeem a refresh token for an access token with a different scope.
+///
+/// `client_id` is the client the refresh token was issued to. Tokens of the
+/// Teams web app are single-page-app tokens, which the token endpoint only
+/// redeems for requests carrying the app's `Origin`.
+///
+/// # Errors
+///
+/// Returns an error if the token endpoint rejects the refresh token.
+pub async fn redeem_refresh_token(
+    refresh_token: &str,
+    client_id: &str,
+    audience: TokenAudience,
+) -> Result<TokenResponse, AuthenticationError> {
+    let scope = scope_for(audience);
+    let mut request = http_client()?
+        .post(format!("{}/token", endpoints::current().authority()))
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", client_id),
+            ("refresh_token", refresh_token),
+            ("scope", &scope),
+        ]);
+    if client_id == AuthManager::TEAMS_CLIENT_ID {
+        // The Teams web app's refresh tokens are only redeemed from its origin.
+        request = request.header(reqwest::header::ORIGIN, endpoints::current().teams_origin());
+    }
+    let response = request
+        .send_traced()
+        .await
+        .map_err(|e| AuthenticationError::TokenExtractionError(format!("token refresh: {e}")))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(AuthenticationError::TokenExtractionError(format!(
+            "refresh for {audience} token failed: {status} - {text}"
+        )));
+    }
+
+    response.json().await.map_err(|e| {
+        AuthenticationError::TokenExtractionError(format!("parsing token response: {e}"))
+    })
+}
@@ -0,0 +1,215 @@
+//! Service hosts of the Microsoft cloud the tenant lives in.
+//!
+//! Worldwide (and GCC) tenants use the public hosts. GCC High, `DoD`, and
+//! 21Vianet (China) tenants sign in and talk to Teams on hosts of their own,
+//! chosen with `[endpoints] cloud`; any single host can be overridden in the
+//! same section. The hosts are fixed once per process by [`configure`]
+//! before the first request, and [`current`] hands them to the client and
+//! the login flows.
+
+use std::sync::OnceLock;
+
+use crate::config::{Cloud, EndpointsConfig};
+
+static ACTIVE: OnceLock<Endpoints> = OnceLock::new();
+
+/// Hosts (without scheme) of the services tmz talks to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+    /// Sign-in (Entra ID) host.
+    pub login: String,
+    /// Teams web app and authz host.
+    pub teams: String,
+    /// Resource host of the skype token.
+    pub skype: String,
+    /// Chat service aggregator host.
+    pub chat: String,
+    /// Microsoft Graph host.
+    pub graph: String,
+    /// Presence service host.
+    pub presence: String,
+    /// Media (ASM) host.
+    pub asm: String,
+}
+
+impl Endpoints {
+    /// Default hosts of `cloud`.
+    #[must_use]
+    pub fn for_cloud(cloud: Cloud) -> Self {
+        let [login, teams, skype, chat, graph, presence, asm] = match cloud {
+            Cloud::Public => [
+                "login.microsoftonline.com",
+                "teams.microsoft.com",
+                "api.spaces.skype.com",
+                "chatsvcagg.teams.microsoft.com",
+                "graph.microsoft.com",
+                "presence.teams.microsoft.com",
+                "api.asm.skype.com",
+            ],
+            Cloud::GccHigh => [
+                "login.microsoftonline.us",
+                "gov.teams.microsoft.us",
+                "api.gov.teams.microsoft.us",
+                "chatsvcagg.gov.teams.microsoft.us",
+                "graph.microsoft.us",
+                "presence.gov.teams.microsoft.us",
+                "asm.gov.teams.microsoft.us",
+            ],
+            Cloud::Dod => [
+                "login.microsoftonline.us",
+                "dod.teams.microsoft.us",
+                "api.dod.teams.microsoft.us",
+                "chatsvcagg.dod.teams.microsoft.us",
+                "dod-graph.microsoft.us",
+                "presence.dod.teams.microsoft.us",
+                "asm.dod.teams.microsoft.us",
+            ],
+            Cloud::China => [
+                "login.partner.microsoftonline.cn",
+                "teams.microsoftonline.cn",
+                "api.teams.microsoftonline.cn",
+                "chatsvcagg.teams.microsoftonline.cn",
+                "microsoftgraph.chinacloudapi.cn",
+                "presence.teams.microsoftonline.cn",
+                "asm.teams.microsoftonline.cn",
+            ],
+        }
+        .map(str::to_string);
+        Self {
+            login,
+            teams,
+            skype,
+            chat,
+            graph,
+            presence,
+            asm,
+        }
+    }
+
+    /// Hosts of the configured cloud with the configured overrides applied.
+    #[must_use]
+    pub fn from_config(config: &EndpointsConfig) -> Self {
+        let mut endpoints = Self::for_cloud(config.cloud);
+        for (host, wanted) in [
+            (&mut endpoints.login, &config.login),
+            (&mut endpoints.teams, &config.teams),
+            (&mut endpoints.skype, &config.skype),
+            (&mut endpoints.chat, &config.chat),
+            (&mut endpoints.graph, &config.graph),
+            (&mut endpoints.presence, &config.presence),
+            (&mut endpoints.asm, &config.asm),
+        ] {
+            if let Some(value) = wanted.as_deref().map(bare_host).filter(|v| !v.is_empty()) {
+                *host = value.to_string();
+            }
+        }
+        endpoints
+    }
+
+    /// Entra ID v2 endpoint for work and school accounts.
+    #[must_use]
+    pub fn authority(&self) -> String {
+        format!("https://{}/organizations/oauth2/v2.0", self.login)
+    }
+
+    /// Origin of the Teams web app.
+    #[must_use]
+    pub fn teams_origin(&self) -> String {
+        format!("https://{}", self.teams)
+    }
+
+    /// URL of the Teams web app.
+    #[must_use]
+    pub fn teams_web_url(&self) -> String {
+        format!("https://{}/v2", self.teams)
+    }
+
+    /// Authz endpoint exchanging a skype-audience token for a skypeToken.
+    #[must_use]
+    pub fn authz_url(&self) -> String {
+        format!("https://{}/api/authsvc/v1.0/authz", self.teams)
+    }
+
+    /// Base URL of the Graph v1.0 API.
+    #[must_use]
+    pub fn graph_api(&self) -> String {
+        format!("https://{}/v1.0", self.graph)
+    }
+
+    /// Base URL of the presence service.
+    #[must_use]
+    pub fn presence_url(&self) -> String {
+        format!("https://{}", self.presence)
+    }
+
+    /// Base URL of the media (ASM) service.
+    #[must_use]
+    pub fn asm_url(&self) -> String {
+        format!("https://{}", self.asm)
+    }
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self::for_cloud(Cloud::Public)
+    }
+}
+
+/// `value` without a URL scheme or trailing slash.
+fn bare_host(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .split_once("://")
+        .map_or(value, |(_, rest)| rest)
+        .trim_end_matches('/')
+}
+
+/// Use the hosts from `config` for the rest of the process.
+///
+/// Only the first call has an effect; later calls (e.g. after a config
+/// reload) keep the hosts already in use.
+pub fn configure(config: &EndpointsConfig) {
+    let _ = ACTIVE.set(Endpoints::from_config(config));
+}
+
+/// The hosts in use: those given to [`configure`], or the public cloud's.
+#[must_use]
+pub fn current() -> &'static Endpoints {
+    ACTIVE.get_or_init(Endpoints::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clouds_pick_their_hosts_and_overrides_win() {
+        let public = Endpoints::default();
+        assert_eq!(
+            public.authz_url(),
+            "https://teams.microsoft.com/api/authsvc/v1.0/authz"
+        );
+        assert_eq!(public.graph_api(), "https://graph.microsoft.com/v1.0");
+
+        let config: EndpointsConfig = toml::from_str(
+            r#"
+            cloud = "gcc-high"
+            graph = "https://graph.example.us/"
+            asm = ""
+            "#,
+        )
+        .unwrap();
+        let gov = Endpoints::from_config(&config);
+        assert_eq!(gov.login, "login.microsoftonline.us");
+        assert_eq!(gov.teams, "gov.teams.microsoft.us");
+        assert_eq!(gov.graph, "graph.example.us");
+        assert_eq!(gov.asm, "asm.gov.teams.microsoft.us");
+
+        let china: EndpointsConfig = toml::from_str(r#"cloud = "china""#).unwrap();
+        assert_eq!(china.cloud, Cloud::China);
+        assert_eq!(
+            Endpoints::from_config(&china).authority(),
+            "https://login.partner.microsoftonline.cn/organizations/oauth2/v2.0"
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! A [`TeamsApi`] backed by fixture JSON instead of the live services.
+//!
+//! The fixture holds what the chat service and Graph would return:
+//!
+//! ```json
+//! {
+//!   "me": {"id": "<object id>", "displayName": "Ann Example"},
+//!   "conversations": [{"id": "19:ops@thread.v2", "threadProperties": {"topic": "Ops"}}],
+//!   "messages": {"19:ops@thread.v2": [{"id": "1", "messagetype": "Text", "content": "hi"}]},
+//!   "images": {"https://.../imgo": "<base64>"},
+//!   "files": {"https://.../report.docx": "<base64>"},
+//!   "events": [{"resourceType": "NewMessage", "resource": {"id": "2", "conversationid": "19:ops@thread.v2"}}]
+//! }
+//! ```
+//!
+//! Messages are listed newest first, like the service does, and are marked
+//! `isFromMe` when `from` ends with the MRI of `me`. `events` are handed out
+//! by the next [`TeamsApi::poll_events`], which returns nothing after that. Sent messages are
+//! prepended to their conversation and recorded for [`MockTeamsClient::sent`].
+//! [`MockTeamsClient::set_offline`] makes every call fail, to test reconnects.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use base64::Engine as _;
+use serde::Deserialize;
+
+use crate::CoreError;
+use crate::teams::api::TeamsApi;
+use crate::teams::client::{mark_own_message, parse_events};
+use crate::teams::models::{Conversation, LiveEvent, Message, PostOptions};
+
+/// Responses a [`MockTeamsClient`] serves.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Fixture {
+    /// Graph profile of the signed-in user.
+    pub me: serde_json::Value,
+    /// Conversations as the chat service lists them.
+    pub conversations: Vec<serde_json::Value>,
+    /// Messages per conversation ID, newest first.
+    pub messages: BTreeMap<String, Vec<serde_json::Value>>,
+    /// Base64 image bytes per URL.
+    pub images: BTreeMap<String, String>,
+    /// Base64 shared file bytes per URL.
+    pub files: BTreeMap<String, String>,
+    /// Event messages for the next poll, as in the service's
+    /// `eventMessages`.
+    pub events: Vec<serde_json::Value>,
+}
+
+/// A message sent through a [`MockTeamsClient`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentPost {
+    /// Target conversation.
+    pub conversation_id: String,
+    /// Message HTML.
+    pub content: String,
+    /// Subject and importance.
+    pub options: PostOptions,
+}
+
+/// A Teams client answering from a [`Fixture`].
+#[derive(Debug, Default)]
+pub struct MockTeamsClient {
+    fixture: Mutex<Fixture>,
+    sent: Mutex<Vec<SentPost>>,
+    offline: AtomicBool,
+}
+
+impl MockTeamsClient {
+    /// A client serving `fixture`.
+    #[must_use]
+    pub fn new(fixture: Fixture) -> Self {
+        Self {
+            fixture: Mutex::new(fixture),
+            ..Self::default()
+        }
+    }
+
+    /// A client serving the fixture in `json`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `json` is not a fixture.
+    pub fn from_json(json: &str) -> Result<Self, CoreError> {
+        serde_json::from_str(json)
+            .map(Self::new)
+            .map_err(|e| CoreError::Serialization(format!("parsing fixture: {e}")))
+    }
+
+    /// A client serving the fixture file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a fixture.
+    pub fn load(path: &Path) -> Result<Self, CoreError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Make every call fail (`true`) or succeed again (`false`), like a
+    /// dropped network connection.
+    pub fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::Relaxed);
+    }
+
+    /// Messages sent so far, oldest first.
+    #[must_use]
+    pub fn sent(&self) -> Vec<SentPost> {
+        self.sent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn fixture(&self, what: &str) -> Result<std::sync::MutexGuard<'_, Fixture>, CoreError> {
+        if self.offline.load(Ordering::Relaxed) {
+            return Err(CoreError::api(format!("{what} failed: network is offline")));
+        }
+        Ok(self.fixture.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// MRI of the fixture's user, e.g. `8:orgid:<id>`.
+fn my_mri(fixture: &Fixture) -> String {
+    format!("8:orgid:{}", fixture.me["id"].as_str().unwrap_or_default())
+}
+
+fn decode(what: &str, url: &str, data: Option<&String>) -> Result<Vec<u8>, CoreError> {
+    let data =
+        data.ok_or_else(|| CoreError::api(format!("{what}: {url} is not in the fixture")))?;
+    base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| CoreError::Serialization(format!("{what}: decoding {url}: {e}")))
+}
+
+impl TeamsApi for MockTeamsClient {
+    async fn get_me(&self) -> Result<serde_json::Value, CoreError> {
+        Ok(self.fixture("get me")?.me.clone())
+    }
+
+    async fn list_chats(&self) -> Result<Vec<Conversation>, CoreError> {
+        let fixture = self.fixture("list chats")?;
+        Ok(fixture
+            .conversations
+            .iter()
+            .cloned()
+            .map(Conversation::from)
+            .collect())
+    }
+
+    async fn get_chat_messages(
+        &self,
+        conversation_id: &str,
+        page_size: Option<i32>,
+    ) -> Result<Vec<Message>, CoreError> {
+        let fixture = self.fixture("get messages")?;
+        let size = usize::try_from(page_size.unwrap_or(200)).unwrap_or(0);
+        let mri = my_mri(&fixture);
+        Ok(fixture
+            .messages
+            .get(conversation_id)
+            .into_iter()
+            .flatten()
+            .take(size)
+            .map(|msg| mark_own_message(msg.clone(), &mri))
+            .collect())
+    }
+
+    async fn poll_events(&self) -> Result<Vec<LiveEvent>, CoreError> {
+        let mut fixture = self.fixture("poll events")?;
+        let mut body = serde_json::json!({ "eventMessages": std::mem::take(&mut fixture.events) });
+        let mri = my_mri(&fixture);
+        drop(fixture);
+        Ok(parse_events(&mut body, &mri))
+    }
+
+    async fn send_post(
+        &self,
+        conversation_id: &str,
+        content: &str,
+        options: &PostOptions,
+    ) -> Result<serde_json::Value, CoreError> {
+        let mut fixture = self.fixture("send message")?;
+        if !fixture
+            .conversations
+            .iter()
+            .any(|conv| conv["id"].as_str() == Some(conversation_id))
+        {
+            return Err(CoreError::api(format!(
+                "send message failed: no conversation {conversation_id}"
+            )));
+        }
+        let now = chrono::Utc::now();
+        let arrival = now.timestamp_millis();
+        let mut message = serde_json::json!({
+            "id": arrival.to_string(),
+            "conversationid": conversation_id,
+            "messagetype": "RichText/Html",
+            "content": content,
+            "from": format!("https://chat/v1/users/ME/contacts/{}", my_mri(&fixture)),
+            "imdisplayname": fixture.me["displayName"],
+            "composetime": now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        });
+        if let Some(ref subject) = options.subject {
+            message["properties"]["subject"] = subject.as_str().into();
+        }
+        if let Some(importance) = options.importance {
+            message["properties"]["importance"] = importance.as_str().into();
+        }
+        fixture
+            .messages
+            .entry(conversation_id.to_string())
+            .or_default()
+            .insert(0, message);
+        drop(fixture);
+
+        self.sent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(SentPost {
+                conversation_id: conversation_id.to_string(),
+                content: content.to_string(),
+                options: options.clone(),
+            });
+        Ok(serde_json::json!({ "OriginalArrivalTime": arrival, "id": arrival.to_string() }))
+    }
+
+    async fn download_image(&self, url: &str) -> Result<Vec<u8>, CoreError> {
+        let data = self.fixture("image download")?.images.get(url).cloned();
+        decode("image download", url, data.as_ref())
+    }
+
+    async fn download_shared_file(
+        &self,
+        url: &str,
+        max_bytes: u64,
+    ) -> Result<Option<Vec<u8>>, CoreError> {
+        let data = self.fixture("file download")?.files.get(url).cloned();
+        let data = decode("file download", url, data.as_ref())?;
+        Ok((data.len() as u64 <= max_bytes).then_some(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Cache;
+    use crate::engine::{Engine, SyncOptions};
+    use crate::testutil::TempDir;
+
+    /// Ann's view of a group chat with Bob, and a DM with Bob.
+    const FIXTURE: &str = r#"{
+        "me": {"id": "ann", "displayName": "Ann"},
+        "conversations": [
+            {"id": "19:ops", "threadProperties": {"topic": "Ops", "threadType": "chat"},
+             "lastMessage": {"content": "deploy done", "composetime": "2024-05-01T09:01:00Z"}},
+            {"id": "19:bob", "threadProperties": {"productThreadType": "OneToOneChat"},
+             "lastMessage": {"content": "hi", "imdisplayname": "Bob", "composetime": "2024-05-01T08:00:00Z"}}
+        ],
+        "messages": {
+            "19:ops": [
+                {"id": "2", "messagetype": "Text", "content": "deploy done",
+                 "from": "https://chat/v1/users/ME/contacts/8:orgid:ann",
+                 "composetime": "2024-05-01T09:01:00Z"},
+                {"id": "1", "messagetype": "RichText/Html", "content": "<p>deploy?</p>",
+                 "from": "https://chat/v1/users/ME/contacts/8:orgid:bob",
+                 "imdisplayname": "Bob", "composetime": "2024-05-01T09:00:00Z",
+                 "properties": {"mentions": [{"mri": "8:orgid:ann"}]}},
+                {"id": "0", "messagetype": "Control/Typing", "content": ""}
+            ]
+        }
+    }"#;
+
+    fn opts() -> SyncOptions {
+        SyncOptions {
+            chats: 10,
+            per_chat: 10,
+            ..SyncOptions::default()
+        }
+    }
+
+    async fn engine(dir: &TempDir) -> Engine {
+        Engine::new(Cache::open(&dir.join("cache.db")).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn the_engine_syncs_a_fixture() {
+        let mock = MockTeamsClient::from_json(FIXTURE).unwrap();
+        let dir = TempDir::new("mock").unwrap();
+        let engine = engine(&dir).await;
+        let report = engine
+            .sync_with_client(&mock, opts(), &mut |_| {})
+            .await
+            .unwrap();
+        assert_eq!(report.conversations, 2);
+        assert_eq!(report.chats, 2);
+        assert_eq!(report.messages, 2);
+        let cached = engine.cache().get_messages("19:ops", 10).await.unwrap();
+        let mine = cached.iter().find(|m| m.id == "2").unwrap();
+        assert!(mine.is_from_me);
+        assert_eq!(mine.from_display_name, "Ann");
+        let theirs = cached.iter().find(|m| m.id == "1").unwrap();
+        assert!(theirs.mentions_me && !theirs.is_from_me);
+        assert_eq!(theirs.content, "deploy?");
+    }
+
+    #[tokio::test]
+    async fn sends_land_in_the_fixture_chat() {
+        let mock = MockTeamsClient::from_json(FIXTURE).unwrap();
+        let dir = TempDir::new("mock-send").unwrap();
+        let engine = engine(&dir).await;
+        let ack = engine
+            .send_message_with(&mock, "19:ops", "<p>rolling back</p>")
+            .await
+            .unwrap();
+        assert!(ack["id"].is_string());
+        assert_eq!(mock.sent()[0].content, "<p>rolling back</p>");
+        let latest = mock.get_chat_messages("19:ops", Some(1)).await.unwrap();
+        assert!(latest[0].is_from_me);
+    }
+
+    #[tokio::test]
+    async fn sends_to_unknown_chats_fail() {
+        let mock = MockTeamsClient::from_json(FIXTURE).unwrap();
+        let dir = TempDir::new("mock-send-unknown").unwrap();
+        let engine = engine(&dir).await;
+        assert!(
+            engine
+                .send_message_with(&mock, "19:nobody", "hi")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn syncing_after_an_outage_reconnects_and_fills_the_gap() {
+        let mock = MockTeamsClient::from_json(FIXTURE).unwrap();
+        let dir = TempDir::new("mock-offline").unwrap();
+        let engine = engine(&dir).await;
+        engine
+            .sync_with_client(&mock, opts(), &mut |_| {})
+            .await
+            .unwrap();
+        mock.set_offline(true);
+        assert!(
+            engine
+                .sync_with_client(&mock, opts(), &mut |_| {})
+                .await
+                .is_err()
+        );
+        assert!(!engine.connection().connected);
+        mock.set_offline(false);
+        engine
+            .sync_with_client(&mock, opts(), &mut |_| {})
+            .await
+            .unwrap();
+        assert_eq!(engine.connection().reconnects, 1);
+        assert!(engine.take_gap_fill().is_some());
+    }
+
+    #[tokio::test]
+    async fn pushed_messages_are_cached_and_broadcast() {
+        let mock = MockTeamsClient::from_json(
+            r#"{
+                "me": {"id": "ann", "displayName": "Ann"},
+                "conversations": [
+                    {"id": "19:new", "threadProperties": {"topic": "Launch", "threadType": "chat"},
+                     "lastMessage": {"content": "go", "composetime": "2024-05-01T10:00:00Z"}}
+                ],
+                "events": [
+                    {"resourceType": "NewMessage", "resource": {
+                        "id": "7", "messagetype": "RichText/Html", "content": "<p>go</p>",
+                        "conversationLink": "https://chat/v1/users/ME/conversations/19:new",
+                        "from": "https://chat/v1/users/ME/contacts/8:orgid:bob",
+                        "imdisplayname": "Bob", "composetime": "2024-05-01T10:00:00Z",
+                        "properties": {"mentions": [{"mri": "8:orgid:ann"}]}}},
+                    {"resourceType": "NewMessage", "resource": {
+                        "id": "8", "messagetype": "Control/Typing", "conversationid": "19:new"}},
+                    {"resourceType": "ConversationUpdate", "resource": {"id": "19:new"}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let dir = TempDir::new("mock-live").unwrap();
+        let engine = engine(&dir).await;
+        let mut events = engine.subscribe();
+
+        assert_eq!(engine.poll_live(&mock).await.unwrap(), 1);
+        let cached = engine.cache().get_messages("19:new", 10).await.unwrap();
+        assert_eq!(cached.len(), 1);
+        assert!(cached[0].mentions_me && !cached[0].is_from_me);
+        // The unknown chat was listed, so it shows up with its name.
+        let convs = engine.cache().list_conversations(10).await.unwrap();
+        assert_eq!(convs[0].display_name, "Launch");
+        assert_eq!(
+            events.recv().await.unwrap(),
+            crate::engine::EngineEvent::MessageReceived {
+                conversation_id: "19:new".to_string(),
+                message_id: "7".to_string(),
+            }
+        );
+        assert!(engine.connection().connected);
+
+        // Events are handed out once; an idle poll stores nothing.
+        assert_eq!(engine.poll_live(&mock).await.unwrap(), 0);
+    }
+}
@@ -1,20 +1,32 @@
 //! Teams API client and authentication module.
 //!
 //! This module provides:
-//! - Authentication via browser automation
+//! - Authentication via browser automation or device code
 //! - Token extraction and storage
+//! - Token import from the official desktop client
+//! - Encrypted token export and import for headless machines
+//! - Service hosts of the public, government, and China clouds
 //! - API clients for Teams endpoints
+//! - A `TeamsApi` trait with a fixture-backed mock for tests
 
+pub mod api;
 pub mod auth;
 pub mod client;
+pub mod desktop;
+pub mod device_code;
+pub mod endpoints;
+pub mod mock;
 pub mod models;
 pub mod storage;
+pub mod transfer;
 
+pub use api::TeamsApi;
 pub use auth::{AuthManager, AuthenticationError};
 pub use client::TeamsClient;
+pub use endpoints::Endpoints;
 pub use models::{
     Attachment, ChannelInfo, ContentType, Conversation, ConversationMember, ConversationType,
-    Message, MessageImportance, PresenceStatus, Reaction, TeamInfo, TeamsSession, TeamsTokens,
-    UserPresence,
+    LiveEvent, Message, MessageImportance, PostOptions, PresenceStatus, Reaction, TeamInfo,
+    TeamsSession, TeamsTokens, TokenAudience, UserPresence,
 };
 pub use storage::TokenStorage;
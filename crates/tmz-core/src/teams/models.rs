@@ -2,29 +2,67 @@
 
 use serde::{Deserialize, Serialize};
 
-/// A Teams conversation (chat, channel, or group chat).
+/// A Teams conversation (chat, channel, or group chat), as listed by the
+/// chat service.
+///
+/// Deserializes from the service's JSON and serializes back to it
+/// unchanged; fields the struct does not model are still in [`Self::raw`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "serde_json::Value", into = "serde_json::Value")]
 pub struct Conversation {
     /// Unique conversation ID (format: 19:{id}@thread.tacv2 or 19:{id}@thread.v2).
     pub id: String,
     /// Conversation type (chat, channel, or meeting).
     pub conversation_type: ConversationType,
-    /// Display title/name of the conversation.
-    pub title: Option<String>,
-    /// Topic for group chats.
+    /// Topic for group chats and channels.
     pub topic: Option<String>,
-    /// List of conversation members.
-    pub members: Vec<ConversationMember>,
+    /// Thread type (`chat`, `meeting`, `space`, `topic`, ...).
+    pub thread_type: String,
+    /// Product type (`OneToOneChat`, `GroupChat`, `TeamsStandardChannel`, ...).
+    pub product_type: String,
     /// Last message in the conversation.
-    pub last_message: Option<Message>,
-    /// Timestamp of last activity (Unix milliseconds).
-    pub last_activity: Option<i64>,
-    /// Number of unread messages.
-    pub unread_count: Option<u32>,
-    /// Teams context for channels.
-    pub team: Option<TeamInfo>,
-    /// Channel context for channel conversations.
-    pub channel: Option<ChannelInfo>,
+    pub last_message: Option<Box<Message>>,
+    /// URL of the conversation's messages.
+    pub messages_url: String,
+    /// The conversation JSON as the service returned it.
+    pub raw: serde_json::Value,
+}
+
+impl From<serde_json::Value> for Conversation {
+    fn from(raw: serde_json::Value) -> Self {
+        let tp = &raw["threadProperties"];
+        let thread_type = string(&tp["threadType"]);
+        let product_type = string(&tp["productThreadType"]);
+        let conversation_type = match (thread_type.as_str(), product_type.as_str()) {
+            ("meeting", _) => ConversationType::Meeting,
+            ("space" | "topic", _) => ConversationType::Channel,
+            (_, "OneToOneChat" | "SfbInteropChat") => ConversationType::Chat,
+            _ => ConversationType::Group,
+        };
+        let last_message = raw
+            .get("lastMessage")
+            .filter(|lm| lm.is_object())
+            .map(|lm| Box::new(Message::from(lm.clone())));
+        Self {
+            id: string(&raw["id"]),
+            conversation_type,
+            topic: tp["topic"]
+                .as_str()
+                .filter(|t| !t.is_empty())
+                .map(str::to_string),
+            thread_type,
+            product_type,
+            last_message,
+            messages_url: string(&raw["messages"]),
+            raw,
+        }
+    }
+}
+
+impl From<Conversation> for serde_json::Value {
+    fn from(conversation: Conversation) -> Self {
+        conversation.raw
+    }
 }
 
 /// Type of conversation.
@@ -56,29 +94,107 @@ pub struct ConversationMember {
     pub tenant_id: Option<String>,
 }
 
-/// A message in a Teams conversation.
+/// A message in a Teams conversation, as returned by the chat service.
+///
+/// Like [`Conversation`], it round-trips the service's JSON through
+/// [`Self::raw`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "serde_json::Value", into = "serde_json::Value")]
 pub struct Message {
     /// Unique message ID.
     pub id: String,
     /// Conversation ID this message belongs to.
     pub conversation_id: String,
-    /// Sender information.
+    /// Sender; the ID is the sender's MRI (e.g. `8:orgid:<oid>`).
     pub from: Option<ConversationMember>,
     /// Message content (HTML or text).
     pub content: String,
     /// Message content type.
     pub content_type: ContentType,
-    /// Timestamp when message was sent (Unix milliseconds).
-    pub timestamp: i64,
+    /// Message type (`Text`, `RichText/Html`, `ThreadActivity/AddMember`, ...).
+    pub message_type: String,
+    /// When the message was composed (RFC 3339).
+    pub compose_time: String,
+    /// Subject line of a channel post.
+    pub subject: Option<String>,
     /// Message importance.
     pub importance: Option<MessageImportance>,
-    /// Reactions to the message.
-    pub reactions: Vec<Reaction>,
-    /// Attachments in the message.
-    pub attachments: Vec<Attachment>,
-    /// Reply thread ID for channel messages.
-    pub reply_to_id: Option<String>,
+    /// Whether the current user sent it (set by [`crate::TeamsClient`]).
+    pub is_from_me: bool,
+    /// Whether it @-mentions the current user (set by [`crate::TeamsClient`]).
+    pub mentions_me: bool,
+    /// The message JSON as the service returned it.
+    pub raw: serde_json::Value,
+}
+
+impl From<serde_json::Value> for Message {
+    fn from(raw: serde_json::Value) -> Self {
+        let message_type = string(&raw["messagetype"]);
+        let content_type = if message_type == "Text" {
+            ContentType::Text
+        } else {
+            ContentType::Html
+        };
+        let mri = raw["from"]
+            .as_str()
+            .and_then(|from| from.rsplit('/').next())
+            .unwrap_or_default();
+        let display_name = string(&raw["imdisplayname"]);
+        let from = (!mri.is_empty() || !display_name.is_empty()).then(|| ConversationMember {
+            id: mri.to_string(),
+            display_name,
+            email: None,
+            upn: None,
+            tenant_id: None,
+        });
+        let properties = &raw["properties"];
+        let subject = properties["subject"]
+            .as_str()
+            .or_else(|| raw["subject"].as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+        let importance = match properties["importance"].as_str() {
+            Some("normal") => Some(MessageImportance::Normal),
+            Some("high") => Some(MessageImportance::High),
+            Some("urgent") => Some(MessageImportance::Urgent),
+            _ => None,
+        };
+        Self {
+            id: string(&raw["id"]),
+            conversation_id: string(&raw["conversationid"]),
+            from,
+            content: string(&raw["content"]),
+            content_type,
+            message_type,
+            compose_time: string(&raw["composetime"]),
+            subject,
+            importance,
+            is_from_me: raw["isFromMe"].as_bool().unwrap_or(false),
+            mentions_me: raw["mentionsMe"].as_bool().unwrap_or(false),
+            raw,
+        }
+    }
+}
+
+impl From<Message> for serde_json::Value {
+    fn from(message: Message) -> Self {
+        message.raw
+    }
+}
+
+impl Message {
+    /// The sender's display name (`imdisplayname`), empty if unknown.
+    #[must_use]
+    pub fn sender_name(&self) -> &str {
+        self.from
+            .as_ref()
+            .map_or("", |from| from.display_name.as_str())
+    }
+}
+
+fn string(value: &serde_json::Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
 }
 
 /// Message content type.
@@ -104,6 +220,40 @@ pub enum MessageImportance {
     Urgent,
 }
 
+impl MessageImportance {
+    /// The value of the chat service's `importance` message property.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::High => "high",
+            Self::Urgent => "urgent",
+        }
+    }
+}
+
+/// Message properties beyond the content, such as the subject and
+/// importance of a channel announcement.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PostOptions {
+    /// Subject line shown above the post (channels only).
+    pub subject: Option<String>,
+    /// Importance; `None` sends a normal message.
+    pub importance: Option<MessageImportance>,
+}
+
+/// A change pushed by the chat service's event subscription (see
+/// [`crate::TeamsClient::poll_events`]).
+#[derive(Debug, Clone)]
+pub enum LiveEvent {
+    /// A message was posted or edited, with `is_from_me` and `mentions_me`
+    /// set.
+    Message(Box<Message>),
+    /// Any other change, by its `resourceType` (`ConversationUpdate`,
+    /// `ThreadUpdate`, ...).
+    Other(String),
+}
+
 /// A reaction to a message.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Reaction {
@@ -176,6 +326,21 @@ pub enum PresenceStatus {
     Unknown,
 }
 
+impl PresenceStatus {
+    /// Human-readable name, as Teams shows it.
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Available => "Available",
+            Self::Busy => "Busy",
+            Self::DoNotDisturb => "Do not disturb",
+            Self::Away => "Away",
+            Self::Offline => "Offline",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
 /// User presence information.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserPresence {
@@ -210,6 +375,84 @@ pub struct TeamsTokens {
     pub user_principal_name: String,
     /// Token expiry timestamp.
     pub expires_at: i64,
+    /// OAuth refresh token, present after a device code or browser login.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub refresh_token: String,
+    /// Client ID the refresh token was issued to. Empty means the device code
+    /// client.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub refresh_client_id: String,
+}
+
+impl TeamsTokens {
+    /// Access token issued for the given audience.
+    #[must_use]
+    pub fn token_for(&self, audience: TokenAudience) -> &str {
+        match audience {
+            TokenAudience::Skype => &self.skype_token,
+            TokenAudience::Chat => &self.chat_token,
+            TokenAudience::Graph => &self.graph_token,
+            TokenAudience::Presence => &self.presence_token,
+        }
+    }
+
+    /// Drop the access token for the given audience, leaving the others intact.
+    pub fn clear_token(&mut self, audience: TokenAudience) {
+        match audience {
+            TokenAudience::Skype => self.skype_token.clear(),
+            TokenAudience::Chat => self.chat_token.clear(),
+            TokenAudience::Graph => self.graph_token.clear(),
+            TokenAudience::Presence => self.presence_token.clear(),
+        }
+    }
+}
+
+/// Service audience an access token is issued for.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenAudience {
+    /// Skype/Teams authz token (api.spaces.skype.com in the public cloud).
+    Skype,
+    /// Chat Service Aggregation token (chatsvcagg.teams.microsoft.com).
+    Chat,
+    /// Microsoft Graph token (graph.microsoft.com).
+    Graph,
+    /// Presence token (presence.teams.microsoft.com).
+    Presence,
+}
+
+impl TokenAudience {
+    /// All audiences, in storage order.
+    pub const ALL: [Self; 4] = [Self::Skype, Self::Chat, Self::Graph, Self::Presence];
+
+    /// Short lowercase name used in messages.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Skype => "skype",
+            Self::Chat => "chat",
+            Self::Graph => "graph",
+            Self::Presence => "presence",
+        }
+    }
+
+    /// Resource host the token is issued for in the configured cloud.
+    #[must_use]
+    pub fn resource(self) -> &'static str {
+        let endpoints = crate::teams::endpoints::current();
+        match self {
+            Self::Skype => &endpoints.skype,
+            Self::Chat => &endpoints.chat,
+            Self::Graph => &endpoints.graph,
+            Self::Presence => &endpoints.presence,
+        }
+    }
+}
+
+impl std::fmt::Display for TokenAudience {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Session data from the Teams authz endpoint.
@@ -233,3 +476,64 @@ pub struct TeamsSession {
     /// Raw authz response for accessing other region-specific URLs.
     pub raw_settings: serde_json::Value,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one_to_one() -> serde_json::Value {
+        serde_json::json!({
+            "id": "19:abc@thread.v2",
+            "messages": "https://chat/v1/users/ME/conversations/19:abc@thread.v2/messages",
+            "threadProperties": {"topic": "", "threadType": "chat", "productThreadType": "OneToOneChat"},
+            "lastMessage": {
+                "id": "1700000000000",
+                "messagetype": "RichText/Html",
+                "content": "<p>hi</p>",
+                "from": "https://chat/v1/users/ME/contacts/8:orgid:ann",
+                "imdisplayname": "Ann",
+                "composetime": "2024-01-01T10:00:00.000Z",
+                "properties": {"importance": "high", "subject": " Plans "}
+            },
+            "unmodelled": {"kept": true}
+        })
+    }
+
+    #[test]
+    fn chats_read_thread_properties() {
+        let conv: Conversation = serde_json::from_value(one_to_one()).unwrap();
+        assert_eq!(conv.conversation_type, ConversationType::Chat);
+        assert_eq!(conv.topic, None);
+        assert_eq!(conv.product_type, "OneToOneChat");
+    }
+
+    #[test]
+    fn last_messages_read_sender_and_properties() {
+        let conv: Conversation = serde_json::from_value(one_to_one()).unwrap();
+        let last = conv.last_message.as_deref().unwrap();
+        assert_eq!(last.sender_name(), "Ann");
+        assert_eq!(last.from.as_ref().unwrap().id, "8:orgid:ann");
+        assert_eq!(last.content_type, ContentType::Html);
+        assert_eq!(last.importance, Some(MessageImportance::High));
+        assert_eq!(last.subject.as_deref(), Some("Plans"));
+        assert!(!last.is_from_me);
+    }
+
+    #[test]
+    fn conversations_round_trip_losslessly() {
+        let raw = one_to_one();
+        let conv: Conversation = serde_json::from_value(raw.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&conv).unwrap(), raw);
+    }
+
+    #[test]
+    fn topic_threads_are_channels() {
+        let channel = Conversation::from(serde_json::json!({
+            "id": "19:c@thread.tacv2",
+            "threadProperties": {"topic": "General", "threadType": "topic"}
+        }));
+        assert_eq!(channel.conversation_type, ConversationType::Channel);
+        assert_eq!(channel.topic.as_deref(), Some("General"));
+        assert!(channel.last_message.is_none());
+    }
+}
@@ -2,11 +2,12 @@
 //!
 //! Tokens are short-lived JWTs (typically 1 hour) so heavyweight encryption
 //! is unnecessary. They are stored at `$XDG_STATE_HOME/tmz/tokens.json` with
-//! `0600` permissions (owner-only read/write).
+//! `0600` permissions (owner-only read/write). Named accounts keep their own
+//! file in `$XDG_STATE_HOME/tmz/accounts/<name>/` (see [`crate::accounts`]).
 
 use crate::CoreError;
-use crate::teams::models::TeamsTokens;
-use std::path::PathBuf;
+use crate::teams::models::{TeamsTokens, TokenAudience};
+use std::path::{Path, PathBuf};
 
 /// Storage for Teams authentication tokens.
 #[derive(Debug)]
@@ -18,7 +19,8 @@ impl TokenStorage {
     /// Token file name.
     const FILENAME: &str = "tokens.json";
 
-    /// Create a new token storage instance using the default state directory.
+    /// Create a new token storage instance using the state directory of the
+    /// selected account.
     ///
     /// # Errors
     ///
@@ -26,9 +28,21 @@ impl TokenStorage {
     pub fn new() -> Result<Self, CoreError> {
         let state_dir = crate::default_state_dir()
             .map_err(|e| CoreError::Path(format!("resolving state dir: {e}")))?;
-        Ok(Self {
+        Ok(Self::in_dir(&state_dir))
+    }
+
+    /// Token storage in a specific state directory, e.g. of another account.
+    #[must_use]
+    pub fn in_dir(state_dir: &Path) -> Self {
+        Self {
             path: state_dir.join(Self::FILENAME),
-        })
+        }
+    }
+
+    /// Path of the token file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
     }
 
     /// Store tokens to disk.
@@ -46,17 +60,7 @@ impl TokenStorage {
         let json = serde_json::to_string_pretty(tokens)
             .map_err(|e| CoreError::Serialization(format!("serializing tokens: {e}")))?;
 
-        std::fs::write(&self.path, json.as_bytes()).map_err(CoreError::Io)?;
-
-        // Restrict to owner read/write
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt as _;
-            std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(0o600))
-                .map_err(CoreError::Io)?;
-        }
-
-        Ok(())
+        crate::secrets::write_private(&self.path, json.as_bytes()).map_err(CoreError::Io)
     }
 
     /// Load tokens from disk.
@@ -66,9 +70,10 @@ impl TokenStorage {
     /// Returns an error if the file does not exist, or parsing fails.
     pub fn load_tokens(&self) -> Result<TeamsTokens, CoreError> {
         if !self.path.exists() {
-            return Err(CoreError::SecretNotFound(
-                "no stored tokens. Run 'tmz auth login' first.".to_string(),
-            ));
+            return Err(CoreError::SecretNotFound(format!(
+                "no stored tokens. Run '{}' first.",
+                crate::accounts::login_command()
+            )));
         }
 
         let json = std::fs::read_to_string(&self.path).map_err(CoreError::Io)?;
@@ -95,6 +100,25 @@ impl TokenStorage {
         }
     }
 
+    /// Drop the stored token for a single audience.
+    ///
+    /// The remaining tokens are kept so unrelated commands keep working.
+    /// Returns `false` if no tokens are stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the token file cannot be read or rewritten.
+    pub fn invalidate_token(&self, audience: TokenAudience) -> Result<bool, CoreError> {
+        let mut tokens = match self.load_tokens() {
+            Ok(tokens) => tokens,
+            Err(CoreError::SecretNotFound(_)) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        tokens.clear_token(audience);
+        self.store_tokens(&tokens)?;
+        Ok(true)
+    }
+
     /// Delete stored tokens.
     ///
     /// # Errors
@@ -0,0 +1,100 @@
+//! Moving tokens to another machine (`tmz auth export` / `tmz auth import
+//! --file`).
+//!
+//! Logging in needs a browser, which a headless server does not have. Log
+//! in on a laptop instead, export the stored tokens (access tokens and the
+//! refresh token) into a passphrase-protected file, copy it over, and import
+//! it there. The file is an ASCII-armored [age](https://age-encryption.org)
+//! file with a scrypt passphrase, so `age -d` opens it as well.
+
+use age::secrecy::SecretString;
+
+use crate::CoreError;
+use crate::teams::models::TeamsTokens;
+
+/// Encrypt `tokens` with `passphrase`.
+///
+/// # Errors
+///
+/// Returns an error if the passphrase is empty or encryption fails.
+pub fn seal(tokens: &TeamsTokens, passphrase: &str) -> Result<String, CoreError> {
+    seal_with(tokens, &age::scrypt::Recipient::new(secret(passphrase)?))
+}
+
+fn seal_with(
+    tokens: &TeamsTokens,
+    recipient: &age::scrypt::Recipient,
+) -> Result<String, CoreError> {
+    let json = serde_json::to_vec(tokens)
+        .map_err(|e| CoreError::Serialization(format!("serializing tokens: {e}")))?;
+    age::encrypt_and_armor(recipient, &json)
+        .map_err(|e| CoreError::Auth(format!("encrypting tokens: {e}")))
+}
+
+/// Decrypt tokens exported by [`seal`] (armored or binary age).
+///
+/// # Errors
+///
+/// Returns [`CoreError::Auth`] if the passphrase is wrong or the data is not
+/// an age file, and [`CoreError::Serialization`] if it holds no tokens.
+pub fn open(data: &[u8], passphrase: &str) -> Result<TeamsTokens, CoreError> {
+    let identity = age::scrypt::Identity::new(secret(passphrase)?);
+    let json = age::decrypt(&identity, data).map_err(|e| {
+        let hint = if matches!(
+            e,
+            age::DecryptError::DecryptionFailed | age::DecryptError::NoMatchingKeys
+        ) {
+            " (wrong passphrase?)"
+        } else {
+            ""
+        };
+        CoreError::Auth(format!("decrypting tokens: {e}{hint}"))
+    })?;
+    serde_json::from_slice(&json)
+        .map_err(|e| CoreError::Serialization(format!("reading exported tokens: {e}")))
+}
+
+fn secret(passphrase: &str) -> Result<SecretString, CoreError> {
+    if passphrase.is_empty() {
+        return Err(CoreError::Auth(
+            "the passphrase must not be empty".to_string(),
+        ));
+    }
+    Ok(SecretString::from(passphrase.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exported_tokens_open_only_with_the_passphrase() {
+        let tokens = TeamsTokens {
+            skype_token: "skype".to_string(),
+            chat_token: "chat".to_string(),
+            graph_token: "graph".to_string(),
+            presence_token: String::new(),
+            tenant_id: "tenant".to_string(),
+            user_id: "user".to_string(),
+            user_principal_name: "alice@example.com".to_string(),
+            expires_at: 1_800_000_000,
+            refresh_token: "refresh".to_string(),
+            refresh_client_id: String::new(),
+        };
+        // A low work factor keeps the test fast in debug builds.
+        let mut recipient = age::scrypt::Recipient::new(secret("correct horse").unwrap());
+        recipient.set_work_factor(4);
+        let sealed = seal_with(&tokens, &recipient).unwrap();
+        assert!(sealed.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+        assert!(!sealed.contains("refresh"));
+
+        let opened = open(sealed.as_bytes(), "correct horse").unwrap();
+        assert_eq!(opened.refresh_token, "refresh");
+        assert_eq!(opened.user_principal_name, "alice@example.com");
+
+        let wrong = open(sealed.as_bytes(), "battery staple").unwrap_err();
+        assert!(wrong.to_string().contains("wrong passphrase"), "{wrong}");
+        assert!(open(b"not an age file", "correct horse").is_err());
+        assert!(seal(&tokens, "").is_err());
+    }
+}
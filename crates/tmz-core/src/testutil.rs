@@ -0,0 +1,71 @@
+//! Helpers shared by the tests of tmz and its frontends.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A scratch directory removed again when dropped.
+///
+/// Names combine a label, the process ID, and a counter, so tests that run
+/// in parallel never share a directory.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a fresh, empty directory labelled `name`.
+    ///
+    /// # Errors
+    /// Returns an error if the directory cannot be created.
+    pub fn new(name: &str) -> std::io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("tmz-{name}-{}-{n}", std::process::id()));
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+        std::fs::create_dir_all(&path)?;
+        Ok(Self(path))
+    }
+
+    /// The directory's path.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directories_are_unique_and_removed_on_drop() {
+        let a = TempDir::new("testutil").unwrap();
+        let b = TempDir::new("testutil").unwrap();
+        assert_ne!(a.path(), b.path());
+        assert!(a.is_dir());
+        let path = a.to_path_buf();
+        drop(a);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,295 @@
+//! Time spent per conversation in the TUI.
+//!
+//! With `[time_tracking] enabled = true`, the TUI counts the time a
+//! conversation is open (the message pane or composer has focus) while you
+//! are active. After `idle_secs` without a key press the clock stops at the
+//! last key press. Each stretch of at least `min_secs` is stored in the
+//! cache's `time_entries` table, summed up by `tmz stats time`, and handed
+//! to the configured command or URL, e.g. to book it in timewarrior or a
+//! billing tool.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tokio::io::AsyncWriteExt as _;
+
+use crate::CoreError;
+use crate::config::TimeTrackingConfig;
+use crate::trace::TracedSend;
+
+/// How long the hook command or URL may take.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A stretch of time spent in one conversation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimeEntry {
+    /// Conversation the time was spent in.
+    pub conversation_id: String,
+    /// When the stretch started.
+    pub started_at: DateTime<Utc>,
+    /// When it ended.
+    pub ended_at: DateTime<Utc>,
+}
+
+impl TimeEntry {
+    /// Length of the stretch in whole seconds.
+    #[must_use]
+    pub fn seconds(&self) -> i64 {
+        (self.ended_at - self.started_at).num_seconds()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    conversation_id: String,
+    started_at: DateTime<Utc>,
+    last_active: DateTime<Utc>,
+}
+
+/// Turns what the TUI shows and when keys are pressed into [`TimeEntry`]s.
+#[derive(Debug, Clone)]
+pub struct TimeTracker {
+    idle: chrono::Duration,
+    min_secs: i64,
+    current: Option<Session>,
+}
+
+impl TimeTracker {
+    /// A tracker with the idle and minimum lengths of `config`.
+    #[must_use]
+    pub fn new(config: &TimeTrackingConfig) -> Self {
+        Self {
+            idle: chrono::Duration::seconds(i64::try_from(config.idle_secs).unwrap_or(i64::MAX)),
+            min_secs: i64::try_from(config.min_secs).unwrap_or(i64::MAX),
+            current: None,
+        }
+    }
+
+    /// Note that `conversation` (`None` if no conversation is open) is
+    /// shown at `now`, and whether a key was just pressed (`active`).
+    ///
+    /// Returns the stretch that ended, if it is long enough to record: the
+    /// open conversation changed, or the user went idle.
+    pub fn observe(
+        &mut self,
+        conversation: Option<&str>,
+        now: DateTime<Utc>,
+        active: bool,
+    ) -> Option<TimeEntry> {
+        let mut ended = None;
+        if let Some(session) = &self.current {
+            if now - session.last_active > self.idle {
+                ended = self.finish(session.last_active);
+            } else if conversation != Some(session.conversation_id.as_str()) {
+                ended = self.finish(now);
+            }
+        }
+        match (&mut self.current, conversation) {
+            (Some(session), _) if active => session.last_active = now,
+            (None, Some(id)) if active => {
+                self.current = Some(Session {
+                    conversation_id: id.to_string(),
+                    started_at: now,
+                    last_active: now,
+                });
+            }
+            _ => {}
+        }
+        ended
+    }
+
+    /// End the current stretch at `now` (or at the last key press if the
+    /// user went idle), e.g. when the TUI quits.
+    pub fn stop(&mut self, now: DateTime<Utc>) -> Option<TimeEntry> {
+        let last_active = self.current.as_ref()?.last_active;
+        let end = if now - last_active > self.idle {
+            last_active
+        } else {
+            now
+        };
+        self.finish(end)
+    }
+
+    fn finish(&mut self, end: DateTime<Utc>) -> Option<TimeEntry> {
+        let session = self.current.take()?;
+        let entry = TimeEntry {
+            conversation_id: session.conversation_id,
+            started_at: session.started_at,
+            ended_at: end,
+        };
+        (entry.seconds() >= self.min_secs.max(1)).then_some(entry)
+    }
+}
+
+/// Whether `config` has a command or URL to hand entries to.
+#[must_use]
+pub const fn has_hook(config: &TimeTrackingConfig) -> bool {
+    !config.command.is_empty() || config.url.is_some()
+}
+
+/// JSON describing `entry` in the conversation named `name`.
+#[must_use]
+pub fn payload(entry: &TimeEntry, name: &str) -> serde_json::Value {
+    json!({
+        "conversation_id": entry.conversation_id,
+        "name": name,
+        "start": rfc3339(entry.started_at),
+        "end": rfc3339(entry.ended_at),
+        "seconds": entry.seconds(),
+    })
+}
+
+fn rfc3339(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Hand `entry` to the configured command and URL.
+///
+/// # Errors
+///
+/// Returns an error if the command cannot be run or fails, or if the URL
+/// does not accept the entry.
+pub async fn emit(
+    config: &TimeTrackingConfig,
+    entry: &TimeEntry,
+    name: &str,
+) -> Result<(), CoreError> {
+    let body = payload(entry, name);
+    if let Some((program, args)) = config.command.split_first() {
+        let args: Vec<String> = args.iter().map(|arg| fill(arg, &body)).collect();
+        let mut child = tokio::process::Command::new(program)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| CoreError::Other(format!("running {program}: {e}")))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A command that ignores stdin may exit before reading it.
+            let _ = stdin.write_all(body.to_string().as_bytes()).await;
+        }
+        let output = tokio::time::timeout(HOOK_TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| CoreError::Other(format!("{program} timed out")))?
+            .map_err(|e| CoreError::Other(format!("running {program}: {e}")))?;
+        if !output.status.success() {
+            return Err(CoreError::Other(format!(
+                "{program} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+    }
+    if let Some(url) = &config.url {
+        let response = crate::network::client_builder()
+            .timeout(HOOK_TIMEOUT)
+            .build()
+            .map_err(|e| CoreError::Other(format!("creating HTTP client: {e}")))?
+            .post(url)
+            .json(&body)
+            .send_traced()
+            .await
+            .map_err(|e| CoreError::api(format!("time tracking hook {url}: {e}")))?;
+        if !response.status().is_success() {
+            return Err(CoreError::api(format!(
+                "time tracking hook {url} answered {}",
+                response.status()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// `arg` with `{field}` placeholders replaced from `payload`.
+fn fill(arg: &str, payload: &serde_json::Value) -> String {
+    let mut out = arg.to_string();
+    for (key, placeholder) in [
+        ("conversation_id", "{conversation_id}"),
+        ("name", "{name}"),
+        ("start", "{start}"),
+        ("end", "{end}"),
+        ("seconds", "{seconds}"),
+    ] {
+        if out.contains(placeholder) {
+            let value = match &payload[key] {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            out = out.replace(placeholder, &value);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-03-09T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            + chrono::Duration::seconds(secs)
+    }
+
+    /// Idle after 5 minutes, ignoring stretches under a minute.
+    fn tracker() -> TimeTracker {
+        TimeTracker::new(&TimeTrackingConfig {
+            idle_secs: 300,
+            min_secs: 60,
+            ..TimeTrackingConfig::default()
+        })
+    }
+
+    #[test]
+    fn switching_chats_ends_the_stretch() {
+        let mut tracker = tracker();
+        assert_eq!(tracker.observe(Some("19:a"), at(0), true), None);
+        assert_eq!(tracker.observe(Some("19:a"), at(200), false), None);
+        assert_eq!(tracker.observe(Some("19:a"), at(240), true), None);
+        let entry = tracker.observe(Some("19:b"), at(400), true).unwrap();
+        assert_eq!(
+            (entry.conversation_id.as_str(), entry.seconds()),
+            ("19:a", 400)
+        );
+    }
+
+    #[test]
+    fn going_idle_ends_the_stretch_at_the_last_key_press() {
+        let mut tracker = tracker();
+        assert_eq!(tracker.observe(Some("19:b"), at(400), true), None);
+        assert_eq!(tracker.observe(Some("19:b"), at(500), true), None);
+        let entry = tracker.observe(Some("19:b"), at(900), false).unwrap();
+        assert_eq!((entry.started_at, entry.ended_at), (at(400), at(500)));
+    }
+
+    #[test]
+    fn skimming_past_a_chat_is_not_recorded() {
+        let mut tracker = tracker();
+        assert_eq!(tracker.observe(Some("19:c"), at(1000), true), None);
+        assert_eq!(tracker.observe(None, at(1030), true), None);
+        assert_eq!(tracker.stop(at(1030)), None);
+    }
+
+    #[test]
+    fn stopping_ends_the_open_stretch_once() {
+        let mut tracker = tracker();
+        assert_eq!(tracker.observe(Some("19:c"), at(1040), true), None);
+        assert_eq!(tracker.stop(at(1200)).unwrap().seconds(), 160);
+        assert_eq!(tracker.stop(at(1300)), None);
+    }
+
+    #[test]
+    fn templates_are_filled_from_the_payload() {
+        let mut tracker = tracker();
+        tracker.observe(Some("19:b"), at(400), true);
+        let entry = tracker.stop(at(500)).unwrap();
+        let body = payload(&entry, "Customer A");
+        assert_eq!(
+            fill("{name}: {start}-{end} ({seconds}s)", &body),
+            "Customer A: 2026-03-09T09:06:40Z-2026-03-09T09:08:20Z (100s)"
+        );
+    }
+}
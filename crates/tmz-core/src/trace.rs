@@ -0,0 +1,256 @@
+//! Opt-in tracing of HTTP requests.
+//!
+//! With `--trace-http` or `[logging] trace_http = true`, every request to
+//! Teams, Graph, and the other services tmz talks to is appended to
+//! `http-trace.jsonl` in the state directory: method, URL (token-like query
+//! values masked), status, latency, the `client-request-id` tmz sent, and
+//! the request ID the service answered with. The file is rotated to
+//! `http-trace.1.jsonl` at 5 MB. `tmz debug last-requests` prints the
+//! newest records.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response};
+
+use crate::CoreError;
+
+/// Trace file name inside the state directory.
+const TRACE_FILE: &str = "http-trace.jsonl";
+
+/// Size at which the trace file is rotated.
+const MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Response headers carrying the service's request ID, in order of
+/// preference (Graph, Azure, chat service).
+const REQUEST_ID_HEADERS: &[&str] = &["request-id", "x-ms-request-id", "ms-cv"];
+
+/// Query parameters whose values are masked in traced URLs.
+const SECRET_PARAMS: &[&str] = &["token", "sig", "key", "secret", "code", "password", "auth"];
+
+/// Directory of the trace file, once tracing is enabled.
+static TRACE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Serializes appends and rotation across tasks.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// One traced request.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TraceRecord {
+    /// When the request was sent (UTC, RFC 3339).
+    pub time: String,
+    /// HTTP method.
+    pub method: String,
+    /// Request URL with secret query values masked.
+    pub url: String,
+    /// Response status, if a response arrived.
+    pub status: Option<u16>,
+    /// Time until the response headers arrived.
+    pub latency_ms: u64,
+    /// Correlation ID sent as `client-request-id`.
+    pub client_request_id: String,
+    /// Request ID the service answered with.
+    pub request_id: Option<String>,
+    /// Transport error, if the request did not complete.
+    pub error: Option<String>,
+}
+
+/// Start tracing requests of this process into `state_dir`.
+pub fn enable(state_dir: &Path) {
+    let _ = TRACE_DIR.set(state_dir.to_path_buf());
+}
+
+/// Path of the trace file in `state_dir`.
+#[must_use]
+pub fn trace_path(state_dir: &Path) -> PathBuf {
+    state_dir.join(TRACE_FILE)
+}
+
+/// The request ID in a response's headers.
+pub(crate) fn response_request_id(headers: &HeaderMap) -> Option<String> {
+    REQUEST_ID_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Sending a request through the tracer.
+pub(crate) trait TracedSend {
+    /// Like [`RequestBuilder::send`], recording the request when tracing
+    /// is enabled.
+    fn send_traced(self) -> impl Future<Output = reqwest::Result<Response>> + Send;
+}
+
+impl TracedSend for RequestBuilder {
+    async fn send_traced(self) -> reqwest::Result<Response> {
+        let Some(dir) = TRACE_DIR.get() else {
+            return self.send().await;
+        };
+        let id = correlation_id();
+        let (client, request) = self
+            .header("client-request-id", &id)
+            .header("x-ms-client-request-id", &id)
+            .build_split();
+        let request = request?;
+        let mut record = TraceRecord {
+            time: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            method: request.method().to_string(),
+            url: redact_url(request.url()),
+            status: None,
+            latency_ms: 0,
+            client_request_id: id,
+            request_id: None,
+            error: None,
+        };
+        let started = Instant::now();
+        let result = client.execute(request).await;
+        record.latency_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        match result {
+            Ok(ref response) => {
+                record.status = Some(response.status().as_u16());
+                record.request_id = response_request_id(response.headers());
+            }
+            Err(ref e) => record.error = Some(e.to_string()),
+        }
+        if let Err(e) = append(dir, &record) {
+            log::debug!("writing HTTP trace: {e}");
+        }
+        result
+    }
+}
+
+/// The newest `limit` traced requests in `state_dir`, oldest first.
+///
+/// # Errors
+///
+/// Returns an error if a trace file exists but cannot be read.
+pub fn last_requests(state_dir: &Path, limit: usize) -> Result<Vec<TraceRecord>, CoreError> {
+    let mut records = Vec::new();
+    for path in [rotated_path(state_dir), trace_path(state_dir)] {
+        if !path.exists() {
+            continue;
+        }
+        let text = std::fs::read_to_string(&path)?;
+        records.extend(
+            text.lines()
+                .filter_map(|line| serde_json::from_str::<TraceRecord>(line).ok()),
+        );
+    }
+    let skip = records.len().saturating_sub(limit);
+    Ok(records.split_off(skip))
+}
+
+fn rotated_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("http-trace.1.jsonl")
+}
+
+fn append(dir: &Path, record: &TraceRecord) -> Result<(), CoreError> {
+    let line =
+        serde_json::to_string(record).map_err(|e| CoreError::Serialization(e.to_string()))?;
+    let _guard = WRITE_LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    std::fs::create_dir_all(dir)?;
+    let path = trace_path(dir);
+    if std::fs::metadata(&path).is_ok_and(|m| m.len() >= MAX_BYTES) {
+        std::fs::rename(&path, rotated_path(dir))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{line}")?;
+    Ok(())
+}
+
+/// The URL with the values of secret-looking query parameters masked.
+fn redact_url(url: &reqwest::Url) -> String {
+    let Some(query) = url.query() else {
+        return url.to_string();
+    };
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _)) if SECRET_PARAMS.iter().any(|s| key.to_lowercase().contains(s)) => {
+                format!("{key}=***")
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    let mut url = url.clone();
+    url.set_query(Some(&query));
+    url.to_string()
+}
+
+/// A random GUID-formatted correlation ID.
+fn correlation_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let hi = RandomState::new().hash_one(n);
+    let lo = RandomState::new().hash_one(hi);
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        hi >> 32,
+        (hi >> 16) & 0xffff,
+        hi & 0xfff,
+        0x8000 | (lo >> 48 & 0x3fff),
+        lo & 0xffff_ffff_ffff
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn secrets_in_query_strings_are_redacted() {
+        let url = reqwest::Url::parse(
+            "https://graph.microsoft.com/v1.0/users?$select=id&access_token=abc&sig=xyz",
+        )
+        .unwrap();
+        assert_eq!(
+            redact_url(&url),
+            "https://graph.microsoft.com/v1.0/users?$select=id&access_token=***&sig=***"
+        );
+    }
+
+    #[test]
+    fn correlation_ids_are_fresh_uuids() {
+        let id = correlation_id();
+        assert_eq!(id.len(), 36);
+        assert_ne!(id, correlation_id());
+    }
+
+    #[test]
+    fn last_requests_read_back_through_the_rotated_file() {
+        let dir = TempDir::new("trace").unwrap();
+        let record = |n: u16| TraceRecord {
+            time: "2026-10-16T09:00:00.000Z".to_string(),
+            method: "GET".to_string(),
+            url: format!("https://example.com/{n}"),
+            status: Some(n),
+            latency_ms: 12,
+            client_request_id: "c".to_string(),
+            request_id: None,
+            error: None,
+        };
+        append(&dir, &record(200)).unwrap();
+        std::fs::rename(trace_path(&dir), rotated_path(&dir)).unwrap();
+        append(&dir, &record(404)).unwrap();
+        append(&dir, &record(500)).unwrap();
+        let last = last_requests(&dir, 2).unwrap();
+        assert_eq!(
+            last.iter().map(|r| r.status).collect::<Vec<_>>(),
+            [Some(404), Some(500)]
+        );
+        assert_eq!(last_requests(&dir, 10).unwrap().len(), 3);
+    }
+}
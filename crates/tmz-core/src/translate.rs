@@ -0,0 +1,264 @@
+//! On-demand message translation through `DeepL` or Azure AI Translator.
+//!
+//! Used by `tmz msg --translate` and the TUI translation toggle. Both
+//! services detect the source language, which is stored with the result.
+//! Translations are cached per message and target language in the cache's
+//! `translations` table, so re-reading a translated chat makes no requests.
+
+use crate::CoreError;
+use crate::cache::{Cache, CachedMessage, Translation};
+use crate::config::{TranslationConfig, TranslationProvider};
+use crate::trace::TracedSend;
+use std::collections::HashMap;
+
+/// Texts sent per request (`DeepL` accepts at most 50).
+const BATCH_SIZE: usize = 50;
+
+/// Translate the text of `messages` into `target`, returning translations
+/// keyed by message id.
+///
+/// Cached translations are reused; only messages without one are sent to
+/// the service, and their results are cached. System events and messages
+/// without text are skipped.
+///
+/// # Errors
+///
+/// Returns an error if no API key is configured, the service request fails,
+/// or the cache cannot be read or written.
+pub async fn translate_messages(
+    cache: &Cache,
+    config: &TranslationConfig,
+    messages: &[CachedMessage],
+    target: &str,
+) -> Result<HashMap<String, Translation>, CoreError> {
+    let target = target.trim().to_ascii_lowercase();
+    let mut translations = HashMap::new();
+    let mut cached_by_conv: HashMap<&str, HashMap<String, Translation>> = HashMap::new();
+    let mut missing = Vec::new();
+
+    for msg in messages {
+        if msg.is_system_event() || msg.content.trim().is_empty() {
+            continue;
+        }
+        if !cached_by_conv.contains_key(msg.conversation_id.as_str()) {
+            let cached = cache.translations(&msg.conversation_id, &target).await?;
+            cached_by_conv.insert(&msg.conversation_id, cached);
+        }
+        match cached_by_conv
+            .get_mut(msg.conversation_id.as_str())
+            .and_then(|cached| cached.remove(&msg.id))
+        {
+            Some(t) => {
+                translations.insert(msg.id.clone(), t);
+            }
+            None => missing.push(msg),
+        }
+    }
+
+    for batch in missing.chunks(BATCH_SIZE) {
+        let texts: Vec<&str> = batch.iter().map(|m| m.content.as_str()).collect();
+        let results = translate_texts(config, &texts, &target).await?;
+        for (msg, translation) in batch.iter().zip(results) {
+            cache.store_translation(msg, &target, &translation).await?;
+            translations.insert(msg.id.clone(), translation);
+        }
+    }
+    Ok(translations)
+}
+
+/// Translate `texts` into `target` with the configured service, in order.
+///
+/// # Errors
+///
+/// Returns an error if no API key is configured or the request fails.
+pub async fn translate_texts(
+    config: &TranslationConfig,
+    texts: &[&str],
+    target: &str,
+) -> Result<Vec<Translation>, CoreError> {
+    let key = config.key().ok_or_else(|| {
+        CoreError::Config(format!(
+            "no translation API key: set {} or [ai.translation] api_key",
+            config.api_key_env
+        ))
+    })?;
+    let client = crate::network::client()?;
+    let request = match config.provider {
+        TranslationProvider::Deepl => {
+            let endpoint = config.endpoint.clone().unwrap_or_else(|| {
+                if key.ends_with(":fx") {
+                    "https://api-free.deepl.com/v2/translate".to_string()
+                } else {
+                    "https://api.deepl.com/v2/translate".to_string()
+                }
+            });
+            client
+                .post(endpoint)
+                .header("Authorization", format!("DeepL-Auth-Key {key}"))
+                .json(&serde_json::json!({
+                    "text": texts,
+                    "target_lang": target.to_ascii_uppercase(),
+                }))
+        }
+        TranslationProvider::Azure => {
+            let endpoint = config
+                .endpoint
+                .as_deref()
+                .unwrap_or("https://api.cognitive.microsofttranslator.com");
+            let body: Vec<_> = texts
+                .iter()
+                .map(|t| serde_json::json!({ "Text": t }))
+                .collect();
+            let request = client
+                .post(format!("{}/translate", endpoint.trim_end_matches('/')))
+                .query(&[("api-version", "3.0"), ("to", target)])
+                .header("Ocp-Apim-Subscription-Key", key)
+                .json(&body);
+            match &config.region {
+                Some(region) => request.header("Ocp-Apim-Subscription-Region", region),
+                None => request,
+            }
+        }
+    };
+
+    let response = request
+        .send_traced()
+        .await
+        .map_err(|e| CoreError::api(format!("translation request failed: {e}")))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| CoreError::api(format!("reading translation response: {e}")))?;
+    if !status.is_success() {
+        log::debug!("translation failed with {status}: {body}");
+        return Err(CoreError::Api(crate::error::ApiError::from_response(
+            "translation",
+            status.as_u16(),
+            &body,
+        )));
+    }
+
+    let translations = match config.provider {
+        TranslationProvider::Deepl => parse_deepl(&body)?,
+        TranslationProvider::Azure => parse_azure(&body)?,
+    };
+    if translations.len() != texts.len() {
+        return Err(CoreError::api(format!(
+            "translation service returned {} results for {} texts",
+            translations.len(),
+            texts.len()
+        )));
+    }
+    Ok(translations)
+}
+
+/// Parse a `DeepL` `/v2/translate` response.
+fn parse_deepl(body: &str) -> Result<Vec<Translation>, CoreError> {
+    let value: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| CoreError::Serialization(format!("parsing DeepL response: {e}")))?;
+    Ok(value["translations"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .map(|t| Translation {
+                    detected_language: t["detected_source_language"]
+                        .as_str()
+                        .map(str::to_ascii_lowercase),
+                    text: t["text"].as_str().unwrap_or_default().to_string(),
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Parse an Azure Translator `/translate` response.
+fn parse_azure(body: &str) -> Result<Vec<Translation>, CoreError> {
+    let value: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| CoreError::Serialization(format!("parsing Azure response: {e}")))?;
+    Ok(value
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .map(|item| Translation {
+                    detected_language: item["detectedLanguage"]["language"]
+                        .as_str()
+                        .map(str::to_ascii_lowercase),
+                    text: item["translations"][0]["text"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn deepl_and_azure_responses_parse_alike() {
+        let deepl = parse_deepl(
+            r#"{"translations":[{"detected_source_language":"DE","text":"Good morning"}]}"#,
+        )
+        .unwrap();
+        let azure = parse_azure(
+            r#"[{"detectedLanguage":{"language":"de","score":1.0},
+                 "translations":[{"text":"Good morning","to":"en"}]}]"#,
+        )
+        .unwrap();
+        assert_eq!(deepl, azure);
+    }
+
+    #[test]
+    fn source_languages_match_ignoring_region() {
+        let translation = Translation {
+            detected_language: Some("de".to_string()),
+            text: "Good morning".to_string(),
+        };
+        assert!(translation.is_from("DE-AT") && !translation.is_from("en"));
+    }
+
+    #[tokio::test]
+    async fn cached_translations_are_reused() {
+        let dir = TempDir::new("translate").unwrap();
+        let cache = Cache::open(&dir.join("cache.db")).await.unwrap();
+        let raw = serde_json::json!({
+            "id": "1",
+            "messagetype": "Text",
+            "content": "Guten Morgen",
+            "composetime": "2026-01-01T09:00:00Z",
+        });
+        let msg = crate::cache::parse_message(
+            &raw.into(),
+            "19:x",
+            None,
+            &crate::MessagesConfig::default(),
+        )
+        .unwrap();
+        cache.upsert_message(&msg).await.unwrap();
+        let translation = Translation {
+            detected_language: Some("de".to_string()),
+            text: "Good morning".to_string(),
+        };
+        cache
+            .store_translation(&msg, "en", &translation)
+            .await
+            .unwrap();
+
+        // Served from the cache: no key is configured, so a request would fail.
+        let config = TranslationConfig {
+            api_key_env: "TMZ_TEST_NO_SUCH_KEY".to_string(),
+            ..TranslationConfig::default()
+        };
+        let found = translate_messages(&cache, &config, &[msg], "EN")
+            .await
+            .unwrap();
+        assert_eq!(found["1"].text, "Good morning");
+    }
+}
@@ -1,24 +1,53 @@
-//! MCP server for rust-workspace.
+//! MCP server for tmz.
+//!
+//! Exposes the local Teams cache (list, read, search) and, when enabled via
+//! `[mcp] allow_send = true`, message sending. Tools can be switched off
+//! individually under `[mcp.tools]`, and every invocation is recorded in
+//! the audit log (`audit.jsonl`) as `tool.<name>`.
+//!
+//! The server speaks MCP over stdio by default. With `--listen ADDR` it
+//! serves the streamable HTTP transport at `/mcp` instead, so several
+//! clients can share one long-running instance; every HTTP request must
+//! carry `Authorization: Bearer <token>`.
 
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use axum::extract::{Request, State};
+use axum::http::{StatusCode, header::AUTHORIZATION};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
 use clap::{Args, Parser};
 use rmcp::{
-    ErrorData as McpError, ServerHandler, ServiceExt,
-    handler::server::tool::ToolRouter,
+    ErrorData as McpError, RoleServer, ServerHandler, ServiceExt,
+    handler::server::tool::{ToolCallContext, ToolRouter},
     handler::server::wrapper::Parameters,
-    model::{CallToolResult, Content, ServerCapabilities, ServerInfo},
+    model::{
+        CallToolRequestParams, CallToolResult, Content, ListToolsResult, PaginatedRequestParams,
+        ServerCapabilities, ServerInfo,
+    },
     schemars::JsonSchema,
     serde::{Deserialize, Serialize},
-    tool, tool_handler, tool_router,
+    service::RequestContext,
+    tool, tool_router,
     transport::io::stdio,
+    transport::streamable_http_server::{
+        StreamableHttpServerConfig, StreamableHttpService, session::local::LocalSessionManager,
+    },
 };
 
 use rmcp::schemars;
 
-use tmz_core::{AppConfig, AppPaths};
+use tmz_core::audit::AuditLog;
+use tmz_core::cache::{OutgoingKind, TargetMatch};
+use tmz_core::markup::BodyFormat;
+use tmz_core::secrets;
+use tmz_core::{AppConfig, AppPaths, Cache};
+
+/// File name of the generated HTTP bearer token inside the state directory.
+const HTTP_TOKEN_FILE: &str = "mcp-token";
 
 fn main() -> anyhow::Result<()> {
     try_main()
@@ -29,8 +58,21 @@ async fn try_main() -> Result<()> {
     let cli = Cli::parse();
     let paths = AppPaths::discover(cli.common.config.as_deref())?;
     let config = AppConfig::load(&paths, false)?;
+    tmz_core::teams::endpoints::configure(&config.endpoints);
+    tmz_core::network::configure(&config.network)?;
+    tmz_core::retry::configure(&config.runtime.retries);
+    tmz_core::ratelimit::configure(&config.runtime.rate_limit);
+    if config.logging.trace_http {
+        tmz_core::trace::enable(&paths.state_dir);
+    }
+
+    if let Some(addr) = cli.listen {
+        let token = http_token(cli.token, &config, &paths)?;
+        let server = McpServer::new(config, paths);
+        return serve_http(server, addr, token).await;
+    }
 
-    let server = McpServer::new(config);
+    let server = McpServer::new(config, paths);
     let transport = stdio();
 
     let service = server
@@ -44,10 +86,80 @@ async fn try_main() -> Result<()> {
 }
 
 #[derive(Debug, Parser)]
-#[command(author, version, about = "MCP server for rust-workspace")]
+#[command(author, version, about = "MCP server for tmz")]
 struct Cli {
     #[command(flatten)]
     common: CommonOpts,
+
+    /// Serve the streamable HTTP transport on this address instead of stdio
+    #[arg(long, value_name = "ADDR")]
+    listen: Option<SocketAddr>,
+
+    /// Bearer token for the HTTP transport (default: `[mcp] http_token`, or a
+    /// generated token stored in the state directory)
+    #[arg(long, env = "TMZ_MCP_TOKEN", value_name = "TOKEN", requires = "listen")]
+    token: Option<String>,
+}
+
+/// Serve MCP over streamable HTTP at `/mcp` until Ctrl-C.
+async fn serve_http(server: McpServer, addr: SocketAddr, token: String) -> Result<()> {
+    let http_config = StreamableHttpServerConfig::default();
+    let cancel = http_config.cancellation_token.clone();
+    let service = StreamableHttpService::new(
+        move || Ok(server.clone()),
+        Arc::new(LocalSessionManager::default()),
+        http_config,
+    );
+
+    let token: Arc<str> = Arc::from(token);
+    let router = axum::Router::new()
+        .nest_service("/mcp", service)
+        .layer(middleware::from_fn_with_state(token, require_token));
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("binding {addr}"))?;
+    eprintln!("tmz-mcp listening on http://{addr}/mcp");
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            let _ = tokio::signal::ctrl_c().await;
+            cancel.cancel();
+        })
+        .await
+        .context("serving MCP over HTTP")
+}
+
+/// Reject HTTP requests without the expected bearer token.
+async fn require_token(State(token): State<Arc<str>>, req: Request, next: Next) -> Response {
+    let authorized = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|given| secrets::constant_time_eq(given, &token));
+
+    if authorized {
+        next.run(req).await
+    } else {
+        (StatusCode::UNAUTHORIZED, "missing or invalid bearer token").into_response()
+    }
+}
+
+/// Pick the HTTP bearer token: flag/env, then config, then the persisted
+/// generated token (created on first use).
+fn http_token(flag: Option<String>, config: &AppConfig, paths: &AppPaths) -> Result<String> {
+    if let Some(token) = flag.or_else(|| config.mcp.http_token.clone()) {
+        return Ok(token);
+    }
+
+    let path = paths.state_dir.join(HTTP_TOKEN_FILE);
+    let (token, generated) = secrets::persisted_token(&path)
+        .with_context(|| format!("creating token in {}", path.display()))?;
+    if generated {
+        eprintln!("Generated MCP HTTP token in {}", path.display());
+    }
+    Ok(token)
 }
 
 #[derive(Debug, Clone, Args)]
@@ -64,19 +176,186 @@ struct EchoParams {
     message: String,
 }
 
+/// Parameters for the `list_chats` tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct ListChatsParams {
+    /// Maximum number of chats to return (default: 50)
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+/// Parameters for the `get_messages` tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct GetMessagesParams {
+    /// Chat name, people alias, or conversation ID
+    chat: String,
+    /// Maximum number of messages to return (default: 20)
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+/// Parameters for the `search_messages` tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct SearchMessagesParams {
+    /// Full-text search query
+    query: String,
+    /// Restrict the search to one chat (name, alias, or conversation ID)
+    #[serde(default)]
+    chat: Option<String>,
+    /// Maximum number of results (default: 20)
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+/// Parameters for the `send_message` tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct SendMessageParams {
+    /// Chat name, people alias, or conversation ID
+    chat: String,
+    /// Message text
+    message: String,
+    /// `text` (default, escaped), `markdown`, or `html`
+    #[serde(default)]
+    format: BodyFormat,
+    /// Notify nobody: mentions and `@channel` become plain text
+    #[serde(default)]
+    silent: bool,
+}
+
 #[derive(Clone)]
 struct McpServer {
     config: Arc<AppConfig>,
+    paths: Arc<AppPaths>,
     tool_router: ToolRouter<Self>,
 }
 
 impl McpServer {
-    fn new(config: AppConfig) -> Self {
+    fn new(config: AppConfig, paths: AppPaths) -> Self {
+        let mut tool_router = Self::tool_router();
+        let disabled: Vec<String> = tool_router
+            .list_all()
+            .into_iter()
+            .map(|t| t.name.to_string())
+            .filter(|name| !config.mcp.tool_enabled(name))
+            .collect();
+        for name in &disabled {
+            log::info!("MCP tool '{name}' disabled by config");
+            tool_router.remove_route(name);
+        }
+
         Self {
             config: Arc::new(config),
-            tool_router: Self::tool_router(),
+            paths: Arc::new(paths),
+            tool_router,
+        }
+    }
+
+    /// Open the cache read-only, so reading tools never compete with the
+    /// daemon for the write lock.
+    async fn open_cache(&self) -> Result<Cache, McpError> {
+        Cache::open_read_only(&self.paths.data_dir.join("cache.db"))
+            .await
+            .map_err(|e| {
+                McpError::internal_error(
+                    format!("cache not available (run 'tmz sync' first): {e}"),
+                    None,
+                )
+            })
+    }
+
+    /// Open the cache read-write, for tools that record something in it.
+    async fn open_writable_cache(&self) -> Result<Cache, McpError> {
+        Cache::open(&self.paths.data_dir.join("cache.db"))
+            .await
+            .map(|c| c.with_raw_json_compression(self.config.cache.compress_raw_json))
+            .map_err(|e| McpError::internal_error(format!("opening cache: {e}"), None))
+    }
+
+    /// Resolve a chat name, alias, or conversation ID against the cache.
+    async fn resolve_chat(&self, cache: &Cache, chat: &str) -> Result<String, McpError> {
+        let found = cache
+            .resolve_target(&self.config, chat)
+            .await
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        match found {
+            TargetMatch::Found(id) => Ok(id),
+            TargetMatch::NotFound => Err(McpError::invalid_params(
+                format!("no cached chat matches '{chat}'"),
+                None,
+            )),
+            TargetMatch::Ambiguous(matches) => {
+                let names: Vec<&str> = matches.iter().map(|c| c.display_name.as_str()).collect();
+                Err(McpError::invalid_params(
+                    format!("'{chat}' is ambiguous, matches: {}", names.join(", ")),
+                    None,
+                ))
+            }
         }
     }
+
+    /// Apply `[sending] off_hours`. There is nobody to confirm with, so
+    /// `confirm` refuses like `block`; `warn` returns the warning.
+    async fn check_off_hours(
+        &self,
+        cache: &Cache,
+        conv_id: &str,
+    ) -> Result<Option<String>, McpError> {
+        let off = match tmz_core::hours::check_recipient(&self.config.sending, conv_id).await {
+            Ok(Some(off)) => off,
+            Ok(None) => return Ok(None),
+            Err(e @ tmz_core::CoreError::Config(_)) => {
+                return Err(McpError::internal_error(e.to_string(), None));
+            }
+            Err(e) => {
+                log::debug!("checking working hours: {e}");
+                return Ok(None);
+            }
+        };
+        let who = cache
+            .find_conversation(conv_id)
+            .await
+            .ok()
+            .and_then(|convs| convs.first().map(|c| c.display_name.clone()))
+            .unwrap_or_else(|| "them".to_string());
+        let reason = off.describe(&who);
+        if self.config.sending.off_hours == tmz_core::OffHoursAction::Warn {
+            return Ok(Some(reason));
+        }
+        Err(McpError::invalid_request(
+            format!(
+                "not sent: {reason}; ask the user to send it or schedule it with tmz msg --schedule"
+            ),
+            None,
+        ))
+    }
+
+    /// Record one tool invocation in the audit log as `tool.<name>`, with
+    /// the chat it named as the target. Other arguments, such as message
+    /// text, are not recorded.
+    fn audit(&self, tool: &str, chat: Option<&str>, result: &Result<CallToolResult, McpError>) {
+        if !self.config.mcp.audit_log {
+            return;
+        }
+
+        let (outcome, detail) = match result {
+            Ok(r) if r.is_error == Some(true) => ("error", None),
+            Ok(_) => ("ok", None),
+            Err(e) if !self.tool_router.has_route(tool) => ("denied", Some(e.message.to_string())),
+            Err(e) => ("error", Some(e.message.to_string())),
+        };
+        AuditLog::new(&self.paths.state_dir, &self.config.audit, "mcp").record_with(
+            &format!("tool.{tool}"),
+            chat,
+            outcome,
+            detail.as_deref(),
+        );
+    }
+}
+
+fn json_result<T: Serialize>(value: &T) -> Result<CallToolResult, McpError> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
 }
 
 #[tool_router]
@@ -108,15 +387,304 @@ impl McpServer {
             serde_json::to_string_pretty(&self.config.runtime).unwrap_or_else(|_| "{}".to_string());
         Ok(CallToolResult::success(vec![Content::text(json)]))
     }
+
+    /// List cached chats
+    #[tool(description = "Lists cached Teams chats, most recently active first")]
+    async fn list_chats(
+        &self,
+        Parameters(params): Parameters<ListChatsParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let cache = self.open_cache().await?;
+        let convs = cache
+            .list_conversations(params.limit.unwrap_or(50))
+            .await
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        let chats: Vec<serde_json::Value> = convs
+            .iter()
+            .map(|c| {
+                serde_json::json!({
+                    "id": c.id,
+                    "name": c.display_name,
+                    "last_activity": c.last_activity,
+                    "last_message_from": c.last_message_from,
+                    "last_message_preview": c.last_message_preview,
+                })
+            })
+            .collect();
+        json_result(&chats)
+    }
+
+    /// Get recent messages from a chat
+    #[tool(description = "Returns recent cached messages from a chat, oldest first")]
+    async fn get_messages(
+        &self,
+        Parameters(params): Parameters<GetMessagesParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let cache = self.open_cache().await?;
+        let conv_id = self.resolve_chat(&cache, &params.chat).await?;
+        let msgs = cache
+            .get_messages(&conv_id, params.limit.unwrap_or(20))
+            .await
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        let msgs: Vec<serde_json::Value> = msgs
+            .iter()
+            .map(|m| {
+                serde_json::json!({
+                    "id": m.id,
+                    "from": m.from_display_name,
+                    "time": m.compose_time,
+                    "content": m.content,
+                    "is_from_me": m.is_from_me,
+                })
+            })
+            .collect();
+        json_result(&msgs)
+    }
+
+    /// Search cached messages
+    #[tool(description = "Full-text search across cached messages, optionally within one chat")]
+    async fn search_messages(
+        &self,
+        Parameters(params): Parameters<SearchMessagesParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let cache = self.open_cache().await?;
+        let limit = params.limit.unwrap_or(20);
+        let results = match params.chat {
+            Some(ref chat) => {
+                let conv_id = self.resolve_chat(&cache, chat).await?;
+                cache
+                    .search_in_conversation(&params.query, &conv_id, limit)
+                    .await
+            }
+            None => cache.search(&params.query, limit).await,
+        }
+        .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        let hits: Vec<serde_json::Value> = results
+            .iter()
+            .map(|r| {
+                serde_json::json!({
+                    "chat": r.conversation_name,
+                    "conversation_id": r.message.conversation_id,
+                    "from": r.message.from_display_name,
+                    "time": r.message.compose_time,
+                    "content": r.message.content,
+                })
+            })
+            .collect();
+        json_result(&hits)
+    }
+
+    /// Send a message to a chat
+    #[tool(description = "Sends a message to a Teams chat (requires [mcp] allow_send = true)")]
+    async fn send_message(
+        &self,
+        Parameters(params): Parameters<SendMessageParams>,
+    ) -> Result<CallToolResult, McpError> {
+        if !self.config.mcp.allow_send {
+            return Err(McpError::invalid_request(
+                "sending is disabled ([mcp] allow_send = false)",
+                None,
+            ));
+        }
+        self.config
+            .security
+            .ensure_writable("sending messages")
+            .map_err(|e| McpError::invalid_request(e.to_string(), None))?;
+        tmz_core::composer::check_outbound(&self.config.composer, &params.message)
+            .map_err(|e| McpError::invalid_request(e.to_string(), None))?;
+        let mut content = params.format.to_html(&params.message);
+        if params.silent || self.config.sending.silent {
+            content = tmz_core::markup::silence(&content);
+        }
+        let max = self.config.sending.max_message_bytes;
+        if content.len() > max {
+            return Err(McpError::invalid_request(
+                format!(
+                    "message is {} bytes, over the {max} byte limit ([sending] max_message_bytes); send it as shorter messages",
+                    content.len()
+                ),
+                None,
+            ));
+        }
+        // The outbox records the delivery state of the send.
+        let cache = self.open_writable_cache().await?;
+        let conv_id = self.resolve_chat(&cache, &params.chat).await?;
+        let warning = self.check_off_hours(&cache, &conv_id).await?;
+        tmz_core::outbox::track(
+            Some(&cache),
+            &conv_id,
+            &content,
+            OutgoingKind::Text,
+            tmz_core::engine::send_message(&conv_id, &content),
+        )
+        .await
+        .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Sent to {conv_id}{}",
+            warning.map_or_else(String::new, |w| format!(" (note: {w})"))
+        ))]))
+    }
 }
 
-#[tool_handler]
 impl ServerHandler for McpServer {
     fn get_info(&self) -> ServerInfo {
+        let mode = if self.config.mcp.allow_send {
+            "Sending is enabled."
+        } else {
+            "Read-only: sending is disabled."
+        };
         ServerInfo {
-            instructions: Some("MCP server for rust-workspace template".to_string()),
+            instructions: Some(format!(
+                "Microsoft Teams chats from the local tmz cache. {mode}"
+            )),
             capabilities: ServerCapabilities::builder().enable_tools().build(),
             ..Default::default()
         }
     }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let tool = request.name.to_string();
+        let chat = request
+            .arguments
+            .as_ref()
+            .and_then(|args| args.get("chat"))
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_string);
+        let tcc = ToolCallContext::new(self, request, context);
+        let result = self.tool_router.call(tcc).await;
+        self.audit(&tool, chat.as_deref(), &result);
+        result
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult {
+            tools: self.tool_router.list_all(),
+            meta: None,
+            next_cursor: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tmz_core::audit::AuditRecord;
+    use tmz_core::testutil::TempDir;
+
+    fn paths(dir: &TempDir) -> AppPaths {
+        AppPaths {
+            config_file: dir.join("config.toml"),
+            system_config_file: dir.join("system.toml"),
+            project_config_file: None,
+            data_dir: dir.to_path_buf(),
+            state_dir: dir.to_path_buf(),
+            browser_profile_dir: dir.join("browser-profile"),
+        }
+    }
+
+    fn tools(server: &McpServer) -> Vec<String> {
+        let mut names: Vec<String> = server
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(|t| t.name.to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn send_message_is_hidden_unless_sending_is_allowed() {
+        let dir = TempDir::new("mcp").unwrap();
+        let server = McpServer::new(AppConfig::default(), paths(&dir));
+        assert!(!server.tool_router.has_route("send_message"));
+        assert!(!tools(&server).contains(&"send_message".to_string()));
+        assert!(server.tool_router.has_route("list_chats"));
+    }
+
+    #[test]
+    fn allow_send_exposes_send_message() {
+        let dir = TempDir::new("mcp").unwrap();
+        let mut config = AppConfig::default();
+        config.mcp.allow_send = true;
+        let server = McpServer::new(config, paths(&dir));
+        assert!(server.tool_router.has_route("send_message"));
+    }
+
+    #[test]
+    fn tools_switched_off_in_config_are_removed() {
+        let dir = TempDir::new("mcp").unwrap();
+        let mut config = AppConfig::default();
+        config.mcp.allow_send = true;
+        config
+            .mcp
+            .tools
+            .insert("search_messages".to_string(), false);
+        config.mcp.tools.insert("echo".to_string(), true);
+        let server = McpServer::new(config, paths(&dir));
+        assert!(!server.tool_router.has_route("search_messages"));
+        assert!(server.tool_router.has_route("echo"));
+        assert!(server.tool_router.has_route("send_message"));
+    }
+
+    #[test]
+    fn config_cannot_enable_send_message_without_allow_send() {
+        let dir = TempDir::new("mcp").unwrap();
+        let mut config = AppConfig::default();
+        config.mcp.tools.insert("send_message".to_string(), true);
+        let server = McpServer::new(config, paths(&dir));
+        assert!(!server.tool_router.has_route("send_message"));
+    }
+
+    #[test]
+    fn calls_to_removed_tools_are_audited_as_denied() {
+        let dir = TempDir::new("mcp").unwrap();
+        let server = McpServer::new(AppConfig::default(), paths(&dir));
+        let refused = Err(McpError::invalid_params("tool not found", None));
+        server.audit("send_message", Some("19:a@thread.v2"), &refused);
+        server.audit("get_profile", None, &Ok(CallToolResult::success(vec![])));
+
+        let log = AuditLog::new(&dir, &AppConfig::default().audit, "mcp");
+        let records: Vec<AuditRecord> = std::fs::read_to_string(log.path())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].action, "tool.send_message");
+        assert_eq!(records[0].target.as_deref(), Some("19:a@thread.v2"));
+        assert_eq!(records[0].outcome, "denied");
+        assert_eq!(records[1].action, "tool.get_profile");
+        assert_eq!(records[1].outcome, "ok");
+    }
+
+    #[test]
+    fn audit_log_can_be_switched_off() {
+        let dir = TempDir::new("mcp").unwrap();
+        let mut config = AppConfig::default();
+        config.mcp.audit_log = false;
+        let audit = config.audit.clone();
+        let server = McpServer::new(config, paths(&dir));
+        server.audit("echo", None, &Ok(CallToolResult::success(vec![])));
+        assert!(!AuditLog::new(&dir, &audit, "mcp").path().exists());
+    }
+
+    #[tokio::test]
+    async fn reading_tools_ask_for_a_sync_without_a_cache() {
+        let dir = TempDir::new("mcp").unwrap();
+        let server = McpServer::new(AppConfig::default(), paths(&dir));
+        let err = server
+            .list_chats(Parameters(ListChatsParams { limit: None }))
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("tmz sync"), "{}", err.message);
+    }
 }
@@ -1,17 +1,58 @@
 //! Application state and main loop.
 
 use crate::event::{self, Event};
+use crate::picker::FilePicker;
 use crate::ui;
 use anyhow::Result;
 use crossterm::{
-    event::KeyEventKind,
+    event::{KeyCode, KeyEventKind, KeyModifiers},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::io::{Stdout, Write as _};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
 use std::time::{Duration, Instant};
-use tmz_core::{AppConfig, AppPaths, CachedConversation, CachedMessage};
+use tmz_core::cache::{OutboxItem, OutgoingKind, SavedItem};
+use tmz_core::health::Problem;
+use tmz_core::restrictions::Action;
+use tmz_core::scripting::{ActionContext, ScriptAction, Scripts};
+use tmz_core::{
+    AppConfig, AppPaths, CachedConversation, CachedMessage, CoreError, Density, EngineEvent,
+    SavedSearch, SearchResult, SearchScope,
+};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::task::JoinHandle;
+
+/// Columns in the reaction picker grid.
+pub const REACTION_COLUMNS: usize = 4;
+
+/// Messages the timeline tab loads at a time.
+pub const TIMELINE_PAGE: usize = 100;
+
+/// Yanked messages kept, in registers `"1` to `"9` and `"0`.
+pub const YANK_HISTORY: usize = 10;
+
+/// A message copied with `y`.
+#[derive(Debug, Clone)]
+pub struct Yank {
+    pub from: String,
+    pub text: String,
+}
+
+/// Register name for yank history index `index` (`1`-`9`, then `0`).
+pub fn register_name(index: usize) -> char {
+    char::from_digit(u32::try_from((index + 1) % 10).unwrap_or(0), 10).unwrap_or('?')
+}
+
+/// Yank history index of register `name`.
+fn register_index(name: char) -> Option<usize> {
+    let digit = usize::try_from(name.to_digit(10)?).ok()?;
+    Some((digit + 9) % 10)
+}
 
 // ─── Focus & Mode ────────────────────────────────────────────────────
 
@@ -32,6 +73,28 @@ pub enum Mode {
     Search,
     Help,
     ChatSearch,
+    Inspect,
+    React,
+    Yanks,
+    Scripts,
+    Switcher,
+    Attach,
+}
+
+/// An operation that failed because the login expired, retried after
+/// logging in from the TUI with `A`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthRetry {
+    Sync,
+    Send {
+        conversation_id: String,
+        text: String,
+    },
+    React {
+        conversation_id: String,
+        message_id: String,
+        reaction: String,
+    },
 }
 
 /// Left panel tab.
@@ -40,6 +103,8 @@ pub enum SideTab {
     Chats,
     Teams,
     Channels,
+    Saved,
+    Timeline,
 }
 
 // ─── App State ───────────────────────────────────────────────────────
@@ -56,6 +121,18 @@ pub struct App {
     pub filtered_conversations: Vec<usize>,
     pub chat_selected: usize,
     pub chat_search: String,
+    // Tags per conversation ID, from `tmz chats tag` and `[tags]`
+    pub conversation_tags: BTreeMap<String, Vec<String>>,
+    // Messages saved with `s` or `tmz save`, listed by the saved tab;
+    // `filtered_saved` runs parallel to `filtered_conversations` there
+    pub saved_items: Vec<SavedItem>,
+    pub filtered_saved: Vec<usize>,
+    // Newest messages of all chats for the timeline tab, newest first, and
+    // whether older pages are left; `filtered_timeline` works like
+    // `filtered_saved`
+    pub timeline: Vec<SearchResult>,
+    pub filtered_timeline: Vec<usize>,
+    pub timeline_more: bool,
 
     // Messages
     pub messages: Vec<CachedMessage>,
@@ -65,12 +142,55 @@ pub struct App {
     pub input: String,
     pub cursor_pos: usize,
 
+    // Files queued with `Ctrl+A`, uploaded after the message is sent, and
+    // the open file picker
+    pub attachments: Vec<PathBuf>,
+    pub picker: Option<FilePicker>,
+
     // In-chat search
     pub search_query: String,
     pub search_results: Vec<usize>,
 
     // Files panel
     pub show_files: bool,
+    pub show_legend: bool,
+
+    // Show messages translated into `[ai.translation] target_language`
+    pub translate: bool,
+
+    // Raw message inspector: index into `messages` and scroll offset
+    pub inspect_index: usize,
+    pub inspect_scroll: usize,
+
+    // Reaction picker: index into `messages`, selected choice, and the
+    // reactions used this session (most recent first)
+    pub react_index: usize,
+    pub react_choice: usize,
+    pub recent_reactions: Vec<String>,
+
+    // Yank history (newest first), the picker selection, and a pending
+    // `"` register prefix (`Some('"')` while waiting for the register name)
+    pub yanks: Vec<Yank>,
+    pub yank_choice: usize,
+
+    // User script actions (`!`), loaded from `script_path` when the picker
+    // opens so edits apply without a restart
+    pub script_path: PathBuf,
+    pub scripts: Option<Scripts>,
+    pub script_actions: Vec<ScriptAction>,
+    pub script_choice: usize,
+    pub pending_register: Option<char>,
+
+    // Quick switcher (`Ctrl+K`): indexes into `conversations` ranked by
+    // frecency then activity, those matching the query, and the selection
+    pub switcher_query: String,
+    pub switcher_ranked: Vec<usize>,
+    pub switcher_matches: Vec<usize>,
+    pub switcher_selected: usize,
+
+    // Saved searches with their match counts (`None` if the query failed)
+    pub saved_counts: Vec<(String, Option<i64>)>,
+    pub unread_mentions: i64,
 
     // Sync state
     pub last_sync: Option<Instant>,
@@ -78,8 +198,48 @@ pub struct App {
     pub token_expires_mins: Option<i64>,
     pub status_msg: String,
 
+    // Parsed `[statusbar] format`
+    pub status_format: tmz_core::statusbar::StatusFormat,
+
     // Cache
     pub cache: Option<tmz_core::Cache>,
+
+    // Composer spell checker (`[composer] spell_check`)
+    pub spell: Option<tmz_core::composer::SpellChecker>,
+
+    // Outbox ids of messages still inside the undo window (`Ctrl+Z`)
+    pub pending_sends: Vec<i64>,
+
+    // Recent outgoing messages of the open chat with their delivery state
+    pub outgoing: Vec<OutboxItem>,
+
+    // Conversation whose out-of-hours warning was acknowledged; the next
+    // Enter sends anyway
+    pub off_hours_ok: Option<String>,
+
+    // Presence, focus time, and working hours of the open 1:1 chat's other
+    // person: conversation id and summary
+    pub availability: Option<(String, String)>,
+
+    // Audit log of sends and cancellations
+    pub audit: Option<tmz_core::audit::AuditLog>,
+
+    // Time spent in the open conversation (`[time_tracking]`)
+    pub time_tracker: Option<tmz_core::timetrack::TimeTracker>,
+
+    // Problems found by the last health check, shown as banners, with the
+    // running check and the running fix (`S` starts the daemon)
+    pub health: Vec<Problem>,
+    pub health_check: Option<JoinHandle<tmz_core::health::Facts>>,
+    pub health_fix: Option<JoinHandle<String>>,
+
+    // Login requested with `A`, run by the main loop outside the alternate
+    // screen, and the operation to retry after it
+    pub login_requested: bool,
+    pub auth_retry: Option<AuthRetry>,
+
+    // Events of messages pushed by the chat service (`[sync] live`)
+    pub live_events: Option<UnboundedReceiver<EngineEvent>>,
 }
 
 impl App {
@@ -95,6 +255,12 @@ impl App {
             filtered_conversations: Vec::new(),
             chat_selected: 0,
             chat_search: String::new(),
+            conversation_tags: BTreeMap::new(),
+            saved_items: Vec::new(),
+            filtered_saved: Vec::new(),
+            timeline: Vec::new(),
+            filtered_timeline: Vec::new(),
+            timeline_more: false,
 
             messages: Vec::new(),
             msg_scroll: 0,
@@ -102,29 +268,239 @@ impl App {
             input: String::new(),
             cursor_pos: 0,
 
+            attachments: Vec::new(),
+            picker: None,
+
             search_query: String::new(),
             search_results: Vec::new(),
 
             show_files: false,
+            show_legend: false,
+
+            translate: false,
+
+            inspect_index: 0,
+            inspect_scroll: 0,
+
+            react_index: 0,
+            react_choice: 0,
+            recent_reactions: Vec::new(),
+
+            yanks: Vec::new(),
+            yank_choice: 0,
+
+            script_path: PathBuf::new(),
+            scripts: None,
+            script_actions: Vec::new(),
+            script_choice: 0,
+            pending_register: None,
+
+            switcher_query: String::new(),
+            switcher_ranked: Vec::new(),
+            switcher_matches: Vec::new(),
+            switcher_selected: 0,
+
+            saved_counts: Vec::new(),
+            unread_mentions: 0,
 
             last_sync: None,
             syncing: false,
             token_expires_mins: None,
             status_msg: String::new(),
 
+            status_format: tmz_core::statusbar::StatusFormat::empty(),
+
             cache: None,
+
+            spell: None,
+
+            pending_sends: Vec::new(),
+            outgoing: Vec::new(),
+
+            off_hours_ok: None,
+
+            availability: None,
+
+            audit: None,
+
+            time_tracker: None,
+
+            health: Vec::new(),
+            health_check: None,
+            health_fix: None,
+
+            login_requested: false,
+            auth_retry: None,
+
+            live_events: None,
         }
     }
 
+    /// Reaction keys offered by the picker.
+    pub fn reaction_choices(&self) -> Vec<String> {
+        tmz_core::reactions::picker_keys(&self.recent_reactions, &self.config.display.reactions)
+    }
+
+    /// Open the reaction picker on `messages[index]`.
+    pub const fn open_reactions(&mut self, index: usize) {
+        self.react_index = index;
+        self.react_choice = 0;
+        self.mode = Mode::React;
+    }
+
+    /// Copy `messages[index]` into yank register `"1`, shifting older yanks.
+    pub fn yank(&mut self, index: usize) {
+        let Some(msg) = self.messages.get(index) else {
+            return;
+        };
+        self.yanks.insert(
+            0,
+            Yank {
+                from: msg.from_display_name.clone(),
+                text: msg.content.clone(),
+            },
+        );
+        self.yanks.truncate(YANK_HISTORY);
+        self.status_msg = format!("Yanked message from {} into \"1", msg.from_display_name);
+    }
+
+    /// Insert yank `index` at the cursor and switch to insert mode.
+    pub fn paste_yank(&mut self, index: usize) {
+        let Some(yank) = self.yanks.get(index) else {
+            self.status_msg = format!("Register \"{} is empty", register_name(index));
+            return;
+        };
+        self.input.insert_str(self.cursor_pos, &yank.text);
+        self.cursor_pos += yank.text.len();
+        self.status_msg = format!("Pasted \"{} from {}", register_name(index), yank.from);
+        self.mode = Mode::Insert;
+        self.focus = Focus::Input;
+    }
+
+    /// Narrow the quick switcher to conversations matching its query,
+    /// keeping the frecency order.
+    pub fn filter_switcher(&mut self) {
+        let query = self.switcher_query.to_lowercase();
+        self.switcher_matches = self
+            .switcher_ranked
+            .iter()
+            .copied()
+            .filter(|&i| {
+                let c = &self.conversations[i];
+                query.is_empty()
+                    || c.display_name.to_lowercase().contains(&query)
+                    || c.member_names.to_lowercase().contains(&query)
+            })
+            .collect();
+        self.switcher_selected = 0;
+    }
+
     /// Get the currently selected conversation.
     pub fn selected_conversation(&self) -> Option<&CachedConversation> {
         let idx = *self.filtered_conversations.get(self.chat_selected)?;
         self.conversations.get(idx)
     }
 
+    /// Restrictions of the loaded conversation `conv_id`.
+    pub fn restrictions(&self, conv_id: &str) -> tmz_core::restrictions::Restrictions {
+        self.conversations
+            .iter()
+            .find(|c| c.id == conv_id)
+            .map(|c| c.restrictions)
+            .unwrap_or_default()
+    }
+
+    /// The saved item selected in the saved tab.
+    pub fn selected_saved(&self) -> Option<&SavedItem> {
+        if self.side_tab != SideTab::Saved {
+            return None;
+        }
+        let idx = *self.filtered_saved.get(self.chat_selected)?;
+        self.saved_items.get(idx)
+    }
+
+    /// Filter the saved tab by the search string, pairing each saved item
+    /// with its conversation.
+    fn filter_saved(&mut self, query: &str) {
+        let (saved, conversations) = self
+            .saved_items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                query.is_empty()
+                    || [
+                        &item.content,
+                        &item.note,
+                        &item.from_display_name,
+                        &item.conversation_name,
+                    ]
+                    .iter()
+                    .any(|text| text.to_lowercase().contains(query))
+            })
+            .filter_map(|(i, item)| {
+                let conv = self
+                    .conversations
+                    .iter()
+                    .position(|c| c.id == item.conversation_id)?;
+                Some((i, conv))
+            })
+            .unzip();
+        self.filtered_saved = saved;
+        self.filtered_conversations = conversations;
+    }
+
+    /// The message selected in the timeline tab.
+    pub fn selected_timeline(&self) -> Option<&SearchResult> {
+        if self.side_tab != SideTab::Timeline {
+            return None;
+        }
+        let idx = *self.filtered_timeline.get(self.chat_selected)?;
+        self.timeline.get(idx)
+    }
+
+    /// Filter the timeline tab by the search string, pairing each message
+    /// with its conversation.
+    fn filter_timeline(&mut self, query: &str) {
+        let (timeline, conversations) = self
+            .timeline
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| {
+                query.is_empty()
+                    || [
+                        &r.message.content,
+                        &r.message.from_display_name,
+                        &r.conversation_name,
+                    ]
+                    .iter()
+                    .any(|text| text.to_lowercase().contains(query))
+            })
+            .filter_map(|(i, r)| {
+                let conv = self
+                    .conversations
+                    .iter()
+                    .position(|c| c.id == r.message.conversation_id)?;
+                Some((i, conv))
+            })
+            .unzip();
+        self.filtered_timeline = timeline;
+        self.filtered_conversations = conversations;
+    }
+
     /// Filter conversations by the current tab and search string.
     pub fn filter_conversations(&mut self) {
         let query = self.chat_search.to_lowercase();
+        if matches!(self.side_tab, SideTab::Saved | SideTab::Timeline) {
+            if self.side_tab == SideTab::Saved {
+                self.filter_saved(&query);
+            } else {
+                self.filter_timeline(&query);
+            }
+            self.chat_selected = self
+                .chat_selected
+                .min(self.filtered_conversations.len().saturating_sub(1));
+            return;
+        }
         self.filtered_conversations = self
             .conversations
             .iter()
@@ -138,14 +514,19 @@ impl App {
                     ),
                     SideTab::Teams => c.thread_type == "space",
                     SideTab::Channels => c.thread_type == "topic",
+                    SideTab::Saved | SideTab::Timeline => false,
                 };
                 if !type_match {
                     return false;
                 }
 
-                // Filter by search
+                // Filter by search; `#tag` matches tags
                 if query.is_empty() {
                     true
+                } else if let Some(tag) = query.strip_prefix('#') {
+                    self.conversation_tags
+                        .get(&c.id)
+                        .is_some_and(|tags| tags.iter().any(|t| t.starts_with(tag)))
                 } else {
                     c.display_name.to_lowercase().contains(&query)
                         || c.member_names.to_lowercase().contains(&query)
@@ -212,9 +593,67 @@ impl App {
 
 // ─── Main loop ───────────────────────────────────────────────────────
 
-pub fn run(config_path: Option<&PathBuf>) -> Result<()> {
+/// Timings of the startup phases, for `--profile-startup`.
+struct StartupProfile {
+    started: Instant,
+    last: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl StartupProfile {
+    fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            last: now,
+            phases: Vec::new(),
+        }
+    }
+
+    /// Record the time since the previous phase ended as `phase`.
+    fn mark(&mut self, phase: &'static str) {
+        let now = Instant::now();
+        self.phases.push((phase, now - self.last));
+        self.last = now;
+    }
+
+    fn report(&self) -> String {
+        let line = |phase: &str, took: Duration| {
+            format!("  {phase:<20} {:>8.1} ms\n", took.as_secs_f64() * 1000.0)
+        };
+        let phases: String = self
+            .phases
+            .iter()
+            .map(|(phase, took)| line(phase, *took))
+            .collect();
+        format!(
+            "tmz-tui startup:\n{phases}{}",
+            line("total", self.last - self.started)
+        )
+    }
+}
+
+pub fn run(
+    config_path: Option<&PathBuf>,
+    profile: Option<&str>,
+    compact: bool,
+    profile_startup: bool,
+) -> Result<()> {
+    let mut startup = StartupProfile::new();
     let paths = AppPaths::discover(config_path.map(PathBuf::as_path))?;
-    let config = AppConfig::load(&paths, false)?;
+    let mut config = AppConfig::load_with_profile(&paths, false, profile)?;
+    if compact {
+        config.display.density = Density::Compact;
+    }
+    let paths = paths.apply_overrides(&config)?;
+    tmz_core::teams::endpoints::configure(&config.endpoints);
+    tmz_core::network::configure(&config.network)?;
+    tmz_core::retry::configure(&config.runtime.retries);
+    tmz_core::ratelimit::configure(&config.runtime.rate_limit);
+    if config.logging.trace_http {
+        tmz_core::trace::enable(&paths.state_dir);
+    }
+    startup.mark("config");
 
     // Set up terminal
     enable_raw_mode()?;
@@ -222,37 +661,34 @@ pub fn run(config_path: Option<&PathBuf>) -> Result<()> {
     execute!(stdout, EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
+    startup.mark("terminal");
 
     let mut app = App::new(config);
+    app.audit = Some(tmz_core::audit::AuditLog::new(
+        &paths.state_dir,
+        &app.config.audit,
+        "tui",
+    ));
+    app.script_path = script_path(&app.config.scripting, &paths);
+    start_time_tracking(&mut app);
 
-    // Load cache
     let rt = tokio::runtime::Runtime::new()?;
-    let cache_dir = tmz_core::default_data_dir()?;
-    let db_path = cache_dir.join("cache.db");
-    let cache = rt.block_on(tmz_core::Cache::open(&db_path))?;
-
-    // Initial load
-    app.conversations = rt.block_on(cache.list_conversations(500))?;
-    app.filter_conversations();
-
-    // Load messages for first conversation
-    if let Some(conv) = app.selected_conversation() {
-        let id = conv.id.clone();
-        app.messages = rt.block_on(cache.get_messages(&id, 200))?;
-        app.msg_scroll_bottom();
-    }
-
-    // Check token status
-    if let Ok(auth) = tmz_core::AuthManager::new()
-        && let Ok(tokens) = auth.get_tokens()
-    {
-        let remaining = tokens.expires_at - chrono::Utc::now().timestamp();
-        app.token_expires_mins = Some(remaining / 60);
-    }
-
-    app.cache = Some(cache);
-    app.last_sync = Some(Instant::now());
-    app.status_msg = format!("{} conversations loaded", app.conversations.len());
+    load_first_window(&mut app, &rt, terminal.size()?.height, &mut startup)?;
+    terminal.draw(|f| ui::draw(f, &app))?;
+    startup.mark("first frame");
+    let first_frame = startup.last - startup.started;
+    start_health_check(&mut app, &rt);
+    warm_up(&mut app, &rt, &mut startup);
+    start_live(&mut app, &rt);
+    log::info!(
+        "first frame after {first_frame:.0?}, ready after {:.0?}",
+        startup.last - startup.started
+    );
+
+    let files: Vec<PathBuf> = paths.config_layers().into_iter().map(|(_, f)| f).collect();
+    let mut watcher = tmz_core::reload::ConfigWatcher::new(&files)
+        .inspect_err(|e| app.status_msg = format!("Config changes need a restart: {e}"))
+        .ok();
 
     // Event loop
     let events = event::spawn_event_reader(Duration::from_millis(200));
@@ -264,29 +700,256 @@ pub fn run(config_path: Option<&PathBuf>) -> Result<()> {
             Event::Key(key) => {
                 if key.kind == KeyEventKind::Press {
                     handle_key(&mut app, key, &rt);
+                    track_time(&mut app, &rt, true);
+                }
+                if std::mem::take(&mut app.login_requested) {
+                    log_in(&mut terminal, &mut app, &rt, &events)?;
                 }
             }
             Event::Resize => {} // ratatui handles this
             Event::Tick => {
+                if watcher
+                    .as_mut()
+                    .is_some_and(tmz_core::reload::ConfigWatcher::poll)
+                {
+                    reload_config(&mut app, &paths, profile, &rt);
+                }
                 handle_tick(&mut app, &rt);
+                track_time(&mut app, &rt, false);
             }
         }
     }
+    stop_time_tracking(&mut app, &rt);
 
     // Restore terminal
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
+    if profile_startup {
+        eprint!("{}", startup.report());
+    }
+
+    Ok(())
+}
+
+/// Open the cache and load what the first frame shows: the chat list rows
+/// that fit on screen, without member names and raw JSON, and the first
+/// chat's messages without raw JSON.
+fn load_first_window(
+    app: &mut App,
+    rt: &tokio::runtime::Runtime,
+    rows: u16,
+    startup: &mut StartupProfile,
+) -> Result<()> {
+    let db_path = tmz_core::default_data_dir()?.join("cache.db");
+    let cache = rt
+        .block_on(tmz_core::Cache::open(&db_path))?
+        .with_raw_json_compression(app.config.cache.compress_raw_json);
+    startup.mark("open cache");
+
+    app.conversations = rt.block_on(cache.list_conversation_summaries(i64::from(rows)))?;
+    app.conversation_tags = rt
+        .block_on(cache.conversation_tags(&app.config.shared_tags()))
+        .unwrap_or_default();
+    app.filter_conversations();
+    startup.mark("chat list window");
+
+    if let Some(conv) = app.selected_conversation() {
+        let id = conv.id.clone();
+        app.messages = rt.block_on(cache.get_messages_without_raw(&id, 200))?;
+        app.msg_scroll_bottom();
+    }
+    startup.mark("first chat");
 
+    if let Ok(auth) = tmz_core::AuthManager::new()
+        && let Ok(tokens) = auth.get_tokens()
+    {
+        let remaining = tokens.expires_at - chrono::Utc::now().timestamp();
+        app.token_expires_mins = Some(remaining / 60);
+    }
+    app.cache = Some(cache);
+    load_status_format(app);
+    app.status_msg = "Loading conversations...".to_string();
+    startup.mark("token and status");
     Ok(())
 }
 
+/// Load what the first frame left out: the full chat list (which the chat
+/// search filters), the mention and saved-search counts, and the spell
+/// checker.
+fn warm_up(app: &mut App, rt: &tokio::runtime::Runtime, startup: &mut StartupProfile) {
+    reload_conversations(app, rt);
+    refresh_counts(app, rt);
+    app.status_msg = format!("{} conversations loaded", app.conversations.len());
+    load_spell_checker(app);
+    app.last_sync = Some(Instant::now());
+    startup.mark("warm-up");
+}
+
+fn load_spell_checker(app: &mut App) {
+    app.spell = None;
+    if app.config.composer.spell_check {
+        match tmz_core::composer::SpellChecker::load(&app.config.composer) {
+            Ok(checker) => app.spell = Some(checker),
+            Err(e) => app.status_msg = format!("Spell check off: {e}"),
+        }
+    }
+}
+
+fn load_status_format(app: &mut App) {
+    app.status_format = tmz_core::statusbar::StatusFormat::parse(&app.config.statusbar.format)
+        .unwrap_or_else(|e| {
+            app.status_msg = e.to_string();
+            tmz_core::statusbar::StatusFormat::parse(tmz_core::statusbar::DEFAULT_FORMAT)
+                .unwrap_or_default()
+        });
+}
+
+/// Reload the config after one of its files changed and apply what can
+/// change while running. The density stays as `D` or `--compact` left it.
+fn reload_config(
+    app: &mut App,
+    paths: &AppPaths,
+    profile: Option<&str>,
+    rt: &tokio::runtime::Runtime,
+) {
+    let mut new = match AppConfig::load_layers(&paths.config_layers(), profile) {
+        Ok(new) => new,
+        Err(e) => {
+            app.status_msg = format!("Config not reloaded: {e}");
+            return;
+        }
+    };
+    new.display.density = app.config.display.density;
+    let changes = tmz_core::reload::apply(&mut app.config, new);
+    if changes.is_empty() {
+        return;
+    }
+    if changes.touches("composer") {
+        load_spell_checker(app);
+    }
+    if changes.touches("statusbar") {
+        load_status_format(app);
+    }
+    if changes.touches("audit") {
+        app.audit = Some(tmz_core::audit::AuditLog::new(
+            &paths.state_dir,
+            &app.config.audit,
+            "tui",
+        ));
+    }
+    if changes.touches("searches") || changes.touches("people") {
+        refresh_counts(app, rt);
+    }
+    if changes.touches("time_tracking") {
+        stop_time_tracking(app, rt);
+        start_time_tracking(app);
+    }
+    if changes.touches("scripting") {
+        app.script_path = script_path(&app.config.scripting, paths);
+    }
+    if changes.touches("runtime.retries") {
+        tmz_core::retry::configure(&app.config.runtime.retries);
+    }
+    if changes.touches("runtime.rate_limit") {
+        tmz_core::ratelimit::configure(&app.config.runtime.rate_limit);
+    }
+
+    let list = |keys: &[String]| {
+        let text = keys.iter().take(3).cloned().collect::<Vec<_>>().join(", ");
+        match keys.len() {
+            0..=3 => text,
+            n => format!("{text} and {} more", n - 3),
+        }
+    };
+    app.status_msg = if changes.restart.is_empty() {
+        format!("Config reloaded: {}", list(&changes.applied))
+    } else {
+        format!("Restart tmz-tui to apply {}", list(&changes.restart))
+    };
+}
+
+fn start_time_tracking(app: &mut App) {
+    let config = &app.config.time_tracking;
+    app.time_tracker = config
+        .enabled
+        .then(|| tmz_core::timetrack::TimeTracker::new(config));
+}
+
+/// Tell the time tracker which conversation is open (one whose messages or
+/// composer have focus) and store the stretch that ended, if any.
+fn track_time(app: &mut App, rt: &tokio::runtime::Runtime, active: bool) {
+    let open = if app.focus == Focus::ChatList {
+        None
+    } else {
+        app.selected_conversation().map(|c| c.id.clone())
+    };
+    let Some(tracker) = app.time_tracker.as_mut() else {
+        return;
+    };
+    if let Some(entry) = tracker.observe(open.as_deref(), chrono::Utc::now(), active) {
+        save_time_entry(app, rt, entry, false);
+    }
+}
+
+/// End the current stretch, e.g. on quit, and wait for its hook.
+fn stop_time_tracking(app: &mut App, rt: &tokio::runtime::Runtime) {
+    if let Some(entry) = app
+        .time_tracker
+        .as_mut()
+        .and_then(|tracker| tracker.stop(chrono::Utc::now()))
+    {
+        save_time_entry(app, rt, entry, true);
+    }
+}
+
+/// Store `entry` in the cache and hand it to the `[time_tracking]` hook in
+/// the background (or before returning, with `wait`).
+fn save_time_entry(
+    app: &App,
+    rt: &tokio::runtime::Runtime,
+    entry: tmz_core::timetrack::TimeEntry,
+    wait: bool,
+) {
+    if let Some(ref cache) = app.cache
+        && let Err(e) = rt.block_on(cache.record_time_entry(&entry))
+    {
+        log::warn!("recording time entry: {e}");
+    }
+    if !tmz_core::timetrack::has_hook(&app.config.time_tracking) {
+        return;
+    }
+    let config = app.config.time_tracking.clone();
+    let name = app
+        .conversations
+        .iter()
+        .find(|c| c.id == entry.conversation_id)
+        .map(|c| c.display_name.clone())
+        .unwrap_or_default();
+    let task = async move {
+        if let Err(e) = tmz_core::timetrack::emit(&config, &entry, &name).await {
+            log::warn!("time tracking hook: {e}");
+        }
+    };
+    if wait {
+        rt.block_on(task);
+    } else {
+        rt.spawn(task);
+    }
+}
+
 fn handle_key(app: &mut App, key: crossterm::event::KeyEvent, rt: &tokio::runtime::Runtime) {
     match app.mode {
         Mode::Normal => handle_normal_key(app, key, rt),
         Mode::Insert => handle_insert_key(app, key, rt),
         Mode::ChatSearch => handle_chat_search_key(app, key, rt),
         Mode::Search => handle_search_key(app, key),
+        Mode::Inspect => handle_inspect_key(app, key, rt),
+        Mode::React => handle_react_key(app, key, rt),
+        Mode::Yanks => handle_yanks_key(app, key),
+        Mode::Scripts => handle_scripts_key(app, key, rt),
+        Mode::Switcher => handle_switcher_key(app, key, rt),
+        Mode::Attach => handle_attach_key(app, key),
         Mode::Help => {
             if matches!(
                 key.code,
@@ -296,20 +959,57 @@ fn handle_key(app: &mut App, key: crossterm::event::KeyEvent, rt: &tokio::runtim
             }
         }
     }
+    if app.mode == Mode::Inspect {
+        load_inspected_raw(app, rt);
+    }
+}
+
+/// Read the raw JSON of the inspected message, which chats are loaded
+/// without.
+fn load_inspected_raw(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let Some(ref cache) = app.cache else {
+        return;
+    };
+    let Some(msg) = app.messages.get_mut(app.inspect_index) else {
+        return;
+    };
+    if msg.raw_json != "{}" {
+        return;
+    }
+    match rt.block_on(cache.get_message(&msg.conversation_id, &msg.id)) {
+        Ok(Some(full)) => msg.raw_json = full.raw_json,
+        Ok(None) => {}
+        Err(e) => log::debug!("reading raw message: {e}"),
+    }
 }
 
 fn handle_normal_key(app: &mut App, key: crossterm::event::KeyEvent, rt: &tokio::runtime::Runtime) {
     use crossterm::event::{KeyCode, KeyModifiers};
 
+    if let Some(register) = app.pending_register.take() {
+        handle_register_key(app, register, key.code);
+        return;
+    }
+    if (!key.modifiers.contains(KeyModifiers::CONTROL) && handle_message_key(app, key.code))
+        || handle_health_key(app, key.code, rt)
+        || handle_tab_key(app, key.code, rt)
+    {
+        return;
+    }
+
     match key.code {
         KeyCode::Char('q') => app.running = false,
         KeyCode::Char('?') => app.mode = Mode::Help,
+        KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            open_switcher(app, rt);
+        }
 
         // Focus switching
         KeyCode::Char('h') | KeyCode::Left => app.focus = Focus::ChatList,
         KeyCode::Char('l') | KeyCode::Right => {
             if app.focus == Focus::ChatList {
                 app.focus = Focus::Messages;
+                record_open(app, rt);
             } else if app.focus == Focus::Messages {
                 app.focus = Focus::Files;
                 app.show_files = true;
@@ -332,10 +1032,7 @@ fn handle_normal_key(app: &mut App, key: crossterm::event::KeyEvent, rt: &tokio:
 
         // Navigation
         KeyCode::Char('j') | KeyCode::Down => match app.focus {
-            Focus::ChatList => {
-                app.chat_next();
-                load_selected_chat(app, rt);
-            }
+            Focus::ChatList => chat_down(app, rt),
             Focus::Messages => app.msg_scroll_down(),
             _ => {}
         },
@@ -371,17 +1068,29 @@ fn handle_normal_key(app: &mut App, key: crossterm::event::KeyEvent, rt: &tokio:
             }
         }
 
-        // Side tabs
-        KeyCode::Char('1') => switch_tab(app, SideTab::Chats, rt),
-        KeyCode::Char('2') => switch_tab(app, SideTab::Teams, rt),
-        KeyCode::Char('3') => switch_tab(app, SideTab::Channels, rt),
-
         // Toggle files panel
         KeyCode::Char('f') => app.show_files = !app.show_files,
+        KeyCode::Char('L') => app.show_legend = !app.show_legend,
+        KeyCode::Char('T') => {
+            app.translate = !app.translate;
+            load_selected_chat(app, rt);
+        }
+        KeyCode::Char('D') => {
+            app.config.display.density = if app.config.display.is_compact() {
+                Density::Comfortable
+            } else {
+                Density::Compact
+            };
+        }
 
         // Sync
         KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             trigger_sync(app, rt);
+            start_health_check(app, rt);
+        }
+
+        KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            undo_send(app, rt);
         }
 
         _ => {}
@@ -389,17 +1098,22 @@ fn handle_normal_key(app: &mut App, key: crossterm::event::KeyEvent, rt: &tokio:
 }
 
 fn handle_insert_key(app: &mut App, key: crossterm::event::KeyEvent, rt: &tokio::runtime::Runtime) {
-    use crossterm::event::KeyCode;
+    use crossterm::event::{KeyCode, KeyModifiers};
 
     match key.code {
         KeyCode::Esc => {
             app.mode = Mode::Normal;
             app.focus = Focus::Messages;
         }
-        KeyCode::Enter => {
-            if !app.input.is_empty() {
-                send_message(app, rt);
-            }
+        KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            undo_send(app, rt);
+        }
+        KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.picker = Some(FilePicker::open(app.attachments.clone()));
+            app.mode = Mode::Attach;
+        }
+        KeyCode::Enter if !app.input.is_empty() || !app.attachments.is_empty() => {
+            send_composed(app, rt);
         }
         KeyCode::Backspace => app.input_backspace(),
         KeyCode::Char(c) => app.input_char(c),
@@ -425,6 +1139,7 @@ fn handle_chat_search_key(
             if !app.filtered_conversations.is_empty() {
                 load_selected_chat(app, rt);
                 app.focus = Focus::Messages;
+                record_open(app, rt);
             }
         }
         KeyCode::Backspace => {
@@ -442,50 +1157,404 @@ fn handle_chat_search_key(
     }
 }
 
-fn handle_search_key(app: &mut App, key: crossterm::event::KeyEvent) {
-    use crossterm::event::KeyCode;
+/// Keys in the quick switcher: type to filter, move, Enter to open.
+fn handle_switcher_key(
+    app: &mut App,
+    key: crossterm::event::KeyEvent,
+    rt: &tokio::runtime::Runtime,
+) {
+    use crossterm::event::{KeyCode, KeyModifiers};
 
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
     match key.code {
-        KeyCode::Esc => {
-            app.mode = Mode::Normal;
-            app.search_query.clear();
-            app.search_results.clear();
-        }
+        KeyCode::Esc => app.mode = Mode::Normal,
+        KeyCode::Char('k') if ctrl => app.mode = Mode::Normal,
         KeyCode::Enter => {
             app.mode = Mode::Normal;
+            if let Some(&index) = app.switcher_matches.get(app.switcher_selected) {
+                switch_to(app, index, rt);
+            }
+        }
+        KeyCode::Down | KeyCode::Tab => {
+            app.switcher_selected =
+                (app.switcher_selected + 1).min(app.switcher_matches.len().saturating_sub(1));
+        }
+        KeyCode::Char('n') if ctrl => {
+            app.switcher_selected =
+                (app.switcher_selected + 1).min(app.switcher_matches.len().saturating_sub(1));
+        }
+        KeyCode::Up | KeyCode::BackTab => {
+            app.switcher_selected = app.switcher_selected.saturating_sub(1);
+        }
+        KeyCode::Char('p') if ctrl => {
+            app.switcher_selected = app.switcher_selected.saturating_sub(1);
         }
         KeyCode::Backspace => {
-            app.search_query.pop();
+            app.switcher_query.pop();
+            app.filter_switcher();
         }
         KeyCode::Char(c) => {
-            app.search_query.push(c);
+            app.switcher_query.push(c);
+            app.filter_switcher();
         }
         _ => {}
     }
 }
 
-fn handle_tick(app: &mut App, rt: &tokio::runtime::Runtime) {
-    // Auto-sync every 60 seconds
-    if let Some(last) = app.last_sync
-        && last.elapsed() > Duration::from_mins(1)
-        && !app.syncing
-    {
-        if let Some(ref cache) = app.cache
-            && let Ok(convs) = rt.block_on(cache.list_conversations(500))
-        {
-            let selected_id = app.selected_conversation().map(|c| c.id.clone());
-            app.conversations = convs;
-            app.filter_conversations();
+/// Keys in the file picker: type to filter, Enter to open a directory or
+/// attach, Tab to mark several files.
+fn handle_attach_key(app: &mut App, key: crossterm::event::KeyEvent) {
+    use crossterm::event::{KeyCode, KeyModifiers};
 
-            if let Some(id) = selected_id
-                && let Some(pos) = app
-                    .filtered_conversations
-                    .iter()
-                    .position(|&i| app.conversations[i].id == id)
-            {
-                app.chat_selected = pos;
+    let Some(picker) = app.picker.as_mut() else {
+        app.mode = Mode::Insert;
+        return;
+    };
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Esc => {
+            app.picker = None;
+            app.mode = Mode::Insert;
+        }
+        KeyCode::Enter => {
+            let Some(entry) = picker.current() else {
+                return;
+            };
+            if entry.is_dir {
+                let dir = entry.path.clone();
+                picker.cd(dir);
+                return;
+            }
+            if !picker.is_marked(&entry.path) {
+                picker.toggle_mark();
+            }
+            app.attachments = std::mem::take(&mut picker.marked);
+            app.picker = None;
+            app.mode = Mode::Insert;
+            app.status_msg = attachments_summary(&app.attachments);
+        }
+        KeyCode::Tab => {
+            picker.toggle_mark();
+            picker.move_by(1);
+        }
+        KeyCode::Char('x') if ctrl => picker.marked.clear(),
+        KeyCode::Down => picker.move_by(1),
+        KeyCode::Char('n') if ctrl => picker.move_by(1),
+        KeyCode::Up | KeyCode::BackTab => picker.move_by(-1),
+        KeyCode::Char('p') if ctrl => picker.move_by(-1),
+        KeyCode::Backspace if picker.query.is_empty() => picker.up(),
+        KeyCode::Backspace => {
+            picker.query.pop();
+            picker.filter();
+        }
+        KeyCode::Char('~') if picker.query.is_empty() => picker.home(),
+        KeyCode::Char(c) if !ctrl => {
+            picker.query.push(c);
+            picker.filter();
+        }
+        _ => {}
+    }
+}
+
+/// Status line for the queued attachments.
+fn attachments_summary(files: &[PathBuf]) -> String {
+    match files {
+        [] => "No files attached".to_string(),
+        [file] => format!(
+            "Attached {} \u{2014} Enter sends",
+            file.file_name().unwrap_or_default().to_string_lossy()
+        ),
+        _ => format!("Attached {} files \u{2014} Enter sends", files.len()),
+    }
+}
+
+fn handle_search_key(app: &mut App, key: crossterm::event::KeyEvent) {
+    use crossterm::event::KeyCode;
+
+    match key.code {
+        KeyCode::Esc => {
+            app.mode = Mode::Normal;
+            app.search_query.clear();
+            app.search_results.clear();
+        }
+        KeyCode::Enter => {
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Backspace => {
+            app.search_query.pop();
+        }
+        KeyCode::Char(c) => {
+            app.search_query.push(c);
+        }
+        _ => {}
+    }
+}
+
+/// Keys in the raw message inspector: scroll, and step to older/newer messages.
+fn handle_inspect_key(
+    app: &mut App,
+    key: crossterm::event::KeyEvent,
+    rt: &tokio::runtime::Runtime,
+) {
+    use crossterm::event::KeyCode;
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q' | 'I') => app.mode = Mode::Normal,
+        KeyCode::Char('r') => app.open_reactions(app.inspect_index),
+        KeyCode::Char('y') => app.yank(app.inspect_index),
+        KeyCode::Char('s') => save_message(app, app.inspect_index, rt),
+        KeyCode::Char('j') | KeyCode::Down => app.inspect_scroll += 1,
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.inspect_scroll = app.inspect_scroll.saturating_sub(1);
+        }
+        KeyCode::Char('h' | '[') | KeyCode::Left => {
+            app.inspect_index = app.inspect_index.saturating_sub(1);
+            app.inspect_scroll = 0;
+        }
+        KeyCode::Char('l' | ']') | KeyCode::Right => {
+            app.inspect_index = (app.inspect_index + 1).min(app.messages.len().saturating_sub(1));
+            app.inspect_scroll = 0;
+        }
+        _ => {}
+    }
+}
+
+/// Keys in the reaction picker: move around the grid, react with Enter.
+fn handle_react_key(app: &mut App, key: crossterm::event::KeyEvent, rt: &tokio::runtime::Runtime) {
+    use crossterm::event::KeyCode;
+
+    let count = app.reaction_choices().len();
+    let choice = app.react_choice;
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q' | 'r') => app.mode = Mode::Normal,
+        KeyCode::Char('h') | KeyCode::Left => app.react_choice = choice.saturating_sub(1),
+        KeyCode::Char('l') | KeyCode::Right => {
+            app.react_choice = (choice + 1).min(count.saturating_sub(1));
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.react_choice = choice.checked_sub(REACTION_COLUMNS).unwrap_or(choice);
+        }
+        KeyCode::Char('j') | KeyCode::Down if choice + REACTION_COLUMNS < count => {
+            app.react_choice = choice + REACTION_COLUMNS;
+        }
+        KeyCode::Enter => add_reaction(app, rt),
+        _ => {}
+    }
+}
+
+/// Normal-mode keys acting on messages: inspect, react, yank, and paste.
+/// Returns whether the key was handled.
+fn handle_message_key(app: &mut App, code: crossterm::event::KeyCode) -> bool {
+    use crossterm::event::KeyCode;
+
+    match code {
+        KeyCode::Char('I') if !app.messages.is_empty() => {
+            app.inspect_index = app.messages.len() - 1;
+            app.inspect_scroll = 0;
+            app.mode = Mode::Inspect;
+        }
+
+        // React to or yank the newest message
+        KeyCode::Char('r') if app.focus == Focus::Messages => {
+            if let Some(index) = app.messages.iter().rposition(|m| !m.is_system_event()) {
+                app.open_reactions(index);
+            }
+        }
+        KeyCode::Char('y') if app.focus == Focus::Messages => {
+            if let Some(index) = app.messages.iter().rposition(|m| !m.is_system_event()) {
+                app.yank(index);
+            }
+        }
+
+        // Paste yanks into the composer
+        KeyCode::Char('"') => app.pending_register = Some('"'),
+        KeyCode::Char('p') => app.paste_yank(0),
+        KeyCode::Char('P') if !app.yanks.is_empty() => {
+            app.yank_choice = 0;
+            app.mode = Mode::Yanks;
+        }
+
+        // Script actions
+        KeyCode::Char('!') => open_scripts(app),
+        _ => return false,
+    }
+    true
+}
+
+/// Normal-mode keys for the side tabs, saved messages, and the timeline. Returns whether
+/// the key was handled.
+fn handle_tab_key(
+    app: &mut App,
+    code: crossterm::event::KeyCode,
+    rt: &tokio::runtime::Runtime,
+) -> bool {
+    use crossterm::event::KeyCode;
+
+    match code {
+        KeyCode::Char('1') => switch_tab(app, SideTab::Chats, rt),
+        KeyCode::Char('2') => switch_tab(app, SideTab::Teams, rt),
+        KeyCode::Char('3') => switch_tab(app, SideTab::Channels, rt),
+        KeyCode::Char('4') => switch_tab(app, SideTab::Saved, rt),
+        KeyCode::Char('5') => switch_tab(app, SideTab::Timeline, rt),
+
+        // Jump from the timeline into the message's conversation
+        KeyCode::Enter if app.side_tab == SideTab::Timeline && app.focus == Focus::ChatList => {
+            if let Some(&conv) = app.filtered_conversations.get(app.chat_selected) {
+                switch_to(app, conv, rt);
+            }
+        }
+
+        // Save the newest message, or remove the selected saved one
+        KeyCode::Char('s') if app.focus == Focus::Messages => {
+            if let Some(index) = app.messages.iter().rposition(|m| !m.is_system_event()) {
+                save_message(app, index, rt);
+            }
+        }
+        KeyCode::Char('x') if app.focus == Focus::ChatList => remove_saved(app, rt),
+        _ => return false,
+    }
+    true
+}
+
+/// Keys after `"`: a register name, then `p` to paste it.
+fn handle_register_key(app: &mut App, register: char, code: crossterm::event::KeyCode) {
+    use crossterm::event::KeyCode;
+
+    match (register, code) {
+        ('"', KeyCode::Char(name)) if register_index(name).is_some() => {
+            app.pending_register = Some(name);
+        }
+        ('"', _) => app.status_msg = "Registers are \"1-\"9 and \"0".to_string(),
+        (name, KeyCode::Char('p')) => {
+            if let Some(index) = register_index(name) {
+                app.paste_yank(index);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Keys in the yank history picker.
+fn handle_yanks_key(app: &mut App, key: crossterm::event::KeyEvent) {
+    use crossterm::event::KeyCode;
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q' | 'P') => app.mode = Mode::Normal,
+        KeyCode::Char('j') | KeyCode::Down => {
+            app.yank_choice = (app.yank_choice + 1).min(app.yanks.len().saturating_sub(1));
+        }
+        KeyCode::Char('k') | KeyCode::Up => app.yank_choice = app.yank_choice.saturating_sub(1),
+        KeyCode::Enter | KeyCode::Char('p') => app.paste_yank(app.yank_choice),
+        KeyCode::Char(name) => {
+            if let Some(index) = register_index(name) {
+                app.paste_yank(index);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Path of the user script for `config` (see [`tmz_core::scripting`]).
+fn script_path(config: &tmz_core::ScriptingConfig, paths: &AppPaths) -> PathBuf {
+    let config_dir = paths.config_file.parent().unwrap_or_else(|| Path::new("."));
+    tmz_core::scripting::script_path(config, config_dir)
+}
+
+/// Load the user script and open the action picker.
+fn open_scripts(app: &mut App) {
+    let scripts = match Scripts::load(&app.script_path, &app.config) {
+        Ok(Some(scripts)) => scripts,
+        Ok(None) => {
+            app.status_msg = format!("No script at {}", app.script_path.display());
+            return;
+        }
+        Err(e) => {
+            app.status_msg = format!("Script not loaded: {e}");
+            return;
+        }
+    };
+    app.script_actions = scripts.actions();
+    if app.script_actions.is_empty() {
+        app.status_msg = format!("{} defines no actions", app.script_path.display());
+        return;
+    }
+    app.scripts = Some(match app.audit {
+        Some(ref audit) => scripts.with_audit(audit.clone()),
+        None => scripts,
+    });
+    app.script_choice = 0;
+    app.mode = Mode::Scripts;
+}
+
+/// Keys in the script action picker.
+fn handle_scripts_key(
+    app: &mut App,
+    key: crossterm::event::KeyEvent,
+    rt: &tokio::runtime::Runtime,
+) {
+    use crossterm::event::KeyCode;
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q' | '!') => app.mode = Mode::Normal,
+        KeyCode::Char('j') | KeyCode::Down => {
+            app.script_choice =
+                (app.script_choice + 1).min(app.script_actions.len().saturating_sub(1));
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.script_choice = app.script_choice.saturating_sub(1);
+        }
+        KeyCode::Enter => {
+            app.mode = Mode::Normal;
+            if let Some(action) = app.script_actions.get(app.script_choice) {
+                let name = action.name.clone();
+                run_script(app, rt, &name);
             }
         }
+        _ => {}
+    }
+}
+
+/// Run the script action `name` on the selected chat and its newest
+/// message, showing its last line of output.
+fn run_script(app: &mut App, rt: &tokio::runtime::Runtime, name: &str) {
+    let (Some(scripts), Some(cache)) = (app.scripts.as_ref(), app.cache.as_ref()) else {
+        return;
+    };
+    let conv = app.selected_conversation();
+    let ctx = ActionContext {
+        chat: conv.map(|c| c.id.clone()),
+        chat_name: conv.map(|c| c.display_name.clone()),
+        message: app
+            .messages
+            .iter()
+            .rfind(|m| !m.is_system_event())
+            .map(|m| m.id.clone()),
+        args: Vec::new(),
+    };
+    app.status_msg = match rt.block_on(scripts.run(cache, name, ctx)) {
+        Ok(lines) => lines
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .find(|line| !line.is_empty())
+            .map_or_else(|| format!("Ran {name}"), str::to_string),
+        Err(e) => format!("Script failed: {e}"),
+    };
+}
+
+fn handle_tick(app: &mut App, rt: &tokio::runtime::Runtime) {
+    dispatch_outbox(app, rt);
+    poll_health(app, rt);
+    show_live_messages(app, rt);
+
+    // Auto-sync every 60 seconds
+    if let Some(last) = app.last_sync
+        && last.elapsed() > Duration::from_mins(1)
+        && !app.syncing
+    {
+        reload_conversations(app, rt);
+        refresh_counts(app, rt);
+        start_health_check(app, rt);
         app.last_sync = Some(Instant::now());
     }
 
@@ -498,27 +1567,735 @@ fn handle_tick(app: &mut App, rt: &tokio::runtime::Runtime) {
     }
 }
 
+/// Receive pushed messages from the daemon's engine, or without a daemon
+/// from a local engine listening on the TUI's cache.
+fn start_live(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let Some(cache) = app.cache.clone() else {
+        return;
+    };
+    if !app.config.sync.live {
+        return;
+    }
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let config = app.config.clone();
+    let audit = app.audit.clone();
+    rt.spawn(async move {
+        if let Some(client) = tmz_core::EngineClient::connect().await {
+            // When the daemon stops, the refresh every minute takes over.
+            if let Ok(mut events) = client.subscribe().await {
+                while let Ok(event) = events.next_event().await {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+            return;
+        }
+        let engine = tmz_core::Engine::new(cache);
+        engine.reconfigure(&config, audit);
+        let mut events = engine.subscribe();
+        let (_stop, shutdown) = tokio::sync::watch::channel(false);
+        let forward = async {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        if tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        };
+        tokio::select! {
+            () = engine.listen(shutdown) => {}
+            () = forward => {}
+        }
+    });
+    app.live_events = Some(rx);
+}
+
+/// Show messages pushed since the last tick: the chat list moves them to
+/// the top, and the open chat shows them.
+fn show_live_messages(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let Some(ref mut events) = app.live_events else {
+        return;
+    };
+    let mut changed = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let EngineEvent::MessageReceived {
+            conversation_id, ..
+        } = event
+        {
+            changed.push(conversation_id);
+        }
+    }
+    if changed.is_empty() {
+        return;
+    }
+    reload_conversations(app, rt);
+    refresh_counts(app, rt);
+    if app
+        .selected_conversation()
+        .is_some_and(|conv| changed.contains(&conv.id))
+    {
+        load_selected_chat(app, rt);
+    }
+}
+
+/// Check auth, the daemon, and the cache in the background, unless a check
+/// is already running.
+fn start_health_check(app: &mut App, rt: &tokio::runtime::Runtime) {
+    if app.health_check.is_none() {
+        let cache = app.cache.clone();
+        app.health_check =
+            Some(rt.spawn(async move { tmz_core::health::check(cache.as_ref()).await }));
+    }
+}
+
+/// Collect a finished health check or fix. A finished fix shows its outcome
+/// and starts a new check, so fixed problems lose their banners.
+fn poll_health(app: &mut App, rt: &tokio::runtime::Runtime) {
+    if let Some(check) = app.health_check.take_if(|task| task.is_finished()) {
+        match rt.block_on(check) {
+            Ok(facts) => app.health = tmz_core::health::assess(&facts, chrono::Utc::now()),
+            Err(e) => log::warn!("health check failed: {e}"),
+        }
+    }
+    if let Some(fix) = app.health_fix.take_if(|task| task.is_finished()) {
+        app.status_msg = rt.block_on(fix).unwrap_or_else(|e| e.to_string());
+        start_health_check(app, rt);
+    }
+}
+
+/// Keys offered by the health banners: `A` logs in, `S` starts the daemon. Returns whether the key was handled.
+fn handle_health_key(
+    app: &mut App,
+    code: crossterm::event::KeyCode,
+    rt: &tokio::runtime::Runtime,
+) -> bool {
+    use crossterm::event::KeyCode;
+
+    match code {
+        KeyCode::Char('A')
+            if app.auth_retry.is_some() || app.health.iter().any(Problem::needs_login) =>
+        {
+            app.login_requested = true;
+        }
+        KeyCode::Char('S') if app.health.contains(&Problem::DaemonNotRunning) => {
+            start_health_fix(app, rt, "Starting the daemon...", start_daemon());
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Run `fix` in the background, unless another one is running.
+fn start_health_fix(
+    app: &mut App,
+    rt: &tokio::runtime::Runtime,
+    status: &str,
+    fix: impl Future<Output = String> + Send + 'static,
+) {
+    if app.health_fix.is_some() {
+        return;
+    }
+    app.status_msg = status.to_string();
+    app.health_fix = Some(rt.spawn(fix));
+}
+
+/// Log in without quitting: leave the alternate screen, refresh the saved
+/// session or run the device code login on the plain terminal, then come
+/// back and retry the operation that failed for lack of a login.
+fn log_in(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    app: &mut App,
+    rt: &tokio::runtime::Runtime,
+    events: &mpsc::Receiver<Event>,
+) -> Result<()> {
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    let login = rt.spawn(login_flow());
+    // Keys still arrive in raw mode, so Esc can cancel the wait.
+    let cancelled = loop {
+        match events.recv()? {
+            Event::Key(key)
+                if key.kind == KeyEventKind::Press
+                    && (key.code == KeyCode::Esc
+                        || (key.code == KeyCode::Char('c')
+                            && key.modifiers.contains(KeyModifiers::CONTROL))) =>
+            {
+                login.abort();
+                break true;
+            }
+            _ if login.is_finished() => break false,
+            _ => {}
+        }
+    };
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    terminal.clear()?;
+
+    if cancelled {
+        app.status_msg = "Login cancelled".to_string();
+        return Ok(());
+    }
+    let result = match rt.block_on(login) {
+        Ok(login) => login.map_err(anyhow::Error::from),
+        Err(e) => Err(e.into()),
+    };
+    if let Some(ref audit) = app.audit {
+        audit.record("auth.login", None, &result);
+    }
+    match result {
+        Ok(tokens) => {
+            let _ = tmz_core::daemon::clear_reauth_needed();
+            app.status_msg = format!("Authenticated as {}", tokens.user_principal_name);
+            if let Some(retry) = app.auth_retry.take() {
+                retry_after_login(app, rt, retry);
+            }
+        }
+        Err(e) => app.status_msg = format!("Login failed: {e}"),
+    }
+    start_health_check(app, rt);
+    Ok(())
+}
+
+/// Refresh the saved session, or log in with the device code flow when it
+/// cannot be refreshed, printing progress to the plain terminal.
+async fn login_flow() -> Result<tmz_core::TeamsTokens, tmz_core::teams::AuthenticationError> {
+    let auth = tmz_core::AuthManager::new()?;
+    say("Logging in to Teams. Press Esc to cancel and return to tmz.");
+    say("");
+    say("Refreshing the saved session...");
+    match auth.refresh_tokens().await {
+        Ok(tokens) => return Ok(tokens),
+        Err(e) => log::debug!("refresh before login failed: {e}"),
+    }
+    let code = auth.start_device_login().await?;
+    say("Opening the sign-in page in your browser. Enter the code below there.");
+    if let Err(e) = open::that_detached(&code.verification_uri) {
+        log::debug!("opening browser failed: {e}");
+        say("Could not open a browser; open the URL yourself.");
+    }
+    say("");
+    say(&format!("  URL:  {}", code.verification_uri));
+    say(&format!("  Code: {}", code.user_code));
+    say("");
+    say(&format!(
+        "Waiting for the login (the code expires in {} minutes)...",
+        code.expires_in / 60
+    ));
+    auth.complete_device_login(&code).await
+}
+
+/// Print a line while the terminal is in raw mode.
+fn say(line: &str) {
+    let mut stdout = std::io::stdout();
+    let _ = write!(stdout, "{line}\r\n");
+    let _ = stdout.flush();
+}
+
+/// Run `retry` again now that the tokens are valid.
+fn retry_after_login(app: &mut App, rt: &tokio::runtime::Runtime, retry: AuthRetry) {
+    match retry {
+        AuthRetry::Sync => trigger_sync(app, rt),
+        AuthRetry::Send {
+            conversation_id,
+            text,
+        } => {
+            let sent = rt.block_on(tmz_core::outbox::track(
+                app.cache.as_ref(),
+                &conversation_id,
+                &text,
+                OutgoingKind::Text,
+                tmz_core::engine::send_message(&conversation_id, &text),
+            ));
+            if let Some(ref audit) = app.audit {
+                audit.record("send", Some(&conversation_id), &sent);
+            }
+            match sent {
+                Ok(_) => {
+                    app.status_msg = "Sent after login".to_string();
+                    load_selected_chat(app, rt);
+                }
+                Err(e) => {
+                    app.status_msg = format!("Send failed: {e}");
+                    load_outgoing(app, rt);
+                }
+            }
+        }
+        AuthRetry::React {
+            conversation_id,
+            message_id,
+            reaction,
+        } => react(app, rt, &conversation_id, &message_id, &reaction),
+    }
+}
+
+/// Remember `retry` if `error` means the login expired, and point at `A`.
+fn offer_login(app: &mut App, error: &CoreError, retry: AuthRetry) {
+    if matches!(error, CoreError::Auth(_)) {
+        app.auth_retry = Some(retry);
+        app.status_msg
+            .push_str(" \u{2014} press A to log in and retry");
+    }
+}
+
+/// Start the daemon with `tmz service start` (the `tmz` next to this
+/// binary, or the one on `PATH`). Returns the status line to show.
+async fn start_daemon() -> String {
+    let tmz = std::env::current_exe()
+        .map(|exe| exe.with_file_name(format!("tmz{}", std::env::consts::EXE_SUFFIX)))
+        .ok()
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("tmz"));
+    match tokio::process::Command::new(&tmz)
+        .args(["service", "start"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+    {
+        Ok(out) if out.status.success() => {
+            // Give the daemon a moment to write its PID file and open its
+            // socket before the next health check.
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            "Daemon started".to_string()
+        }
+        Ok(out) => format!(
+            "Starting the daemon failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        ),
+        Err(e) => format!("Starting the daemon failed: {}: {e}", tmz.display()),
+    }
+}
+
+/// Reload the chat list from the cache, keeping the selected chat selected.
+fn reload_conversations(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let Some(ref cache) = app.cache else {
+        return;
+    };
+    let Ok(convs) = rt.block_on(cache.list_conversations(500)) else {
+        return;
+    };
+    let selected_id = app.selected_conversation().map(|c| c.id.clone());
+    app.conversations = convs;
+    if let Ok(tags) = rt.block_on(cache.conversation_tags(&app.config.shared_tags())) {
+        app.conversation_tags = tags;
+    }
+    if app.side_tab == SideTab::Timeline {
+        load_timeline(app, rt);
+        return;
+    }
+    app.filter_conversations();
+
+    if let Some(id) = selected_id
+        && let Some(pos) = app
+            .filtered_conversations
+            .iter()
+            .position(|&i| app.conversations[i].id == id)
+    {
+        app.chat_selected = pos;
+    }
+}
+
+/// Recount unread mentions and matches for every saved search.
+fn refresh_counts(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let Some(ref cache) = app.cache else {
+        return;
+    };
+    app.unread_mentions = rt.block_on(cache.unread_mention_count()).unwrap_or(0);
+    app.saved_counts = app
+        .config
+        .saved_searches()
+        .into_iter()
+        .map(|(name, search)| {
+            let count = rt.block_on(saved_search_count(&app.config, cache, search));
+            (name.to_string(), count.ok())
+        })
+        .collect();
+}
+
+/// Number of results a saved search would return (chats plus messages).
+async fn saved_search_count(
+    config: &AppConfig,
+    cache: &tmz_core::Cache,
+    search: &SavedSearch,
+) -> tmz_core::Result<i64> {
+    let conv_id = match search.chat {
+        Some(ref chat) => {
+            let target = config.resolve_alias(chat).unwrap_or(chat);
+            if tmz_core::cache::is_conversation_id(target) {
+                Some(target.to_string())
+            } else {
+                match cache.find_conversation(target).await?.first() {
+                    Some(conv) => Some(conv.id.clone()),
+                    None => return Ok(0),
+                }
+            }
+        }
+        None => None,
+    };
+    if let Some(ref tag) = search.tag {
+        return tagged_search_count(config, cache, search, conv_id.as_deref(), tag).await;
+    }
+    if search.mentions_me {
+        let query = Some(search.query.as_str()).filter(|q| !q.is_empty());
+        let found = cache
+            .search_mentions(query, conv_id.as_deref(), i64::MAX)
+            .await?;
+        return Ok(i64::try_from(found.len()).unwrap_or(i64::MAX));
+    }
+    if conv_id.is_some() {
+        return cache
+            .count_message_matches(&search.query, conv_id.as_deref())
+            .await;
+    }
+    let chats = if search.what == SearchScope::Messages {
+        0
+    } else {
+        cache.count_conversation_matches(&search.query).await?
+    };
+    let messages = if search.what == SearchScope::Chats {
+        0
+    } else {
+        cache.count_message_matches(&search.query, None).await?
+    };
+    Ok(chats + messages)
+}
+
+/// [`saved_search_count`] of a search limited to a tag, which the cache
+/// cannot count directly.
+async fn tagged_search_count(
+    config: &AppConfig,
+    cache: &tmz_core::Cache,
+    search: &SavedSearch,
+    conv_id: Option<&str>,
+    tag: &str,
+) -> tmz_core::Result<i64> {
+    let tagged = cache
+        .conversations_tagged(tag, &config.shared_tags())
+        .await?;
+    let query = search.query.as_str();
+    let messages_only = conv_id.is_some() || search.mentions_me;
+    let chats = if messages_only || search.what == SearchScope::Messages {
+        0
+    } else {
+        cache
+            .search_conversations(query, i64::MAX)
+            .await?
+            .iter()
+            .filter(|c| tagged.contains(&c.id))
+            .count()
+    };
+    let messages = if search.mentions_me {
+        let query = Some(query).filter(|q| !q.is_empty());
+        cache.search_mentions(query, conv_id, i64::MAX).await?
+    } else if let Some(conv_id) = conv_id {
+        cache
+            .search_in_conversation(query, conv_id, i64::MAX)
+            .await?
+    } else if search.what == SearchScope::Chats {
+        Vec::new()
+    } else {
+        cache.search(query, i64::MAX).await?
+    };
+    let messages = messages
+        .iter()
+        .filter(|r| tagged.contains(&r.message.conversation_id))
+        .count();
+    Ok(i64::try_from(chats + messages).unwrap_or(i64::MAX))
+}
+
 fn switch_tab(app: &mut App, tab: SideTab, rt: &tokio::runtime::Runtime) {
     if app.side_tab == tab {
         return;
     }
+    if tab == SideTab::Saved {
+        load_saved_items(app, rt);
+    }
     app.side_tab = tab;
     app.chat_selected = 0;
     app.chat_search.clear();
+    if tab == SideTab::Timeline {
+        app.timeline.clear();
+        load_timeline(app, rt);
+    } else {
+        app.filter_conversations();
+    }
+    load_selected_chat(app, rt);
+}
+
+/// Reload the saved items from the cache.
+fn load_saved_items(app: &mut App, rt: &tokio::runtime::Runtime) {
+    if let Some(ref cache) = app.cache {
+        match rt.block_on(cache.list_saved_items(None, 500)) {
+            Ok(items) => app.saved_items = items,
+            Err(e) => app.status_msg = format!("Saved items not loaded: {e}"),
+        }
+    }
+}
+
+/// Reload the newest messages of the timeline tab, as many as were loaded
+/// before, keeping the selected message selected.
+fn load_timeline(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let Some(ref cache) = app.cache else {
+        return;
+    };
+    let limit = app.timeline.len().max(TIMELINE_PAGE);
+    let selected = app
+        .selected_timeline()
+        .map(|r| (r.message.conversation_id.clone(), r.message.id.clone()));
+    match rt.block_on(cache.timeline(None, i64::try_from(limit).unwrap_or(i64::MAX))) {
+        Ok(items) => {
+            app.timeline_more = items.len() == limit;
+            app.timeline = items;
+        }
+        Err(e) => app.status_msg = format!("Timeline not loaded: {e}"),
+    }
     app.filter_conversations();
+    if let Some((conv, id)) = selected
+        && let Some(pos) = app.filtered_timeline.iter().position(|&i| {
+            let msg = &app.timeline[i].message;
+            msg.conversation_id == conv && msg.id == id
+        })
+    {
+        app.chat_selected = pos;
+    }
+}
+
+/// Select the next chat list entry, loading older timeline messages at
+/// the end of the timeline.
+fn chat_down(app: &mut App, rt: &tokio::runtime::Runtime) {
+    if app.side_tab == SideTab::Timeline && app.chat_selected + 1 >= app.chat_list_len() {
+        load_older_timeline(app, rt);
+    }
+    app.chat_next();
     load_selected_chat(app, rt);
 }
 
+/// Append the next page of older messages to the timeline tab.
+fn load_older_timeline(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let (Some(cache), Some(last)) = (app.cache.as_ref(), app.timeline.last()) else {
+        return;
+    };
+    if !app.timeline_more {
+        return;
+    }
+    let page = i64::try_from(TIMELINE_PAGE).unwrap_or(i64::MAX);
+    let before = (last.message.compose_time.as_str(), last.message.id.as_str());
+    match rt.block_on(cache.timeline(Some(before), page)) {
+        Ok(items) => {
+            app.timeline_more = items.len() == TIMELINE_PAGE;
+            app.timeline.extend(items);
+            app.filter_conversations();
+        }
+        Err(e) => app.status_msg = format!("Timeline not loaded: {e}"),
+    }
+}
+
+/// Copy `messages[index]` into the saved items (`s`).
+fn save_message(app: &mut App, index: usize, rt: &tokio::runtime::Runtime) {
+    let (Some(cache), Some(msg)) = (app.cache.as_ref(), app.messages.get(index)) else {
+        return;
+    };
+    // Translations replace the loaded text, so save the cached original.
+    let original = rt
+        .block_on(cache.get_message(&msg.conversation_id, &msg.id))
+        .ok()
+        .flatten();
+    let msg = original.as_ref().unwrap_or(msg);
+    app.status_msg = match rt.block_on(cache.save_item(msg, "")) {
+        Ok(item) => format!(
+            "Saved message from {} as #{} (4 lists saved items)",
+            item.from_display_name, item.id
+        ),
+        Err(e) => format!("Not saved: {e}"),
+    };
+    if app.side_tab == SideTab::Saved {
+        load_saved_items(app, rt);
+        app.filter_conversations();
+    }
+}
+
+/// Remove the saved item selected in the saved tab (`x`).
+fn remove_saved(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let (Some(cache), Some(item)) = (app.cache.as_ref(), app.selected_saved()) else {
+        return;
+    };
+    let id = item.id;
+    app.status_msg = match rt.block_on(cache.remove_saved_item(id)) {
+        Ok(_) => format!("Removed saved item #{id}"),
+        Err(e) => format!("Not removed: {e}"),
+    };
+    load_saved_items(app, rt);
+    app.filter_conversations();
+    load_selected_chat(app, rt);
+}
+
+/// Open the quick switcher with conversations ranked by frecency, then
+/// the rest by last activity.
+fn open_switcher(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let frecent = app
+        .cache
+        .as_ref()
+        .and_then(|cache| rt.block_on(cache.frecent_conversations(50)).ok())
+        .unwrap_or_default();
+    let mut ranked: Vec<usize> = frecent
+        .iter()
+        .filter_map(|(conv, _)| app.conversations.iter().position(|c| c.id == conv.id))
+        .collect();
+    let rest: Vec<usize> = (0..app.conversations.len())
+        .filter(|i| !ranked.contains(i))
+        .collect();
+    ranked.extend(rest);
+    app.switcher_ranked = ranked;
+    app.switcher_query.clear();
+    app.filter_switcher();
+    app.mode = Mode::Switcher;
+}
+
+/// Select `conversations[index]` in the chat list (switching tabs if
+/// needed) and open it.
+fn switch_to(app: &mut App, index: usize, rt: &tokio::runtime::Runtime) {
+    let tab = match app.conversations[index].thread_type.as_str() {
+        "space" => SideTab::Teams,
+        "topic" => SideTab::Channels,
+        _ => SideTab::Chats,
+    };
+    app.side_tab = tab;
+    app.chat_search.clear();
+    app.filter_conversations();
+    if let Some(pos) = app.filtered_conversations.iter().position(|&i| i == index) {
+        app.chat_selected = pos;
+    }
+    load_selected_chat(app, rt);
+    app.focus = Focus::Messages;
+    record_open(app, rt);
+}
+
+/// Count the selected chat as opened for the frecency ranking.
+fn record_open(app: &mut App, rt: &tokio::runtime::Runtime) {
+    if let (Some(cache), Some(conv)) = (&app.cache, app.selected_conversation())
+        && let Err(e) = rt.block_on(cache.record_open(&conv.id))
+    {
+        log::debug!("recording conversation open: {e}");
+    }
+    refresh_availability(app, rt);
+}
+
+/// Look up whether the other person in an open 1:1 chat is around, for the
+/// message pane's title.
+fn refresh_availability(app: &mut App, rt: &tokio::runtime::Runtime) {
+    app.availability = None;
+    let Some(conv_id) = app
+        .selected_conversation()
+        .map(|c| c.id.clone())
+        .filter(|id| id.ends_with("@unq.gbl.spaces"))
+    else {
+        return;
+    };
+    let fallback = &app.config.sending.working_hours;
+    let result = rt.block_on(async {
+        let client = tmz_core::TeamsClient::new()?;
+        let me = client.user_id().await?;
+        let Some(peer) = tmz_core::hours::one_on_one_peer(&conv_id, &me) else {
+            return Ok(None);
+        };
+        tmz_core::availability::lookup(&client, peer, fallback)
+            .await
+            .map(|(_, availability)| Some(availability))
+    });
+    match result {
+        Ok(Some(availability)) => {
+            let summary = availability.summary(chrono::Utc::now());
+            app.availability = Some((conv_id, summary));
+        }
+        Ok(None) => {}
+        Err(e) => log::debug!("looking up availability: {e}"),
+    }
+}
+
 fn load_selected_chat(app: &mut App, rt: &tokio::runtime::Runtime) {
     if let Some(conv) = app.selected_conversation() {
         let id = conv.id.clone();
         if let Some(ref cache) = app.cache
-            && let Ok(msgs) = rt.block_on(cache.get_messages(&id, 200))
+            && let Ok(msgs) = rt.block_on(cache.get_messages_without_raw(&id, 200))
         {
             app.messages = msgs;
             app.msg_scroll_bottom();
         }
     }
+    load_outgoing(app, rt);
+    if app.translate {
+        translate_messages(app, rt);
+    }
+}
+
+/// Outgoing messages sent to the open chat, for the delivery markers.
+fn load_outgoing(app: &mut App, rt: &tokio::runtime::Runtime) {
+    app.outgoing = match (&app.cache, app.selected_conversation()) {
+        (Some(cache), Some(conv)) => rt
+            .block_on(cache.outgoing_history(Some(&conv.id), 50))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+}
+
+/// Replace the loaded messages' text with translations (`T`). Translations
+/// are cached, so only new messages cost a request.
+fn translate_messages(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let Some(ref cache) = app.cache else {
+        return;
+    };
+    let config = &app.config.ai.translation;
+    let lang = config.target_language.clone();
+    match rt.block_on(tmz_core::translate::translate_messages(
+        cache,
+        config,
+        &app.messages,
+        &lang,
+    )) {
+        Ok(translations) => {
+            for msg in &mut app.messages {
+                if let Some(t) = translations.get(&msg.id)
+                    && !t.is_from(&lang)
+                {
+                    let from = t.detected_language.as_deref().unwrap_or("?");
+                    msg.content = format!("{}\n[{from} \u{2192} {lang}]", t.text);
+                }
+            }
+            app.status_msg = format!("Translated into {lang}");
+        }
+        Err(e) => {
+            app.translate = false;
+            app.status_msg = format!("Translation failed: {e}");
+        }
+    }
+}
+
+/// Send the composer's text, if any, then upload the attached files once
+/// the text is out of the input (sent, queued, or handled as a command).
+fn send_composed(app: &mut App, rt: &tokio::runtime::Runtime) {
+    if !app.input.is_empty() {
+        send_message(app, rt);
+        if !app.input.is_empty() {
+            return;
+        }
+    }
+    let Some(conv_id) = app.selected_conversation().map(|c| c.id.clone()) else {
+        return;
+    };
+    let files = std::mem::take(&mut app.attachments);
+    for (i, path) in files.iter().enumerate() {
+        if !upload_file(app, rt, &conv_id, path) {
+            // Keep the failed file and the rest for another try.
+            app.attachments = files[i..].to_vec();
+            return;
+        }
+    }
+    if files.len() > 1 {
+        app.status_msg = format!("Uploaded {} files", files.len());
+    }
 }
 
 fn send_message(app: &mut App, rt: &tokio::runtime::Runtime) {
@@ -526,51 +2303,380 @@ fn send_message(app: &mut App, rt: &tokio::runtime::Runtime) {
         return;
     };
     let conv_id = conv.id.clone();
-    let text = app.input.clone();
+    let Some(text) = expand_slash_command(app, rt, &conv_id) else {
+        return;
+    };
+    if let Err(e) = app.config.security.ensure_writable("sending messages") {
+        app.status_msg = e.to_string();
+        return;
+    }
+    let warnings = match tmz_core::composer::check_outbound(&app.config.composer, &app.input) {
+        Ok(hits) => hits,
+        Err(e) => {
+            // Keep the draft so it can be edited.
+            app.status_msg = e.to_string();
+            return;
+        }
+    };
+    let max = app.config.sending.max_message_bytes;
+    if text.len() > max {
+        app.status_msg = format!(
+            "Message is {} bytes, over the {max} byte limit; shorten it or attach it as a file (Ctrl+A)",
+            text.len()
+        );
+        return;
+    }
+    let ControlFlow::Continue(off_hours) = check_off_hours(app, rt, &conv_id) else {
+        return;
+    };
     app.input_clear();
 
-    match tmz_core::TeamsClient::new() {
-        Ok(client) => match rt.block_on(client.send_message(&conv_id, &text)) {
-            Ok(_) => {
-                app.status_msg = "Sent".to_string();
-                load_selected_chat(app, rt);
+    let window = app.config.sending.undo_window().unwrap_or_default();
+    if !window.is_zero()
+        && let Some(ref cache) = app.cache
+    {
+        match rt.block_on(cache.queue_outgoing(&conv_id, &text, window)) {
+            Ok(item) => {
+                if let Some(ref audit) = app.audit {
+                    let detail = format!("outbox #{}", item.id);
+                    audit.record_with("send.queue", Some(&conv_id), "ok", Some(&detail));
+                }
+                app.pending_sends.push(item.id);
+                app.status_msg =
+                    format!("Sending in {}s \u{2014} Ctrl+Z to undo", window.as_secs());
+                load_outgoing(app, rt);
             }
-            Err(e) => {
-                app.status_msg = format!("Send failed: {e}");
+            Err(e) => app.status_msg = format!("Send failed: {e}"),
+        }
+        return;
+    }
+
+    let sent = rt.block_on(tmz_core::outbox::track(
+        app.cache.as_ref(),
+        &conv_id,
+        &text,
+        OutgoingKind::Text,
+        tmz_core::engine::send_message(&conv_id, &text),
+    ));
+    if let Some(ref audit) = app.audit {
+        audit.record("send", Some(&conv_id), &sent);
+    }
+    match sent {
+        Ok(_) => {
+            let warning = warnings
+                .first()
+                .map(|hit| hit.message.clone())
+                .or(off_hours);
+            app.status_msg = warning.map_or_else(
+                || "Sent".to_string(),
+                |warning| format!("Sent (warning: {warning})"),
+            );
+            load_selected_chat(app, rt);
+        }
+        Err(e) => {
+            app.status_msg = format!("Send failed: {e}");
+            offer_login(
+                app,
+                &e,
+                AuthRetry::Send {
+                    conversation_id: conv_id,
+                    text,
+                },
+            );
+            load_outgoing(app, rt);
+        }
+    }
+}
+
+/// The message HTML to send for the input: the escaped text, or the result
+/// of its slash command (`/me`, `/code`, `/shrug`), silenced for `[sending]
+/// silent`. `/file` and `/react` are carried out here and give `None`, as
+/// do invalid commands, which keep the draft.
+fn expand_slash_command(
+    app: &mut App,
+    rt: &tokio::runtime::Runtime,
+    conv_id: &str,
+) -> Option<String> {
+    use tmz_core::slash::SlashCommand;
+    let me = app
+        .cache
+        .as_ref()
+        .and_then(|cache| rt.block_on(cache.my_display_name()).ok().flatten());
+    let html = match tmz_core::slash::parse(&app.input, me.as_deref()) {
+        Ok(None) => tmz_core::markup::BodyFormat::Text.to_html(&app.input),
+        Ok(Some(SlashCommand::Send(html))) => html,
+        Ok(Some(SlashCommand::File(path))) => {
+            upload_file(app, rt, conv_id, &path);
+            return None;
+        }
+        Ok(Some(SlashCommand::React(key))) => {
+            let newest = app
+                .messages
+                .iter()
+                .rev()
+                .find(|m| !m.is_from_me && !m.is_system_event())
+                .map(|m| m.id.clone());
+            match newest {
+                Some(msg_id) => {
+                    app.input_clear();
+                    react(app, rt, conv_id, &msg_id, &key);
+                }
+                None => app.status_msg = "No message from someone else to react to".to_string(),
             }
-        },
+            return None;
+        }
+        Err(e) => {
+            app.status_msg = e.to_string();
+            return None;
+        }
+    };
+    Some(if app.config.sending.silent {
+        tmz_core::markup::silence(&html)
+    } else {
+        html
+    })
+}
+
+/// `/file` and attachments: upload `path` to `conv_id`. Returns whether it
+/// was uploaded.
+fn upload_file(app: &mut App, rt: &tokio::runtime::Runtime, conv_id: &str, path: &Path) -> bool {
+    let allowed = app
+        .config
+        .security
+        .ensure_writable("uploading files")
+        .and_then(|()| app.restrictions(conv_id).check(Action::Upload));
+    if let Err(e) = allowed {
+        app.status_msg = e.to_string();
+        return false;
+    }
+    if !path.is_file() {
+        app.status_msg = format!("File not found: {}", path.display());
+        return false;
+    }
+    let uploaded = rt.block_on(tmz_core::outbox::track(
+        app.cache.as_ref(),
+        conv_id,
+        &path.display().to_string(),
+        OutgoingKind::File,
+        async { tmz_core::TeamsClient::new()?.send_file(conv_id, path).await },
+    ));
+    if let Some(ref audit) = app.audit {
+        audit.record("upload", Some(conv_id), &uploaded);
+    }
+    match uploaded {
+        Ok(_) => {
+            app.input_clear();
+            app.status_msg = "Uploaded".to_string();
+            load_selected_chat(app, rt);
+            true
+        }
         Err(e) => {
-            app.status_msg = format!("Not connected: {e}");
+            app.status_msg = format!("Upload failed: {e}");
+            load_outgoing(app, rt);
+            false
         }
     }
 }
 
+/// Apply `[sending] off_hours` before sending to `conv_id`. Breaks to hold
+/// the draft (blocked, or waiting for a second Enter); otherwise continues
+/// with the warning to show after sending, if any.
+fn check_off_hours(
+    app: &mut App,
+    rt: &tokio::runtime::Runtime,
+    conv_id: &str,
+) -> ControlFlow<(), Option<String>> {
+    use tmz_core::OffHoursAction;
+    if app.off_hours_ok.take().as_deref() == Some(conv_id) {
+        return ControlFlow::Continue(None);
+    }
+    let off = match rt.block_on(tmz_core::hours::check_recipient(
+        &app.config.sending,
+        conv_id,
+    )) {
+        Ok(Some(off)) => off,
+        Ok(None) => return ControlFlow::Continue(None),
+        Err(e @ tmz_core::CoreError::Config(_)) => {
+            app.status_msg = e.to_string();
+            return ControlFlow::Break(());
+        }
+        Err(e) => {
+            log::debug!("checking working hours: {e}");
+            return ControlFlow::Continue(None);
+        }
+    };
+    let who = app
+        .selected_conversation()
+        .map_or_else(|| "them".to_string(), |c| c.display_name.clone());
+    let reason = off.describe(&who);
+    let schedule = off
+        .schedule_hint(chrono::Local::now())
+        .map(|h| format!("tmz msg --schedule \"{h}\""));
+    match app.config.sending.off_hours {
+        OffHoursAction::Off => ControlFlow::Continue(None),
+        OffHoursAction::Warn => ControlFlow::Continue(Some(reason)),
+        OffHoursAction::Confirm => {
+            app.off_hours_ok = Some(conv_id.to_string());
+            let or = schedule.map_or_else(String::new, |s| format!(" or use {s}"));
+            app.status_msg = format!("Note: {reason}. Enter again to send anyway{or}");
+            ControlFlow::Break(())
+        }
+        OffHoursAction::Block => {
+            let or = schedule.map_or_else(String::new, |s| format!(" (use {s})"));
+            app.status_msg = format!("Not sent: {reason}{or}");
+            ControlFlow::Break(())
+        }
+    }
+}
+
+/// React to the picker's message with the selected reaction.
+fn add_reaction(app: &mut App, rt: &tokio::runtime::Runtime) {
+    app.mode = Mode::Normal;
+    let Some(reaction) = app.reaction_choices().into_iter().nth(app.react_choice) else {
+        return;
+    };
+    let Some(msg) = app.messages.get(app.react_index) else {
+        return;
+    };
+    let (conv_id, msg_id) = (msg.conversation_id.clone(), msg.id.clone());
+    react(app, rt, &conv_id, &msg_id, &reaction);
+}
+
+/// React to `msg_id` in `conv_id` with `reaction`.
+fn react(app: &mut App, rt: &tokio::runtime::Runtime, conv_id: &str, msg_id: &str, reaction: &str) {
+    let allowed = app
+        .config
+        .security
+        .ensure_writable("adding reactions")
+        .and_then(|()| app.restrictions(conv_id).check(Action::React));
+    if let Err(e) = allowed {
+        app.status_msg = e.to_string();
+        return;
+    }
+    let result = rt.block_on(async {
+        tmz_core::TeamsClient::new()?
+            .add_reaction(conv_id, msg_id, reaction)
+            .await
+    });
+    if let Some(ref audit) = app.audit {
+        audit.record("react", Some(conv_id), &result);
+    }
+    let label = tmz_core::reactions::emoji(reaction).unwrap_or_else(|| reaction.to_string());
+    match result {
+        Ok(()) => {
+            tmz_core::reactions::remember(&mut app.recent_reactions, reaction);
+            app.status_msg = format!("Reacted {label}");
+        }
+        Err(e) => {
+            app.status_msg = format!("Reaction failed: {e}");
+            offer_login(
+                app,
+                &e,
+                AuthRetry::React {
+                    conversation_id: conv_id.to_string(),
+                    message_id: msg_id.to_string(),
+                    reaction: reaction.to_string(),
+                },
+            );
+        }
+    }
+}
+
+/// Cancel the most recent message still in the undo window and put its
+/// text back in the input.
+fn undo_send(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let Some(ref cache) = app.cache else {
+        return;
+    };
+    while let Some(id) = app.pending_sends.pop() {
+        if let Ok(Some(item)) = rt.block_on(cache.cancel_outgoing(id)) {
+            if let Some(ref audit) = app.audit {
+                audit.record_with("outbox.cancel", Some(&format!("#{id}")), "ok", None);
+            }
+            app.input = tmz_core::markup::html_to_text(&item.content);
+            app.cursor_pos = app.input.len();
+            app.mode = Mode::Insert;
+            app.focus = Focus::Input;
+            app.status_msg = "Send cancelled".to_string();
+            load_outgoing(app, rt);
+            return;
+        }
+    }
+    app.status_msg = "Nothing to undo".to_string();
+}
+
+/// Send outbox messages whose undo window has passed.
+fn dispatch_outbox(app: &mut App, rt: &tokio::runtime::Runtime) {
+    let Some(ref cache) = app.cache else {
+        return;
+    };
+    let audit = app.audit.as_ref();
+    let sent = rt.block_on(tmz_core::outbox::dispatch_due(
+        cache,
+        None,
+        |conv, content| async move {
+            let sent = tmz_core::engine::send_message(&conv, &content).await;
+            if let Some(audit) = audit {
+                audit.record("send", Some(&conv), &sent);
+            }
+            sent
+        },
+    ));
+    match sent {
+        Ok(sent) if !sent.is_empty() => {
+            app.pending_sends
+                .retain(|id| !sent.iter().any(|s| s.id == *id));
+            app.status_msg = "Sent".to_string();
+            load_selected_chat(app, rt);
+        }
+        // Keep the markers of queued messages current.
+        Ok(_) if !app.pending_sends.is_empty() => load_outgoing(app, rt),
+        Ok(_) => {}
+        Err(e) => app.status_msg = format!("Outbox: {e}"),
+    }
+}
+
 fn trigger_sync(app: &mut App, rt: &tokio::runtime::Runtime) {
     app.status_msg = "Syncing...".to_string();
     app.syncing = true;
 
-    match tmz_core::TeamsClient::new() {
-        Ok(client) => {
-            if let Ok(data) = rt.block_on(client.list_chats()) {
-                if let Some(ref cache) = app.cache {
-                    if let Some(convs) = data["conversations"].as_array() {
-                        for conv in convs {
-                            let cached = tmz_core::cache::parse_conversation(conv);
-                            let _ = rt.block_on(cache.upsert_conversation(&cached));
-                        }
-                    }
-                    if let Ok(convs) = rt.block_on(cache.list_conversations(500)) {
-                        app.conversations = convs;
-                        app.filter_conversations();
-                    }
-                }
+    // Prefer the daemon's engine so the TUI doesn't write the cache itself.
+    if let Some(mut engine) = rt.block_on(tmz_core::EngineClient::connect()) {
+        let opts = tmz_core::SyncOptions {
+            chats: 0,
+            ..tmz_core::SyncOptions::default()
+        };
+        match rt.block_on(engine.sync(opts)) {
+            Ok(_) => {
+                reload_conversations(app, rt);
+                refresh_counts(app, rt);
                 app.status_msg = "Synced".to_string();
-            } else {
-                app.status_msg = "Sync failed".to_string();
             }
+            Err(e) => {
+                app.status_msg = format!("Sync failed: {e}");
+                offer_login(app, &e, AuthRetry::Sync);
+            }
+        }
+        app.syncing = false;
+        app.last_sync = Some(Instant::now());
+        return;
+    }
+
+    match tmz_core::TeamsClient::new().and_then(|client| rt.block_on(client.list_chats())) {
+        Ok(convs) => {
+            if let Some(ref cache) = app.cache {
+                for conv in &convs {
+                    let cached = tmz_core::cache::parse_conversation(conv);
+                    let _ = rt.block_on(cache.upsert_conversation(&cached));
+                }
+            }
+            reload_conversations(app, rt);
+            refresh_counts(app, rt);
+            app.status_msg = "Synced".to_string();
         }
         Err(e) => {
             app.status_msg = format!("Sync failed: {e}");
+            offer_login(app, &e, AuthRetry::Sync);
         }
     }
 
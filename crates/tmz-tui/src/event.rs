@@ -21,18 +21,13 @@ pub fn spawn_event_reader(tick_rate: Duration) -> mpsc::Receiver<Event> {
     std::thread::spawn(move || {
         loop {
             if event::poll(tick_rate).unwrap_or(false) {
-                match event::read() {
-                    Ok(CEvent::Key(key)) => {
-                        if tx.send(Event::Key(key)).is_err() {
-                            return;
-                        }
-                    }
-                    Ok(CEvent::Resize(_, _)) => {
-                        if tx.send(Event::Resize).is_err() {
-                            return;
-                        }
-                    }
-                    _ => {}
+                let forwarded = match event::read() {
+                    Ok(CEvent::Key(key)) => Some(Event::Key(key)),
+                    Ok(CEvent::Resize(_, _)) => Some(Event::Resize),
+                    _ => None,
+                };
+                if forwarded.is_some_and(|event| tx.send(event).is_err()) {
+                    return;
                 }
             }
             // Always send a tick so the UI can update background state
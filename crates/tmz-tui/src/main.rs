@@ -19,6 +19,7 @@
 
 mod app;
 mod event;
+mod picker;
 mod ui;
 
 use anyhow::Result;
@@ -27,7 +28,15 @@ use std::path::PathBuf;
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    app::run(cli.common.config.as_ref())
+    if let Some(ref account) = cli.common.account {
+        tmz_core::accounts::select(account)?;
+    }
+    app::run(
+        cli.common.config.as_ref(),
+        cli.common.profile.as_deref(),
+        cli.common.compact,
+        cli.common.profile_startup,
+    )
 }
 
 #[derive(Debug, Parser)]
@@ -47,4 +56,17 @@ struct CommonOpts {
     /// Override the config file path.
     #[arg(long, value_name = "PATH")]
     config: Option<PathBuf>,
+    /// Config profile to apply (`[profiles.NAME]`; default: `TMZ_PROFILE` or `profile`).
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+    /// Named account with its own tokens and cache (`default` for the
+    /// unnamed one).
+    #[arg(long, value_name = "NAME", env = "TMZ_ACCOUNT")]
+    account: Option<String>,
+    /// Show messages as compact one-liners (overrides `[display] density`).
+    #[arg(long)]
+    compact: bool,
+    /// Print how long each startup phase took after exiting.
+    #[arg(long)]
+    profile_startup: bool,
 }
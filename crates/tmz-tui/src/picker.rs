@@ -0,0 +1,197 @@
+//! File picker for attachments (`Ctrl+A` in the composer).
+//!
+//! Shows one directory at a time, starting in the working directory (or
+//! home if that is gone). Typing filters its entries fuzzily; hidden ones
+//! show up once the query starts with `.`. The marked files are queued and
+//! uploaded after the message is sent.
+
+use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Matcher};
+use std::path::{Path, PathBuf};
+
+/// A directory entry.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// File name, `..` for the parent directory.
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// Size in bytes (0 for directories).
+    pub size: u64,
+}
+
+/// State of the open picker.
+pub struct FilePicker {
+    pub dir: PathBuf,
+    pub query: String,
+    entries: Vec<Entry>,
+    /// Indexes into the directory's entries matching `query`, best first.
+    pub matches: Vec<usize>,
+    pub selected: usize,
+    /// Files picked so far, in the order they were marked.
+    pub marked: Vec<PathBuf>,
+    /// Why the directory could not be read.
+    pub error: Option<String>,
+    matcher: Matcher,
+}
+
+impl FilePicker {
+    /// Open the picker in the working directory with `marked` (the files
+    /// already queued) marked.
+    pub fn open(marked: Vec<PathBuf>) -> Self {
+        let dir = std::env::current_dir()
+            .ok()
+            .or_else(home_dir)
+            .unwrap_or_else(|| PathBuf::from("/"));
+        let mut picker = Self {
+            dir: PathBuf::new(),
+            query: String::new(),
+            entries: Vec::new(),
+            matches: Vec::new(),
+            selected: 0,
+            marked,
+            error: None,
+            matcher: Matcher::new(Config::DEFAULT.match_paths()),
+        };
+        picker.cd(dir);
+        picker
+    }
+
+    /// The entry at `matches[index]`.
+    pub fn entry(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(*self.matches.get(index)?)
+    }
+
+    /// The highlighted entry.
+    pub fn current(&self) -> Option<&Entry> {
+        self.entry(self.selected)
+    }
+
+    /// Whether `path` is marked.
+    pub fn is_marked(&self, path: &Path) -> bool {
+        self.marked.iter().any(|p| p == path)
+    }
+
+    /// List `dir` and clear the query.
+    pub fn cd(&mut self, dir: PathBuf) {
+        self.entries.clear();
+        self.error = None;
+        if let Some(parent) = dir.parent() {
+            self.entries.push(Entry {
+                name: "..".to_string(),
+                path: parent.to_path_buf(),
+                is_dir: true,
+                size: 0,
+            });
+        }
+        match std::fs::read_dir(&dir) {
+            Ok(read) => {
+                let mut listed: Vec<Entry> = read
+                    .filter_map(Result::ok)
+                    .map(|item| {
+                        // Follow symlinks, so a link to a directory opens it.
+                        let meta = std::fs::metadata(item.path()).ok();
+                        let is_dir = meta.as_ref().is_some_and(std::fs::Metadata::is_dir);
+                        Entry {
+                            name: item.file_name().to_string_lossy().into_owned(),
+                            path: item.path(),
+                            is_dir,
+                            size: meta.filter(|_| !is_dir).map_or(0, |m| m.len()),
+                        }
+                    })
+                    .collect();
+                listed.sort_by(|a, b| {
+                    b.is_dir
+                        .cmp(&a.is_dir)
+                        .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+                });
+                self.entries.extend(listed);
+            }
+            Err(e) => self.error = Some(format!("{}: {e}", dir.display())),
+        }
+        self.dir = dir;
+        self.query.clear();
+        self.filter();
+    }
+
+    /// Go to the parent directory.
+    pub fn up(&mut self) {
+        if let Some(parent) = self.dir.parent() {
+            self.cd(parent.to_path_buf());
+        }
+    }
+
+    /// Go to the home directory.
+    pub fn home(&mut self) {
+        if let Some(home) = home_dir() {
+            self.cd(home);
+        }
+    }
+
+    /// Narrow the entries to those matching the query, best match first.
+    /// Without a query, hidden entries are left out and the listing order
+    /// is kept.
+    pub fn filter(&mut self) {
+        let show_hidden = self.query.starts_with('.');
+        let visible = self.entries.iter().enumerate().filter(|(_, e)| {
+            (e.name == ".." && self.query.is_empty()) || show_hidden || !e.name.starts_with('.')
+        });
+        self.matches = if self.query.is_empty() {
+            visible.map(|(i, _)| i).collect()
+        } else {
+            let pattern = Pattern::parse(&self.query, CaseMatching::Smart, Normalization::Smart);
+            let mut buf = Vec::new();
+            let mut scored: Vec<(usize, u32)> = visible
+                .filter(|(_, e)| e.name != "..")
+                .filter_map(|(i, e)| {
+                    let haystack = nucleo_matcher::Utf32Str::new(&e.name, &mut buf);
+                    pattern.score(haystack, &mut self.matcher).map(|s| (i, s))
+                })
+                .collect();
+            scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            scored.into_iter().map(|(i, _)| i).collect()
+        };
+        self.selected = 0;
+    }
+
+    /// Move the highlight by `delta` rows.
+    pub fn move_by(&mut self, delta: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Mark or unmark the highlighted file.
+    pub fn toggle_mark(&mut self) {
+        let Some(entry) = self.current().filter(|e| !e.is_dir) else {
+            return;
+        };
+        let path = entry.path.clone();
+        if let Some(pos) = self.marked.iter().position(|p| *p == path) {
+            self.marked.remove(pos);
+        } else {
+            self.marked.push(path);
+        }
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// `bytes` as a short human-readable size.
+#[expect(clippy::cast_precision_loss, reason = "one decimal is shown")]
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
@@ -1,16 +1,23 @@
 //! UI rendering.
 
-use crate::app::{App, Focus, Mode, SideTab};
+use crate::app::{App, Focus, Mode, REACTION_COLUMNS, SideTab, register_name};
+use crate::picker::format_size;
 use ratatui::{
     Frame,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{
-        Block, BorderType, Borders, Clear, List, ListItem, Padding, Paragraph, Scrollbar,
-        ScrollbarOrientation, ScrollbarState, Wrap,
+        Block, BorderType, Borders, Clear, List, ListItem, ListState, Padding, Paragraph,
+        Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap,
     },
 };
+use std::collections::HashSet;
+use std::path::Path;
+use tmz_core::DisplayConfig;
+use tmz_core::colors::AnsiColor;
+use tmz_core::inspect::TokenKind;
+use unicode_width::UnicodeWidthStr;
 
 // ─── Colors ──────────────────────────────────────────────────────────
 
@@ -21,12 +28,12 @@ use ratatui::{
 
 const ACCENT: Color = Color::Blue;
 const SELF_COLOR: Color = Color::Cyan;
-const OTHER_COLOR: Color = Color::Yellow;
 const DIM: Color = Color::DarkGray;
 // 236 = #303030, subtle highlight that works across themes
 const BG_SELECTED: Color = Color::Indexed(236);
 const BG_INPUT: Color = Color::Reset;
 const SEARCH_HIGHLIGHT: Color = Color::Yellow;
+const TAG_CHIP: Color = Color::LightBlue;
 
 // Thread type bar colors
 const BAR_CHAT: Color = Color::Cyan;
@@ -42,9 +49,21 @@ pub fn draw(f: &mut Frame<'_>, app: &App) {
         .constraints([Constraint::Min(0), Constraint::Length(1)])
         .split(f.area());
 
-    let main_area = outer[0];
+    let mut main_area = outer[0];
     let status_area = outer[1];
 
+    if !app.health.is_empty() {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(u16::try_from(app.health.len()).unwrap_or(u16::MAX)),
+                Constraint::Min(0),
+            ])
+            .split(main_area);
+        draw_health_banners(f, app, rows[0]);
+        main_area = rows[1];
+    }
+
     // Main 3-panel layout (or 2 if files hidden)
     let constraints = if app.show_files {
         vec![
@@ -79,8 +98,15 @@ pub fn draw(f: &mut Frame<'_>, app: &App) {
     draw_status_bar(f, app, status_area);
 
     // Overlays
-    if matches!(app.mode, Mode::Help) {
-        draw_help(f);
+    match app.mode {
+        Mode::Help => draw_help(f),
+        Mode::Inspect => draw_inspector(f, app),
+        Mode::React => draw_reaction_picker(f, app),
+        Mode::Yanks => draw_yank_picker(f, app),
+        Mode::Scripts => draw_script_picker(f, app),
+        Mode::Switcher => draw_switcher(f, app),
+        Mode::Attach => draw_file_picker(f, app),
+        _ => {}
     }
 }
 
@@ -101,6 +127,10 @@ fn draw_chat_list(f: &mut Frame<'_>, app: &App, area: Rect) {
         tab_span("teams", app.side_tab == SideTab::Teams, is_focused),
         Span::raw(" "),
         tab_span("chan", app.side_tab == SideTab::Channels, is_focused),
+        Span::raw(" "),
+        tab_span("\u{2605}", app.side_tab == SideTab::Saved, is_focused),
+        Span::raw(" "),
+        tab_span("time", app.side_tab == SideTab::Timeline, is_focused),
     ]);
 
     let block = Block::default()
@@ -112,13 +142,14 @@ fn draw_chat_list(f: &mut Frame<'_>, app: &App, area: Rect) {
     let inner = block.inner(area);
     f.render_widget(block, area);
 
-    // Layout: tabs + search + list
+    // Layout: tabs + search + list + saved searches
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(1), // tabs
-            Constraint::Length(1), // search
-            Constraint::Min(0),    // list
+            Constraint::Length(1),                          // tabs
+            Constraint::Length(1),                          // search
+            Constraint::Min(0),                             // list
+            Constraint::Length(saved_searches_height(app)), // saved searches
         ])
         .split(inner);
 
@@ -139,63 +170,255 @@ fn draw_chat_list(f: &mut Frame<'_>, app: &App, area: Rect) {
 
     // Conversation list
     let max_name_len = (area.width as usize).saturating_sub(6); // border + padding + bar + space
-    let items: Vec<ListItem<'_>> = app
-        .filtered_conversations
-        .iter()
-        .enumerate()
-        .map(|(i, &conv_idx)| {
-            let conv = &app.conversations[conv_idx];
-            let name = if conv.display_name.is_empty() {
-                conv.member_names.as_str()
-            } else {
-                conv.display_name.as_str()
-            };
-            let truncated: String = name.chars().take(max_name_len).collect();
-
-            let preview: String = conv
-                .last_message_preview
-                .chars()
-                .take(max_name_len.saturating_sub(1))
-                .collect::<String>()
-                .replace('\n', " ");
+    let items: Vec<ListItem<'_>> = if app.side_tab == SideTab::Saved {
+        app.filtered_saved
+            .iter()
+            .enumerate()
+            .map(|(i, &idx)| saved_list_item(app, idx, i == app.chat_selected, max_name_len))
+            .collect()
+    } else if app.side_tab == SideTab::Timeline {
+        app.filtered_timeline
+            .iter()
+            .zip(&app.filtered_conversations)
+            .enumerate()
+            .map(|(i, (&idx, &conv_idx))| {
+                timeline_list_item(app, idx, conv_idx, i == app.chat_selected, max_name_len)
+            })
+            .collect()
+    } else {
+        app.filtered_conversations
+            .iter()
+            .enumerate()
+            .map(|(i, &conv_idx)| {
+                chat_list_item(app, conv_idx, i == app.chat_selected, max_name_len)
+            })
+            .collect()
+    };
 
-            let is_selected = i == app.chat_selected;
-            let bar_color = thread_type_color(&conv.thread_type);
+    // Stateful so the list scrolls to keep the selection visible
+    let list = List::new(items);
+    let mut state = ListState::default().with_selected(Some(app.chat_selected));
+    f.render_stateful_widget(list, chunks[2], &mut state);
 
-            let bar_style = if is_selected {
-                Style::default().fg(bar_color).bg(BG_SELECTED)
-            } else {
-                Style::default().fg(bar_color)
-            };
-            let name_style = if is_selected {
-                Style::default()
-                    .bg(BG_SELECTED)
-                    .fg(Color::White)
-                    .add_modifier(Modifier::BOLD)
-            } else {
-                Style::default().fg(Color::Gray)
-            };
-            let preview_style = if is_selected {
-                Style::default().bg(BG_SELECTED).fg(DIM)
-            } else {
-                Style::default().fg(DIM)
-            };
+    draw_saved_searches(f, app, chunks[3]);
+}
 
-            ListItem::new(vec![
-                Line::from(vec![
-                    Span::styled("│ ", bar_style),
-                    Span::styled(truncated, name_style),
-                ]),
-                Line::from(vec![
-                    Span::styled("│ ", bar_style),
-                    Span::styled(preview, preview_style),
-                ]),
-            ])
+/// One conversation in the chat list: name with tag chips, then the
+/// last message.
+fn chat_list_item(
+    app: &App,
+    conv_idx: usize,
+    is_selected: bool,
+    max_name_len: usize,
+) -> ListItem<'_> {
+    let conv = &app.conversations[conv_idx];
+    let name = if conv.display_name.is_empty() {
+        conv.member_names.as_str()
+    } else {
+        conv.display_name.as_str()
+    };
+    let chips = app
+        .conversation_tags
+        .get(&conv.id)
+        .map(|tags| {
+            tags.iter()
+                .fold(String::new(), |chips, tag| chips + " #" + tag.as_str())
         })
+        .unwrap_or_default();
+    let name_len = max_name_len.saturating_sub(chips.chars().count()).max(8);
+    let truncated: String = name.chars().take(name_len).collect();
+
+    let preview: String = conv
+        .last_message_preview
+        .chars()
+        .take(max_name_len.saturating_sub(1))
+        .collect::<String>()
+        .replace('\n', " ");
+
+    let bar_color = thread_type_color(&conv.thread_type);
+
+    let bar_style = if is_selected {
+        Style::default().fg(bar_color).bg(BG_SELECTED)
+    } else {
+        Style::default().fg(bar_color)
+    };
+    let name_style = if is_selected {
+        Style::default()
+            .bg(BG_SELECTED)
+            .fg(Color::White)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Gray)
+    };
+    let preview_style = if is_selected {
+        Style::default().bg(BG_SELECTED).fg(DIM)
+    } else {
+        Style::default().fg(DIM)
+    };
+    let chip_style = if is_selected {
+        Style::default().bg(BG_SELECTED).fg(TAG_CHIP)
+    } else {
+        Style::default().fg(TAG_CHIP)
+    };
+
+    ListItem::new(vec![
+        Line::from(vec![
+            Span::styled("│ ", bar_style),
+            Span::styled(truncated, name_style),
+            Span::styled(chips, chip_style),
+        ]),
+        Line::from(vec![
+            Span::styled("│ ", bar_style),
+            Span::styled(preview, preview_style),
+        ]),
+    ])
+}
+
+/// One saved message in the saved tab: chat and sender, then the text,
+/// or the note when there is one.
+fn saved_list_item(app: &App, idx: usize, is_selected: bool, width: usize) -> ListItem<'_> {
+    let item = &app.saved_items[idx];
+    let chat = if item.conversation_name.is_empty() {
+        &item.conversation_id
+    } else {
+        &item.conversation_name
+    };
+    let header: String = format!("{chat} \u{b7} {}", item.from_display_name)
+        .chars()
+        .take(width)
         .collect();
+    let (text, text_color) = if item.note.is_empty() {
+        (&item.content, DIM)
+    } else {
+        (&item.note, TAG_CHIP)
+    };
+    let text: String = text
+        .chars()
+        .take(width.saturating_sub(1))
+        .collect::<String>()
+        .replace('\n', " ");
+
+    let bg = if is_selected {
+        BG_SELECTED
+    } else {
+        Color::Reset
+    };
+    let name_style = if is_selected {
+        Style::default()
+            .bg(bg)
+            .fg(Color::White)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Gray)
+    };
+    let bar_style = Style::default().fg(Color::Yellow).bg(bg);
+    ListItem::new(vec![
+        Line::from(vec![
+            Span::styled("\u{2605} ", bar_style),
+            Span::styled(header, name_style),
+        ]),
+        Line::from(vec![
+            Span::styled("\u{2502} ", bar_style),
+            Span::styled(text, Style::default().fg(text_color).bg(bg)),
+        ]),
+    ])
+}
 
-    let list = List::new(items);
-    f.render_widget(list, chunks[2]);
+/// One message in the timeline tab: time, sender, and chat, then the text,
+/// with the bar colored by the kind of chat.
+fn timeline_list_item(
+    app: &App,
+    idx: usize,
+    conv_idx: usize,
+    is_selected: bool,
+    width: usize,
+) -> ListItem<'_> {
+    let r = &app.timeline[idx];
+    let conv = &app.conversations[conv_idx];
+    let chat = if r.conversation_name.is_empty() {
+        &r.message.conversation_id
+    } else {
+        &r.conversation_name
+    };
+    let sender = if r.message.is_from_me {
+        "you"
+    } else {
+        &r.message.from_display_name
+    };
+    let header: String = format!(
+        "{} {sender} \u{b7} {chat}",
+        timeline_time(&r.message.compose_time)
+    )
+    .chars()
+    .take(width)
+    .collect();
+    let text: String = r
+        .message
+        .content
+        .chars()
+        .take(width.saturating_sub(1))
+        .collect::<String>()
+        .replace('\n', " ");
+
+    let bg = if is_selected {
+        BG_SELECTED
+    } else {
+        Color::Reset
+    };
+    let name_style = if is_selected {
+        Style::default()
+            .bg(bg)
+            .fg(Color::White)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Gray)
+    };
+    let bar_style = Style::default()
+        .fg(thread_type_color(&conv.thread_type))
+        .bg(bg);
+    ListItem::new(vec![
+        Line::from(vec![
+            Span::styled("\u{2502} ", bar_style),
+            Span::styled(header, name_style),
+        ]),
+        Line::from(vec![
+            Span::styled("\u{2502} ", bar_style),
+            Span::styled(text, Style::default().fg(DIM).bg(bg)),
+        ]),
+    ])
+}
+
+/// Rows needed for the saved searches section (header plus one per search).
+fn saved_searches_height(app: &App) -> u16 {
+    if app.saved_counts.is_empty() {
+        0
+    } else {
+        u16::try_from(app.saved_counts.len() + 1).unwrap_or(u16::MAX)
+    }
+}
+
+/// Saved searches with their match counts, refreshed after each sync.
+fn draw_saved_searches(f: &mut Frame<'_>, app: &App, area: Rect) {
+    let width = area.width as usize;
+    let mut lines = vec![Line::from(Span::styled(
+        "saved",
+        Style::default().fg(DIM).add_modifier(Modifier::BOLD),
+    ))];
+    for (name, count) in &app.saved_counts {
+        let count = count.map_or_else(|| "!".to_string(), |n| n.to_string());
+        let name: String = name
+            .chars()
+            .take(width.saturating_sub(count.len() + 3))
+            .collect();
+        let gap = width.saturating_sub(name.chars().count() + count.len() + 2);
+        lines.push(Line::from(vec![
+            Span::styled("\u{2315} ", Style::default().fg(ACCENT)),
+            Span::styled(name, Style::default().fg(Color::Gray)),
+            Span::raw(" ".repeat(gap)),
+            Span::styled(count, Style::default().fg(DIM)),
+        ]));
+    }
+    f.render_widget(Paragraph::new(lines), area);
 }
 
 fn tab_span(label: &str, active: bool, focused: bool) -> Span<'_> {
@@ -230,6 +453,20 @@ fn draw_messages(f: &mut Frame<'_>, app: &App, area: Rect) {
             }
         },
     );
+    let title = match (&app.availability, app.selected_conversation()) {
+        (Some((id, summary)), Some(conv)) if *id == conv.id => {
+            format!("{title}\u{b7} {summary} ")
+        }
+        _ => title,
+    };
+    let title = if app.translate {
+        format!(
+            "{title}\u{b7} {} ",
+            app.config.ai.translation.target_language
+        )
+    } else {
+        title
+    };
 
     let block = Block::default()
         .title(title)
@@ -237,9 +474,24 @@ fn draw_messages(f: &mut Frame<'_>, app: &App, area: Rect) {
         .border_type(BorderType::Rounded)
         .border_style(border_style);
 
-    let inner = block.inner(area);
+    let mut inner = block.inner(area);
     f.render_widget(block, area);
 
+    if let Some(banner) = app
+        .selected_conversation()
+        .and_then(|c| c.restrictions.banner())
+    {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(inner);
+        f.render_widget(
+            Paragraph::new(format!(" {banner}")).style(Style::default().fg(Color::Yellow)),
+            rows[0],
+        );
+        inner = rows[1];
+    }
+
     if app.messages.is_empty() {
         let empty = Paragraph::new("  No messages. Select a chat or sync first.")
             .style(Style::default().fg(DIM));
@@ -247,7 +499,22 @@ fn draw_messages(f: &mut Frame<'_>, app: &App, area: Rect) {
         return;
     }
 
-    let lines = build_message_lines(&app.messages);
+    let delivered: HashSet<&str> = app
+        .outgoing
+        .iter()
+        .filter_map(|item| item.message_id.as_deref())
+        .collect();
+    let mut lines = build_message_lines(&app.messages, &app.config.display, &delivered);
+    lines.extend(outgoing_lines(app));
+    if app.show_legend {
+        lines.splice(
+            0..0,
+            [
+                legend_line(&app.messages, &app.config.display),
+                Line::from(""),
+            ],
+        );
+    }
     let total_lines = lines.len();
     let visible = inner.height as usize;
     let max_scroll = total_lines.saturating_sub(visible);
@@ -268,12 +535,142 @@ fn draw_messages(f: &mut Frame<'_>, app: &App, area: Rect) {
     }
 }
 
-fn build_message_lines<'a>(messages: &'a [tmz_core::CachedMessage]) -> Vec<Line<'a>> {
+/// Terminal palette color for a configured ANSI color.
+const fn ansi_color(color: AnsiColor) -> Color {
+    match color {
+        AnsiColor::Red => Color::Red,
+        AnsiColor::Green => Color::Green,
+        AnsiColor::Yellow => Color::Yellow,
+        AnsiColor::Blue => Color::Blue,
+        AnsiColor::Magenta => Color::Magenta,
+        AnsiColor::Cyan => Color::Cyan,
+        AnsiColor::BrightRed => Color::LightRed,
+        AnsiColor::BrightGreen => Color::LightGreen,
+        AnsiColor::BrightYellow => Color::LightYellow,
+        AnsiColor::BrightBlue => Color::LightBlue,
+        AnsiColor::BrightMagenta => Color::LightMagenta,
+        AnsiColor::BrightCyan => Color::LightCyan,
+    }
+}
+
+fn sender_color(name: &str, is_me: bool, display: &DisplayConfig) -> Color {
+    if is_me {
+        SELF_COLOR
+    } else {
+        ansi_color(display.sender_color(name))
+    }
+}
+
+/// One line naming every sender in the chat in their color.
+fn legend_line<'a>(messages: &'a [tmz_core::CachedMessage], display: &DisplayConfig) -> Line<'a> {
+    let mut seen: Vec<&str> = Vec::new();
+    let mut spans = vec![Span::raw(" ")];
+    for msg in messages {
+        let name = msg.from_display_name.as_str();
+        if name.is_empty() || seen.contains(&name) {
+            continue;
+        }
+        seen.push(name);
+        spans.push(Span::styled(
+            format!(" \u{25cf} {name} "),
+            Style::default().fg(sender_color(name, msg.is_from_me, display)),
+        ));
+    }
+    Line::from(spans)
+}
+
+/// Dim check mark after a message the service acknowledged.
+fn delivered_mark() -> Span<'static> {
+    Span::styled(" \u{2713}", Style::default().fg(DIM))
+}
+
+/// Add [`delivered_mark`] to the last line of `msg` if it is in
+/// `delivered`.
+fn mark_delivered(
+    lines: &mut [Line<'_>],
+    msg: &tmz_core::CachedMessage,
+    delivered: &HashSet<&str>,
+) {
+    if msg.is_from_me
+        && delivered.contains(msg.id.as_str())
+        && let Some(last) = lines.last_mut()
+    {
+        last.spans.push(delivered_mark());
+    }
+}
+
+/// Messages sent from here that are not in the chat yet: queued, in
+/// flight, failed, or sent and waiting for the next sync.
+fn outgoing_lines(app: &App) -> Vec<Line<'_>> {
+    let newest = app
+        .messages
+        .last()
+        .map_or("", |msg| msg.compose_time.as_str());
+    app.outgoing
+        .iter()
+        .rev()
+        .filter(|item| match item.status.as_str() {
+            "pending" | "sending" | "uploading" => true,
+            "sent" => {
+                item.queued_at.as_str() > newest
+                    && !app
+                        .messages
+                        .iter()
+                        .any(|msg| Some(msg.id.as_str()) == item.message_id.as_deref())
+            }
+            "failed" => item.queued_at.as_str() > newest,
+            _ => false,
+        })
+        .map(|item| {
+            let (marker, color) = match item.status.as_str() {
+                "sent" => ("\u{2713} sent", DIM),
+                "failed" => ("\u{2717} failed", Color::Red),
+                "uploading" => ("\u{2191} uploading", Color::Yellow),
+                "sending" => ("\u{2026} sending", Color::Yellow),
+                _ => ("\u{2026} queued", Color::Yellow),
+            };
+            let text = if item.kind == "file" {
+                Path::new(&item.content).file_name().map_or_else(
+                    || item.content.clone(),
+                    |name| name.to_string_lossy().into_owned(),
+                )
+            } else {
+                tmz_core::cache::strip_html(&item.content)
+            };
+            let mut spans = vec![
+                Span::styled(format!("  {marker} "), Style::default().fg(color)),
+                Span::styled(
+                    text.lines().next().unwrap_or_default().to_string(),
+                    Style::default().fg(DIM),
+                ),
+            ];
+            if let Some(ref error) = item.error {
+                spans.push(Span::styled(
+                    format!("  {error}"),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+fn build_message_lines<'a>(
+    messages: &'a [tmz_core::CachedMessage],
+    display: &DisplayConfig,
+    delivered: &HashSet<&str>,
+) -> Vec<Line<'a>> {
+    if display.is_compact() {
+        return build_compact_lines(messages, display, delivered);
+    }
     let mut lines: Vec<Line<'a>> = Vec::new();
     let mut prev_sender: Option<&str> = None;
     let mut prev_date: Option<String> = None;
 
     for msg in messages {
+        if msg.is_system_event() && !display.system_events {
+            continue;
+        }
         let date = msg.compose_time.split('T').next().unwrap_or("");
         if prev_date.as_deref() != Some(date) {
             if !lines.is_empty() {
@@ -289,6 +686,15 @@ fn build_message_lines<'a>(messages: &'a [tmz_core::CachedMessage]) -> Vec<Line<
             prev_date = Some(date.to_string());
         }
 
+        if msg.is_system_event() {
+            if prev_sender.is_some() {
+                lines.push(Line::from(""));
+            }
+            lines.push(system_event_line(msg));
+            prev_sender = None;
+            continue;
+        }
+
         let sender = &msg.from_display_name;
         let time = extract_time(&msg.compose_time);
         let is_me = msg.is_from_me;
@@ -297,7 +703,7 @@ fn build_message_lines<'a>(messages: &'a [tmz_core::CachedMessage]) -> Vec<Line<
             if prev_sender.is_some() {
                 lines.push(Line::from(""));
             }
-            let color = if is_me { SELF_COLOR } else { OTHER_COLOR };
+            let color = sender_color(sender, is_me, display);
             lines.push(Line::from(vec![
                 Span::styled("  | ", Style::default().fg(color)),
                 Span::styled(
@@ -309,7 +715,12 @@ fn build_message_lines<'a>(messages: &'a [tmz_core::CachedMessage]) -> Vec<Line<
             prev_sender = Some(sender.as_str());
         }
 
-        let color = if is_me { SELF_COLOR } else { OTHER_COLOR };
+        let color = sender_color(sender, is_me, display);
+        if let Some(headline) = post_headline(msg) {
+            let mut spans = vec![Span::styled("  | ", Style::default().fg(color))];
+            spans.extend(headline);
+            lines.push(Line::from(spans));
+        }
         let content = if msg.content.is_empty() {
             "[image]"
         } else {
@@ -324,6 +735,106 @@ fn build_message_lines<'a>(messages: &'a [tmz_core::CachedMessage]) -> Vec<Line<
                 ]));
             }
         }
+        mark_delivered(&mut lines, msg, delivered);
+    }
+
+    lines
+}
+
+/// Bold subject line of a channel post, with a red `!` when it is marked
+/// important.
+fn post_headline(msg: &tmz_core::CachedMessage) -> Option<Vec<Span<'_>>> {
+    let alert = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    if msg.important {
+        spans.push(Span::styled("! ", alert));
+    }
+    if !msg.subject.is_empty() {
+        spans.push(Span::styled(
+            msg.subject.as_str(),
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ));
+    } else if msg.important {
+        spans.push(Span::styled("Important", alert));
+    }
+    (!spans.is_empty()).then_some(spans)
+}
+
+/// Dim `— Alice added Bob —` separator for a system event.
+fn system_event_line(msg: &tmz_core::CachedMessage) -> Line<'_> {
+    Line::from(vec![
+        Span::styled(
+            format!("  \u{2014} {} \u{2014}", msg.content),
+            Style::default().fg(DIM).add_modifier(Modifier::ITALIC),
+        ),
+        Span::styled(
+            format!("  {}", extract_time(&msg.compose_time)),
+            Style::default().fg(DIM),
+        ),
+    ])
+}
+
+/// `HH:MM name: message` one-liners with no spacing (`density = "compact"`).
+fn build_compact_lines<'a>(
+    messages: &'a [tmz_core::CachedMessage],
+    display: &DisplayConfig,
+    delivered: &HashSet<&str>,
+) -> Vec<Line<'a>> {
+    let mut lines: Vec<Line<'a>> = Vec::new();
+    let mut prev_date: Option<&str> = None;
+
+    for msg in messages {
+        if msg.is_system_event() && !display.system_events {
+            continue;
+        }
+        let date = msg.compose_time.split('T').next().unwrap_or("");
+        if prev_date != Some(date) {
+            lines.push(Line::from(Span::styled(
+                format!(" -- {} --", format_date(date)),
+                Style::default().fg(DIM),
+            )));
+            prev_date = Some(date);
+        }
+
+        if msg.is_system_event() {
+            lines.push(system_event_line(msg));
+            continue;
+        }
+
+        let sender = msg.from_display_name.as_str();
+        let color = sender_color(sender, msg.is_from_me, display);
+        let time = extract_time(&msg.compose_time);
+        let indent = " ".repeat(time.chars().count() + sender.chars().count() + 3);
+        let content = if msg.content.is_empty() {
+            "[image]"
+        } else {
+            &msg.content
+        };
+
+        for (i, text_line) in content.lines().filter(|l| !l.is_empty()).enumerate() {
+            let text = Span::styled(text_line.to_string(), Style::default().fg(Color::White));
+            if i == 0 {
+                let mut spans = vec![
+                    Span::styled(format!("{time} "), Style::default().fg(DIM)),
+                    Span::styled(
+                        sender.to_string(),
+                        Style::default().fg(color).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(": "),
+                ];
+                if let Some(headline) = post_headline(msg) {
+                    spans.extend(headline);
+                    spans.push(Span::raw(" \u{2014} "));
+                }
+                spans.push(text);
+                lines.push(Line::from(spans));
+            } else {
+                lines.push(Line::from(vec![Span::raw(indent.clone()), text]));
+            }
+        }
+        mark_delivered(&mut lines, msg, delivered);
     }
 
     lines
@@ -339,11 +850,27 @@ fn draw_input(f: &mut Frame<'_>, app: &App, area: Rect) {
         Style::default().fg(DIM)
     };
 
-    let block = Block::default()
+    let mut block = Block::default()
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
         .border_style(border_style)
         .style(Style::default().bg(BG_INPUT));
+    if !app.attachments.is_empty() {
+        let names: Vec<String> = app
+            .attachments
+            .iter()
+            .map(|path| {
+                path.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        block = block.title(Span::styled(
+            format!(" \u{1f4ce} {} ", names.join(", ")),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
 
     let inner = block.inner(area);
     f.render_widget(block, area);
@@ -351,7 +878,7 @@ fn draw_input(f: &mut Frame<'_>, app: &App, area: Rect) {
     let display = if app.input.is_empty() && !is_focused {
         Paragraph::new("  Type a message... (i)").style(Style::default().fg(DIM))
     } else {
-        Paragraph::new(format!("  {}", app.input)).style(Style::default().fg(Color::White))
+        Paragraph::new(input_line(app)).style(Style::default().fg(Color::White))
     };
 
     f.render_widget(display, inner);
@@ -364,6 +891,29 @@ fn draw_input(f: &mut Frame<'_>, app: &App, area: Rect) {
     }
 }
 
+/// The composer text with misspelled words underlined.
+fn input_line(app: &App) -> Line<'_> {
+    let mut spans = vec![Span::raw("  ")];
+    let misspelled = app
+        .spell
+        .as_ref()
+        .map(|checker| checker.misspelled(&app.input))
+        .unwrap_or_default();
+    let mut pos = 0;
+    for range in misspelled {
+        spans.push(Span::raw(&app.input[pos..range.start]));
+        spans.push(Span::styled(
+            &app.input[range.clone()],
+            Style::default()
+                .fg(Color::LightRed)
+                .add_modifier(Modifier::UNDERLINED),
+        ));
+        pos = range.end;
+    }
+    spans.push(Span::raw(&app.input[pos..]));
+    Line::from(spans)
+}
+
 // ─── Right panel: files ──────────────────────────────────────────────
 
 fn draw_files(f: &mut Frame<'_>, app: &App, area: Rect) {
@@ -390,8 +940,9 @@ fn draw_files(f: &mut Frame<'_>, app: &App, area: Rect) {
 
 // ─── Status bar ──────────────────────────────────────────────────────
 
-fn draw_status_bar(f: &mut Frame<'_>, app: &App, area: Rect) {
-    let mode_span = match app.mode {
+/// The mode badge at the start of the status bar.
+fn mode_span(mode: Mode) -> Span<'static> {
+    match mode {
         Mode::Normal => Span::styled(
             " NORMAL ",
             Style::default().fg(Color::Black).bg(ACCENT).bold(),
@@ -418,56 +969,106 @@ fn draw_status_bar(f: &mut Frame<'_>, app: &App, area: Rect) {
             " HELP ",
             Style::default().fg(Color::Black).bg(Color::Yellow).bold(),
         ),
-    };
+        Mode::Inspect => Span::styled(
+            " INSPECT ",
+            Style::default().fg(Color::Black).bg(Color::Magenta).bold(),
+        ),
+        Mode::React => Span::styled(
+            " REACT ",
+            Style::default().fg(Color::Black).bg(Color::Yellow).bold(),
+        ),
+        Mode::Yanks => Span::styled(
+            " YANKS ",
+            Style::default().fg(Color::Black).bg(Color::Yellow).bold(),
+        ),
+        Mode::Scripts => Span::styled(
+            " SCRIPT ",
+            Style::default().fg(Color::Black).bg(Color::Green).bold(),
+        ),
+        Mode::Switcher => Span::styled(
+            " SWITCH ",
+            Style::default()
+                .fg(Color::Black)
+                .bg(SEARCH_HIGHLIGHT)
+                .bold(),
+        ),
+        Mode::Attach => Span::styled(
+            " ATTACH ",
+            Style::default().fg(Color::Black).bg(Color::Green).bold(),
+        ),
+    }
+}
+
+fn draw_status_bar(f: &mut Frame<'_>, app: &App, area: Rect) {
+    let mode_span = mode_span(app.mode);
 
     let token_span = match app.token_expires_mins {
         Some(mins) if mins > 10 => {
-            Span::styled(format!(" {mins}m "), Style::default().fg(Color::Green))
+            Span::styled(format!("{mins}m"), Style::default().fg(Color::Green))
         }
         Some(mins) if mins > 0 => {
-            Span::styled(format!(" {mins}m "), Style::default().fg(Color::Yellow))
+            Span::styled(format!("{mins}m"), Style::default().fg(Color::Yellow))
         }
-        Some(_) => Span::styled(" expired ", Style::default().fg(Color::Red)),
-        None => Span::styled(" no auth ", Style::default().fg(Color::Red)),
+        Some(_) => Span::styled("expired", Style::default().fg(Color::Red)),
+        None => Span::styled("no auth", Style::default().fg(Color::Red)),
     };
 
     let sync_span = if app.syncing {
-        Span::styled(" syncing... ", Style::default().fg(Color::Yellow))
+        Span::styled("syncing...", Style::default().fg(Color::Yellow))
     } else if let Some(last) = app.last_sync {
         let ago = last.elapsed().as_secs();
         if ago < 60 {
-            Span::styled(" synced ", Style::default().fg(Color::Green))
+            Span::styled("synced", Style::default().fg(Color::Green))
         } else {
-            Span::styled(format!(" {}m ago ", ago / 60), Style::default().fg(DIM))
+            Span::styled(format!("{}m ago", ago / 60), Style::default().fg(DIM))
         }
     } else {
-        Span::styled(" not synced ", Style::default().fg(DIM))
+        Span::styled("not synced", Style::default().fg(DIM))
     };
 
-    let status = Span::styled(format!(" {} ", app.status_msg), Style::default().fg(DIM));
-
-    let profile = Span::styled(
-        format!(" [{}] ", app.config.profile),
-        Style::default().fg(DIM),
-    );
-
-    let keys_hint = Span::styled(
-        " ? help  / search  i msg  q quit ",
-        Style::default().fg(DIM),
-    );
-
-    let line = Line::from(vec![
-        mode_span,
-        Span::raw(" "),
-        token_span,
-        Span::raw("│"),
-        sync_span,
-        Span::raw("│"),
-        status,
-        profile,
-        Span::raw(" "),
-        keys_hint,
-    ]);
+    // Segments for `[statusbar] format`; `None` means empty.
+    let field = |name: &str| -> Option<Span<'_>> {
+        let dim =
+            |text: String| (!text.is_empty()).then(|| Span::styled(text, Style::default().fg(DIM)));
+        match name {
+            "mode" => Some(mode_span.clone()),
+            "token" => Some(token_span.clone()),
+            "sync" => Some(sync_span.clone()),
+            "unread" => (app.unread_mentions > 0).then(|| {
+                Span::styled(
+                    format!("@{}", app.unread_mentions),
+                    Style::default().fg(Color::Red).bold(),
+                )
+            }),
+            "status" => dim(app.status_msg.clone()),
+            "profile" => dim(app.config.profile.clone()),
+            "keys" => dim("? help  / search  i msg  q quit".to_string()),
+            "chat" => dim(app
+                .selected_conversation()
+                .map_or_else(String::new, |conv| {
+                    if conv.display_name.is_empty() {
+                        conv.member_names.clone()
+                    } else {
+                        conv.display_name.clone()
+                    }
+                })),
+            "outbox" => (!app.pending_sends.is_empty()).then(|| {
+                Span::styled(
+                    format!("{} queued", app.pending_sends.len()),
+                    Style::default().fg(Color::Yellow),
+                )
+            }),
+            "readonly" => app
+                .config
+                .security
+                .is_read_only()
+                .then(|| Span::styled("read-only", Style::default().fg(Color::Red).bold())),
+            "time" => dim(chrono::Local::now().format("%H:%M").to_string()),
+            _ => None,
+        }
+    };
+    let text = |s: &str| Span::styled(s.to_string(), Style::default().fg(DIM));
+    let line = Line::from(app.status_format.render(&field, &text));
 
     f.render_widget(Paragraph::new(line).alignment(Alignment::Left), area);
 }
@@ -484,7 +1085,7 @@ fn draw_help(f: &mut Frame<'_>) {
         .border_type(BorderType::Rounded)
         .style(Style::default().bg(Color::Rgb(25, 25, 35)));
 
-    let help = vec![
+    let mut help = vec![
         Line::from(""),
         section("navigation"),
         key("j / k", "move up / down"),
@@ -498,8 +1099,36 @@ fn draw_help(f: &mut Frame<'_>) {
         key("Esc", "back to normal mode"),
         key("/", "search (chats or messages)"),
         key("f", "toggle files panel"),
+        key("L", "toggle sender color legend"),
+        key("D", "toggle compact / comfortable density"),
+        key("I", "inspect raw message JSON"),
+        key("r", "react to newest (or inspected) message"),
+        key("y", "yank newest (or inspected) message"),
+        key("s", "save newest (or inspected) message"),
+        key("p / \"Np", "paste last yank / register N"),
+        key("P", "yank history"),
+        key("!", "run a script action (scripts.rhai)"),
+        key("T", "toggle translation"),
+        key("Ctrl+k", "quick switch (frecent chats first)"),
         key("Ctrl+r", "sync now"),
-        key("1 2 3", "switch tabs: chats / teams / channels"),
+        key("Ctrl+z", "undo last send"),
+        key("A / S", "log in / start daemon (when asked)"),
+        key(
+            "1 - 5",
+            "switch tabs: chats / teams / channels / saved / timeline",
+        ),
+        key("Enter", "open the chat of the selected timeline message"),
+        key("x", "remove the selected saved message"),
+        Line::from(""),
+        section("composer (//text sends a literal /)"),
+        key("Ctrl+a", "attach files (Tab marks several)"),
+    ];
+    help.extend(
+        tmz_core::slash::COMMANDS
+            .iter()
+            .map(|(command, desc)| key(command, desc)),
+    );
+    help.extend([
         Line::from(""),
         section("general"),
         key("?", "toggle this help"),
@@ -509,12 +1138,395 @@ fn draw_help(f: &mut Frame<'_>) {
             "  press ? or Esc to close",
             Style::default().fg(DIM),
         )),
-    ];
+    ]);
 
     let para = Paragraph::new(help).block(block);
     f.render_widget(para, area);
 }
 
+// ─── Health banners ──────────────────────────────────────────────────
+
+/// One line per problem found by the startup health check, with the key
+/// that fixes it.
+fn draw_health_banners(f: &mut Frame<'_>, app: &App, area: Rect) {
+    use tmz_core::health::Problem;
+
+    let lines: Vec<Line<'_>> = app
+        .health
+        .iter()
+        .map(|problem| {
+            let action = match problem {
+                p if p.needs_login() => "press A to log in",
+                Problem::DaemonNotRunning => "press S to start",
+                Problem::DaemonOffline { .. } => "check the network or VPN",
+                _ => "press Ctrl+r to sync",
+            };
+            let color = if problem.needs_login() {
+                Color::Red
+            } else {
+                Color::Yellow
+            };
+            Line::from(vec![
+                Span::styled(
+                    format!(" {} \u{2014} ", problem.describe()),
+                    Style::default().fg(color),
+                ),
+                Span::styled(
+                    action,
+                    Style::default().fg(color).add_modifier(Modifier::BOLD),
+                ),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), area);
+}
+
+// ─── Raw message inspector ───────────────────────────────────────────
+
+fn draw_inspector(f: &mut Frame<'_>, app: &App) {
+    let Some(msg) = app.messages.get(app.inspect_index) else {
+        return;
+    };
+    let area = centered_rect(80, 80, f.area());
+    f.render_widget(Clear, area);
+
+    let title = format!(
+        " message {} ({}/{}) ",
+        msg.id,
+        app.inspect_index + 1,
+        app.messages.len()
+    );
+    let block = Block::default()
+        .title(title)
+        .title_bottom(" j/k scroll  h/l older/newer  Esc close ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .style(Style::default().bg(Color::Rgb(25, 25, 35)));
+
+    let lines: Vec<Line<'_>> = serde_json::from_str::<serde_json::Value>(&msg.raw_json)
+        .map_or_else(
+            |e| vec![Line::from(format!("raw_json is not valid JSON: {e}"))],
+            |raw| {
+                tmz_core::inspect::inspect_lines(&raw)
+                    .into_iter()
+                    .map(|tokens| {
+                        Line::from(
+                            tokens
+                                .into_iter()
+                                .map(|t| Span::styled(t.text, token_style(t.kind)))
+                                .collect::<Vec<_>>(),
+                        )
+                    })
+                    .collect()
+            },
+        );
+
+    let max_scroll = lines.len().saturating_sub(1);
+    let scroll = u16::try_from(app.inspect_scroll.min(max_scroll)).unwrap_or(u16::MAX);
+    let para = Paragraph::new(lines).block(block).scroll((scroll, 0));
+    f.render_widget(para, area);
+}
+
+// ─── Reaction picker ─────────────────────────────────────────────────
+
+fn draw_reaction_picker(f: &mut Frame<'_>, app: &App) {
+    let Some(msg) = app.messages.get(app.react_index) else {
+        return;
+    };
+    let choices = app.reaction_choices();
+    let rows = choices.len().div_ceil(REACTION_COLUMNS);
+    let cell_width = 16;
+    let width = u16::try_from(REACTION_COLUMNS * cell_width + 4).unwrap_or(u16::MAX);
+    let height = u16::try_from(rows + 4).unwrap_or(u16::MAX);
+    let screen = f.area();
+    let area = Rect::new(
+        screen.x + screen.width.saturating_sub(width) / 2,
+        screen.y + screen.height.saturating_sub(height) / 2,
+        width.min(screen.width),
+        height.min(screen.height),
+    );
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" react to {} ", msg.from_display_name))
+        .title_bottom(" hjkl move  Enter react  Esc close ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(Color::Rgb(25, 25, 35)));
+
+    let mut lines = vec![Line::from("")];
+    for (row, keys) in choices.chunks(REACTION_COLUMNS).enumerate() {
+        let spans: Vec<Span<'_>> = keys
+            .iter()
+            .enumerate()
+            .map(|(col, key)| {
+                let emoji = tmz_core::reactions::emoji(key).unwrap_or_default();
+                let name = key.split_once('_').map_or(key.as_str(), |(_, name)| name);
+                let label = format!(" {emoji} {name}");
+                let pad = cell_width.saturating_sub(label.width());
+                let text = format!("{label}{}", " ".repeat(pad));
+                let style = if row * REACTION_COLUMNS + col == app.react_choice {
+                    Style::default().fg(Color::Black).bg(ACCENT).bold()
+                } else if row * REACTION_COLUMNS + col < app.recent_reactions.len() {
+                    Style::default().fg(Color::White)
+                } else {
+                    Style::default().fg(Color::Gray)
+                };
+                Span::styled(text, style)
+            })
+            .collect();
+        lines.push(Line::from(spans));
+    }
+
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+// ─── Quick switcher ──────────────────────────────────────────────────
+
+fn draw_switcher(f: &mut Frame<'_>, app: &App) {
+    let area = centered_rect(50, 60, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" switch to ")
+        .title_bottom(" type to filter  \u{2191}\u{2193} move  Enter open  Esc close ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(Color::Rgb(25, 25, 35)));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(inner);
+    let prompt = Line::from(vec![
+        Span::styled("> ", Style::default().fg(ACCENT)),
+        Span::raw(app.switcher_query.as_str()),
+        Span::styled("\u{258f}", Style::default().fg(ACCENT)),
+    ]);
+    f.render_widget(Paragraph::new(prompt), rows[0]);
+
+    // Keep the selection in view.
+    let height = usize::from(rows[1].height);
+    let skip = (app.switcher_selected + 1).saturating_sub(height);
+    let items: Vec<ListItem<'_>> = app
+        .switcher_matches
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .take(height)
+        .map(|(i, &index)| {
+            let conv = &app.conversations[index];
+            let name = if conv.display_name.is_empty() {
+                conv.member_names.as_str()
+            } else {
+                conv.display_name.as_str()
+            };
+            let style = if i == app.switcher_selected {
+                Style::default().fg(Color::Black).bg(ACCENT)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    "\u{258e} ",
+                    Style::default().fg(thread_type_color(&conv.thread_type)),
+                ),
+                Span::raw(name),
+            ]))
+            .style(style)
+        })
+        .collect();
+    f.render_widget(List::new(items), rows[1]);
+}
+
+// ─── File picker ─────────────────────────────────────────────────────
+
+fn draw_file_picker(f: &mut Frame<'_>, app: &App) {
+    let Some(picker) = &app.picker else {
+        return;
+    };
+    let area = centered_rect(60, 70, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" attach: {} ", picker.dir.display()))
+        .title_bottom(
+            " type to filter  Tab mark  Enter open/attach  Backspace up  ~ home  Esc close ",
+        )
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(Color::Rgb(25, 25, 35)));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(inner);
+    let mut prompt = vec![
+        Span::styled("> ", Style::default().fg(ACCENT)),
+        Span::raw(picker.query.as_str()),
+        Span::styled("\u{258f}", Style::default().fg(ACCENT)),
+    ];
+    if !picker.marked.is_empty() {
+        prompt.push(Span::styled(
+            format!("  {} marked", picker.marked.len()),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    f.render_widget(Paragraph::new(Line::from(prompt)), rows[0]);
+
+    if let Some(error) = &picker.error {
+        let para = Paragraph::new(error.as_str()).style(Style::default().fg(Color::Red));
+        f.render_widget(para, rows[1]);
+        return;
+    }
+
+    // Keep the selection in view.
+    let height = usize::from(rows[1].height);
+    let width = usize::from(rows[1].width);
+    let skip = (picker.selected + 1).saturating_sub(height);
+    let items: Vec<ListItem<'_>> = (skip..picker.matches.len())
+        .take(height)
+        .filter_map(|i| Some((i, picker.entry(i)?)))
+        .map(|(i, entry)| {
+            let mark = if picker.is_marked(&entry.path) {
+                "\u{2713} "
+            } else {
+                "  "
+            };
+            let (name, size) = if entry.is_dir {
+                (format!("{}/", entry.name), String::new())
+            } else {
+                (entry.name.clone(), format_size(entry.size))
+            };
+            let pad = width.saturating_sub(2 + name.chars().count() + size.chars().count());
+            let style = if i == picker.selected {
+                Style::default().fg(Color::Black).bg(ACCENT)
+            } else if entry.is_dir {
+                Style::default().fg(ACCENT)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(mark, Style::default().fg(Color::Green)),
+                Span::raw(name),
+                Span::raw(" ".repeat(pad)),
+                Span::styled(size, Style::default().fg(DIM)),
+            ]))
+            .style(style)
+        })
+        .collect();
+    f.render_widget(List::new(items), rows[1]);
+}
+
+// ─── Yank history ────────────────────────────────────────────────────
+
+fn draw_yank_picker(f: &mut Frame<'_>, app: &App) {
+    let area = centered_rect(60, 50, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" yanks ")
+        .title_bottom(" j/k move  Enter/p or 0-9 paste  Esc close ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(Color::Rgb(25, 25, 35)));
+
+    let width = usize::from(area.width.saturating_sub(8));
+    let items: Vec<ListItem<'_>> = app
+        .yanks
+        .iter()
+        .enumerate()
+        .map(|(i, yank)| {
+            let preview: String = yank
+                .text
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .chars()
+                .take(width.saturating_sub(yank.from.chars().count() + 6))
+                .collect();
+            let style = if i == app.yank_choice {
+                Style::default().fg(Color::Black).bg(ACCENT)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("\"{} ", register_name(i)), Style::default().fg(DIM)),
+                Span::styled(
+                    format!("{}: ", yank.from),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::raw(preview),
+            ]))
+            .style(style)
+        })
+        .collect();
+
+    f.render_widget(List::new(items).block(block), area);
+}
+
+/// Actions of the user script, run on the selected chat with Enter.
+fn draw_script_picker(f: &mut Frame<'_>, app: &App) {
+    let area = centered_rect(60, 50, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" script actions ")
+        .title_bottom(" j/k move  Enter run  Esc close ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .padding(Padding::horizontal(1))
+        .style(Style::default().bg(Color::Rgb(25, 25, 35)));
+
+    let width = app
+        .script_actions
+        .iter()
+        .map(|a| a.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<ListItem<'_>> = app
+        .script_actions
+        .iter()
+        .enumerate()
+        .map(|(i, action)| {
+            let style = if i == app.script_choice {
+                Style::default().fg(Color::Black).bg(ACCENT)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<width$}  ", action.name),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::raw(action.description.as_str()),
+            ]))
+            .style(style)
+        })
+        .collect();
+
+    f.render_widget(List::new(items).block(block), area);
+}
+
+fn token_style(kind: TokenKind) -> Style {
+    match kind {
+        TokenKind::Key => Style::default().fg(Color::Blue),
+        TokenKind::String => Style::default().fg(Color::Green),
+        TokenKind::Number => Style::default().fg(Color::Yellow),
+        TokenKind::Literal => Style::default().fg(Color::Magenta),
+        TokenKind::Punct => Style::default().fg(Color::White),
+        TokenKind::Note => Style::default().fg(DIM).add_modifier(Modifier::ITALIC),
+    }
+}
+
 fn section(name: &str) -> Line<'_> {
     Line::from(Span::styled(
         format!("  {name}"),
@@ -557,6 +1569,18 @@ fn extract_time(compose_time: &str) -> String {
         .to_string()
 }
 
+/// Time of a timeline message today, its day before that.
+fn timeline_time(compose_time: &str) -> String {
+    // "2026-02-18T09:38:22.933Z" -> "09:38" today, "Feb 18" otherwise
+    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    let date = compose_time.split('T').next().unwrap_or("");
+    if date == today {
+        return extract_time(compose_time);
+    }
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_or_else(|_| date.to_string(), |d| d.format("%b %d").to_string())
+}
+
 fn format_date(date_str: &str) -> String {
     // "2026-02-18" -> "February 18, 2026"
     chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d").map_or_else(
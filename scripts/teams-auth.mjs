@@ -8,23 +8,44 @@
 // cleared and the page reloaded to force a fresh SSO authentication.
 //
 // Usage:
-//   node teams-auth.mjs [--timeout 300] [--headless]
+//   node teams-auth.mjs [--timeout 300] [--headless] [--fresh] [--profile-dir <path>]
+//                       [--endpoints <json>]
+//
+// --endpoints gives the service hosts of the tenant's cloud as JSON
+// ({ "login", "teams", "skype", "chat", "graph", "presence" }); missing
+// hosts default to the public cloud.
+//
+// TMZ_PROXY (a proxy URL, optionally with credentials) and TMZ_PROXY_BYPASS
+// (comma-separated hosts) route the browser through an outbound proxy.
 //
 // Output (JSON to stdout):
-//   { "<localStorage-key>": "<localStorage-value>", ... }
+//   { "skype_token", "chat_token", "graph_token", "presence_token",
+//     "refresh_token" }
+//
+// The refresh token is the Teams web app's MSAL refresh token, which tmz
+// redeems directly for later refreshes.
 
 import { chromium } from "playwright";
 
-const TEAMS_URL = "https://teams.microsoft.com/v2";
+const TEAMS_CLIENT_ID = "5e3ce6c0-2b1f-4285-8d4b-75ee78787346";
 const DEFAULT_TIMEOUT_SECS = 300;
 const POLL_INTERVAL_MS = 2000;
 
-const REQUIRED_RESOURCES = [
-  "api.spaces.skype.com",
-  "chatsvcagg.teams.microsoft.com",
-  "graph.microsoft.com",
-  "presence.teams.microsoft.com",
-];
+const PUBLIC_ENDPOINTS = {
+  login: "login.microsoftonline.com",
+  teams: "teams.microsoft.com",
+  skype: "api.spaces.skype.com",
+  chat: "chatsvcagg.teams.microsoft.com",
+  graph: "graph.microsoft.com",
+  presence: "presence.teams.microsoft.com",
+};
+
+// Set from --endpoints in main().
+let endpoints = PUBLIC_ENDPOINTS;
+
+function requiredResources() {
+  return [endpoints.skype, endpoints.chat, endpoints.graph, endpoints.presence];
+}
 
 const REQUIRED_CAPTURED_FIELDS = [
   "skype_token",
@@ -40,6 +61,8 @@ function parseArgs() {
   let timeout = DEFAULT_TIMEOUT_SECS;
   let headless = false;
   let fresh = false;
+  let profileDir = null;
+  let hosts = {};
   for (let i = 0; i < args.length; i++) {
     if (args[i] === "--timeout" && args[i + 1]) {
       timeout = parseInt(args[i + 1], 10);
@@ -48,9 +71,27 @@ function parseArgs() {
       headless = true;
     } else if (args[i] === "--fresh") {
       fresh = true;
+    } else if (args[i] === "--profile-dir" && args[i + 1]) {
+      profileDir = args[i + 1];
+      i++;
+    } else if (args[i] === "--endpoints" && args[i + 1]) {
+      hosts = JSON.parse(args[i + 1]);
+      i++;
     }
   }
-  return { timeout, headless, fresh };
+  return { timeout, headless, fresh, profileDir, hosts };
+}
+
+/** Playwright proxy settings from TMZ_PROXY, or undefined for none. */
+function proxyFromEnv() {
+  const server = process.env.TMZ_PROXY;
+  if (!server) return undefined;
+  const url = new URL(server);
+  const proxy = { server: `${url.protocol}//${url.host}` };
+  if (url.username) proxy.username = decodeURIComponent(url.username);
+  if (url.password) proxy.password = decodeURIComponent(url.password);
+  if (process.env.TMZ_PROXY_BYPASS) proxy.bypass = process.env.TMZ_PROXY_BYPASS;
+  return proxy;
 }
 
 function log(msg) {
@@ -62,11 +103,7 @@ async function extractAccessTokens(page) {
     const tokens = {};
     for (let i = 0; i < localStorage.length; i++) {
       const key = localStorage.key(i);
-      if (
-        key &&
-        key.includes("accesstoken") &&
-        key.includes("login.windows.net")
-      ) {
+      if (key && key.includes("accesstoken")) {
         tokens[key] = localStorage.getItem(key);
       }
     }
@@ -74,9 +111,37 @@ async function extractAccessTokens(page) {
   });
 }
 
+/** MSAL refresh token of the Teams web app, or "" if none is cached. */
+async function extractRefreshToken(page) {
+  try {
+    return await page.evaluate((clientId) => {
+      const entries = [];
+      for (let i = 0; i < localStorage.length; i++) {
+        const key = localStorage.key(i);
+        if (key && key.includes("refreshtoken")) {
+          entries.push(key);
+        }
+      }
+      // Prefer the Teams client's own entry over family refresh tokens.
+      entries.sort((a, b) => Number(!a.includes(clientId)) - Number(!b.includes(clientId)));
+      for (const key of entries) {
+        try {
+          const secret = JSON.parse(localStorage.getItem(key)).secret;
+          if (typeof secret === "string" && secret) return secret;
+        } catch {
+          // not an MSAL credential entry
+        }
+      }
+      return "";
+    }, TEAMS_CLIENT_ID);
+  } catch {
+    return "";
+  }
+}
+
 function hasAllRequiredTokens(tokens) {
   const keys = Object.keys(tokens);
-  return REQUIRED_RESOURCES.every((resource) =>
+  return requiredResources().every((resource) =>
     keys.some((key) => key.toLowerCase().includes(resource.toLowerCase()))
   );
 }
@@ -127,27 +192,30 @@ function hasMinimumCapturedTokens(tokens) {
   });
 }
 
-function buildScriptTokenOutput(tokens) {
+function buildScriptTokenOutput(tokens, refreshToken) {
   return {
     skype_token: tokens.skype_token || "",
     chat_token: tokens.chat_token || "",
     graph_token: tokens.graph_token || "",
     presence_token: tokens.presence_token || "",
+    refresh_token: refreshToken || "",
   };
 }
 
 async function main() {
-  const { timeout, headless, fresh } = parseArgs();
+  const { timeout, headless, fresh, profileDir, hosts } = parseArgs();
+  endpoints = { ...PUBLIC_ENDPOINTS, ...hosts };
   const deadlineMs = Date.now() + timeout * 1000;
 
   log(headless ? "Headless token refresh..." : "Launching browser...");
 
   const userDataDir =
-    process.env.XDG_STATE_HOME
+    profileDir ||
+    (process.env.XDG_STATE_HOME
       ? `${process.env.XDG_STATE_HOME}/tmz/browser-profile`
       : process.env.HOME
         ? `${process.env.HOME}/.local/state/tmz/browser-profile`
-        : `/tmp/tmz-browser-profile`;
+        : `/tmp/tmz-browser-profile`);
 
   // --fresh: nuke the browser profile to force clean login
   if (fresh) {
@@ -164,6 +232,7 @@ async function main() {
     args: ["--disable-blink-features=AutomationControlled"],
     viewport: { width: 1280, height: 900 },
     locale: "en-US",
+    proxy: proxyFromEnv(),
   });
 
   const page = context.pages()[0] || (await context.newPage());
@@ -184,22 +253,22 @@ async function main() {
       const url = request.url().toLowerCase();
 
       // Teams authz exchange uses the Skype access token as bearer.
-      if (url.includes("teams.microsoft.com/api/authsvc/v1.0/authz") && !capturedTokens.skype_token) {
+      if (url.includes(`${endpoints.teams}/api/authsvc/v1.0/authz`) && !capturedTokens.skype_token) {
         capturedTokens.skype_token = token;
         return;
       }
 
-      if (url.includes("chatsvcagg.teams.microsoft.com") && !capturedTokens.chat_token) {
+      if (url.includes(endpoints.chat) && !capturedTokens.chat_token) {
         capturedTokens.chat_token = token;
         return;
       }
 
-      if (url.includes("graph.microsoft.com") && !capturedTokens.graph_token) {
+      if (url.includes(endpoints.graph) && !capturedTokens.graph_token) {
         capturedTokens.graph_token = token;
         return;
       }
 
-      if (url.includes("presence.teams.microsoft.com") && !capturedTokens.presence_token) {
+      if (url.includes(endpoints.presence) && !capturedTokens.presence_token) {
         capturedTokens.presence_token = token;
       }
     } catch {
@@ -208,7 +277,7 @@ async function main() {
   });
 
   try {
-    await page.goto(TEAMS_URL, { waitUntil: "domcontentloaded" });
+    await page.goto(`https://${endpoints.teams}/v2`, { waitUntil: "domcontentloaded" });
     log("Waiting for authentication...");
 
     let clearedCache = false;
@@ -228,7 +297,7 @@ async function main() {
       }
 
       const isOnLogin =
-        url.includes("login.microsoftonline.com") ||
+        url.includes(endpoints.login) ||
         url.includes("login.live.com");
 
       if (isOnLogin) {
@@ -253,7 +322,7 @@ async function main() {
 
       if (hasAllCapturedTokens(capturedTokens)) {
         log("All tokens extracted (network capture).");
-        process.stdout.write(JSON.stringify(buildScriptTokenOutput(capturedTokens)));
+        process.stdout.write(JSON.stringify(buildScriptTokenOutput(capturedTokens, await extractRefreshToken(page))));
         await context.close();
         process.exit(0);
       }
@@ -266,7 +335,7 @@ async function main() {
         // Give request-capture path a short grace period before minimal-output fallback.
         if (Date.now() - localStorageReadySince >= 6000 && hasMinimumCapturedTokens(capturedTokens)) {
           log("Using minimum captured tokens (skype_token) with optional token gaps.");
-          process.stdout.write(JSON.stringify(buildScriptTokenOutput(capturedTokens)));
+          process.stdout.write(JSON.stringify(buildScriptTokenOutput(capturedTokens, await extractRefreshToken(page))));
           await context.close();
           process.exit(0);
         }
@@ -296,13 +365,13 @@ async function main() {
 
       const count = Object.keys(tokens).length;
       if (count > 0) {
-        log(`${count} tokens found, waiting for all ${REQUIRED_RESOURCES.length}...`);
+        log(`${count} tokens found, waiting for all ${requiredResources().length}...`);
       }
     }
 
     if (hasMinimumCapturedTokens(capturedTokens)) {
       log("Timed out before full token set; using captured minimum token set.");
-      process.stdout.write(JSON.stringify(buildScriptTokenOutput(capturedTokens)));
+      process.stdout.write(JSON.stringify(buildScriptTokenOutput(capturedTokens, await extractRefreshToken(page))));
       await context.close();
       process.exit(0);
     }